@@ -0,0 +1,657 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Formatrix command-line tool
+//!
+//! The conversion core without the GUI, for scripts and CI. Every command
+//! that takes a document reads a file, or stdin when the path is `-` or
+//! left out, and writes its result to stdout unless told otherwise.
+//!
+//! Formats are named by extension (`md`, `adoc`, `dj`, `org`, `rst`, `typ`,
+//! `txt`) or by their usual aliases. A file's format comes from its
+//! extension, stdin's from its content, unless `--from` says otherwise.
+//!
+//! With `--json`, every command prints one JSON value to stdout instead of
+//! text, for other tools to wrap: the detected format, the conversion
+//! report, the lint diagnostics and so on. Errors are then printed as
+//! `{"error": "..."}` as well.
+//!
+//! `preview` shows a document styled for the terminal. Its output is
+//! coloured only when stdout is a terminal and `NO_COLOR` is unset, unless
+//! `--color` says otherwise.
+//!
+//! `convert` opens and saves files as the editor does: includes are
+//! expanded, regions that do not parse are kept raw, and the output is
+//! written atomically in the input's encoding and line endings. With
+//! `--interactive` it asks on the terminal when a file could be in several
+//! formats, when the target cannot hold everything in the document, and
+//! what to do with links to sibling documents. `--limits untrusted` makes
+//! `convert` and `batch` refuse input too large or too deeply nested, as
+//! for files from an unknown source.
+//!
+//! `lint` exits with status 1 when it finds errors, or any problem at all
+//! with `--deny-warnings`; `batch` exits with status 1 when a file fails.
+//! Any other failure exits with status 2.
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Args, Parser as _, Subcommand, ValueEnum};
+use formatrix_core::formats::handler_for;
+use formatrix_core::{
+    convert_dir, format_candidates, format_from_content, format_from_extension, open_file_as,
+    open_file_interactive, outline, parse_string, read_text, save_file_as_interactive, AnsiConfig,
+    AnsiHandler, BackupPolicy, BatchOptions, ConversionInteractor, ConversionReport, Diagnostic,
+    Document, FinalNewline, LintConfig, Linter, NonInteractive, OutlineEntry, ParseConfig,
+    ParseLimits, PromptInteractor, RenderConfig, Severity, SourceFormat,
+};
+use serde::Serialize;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(clap::Parser)]
+#[command(
+    name = "formatrix",
+    version,
+    about = "Convert, lint and inspect documents"
+)]
+struct Cli {
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a document to another format
+    Convert {
+        #[command(flatten)]
+        input: Input,
+        /// Target format; taken from the output file's extension if omitted
+        #[arg(long, short)]
+        to: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Leave out subtrees tagged with the document's exclude tags
+        /// (`noexport` unless it names others), as Org export does
+        #[arg(long)]
+        exclude_tags: bool,
+        /// Ask on the terminal instead of deciding alone
+        #[arg(long)]
+        interactive: bool,
+        /// Keep the file being replaced as `<name>.bak`
+        #[arg(long)]
+        backup: bool,
+        /// Limits on parsing the input
+        #[arg(long, value_enum, default_value_t = Limits::None)]
+        limits: Limits,
+    },
+    /// Print the format of a document
+    Detect {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Convert every matching document under a directory
+    Batch {
+        input_dir: PathBuf,
+        output_dir: PathBuf,
+        /// Target format
+        #[arg(long, short)]
+        to: String,
+        /// Files to convert, relative to the input directory
+        #[arg(long, default_value = "**/*")]
+        glob: String,
+        /// Files converted at once; 0 means one per CPU
+        #[arg(long, short, default_value_t = 0)]
+        jobs: usize,
+        /// Leave out subtrees tagged with the document's exclude tags
+        /// (`noexport` unless it names others), as Org export does
+        #[arg(long)]
+        exclude_tags: bool,
+        /// Limits on parsing each input
+        #[arg(long, value_enum, default_value_t = Limits::None)]
+        limits: Limits,
+    },
+    /// Check documents for problems
+    Lint {
+        /// Documents to check; stdin if none
+        files: Vec<PathBuf>,
+        /// Format of stdin, or of every file
+        #[arg(long, short)]
+        from: Option<String>,
+        /// TOML file of rule severities and limits
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Fail on warnings as well as errors
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Print the table of contents
+    Toc {
+        #[command(flatten)]
+        input: Input,
+        /// Deepest heading level shown
+        #[arg(long, default_value_t = 6)]
+        depth: u8,
+    },
+    /// Print word, character and block counts
+    Stats {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Show a document formatted for the terminal
+    Preview {
+        #[command(flatten)]
+        input: Input,
+        /// Columns to wrap to, 0 for none; `$COLUMNS`, or 80, if omitted
+        #[arg(long, short)]
+        width: Option<usize>,
+        /// When to style the output with colours and bold
+        #[arg(long, value_enum, default_value_t = Color::Auto)]
+        color: Color,
+    },
+}
+
+/// When `preview` styles its output
+#[derive(Clone, Copy, ValueEnum)]
+enum Color {
+    /// When printing to a terminal, without `--json`, and `NO_COLOR` is unset
+    Auto,
+    Always,
+    Never,
+}
+
+/// Limits on parsing, from `--limits`
+#[derive(Clone, Copy, ValueEnum)]
+enum Limits {
+    /// None, for documents you trust
+    None,
+    /// Caps on size, nesting and block count, for documents from an
+    /// unknown source
+    Untrusted,
+}
+
+impl Limits {
+    fn parse_limits(self) -> ParseLimits {
+        match self {
+            Limits::None => ParseLimits::default(),
+            Limits::Untrusted => ParseLimits::untrusted(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct Input {
+    /// Input file; stdin if `-` or omitted
+    input: Option<PathBuf>,
+    /// Format of the input, instead of detecting it
+    #[arg(long, short)]
+    from: Option<String>,
+}
+
+impl Input {
+    fn path(&self) -> Option<&Path> {
+        self.input.as_deref().filter(|path| *path != Path::new("-"))
+    }
+
+    fn read(&self) -> anyhow::Result<Source> {
+        read_input(self.path(), self.from.as_deref())
+    }
+}
+
+/// An input document's text and format
+struct Source {
+    text: String,
+    format: SourceFormat,
+    detected_by: DetectedBy,
+}
+
+/// Where the format of an input came from
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DetectedBy {
+    /// `--from`
+    Option,
+    Extension,
+    Content,
+}
+
+/// What `detect --json` prints
+#[derive(Serialize)]
+struct Detection {
+    format: &'static str,
+    detected_by: DetectedBy,
+    /// Formats the content could be, most likely first
+    candidates: Vec<&'static str>,
+}
+
+/// What `convert --json` prints
+#[derive(Serialize)]
+struct Conversion {
+    /// File written, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<PathBuf>,
+    /// The converted document, when not written to a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    report: ConversionReport,
+    /// Where the document uses what the target format loses
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// One finding in `lint --json`
+#[derive(Serialize)]
+struct LintEntry {
+    file: String,
+    #[serde(flatten)]
+    diagnostic: Diagnostic,
+}
+
+/// What `stats` prints
+#[derive(Serialize)]
+struct Stats {
+    format: &'static str,
+    words: usize,
+    characters: usize,
+    lines: usize,
+    blocks: usize,
+    headings: usize,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+    match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            if json {
+                println!("{}", serde_json::json!({ "error": format!("{:#}", e) }));
+            } else {
+                eprintln!("formatrix: {:#}", e);
+            }
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<ExitCode> {
+    let json = cli.json;
+    match cli.command {
+        Command::Convert {
+            input,
+            to,
+            output,
+            exclude_tags,
+            interactive,
+            backup,
+            limits,
+        } => {
+            let to = match (to, &output) {
+                (Some(name), _) => parse_format(&name)?,
+                (None, Some(path)) => format_from_extension(path)
+                    .ok_or_else(|| anyhow!("Cannot tell the format of {}", path.display()))?,
+                (None, None) => bail!("--to is required when writing to stdout"),
+            };
+            if interactive && input.path().is_none() {
+                bail!("--interactive needs an input file, since answers are read from stdin");
+            }
+            let mut interactor: Box<dyn ConversionInteractor> = if interactive {
+                Box::new(PromptInteractor::stdio())
+            } else {
+                Box::new(NonInteractive)
+            };
+            let parse_config = ParseConfig {
+                limits: limits.parse_limits(),
+                ..parse_config()
+            };
+            let mut config = RenderConfig {
+                exclude_tags,
+                backup: if backup {
+                    BackupPolicy::Single
+                } else {
+                    BackupPolicy::None
+                },
+                ..render_config()
+            };
+
+            let (doc, mut diagnostics) = match input.path() {
+                Some(path) => {
+                    let opened = match input.from.as_deref() {
+                        Some(name) => open_file_as(path, parse_format(name)?, &parse_config),
+                        None => open_file_interactive(path, &parse_config, interactor.as_mut()),
+                    }
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                    config.encoding = opened.file_info.encoding;
+                    config.line_ending = opened.file_info.line_ending;
+                    (opened.document, opened.diagnostics)
+                }
+                None => {
+                    let source = input.read()?;
+                    parse_string(&source.text, source.format, &parse_config)
+                        .context("Failed to parse stdin")?
+                }
+            };
+            let handler = handler_for(to);
+            let report = ConversionReport::new(&doc, handler.as_ref());
+            let rendered = match &output {
+                Some(path) => {
+                    save_file_as_interactive(&doc, path, to, &config, interactor.as_mut())
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    None
+                }
+                None => Some(
+                    handler
+                        .render(&doc, &config)
+                        .map_err(|e| anyhow!("{}", e))?,
+                ),
+            };
+
+            if json {
+                diagnostics.extend(report.diagnostics(&doc));
+                print_json(&Conversion {
+                    diagnostics,
+                    content: rendered,
+                    output,
+                    report,
+                })?;
+            } else {
+                if !report.is_lossless() {
+                    eprintln!(
+                        "formatrix: warning: {} cannot hold {}",
+                        to.label(),
+                        report.lost_features.join(", ")
+                    );
+                }
+                for diagnostic in &diagnostics {
+                    eprintln!("formatrix: {}", diagnostic);
+                }
+                if let Some(rendered) = rendered {
+                    write_stdout(&rendered)?;
+                }
+            }
+        }
+        Command::Detect { input } => {
+            let source = input.read()?;
+            if json {
+                print_json(&Detection {
+                    format: source.format.extension(),
+                    detected_by: source.detected_by,
+                    candidates: format_candidates(&source.text)
+                        .into_iter()
+                        .map(|format| format.extension())
+                        .collect(),
+                })?;
+            } else {
+                println!("{}", source.format.extension());
+            }
+        }
+        Command::Batch {
+            input_dir,
+            output_dir,
+            to,
+            glob,
+            jobs,
+            exclude_tags,
+            limits,
+        } => {
+            let options = BatchOptions {
+                parse: ParseConfig {
+                    limits: limits.parse_limits(),
+                    ..Default::default()
+                },
+                render: RenderConfig {
+                    exclude_tags,
+                    ..render_config()
+                },
+                jobs,
+            };
+            let report = convert_dir(&input_dir, &output_dir, &glob, parse_format(&to)?, &options)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                for (path, error) in report.failures() {
+                    eprintln!("{}: {}", path.display(), error);
+                }
+                eprintln!(
+                    "{} converted, {} skipped, {} failed",
+                    report.converted(),
+                    report.skipped(),
+                    report.failures().count()
+                );
+            }
+            if !report.is_success() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Lint {
+            files,
+            from,
+            config,
+            deny_warnings,
+        } => return lint(files, from, config, deny_warnings, json),
+        Command::Toc { input, depth } => {
+            let source = input.read()?;
+            let mut entries = outline(&parse(&source.text, source.format)?);
+            prune(&mut entries, depth);
+            if json {
+                print_json(&entries)?;
+            } else {
+                let mut out = String::new();
+                toc_list(&entries, 0, &mut out);
+                write_stdout(&out)?;
+            }
+        }
+        Command::Stats { input } => {
+            let source = input.read()?;
+            let doc = parse(&source.text, source.format)?;
+            let stats = Stats {
+                format: source.format.extension(),
+                words: doc.word_count(),
+                characters: doc.char_count(),
+                lines: source.text.lines().count(),
+                blocks: doc.content.len(),
+                headings: count_headings(&outline(&doc)),
+            };
+            if json {
+                print_json(&stats)?;
+            } else {
+                println!("format: {}", stats.format);
+                println!("words: {}", stats.words);
+                println!("characters: {}", stats.characters);
+                println!("lines: {}", stats.lines);
+                println!("blocks: {}", stats.blocks);
+                println!("headings: {}", stats.headings);
+            }
+        }
+        Command::Preview {
+            input,
+            width,
+            color,
+        } => {
+            let source = input.read()?;
+            let doc = parse(&source.text, source.format)?;
+            let config = AnsiConfig {
+                width: width.unwrap_or_else(terminal_width),
+                color: match color {
+                    Color::Auto => {
+                        !json
+                            && std::io::stdout().is_terminal()
+                            && std::env::var_os("NO_COLOR").is_none()
+                    }
+                    Color::Always => true,
+                    Color::Never => false,
+                },
+            };
+            let rendered = AnsiHandler::new().render(&doc, &config);
+            if json {
+                print_json(&serde_json::json!({ "content": rendered }))?;
+            } else {
+                write_stdout(&rendered)?;
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn lint(
+    files: Vec<PathBuf>,
+    from: Option<String>,
+    config: Option<PathBuf>,
+    deny_warnings: bool,
+    json: bool,
+) -> anyhow::Result<ExitCode> {
+    let config: LintConfig = match config {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            toml::from_str(&text).with_context(|| format!("Invalid {}", path.display()))?
+        }
+        None => LintConfig::default(),
+    };
+    let linter = Linter::with_default_rules();
+    let inputs: Vec<Option<&Path>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|path| Some(path.as_path())).collect()
+    };
+
+    let mut failed = false;
+    let mut entries = Vec::new();
+    for path in inputs {
+        let source = read_input(path, from.as_deref())?;
+        let doc = parse(&source.text, source.format)?;
+        let file = path.map_or("-".to_string(), |p| p.display().to_string());
+        for diagnostic in linter.lint(&doc, Some(&source.text), &config) {
+            failed |= diagnostic.severity == Severity::Error
+                || (deny_warnings && diagnostic.severity >= Severity::Warning);
+            if json {
+                entries.push(LintEntry {
+                    file: file.clone(),
+                    diagnostic,
+                });
+            } else {
+                println!("{}:{}", file, diagnostic);
+            }
+        }
+    }
+    if json {
+        print_json(&entries)?;
+    }
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Text and format of `path`, or of stdin
+fn read_input(path: Option<&Path>, from: Option<&str>) -> anyhow::Result<Source> {
+    let from = from.map(parse_format).transpose()?;
+    let (text, by_extension) = match path {
+        Some(path) => {
+            let (text, _) =
+                read_text(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            (text, format_from_extension(path))
+        }
+        None => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read stdin")?;
+            (text, None)
+        }
+    };
+    let (format, detected_by) = match (from, by_extension) {
+        (Some(format), _) => (format, DetectedBy::Option),
+        (None, Some(format)) => (format, DetectedBy::Extension),
+        (None, None) => (format_from_content(&text), DetectedBy::Content),
+    };
+    Ok(Source {
+        text,
+        format,
+        detected_by,
+    })
+}
+
+/// Parse with spans, so diagnostics can point into the source
+fn parse(text: &str, format: SourceFormat) -> anyhow::Result<Document> {
+    handler_for(format)
+        .parse(text, &parse_config())
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Spans are kept, so diagnostics can point into the source
+fn parse_config() -> ParseConfig {
+    ParseConfig {
+        preserve_spans: true,
+        ..Default::default()
+    }
+}
+
+/// Output ends in a newline, as text files and terminals expect
+fn render_config() -> RenderConfig {
+    RenderConfig {
+        final_newline: FinalNewline::Single,
+        ..Default::default()
+    }
+}
+
+/// A format from its extension, an alias or its name
+fn parse_format(name: &str) -> anyhow::Result<SourceFormat> {
+    let name = name.to_lowercase();
+    SourceFormat::ALL
+        .into_iter()
+        .find(|format| format.label().eq_ignore_ascii_case(&name))
+        .or_else(|| format_from_extension(&Path::new("document").with_extension(&name)))
+        .ok_or_else(|| anyhow!("Unknown format: {}", name))
+}
+
+/// Drop entries deeper than `depth`
+fn prune(entries: &mut Vec<OutlineEntry>, depth: u8) {
+    entries.retain(|entry| entry.level <= depth);
+    for entry in entries {
+        prune(&mut entry.children, depth);
+    }
+}
+
+/// `entries` as a nested Markdown list of links to their anchors
+fn toc_list(entries: &[OutlineEntry], indent: usize, out: &mut String) {
+    for entry in entries {
+        out.push_str(&format!(
+            "{}- [{}](#{})\n",
+            "  ".repeat(indent),
+            entry.title,
+            entry.id
+        ));
+        toc_list(&entry.children, indent + 1, out);
+    }
+}
+
+/// `$COLUMNS` as the shell sets it, or 80
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80)
+}
+
+fn count_headings(entries: &[OutlineEntry]) -> usize {
+    entries
+        .iter()
+        .map(|entry| 1 + count_headings(&entry.children))
+        .sum()
+}
+
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    let mut json = serde_json::to_string_pretty(value)?;
+    json.push('\n');
+    write_stdout(&json)
+}
+
+fn write_stdout(text: &str) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Tests of the formatrix binary through its command line
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_formatrix-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The binary may exit on bad arguments before reading its input
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_convert_stdin_to_stdout() {
+    let output = run(
+        &["convert", "--from", "md", "--to", "adoc"],
+        "# Title\n\nSome *text*.\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "= Title\n\nSome _text_.\n");
+}
+
+#[test]
+fn test_convert_file_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.md");
+    let output_path = dir.path().join("out.org");
+    std::fs::write(&input, "# Title\n").unwrap();
+
+    let output = run(
+        &[
+            "convert",
+            input.to_str().unwrap(),
+            "-o",
+            output_path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(output_path).unwrap(), "* Title\n");
+}
+
+#[test]
+fn test_convert_exclude_tags() {
+    let output = run(
+        &["convert", "--from", "org", "--to", "md", "--exclude-tags"],
+        "* Title\n** Notes :noexport:\nPrivate.\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "# Title\n");
+}
+
+#[test]
+fn test_convert_keeps_line_endings_and_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.md");
+    let output_path = dir.path().join("out.org");
+    std::fs::write(&input, "# Title\r\n\r\nText.\r\n").unwrap();
+    std::fs::write(&output_path, "old\n").unwrap();
+
+    let output = run(
+        &[
+            "convert",
+            input.to_str().unwrap(),
+            "-o",
+            output_path.to_str().unwrap(),
+            "--backup",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "* Title\r\n\r\nText.\r\n"
+    );
+    let backup = dir.path().join("out.org.bak");
+    assert_eq!(std::fs::read_to_string(backup).unwrap(), "old\n");
+}
+
+#[test]
+fn test_convert_interactive() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.md");
+    let output_path = dir.path().join("out.txt");
+    std::fs::write(&input, "| a | b |\n|---|---|\n| 1 | 2 |\n").unwrap();
+    let args = [
+        "convert",
+        input.to_str().unwrap(),
+        "-o",
+        output_path.to_str().unwrap(),
+        "--interactive",
+    ];
+
+    let output = run(&args, "n\n");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("will lose"));
+    assert!(stderr.contains("Operation cancelled"));
+    assert!(!output_path.exists());
+
+    let output = run(&args, "y\n");
+    assert!(output.status.success());
+    assert!(output_path.exists());
+
+    let output = run(&["convert", "--to", "md", "--interactive"], "text");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_limits() {
+    let deep = ">".repeat(50_000);
+    let output = run(
+        &[
+            "convert",
+            "--from",
+            "md",
+            "--to",
+            "txt",
+            "--limits",
+            "untrusted",
+        ],
+        &deep,
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nested more than"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let (input, output_dir) = (dir.path().join("in"), dir.path().join("out"));
+    std::fs::create_dir_all(&input).unwrap();
+    std::fs::write(input.join("deep.md"), &deep).unwrap();
+    let output = run(
+        &[
+            "batch",
+            input.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            "--to",
+            "djot",
+            "--limits",
+            "untrusted",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output_dir.join("deep.dj").exists());
+}
+
+#[test]
+fn test_detect() {
+    let output = run(&["detect"], "= Title\n\n== Section\n");
+    assert_eq!(stdout(&output), "adoc\n");
+
+    let output = run(&["detect", "--json"], "= Title\n\n== Section\n");
+    let detection: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(detection["format"], "adoc");
+    assert_eq!(detection["detected_by"], "content");
+    assert_eq!(detection["candidates"][0], "adoc");
+}
+
+#[test]
+fn test_convert_json_report() {
+    let output = run(
+        &["--json", "convert", "--from", "md", "--to", "txt"],
+        "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n",
+    );
+    assert!(output.status.success());
+    let conversion: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(conversion["report"]["from"], "markdown");
+    assert_eq!(conversion["report"]["to"], "plaintext");
+    assert!(conversion["content"].as_str().unwrap().contains("Title"));
+    assert!(conversion.get("output").is_none());
+    let lost = conversion["report"]["lost_features"].as_array().unwrap();
+    assert!(!lost.is_empty());
+    assert_eq!(conversion["diagnostics"][0]["code"], "lost-feature");
+}
+
+#[test]
+fn test_toc() {
+    let output = run(
+        &["toc", "--from", "md"],
+        "# Guide\n\n## Setup\n\n### Deep\n\n# Appendix\n",
+    );
+    assert_eq!(
+        stdout(&output),
+        "- [Guide](#guide)\n  - [Setup](#setup)\n    - [Deep](#deep)\n- [Appendix](#appendix)\n"
+    );
+
+    let output = run(
+        &["toc", "--from", "md", "--depth", "1"],
+        "# Guide\n\n## Setup\n",
+    );
+    assert_eq!(stdout(&output), "- [Guide](#guide)\n");
+}
+
+#[test]
+fn test_stats_json() {
+    let output = run(
+        &["stats", "--from", "md", "--json"],
+        "# Title\n\nOne two three.\n",
+    );
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["words"], 4);
+    assert_eq!(stats["headings"], 1);
+    assert_eq!(stats["format"], "md");
+}
+
+#[test]
+fn test_preview() {
+    let source = "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+    let output = run(&["preview", "--from", "md", "--width", "20"], source);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Title\n═════\n\n┌───┬───┐\n│ a │ b │\n├───┼───┤\n│ 1 │ 2 │\n└───┴───┘\n"
+    );
+
+    let output = run(
+        &["preview", "--from", "md", "--color", "always"],
+        "Some **bold**.\n",
+    );
+    assert_eq!(stdout(&output), "Some \x1b[1mbold\x1b[0m.\n");
+}
+
+#[test]
+fn test_lint_exit_status() {
+    let source = "# Title\n\n### Skipped\n";
+    let output = run(&["lint", "--from", "md"], source);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("heading-increment"));
+
+    let output = run(&["lint", "--from", "md", "--deny-warnings"], source);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run(&["lint", "--from", "md", "--json"], source);
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries[0]["file"], "-");
+    assert_eq!(entries[0]["code"], "heading-increment");
+    assert_eq!(entries[0]["severity"], "warning");
+}
+
+#[test]
+fn test_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, output_dir) = (dir.path().join("in"), dir.path().join("out"));
+    std::fs::create_dir_all(input.join("sub")).unwrap();
+    std::fs::write(input.join("a.md"), "# A\n").unwrap();
+    std::fs::write(input.join("sub/b.md"), "# B\n").unwrap();
+
+    let output = run(
+        &[
+            "batch",
+            input.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            "--to",
+            "djot",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert!(output_dir.join("a.dj").exists());
+    assert!(output_dir.join("sub/b.dj").exists());
+
+    std::fs::write(input.join("c.txt"), "Plain\n").unwrap();
+    let output = run(
+        &[
+            "batch",
+            input.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            "--to",
+            "djot",
+            "--json",
+        ],
+        "",
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let entries = report["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry["status"] == "converted"));
+}
+
+#[test]
+fn test_unknown_format() {
+    let output = run(&["convert", "--to", "docx"], "text");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown format: docx"));
+
+    let output = run(&["convert", "--to", "docx", "--json"], "text");
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(error["error"], "Unknown format: docx");
+}
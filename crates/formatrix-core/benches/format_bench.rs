@@ -123,7 +123,7 @@ fn bench_document_creation(c: &mut Criterion) {
                 source_format: SourceFormat::PlainText,
                 meta: DocumentMeta {
                     title: Some("Test Title".to_string()),
-                    authors: vec!["Author".to_string()],
+                    authors: vec!["Author".into()],
                     date: Some("2026-04-04".to_string()),
                     language: Some("en".to_string()),
                     ..Default::default()
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Writes `include/formatrix.h` from the FFI exports when the `ffi` feature
+//! is on, so C and Ada callers build against the declarations the library
+//! actually has
+//!
+//! The header is checked in; a build only rewrites it when `src/ffi.rs`
+//! changes what it exports.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    write_header();
+}
+
+#[cfg(feature = "ffi")]
+fn write_header() {
+    use std::path::Path;
+
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml is invalid");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/ffi.rs"))
+        .generate()
+        .expect("cannot generate the C header from src/ffi.rs")
+        .write_to_file(crate_dir.join("include/formatrix.h"));
+}
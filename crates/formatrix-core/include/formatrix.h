@@ -0,0 +1,866 @@
+/* SPDX-License-Identifier: MPL-2.0 */
+/* Formatrix core C API, for the Ada TUI and other C callers */
+
+#ifndef FORMATRIX_H
+#define FORMATRIX_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Version of the C API this library provides
+//
+// Raised whenever a function or type changes in a way existing callers
+// cannot cope with; additions alone leave it. A caller built against the
+// same value can use every declaration in its header.
+#define FORMATRIX_ABI_VERSION 3
+
+// Block kind for FFI
+typedef enum FfiBlockType {
+  FFI_BLOCK_TYPE_PARAGRAPH = 0,
+  FFI_BLOCK_TYPE_HEADING = 1,
+  FFI_BLOCK_TYPE_CODE_BLOCK = 2,
+  FFI_BLOCK_TYPE_BLOCK_QUOTE = 3,
+  FFI_BLOCK_TYPE_LIST = 4,
+  FFI_BLOCK_TYPE_DEFINITION_LIST = 5,
+  FFI_BLOCK_TYPE_TABLE = 6,
+  FFI_BLOCK_TYPE_THEMATIC_BREAK = 7,
+  FFI_BLOCK_TYPE_MATH_BLOCK = 8,
+  FFI_BLOCK_TYPE_CONTAINER = 9,
+  FFI_BLOCK_TYPE_FIGURE = 10,
+  FFI_BLOCK_TYPE_RAW = 11,
+  FFI_BLOCK_TYPE_FOOTNOTE_DEFINITION = 12,
+  FFI_BLOCK_TYPE_TABLE_OF_CONTENTS = 13,
+  FFI_BLOCK_TYPE_COMMENT = 14,
+  FFI_BLOCK_TYPE_DETAILS = 15,
+  // Not a block: the handle was null
+  FFI_BLOCK_TYPE_INVALID = -1,
+} FfiBlockType;
+
+// Step of a conversion started by `formatrix_convert_file_async`
+typedef enum FfiConvertStage {
+  // Reading and parsing the input
+  FFI_CONVERT_STAGE_OPENING = 0,
+  // Rendering and writing the output
+  FFI_CONVERT_STAGE_SAVING = 1,
+  // Done; the result says how it went
+  FFI_CONVERT_STAGE_FINISHED = 2,
+} FfiConvertStage;
+
+// Trailing newline policy for FFI
+typedef enum FfiFinalNewline {
+  // Leave the renderer's output untouched
+  FFI_FINAL_NEWLINE_KEEP = 0,
+  // Remove all trailing newlines
+  FFI_FINAL_NEWLINE_STRIP = 1,
+  // End with exactly one newline
+  FFI_FINAL_NEWLINE_SINGLE = 2,
+} FfiFinalNewline;
+
+// Document format for FFI
+typedef enum FfiFormat {
+  FFI_FORMAT_PLAIN_TEXT = 0,
+  FFI_FORMAT_MARKDOWN = 1,
+  FFI_FORMAT_ASCII_DOC = 2,
+  FFI_FORMAT_DJOT = 3,
+  FFI_FORMAT_ORG_MODE = 4,
+  FFI_FORMAT_RE_STRUCTURED_TEXT = 5,
+  FFI_FORMAT_TYPST = 6,
+} FfiFormat;
+
+// Markdown heading syntax for FFI
+typedef enum FfiHeadingStyle {
+  // The style profile's
+  FFI_HEADING_STYLE_PROFILE = 0,
+  // `#` marks before the heading
+  FFI_HEADING_STYLE_ATX = 1,
+  // A line of `=` or `-` under headings of level 1 and 2
+  FFI_HEADING_STYLE_SETEXT = 2,
+} FfiHeadingStyle;
+
+// Inline element kind for FFI
+typedef enum FfiInlineType {
+  FFI_INLINE_TYPE_TEXT = 0,
+  FFI_INLINE_TYPE_EMPHASIS = 1,
+  FFI_INLINE_TYPE_STRONG = 2,
+  FFI_INLINE_TYPE_STRIKETHROUGH = 3,
+  FFI_INLINE_TYPE_UNDERLINE = 4,
+  FFI_INLINE_TYPE_SUPERSCRIPT = 5,
+  FFI_INLINE_TYPE_SUBSCRIPT = 6,
+  FFI_INLINE_TYPE_SMALL_CAPS = 7,
+  FFI_INLINE_TYPE_CODE = 8,
+  FFI_INLINE_TYPE_MATH = 9,
+  FFI_INLINE_TYPE_LINK = 10,
+  FFI_INLINE_TYPE_IMAGE = 11,
+  FFI_INLINE_TYPE_FOOTNOTE_REF = 12,
+  FFI_INLINE_TYPE_CITATION = 13,
+  FFI_INLINE_TYPE_LINE_BREAK = 14,
+  FFI_INLINE_TYPE_SOFT_BREAK = 15,
+  FFI_INLINE_TYPE_NON_BREAKING_SPACE = 16,
+  FFI_INLINE_TYPE_SPAN = 17,
+  FFI_INLINE_TYPE_RAW_INLINE = 18,
+  FFI_INLINE_TYPE_QUOTED = 19,
+  FFI_INLINE_TYPE_KEYBOARD = 20,
+  FFI_INLINE_TYPE_HIGHLIGHT = 21,
+  FFI_INLINE_TYPE_COMMENT = 22,
+  FFI_INLINE_TYPE_EMOJI = 23,
+  FFI_INLINE_TYPE_BUTTON = 24,
+  FFI_INLINE_TYPE_MENU = 25,
+  // Not an inline element: the handle was null
+  FFI_INLINE_TYPE_INVALID = -1,
+} FfiInlineType;
+
+// Newline sequence for FFI
+typedef enum FfiLineEnding {
+  FFI_LINE_ENDING_LF = 0,
+  FFI_LINE_ENDING_CR_LF = 1,
+  FFI_LINE_ENDING_CR = 2,
+} FfiLineEnding;
+
+// Fidelity mode for FFI: what renderers do with raw content from another
+// format
+typedef enum FfiRawPolicy {
+  // Write it as it is
+  FFI_RAW_POLICY_KEEP = 0,
+  // Leave it out
+  FFI_RAW_POLICY_DROP = 1,
+  // Write it as a comment
+  FFI_RAW_POLICY_COMMENT = 2,
+  // Parse it with its own format's parser and write the result
+  FFI_RAW_POLICY_CONVERT = 3,
+} FfiRawPolicy;
+
+// Result code for FFI operations
+typedef enum FfiResult {
+  FFI_RESULT_SUCCESS = 0,
+  FFI_RESULT_INVALID_INPUT = 1,
+  FFI_RESULT_PARSE_ERROR = 2,
+  FFI_RESULT_RENDER_ERROR = 3,
+  FFI_RESULT_UNSUPPORTED_FORMAT = 4,
+  FFI_RESULT_NULL_POINTER = 5,
+  FFI_RESULT_UTF8_ERROR = 6,
+  // The library panicked; the last error has the panic message
+  FFI_RESULT_INTERNAL_ERROR = 7,
+  // Stopped through a cancel token
+  FFI_RESULT_CANCELLED = 8,
+} FfiResult;
+
+// Dialect of reStructuredText for FFI
+typedef enum FfiRstFlavor {
+  // Plain docutils
+  FFI_RST_FLAVOR_DOCUTILS = 0,
+  // Sphinx, with its roles and directives
+  FFI_RST_FLAVOR_SPHINX = 1,
+} FfiRstFlavor;
+
+// House style for FFI, which among other things picks the Markdown
+// flavor written
+typedef enum FfiStyleProfile {
+  // Each renderer's own style
+  FFI_STYLE_PROFILE_NONE = 0,
+  // GitHub Flavored Markdown
+  FFI_STYLE_PROFILE_GITHUB = 1,
+  // Pandoc's Markdown and reStructuredText
+  FFI_STYLE_PROFILE_PANDOC = 2,
+  // Sphinx, with MyST for Markdown
+  FFI_STYLE_PROFILE_SPHINX = 3,
+  // Kramdown, as used by Jekyll
+  FFI_STYLE_PROFILE_KRAMDOWN = 4,
+} FfiStyleProfile;
+
+// Borrowed handle to a block inside a document
+//
+// Valid until the document it came from is edited or freed; never freed
+// itself.
+typedef struct BlockHandle BlockHandle;
+
+// Shared flag for stopping background work, from
+// `formatrix_cancel_token_new`
+typedef struct CancelToken CancelToken;
+
+// Opaque handle to a document
+//
+// Not locked: any thread may use it, and several at once while none edits
+// it. Give another thread its own copy with `formatrix_clone_document`.
+typedef struct DocumentHandle DocumentHandle;
+
+// Borrowed handle to an inline element inside a document
+//
+// Valid until the document it came from is edited or freed; never freed
+// itself.
+typedef struct InlineHandle InlineHandle;
+
+#if defined(FORMATRIX_WATCH)
+// Opaque handle to a file watch, from `formatrix_watch_file`
+typedef struct WatchHandle WatchHandle;
+#endif
+
+// Parse options for `formatrix_parse_with_options`
+//
+// Fill them in with `formatrix_parse_options_init` and change the fields
+// you need. The library reads only the fields that fit in `size`, and
+// takes its defaults for the rest, so a caller built against an older
+// header keeps working; fields from a newer header are ignored.
+typedef struct FfiParseOptions {
+  // `sizeof(FfiParseOptions)` in the caller's header
+  size_t size;
+  // Record where each block came from in the source
+  bool preserve_spans;
+  // Keep raw source for lossless round-trip
+  bool preserve_raw_source;
+  // Fail on malformed input, instead of keeping the regions that do not
+  // parse as raw blocks
+  bool strict;
+  // Expand tabs to the next multiple of this many columns (0 = keep tabs)
+  size_t tab_width;
+  // Dialect reStructuredText is read as
+  enum FfiRstFlavor rst_flavor;
+  // Largest input accepted, in bytes (0 = no limit)
+  //
+  // Input past any of the limits fails with `ParseError`. With a limit
+  // set, malformed input that is not strict is recovered as by
+  // `formatrix_open_file`, without the spans a parse by region adds.
+  size_t max_input_bytes;
+  // Deepest nesting accepted, top-level blocks being at depth 1 (0 = no
+  // limit); set it for untrusted input, which may nest deep enough to
+  // overflow the stack otherwise
+  size_t max_depth;
+  // Most blocks accepted, nested ones included (0 = no limit)
+  size_t max_blocks;
+  // Longest the parse may take, in milliseconds (0 = no limit)
+  //
+  // Only the Djot reader checks the time as it goes; other formats fail
+  // with `ParseError` when it is set, since their parse cannot be
+  // stopped part way.
+  uint64_t timeout_ms;
+} FfiParseOptions;
+
+// Render options for `formatrix_render_with_options`
+//
+// Fill them in with `formatrix_render_options_init` and change the fields
+// you need. As with `FfiParseOptions`, the library reads only the fields
+// that fit in `size`.
+typedef struct FfiRenderOptions {
+  // `sizeof(FfiRenderOptions)` in the caller's header
+  size_t size;
+  // Target line width for wrapping (0 = no wrap)
+  size_t line_width;
+  // Use hard line breaks
+  bool hard_breaks;
+  // Newline sequence used in the output
+  enum FfiLineEnding line_ending;
+  // Trailing newline policy
+  enum FfiFinalNewline final_newline;
+  // House style to follow
+  enum FfiStyleProfile profile;
+  // Markdown heading syntax
+  enum FfiHeadingStyle heading_style;
+  // Fidelity mode for raw content from another format
+  enum FfiRawPolicy raw_policy;
+} FfiRenderOptions;
+
+// Receives one chunk of a streamed render
+//
+// `data` holds `length` bytes of UTF-8, valid only during the call.
+// Return false to stop the render.
+typedef bool (*FfiChunkCallback)(const uint8_t *data, size_t length, void *userdata);
+
+// One heading of a document outline, from `formatrix_get_outline`
+typedef struct FfiOutlineEntry {
+  // Heading level, 1-6
+  uint8_t level;
+  // Plain text of the heading, owned by the outline
+  uint8_t *title;
+  size_t title_length;
+  // Whether the parser recorded where the heading starts
+  bool has_offset;
+  // Byte offset of the heading in the source, when `has_offset` is set
+  size_t byte_offset;
+} FfiOutlineEntry;
+
+// Told how a background conversion is going
+//
+// Runs on the conversion's own thread. `result` is `Success` until the
+// `Finished` stage; `message` is null on success and otherwise describes
+// the failure, valid only during the call.
+typedef void (*FfiProgressCallback)(enum FfiConvertStage stage,
+                                    enum FfiResult result,
+                                    const char *message,
+                                    void *userdata);
+
+#if defined(FORMATRIX_WATCH)
+// Told that a watched file changed on disk
+//
+// Runs on the library's watcher thread, not the caller's. `path` is the
+// path given to `formatrix_watch_file`, valid only during the call.
+typedef void (*FfiWatchCallback)(const char *path, void *userdata);
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Message of the last error on this thread, or null if there has been none
+//
+// Every function that returns a failing `FfiResult` sets it first; calls
+// that succeed leave it alone.
+//
+// # Safety
+// Returns a string owned by the library, valid until the next failing call
+// on this thread or `formatrix_clear_last_error`; do not free it
+const char *formatrix_last_error_message(void);
+
+// Line and column of the last error on this thread, if it has them
+//
+// Returns false, and leaves the outputs alone, when the last error has no
+// position in the source.
+//
+// # Safety
+// - `out_line` and `out_column` must be valid pointers
+bool formatrix_last_error_span(uint32_t *out_line, uint32_t *out_column);
+
+// Forget the last error on this thread
+void formatrix_clear_last_error(void);
+
+// Parse content into a document handle
+//
+// # Safety
+// - `content` must be a valid null-terminated UTF-8 string
+// - `out_handle` must be a valid pointer to store the result
+enum FfiResult formatrix_parse(const char *content,
+                               enum FfiFormat format,
+                               struct DocumentHandle **out_handle);
+
+// Fill in parse options with the library's defaults
+//
+// Writes only the first `size` bytes of `options`, and sets its `size`.
+//
+// # Safety
+// - `options` must point to `size` writable bytes
+enum FfiResult formatrix_parse_options_init(struct FfiParseOptions *options, size_t size);
+
+// Parse content into a document handle, with `options`
+//
+// # Safety
+// - `content` must be a valid null-terminated UTF-8 string
+// - `options` must be a valid pointer to parse options, `size` bytes long
+// - `out_handle` must be a valid pointer to store the result
+enum FfiResult formatrix_parse_with_options(const char *content,
+                                            enum FfiFormat format,
+                                            const struct FfiParseOptions *options,
+                                            struct DocumentHandle **out_handle);
+
+// Render a document to a string in the specified format
+//
+// # Safety
+// - `handle` must be a valid document handle from `formatrix_parse`
+// - `out_content` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_render(const struct DocumentHandle *handle,
+                                enum FfiFormat format,
+                                uint8_t **out_content,
+                                size_t *out_length);
+
+// Fill in render options with the library's defaults
+//
+// Writes only the first `size` bytes of `options`, and sets its `size`.
+//
+// # Safety
+// - `options` must point to `size` writable bytes
+enum FfiResult formatrix_render_options_init(struct FfiRenderOptions *options, size_t size);
+
+// Render a document to a string in the specified format, with `options`
+//
+// # Safety
+// - `handle` must be a valid document handle from `formatrix_parse`
+// - `options` must be a valid pointer to render options, `size` bytes long
+// - `out_content` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_render_with_options(const struct DocumentHandle *handle,
+                                             enum FfiFormat format,
+                                             const struct FfiRenderOptions *options,
+                                             uint8_t **out_content,
+                                             size_t *out_length);
+
+// Render a document with `options`, in the house style of the profile
+// called `profile`
+//
+// The profiles are "github", "pandoc", "sphinx" and "kramdown"; an
+// unknown name fails with `InvalidInput`. The name overrides
+// `options.profile`.
+//
+// # Safety
+// - `handle` must be a valid document handle from `formatrix_parse`
+// - `options` must be a valid pointer to render options, `size` bytes long
+// - `profile` must be a valid null-terminated string
+// - `out_content` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_render_with_profile(const struct DocumentHandle *handle,
+                                             enum FfiFormat format,
+                                             const struct FfiRenderOptions *options,
+                                             const char *profile,
+                                             uint8_t **out_content,
+                                             size_t *out_length);
+
+// Render a document as styled terminal text, wrapped to `width` columns
+//
+// A `width` of 0 turns wrapping off; with `color` false no escape codes are
+// written.
+//
+// # Safety
+// - `handle` must be a valid document handle from `formatrix_parse`
+// - `out_content` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_render_ansi(const struct DocumentHandle *handle,
+                                     size_t width,
+                                     bool color,
+                                     uint8_t **out_content,
+                                     size_t *out_length);
+
+// Render a document in the specified format one top-level block at a time
+//
+// `callback` gets the front matter, if any, then each block with the blank
+// line before it, so a caller can show the start of a large document
+// before the rest is rendered. The chunks join up to what `formatrix_render`
+// gives, except that each block is rendered without its neighbours.
+// Stopping early from the callback still returns `Success`.
+//
+// # Safety
+// - `handle` must be a valid document handle from `formatrix_parse`
+// - `callback` must be safe to call with `userdata`
+enum FfiResult formatrix_render_stream(const struct DocumentHandle *handle,
+                                       enum FfiFormat format,
+                                       FfiChunkCallback callback,
+                                       void *userdata);
+
+// Parse UTF-16 content into a document handle
+//
+// For hosts whose strings are UTF-16, such as Windows frontends. `length`
+// counts code units, not bytes; no terminator is needed. Parses with the
+// options of `formatrix_parse`, and fails with `InvalidInput` on unpaired
+// surrogates.
+//
+// # Safety
+// - `content` must point to `length` UTF-16 code units (or be null with a
+//   `length` of 0)
+// - `out_handle` must be a valid pointer to store the result
+enum FfiResult formatrix_parse_utf16(const uint16_t *content,
+                                     size_t length,
+                                     enum FfiFormat format,
+                                     struct DocumentHandle **out_handle);
+
+// Render a document to UTF-16 in the specified format
+//
+// `out_length` gets the number of code units. Free the result with
+// `formatrix_free_buffer_utf16`.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `out_content` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_render_utf16(const struct DocumentHandle *handle,
+                                      enum FfiFormat format,
+                                      uint16_t **out_content,
+                                      size_t *out_length);
+
+// Get the title of a document
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `out_title` must be a valid pointer
+// - `out_length` must be a valid pointer
+enum FfiResult formatrix_get_title(const struct DocumentHandle *handle,
+                                   uint8_t **out_title,
+                                   size_t *out_length);
+
+// Get the number of blocks in a document
+//
+// # Safety
+// - `handle` must be a valid document handle
+size_t formatrix_block_count(const struct DocumentHandle *handle);
+
+// Get the source format of a document
+//
+// # Safety
+// - `handle` must be a valid document handle
+enum FfiFormat formatrix_get_format(const struct DocumentHandle *handle);
+
+// Get the headings of a document, in document order
+//
+// The outline tree is flattened depth first, so each entry's parent is
+// the nearest entry before it with a lower level. Headings inside block
+// quotes, lists and tables are left out. Byte offsets are known for
+// headings set off by blank lines in documents parsed with
+// `preserve_spans` and without `strict`. Free the entries with
+// `formatrix_free_outline`.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `out_entries` must be a valid pointer to store the result
+// - `out_count` must be a valid pointer to store the number of entries
+enum FfiResult formatrix_get_outline(const struct DocumentHandle *handle,
+                                     struct FfiOutlineEntry **out_entries,
+                                     size_t *out_count);
+
+// Free an outline from `formatrix_get_outline`, with its titles
+//
+// # Safety
+// - `entries` must be an outline from this library, with the `count` that
+//   came with it, or null
+void formatrix_free_outline(struct FfiOutlineEntry *entries, size_t count);
+
+// Serialize a document to JSON
+//
+// The JSON is the serde form of the document AST, the same one the GUI
+// and the database use, so tooling in any language can read it without a
+// per-node API.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `out_json` must be a valid pointer to store the result
+// - `out_length` must be a valid pointer to store the length
+enum FfiResult formatrix_document_to_json(const struct DocumentHandle *handle,
+                                          uint8_t **out_json,
+                                          size_t *out_length);
+
+// Copy a document into a new handle, for another thread to use
+//
+// The copy shares nothing with the original, so each can be edited or
+// freed without regard to the other.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `out_handle` must be a valid pointer to store the result
+enum FfiResult formatrix_clone_document(const struct DocumentHandle *handle,
+                                        struct DocumentHandle **out_handle);
+
+// Build a document handle from the JSON `formatrix_document_to_json` gives
+//
+// On failure the last error has the line and column of the problem.
+//
+// # Safety
+// - `json` must be a valid null-terminated UTF-8 string
+// - `out_handle` must be a valid pointer to store the result
+enum FfiResult formatrix_document_from_json(const char *json, struct DocumentHandle **out_handle);
+
+// Get a top-level block of a document, or null if `index` is out of range
+//
+// # Safety
+// - `handle` must be a valid document handle
+const struct BlockHandle *formatrix_get_block(const struct DocumentHandle *handle, size_t index);
+
+// Get the kind of a block, or `Invalid` for a null handle
+//
+// # Safety
+// - `block` must be a valid block handle
+enum FfiBlockType formatrix_get_block_type(const struct BlockHandle *block);
+
+// Get the text of a block
+//
+// Code, math and raw blocks give their content as written; other blocks
+// give the plain text of their own inline content (a heading's title, a
+// table's caption), one line per run, and nothing of their child blocks.
+//
+// # Safety
+// - `block` must be a valid block handle
+// - `out_text` must be a valid pointer
+// - `out_length` must be a valid pointer
+enum FfiResult formatrix_get_block_text(const struct BlockHandle *block,
+                                        uint8_t **out_text,
+                                        size_t *out_length);
+
+// Get the level of a heading block, or 0 for any other block
+//
+// # Safety
+// - `block` must be a valid block handle
+uint8_t formatrix_get_heading_level(const struct BlockHandle *block);
+
+// Get the number of blocks nested directly inside a block
+//
+// List items, definitions and table cells are flattened, in document
+// order.
+//
+// # Safety
+// - `block` must be a valid block handle
+size_t formatrix_get_block_child_count(const struct BlockHandle *block);
+
+// Get a block nested directly inside a block, or null if `index` is out of
+// range
+//
+// # Safety
+// - `block` must be a valid block handle
+const struct BlockHandle *formatrix_get_block_child(const struct BlockHandle *block, size_t index);
+
+// Get the number of inline elements held directly by a block
+//
+// # Safety
+// - `block` must be a valid block handle
+size_t formatrix_get_inline_count(const struct BlockHandle *block);
+
+// Get an inline element held directly by a block, or null if `index` is
+// out of range
+//
+// # Safety
+// - `block` must be a valid block handle
+const struct InlineHandle *formatrix_get_inline(const struct BlockHandle *block, size_t index);
+
+// Get the kind of an inline element, or `Invalid` for a null handle
+//
+// # Safety
+// - `inline` must be a valid inline handle
+enum FfiInlineType formatrix_get_inline_type(const struct InlineHandle *inline_);
+
+// Get the plain text of an inline element and everything nested in it
+//
+// # Safety
+// - `inline` must be a valid inline handle
+// - `out_text` must be a valid pointer
+// - `out_length` must be a valid pointer
+enum FfiResult formatrix_get_inline_text(const struct InlineHandle *inline_,
+                                         uint8_t **out_text,
+                                         size_t *out_length);
+
+// Get the target of a link or the source of an image
+//
+// Fails with `InvalidInput` for any other inline element.
+//
+// # Safety
+// - `inline` must be a valid inline handle
+// - `out_url` must be a valid pointer
+// - `out_length` must be a valid pointer
+enum FfiResult formatrix_get_inline_url(const struct InlineHandle *inline_,
+                                        uint8_t **out_url,
+                                        size_t *out_length);
+
+// Get the number of inline elements nested directly inside an inline
+// element
+//
+// # Safety
+// - `inline` must be a valid inline handle
+size_t formatrix_get_inline_child_count(const struct InlineHandle *inline_);
+
+// Get an inline element nested directly inside an inline element, or null
+// if `index` is out of range
+//
+// # Safety
+// - `inline` must be a valid inline handle
+const struct InlineHandle *formatrix_get_inline_child(const struct InlineHandle *inline_,
+                                                      size_t index);
+
+// Insert a paragraph of `text` so that it becomes top-level block `index`
+//
+// `index` may equal the block count, to append. Lines of `text` are joined
+// by soft breaks. Block and inline handles taken before the edit are no
+// longer valid.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `text` must be a valid null-terminated UTF-8 string
+enum FfiResult formatrix_insert_paragraph(struct DocumentHandle *handle,
+                                          size_t index,
+                                          const char *text);
+
+// Delete top-level block `index`, with everything nested in it
+//
+// Block and inline handles taken before the edit are no longer valid.
+//
+// # Safety
+// - `handle` must be a valid document handle
+enum FfiResult formatrix_delete_block(struct DocumentHandle *handle, size_t index);
+
+// Set the title of a document; an empty `title` removes it
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `title` must be a valid null-terminated UTF-8 string
+enum FfiResult formatrix_set_title(struct DocumentHandle *handle, const char *title);
+
+// Replace the text of top-level block `index`
+//
+// Paragraphs and headings get `text` as plain inline content, dropping any
+// formatting, with lines joined by soft breaks; code, math and raw blocks
+// get it as written. Fails with `InvalidInput` for any other block. Block
+// and inline handles taken before the edit are no longer valid.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `text` must be a valid null-terminated UTF-8 string
+enum FfiResult formatrix_set_block_text(struct DocumentHandle *handle,
+                                        size_t index,
+                                        const char *text);
+
+// Free a document handle
+//
+// # Safety
+// - `handle` must be a valid document handle or null
+void formatrix_free_document(struct DocumentHandle *handle);
+
+// Free a buffer the library handed out
+//
+// # Safety
+// - `data` must be a buffer from this library, with the `length` that came
+//   with it, or null
+void formatrix_free_buffer(uint8_t *data, size_t length);
+
+// Get the version of the C API, to compare with `FORMATRIX_ABI_VERSION`
+// from the header a caller was built against
+uint32_t formatrix_abi_version(void);
+
+// Whether the library was built with the Cargo feature `name`
+//
+// False for names the library does not know, so a caller can ask about
+// features newer than the library.
+//
+// # Safety
+// - `name` must be a valid null-terminated string or null
+bool formatrix_has_feature(const char *name);
+
+// Free a UTF-16 buffer the library handed out
+//
+// # Safety
+// - `data` must be a buffer from `formatrix_render_utf16`, with the
+//   `length` that came with it, or null
+void formatrix_free_buffer_utf16(uint16_t *data, size_t length);
+
+// Get library version
+//
+// # Safety
+// Returns a static string, do not free
+const char *formatrix_version(void);
+
+// Detect format from content
+//
+// # Safety
+// - `content` must be a valid null-terminated UTF-8 string
+enum FfiFormat formatrix_detect_format(const char *content);
+
+// Convert content from one format to another
+//
+// # Safety
+// - All pointers must be valid
+enum FfiResult formatrix_convert(const char *content,
+                                 enum FfiFormat from_format,
+                                 enum FfiFormat to_format,
+                                 uint8_t **out_content,
+                                 size_t *out_length);
+
+// Open a file and parse it into a document handle
+//
+// # Safety
+// - `path` must be a valid null-terminated UTF-8 file path
+// - `out_handle` must be a valid pointer to store the result
+// - `out_format` must be a valid pointer to store the detected format
+enum FfiResult formatrix_open_file(const char *path,
+                                   struct DocumentHandle **out_handle,
+                                   enum FfiFormat *out_format);
+
+// Save a document to a file
+//
+// Format is determined by the file extension. If no extension matches,
+// uses the document's source format.
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `path` must be a valid null-terminated UTF-8 file path
+enum FfiResult formatrix_save_file(const struct DocumentHandle *handle, const char *path);
+
+// Save a document to a file in a specific format
+//
+// # Safety
+// - `handle` must be a valid document handle
+// - `path` must be a valid null-terminated UTF-8 file path
+enum FfiResult formatrix_save_file_as(const struct DocumentHandle *handle,
+                                      const char *path,
+                                      enum FfiFormat format);
+
+// Create a cancel token, for `formatrix_convert_file_async`
+//
+// One token may be shared by several conversions, to stop a whole batch.
+struct CancelToken *formatrix_cancel_token_new(void);
+
+// Ask the work using a cancel token to stop
+//
+// Work stops at its next checkpoint rather than at once.
+//
+// # Safety
+// - `token` must be a valid cancel token or null
+void formatrix_cancel(const struct CancelToken *token);
+
+// Free a cancel token
+//
+// Conversions already using it keep their own reference, so it may be
+// freed before they finish; they can then no longer be cancelled.
+//
+// # Safety
+// - `token` must be a valid cancel token or null
+void formatrix_cancel_token_free(struct CancelToken *token);
+
+// Convert the file at `input` to `to_format`, writing it to `output`, on a
+// background thread
+//
+// Returns as soon as the conversion has started. `progress` is called as
+// each stage starts and once more with the `Finished` stage and the
+// outcome, which is `Cancelled` if `cancel` was triggered first; the
+// token is checked between stages. `cancel` may be null.
+//
+// # Safety
+// - `input` and `output` must be valid null-terminated UTF-8 file paths
+// - `progress` must be safe to call with `userdata` from another thread
+// - `cancel` must be a valid cancel token or null
+enum FfiResult formatrix_convert_file_async(const char *input,
+                                            const char *output,
+                                            enum FfiFormat to_format,
+                                            FfiProgressCallback progress,
+                                            void *userdata,
+                                            const struct CancelToken *cancel);
+
+#if defined(FORMATRIX_WATCH)
+// Call `callback` whenever the file at `path` changes on disk
+//
+// Changes are debounced, so a save reports once, and the watch follows a
+// save that renames a new file over the old one; removing the file is
+// also reported. Stop watching with `formatrix_unwatch`. Only built with
+// the `watch` feature.
+//
+// # Safety
+// - `path` must be a valid null-terminated UTF-8 path to an existing file
+// - `callback` must be safe to call with `userdata` from another thread
+// - `out_watch` must be a valid pointer to store the result
+enum FfiResult formatrix_watch_file(const char *path,
+                                    FfiWatchCallback callback,
+                                    void *userdata,
+                                    struct WatchHandle **out_watch);
+#endif
+
+#if defined(FORMATRIX_WATCH)
+// Stop a file watch and free its handle
+//
+// Waits for a callback that is already running, so once this returns the
+// callback is not called again; do not call it from the callback.
+//
+// # Safety
+// - `watch` must be a valid watch handle or null
+void formatrix_unwatch(struct WatchHandle *watch);
+#endif
+
+// Detect format from file path (by extension)
+//
+// # Safety
+// - `path` must be a valid null-terminated UTF-8 file path
+enum FfiFormat formatrix_detect_file_format(const char *path);
+
+// Get the file extension for a format
+//
+// # Safety
+// Returns a static string, do not free
+const char *formatrix_format_extension(enum FfiFormat format);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FORMATRIX_H */
@@ -0,0 +1,736 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Styled terminal output, for previews in the TUI and the CLI
+//!
+//! Like HTML, this is an output format with no
+//! [`SourceFormat`](crate::ast::SourceFormat) of its own. Text is wrapped to
+//! the terminal width, tables are drawn with box-drawing characters, and
+//! headings, emphasis, code and links are styled with SGR escape codes. With
+//! [`AnsiConfig::color`] off no escape codes are written, for pipes and
+//! terminals that do not take them; the layout is the same either way.
+
+use crate::ast::{
+    AdmonitionType, Block, ColumnAlignment, ColumnSpec, Document, Inline, ListKind, QuoteType,
+    TableCell, TableRow,
+};
+use crate::traits::EmojiStyle;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const ITALIC: &str = "3";
+const UNDERLINE: &str = "4";
+const REVERSE: &str = "7";
+const STRIKETHROUGH: &str = "9";
+const CODE: &str = "36";
+const MATH: &str = "35";
+const LINK: &str = "4;34";
+const HIGHLIGHT: &str = "30;43";
+
+/// Columns a thematic break spans when wrapping is off
+const UNWRAPPED_RULE_WIDTH: usize = 80;
+
+/// Table columns are not narrowed below this to fit the terminal
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Options for [`AnsiHandler::render`]
+#[derive(Debug, Clone)]
+pub struct AnsiConfig {
+    /// Columns to wrap to; 0 turns wrapping off
+    pub width: usize,
+    /// Style text with escape codes
+    pub color: bool,
+}
+
+impl Default for AnsiConfig {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            color: true,
+        }
+    }
+}
+
+/// Terminal renderer
+pub struct AnsiHandler;
+
+impl AnsiHandler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Render `doc` as lines of terminal text, each ending in a newline
+    pub fn render(&self, doc: &Document, config: &AnsiConfig) -> String {
+        let width = match config.width {
+            0 => usize::MAX,
+            width => width,
+        };
+        let terminal = Terminal {
+            color: config.color,
+        };
+        let mut output = String::new();
+        for line in terminal.blocks(&doc.content, width) {
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output
+    }
+}
+
+impl Default for AnsiHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inline content flattened for wrapping
+enum Piece {
+    /// Text and the SGR parameters it is drawn with
+    Text(String, String),
+    /// A hard line break
+    Break,
+}
+
+struct Terminal {
+    color: bool,
+}
+
+impl Terminal {
+    fn paint(&self, text: &str, style: &str) -> String {
+        paint(self.color, text, style)
+    }
+
+    /// `blocks` as lines no wider than `width`, with a blank line between
+    /// blocks
+    fn blocks(&self, blocks: &[Block], width: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        for block in blocks {
+            let block_lines = self.block(block, width);
+            if block_lines.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(block_lines);
+        }
+        lines
+    }
+
+    fn block(&self, block: &Block, width: usize) -> Vec<String> {
+        match block {
+            Block::Paragraph { content, .. } => self.wrap(&self.pieces(content, ""), width),
+
+            Block::Heading { level, content, .. } => {
+                let style = heading_style(*level);
+                let mut lines = self.wrap(&self.pieces(content, style), width);
+                let rule = match level {
+                    1 => "═",
+                    2 => "─",
+                    _ => return lines,
+                };
+                let rule_width = lines.iter().map(|line| visible_width(line)).max();
+                if let Some(rule_width) = rule_width {
+                    lines.push(self.paint(&rule.repeat(rule_width), style));
+                }
+                lines
+            }
+
+            Block::CodeBlock { content, .. } => content
+                .lines()
+                .map(|line| format!("{}{}", self.paint("│ ", DIM), self.paint(line, CODE)))
+                .collect(),
+
+            Block::BlockQuote {
+                content,
+                attribution,
+                admonition,
+                ..
+            } => {
+                let inner_width = width.saturating_sub(2).max(1);
+                let mut lines = Vec::new();
+                let bar_style = match admonition {
+                    Some(kind) => {
+                        let (label, style) = admonition_label(*kind);
+                        lines.push(self.paint(label, &nested(style, BOLD)));
+                        style
+                    }
+                    None => DIM,
+                };
+                lines.extend(self.blocks(content, inner_width));
+                if let Some(attribution) = attribution {
+                    let mut pieces = vec![Piece::Text("— ".to_string(), String::new())];
+                    pieces.extend(self.pieces(attribution, ""));
+                    lines.push(String::new());
+                    lines.extend(self.wrap(&pieces, inner_width));
+                }
+                self.indent(lines, "│ ", "│ ", bar_style)
+            }
+
+            Block::List {
+                kind, items, start, ..
+            } => {
+                let start = start.unwrap_or(1) as usize;
+                let markers: Vec<String> = items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| match (item.checked, kind) {
+                        (Some(true), _) => "[x]".to_string(),
+                        (Some(false), _) => "[ ]".to_string(),
+                        (None, ListKind::Ordered) => format!("{}.", start + i),
+                        (None, ListKind::Bullet | ListKind::Task) => "•".to_string(),
+                    })
+                    .collect();
+                let marker_width = markers.iter().map(|m| m.width()).max().unwrap_or(0) + 1;
+                let inner_width = width.saturating_sub(marker_width).max(1);
+                let mut lines = Vec::new();
+                for (item, marker) in items.iter().zip(markers) {
+                    let first = format!("{:<width$}", marker, width = marker_width);
+                    let item_lines = self.blocks(&item.content, inner_width);
+                    let item_lines = if item_lines.is_empty() {
+                        vec![String::new()]
+                    } else {
+                        item_lines
+                    };
+                    lines.extend(self.indent(item_lines, &first, &" ".repeat(marker_width), ""));
+                }
+                lines
+            }
+
+            Block::DefinitionList { items, .. } => {
+                let inner_width = width.saturating_sub(4).max(1);
+                let mut lines = Vec::new();
+                for item in items {
+                    if !lines.is_empty() {
+                        lines.push(String::new());
+                    }
+                    lines.extend(self.wrap(&self.pieces(&item.term, BOLD), width));
+                    for definition in &item.definitions {
+                        let definition = self.blocks(definition, inner_width);
+                        lines.extend(self.indent(definition, "    ", "    ", ""));
+                    }
+                }
+                lines
+            }
+
+            Block::Table {
+                caption,
+                columns,
+                header,
+                body,
+                footer,
+                ..
+            } => {
+                let mut lines = match caption {
+                    Some(caption) => self.wrap(&self.pieces(caption, ITALIC), width),
+                    None => Vec::new(),
+                };
+                lines.extend(self.table(columns, header.as_ref(), body, footer.as_ref(), width));
+                lines
+            }
+
+            Block::ThematicBreak { .. } => {
+                let rule_width = match width {
+                    usize::MAX => UNWRAPPED_RULE_WIDTH,
+                    width => width,
+                };
+                vec![self.paint(&"─".repeat(rule_width), DIM)]
+            }
+
+            Block::MathBlock { content, .. } => content
+                .lines()
+                .map(|line| format!("  {}", self.paint(line, MATH)))
+                .collect(),
+
+            Block::Container { content, .. } => self.blocks(content, width),
+
+            // A terminal cannot fold, so the content is always shown
+            Block::Details {
+                summary, content, ..
+            } => {
+                let mut pieces = vec![Piece::Text("▾ ".to_string(), DIM.to_string())];
+                if let Some(summary) = summary {
+                    pieces.extend(self.pieces(summary, BOLD));
+                }
+                let mut lines = self.wrap(&pieces, width);
+                let inner = self.blocks(content, width.saturating_sub(2).max(1));
+                lines.extend(self.indent(inner, "  ", "  ", ""));
+                lines
+            }
+
+            Block::Figure {
+                content, caption, ..
+            } => {
+                let mut lines = self.blocks(content, width);
+                if let Some(caption) = caption {
+                    lines.extend(self.wrap(&self.pieces(caption, ITALIC), width));
+                }
+                lines
+            }
+
+            Block::Raw { content, .. } => content.lines().map(str::to_string).collect(),
+
+            Block::FootnoteDefinition { label, content, .. } => {
+                let marker = format!("[{}] ", label);
+                let marker_width = marker.width();
+                let lines = self.blocks(content, width.saturating_sub(marker_width).max(1));
+                let mut lines = self.indent(lines, "", &" ".repeat(marker_width), "");
+                match lines.first_mut() {
+                    Some(first) => first.insert_str(0, &self.paint(&marker, DIM)),
+                    None => lines.push(self.paint(marker.trim_end(), DIM)),
+                }
+                lines
+            }
+
+            // Generated by the viewer, if at all
+            Block::TableOfContents { .. } => Vec::new(),
+
+            Block::Comment { .. } => Vec::new(),
+        }
+    }
+
+    /// Prefix the first line with `first` and the rest with `rest`; blank
+    /// lines keep no trailing space
+    fn indent(&self, lines: Vec<String>, first: &str, rest: &str, style: &str) -> Vec<String> {
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let prefix = if i == 0 { first } else { rest };
+                if line.is_empty() {
+                    self.paint(prefix.trim_end(), style)
+                } else {
+                    format!("{}{}", self.paint(prefix, style), line)
+                }
+            })
+            .collect()
+    }
+
+    /// A box-drawn table, its columns narrowed and their cells wrapped when
+    /// it is wider than `width`
+    fn table(
+        &self,
+        columns: &[ColumnSpec],
+        header: Option<&TableRow>,
+        body: &[TableRow],
+        footer: Option<&TableRow>,
+        width: usize,
+    ) -> Vec<String> {
+        let rows: Vec<(&TableRow, &str)> = header
+            .map(|row| (row, BOLD))
+            .into_iter()
+            .chain(body.iter().map(|row| (row, "")))
+            .chain(footer.map(|row| (row, "")))
+            .collect();
+        let count = rows
+            .iter()
+            .map(|(row, _)| row.cells.len())
+            .max()
+            .unwrap_or(0);
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let cells: Vec<Vec<Vec<Piece>>> = rows
+            .iter()
+            .map(|(row, style)| {
+                row.cells
+                    .iter()
+                    .map(|cell| self.cell_pieces(cell, style))
+                    .collect()
+            })
+            .collect();
+        let mut widths = vec![1; count];
+        for row in &cells {
+            for (i, pieces) in row.iter().enumerate() {
+                for line in self.wrap(pieces, usize::MAX) {
+                    widths[i] = widths[i].max(visible_width(&line));
+                }
+            }
+        }
+        if width != usize::MAX {
+            let available = width.saturating_sub(3 * count + 1);
+            while widths.iter().sum::<usize>() > available {
+                let widest = (0..count).max_by_key(|&i| widths[i]).unwrap_or(0);
+                if widths[widest] <= MIN_COLUMN_WIDTH {
+                    break;
+                }
+                widths[widest] -= 1;
+            }
+        }
+
+        let border = |left: &str, middle: &str, right: &str| {
+            let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+            self.paint(&format!("{}{}{}", left, segments.join(middle), right), DIM)
+        };
+        let mut lines = vec![border("┌", "┬", "┐")];
+        for (index, ((row, _), pieces)) in rows.iter().zip(&cells).enumerate() {
+            let ends_header = index == 0 && header.is_some() && rows.len() > 1;
+            let starts_footer = index + 1 == rows.len() - 1 && footer.is_some();
+            let wrapped: Vec<Vec<String>> = (0..count)
+                .map(|i| match pieces.get(i) {
+                    Some(pieces) => self.wrap(pieces, widths[i]),
+                    None => Vec::new(),
+                })
+                .collect();
+            let height = wrapped.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            for line_index in 0..height {
+                let mut line = self.paint("│", DIM);
+                for (i, cell_lines) in wrapped.iter().enumerate() {
+                    let text = cell_lines.get(line_index).map_or("", String::as_str);
+                    let alignment = row
+                        .cells
+                        .get(i)
+                        .and_then(|cell| cell.alignment)
+                        .or(columns.get(i).map(|column| column.alignment));
+                    line.push(' ');
+                    line.push_str(&pad(text, widths[i], alignment));
+                    line.push(' ');
+                    line.push_str(&self.paint("│", DIM));
+                }
+                lines.push(line);
+            }
+            if ends_header || starts_footer {
+                lines.push(border("├", "┼", "┤"));
+            }
+        }
+        lines.push(border("└", "┴", "┘"));
+        lines
+    }
+
+    /// A cell's content as one run of inline pieces, its blocks on
+    /// separate lines
+    fn cell_pieces(&self, cell: &TableCell, style: &str) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        for block in &cell.content {
+            if !pieces.is_empty() {
+                pieces.push(Piece::Break);
+            }
+            match block {
+                Block::Paragraph { content, .. } => pieces.extend(self.pieces(content, style)),
+                block => {
+                    let plain = Terminal { color: false };
+                    for (i, line) in plain.block(block, usize::MAX).into_iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Break);
+                        }
+                        pieces.push(Piece::Text(line, style.to_string()));
+                    }
+                }
+            }
+        }
+        pieces
+    }
+
+    fn pieces(&self, inlines: &[Inline], style: &str) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        for inline in inlines {
+            self.inline(&mut pieces, inline, style);
+        }
+        pieces
+    }
+
+    fn inline(&self, pieces: &mut Vec<Piece>, inline: &Inline, style: &str) {
+        let text = |text: &str, code: &str| Piece::Text(text.to_string(), nested(style, code));
+        match inline {
+            Inline::Text { content } | Inline::RawInline { content, .. } => {
+                pieces.push(text(content, ""))
+            }
+            Inline::Emphasis { content } => {
+                pieces.extend(self.pieces(content, &nested(style, ITALIC)))
+            }
+            Inline::Strong { content } => pieces.extend(self.pieces(content, &nested(style, BOLD))),
+            Inline::Strikethrough { content } => {
+                pieces.extend(self.pieces(content, &nested(style, STRIKETHROUGH)))
+            }
+            Inline::Underline { content } => {
+                pieces.extend(self.pieces(content, &nested(style, UNDERLINE)))
+            }
+            Inline::Highlight { content } => {
+                pieces.extend(self.pieces(content, &nested(style, HIGHLIGHT)))
+            }
+            Inline::Superscript { content }
+            | Inline::Subscript { content }
+            | Inline::SmallCaps { content }
+            | Inline::Span { content, .. } => pieces.extend(self.pieces(content, style)),
+            Inline::Code { content, .. } => pieces.push(text(content, CODE)),
+            Inline::Math { content, .. } => pieces.push(text(content, MATH)),
+            Inline::Keyboard { content } => pieces.push(text(content, REVERSE)),
+            Inline::Button { content } => pieces.push(text(&format!("[{}]", content), BOLD)),
+            Inline::Menu { items } => pieces.push(text(&items.join(" ▸ "), BOLD)),
+            Inline::Link { url, content, .. } => {
+                let label = self.pieces(content, &nested(style, LINK));
+                let shows_url = label
+                    .iter()
+                    .any(|piece| matches!(piece, Piece::Text(label, _) if label == url));
+                pieces.extend(label);
+                if !shows_url && !url.starts_with('#') {
+                    pieces.push(text(&format!(" <{}>", url), DIM));
+                }
+            }
+            Inline::Image { url, alt, .. } => {
+                let alt = if alt.is_empty() { url } else { alt };
+                pieces.push(text(&format!("[{}]", alt), DIM));
+            }
+            Inline::FootnoteRef { label } => pieces.push(text(&format!("[{}]", label), DIM)),
+            Inline::Citation {
+                keys,
+                prefix,
+                suffix,
+            } => {
+                pieces.push(text("[", DIM));
+                if let Some(prefix) = prefix {
+                    pieces.extend(self.pieces(prefix, &nested(style, DIM)));
+                    pieces.push(text(" ", DIM));
+                }
+                let keys: Vec<String> = keys.iter().map(|key| format!("@{}", key)).collect();
+                pieces.push(text(&keys.join("; "), DIM));
+                if let Some(suffix) = suffix {
+                    pieces.push(text(" ", DIM));
+                    pieces.extend(self.pieces(suffix, &nested(style, DIM)));
+                }
+                pieces.push(text("]", DIM));
+            }
+            Inline::Quoted {
+                quote_type,
+                content,
+            } => {
+                let (open, close) = match quote_type {
+                    QuoteType::Single => ("‘", "’"),
+                    QuoteType::Double => ("“", "”"),
+                };
+                pieces.push(text(open, ""));
+                pieces.extend(self.pieces(content, style));
+                pieces.push(text(close, ""));
+            }
+            Inline::LineBreak => pieces.push(Piece::Break),
+            Inline::SoftBreak => pieces.push(text(" ", "")),
+            Inline::NonBreakingSpace => pieces.push(text("\u{a0}", "")),
+            Inline::Comment { .. } => {}
+            Inline::Emoji { shortcode } => {
+                let emoji = crate::emoji::render_emoji(shortcode, &EmojiStyle::Unicode);
+                self.inline(pieces, &emoji, style)
+            }
+        }
+    }
+
+    /// Lay `pieces` out in lines no wider than `width`, breaking at spaces,
+    /// and within words only when a word is wider than a line
+    fn wrap(&self, pieces: &[Piece], width: usize) -> Vec<String> {
+        let mut layout = Layout {
+            color: self.color,
+            width,
+            lines: Vec::new(),
+            line: String::new(),
+            line_width: 0,
+            word: Vec::new(),
+            word_width: 0,
+        };
+        for piece in pieces {
+            match piece {
+                Piece::Text(text, style) => {
+                    for (i, part) in text.split([' ', '\t', '\n']).enumerate() {
+                        if i > 0 {
+                            layout.end_word();
+                        }
+                        if !part.is_empty() {
+                            layout.word.push((part, style));
+                            layout.word_width += part.width();
+                        }
+                    }
+                }
+                Piece::Break => {
+                    layout.end_word();
+                    layout.end_line();
+                }
+            }
+        }
+        layout.end_word();
+        if !layout.line.is_empty() {
+            layout.end_line();
+        }
+        layout.lines
+    }
+}
+
+/// Line-filling state for [`Terminal::wrap`]
+struct Layout<'a> {
+    color: bool,
+    width: usize,
+    lines: Vec<String>,
+    line: String,
+    line_width: usize,
+    /// The word being read, as text and style
+    word: Vec<(&'a str, &'a str)>,
+    word_width: usize,
+}
+
+impl Layout<'_> {
+    fn end_word(&mut self) {
+        let word = std::mem::take(&mut self.word);
+        let word_width = std::mem::take(&mut self.word_width);
+        if word.is_empty() {
+            return;
+        }
+        if self.line_width > 0 {
+            if self.line_width + 1 + word_width <= self.width {
+                self.line.push(' ');
+                self.line_width += 1;
+            } else {
+                self.end_line();
+            }
+        }
+        for (text, style) in word {
+            let mut chunk = String::new();
+            for ch in text.chars() {
+                let ch_width = ch.width().unwrap_or(0);
+                if self.line_width > 0 && self.line_width + ch_width > self.width {
+                    self.line.push_str(&paint(self.color, &chunk, style));
+                    chunk.clear();
+                    self.end_line();
+                }
+                chunk.push(ch);
+                self.line_width += ch_width;
+            }
+            self.line.push_str(&paint(self.color, &chunk, style));
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.lines.push(std::mem::take(&mut self.line));
+        self.line_width = 0;
+    }
+}
+
+fn paint(color: bool, text: &str, style: &str) -> String {
+    if color && !style.is_empty() && !text.is_empty() {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// SGR parameters for `code` inside text already drawn with `style`
+fn nested(style: &str, code: &str) -> String {
+    match (style, code) {
+        ("", code) => code.to_string(),
+        (style, "") => style.to_string(),
+        (style, code) => format!("{};{}", style, code),
+    }
+}
+
+fn heading_style(level: u8) -> &'static str {
+    match level {
+        1 => "1;35",
+        2 => "1;34",
+        3 => "1;36",
+        _ => BOLD,
+    }
+}
+
+fn admonition_label(kind: AdmonitionType) -> (&'static str, &'static str) {
+    match kind {
+        AdmonitionType::Note => ("Note", "34"),
+        AdmonitionType::Tip => ("Tip", "32"),
+        AdmonitionType::Important => ("Important", "35"),
+        AdmonitionType::Warning => ("Warning", "33"),
+        AdmonitionType::Caution => ("Caution", "33"),
+        AdmonitionType::Danger => ("Danger", "31"),
+        AdmonitionType::Custom => ("Note", "34"),
+    }
+}
+
+/// Columns `line` takes up on screen, escape codes aside
+fn visible_width(line: &str) -> usize {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            chars.by_ref().take_while(|&c| c != 'm').for_each(drop);
+        } else {
+            text.push(ch);
+        }
+    }
+    text.width()
+}
+
+/// `text` padded to `width` columns
+fn pad(text: &str, width: usize, alignment: Option<ColumnAlignment>) -> String {
+    let gap = width.saturating_sub(visible_width(text));
+    let (left, right) = match alignment {
+        Some(ColumnAlignment::Right) => (gap, 0),
+        Some(ColumnAlignment::Center) => (gap / 2, gap - gap / 2),
+        Some(ColumnAlignment::Left | ColumnAlignment::Default) | None => (0, gap),
+    };
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser};
+
+    fn render(markdown: &str, width: usize, color: bool) -> String {
+        let doc = MarkdownHandler::new()
+            .parse(markdown, &ParseConfig::default())
+            .unwrap();
+        AnsiHandler::new().render(&doc, &AnsiConfig { width, color })
+    }
+
+    #[test]
+    fn test_render_plain() {
+        assert_eq!(
+            render(
+                "# Title\n\nSome *emphasis* and [a link](https://example.com) to \
+                 wrap at some columns.\n\n- one\n- two\n",
+                24,
+                false
+            ),
+            "Title\n═════\n\nSome emphasis and a link\n<https://example.com> to\nwrap at some columns.\n\n\
+             • one\n• two\n"
+        );
+    }
+
+    #[test]
+    fn test_render_styled() {
+        let output = render("Some **bold** and `code`.\n", 0, true);
+        assert_eq!(output, "Some \x1b[1mbold\x1b[0m and \x1b[36mcode\x1b[0m.\n");
+        assert!(!render("Some **bold**.\n", 0, false).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let output = render(
+            "| Name | Count |\n|:-----|------:|\n| apples | 3 |\n| pears | 12 |\n",
+            80,
+            false,
+        );
+        assert_eq!(
+            output,
+            "┌────────┬───────┐\n\
+             │ Name   │ Count │\n\
+             ├────────┼───────┤\n\
+             │ apples │     3 │\n\
+             │ pears  │    12 │\n\
+             └────────┴───────┘\n"
+        );
+
+        let narrow = render(
+            "| Column | Description |\n|---|---|\n| a | a long description that wraps |\n",
+            24,
+            true,
+        );
+        for line in narrow.lines() {
+            assert_eq!(visible_width(line), 24, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_wrap_long_word() {
+        assert_eq!(
+            render("abcdefghij klm\n", 4, false),
+            "abcd\nefgh\nij\nklm\n"
+        );
+    }
+}
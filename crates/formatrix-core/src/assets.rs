@@ -0,0 +1,628 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Images and attachments referenced by a document
+//!
+//! A converted document is usually written somewhere other than its source,
+//! which breaks every relative image path. [`collect_assets`] copies the local
+//! files a document references into one assets directory next to the output
+//! and points the document at the copies.
+//!
+//! [`import_file`] does the same for one file, such as an image dropped onto
+//! the editor, and [`attach_image`] writes pasted image data there.
+//!
+//! Images can also travel inside the document: [`embed_images`] replaces
+//! local image paths with base64 `data:` URIs for self-contained output, and
+//! [`extract_images`] writes such URIs back out to files.
+
+use crate::ast::Document;
+use crate::file_ops::{format_from_extension, FileError, FileResult};
+use crate::toolkit::links::{classify, visit_image_urls_mut, visit_link_urls_mut, LinkTarget};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How referenced files are gathered during conversion
+#[derive(Debug, Clone)]
+pub struct AssetOptions {
+    /// Directory the files are copied into, relative to the output file
+    pub dir: PathBuf,
+    /// Also collect link targets that are not documents (PDFs, archives, ...)
+    pub attachments: bool,
+}
+
+impl Default for AssetOptions {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("assets"),
+            attachments: true,
+        }
+    }
+}
+
+/// A file copied into the assets directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectedAsset {
+    /// Where the file was found
+    pub source: PathBuf,
+    /// Where it was copied to
+    pub dest: PathBuf,
+    /// URL the document now uses for it
+    pub url: String,
+}
+
+/// Copy the local files `doc` references into the assets directory and
+/// rewrite their URLs
+///
+/// Relative URLs are resolved against `source_dir`, the directory of the file
+/// `doc` was read from; the assets directory is created under `output_dir`.
+/// Targets that do not exist are left as they are for the link checker to
+/// report. Files with the same name from different directories get numbered
+/// copies, and a file referenced twice is copied once.
+pub fn collect_assets(
+    doc: &mut Document,
+    source_dir: &Path,
+    output_dir: &Path,
+    options: &AssetOptions,
+) -> FileResult<Vec<CollectedAsset>> {
+    let mut collector = Collector {
+        source_dir,
+        dest_dir: output_dir.join(&options.dir),
+        url_dir: options.dir.to_string_lossy().replace('\\', "/"),
+        by_source: HashMap::new(),
+        collected: Vec::new(),
+        error: None,
+    };
+
+    let mut finished = visit_image_urls_mut(doc, &mut |url| collector.rewrite(url, true));
+    if finished.is_some() && options.attachments {
+        finished = visit_link_urls_mut(doc, &mut |url| collector.rewrite(url, false));
+    }
+    match collector.error {
+        Some(e) if finished.is_none() => Err(e.into()),
+        _ => Ok(collector.collected),
+    }
+}
+
+struct Collector<'a> {
+    source_dir: &'a Path,
+    dest_dir: PathBuf,
+    url_dir: String,
+    /// Canonical source path to index in `collected`
+    by_source: HashMap<PathBuf, usize>,
+    collected: Vec<CollectedAsset>,
+    error: Option<std::io::Error>,
+}
+
+impl Collector<'_> {
+    /// Point `url` at a copy of its target; returns `None` after an I/O error
+    fn rewrite(&mut self, url: &mut String, is_image: bool) -> Option<()> {
+        let LinkTarget::LocalFile { path, fragment } = classify(url) else {
+            return Some(());
+        };
+        if path.is_empty() {
+            return Some(());
+        }
+        let source = self.source_dir.join(&path);
+        // Links to other documents are the converter's business, not assets
+        if !source.is_file() || (!is_image && format_from_extension(&source).is_some()) {
+            return Some(());
+        }
+
+        let index = match self.copy(&source) {
+            Ok(index) => index,
+            Err(e) => {
+                self.error = Some(e);
+                return None;
+            }
+        };
+        *url = self.collected[index].url.clone();
+        if let Some(fragment) = fragment {
+            url.push('#');
+            url.push_str(&fragment);
+        }
+        Some(())
+    }
+
+    fn copy(&mut self, source: &Path) -> std::io::Result<usize> {
+        let canonical = source.canonicalize()?;
+        if let Some(&index) = self.by_source.get(&canonical) {
+            return Ok(index);
+        }
+
+        let name = self.unused_name(&canonical);
+        let dest = self.dest_dir.join(&name);
+        fs::create_dir_all(&self.dest_dir)?;
+        fs::copy(&canonical, &dest)?;
+
+        let url = if self.url_dir.is_empty() {
+            name
+        } else {
+            format!("{}/{}", self.url_dir.trim_end_matches('/'), name)
+        };
+        self.collected.push(CollectedAsset {
+            source: canonical.clone(),
+            dest,
+            url,
+        });
+        self.by_source.insert(canonical, self.collected.len() - 1);
+        Ok(self.collected.len() - 1)
+    }
+
+    /// File name for `source` not yet used by another collected file
+    fn unused_name(&self, source: &Path) -> String {
+        let (stem, ext) = name_parts(source);
+        let taken = |name: &str| {
+            self.collected
+                .iter()
+                .any(|asset| asset.dest.file_name().is_some_and(|n| n == name))
+        };
+        let mut name = format!("{}{}", stem, ext);
+        let mut n = 2;
+        while taken(&name) {
+            name = format!("{}-{}{}", stem, n, ext);
+            n += 1;
+        }
+        name
+    }
+}
+
+/// Stem and extension, with its dot, to number copies of `path` by
+fn name_parts(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "asset".to_string());
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, ext)
+}
+
+/// Copy one file into the assets directory of the document in `doc_dir`
+///
+/// The returned URL is relative to `doc_dir`. A file already in the assets
+/// directory, or matching the contents of the file there with its name, is
+/// not copied again; a different file with the same name gets a numbered
+/// copy.
+pub fn import_file(
+    source: &Path,
+    doc_dir: &Path,
+    options: &AssetOptions,
+) -> FileResult<CollectedAsset> {
+    let source = source.canonicalize()?;
+    let dest_dir = doc_dir.join(&options.dir);
+    let url_dir = options.dir.to_string_lossy().replace('\\', "/");
+    fs::create_dir_all(&dest_dir)?;
+
+    let (stem, ext) = name_parts(&source);
+    let mut name = format!("{}{}", stem, ext);
+    let mut n = 2;
+    let dest = loop {
+        let dest = dest_dir.join(&name);
+        if !dest.exists() {
+            fs::copy(&source, &dest)?;
+            break dest;
+        }
+        if dest.canonicalize()? == source || fs::read(&dest)? == fs::read(&source)? {
+            break dest;
+        }
+        name = format!("{}-{}{}", stem, n, ext);
+        n += 1;
+    };
+
+    let url = if url_dir.is_empty() {
+        name
+    } else {
+        format!("{}/{}", url_dir.trim_end_matches('/'), name)
+    };
+    Ok(CollectedAsset { source, dest, url })
+}
+
+/// Where pasted images are saved and what they are called
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachOptions {
+    /// Directory the images are written to, relative to the document
+    pub dir: PathBuf,
+    /// File name without the extension. `{doc}` is the document's file
+    /// stem, `{date}` and `{time}` the UTC date and time as `YYYY-MM-DD` and
+    /// `HHMMSS`, and `{n}` a number starting at 1 that makes the name unique.
+    pub name_template: String,
+}
+
+impl Default for AttachOptions {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("assets"),
+            name_template: "{doc}-{n}".to_string(),
+        }
+    }
+}
+
+/// Save the image in a base64 `data:` URI next to the document at
+/// `doc_path`, returning the file and the URL the document should use
+///
+/// An existing file with the same contents is reused. If the template has
+/// no `{n}` and the name is taken by another image, `-2`, `-3`, ... is
+/// appended.
+pub fn attach_image(
+    data_uri: &str,
+    doc_path: &Path,
+    options: &AttachOptions,
+) -> FileResult<(PathBuf, String)> {
+    let (ext, data) = parse_data_uri(data_uri)
+        .ok_or_else(|| FileError::Parse("Not a base64 image data URI".to_string()))?;
+    let bytes = BASE64
+        .decode(data)
+        .map_err(|e| FileError::Parse(format!("Invalid data URI: {}", e)))?;
+
+    let (date, time) = utc_date_time(SystemTime::now());
+    let doc = doc_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    let base = options
+        .name_template
+        .replace("{doc}", &doc)
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace(['/', '\\'], "-");
+    let numbered = base.contains("{n}");
+
+    let dest_dir = doc_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(&options.dir);
+    fs::create_dir_all(&dest_dir)?;
+    let mut n = 1;
+    let name = loop {
+        let stem = match (numbered, n) {
+            (true, _) => base.replace("{n}", &n.to_string()),
+            (false, 1) => base.clone(),
+            (false, _) => format!("{}-{}", base, n),
+        };
+        let name = format!("{}.{}", stem, ext);
+        n += 1;
+        match fs::read(dest_dir.join(&name)) {
+            Ok(existing) if existing != bytes => continue,
+            Ok(_) => {}
+            Err(_) => fs::write(dest_dir.join(&name), &bytes)?,
+        }
+        break name;
+    };
+
+    let url_dir = options.dir.to_string_lossy().replace('\\', "/");
+    let url = if url_dir.is_empty() {
+        name.clone()
+    } else {
+        format!("{}/{}", url_dir.trim_end_matches('/'), name)
+    };
+    Ok((dest_dir.join(name), url))
+}
+
+/// UTC date as `YYYY-MM-DD` and time as `HHMMSS`
+fn utc_date_time(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01, in 400-year eras from 0000-03-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}{:02}{:02}",
+            secs / 3_600,
+            secs % 3_600 / 60,
+            secs % 60
+        ),
+    )
+}
+
+/// Image types that can be embedded, by extension
+const IMAGE_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/x-icon"),
+];
+
+/// Replace every local image in `doc` with a base64 `data:` URI
+///
+/// Relative paths are resolved against `base_dir`. Missing files and files
+/// that are not a known image type are left as they are. Returns the number
+/// of images embedded.
+pub fn embed_images(doc: &mut Document, base_dir: &Path) -> FileResult<usize> {
+    let mut embedded = 0;
+    let mut error = None;
+    visit_image_urls_mut(doc, &mut |url| {
+        let LinkTarget::LocalFile { path, .. } = classify(url) else {
+            return Some(());
+        };
+        let source = base_dir.join(&path);
+        let Some(mime) = mime_for(&source) else {
+            return Some(());
+        };
+        if !source.is_file() {
+            return Some(());
+        }
+        match fs::read(&source) {
+            Ok(bytes) => {
+                *url = format!("data:{};base64,{}", mime, BASE64.encode(bytes));
+                embedded += 1;
+                Some(())
+            }
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        }
+    });
+    match error {
+        Some(e) => Err(e.into()),
+        None => Ok(embedded),
+    }
+}
+
+/// Write every base64 `data:` image in `doc` to a file in `dir` and point the
+/// image at it
+///
+/// `dir` is relative to `base_dir`, the directory of the document, and the
+/// rewritten URLs are relative in the same way. Files are named `image-N`
+/// with an extension for the image type; identical images share one file,
+/// and an existing file is reused only if it has the same contents. Returns
+/// the files written or reused.
+pub fn extract_images(doc: &mut Document, base_dir: &Path, dir: &Path) -> FileResult<Vec<PathBuf>> {
+    let dest_dir = base_dir.join(dir);
+    let url_dir = dir.to_string_lossy().replace('\\', "/");
+    let mut by_data: HashMap<String, (PathBuf, String)> = HashMap::new();
+    let mut written = Vec::new();
+    let mut next = 1;
+    let mut error = None;
+
+    visit_image_urls_mut(doc, &mut |url| {
+        let Some((ext, data)) = parse_data_uri(url) else {
+            return Some(());
+        };
+        if let Some((_, rewritten)) = by_data.get(data) {
+            *url = rewritten.clone();
+            return Some(());
+        }
+        let result = BASE64
+            .decode(data)
+            .map_err(|e| FileError::Parse(format!("Invalid data URI: {}", e)))
+            .and_then(|bytes| {
+                fs::create_dir_all(&dest_dir)?;
+                loop {
+                    let name = format!("image-{}.{}", next, ext);
+                    next += 1;
+                    let path = dest_dir.join(&name);
+                    match fs::read(&path) {
+                        Ok(existing) if existing != bytes => continue,
+                        Ok(_) => {}
+                        Err(_) => fs::write(&path, &bytes)?,
+                    }
+                    return Ok((path, name));
+                }
+            });
+        match result {
+            Ok((path, name)) => {
+                let rewritten = if url_dir.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", url_dir.trim_end_matches('/'), name)
+                };
+                by_data.insert(data.to_string(), (path.clone(), rewritten.clone()));
+                written.push(path);
+                *url = rewritten;
+                Some(())
+            }
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(written),
+    }
+}
+
+/// Whether `path` has the extension of a known image type
+pub fn is_image(path: &Path) -> bool {
+    mime_for(path).is_some()
+}
+
+fn mime_for(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    IMAGE_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// File extension and base64 payload of an image `data:` URI
+fn parse_data_uri(url: &str) -> Option<(&'static str, &str)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mut params = header.split(';');
+    let mime = params.next()?.to_ascii_lowercase();
+    if !params.any(|p| p.eq_ignore_ascii_case("base64")) {
+        return None;
+    }
+    IMAGE_TYPES
+        .iter()
+        .find(|(_, m)| *m == mime)
+        .map(|(ext, _)| (*ext, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::toolkit::links::extract_links;
+    use crate::traits::{ParseConfig, Parser};
+
+    fn image_urls(doc: &Document) -> Vec<String> {
+        extract_links(doc).into_iter().map(|l| l.url).collect()
+    }
+
+    #[test]
+    fn test_embed_and_extract_images() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("dot.png"), b"\x89PNG dot").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"not an image").unwrap();
+
+        let mut doc = MarkdownHandler::new()
+            .parse(
+                "![a](dot.png) ![b](notes.txt) ![c](dot.png) ![d](gone.png)\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(embed_images(&mut doc, dir.path()).unwrap(), 2);
+
+        let urls = image_urls(&doc);
+        assert_eq!(
+            urls[0],
+            format!("data:image/png;base64,{}", BASE64.encode(b"\x89PNG dot"))
+        );
+        assert_eq!(urls[1..], ["notes.txt", urls[0].as_str(), "gone.png"]);
+
+        // An unrelated file already holds the first name
+        fs::create_dir(dir.path().join("media")).unwrap();
+        fs::write(dir.path().join("media/image-1.png"), b"other").unwrap();
+
+        let written = extract_images(&mut doc, dir.path(), Path::new("media")).unwrap();
+        assert_eq!(written, vec![dir.path().join("media/image-2.png")]);
+        assert_eq!(fs::read(&written[0]).unwrap(), b"\x89PNG dot");
+        assert_eq!(
+            image_urls(&doc),
+            [
+                "media/image-2.png",
+                "notes.txt",
+                "media/image-2.png",
+                "gone.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("img/old")).unwrap();
+        fs::write(src.join("img/logo.png"), b"new logo").unwrap();
+        fs::write(src.join("img/old/logo.png"), b"old logo").unwrap();
+        fs::write(src.join("spec.pdf"), b"%PDF").unwrap();
+        fs::write(src.join("other.md"), "# Other\n").unwrap();
+
+        let mut doc = MarkdownHandler::new()
+            .parse(
+                "![a](img/logo.png) ![b](img/old/logo.png) ![c](./img/logo.png)\n\n\
+                 [spec](spec.pdf#page=2) [other](other.md) ![gone](missing.png) \
+                 ![remote](https://example.com/x.png)\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        let out = dir.path().join("out");
+        let collected = collect_assets(&mut doc, &src, &out, &AssetOptions::default()).unwrap();
+
+        assert_eq!(collected.len(), 3);
+        assert_eq!(fs::read(out.join("assets/logo.png")).unwrap(), b"new logo");
+        assert_eq!(
+            fs::read(out.join("assets/logo-2.png")).unwrap(),
+            b"old logo"
+        );
+        assert_eq!(
+            image_urls(&doc),
+            vec![
+                "assets/logo.png",
+                "assets/logo-2.png",
+                "assets/logo.png",
+                "assets/spec.pdf#page=2",
+                "other.md",
+                "missing.png",
+                "https://example.com/x.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloads = dir.path().join("downloads");
+        fs::create_dir_all(downloads.join("old")).unwrap();
+        fs::write(downloads.join("photo.png"), b"new photo").unwrap();
+        fs::write(downloads.join("old/photo.png"), b"old photo").unwrap();
+        let docs = dir.path().join("docs");
+        let options = AssetOptions::default();
+
+        let first = import_file(&downloads.join("photo.png"), &docs, &options).unwrap();
+        assert_eq!(first.url, "assets/photo.png");
+        assert_eq!(fs::read(&first.dest).unwrap(), b"new photo");
+
+        // The same contents again, then a different file of the same name
+        let again = import_file(&downloads.join("photo.png"), &docs, &options).unwrap();
+        assert_eq!(again.url, "assets/photo.png");
+        let other = import_file(&downloads.join("old/photo.png"), &docs, &options).unwrap();
+        assert_eq!(other.url, "assets/photo-2.png");
+
+        // A file already in the assets directory stays where it is
+        let inside = import_file(&other.dest, &docs, &options).unwrap();
+        assert_eq!(inside.url, "assets/photo-2.png");
+        assert!(is_image(&inside.dest));
+        assert!(!is_image(Path::new("notes.md")));
+    }
+
+    #[test]
+    fn test_attach_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let doc = dir.path().join("notes.md");
+        let uri = |bytes: &[u8]| format!("data:image/png;base64,{}", BASE64.encode(bytes));
+        let options = AttachOptions::default();
+
+        let (path, url) = attach_image(&uri(b"first"), &doc, &options).unwrap();
+        assert_eq!(url, "assets/notes-1.png");
+        assert_eq!(fs::read(path).unwrap(), b"first");
+        let (_, url) = attach_image(&uri(b"second"), &doc, &options).unwrap();
+        assert_eq!(url, "assets/notes-2.png");
+        let (_, url) = attach_image(&uri(b"first"), &doc, &options).unwrap();
+        assert_eq!(url, "assets/notes-1.png");
+
+        let options = AttachOptions {
+            dir: PathBuf::from("img"),
+            name_template: "pasted".to_string(),
+        };
+        attach_image(&uri(b"one"), &doc, &options).unwrap();
+        let (_, url) = attach_image(&uri(b"two"), &doc, &options).unwrap();
+        assert_eq!(url, "img/pasted-2.png");
+
+        assert!(attach_image("data:text/plain;base64,eA==", &doc, &options).is_err());
+        assert_eq!(
+            utc_date_time(UNIX_EPOCH + std::time::Duration::from_secs(951_827_696)),
+            ("2000-02-29".to_string(), "123456".to_string())
+        );
+    }
+}
@@ -6,7 +6,9 @@
 //! Format-specific features that cannot be cleanly mapped are preserved as Raw nodes.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+pub use crate::merge::concat;
 
 /// Source format identifier for provenance tracking
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -73,15 +75,164 @@ pub struct Span {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DocumentMeta {
     pub title: Option<String>,
-    pub authors: Vec<String>,
+    pub authors: Vec<Author>,
+    /// Creation or publication date, as written in the source
     pub date: Option<String>,
+    /// Last-modified date, as written in the source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// BCP 47 language tag, e.g. `en-GB`
     pub language: Option<String>,
+    /// Base text direction, when the source states one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<TextDirection>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
     /// Format-specific metadata preserved as key-value pairs
     pub custom: HashMap<String, MetaValue>,
 }
 
+impl DocumentMeta {
+    /// Base direction: as stated, else implied by the language
+    ///
+    /// `None` when neither says anything.
+    pub fn text_direction(&self) -> Option<TextDirection> {
+        self.direction
+            .or_else(|| self.language.as_deref().map(TextDirection::for_language))
+    }
+}
+
+/// Text direction, as in the HTML `dir` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+    /// Decided by the first strong character of the text
+    Auto,
+}
+
+impl TextDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TextDirection::Ltr => "ltr",
+            TextDirection::Rtl => "rtl",
+            TextDirection::Auto => "auto",
+        }
+    }
+
+    /// Parse `ltr`, `rtl` or `auto`, ignoring case
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "ltr" => Some(TextDirection::Ltr),
+            "rtl" => Some(TextDirection::Rtl),
+            "auto" => Some(TextDirection::Auto),
+            _ => None,
+        }
+    }
+
+    /// Direction a BCP 47 language tag is written in
+    ///
+    /// An explicit script subtag wins over the language, so `az-Arab` is
+    /// right-to-left and `ku-Latn` is not.
+    pub fn for_language(tag: &str) -> Self {
+        const RTL_LANGUAGES: &[&str] = &[
+            "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ks", "ku", "ps", "sd", "syr", "ug", "ur",
+            "yi",
+        ];
+        const RTL_SCRIPTS: &[&str] = &[
+            "adlm", "arab", "hebr", "mand", "nkoo", "rohg", "samr", "syrc", "thaa",
+        ];
+
+        let tag = tag.to_ascii_lowercase();
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next().unwrap_or_default();
+        match subtags.find(|s| s.len() == 4 && s.chars().all(|c| c.is_ascii_alphabetic())) {
+            Some(script) if RTL_SCRIPTS.contains(&script) => TextDirection::Rtl,
+            Some(_) => TextDirection::Ltr,
+            None if RTL_LANGUAGES.contains(&language) => TextDirection::Rtl,
+            None => TextDirection::Ltr,
+        }
+    }
+
+    /// Direction of the first strongly directional character in `text`
+    pub fn detect(text: &str) -> Option<Self> {
+        text.chars().find_map(|c| {
+            if is_rtl_char(c) {
+                Some(TextDirection::Rtl)
+            } else if c.is_alphabetic() {
+                Some(TextDirection::Ltr)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Letters of the right-to-left scripts: Hebrew, Arabic, Syriac, Thaana,
+/// N'Ko and their presentation forms and supplements
+fn is_rtl_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    ) && c.is_alphabetic()
+}
+
+/// A document author
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Author {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl Author {
+    /// Parse `Name <email>`, or a bare name
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        if let Some((name, rest)) = text.split_once('<') {
+            if let Some(email) = rest.strip_suffix('>') {
+                return Self {
+                    name: name.trim().to_string(),
+                    email: Some(email.trim().to_string()),
+                };
+            }
+        }
+        Self {
+            name: text.to_string(),
+            email: None,
+        }
+    }
+}
+
+impl From<&str> for Author {
+    fn from(text: &str) -> Self {
+        Self::parse(text)
+    }
+}
+
+impl From<String> for Author {
+    fn from(text: String) -> Self {
+        Self::parse(&text)
+    }
+}
+
+/// `Name <email>`, or just the name
+impl std::fmt::Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.email {
+            Some(email) => write!(f, "{} <{}>", self.name, email),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
 /// Metadata value (recursive for nested structures)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MetaValue {
     String(String),
@@ -141,6 +292,9 @@ pub enum Block {
         level: u8,
         content: Vec<Inline>,
         id: Option<String>,
+        /// Classes on the heading; Org tags are read as classes
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        classes: Vec<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         span: Option<Span>,
     },
@@ -210,12 +364,22 @@ pub enum Block {
     Container {
         id: Option<String>,
         classes: Vec<String>,
-        attributes: HashMap<String, String>,
+        attributes: BTreeMap<String, String>,
         content: Vec<Block>,
         #[serde(skip_serializing_if = "Option::is_none")]
         span: Option<Span>,
     },
 
+    /// Collapsible block, shown folded behind its summary until opened
+    Details {
+        summary: Option<Vec<Inline>>,
+        content: Vec<Block>,
+        /// Whether it starts out open
+        open: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+
     /// Figure with caption
     Figure {
         content: Vec<Block>,
@@ -247,6 +411,13 @@ pub enum Block {
         #[serde(skip_serializing_if = "Option::is_none")]
         span: Option<Span>,
     },
+
+    /// Comment, written to the source but not shown to readers
+    Comment {
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
 }
 
 impl Block {
@@ -263,6 +434,15 @@ pub fn word_count(&self) -> usize {
                 .map(|b| b.word_count())
                 .sum(),
             Block::Container { content, .. } => content.iter().map(|b| b.word_count()).sum(),
+            Block::Details {
+                summary, content, ..
+            } => {
+                let content_count: usize = content.iter().map(|b| b.word_count()).sum();
+                let summary_count: usize = summary
+                    .as_ref()
+                    .map_or(0, |s| s.iter().map(|i| i.word_count()).sum());
+                content_count + summary_count
+            }
             Block::Figure {
                 content, caption, ..
             } => {
@@ -289,9 +469,197 @@ pub fn char_count(&self) -> usize {
                 .map(|b| b.char_count())
                 .sum(),
             Block::Container { content, .. } => content.iter().map(|b| b.char_count()).sum(),
+            Block::Details {
+                summary, content, ..
+            } => {
+                let content_count: usize = content.iter().map(|b| b.char_count()).sum();
+                let summary_count: usize = summary
+                    .as_ref()
+                    .map_or(0, |s| s.iter().map(|i| i.char_count()).sum());
+                content_count + summary_count
+            }
+            Block::Figure {
+                content, caption, ..
+            } => {
+                let content_count: usize = content.iter().map(|b| b.char_count()).sum();
+                let caption_count: usize = caption
+                    .as_ref()
+                    .map_or(0, |c| c.iter().map(|i| i.char_count()).sum());
+                content_count + caption_count
+            }
             _ => 0,
         }
     }
+
+    /// Source span of this block, if recorded by the parser
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Block::Paragraph { span, .. }
+            | Block::Heading { span, .. }
+            | Block::CodeBlock { span, .. }
+            | Block::BlockQuote { span, .. }
+            | Block::List { span, .. }
+            | Block::DefinitionList { span, .. }
+            | Block::Table { span, .. }
+            | Block::ThematicBreak { span }
+            | Block::MathBlock { span, .. }
+            | Block::Container { span, .. }
+            | Block::Details { span, .. }
+            | Block::Figure { span, .. }
+            | Block::Raw { span, .. }
+            | Block::FootnoteDefinition { span, .. }
+            | Block::TableOfContents { span, .. }
+            | Block::Comment { span, .. } => *span,
+        }
+    }
+
+    /// Mutable access to this block's span
+    pub fn span_mut(&mut self) -> Option<&mut Span> {
+        match self {
+            Block::Paragraph { span, .. }
+            | Block::Heading { span, .. }
+            | Block::CodeBlock { span, .. }
+            | Block::BlockQuote { span, .. }
+            | Block::List { span, .. }
+            | Block::DefinitionList { span, .. }
+            | Block::Table { span, .. }
+            | Block::ThematicBreak { span }
+            | Block::MathBlock { span, .. }
+            | Block::Container { span, .. }
+            | Block::Details { span, .. }
+            | Block::Figure { span, .. }
+            | Block::Raw { span, .. }
+            | Block::FootnoteDefinition { span, .. }
+            | Block::TableOfContents { span, .. }
+            | Block::Comment { span, .. } => span.as_mut(),
+        }
+    }
+
+    /// Replace this block's span
+    pub fn set_span(&mut self, new: Option<Span>) {
+        match self {
+            Block::Paragraph { span, .. }
+            | Block::Heading { span, .. }
+            | Block::CodeBlock { span, .. }
+            | Block::BlockQuote { span, .. }
+            | Block::List { span, .. }
+            | Block::DefinitionList { span, .. }
+            | Block::Table { span, .. }
+            | Block::ThematicBreak { span }
+            | Block::MathBlock { span, .. }
+            | Block::Container { span, .. }
+            | Block::Details { span, .. }
+            | Block::Figure { span, .. }
+            | Block::Raw { span, .. }
+            | Block::FootnoteDefinition { span, .. }
+            | Block::TableOfContents { span, .. }
+            | Block::Comment { span, .. } => *span = new,
+        }
+    }
+
+    /// Direction override set with a `dir` attribute on a container
+    pub fn direction(&self) -> Option<TextDirection> {
+        match self {
+            Block::Container { attributes, .. } => {
+                attributes.get("dir").and_then(|d| TextDirection::parse(d))
+            }
+            _ => None,
+        }
+    }
+
+    /// Blocks nested directly inside this block, in document order
+    pub fn child_blocks(&self) -> Vec<&Block> {
+        match self {
+            Block::BlockQuote { content, .. }
+            | Block::Container { content, .. }
+            | Block::Details { content, .. }
+            | Block::Figure { content, .. }
+            | Block::FootnoteDefinition { content, .. } => content.iter().collect(),
+            Block::List { items, .. } => items.iter().flat_map(|i| &i.content).collect(),
+            Block::DefinitionList { items, .. } => items
+                .iter()
+                .flat_map(|i| &i.definitions)
+                .flatten()
+                .collect(),
+            Block::Table {
+                header,
+                body,
+                footer,
+                ..
+            } => header
+                .iter()
+                .chain(body)
+                .chain(footer)
+                .flat_map(|row| &row.cells)
+                .flat_map(|cell| &cell.content)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Inline sequences held directly by this block (not by nested blocks)
+    pub fn inline_groups(&self) -> Vec<&[Inline]> {
+        match self {
+            Block::Paragraph { content, .. } | Block::Heading { content, .. } => {
+                vec![content.as_slice()]
+            }
+            Block::BlockQuote { attribution, .. } => {
+                attribution.iter().map(|a| a.as_slice()).collect()
+            }
+            Block::DefinitionList { items, .. } => {
+                items.iter().map(|i| i.term.as_slice()).collect()
+            }
+            Block::Table { caption, .. } | Block::Figure { caption, .. } => {
+                caption.iter().map(|c| c.as_slice()).collect()
+            }
+            Block::Details { summary, .. } => summary.iter().map(|s| s.as_slice()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable counterpart of [`Block::child_blocks`]
+    pub fn child_blocks_mut(&mut self) -> Vec<&mut Block> {
+        match self {
+            Block::BlockQuote { content, .. }
+            | Block::Container { content, .. }
+            | Block::Details { content, .. }
+            | Block::Figure { content, .. }
+            | Block::FootnoteDefinition { content, .. } => content.iter_mut().collect(),
+            Block::List { items, .. } => items.iter_mut().flat_map(|i| &mut i.content).collect(),
+            Block::DefinitionList { items, .. } => items
+                .iter_mut()
+                .flat_map(|i| &mut i.definitions)
+                .flatten()
+                .collect(),
+            Block::Table {
+                header,
+                body,
+                footer,
+                ..
+            } => header
+                .iter_mut()
+                .chain(body)
+                .chain(footer)
+                .flat_map(|row| &mut row.cells)
+                .flat_map(|cell| &mut cell.content)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable counterpart of [`Block::inline_groups`]
+    pub fn inline_groups_mut(&mut self) -> Vec<&mut Vec<Inline>> {
+        match self {
+            Block::Paragraph { content, .. } | Block::Heading { content, .. } => vec![content],
+            Block::BlockQuote { attribution, .. } => attribution.iter_mut().collect(),
+            Block::DefinitionList { items, .. } => items.iter_mut().map(|i| &mut i.term).collect(),
+            Block::Table { caption, .. } | Block::Figure { caption, .. } => {
+                caption.iter_mut().collect()
+            }
+            Block::Details { summary, .. } => summary.iter_mut().collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -361,6 +729,7 @@ pub enum MathNotation {
     LaTeX,
     AsciiMath,
     MathML,
+    Typst,
 }
 
 /// Inline elements (character-level)
@@ -443,7 +812,7 @@ pub enum Inline {
     Span {
         id: Option<String>,
         classes: Vec<String>,
-        attributes: HashMap<String, String>,
+        attributes: BTreeMap<String, String>,
         content: Vec<Inline>,
     },
 
@@ -453,6 +822,12 @@ pub enum Inline {
         content: String,
     },
 
+    /// Comment within a line, not shown to readers
+    Comment { content: String },
+
+    /// Emoji, by its shortcode without the colons, such as `smile`
+    Emoji { shortcode: String },
+
     /// Quoted text
     Quoted {
         quote_type: QuoteType,
@@ -462,6 +837,12 @@ pub enum Inline {
     /// Keyboard input
     Keyboard { content: String },
 
+    /// Button in a user interface, by its label
+    Button { content: String },
+
+    /// Path through a user interface's menus, such as `File`, `Save As`
+    Menu { items: Vec<String> },
+
     /// Highlight/mark
     Highlight { content: Vec<Inline> },
 }
@@ -494,6 +875,48 @@ pub fn char_count(&self) -> usize {
             _ => 0,
         }
     }
+
+    /// Inline children nested inside this element
+    pub fn children(&self) -> Vec<&Inline> {
+        match self {
+            Inline::Emphasis { content }
+            | Inline::Strong { content }
+            | Inline::Strikethrough { content }
+            | Inline::Underline { content }
+            | Inline::Superscript { content }
+            | Inline::Subscript { content }
+            | Inline::SmallCaps { content }
+            | Inline::Link { content, .. }
+            | Inline::Span { content, .. }
+            | Inline::Quoted { content, .. }
+            | Inline::Highlight { content } => content.iter().collect(),
+            Inline::Citation { prefix, suffix, .. } => {
+                prefix.iter().chain(suffix).flatten().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable counterpart of [`Inline::children`]
+    pub fn children_mut(&mut self) -> Vec<&mut Inline> {
+        match self {
+            Inline::Emphasis { content }
+            | Inline::Strong { content }
+            | Inline::Strikethrough { content }
+            | Inline::Underline { content }
+            | Inline::Superscript { content }
+            | Inline::Subscript { content }
+            | Inline::SmallCaps { content }
+            | Inline::Link { content, .. }
+            | Inline::Span { content, .. }
+            | Inline::Quoted { content, .. }
+            | Inline::Highlight { content } => content.iter_mut().collect(),
+            Inline::Citation { prefix, suffix, .. } => {
+                prefix.iter_mut().chain(suffix).flatten().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -538,6 +961,47 @@ fn test_word_count() {
         };
         assert_eq!(doc.word_count(), 6);
     }
+
+    #[test]
+    fn test_details_counts() {
+        let text = |content: &str| Inline::Text {
+            content: content.to_string(),
+        };
+        let details = Block::Details {
+            summary: Some(vec![text("More info")]),
+            content: vec![Block::Paragraph {
+                content: vec![text("Hidden text")],
+                span: None,
+            }],
+            open: false,
+            span: None,
+        };
+        assert_eq!(details.word_count(), 4);
+        assert_eq!(details.char_count(), 20);
+    }
+
+    #[test]
+    fn test_text_direction() {
+        assert_eq!(TextDirection::for_language("ar-EG"), TextDirection::Rtl);
+        assert_eq!(TextDirection::for_language("he"), TextDirection::Rtl);
+        assert_eq!(TextDirection::for_language("az-Arab"), TextDirection::Rtl);
+        assert_eq!(TextDirection::for_language("ku-Latn"), TextDirection::Ltr);
+        assert_eq!(TextDirection::for_language("en-GB"), TextDirection::Ltr);
+
+        assert_eq!(
+            TextDirection::detect("123 שלום world"),
+            Some(TextDirection::Rtl)
+        );
+        assert_eq!(TextDirection::detect("(1) Hello"), Some(TextDirection::Ltr));
+        assert_eq!(TextDirection::detect("42 - !"), None);
+
+        let meta = DocumentMeta {
+            language: Some("fa".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(meta.text_direction(), Some(TextDirection::Rtl));
+        assert_eq!(DocumentMeta::default().text_direction(), None);
+    }
 }
 
 #[cfg(test)]
@@ -609,6 +1073,7 @@ fn heading_strategy() -> impl Strategy<Value = Block> {
                 level,
                 content,
                 id: None,
+                classes: Vec::new(),
                 span: None,
             })
     }
@@ -739,6 +1204,7 @@ fn prop_heading_level_in_range(level in 1u8..=6, content in prop::collection::ve
                 level,
                 content,
                 id: None,
+                classes: Vec::new(),
                 span: None,
             };
             if let Block::Heading { level: l, .. } = block {
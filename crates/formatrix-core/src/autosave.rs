@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Autosave drafts for crash recovery
+//!
+//! An editor hands every changed buffer to an [`Autosaver`], which writes
+//! the dirty ones to a [`RecoveryDir`] on a timer. Saving a buffer for real
+//! discards its draft, so whatever is left in the directory at the next
+//! launch is work that would otherwise have been lost, ready to be offered
+//! back with [`RecoveryDir::drafts`].
+//!
+//! Drafts are JSON files written with [`write_atomic`], one per buffer.
+
+use crate::ast::SourceFormat;
+use crate::file_ops::{write_atomic, BackupPolicy, FileError, FileResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Unsaved contents of one editor buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Draft {
+    /// Identifies the buffer, e.g. an editor tab id
+    pub id: String,
+    /// File the buffer belongs to; `None` if it was never saved
+    pub path: Option<PathBuf>,
+    pub format: SourceFormat,
+    pub content: String,
+    /// Seconds since the Unix epoch when the draft was written
+    pub saved_at: u64,
+}
+
+/// Directory holding drafts
+#[derive(Debug, Clone)]
+pub struct RecoveryDir {
+    dir: PathBuf,
+}
+
+impl RecoveryDir {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write `draft`, replacing the previous draft of the same buffer
+    pub fn save_draft(&self, draft: &Draft) -> FileResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(draft).map_err(|e| FileError::Render(e.to_string()))?;
+        write_atomic(self.draft_path(&draft.id), &json, BackupPolicy::None)?;
+        Ok(())
+    }
+
+    /// Every draft in the directory, newest first
+    ///
+    /// Files that are not readable drafts are skipped.
+    pub fn drafts(&self) -> FileResult<Vec<Draft>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut drafts: Vec<Draft> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                serde_json::from_slice(&fs::read(path).ok()?).ok()
+            })
+            .collect();
+        drafts.sort_by(|a, b| b.saved_at.cmp(&a.saved_at).then(a.id.cmp(&b.id)));
+        Ok(drafts)
+    }
+
+    /// Remove the draft of buffer `id`, if there is one
+    pub fn discard(&self, id: &str) -> FileResult<()> {
+        match fs::remove_file(self.draft_path(id)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Buffer ids may hold any characters, so files are named by their
+    /// FNV-1a hash, which unlike `DefaultHasher` is the same in every build
+    fn draft_path(&self, id: &str) -> PathBuf {
+        let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        self.dir.join(format!("{:016x}.json", hash))
+    }
+}
+
+/// Buffers changed since they were last written as drafts
+type DirtyBuffers = HashMap<String, Draft>;
+
+/// Writes dirty buffers to a [`RecoveryDir`] in the background
+///
+/// Dropping it stops the timer after writing anything still pending.
+#[derive(Debug)]
+pub struct Autosaver {
+    recovery: RecoveryDir,
+    dirty: Arc<Mutex<DirtyBuffers>>,
+    stop: Option<mpsc::Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Autosaver {
+    /// Start writing drafts to `recovery` every `interval`
+    pub fn start(recovery: RecoveryDir, interval: Duration) -> Self {
+        let dirty = Arc::new(Mutex::new(DirtyBuffers::new()));
+        let (stop, stopped) = mpsc::channel();
+        let worker = {
+            let recovery = recovery.clone();
+            let dirty = Arc::clone(&dirty);
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let _ = write_dirty(&recovery, &dirty);
+                }
+            })
+        };
+        Self {
+            recovery,
+            dirty,
+            stop: Some(stop),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn recovery_dir(&self) -> &RecoveryDir {
+        &self.recovery
+    }
+
+    /// Record new contents for buffer `id`, to be written on the next tick
+    pub fn update(
+        &self,
+        id: impl Into<String>,
+        path: Option<PathBuf>,
+        format: SourceFormat,
+        content: impl Into<String>,
+    ) {
+        let id = id.into();
+        let draft = Draft {
+            id: id.clone(),
+            path,
+            format,
+            content: content.into(),
+            saved_at: 0,
+        };
+        self.dirty
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, draft);
+    }
+
+    /// Buffer `id` was saved or closed without changes: forget its draft
+    pub fn mark_clean(&self, id: &str) -> FileResult<()> {
+        // Hold the lock so a tick in progress cannot write the draft back
+        let mut dirty = self.dirty.lock().unwrap_or_else(|e| e.into_inner());
+        dirty.remove(id);
+        self.recovery.discard(id)
+    }
+
+    /// Write every pending draft now, returning how many were written
+    pub fn flush(&self) -> FileResult<usize> {
+        write_dirty(&self.recovery, &self.dirty)
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        let _ = self.flush();
+    }
+}
+
+fn write_dirty(recovery: &RecoveryDir, dirty: &Mutex<DirtyBuffers>) -> FileResult<usize> {
+    let mut dirty = dirty.lock().unwrap_or_else(|e| e.into_inner());
+    let saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // Drafts that fail to write stay pending for the next try
+    let mut written = 0;
+    let mut error = None;
+    dirty.retain(|_, draft| {
+        draft.saved_at = saved_at;
+        match recovery.save_draft(draft) {
+            Ok(()) => {
+                written += 1;
+                false
+            }
+            Err(err) => {
+                error.get_or_insert(err);
+                true
+            }
+        }
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok(written),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autosave_and_recover() {
+        let dir = tempfile::tempdir().unwrap();
+        let recovery = RecoveryDir::new(dir.path().join("drafts"));
+        assert!(recovery.drafts().unwrap().is_empty());
+
+        let autosaver = Autosaver::start(recovery.clone(), Duration::from_secs(3600));
+        autosaver.update("tab/1", None, SourceFormat::Markdown, "# Draft\n");
+        autosaver.update(
+            "tab/2",
+            Some("notes.org".into()),
+            SourceFormat::OrgMode,
+            "* x\n",
+        );
+        autosaver.update(
+            "tab/2",
+            Some("notes.org".into()),
+            SourceFormat::OrgMode,
+            "* y\n",
+        );
+        assert_eq!(autosaver.flush().unwrap(), 2);
+        assert_eq!(autosaver.flush().unwrap(), 0);
+
+        autosaver.mark_clean("tab/1").unwrap();
+        drop(autosaver);
+
+        let drafts = recovery.drafts().unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].id, "tab/2");
+        assert_eq!(drafts[0].path, Some(PathBuf::from("notes.org")));
+        assert_eq!(drafts[0].content, "* y\n");
+
+        recovery.discard("tab/2").unwrap();
+        assert!(recovery.drafts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_timer_writes_drafts() {
+        let dir = tempfile::tempdir().unwrap();
+        let recovery = RecoveryDir::new(dir.path());
+        let autosaver = Autosaver::start(recovery.clone(), Duration::from_millis(20));
+        autosaver.update("a", None, SourceFormat::PlainText, "text");
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while recovery.drafts().unwrap().is_empty() {
+            assert!(std::time::Instant::now() < deadline, "no draft written");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(recovery.drafts().unwrap()[0].content, "text");
+    }
+}
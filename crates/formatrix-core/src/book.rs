@@ -0,0 +1,381 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Book assembly from an ordered list of chapter files
+//!
+//! A manifest names the chapters in reading order; they may be in any mix of
+//! supported formats. The book is either merged into one [`Document`] or
+//! written back out as one file per chapter in a single target format.
+//!
+//! When merging, each chapter's headings are shifted so its top level sits
+//! directly under the book title, and links between chapter files become
+//! links to anchors within the merged document. When splitting, those links
+//! are pointed at the renamed output files instead.
+//!
+//! Manifests are JSON:
+//!
+//! ```json
+//! { "title": "User Guide", "chapters": ["intro.md", "setup.adoc", "usage.org"] }
+//! ```
+//!
+//! A [`Project`] can stand in for the manifest with [`Book::from_project`].
+
+use crate::ast::{Block, Document, Inline, SourceFormat};
+use crate::file_ops::{open_file, save_file_as, FileError, FileResult};
+use crate::merge::{MergeOptions, MetaMerge};
+use crate::project::Project;
+use crate::toolkit::inline_text;
+use crate::toolkit::links::{classify, heading_slug, visit_link_urls_mut, LinkTarget};
+use crate::traits::RenderConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Chapter list as read from a manifest file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookManifest {
+    /// Rendered as a level-1 heading above the chapters when merging
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Chapter files in reading order, relative to the manifest
+    pub chapters: Vec<PathBuf>,
+}
+
+/// One parsed chapter
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub path: PathBuf,
+    pub document: Document,
+}
+
+impl Chapter {
+    /// Anchor a link to the whole chapter should point at: the id or slug of
+    /// its first heading
+    fn anchor(&self) -> Option<String> {
+        self.document.content.iter().find_map(|block| match block {
+            Block::Heading { id, content, .. } => Some(
+                id.clone()
+                    .unwrap_or_else(|| heading_slug(&inline_text(content))),
+            ),
+            _ => None,
+        })
+    }
+}
+
+/// Chapters loaded and ready to merge or split
+#[derive(Debug, Clone)]
+pub struct Book {
+    pub title: Option<String>,
+    pub chapters: Vec<Chapter>,
+}
+
+impl Book {
+    /// Read a manifest file and load its chapters
+    pub fn load(manifest_path: impl AsRef<Path>) -> FileResult<Self> {
+        let manifest_path = manifest_path.as_ref();
+        let json = fs::read_to_string(manifest_path)?;
+        let manifest: BookManifest =
+            serde_json::from_str(&json).map_err(|e| FileError::Parse(e.to_string()))?;
+        let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+        Self::from_manifest(&manifest, base_dir)
+    }
+
+    /// Load the chapters of `manifest`, resolving paths against `base_dir`
+    pub fn from_manifest(manifest: &BookManifest, base_dir: &Path) -> FileResult<Self> {
+        let chapters = manifest
+            .chapters
+            .iter()
+            .map(|chapter| {
+                let path = normalize(&base_dir.join(chapter));
+                let opened = open_file(&path)?;
+                Ok(Chapter {
+                    path,
+                    document: opened.document,
+                })
+            })
+            .collect::<FileResult<_>>()?;
+
+        Ok(Self {
+            title: manifest.title.clone(),
+            chapters,
+        })
+    }
+
+    /// Use the documents of `project` as chapters, in its reading order
+    pub fn from_project(project: &Project) -> Self {
+        let chapters = project
+            .documents
+            .iter()
+            .map(|doc| Chapter {
+                path: normalize(&project.root.join(&doc.path)),
+                document: doc.document.clone(),
+            })
+            .collect();
+        Self {
+            title: project.manifest.title.clone(),
+            chapters,
+        }
+    }
+
+    /// Merge every chapter into one document in `format`
+    pub fn assemble(&self, format: SourceFormat) -> Document {
+        let mut doc = Document::new(format);
+        let top_level = match &self.title {
+            Some(title) => {
+                doc.content.push(Block::Heading {
+                    level: 1,
+                    content: vec![Inline::Text {
+                        content: title.clone(),
+                    }],
+                    id: None,
+                    classes: Vec::new(),
+                    span: None,
+                });
+                2
+            }
+            None => 1,
+        };
+
+        let anchors: Vec<_> = self.chapters.iter().map(Chapter::anchor).collect();
+        for chapter in &self.chapters {
+            let mut chapter_doc = chapter.document.clone();
+            shift_headings(&mut chapter_doc.content, top_level);
+            self.rewrite_chapter_links(chapter, &mut chapter_doc, |target, fragment| {
+                fragment
+                    .or_else(|| anchors[target].clone())
+                    .map(|anchor| format!("#{}", anchor))
+            });
+            doc.append(
+                chapter_doc,
+                &MergeOptions {
+                    demote_titles: false,
+                    meta: MetaMerge::Keep,
+                },
+            );
+        }
+        doc
+    }
+
+    /// One document per chapter in `format`, with output file names
+    ///
+    /// Files are named `NN-<stem>.<ext>` in reading order so they sort
+    /// correctly, and links between chapters use those names.
+    pub fn split(&self, format: SourceFormat) -> Vec<(String, Document)> {
+        let names: Vec<String> = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| {
+                let stem = chapter
+                    .path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "chapter".to_string());
+                format!("{:02}-{}.{}", i + 1, stem, format.extension())
+            })
+            .collect();
+
+        self.chapters
+            .iter()
+            .zip(&names)
+            .map(|(chapter, name)| {
+                let mut doc = chapter.document.clone();
+                doc.source_format = format;
+                shift_headings(&mut doc.content, 1);
+                self.rewrite_chapter_links(chapter, &mut doc, |target, fragment| {
+                    Some(match fragment {
+                        Some(fragment) => format!("{}#{}", names[target], fragment),
+                        None => names[target].clone(),
+                    })
+                });
+                (name.clone(), doc)
+            })
+            .collect()
+    }
+
+    /// Render the merged book to `path` in `format`
+    pub fn write_single(
+        &self,
+        path: impl AsRef<Path>,
+        format: SourceFormat,
+        config: &RenderConfig,
+    ) -> FileResult<()> {
+        save_file_as(&self.assemble(format), path, format, config)
+    }
+
+    /// Render one file per chapter into `dir`, returning the written paths
+    pub fn write_chapters(
+        &self,
+        dir: impl AsRef<Path>,
+        format: SourceFormat,
+        config: &RenderConfig,
+    ) -> FileResult<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        self.split(format)
+            .into_iter()
+            .map(|(name, doc)| {
+                let path = dir.join(name);
+                save_file_as(&doc, &path, format, config)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    /// Replace links from `chapter` into other chapter files with whatever
+    /// `target_url` returns for (chapter index, fragment)
+    fn rewrite_chapter_links(
+        &self,
+        chapter: &Chapter,
+        doc: &mut Document,
+        target_url: impl Fn(usize, Option<String>) -> Option<String>,
+    ) {
+        let dir = chapter.path.parent().unwrap_or(Path::new(""));
+        visit_link_urls_mut(doc, &mut |url| {
+            if let LinkTarget::LocalFile { path, fragment } = classify(url) {
+                let resolved = normalize(&dir.join(path));
+                if let Some(target) = self.chapters.iter().position(|c| c.path == resolved) {
+                    if let Some(rewritten) = target_url(target, fragment) {
+                        *url = rewritten;
+                    }
+                }
+            }
+            Some(())
+        });
+    }
+}
+
+/// Shift every heading in `blocks` so the shallowest is at `top_level`
+pub(crate) fn shift_headings(blocks: &mut [Block], top_level: u8) {
+    fn min_level<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> Option<u8> {
+        blocks
+            .into_iter()
+            .filter_map(|block| match block {
+                Block::Heading { level, .. } => Some(*level),
+                other => min_level(other.child_blocks()),
+            })
+            .min()
+    }
+
+    fn shift<'a>(blocks: impl IntoIterator<Item = &'a mut Block>, delta: i16) {
+        for block in blocks {
+            if let Block::Heading { level, .. } = block {
+                *level = (*level as i16 + delta).clamp(1, 6) as u8;
+            }
+            shift(block.child_blocks_mut(), delta);
+        }
+    }
+
+    if let Some(min) = min_level(blocks.iter()) {
+        shift(blocks.iter_mut(), top_level as i16 - min as i16);
+    }
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolkit::links::extract_links;
+
+    fn headings(doc: &Document) -> Vec<(u8, String)> {
+        doc.content
+            .iter()
+            .filter_map(|block| match block {
+                Block::Heading { level, content, .. } => Some((*level, inline_text(content))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn urls(doc: &Document) -> Vec<String> {
+        extract_links(doc).into_iter().map(|l| l.url).collect()
+    }
+
+    fn book() -> (tempfile::TempDir, Book) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("intro.md"),
+            "# Intro\n\nRead [setup](chapters/setup.dj#install) next.\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("chapters")).unwrap();
+        fs::write(
+            dir.path().join("chapters/setup.dj"),
+            "## Setup\n\n### Install\n\nBack to [the intro](../intro.md).\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("book.json"),
+            r#"{ "title": "Guide", "chapters": ["intro.md", "chapters/setup.dj"] }"#,
+        )
+        .unwrap();
+
+        let book = Book::load(dir.path().join("book.json")).unwrap();
+        (dir, book)
+    }
+
+    #[test]
+    fn test_assemble() {
+        let (_dir, book) = book();
+        let doc = book.assemble(SourceFormat::Markdown);
+
+        assert_eq!(
+            headings(&doc),
+            vec![
+                (1, "Guide".to_string()),
+                (2, "Intro".to_string()),
+                (2, "Setup".to_string()),
+                (3, "Install".to_string()),
+            ]
+        );
+        assert_eq!(urls(&doc), vec!["#install", "#intro"]);
+    }
+
+    #[test]
+    fn test_write_chapters() {
+        let (dir, book) = book();
+        let out = dir.path().join("out");
+        let written = book
+            .write_chapters(&out, SourceFormat::OrgMode, &RenderConfig::default())
+            .unwrap();
+
+        assert_eq!(
+            written,
+            vec![out.join("01-intro.org"), out.join("02-setup.org")]
+        );
+
+        let split = book.split(SourceFormat::OrgMode);
+        assert_eq!(urls(&split[0].1), vec!["02-setup.org#install"]);
+        assert_eq!(urls(&split[1].1), vec!["01-intro.org"]);
+        assert_eq!(headings(&split[1].1)[0], (1, "Setup".to_string()));
+    }
+
+    #[test]
+    fn test_from_project() {
+        let (dir, _) = book();
+        fs::write(
+            dir.path().join(crate::project::MANIFEST_FILE),
+            r#"{ "title": "Guide", "documents": ["intro.md", "chapters/setup.dj"] }"#,
+        )
+        .unwrap();
+        let project = Project::open(dir.path()).unwrap();
+        let doc = Book::from_project(&project).assemble(SourceFormat::Markdown);
+        assert_eq!(headings(&doc)[0], (1, "Guide".to_string()));
+        assert_eq!(urls(&doc), vec!["#install", "#intro"]);
+    }
+}
@@ -0,0 +1,721 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Pasting from the clipboard
+//!
+//! Clipboard text is either markup in one of the supported formats, found
+//! with [`format_from_content`], or the HTML flavor that browsers and word
+//! processors put alongside it. [`parse_html`] reads the structural elements
+//! of that HTML (paragraphs, headings, lists, tables, emphasis, links and
+//! images) into the AST and keeps only the text of everything else, so the
+//! styling spans and `mso-` classes Word adds do not come along.
+//! [`convert_paste`] turns either into markup for the document being edited.
+
+use crate::ast::{
+    Block, ColumnAlignment, ColumnSpec, Document, Inline, LinkType, ListItem, ListKind,
+    SourceFormat, TableCell, TableRow,
+};
+use crate::edit::children_mut;
+use crate::file_ops::format_from_content;
+use crate::formats::handler_for;
+use crate::toolkit::sanitize::{skip_element, Tag};
+use crate::traits::{ParseConfig, RenderConfig, Result};
+use serde::{Deserialize, Serialize};
+
+/// What the pasted content was read as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasteSource {
+    /// The HTML clipboard flavor
+    Html,
+    /// Plain clipboard text, detected as this format
+    Text(SourceFormat),
+}
+
+/// Clipboard content as markup for the document being pasted into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Paste {
+    pub markup: String,
+    pub source: PasteSource,
+}
+
+/// Convert clipboard content to `target` markup
+///
+/// The HTML flavor is preferred when it holds any content. Plain text
+/// already in `target` is pasted as it is; text in another format is
+/// converted. Trailing newlines are removed so a single paragraph pastes
+/// inline.
+pub fn convert_paste(
+    text: &str,
+    html: Option<&str>,
+    target: SourceFormat,
+    config: &RenderConfig,
+) -> Result<Paste> {
+    let from_html = html.map(parse_html).filter(|doc| !doc.content.is_empty());
+    let (doc, source) = match from_html {
+        Some(doc) => (doc, PasteSource::Html),
+        None => {
+            let format = format_from_content(text);
+            if format == target {
+                return Ok(Paste {
+                    markup: text.to_string(),
+                    source: PasteSource::Text(format),
+                });
+            }
+            let doc = handler_for(format).parse(text, &ParseConfig::default())?;
+            (doc, PasteSource::Text(format))
+        }
+    };
+
+    let markup = handler_for(target).render(&doc, config)?;
+    Ok(Paste {
+        markup: markup.trim_end_matches('\n').to_string(),
+        source,
+    })
+}
+
+/// Read an HTML fragment or page into a document
+///
+/// HTML has no [`SourceFormat`], so the document is marked as plain text.
+/// On a Windows clipboard, only the part between the `StartFragment` and
+/// `EndFragment` markers is read.
+pub fn parse_html(html: &str) -> Document {
+    let fragment = html
+        .split_once("<!--StartFragment-->")
+        .map(|(_, rest)| rest.split("<!--EndFragment-->").next().unwrap_or(rest))
+        .unwrap_or(html);
+
+    let mut doc = Document::new(SourceFormat::PlainText);
+    doc.content = blocks(&parse_tree(fragment).children);
+    doc
+}
+
+/// Elements with no content or end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "wbr",
+];
+
+/// Elements whose content is not part of the text
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "template", "title", "noscript", "iframe", "object",
+];
+
+/// Elements that start a block rather than continue a paragraph
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "center",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, Option<String>)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn new(tag: Tag) -> Self {
+        Self {
+            name: tag.name,
+            attributes: tag.attributes,
+            children: Vec::new(),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    /// The outermost elements inside this one with one of `names`
+    fn descendants<'a>(&'a self, names: &[&str], out: &mut Vec<&'a Element>) {
+        for child in &self.children {
+            if let Node::Element(element) = child {
+                if names.contains(&element.name.as_str()) {
+                    out.push(element);
+                } else {
+                    element.descendants(names, out);
+                }
+            }
+        }
+    }
+
+    /// All text inside the element, as written
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for child in &self.children {
+            match child {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(e) if e.name == "br" => text.push('\n'),
+                Node::Element(e) => text.push_str(&e.text()),
+            }
+        }
+        text
+    }
+}
+
+/// Build an element tree, tolerating the unclosed and misnested tags real
+/// clipboard HTML is full of
+fn parse_tree(html: &str) -> Element {
+    let root = Tag {
+        name: String::new(),
+        closing: false,
+        self_closing: false,
+        attributes: Vec::new(),
+        len: 0,
+    };
+    let mut stack = vec![Element::new(root)];
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        push_text(&mut stack, &rest[..lt]);
+        rest = &rest[lt..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+        let Some(tag) = Tag::parse(rest) else {
+            push_text(&mut stack, "<");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.len..];
+
+        if tag.closing {
+            if stack[1..].iter().any(|e| e.name == tag.name) {
+                while close_top(&mut stack) != tag.name {}
+            }
+            continue;
+        }
+        if SKIPPED_ELEMENTS.contains(&tag.name.as_str()) {
+            rest = skip_element(rest, &tag.name);
+            continue;
+        }
+        while stack.len() > 1 && ends_implicitly(&stack[stack.len() - 1].name, &tag.name) {
+            close_top(&mut stack);
+        }
+
+        let void = tag.self_closing || VOID_ELEMENTS.contains(&tag.name.as_str());
+        let element = Element::new(tag);
+        if void {
+            let parent = stack.last_mut().expect("root is never closed");
+            parent.children.push(Node::Element(element));
+        } else {
+            stack.push(element);
+        }
+    }
+    push_text(&mut stack, rest);
+
+    while stack.len() > 1 {
+        close_top(&mut stack);
+    }
+    stack.pop().expect("root is never closed")
+}
+
+/// Close the innermost open element, returning its name
+fn close_top(stack: &mut Vec<Element>) -> String {
+    let element = stack.pop().expect("root is never closed");
+    let name = element.name.clone();
+    let parent = stack.last_mut().expect("root is never closed");
+    parent.children.push(Node::Element(element));
+    name
+}
+
+/// Whether an open `open` element ends where a `next` start tag appears
+fn ends_implicitly(open: &str, next: &str) -> bool {
+    match next {
+        "li" => matches!(open, "li" | "p"),
+        "tr" => matches!(open, "tr" | "td" | "th" | "p"),
+        "td" | "th" => matches!(open, "td" | "th" | "p"),
+        _ => open == "p" && BLOCK_ELEMENTS.contains(&next),
+    }
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if !text.is_empty() {
+        let parent = stack.last_mut().expect("root is never closed");
+        parent.children.push(Node::Text(decode_entities(text)));
+    }
+}
+
+/// Replace character references with the characters they stand for
+///
+/// Non-breaking spaces become ordinary spaces; Word pads with them.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..end + 1];
+                let c = match name {
+                    "amp" => '&',
+                    "lt" => '<',
+                    "gt" => '>',
+                    "quot" => '"',
+                    "apos" => '\'',
+                    "nbsp" => ' ',
+                    "ndash" => '–',
+                    "mdash" => '—',
+                    "hellip" => '…',
+                    "lsquo" => '‘',
+                    "rsquo" => '’',
+                    "ldquo" => '“',
+                    "rdquo" => '”',
+                    "copy" => '©',
+                    _ => {
+                        let number = name.strip_prefix('#')?;
+                        let code = match number.strip_prefix(['x', 'X']) {
+                            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                            None => number.parse().ok()?,
+                        };
+                        match char::from_u32(code)? {
+                            '\u{a0}' => ' ',
+                            c => c,
+                        }
+                    }
+                };
+                Some((c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Blocks from a run of nodes; text and inline elements between blocks
+/// become paragraphs
+fn blocks<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut run = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Element(element) if BLOCK_ELEMENTS.contains(&element.name.as_str()) => {
+                push_paragraph(&mut blocks, std::mem::take(&mut run));
+                block(element, &mut blocks);
+            }
+            node => inline(node, &mut run),
+        }
+    }
+    push_paragraph(&mut blocks, run);
+    blocks
+}
+
+fn push_paragraph(blocks: &mut Vec<Block>, content: Vec<Inline>) {
+    let content = tidy(content);
+    if !content.is_empty() {
+        blocks.push(Block::Paragraph {
+            content,
+            span: None,
+        });
+    }
+}
+
+fn block(element: &Element, out: &mut Vec<Block>) {
+    match element.name.as_str() {
+        "p" | "dt" | "dd" | "summary" | "figcaption" => {
+            push_paragraph(out, inlines(&element.children))
+        }
+        name @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+            let content = tidy(inlines(&element.children));
+            if !content.is_empty() {
+                out.push(Block::Heading {
+                    level: name.as_bytes()[1] - b'0',
+                    content,
+                    id: element.attribute("id").map(str::to_string),
+                    classes: element
+                        .attribute("class")
+                        .map(|class| class.split_whitespace().map(String::from).collect())
+                        .unwrap_or_default(),
+                    span: None,
+                });
+            }
+        }
+        "pre" => {
+            let mut tagged = vec![element];
+            element.descendants(&["code"], &mut tagged);
+            let language = tagged.iter().find_map(|e| {
+                e.attribute("class")?
+                    .split_whitespace()
+                    .find_map(|class| class.strip_prefix("language-"))
+                    .map(str::to_string)
+            });
+            let text = element.text();
+            out.push(Block::CodeBlock {
+                language,
+                content: text
+                    .strip_prefix('\n')
+                    .unwrap_or(&text)
+                    .trim_end()
+                    .to_string(),
+                line_numbers: false,
+                highlight_lines: Vec::new(),
+                span: None,
+            });
+        }
+        "blockquote" => out.push(Block::BlockQuote {
+            content: blocks(&element.children),
+            attribution: None,
+            admonition: None,
+            span: None,
+        }),
+        "details" => {
+            let is_summary = |node: &&Node| matches!(node, Node::Element(e) if e.name == "summary");
+            let summary = element
+                .children
+                .iter()
+                .find(is_summary)
+                .and_then(|node| match node {
+                    Node::Element(summary) => Some(tidy(inlines(&summary.children))),
+                    Node::Text(_) => None,
+                })
+                .filter(|summary| !summary.is_empty());
+            out.push(Block::Details {
+                summary,
+                content: blocks(element.children.iter().filter(|node| !is_summary(node))),
+                open: element.attributes.iter().any(|(n, _)| n == "open"),
+                span: None,
+            });
+        }
+        "ul" | "ol" => out.push(list(element)),
+        "table" => out.extend(table(element)),
+        "hr" => out.push(Block::ThematicBreak { span: None }),
+        _ => out.extend(blocks(&element.children)),
+    }
+}
+
+fn list(element: &Element) -> Block {
+    let mut entries = Vec::new();
+    element.descendants(&["li"], &mut entries);
+
+    let mut task = false;
+    let items = entries
+        .into_iter()
+        .map(|item| {
+            let mut inputs = Vec::new();
+            item.descendants(&["input"], &mut inputs);
+            let checked = inputs
+                .into_iter()
+                .find(|input| input.attribute("type") == Some("checkbox"))
+                .map(|input| input.attributes.iter().any(|(n, _)| n == "checked"));
+            task |= checked.is_some();
+            ListItem {
+                content: blocks(&item.children),
+                checked,
+                marker: None,
+            }
+        })
+        .collect();
+
+    let ordered = element.name == "ol";
+    Block::List {
+        kind: match (task, ordered) {
+            (true, _) => ListKind::Task,
+            (false, true) => ListKind::Ordered,
+            (false, false) => ListKind::Bullet,
+        },
+        items,
+        start: element
+            .attribute("start")
+            .and_then(|s| s.parse().ok())
+            .filter(|_| ordered),
+        span: None,
+    }
+}
+
+fn table(element: &Element) -> Option<Block> {
+    let mut rows = Vec::new();
+    element.descendants(&["tr"], &mut rows);
+
+    let mut rows: Vec<(bool, TableRow)> = rows
+        .into_iter()
+        .map(|row| {
+            let mut cells = Vec::new();
+            row.descendants(&["td", "th"], &mut cells);
+            let header = !cells.is_empty() && cells.iter().all(|c| c.name == "th");
+            let span = |cell: &Element, name| {
+                cell.attribute(name)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(1)
+            };
+            let cells = cells
+                .into_iter()
+                .map(|cell| TableCell {
+                    content: blocks(&cell.children),
+                    colspan: span(cell, "colspan"),
+                    rowspan: span(cell, "rowspan"),
+                    alignment: None,
+                })
+                .collect();
+            (header, TableRow { cells })
+        })
+        .collect();
+    let columns = rows
+        .iter()
+        .map(|(_, row)| row.cells.iter().map(|c| c.colspan as usize).sum())
+        .max()?;
+
+    let header = match rows.first() {
+        Some((true, _)) => Some(rows.remove(0).1),
+        _ => None,
+    };
+    Some(Block::Table {
+        caption: None,
+        columns: vec![
+            ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            };
+            columns
+        ],
+        header,
+        body: rows.into_iter().map(|(_, row)| row).collect(),
+        footer: None,
+        span: None,
+    })
+}
+
+fn inlines(nodes: &[Node]) -> Vec<Inline> {
+    let mut out = Vec::new();
+    for node in nodes {
+        inline(node, &mut out);
+    }
+    out
+}
+
+fn inline(node: &Node, out: &mut Vec<Inline>) {
+    let element = match node {
+        Node::Text(text) => {
+            out.push(Inline::Text {
+                content: text.clone(),
+            });
+            return;
+        }
+        Node::Element(element) => element,
+    };
+
+    let children = || inlines(&element.children);
+    let inline = match element.name.as_str() {
+        "strong" | "b" => Inline::Strong {
+            content: children(),
+        },
+        "em" | "i" => Inline::Emphasis {
+            content: children(),
+        },
+        "s" | "del" | "strike" => Inline::Strikethrough {
+            content: children(),
+        },
+        "u" | "ins" => Inline::Underline {
+            content: children(),
+        },
+        "sup" => Inline::Superscript {
+            content: children(),
+        },
+        "sub" => Inline::Subscript {
+            content: children(),
+        },
+        "mark" => Inline::Highlight {
+            content: children(),
+        },
+        "kbd" => Inline::Keyboard {
+            content: element.text(),
+        },
+        "code" | "samp" | "tt" => Inline::Code {
+            content: element.text(),
+            language: None,
+        },
+        "a" if element.attribute("href").is_some() => Inline::Link {
+            url: element.attribute("href").unwrap_or_default().to_string(),
+            title: element.attribute("title").map(str::to_string),
+            content: children(),
+            link_type: LinkType::Inline,
+        },
+        "img" => match element.attribute("src") {
+            Some(src) => Inline::Image {
+                url: src.to_string(),
+                alt: element.attribute("alt").unwrap_or_default().to_string(),
+                title: element.attribute("title").map(str::to_string),
+                width: element.attribute("width").map(str::to_string),
+                height: element.attribute("height").map(str::to_string),
+            },
+            None => return,
+        },
+        "br" => Inline::LineBreak,
+        "input" => return,
+        _ => {
+            out.extend(children());
+            return;
+        }
+    };
+    out.push(inline);
+}
+
+/// Collapse whitespace as a browser would, trim the ends and drop text
+/// left empty
+fn tidy(mut content: Vec<Inline>) -> Vec<Inline> {
+    collapse(&mut content, &mut true);
+    trim_end(&mut content);
+    content
+}
+
+/// Collapse runs of whitespace to one space, dropping a space that follows
+/// another or a line break; `space` says whether the text before ends in one
+fn collapse(content: &mut Vec<Inline>, space: &mut bool) {
+    content.retain_mut(|inline| {
+        match inline {
+            Inline::Text { content } => {
+                let mut collapsed = String::with_capacity(content.len());
+                for c in content.chars() {
+                    if c.is_whitespace() {
+                        if !*space {
+                            collapsed.push(' ');
+                        }
+                        *space = true;
+                    } else {
+                        collapsed.push(c);
+                        *space = false;
+                    }
+                }
+                *content = collapsed;
+                return !content.is_empty();
+            }
+            Inline::LineBreak => *space = true,
+            Inline::Code { .. } | Inline::Image { .. } => *space = false,
+            other => {
+                if let Some(children) = children_mut(other) {
+                    collapse(children, space);
+                }
+            }
+        }
+        true
+    });
+}
+
+/// Remove trailing spaces, and spaces before line breaks
+fn trim_end(content: &mut Vec<Inline>) -> bool {
+    let mut trim = true;
+    for inline in content.iter_mut().rev() {
+        match inline {
+            Inline::Text { content } if trim => {
+                content.truncate(content.trim_end().len());
+                trim = content.is_empty();
+            }
+            Inline::LineBreak => trim = true,
+            other => {
+                if let Some(children) = children_mut(other) {
+                    if trim {
+                        trim = trim_end(children);
+                        continue;
+                    }
+                }
+                trim = false;
+            }
+        }
+    }
+    content.retain(|inline| !matches!(inline, Inline::Text { content } if content.is_empty()));
+    trim
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_html() {
+        let html = "<html><head><style>p { color: red }</style></head><body>\
+            <h2 id=\"intro\">Intro</h2>\n\
+            <p class=MsoNormal>Some <b>bold</b>&nbsp;and\n  <a href=\"https://example.com\">a <i>link</i></a>.\
+            <p>Second <span style=\"mso-bidi\">para<o:p></o:p></span>\
+            <ul><li>one<li><input type=checkbox checked> two</ul>\
+            <pre><code class=\"language-rust\">fn main() {}\n</code></pre>\
+            </body></html>";
+        let doc = parse_html(html);
+        let markdown = handler_for(SourceFormat::Markdown)
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert_eq!(
+            markdown,
+            "## Intro\n\nSome **bold** and [a *link*](https://example.com).\n\n\
+             Second para\n\n- [ ] one\n- [x] two\n\n```rust\nfn main() {}\n```"
+        );
+    }
+
+    #[test]
+    fn test_convert_paste() {
+        let paste = convert_paste(
+            "# Title\n\nSome *text*\n",
+            None,
+            SourceFormat::OrgMode,
+            &RenderConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(paste.source, PasteSource::Text(SourceFormat::Markdown));
+        assert_eq!(paste.markup, "* Title\n\nSome /text/");
+
+        let paste = convert_paste(
+            "bold",
+            Some("<meta charset='utf-8'><b>bold</b>"),
+            SourceFormat::AsciiDoc,
+            &RenderConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(paste.source, PasteSource::Html);
+        assert_eq!(paste.markup, "*bold*");
+    }
+}
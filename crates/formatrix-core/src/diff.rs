@@ -0,0 +1,364 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Structural diff of two documents
+//!
+//! Blocks are compared in a format-neutral form, their HTML rendering, so a
+//! Markdown file and its AsciiDoc conversion compare equal wherever the
+//! conversion kept everything. Heading ids are left out of the comparison,
+//! since formats generate them differently.
+//!
+//! Top-level blocks are matched along their longest common subsequence. A
+//! run of removed blocks next to a run of added ones becomes one changed
+//! hunk, which a side-by-side view shows as a pair.
+//!
+//! [`Document::semantically_equals`] and [`Document::similarity`] compare
+//! whole documents the same way, after normalizing both.
+
+use crate::ast::{Block, Document, Inline, Span};
+use crate::edit::children_mut;
+use crate::html::render_html;
+use crate::text::TextOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How the two sides of a hunk relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HunkKind {
+    Equal,
+    Changed,
+    Added,
+    Removed,
+}
+
+/// One top-level block as a diff shows it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffBlock {
+    /// Index in `Document::content`
+    pub index: usize,
+    /// Block type, as in the AST's serialized form
+    pub kind: String,
+    /// Plain text of the block
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// A run of blocks with the same relation between the two documents
+///
+/// `old` is empty for added hunks and `new` for removed ones; equal hunks
+/// have as many blocks on each side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub kind: HunkKind,
+    pub old: Vec<DiffBlock>,
+    pub new: Vec<DiffBlock>,
+}
+
+/// Compare the top-level blocks of `old` and `new`
+pub fn diff_documents(old: &Document, new: &Document) -> Vec<DiffHunk> {
+    let old_keys: Vec<_> = old.content.iter().map(key).collect();
+    let new_keys: Vec<_> = new.content.iter().map(key).collect();
+
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    for op in align(&old_keys, &new_keys) {
+        let (kind, old_block, new_block) = match op {
+            Op::Equal(i, j) => (HunkKind::Equal, Some(i), Some(j)),
+            Op::Remove(i) => (HunkKind::Removed, Some(i), None),
+            Op::Add(j) => (HunkKind::Added, None, Some(j)),
+        };
+        let old_block = old_block.map(|i| diff_block(i, &old.content[i]));
+        let new_block = new_block.map(|j| diff_block(j, &new.content[j]));
+
+        // Removals and additions next to each other are one change
+        let joins = |last: HunkKind| match kind {
+            HunkKind::Equal => last == HunkKind::Equal,
+            _ => last != HunkKind::Equal,
+        };
+        match hunks.last_mut() {
+            Some(last) if joins(last.kind) => {
+                if last.kind != kind {
+                    last.kind = HunkKind::Changed;
+                }
+                last.old.extend(old_block);
+                last.new.extend(new_block);
+            }
+            _ => hunks.push(DiffHunk {
+                kind,
+                old: old_block.into_iter().collect(),
+                new: new_block.into_iter().collect(),
+            }),
+        }
+    }
+    hunks
+}
+
+impl Document {
+    /// Whether `self` and `other` have the same title and content
+    ///
+    /// Blocks compare as in [`diff_documents`], after
+    /// [normalizing](Document::normalize) both documents and treating soft
+    /// line breaks as spaces, so rewrapped source and the same document in
+    /// another format compare equal. Other metadata, spans and kept source
+    /// are left out.
+    pub fn semantically_equals(&self, other: &Document) -> bool {
+        let (a, b) = (comparable(self), comparable(other));
+        a.meta.title == b.meta.title
+            && a.content.len() == b.content.len()
+            && a.content
+                .iter()
+                .zip(&b.content)
+                .all(|(x, y)| key(x) == key(y))
+    }
+
+    /// How much content `self` and `other` share, from 0 for nothing to 1
+    /// for [semantically equal](Document::semantically_equals) content
+    ///
+    /// Blocks are weighted by their words, plus one so that blocks without
+    /// text count. Blocks that compare equal count in full, and a changed
+    /// run of blocks counts the words its old and new sides have in common.
+    pub fn similarity(&self, other: &Document) -> f32 {
+        let (a, b) = (comparable(self), comparable(other));
+        let weight = |block: &DiffBlock| block.text.split_whitespace().count() + 1;
+
+        let (mut shared, mut total) = (0, 0);
+        for hunk in diff_documents(&a, &b) {
+            total += hunk.old.iter().chain(&hunk.new).map(weight).sum::<usize>();
+            shared += match hunk.kind {
+                HunkKind::Equal => hunk.old.iter().map(weight).sum(),
+                HunkKind::Changed => shared_words(&hunk.old, &hunk.new),
+                HunkKind::Added | HunkKind::Removed => 0,
+            };
+        }
+        if total == 0 {
+            1.0
+        } else {
+            (2 * shared) as f32 / total as f32
+        }
+    }
+}
+
+/// `doc` normalized, with soft breaks as spaces
+fn comparable(doc: &Document) -> Document {
+    fn soften(inlines: &mut [Inline]) {
+        for inline in inlines {
+            if matches!(inline, Inline::SoftBreak) {
+                *inline = Inline::Text {
+                    content: " ".to_string(),
+                };
+            } else if let Some(children) = children_mut(inline) {
+                soften(children);
+            }
+        }
+    }
+    fn soften_blocks(blocks: &mut [Block]) {
+        for block in blocks {
+            for group in block.inline_groups_mut() {
+                soften(group);
+            }
+            for child in block.child_blocks_mut() {
+                soften_blocks(std::slice::from_mut(child));
+            }
+        }
+    }
+
+    let mut doc = doc.clone();
+    soften_blocks(&mut doc.content);
+    doc.normalize();
+    doc
+}
+
+/// Words the texts of `old` and `new` have in common, counting repeats
+fn shared_words(old: &[DiffBlock], new: &[DiffBlock]) -> usize {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in old.iter().flat_map(|b| b.text.split_whitespace()) {
+        *counts.entry(word).or_default() += 1;
+    }
+    let mut shared = 0;
+    for word in new.iter().flat_map(|b| b.text.split_whitespace()) {
+        if let Some(count) = counts.get_mut(word).filter(|count| **count > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+    }
+    shared
+}
+
+/// What two blocks must share to be equal
+fn key(block: &Block) -> String {
+    let mut block = block.clone();
+    if let Block::Heading { id, .. } = &mut block {
+        *id = None;
+    }
+    let mut doc = Document::new(crate::ast::SourceFormat::PlainText);
+    doc.content.push(block);
+    render_html(&doc)
+}
+
+fn diff_block(index: usize, block: &Block) -> DiffBlock {
+    DiffBlock {
+        index,
+        kind: kind(block).to_string(),
+        text: block_text(block),
+        span: block.span(),
+    }
+}
+
+fn kind(block: &Block) -> &'static str {
+    match block {
+        Block::Paragraph { .. } => "paragraph",
+        Block::Heading { .. } => "heading",
+        Block::CodeBlock { .. } => "code_block",
+        Block::BlockQuote { .. } => "block_quote",
+        Block::List { .. } => "list",
+        Block::DefinitionList { .. } => "definition_list",
+        Block::Table { .. } => "table",
+        Block::ThematicBreak { .. } => "thematic_break",
+        Block::MathBlock { .. } => "math_block",
+        Block::Container { .. } => "container",
+        Block::Details { .. } => "details",
+        Block::Figure { .. } => "figure",
+        Block::Raw { .. } => "raw",
+        Block::FootnoteDefinition { .. } => "footnote_definition",
+        Block::TableOfContents { .. } => "table_of_contents",
+        Block::Comment { .. } => "comment",
+    }
+}
+
+/// Text of a block and the blocks inside it, one line per block
+fn block_text(block: &Block) -> String {
+    block.text_content(&TextOptions {
+        block_separator: "\n".to_string(),
+        include_raw: true,
+        ..Default::default()
+    })
+}
+
+enum Op {
+    Equal(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+/// Edit script turning `old` into `new`, removals before additions where
+/// both are needed
+fn align(old: &[String], new: &[String]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lengths[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            ops.push(Op::Remove(prefix + i));
+            i += 1;
+        } else {
+            ops.push(Op::Add(prefix + j));
+            j += 1;
+        }
+    }
+    ops.extend((0..suffix).map(|k| Op::Equal(old.len() - suffix + k, new.len() - suffix + k)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{AsciidocHandler, MarkdownHandler};
+    use crate::traits::{ParseConfig, Parser};
+
+    #[test]
+    fn test_diff_across_formats() {
+        let old = MarkdownHandler::new()
+            .parse(
+                "## Guide\n\nIntro text.\n\nOld step.\n\nGone.\n\n### End\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let new = AsciidocHandler::new()
+            .parse(
+                "== Guide\n\nIntro text.\n\nNew step.\n\n=== End\n\nAdded.\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        let hunks = diff_documents(&old, &new);
+        let kinds: Vec<_> = hunks.iter().map(|h| h.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HunkKind::Equal,
+                HunkKind::Changed,
+                HunkKind::Equal,
+                HunkKind::Added
+            ]
+        );
+
+        let texts = |blocks: &[DiffBlock]| -> Vec<String> {
+            blocks.iter().map(|b| b.text.clone()).collect()
+        };
+        assert_eq!(texts(&hunks[0].new), vec!["Guide", "Intro text."]);
+        assert_eq!(texts(&hunks[1].old), vec!["Old step.", "Gone."]);
+        assert_eq!(texts(&hunks[1].new), vec!["New step."]);
+        assert_eq!(hunks[2].old[0].kind, "heading");
+        assert_eq!((hunks[3].new[0].index, hunks[3].old.len()), (4, 0));
+    }
+
+    #[test]
+    fn test_semantic_comparison() {
+        let markdown = MarkdownHandler::new()
+            .parse(
+                "## Guide\n\nSome intro\ntext, wrapped.\n\n- one\n- two\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let asciidoc = AsciidocHandler::new()
+            .parse(
+                "== Guide\n\nSome intro text, wrapped.\n\n* one\n* two\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let changed = MarkdownHandler::new()
+            .parse(
+                "## Guide\n\nSome intro text, rewrapped.\n\n- one\n- two\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        assert!(markdown.semantically_equals(&asciidoc));
+        assert_eq!(markdown.similarity(&asciidoc), 1.0);
+        assert!(!markdown.semantically_equals(&changed));
+        // Heading and list equal, three of four words kept in the paragraph
+        assert_eq!(markdown.similarity(&changed), 0.8);
+
+        let unrelated = MarkdownHandler::new()
+            .parse("Nothing alike.\n", &ParseConfig::default())
+            .unwrap();
+        assert_eq!(markdown.similarity(&unrelated), 0.0);
+    }
+}
@@ -0,0 +1,1024 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Structural editing of a parsed document
+//!
+//! The operations an editor frontend needs on the AST - inserting,
+//! splitting and merging blocks, toggling inline styles, changing heading
+//! levels, reordering list items and editing tables - done here once instead
+//! of in each frontend.
+//!
+//! Blocks are addressed by index path from `Document::content`, numbered
+//! like [`Block::child_blocks`], the same paths search hits and extracted
+//! links carry. Offsets within a block count characters of its inline
+//! content; a line break or an atom such as an image or footnote reference
+//! counts as one.
+//!
+//! Table rows are numbered as rendered: the header row, the body rows, then
+//! the footer row. Columns are numbered by position, so a cell spanning two
+//! columns covers two numbers; cells spanning rows are taken to occupy only
+//! their first row.
+//!
+//! Every operation returns what it [`Affected`]: the paths of the changed
+//! blocks after the edit and the source range they covered before it, so a
+//! frontend knows what to re-render. Spans recorded by the parser still
+//! describe the source before the edit.
+
+use crate::ast::{Block, ColumnAlignment, ColumnSpec, Document, Inline, Span, TableCell, TableRow};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EditError {
+    #[error("No block at {0:?}")]
+    NoSuchBlock(Vec<usize>),
+
+    #[error("Block at {0:?} does not hold blocks")]
+    NotAContainer(Vec<usize>),
+
+    #[error("Offset {offset} is past the end ({len})")]
+    OutOfRange { offset: usize, len: usize },
+
+    #[error("Invalid heading level: {0}")]
+    InvalidLevel(u8),
+
+    #[error("Unsupported edit: {0}")]
+    Unsupported(String),
+}
+
+pub type EditResult<T> = Result<T, EditError>;
+
+/// Blocks touched by an edit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Affected {
+    /// Paths of the changed and new blocks, after the edit
+    pub paths: Vec<Vec<usize>>,
+    /// Source range the changed blocks covered, when the parser recorded it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// Inline styles that can be toggled over a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InlineStyle {
+    Emphasis,
+    Strong,
+    Strikethrough,
+    Underline,
+    Superscript,
+    Subscript,
+    SmallCaps,
+    Highlight,
+}
+
+impl InlineStyle {
+    fn wrap(self, content: Vec<Inline>) -> Inline {
+        match self {
+            InlineStyle::Emphasis => Inline::Emphasis { content },
+            InlineStyle::Strong => Inline::Strong { content },
+            InlineStyle::Strikethrough => Inline::Strikethrough { content },
+            InlineStyle::Underline => Inline::Underline { content },
+            InlineStyle::Superscript => Inline::Superscript { content },
+            InlineStyle::Subscript => Inline::Subscript { content },
+            InlineStyle::SmallCaps => Inline::SmallCaps { content },
+            InlineStyle::Highlight => Inline::Highlight { content },
+        }
+    }
+
+    fn of(inline: &Inline) -> Option<Self> {
+        Some(match inline {
+            Inline::Emphasis { .. } => InlineStyle::Emphasis,
+            Inline::Strong { .. } => InlineStyle::Strong,
+            Inline::Strikethrough { .. } => InlineStyle::Strikethrough,
+            Inline::Underline { .. } => InlineStyle::Underline,
+            Inline::Superscript { .. } => InlineStyle::Superscript,
+            Inline::Subscript { .. } => InlineStyle::Subscript,
+            Inline::SmallCaps { .. } => InlineStyle::SmallCaps,
+            Inline::Highlight { .. } => InlineStyle::Highlight,
+            _ => return None,
+        })
+    }
+}
+
+/// The block at `path`
+pub fn block_at<'a>(doc: &'a Document, path: &[usize]) -> Option<&'a Block> {
+    let (first, rest) = path.split_first()?;
+    rest.iter().try_fold(doc.content.get(*first)?, |block, &i| {
+        block.child_blocks().into_iter().nth(i)
+    })
+}
+
+/// Insert `block` so that it ends up at `path`
+///
+/// The last index may be one past the end of its sequence, to append.
+pub fn insert_block_at(doc: &mut Document, path: &[usize], block: Block) -> EditResult<Affected> {
+    let (blocks, index) = sequence_mut(doc, path)?;
+    if index > blocks.len() {
+        return Err(EditError::NoSuchBlock(path.to_vec()));
+    }
+    // An empty range where the displaced block started
+    let span = blocks
+        .get(index)
+        .and_then(Block::span)
+        .map(|s| Span { end: s.start, ..s });
+    blocks.insert(index, block);
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Split a paragraph, heading or code block in two at `offset`
+///
+/// The second half of a heading becomes a paragraph, as when pressing Enter
+/// in a heading.
+pub fn split_block(doc: &mut Document, path: &[usize], offset: usize) -> EditResult<Affected> {
+    let (blocks, index) = sequence_mut(doc, path)?;
+    let block = blocks
+        .get_mut(index)
+        .ok_or_else(|| EditError::NoSuchBlock(path.to_vec()))?;
+    let old_span = block.span();
+
+    let second = match block {
+        Block::Paragraph { content, .. } | Block::Heading { content, .. } => {
+            check_offset(offset, inlines_len(content))?;
+            let (first, second) = split_inlines(std::mem::take(content), offset);
+            *content = first;
+            Block::Paragraph {
+                content: second,
+                span: None,
+            }
+        }
+        Block::CodeBlock {
+            language,
+            content,
+            line_numbers,
+            ..
+        } => {
+            check_offset(offset, content.chars().count())?;
+            let rest = content.split_off(byte_offset(content, offset));
+            Block::CodeBlock {
+                language: language.clone(),
+                content: rest,
+                line_numbers: *line_numbers,
+                highlight_lines: Vec::new(),
+                span: None,
+            }
+        }
+        _ => {
+            return Err(EditError::Unsupported(
+                "only paragraphs, headings and code blocks can be split".to_string(),
+            ))
+        }
+    };
+    blocks.insert(index + 1, second);
+    Ok(Affected {
+        paths: vec![path.to_vec(), sibling(path, 1)],
+        span: old_span,
+    })
+}
+
+/// Merge the block at `path` with the block after it
+///
+/// Text blocks join their inline content and keep the first block's kind;
+/// code blocks join by line, and lists of one kind and block quotes join
+/// their contents.
+pub fn merge_blocks(doc: &mut Document, path: &[usize]) -> EditResult<Affected> {
+    let (blocks, index) = sequence_mut(doc, path)?;
+    if index + 1 >= blocks.len() {
+        return Err(EditError::NoSuchBlock(sibling(path, 1)));
+    }
+    if !mergeable(&blocks[index], &blocks[index + 1]) {
+        return Err(EditError::Unsupported(
+            "these blocks cannot be merged".to_string(),
+        ));
+    }
+
+    let next = blocks.remove(index + 1);
+    let span = union(blocks[index].span(), next.span());
+    match (&mut blocks[index], next) {
+        (
+            Block::Paragraph { content, .. } | Block::Heading { content, .. },
+            Block::Paragraph { content: more, .. } | Block::Heading { content: more, .. },
+        ) => {
+            content.extend(more);
+            *content = normalize(std::mem::take(content));
+        }
+        (Block::CodeBlock { content, .. }, Block::CodeBlock { content: more, .. }) => {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&more);
+        }
+        (Block::List { items, .. }, Block::List { items: more, .. }) => items.extend(more),
+        (Block::BlockQuote { content, .. }, Block::BlockQuote { content: more, .. }) => {
+            content.extend(more)
+        }
+        _ => unreachable!("checked by mergeable"),
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Toggle `style` over `range` in a paragraph or heading
+///
+/// If all of the range already has the style it is removed, otherwise the
+/// whole range gets it.
+pub fn toggle_inline_style(
+    doc: &mut Document,
+    path: &[usize],
+    range: Range<usize>,
+    style: InlineStyle,
+) -> EditResult<Affected> {
+    let block = block_mut(doc, path)?;
+    let span = block.span();
+    let (Block::Paragraph { content, .. } | Block::Heading { content, .. }) = block else {
+        return Err(EditError::Unsupported(
+            "inline styles need a paragraph or heading".to_string(),
+        ));
+    };
+    let len = inlines_len(content);
+    check_offset(range.end, len)?;
+    check_offset(range.start, range.end)?;
+
+    let (mut inlines, rest) = split_inlines(std::mem::take(content), range.start);
+    let (middle, after) = split_inlines(rest, range.end - range.start);
+    let styled = has_style(&middle, style);
+    let middle = remove_style(middle, style);
+    if styled {
+        inlines.extend(middle);
+    } else {
+        inlines.push(style.wrap(middle));
+    }
+    inlines.extend(after);
+    *content = normalize(inlines);
+
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Set the level of a heading, or turn a paragraph into a heading
+///
+/// Level 0 turns a heading back into a paragraph.
+pub fn change_heading_level(doc: &mut Document, path: &[usize], level: u8) -> EditResult<Affected> {
+    if level > 6 {
+        return Err(EditError::InvalidLevel(level));
+    }
+    let block = block_mut(doc, path)?;
+    let span = block.span();
+    match block {
+        Block::Heading { level: current, .. } if level > 0 => *current = level,
+        Block::Heading { content, span, .. } => {
+            *block = Block::Paragraph {
+                content: std::mem::take(content),
+                span: *span,
+            }
+        }
+        Block::Paragraph { content, span } if level > 0 => {
+            *block = Block::Heading {
+                level,
+                content: std::mem::take(content),
+                id: None,
+                classes: Vec::new(),
+                span: *span,
+            }
+        }
+        Block::Paragraph { .. } => {}
+        _ => {
+            return Err(EditError::Unsupported(
+                "only paragraphs and headings have a level".to_string(),
+            ))
+        }
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Move item `from` of the list at `path` so that it becomes item `to`
+pub fn move_list_item(
+    doc: &mut Document,
+    path: &[usize],
+    from: usize,
+    to: usize,
+) -> EditResult<Affected> {
+    let block = block_mut(doc, path)?;
+    let span = block.span();
+    let Block::List { items, .. } = block else {
+        return Err(EditError::Unsupported(format!(
+            "block at {:?} is not a list",
+            path
+        )));
+    };
+    check_offset(from.max(to), items.len().saturating_sub(1))?;
+    let item = items.remove(from);
+    items.insert(to, item);
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Insert an empty table of `rows` by `columns` so that it ends up at
+/// `path`; with `header`, the first of the rows is a header row
+pub fn insert_table(
+    doc: &mut Document,
+    path: &[usize],
+    rows: usize,
+    columns: usize,
+    header: bool,
+) -> EditResult<Affected> {
+    if rows == 0 || columns == 0 {
+        return Err(EditError::Unsupported(
+            "a table needs at least one row and one column".to_string(),
+        ));
+    }
+    let mut body: Vec<_> = (0..rows).map(|_| empty_row(columns)).collect();
+    let header = header.then(|| body.remove(0));
+    let table = Block::Table {
+        caption: None,
+        columns: vec![
+            ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            };
+            columns
+        ],
+        header,
+        body,
+        footer: None,
+        span: None,
+    };
+    insert_block_at(doc, path, table)
+}
+
+/// Insert an empty body row into the table at `path`, so that it becomes
+/// row `at` or as near to it as the header and footer allow
+pub fn insert_table_row(doc: &mut Document, path: &[usize], at: usize) -> EditResult<Affected> {
+    let (table, span) = table_mut(doc, path)?;
+    check_offset(at, table.rows())?;
+    let index = at
+        .saturating_sub(table.header.is_some() as usize)
+        .min(table.body.len());
+    let width = table.width();
+    table.body.insert(index, empty_row(width));
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Remove row `index` of the table at `path`
+pub fn remove_table_row(doc: &mut Document, path: &[usize], index: usize) -> EditResult<Affected> {
+    let (table, span) = table_mut(doc, path)?;
+    let rows = table.rows();
+    check_offset(index, rows.saturating_sub(1))?;
+    if rows == 1 {
+        return Err(EditError::Unsupported(
+            "a table needs at least one row".to_string(),
+        ));
+    }
+    let first_body = table.header.is_some() as usize;
+    if index < first_body {
+        *table.header = None;
+    } else if index - first_body < table.body.len() {
+        table.body.remove(index - first_body);
+    } else {
+        *table.footer = None;
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Insert an empty column into the table at `path`, so that it becomes
+/// column `at`
+///
+/// A cell spanning across `at` is widened rather than split.
+pub fn insert_table_column(doc: &mut Document, path: &[usize], at: usize) -> EditResult<Affected> {
+    let (mut table, span) = table_mut(doc, path)?;
+    let width = table.width();
+    check_offset(at, width)?;
+    table.fill_columns(width);
+    table.columns.insert(
+        at,
+        ColumnSpec {
+            alignment: ColumnAlignment::Default,
+            width: None,
+        },
+    );
+    for row in table.rows_mut() {
+        match cell_at(row, at) {
+            Some((i, start)) if start < at => row.cells[i].colspan += 1,
+            Some((i, _)) => row.cells.insert(i, empty_cell()),
+            None => row.cells.push(empty_cell()),
+        }
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Remove column `index` of the table at `path`
+///
+/// A cell spanning more than that column is narrowed rather than removed.
+pub fn remove_table_column(
+    doc: &mut Document,
+    path: &[usize],
+    index: usize,
+) -> EditResult<Affected> {
+    let (mut table, span) = table_mut(doc, path)?;
+    let width = table.width();
+    check_offset(index, width.saturating_sub(1))?;
+    if width == 1 {
+        return Err(EditError::Unsupported(
+            "a table needs at least one column".to_string(),
+        ));
+    }
+    if index < table.columns.len() {
+        table.columns.remove(index);
+    }
+    for row in table.rows_mut() {
+        if let Some((i, _)) = cell_at(row, index) {
+            if row.cells[i].colspan > 1 {
+                row.cells[i].colspan -= 1;
+            } else {
+                row.cells.remove(i);
+            }
+        }
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Make the first row of the table at `path` a header row, or the header
+/// row an ordinary first row
+pub fn toggle_table_header(doc: &mut Document, path: &[usize]) -> EditResult<Affected> {
+    let (table, span) = table_mut(doc, path)?;
+    if let Some(header) = table.header.take() {
+        table.body.insert(0, header);
+    } else if !table.body.is_empty() {
+        *table.header = Some(table.body.remove(0));
+    } else {
+        return Err(EditError::Unsupported(
+            "the table has no row to make a header".to_string(),
+        ));
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// Set the alignment of column `column` of the table at `path`
+///
+/// Cells in the column that had an alignment of their own lose it.
+pub fn set_column_alignment(
+    doc: &mut Document,
+    path: &[usize],
+    column: usize,
+    alignment: ColumnAlignment,
+) -> EditResult<Affected> {
+    let (mut table, span) = table_mut(doc, path)?;
+    let width = table.width();
+    check_offset(column, width.saturating_sub(1))?;
+    table.fill_columns(width);
+    table.columns[column].alignment = alignment;
+    for row in table.rows_mut() {
+        if let Some((i, start)) = cell_at(row, column) {
+            if start == column {
+                row.cells[i].alignment = None;
+            }
+        }
+    }
+    Ok(Affected {
+        paths: vec![path.to_vec()],
+        span,
+    })
+}
+
+/// The parts of a table being edited
+struct TableMut<'a> {
+    columns: &'a mut Vec<ColumnSpec>,
+    header: &'a mut Option<TableRow>,
+    body: &'a mut Vec<TableRow>,
+    footer: &'a mut Option<TableRow>,
+}
+
+impl TableMut<'_> {
+    fn rows(&self) -> usize {
+        self.header.is_some() as usize + self.body.len() + self.footer.is_some() as usize
+    }
+
+    fn rows_mut(&mut self) -> impl Iterator<Item = &mut TableRow> {
+        self.header
+            .iter_mut()
+            .chain(self.body.iter_mut())
+            .chain(self.footer.iter_mut())
+    }
+
+    /// Number of columns: of the widest row, or of the column specs if
+    /// there are more of them
+    fn width(&self) -> usize {
+        let rows = self
+            .header
+            .iter()
+            .chain(self.body.iter())
+            .chain(self.footer.iter());
+        rows.map(|row| row.cells.iter().map(|c| c.colspan.max(1) as usize).sum())
+            .max()
+            .unwrap_or(0)
+            .max(self.columns.len())
+    }
+
+    /// Give every column a spec, as parsers do not always record them
+    fn fill_columns(&mut self, width: usize) {
+        self.columns.resize(
+            width,
+            ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            },
+        );
+    }
+}
+
+fn table_mut<'a>(
+    doc: &'a mut Document,
+    path: &[usize],
+) -> EditResult<(TableMut<'a>, Option<Span>)> {
+    let block = block_mut(doc, path)?;
+    let span = block.span();
+    let Block::Table {
+        columns,
+        header,
+        body,
+        footer,
+        ..
+    } = block
+    else {
+        return Err(EditError::Unsupported(format!(
+            "block at {:?} is not a table",
+            path
+        )));
+    };
+    let table = TableMut {
+        columns,
+        header,
+        body,
+        footer,
+    };
+    Ok((table, span))
+}
+
+/// Index of the cell of `row` covering column `column`, and the column the
+/// cell starts at
+fn cell_at(row: &TableRow, column: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for (i, cell) in row.cells.iter().enumerate() {
+        let span = cell.colspan.max(1) as usize;
+        if column < start + span {
+            return Some((i, start));
+        }
+        start += span;
+    }
+    None
+}
+
+fn empty_cell() -> TableCell {
+    TableCell {
+        content: vec![Block::Paragraph {
+            content: Vec::new(),
+            span: None,
+        }],
+        colspan: 1,
+        rowspan: 1,
+        alignment: None,
+    }
+}
+
+fn empty_row(columns: usize) -> TableRow {
+    TableRow {
+        cells: (0..columns).map(|_| empty_cell()).collect(),
+    }
+}
+
+fn block_mut<'a>(doc: &'a mut Document, path: &[usize]) -> EditResult<&'a mut Block> {
+    let missing = || EditError::NoSuchBlock(path.to_vec());
+    let (first, rest) = path.split_first().ok_or_else(missing)?;
+    let mut block = doc.content.get_mut(*first).ok_or_else(missing)?;
+    for &i in rest {
+        block = block
+            .child_blocks_mut()
+            .into_iter()
+            .nth(i)
+            .ok_or_else(missing)?;
+    }
+    Ok(block)
+}
+
+/// The block sequence holding `path`, and the index into it
+///
+/// Blocks such as lists and tables hold several sequences, numbered as one
+/// by [`Block::child_blocks`]; an index one past the end falls into the
+/// last of them.
+fn sequence_mut<'a>(
+    doc: &'a mut Document,
+    path: &[usize],
+) -> EditResult<(&'a mut Vec<Block>, usize)> {
+    let Some((&index, parent)) = path.split_last() else {
+        return Err(EditError::NoSuchBlock(Vec::new()));
+    };
+    if parent.is_empty() {
+        return Ok((&mut doc.content, index));
+    }
+
+    let sequences = child_sequences_mut(block_mut(doc, parent)?);
+    let Some(last) = sequences.len().checked_sub(1) else {
+        return Err(EditError::NotAContainer(parent.to_vec()));
+    };
+    let mut index = index;
+    for (n, blocks) in sequences.into_iter().enumerate() {
+        if index < blocks.len() || (n == last && index == blocks.len()) {
+            return Ok((blocks, index));
+        }
+        index -= blocks.len();
+    }
+    Err(EditError::NoSuchBlock(path.to_vec()))
+}
+
+/// The block sequences behind [`Block::child_blocks_mut`], in order
+fn child_sequences_mut(block: &mut Block) -> Vec<&mut Vec<Block>> {
+    match block {
+        Block::BlockQuote { content, .. }
+        | Block::Container { content, .. }
+        | Block::Details { content, .. }
+        | Block::Figure { content, .. }
+        | Block::FootnoteDefinition { content, .. } => vec![content],
+        Block::List { items, .. } => items.iter_mut().map(|i| &mut i.content).collect(),
+        Block::DefinitionList { items, .. } => {
+            items.iter_mut().flat_map(|i| &mut i.definitions).collect()
+        }
+        Block::Table {
+            header,
+            body,
+            footer,
+            ..
+        } => header
+            .iter_mut()
+            .chain(body)
+            .chain(footer)
+            .flat_map(|row| &mut row.cells)
+            .map(|cell| &mut cell.content)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn mergeable(first: &Block, second: &Block) -> bool {
+    match (first, second) {
+        (
+            Block::Paragraph { .. } | Block::Heading { .. },
+            Block::Paragraph { .. } | Block::Heading { .. },
+        )
+        | (Block::CodeBlock { .. }, Block::CodeBlock { .. })
+        | (Block::BlockQuote { .. }, Block::BlockQuote { .. }) => true,
+        (Block::List { kind, .. }, Block::List { kind: other, .. }) => kind == other,
+        _ => false,
+    }
+}
+
+fn check_offset(offset: usize, len: usize) -> EditResult<()> {
+    if offset > len {
+        return Err(EditError::OutOfRange { offset, len });
+    }
+    Ok(())
+}
+
+/// `path` with its last index moved by `delta`
+fn sibling(path: &[usize], delta: usize) -> Vec<usize> {
+    let mut path = path.to_vec();
+    if let Some(last) = path.last_mut() {
+        *last += delta;
+    }
+    path
+}
+
+fn union(a: Option<Span>, b: Option<Span>) -> Option<Span> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let first = if a.start <= b.start { a } else { b };
+            Some(Span {
+                end: a.end.max(b.end),
+                ..first
+            })
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+/// Inline content of elements that wrap other inlines
+fn children(inline: &Inline) -> Option<&Vec<Inline>> {
+    match inline {
+        Inline::Emphasis { content }
+        | Inline::Strong { content }
+        | Inline::Strikethrough { content }
+        | Inline::Underline { content }
+        | Inline::Superscript { content }
+        | Inline::Subscript { content }
+        | Inline::SmallCaps { content }
+        | Inline::Link { content, .. }
+        | Inline::Span { content, .. }
+        | Inline::Quoted { content, .. }
+        | Inline::Highlight { content } => Some(content),
+        _ => None,
+    }
+}
+
+pub(crate) fn children_mut(inline: &mut Inline) -> Option<&mut Vec<Inline>> {
+    match inline {
+        Inline::Emphasis { content }
+        | Inline::Strong { content }
+        | Inline::Strikethrough { content }
+        | Inline::Underline { content }
+        | Inline::Superscript { content }
+        | Inline::Subscript { content }
+        | Inline::SmallCaps { content }
+        | Inline::Link { content, .. }
+        | Inline::Span { content, .. }
+        | Inline::Quoted { content, .. }
+        | Inline::Highlight { content } => Some(content),
+        _ => None,
+    }
+}
+
+pub(crate) fn inline_len(inline: &Inline) -> usize {
+    match inline {
+        Inline::Text { content } | Inline::Code { content, .. } | Inline::Keyboard { content } => {
+            content.chars().count()
+        }
+        other => children(other).map_or(1, |c| inlines_len(c)),
+    }
+}
+
+fn inlines_len(inlines: &[Inline]) -> usize {
+    inlines.iter().map(inline_len).sum()
+}
+
+/// Split `inlines` at character `offset`, splitting elements that straddle it
+fn split_inlines(inlines: Vec<Inline>, mut offset: usize) -> (Vec<Inline>, Vec<Inline>) {
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for mut inline in inlines {
+        let len = inline_len(&inline);
+        if offset == 0 {
+            right.push(inline);
+            continue;
+        }
+        if len <= offset {
+            offset -= len;
+            left.push(inline);
+            continue;
+        }
+
+        // Only text and wrapping elements are longer than one character
+        match &mut inline {
+            Inline::Text { content }
+            | Inline::Code { content, .. }
+            | Inline::Keyboard { content } => {
+                let rest = content.split_off(byte_offset(content, offset));
+                let mut second = inline.clone();
+                if let Inline::Text { content }
+                | Inline::Code { content, .. }
+                | Inline::Keyboard { content } = &mut second
+                {
+                    *content = rest;
+                }
+                left.push(inline);
+                right.push(second);
+            }
+            other => {
+                let content = children_mut(other).expect("only wrappers are split");
+                let (first, rest) = split_inlines(std::mem::take(content), offset);
+                *content = rest;
+                let mut head = inline.clone();
+                *children_mut(&mut head).expect("clone of a wrapper") = first;
+                left.push(head);
+                right.push(inline);
+            }
+        }
+        offset = 0;
+    }
+    (left, right)
+}
+
+/// Whether every non-empty inline in `inlines` has `style`
+fn has_style(inlines: &[Inline], style: InlineStyle) -> bool {
+    inlines.iter().all(|inline| {
+        inline_len(inline) == 0
+            || InlineStyle::of(inline) == Some(style)
+            || children(inline).is_some_and(|c| has_style(c, style))
+    })
+}
+
+/// Unwrap every element of `style`, at any depth
+fn remove_style(inlines: Vec<Inline>, style: InlineStyle) -> Vec<Inline> {
+    let mut out = Vec::new();
+    for mut inline in inlines {
+        let Some(content) = children_mut(&mut inline) else {
+            out.push(inline);
+            continue;
+        };
+        let content_without = remove_style(std::mem::take(content), style);
+        if InlineStyle::of(&inline) == Some(style) {
+            out.extend(content_without);
+        } else {
+            *children_mut(&mut inline).expect("checked above") = content_without;
+            out.push(inline);
+        }
+    }
+    out
+}
+
+/// Join adjacent text and adjacent elements of one style, and drop empty ones
+fn normalize(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut out: Vec<Inline> = Vec::new();
+    for mut inline in inlines {
+        if let Some(content) = children_mut(&mut inline) {
+            *content = normalize(std::mem::take(content));
+            if content.is_empty() && InlineStyle::of(&inline).is_some() {
+                continue;
+            }
+        }
+        if let (Some(Inline::Text { content: prev }), Inline::Text { content }) =
+            (out.last_mut(), &inline)
+        {
+            prev.push_str(content);
+            continue;
+        }
+        let style = InlineStyle::of(&inline);
+        if style.is_some() && out.last().and_then(InlineStyle::of) == style {
+            let prev = out
+                .last_mut()
+                .and_then(children_mut)
+                .expect("checked above");
+            prev.extend(
+                children_mut(&mut inline)
+                    .map(std::mem::take)
+                    .unwrap_or_default(),
+            );
+            *prev = normalize(std::mem::take(prev));
+            continue;
+        }
+        if !matches!(&inline, Inline::Text { content } if content.is_empty()) {
+            out.push(inline);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+
+    fn parse(input: &str) -> Document {
+        MarkdownHandler::new()
+            .parse(input, &ParseConfig::default())
+            .unwrap()
+    }
+
+    fn render(doc: &Document) -> String {
+        MarkdownHandler::new()
+            .render(doc, &RenderConfig::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let mut doc = parse("# Title here\n\nSome *very long* text.");
+        let affected = split_block(&mut doc, &[1], 10).unwrap();
+        assert_eq!(affected.paths, vec![vec![1], vec![2]]);
+        assert_eq!(render(&doc), "# Title here\n\nSome *very *\n\n*long* text.");
+
+        merge_blocks(&mut doc, &[1]).unwrap();
+        assert_eq!(render(&doc), "# Title here\n\nSome *very long* text.");
+
+        split_block(&mut doc, &[0], 5).unwrap();
+        assert!(matches!(doc.content[1], Block::Paragraph { .. }));
+        assert_eq!(
+            split_block(&mut doc, &[0], 99),
+            Err(EditError::OutOfRange { offset: 99, len: 5 })
+        );
+        assert!(merge_blocks(&mut doc, &[3]).is_err());
+    }
+
+    #[test]
+    fn test_toggle_inline_style() {
+        let mut doc = parse("Some plain text");
+        toggle_inline_style(&mut doc, &[0], 5..10, InlineStyle::Strong).unwrap();
+        assert_eq!(render(&doc), "Some **plain** text");
+        toggle_inline_style(&mut doc, &[0], 0..10, InlineStyle::Strong).unwrap();
+        assert_eq!(render(&doc), "**Some plain** text");
+        toggle_inline_style(&mut doc, &[0], 4..10, InlineStyle::Strong).unwrap();
+        assert_eq!(render(&doc), "**Some** plain text");
+        toggle_inline_style(&mut doc, &[0], 0..4, InlineStyle::Strong).unwrap();
+        assert_eq!(render(&doc), "Some plain text");
+    }
+
+    #[test]
+    fn test_heading_level_and_insert() {
+        let mut doc = parse("Intro\n\n## Part\n");
+        change_heading_level(&mut doc, &[0], 1).unwrap();
+        change_heading_level(&mut doc, &[1], 0).unwrap();
+        assert_eq!(render(&doc), "# Intro\n\nPart");
+        assert_eq!(
+            change_heading_level(&mut doc, &[0], 7),
+            Err(EditError::InvalidLevel(7))
+        );
+
+        let rule = Block::ThematicBreak { span: None };
+        insert_block_at(&mut doc, &[2], rule.clone()).unwrap();
+        assert!(matches!(
+            block_at(&doc, &[2]),
+            Some(Block::ThematicBreak { .. })
+        ));
+        assert!(insert_block_at(&mut doc, &[5], rule.clone()).is_err());
+        assert_eq!(
+            insert_block_at(&mut doc, &[0, 0], rule),
+            Err(EditError::NotAContainer(vec![0]))
+        );
+    }
+
+    #[test]
+    fn test_list_items() {
+        let mut doc = parse("- one\n- two\n- three\n\n> quote\n");
+        move_list_item(&mut doc, &[0], 2, 0).unwrap();
+        assert!(render(&doc).starts_with("- three\n- one\n- two\n"));
+        assert!(move_list_item(&mut doc, &[0], 0, 3).is_err());
+
+        // Paths inside a list number the blocks of every item in turn
+        split_block(&mut doc, &[0, 1], 1).unwrap();
+        assert_eq!(render(&doc), "- three\n- o\n\n  ne\n- two\n\n> quote");
+        insert_block_at(
+            &mut doc,
+            &[1, 1],
+            Block::Paragraph {
+                content: vec![Inline::Text {
+                    content: "more".to_string(),
+                }],
+                span: None,
+            },
+        )
+        .unwrap();
+        assert!(render(&doc).ends_with("> quote\n>\n> more"));
+    }
+
+    #[test]
+    fn test_table_editing() {
+        let mut doc = parse("Intro\n");
+        insert_table(&mut doc, &[1], 2, 2, true).unwrap();
+        assert_eq!(render(&doc), "Intro\n\n|  |  |\n| --- | --- |\n|  |  |");
+
+        let mut doc = parse("| a | b |\n|---|--:|\n| 1 | 2 |\n");
+        insert_table_row(&mut doc, &[0], 2).unwrap();
+        insert_table_column(&mut doc, &[0], 1).unwrap();
+        set_column_alignment(&mut doc, &[0], 0, ColumnAlignment::Center).unwrap();
+        assert_eq!(
+            render(&doc),
+            "| a |  | b |\n| :-: | --- | --: |\n| 1 |  | 2 |\n|  |  |  |"
+        );
+
+        remove_table_column(&mut doc, &[0], 0).unwrap();
+        remove_table_row(&mut doc, &[0], 1).unwrap();
+        assert_eq!(render(&doc), "|  | b |\n| --- | --: |\n|  |  |");
+        toggle_table_header(&mut doc, &[0]).unwrap();
+        assert!(matches!(
+            &doc.content[0],
+            Block::Table { header: None, body, .. } if body.len() == 2
+        ));
+
+        assert_eq!(
+            remove_table_column(&mut doc, &[0], 2),
+            Err(EditError::OutOfRange { offset: 2, len: 1 })
+        );
+        assert!(insert_table_row(&mut parse("Text"), &[0], 0).is_err());
+    }
+}
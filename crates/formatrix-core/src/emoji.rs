@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Emoji shortcodes
+//!
+//! With [`ParseConfig::emoji_shortcodes`](crate::traits::ParseConfig) set,
+//! every parser reads `:smile:`-style shortcodes for known emoji into
+//! [`Inline::Emoji`] nodes; unknown names, and shortcodes in code, are left
+//! as text. Djot marks its `:symbols:` itself, so those naming an emoji are
+//! read as one whatever the setting. Renderers write the nodes in the
+//! [`EmojiStyle`] of their config, so a document reads the same whatever
+//! the target.
+
+use crate::ast::{Block, Inline};
+use crate::edit::children_mut;
+use crate::traits::EmojiStyle;
+
+/// Replace shortcodes for known emoji in the text of `blocks` with
+/// [`Inline::Emoji`] nodes
+pub fn parse_shortcodes(blocks: &mut [Block]) {
+    for block in blocks {
+        for group in block.inline_groups_mut() {
+            parse_inlines(group);
+        }
+        for child in block.child_blocks_mut() {
+            parse_shortcodes(std::slice::from_mut(child));
+        }
+    }
+}
+
+fn parse_inlines(inlines: &mut Vec<Inline>) {
+    let mut parsed = Vec::with_capacity(inlines.len());
+    for mut inline in inlines.drain(..) {
+        if let Inline::Text { content } = &inline {
+            parsed.extend(split_shortcodes(content));
+            continue;
+        }
+        if let Some(children) = children_mut(&mut inline) {
+            parse_inlines(children);
+        }
+        if let Inline::Citation { prefix, suffix, .. } = &mut inline {
+            for group in prefix.iter_mut().chain(suffix) {
+                parse_inlines(group);
+            }
+        }
+        parsed.push(inline);
+    }
+    *inlines = parsed;
+}
+
+/// `text` as text and emoji nodes
+fn split_shortcodes(text: &str) -> Vec<Inline> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-');
+
+    let mut out = Vec::new();
+    // Start of the text not yet taken, and of the search for the next colon
+    let mut plain = 0;
+    let mut search = 0;
+    while let Some(open) = text[search..].find(':').map(|i| search + i) {
+        let name_start = open + 1;
+        let close = text[name_start..]
+            .find(|c: char| !is_name_char(c))
+            .map(|i| name_start + i)
+            .filter(|&close| close > name_start && text[close..].starts_with(':'));
+        match close {
+            Some(close) if emojis::get_by_shortcode(&text[name_start..close]).is_some() => {
+                if plain < open {
+                    out.push(Inline::Text {
+                        content: text[plain..open].to_string(),
+                    });
+                }
+                out.push(Inline::Emoji {
+                    shortcode: text[name_start..close].to_string(),
+                });
+                plain = close + 1;
+                search = close + 1;
+            }
+            _ => search = name_start,
+        }
+    }
+    if plain < text.len() || out.is_empty() {
+        out.push(Inline::Text {
+            content: text[plain..].to_string(),
+        });
+    }
+    out
+}
+
+/// Whether `blocks` hold any [`Inline::Emoji`] nodes
+pub fn has_emoji(blocks: &[Block]) -> bool {
+    fn in_inline(inline: &Inline) -> bool {
+        matches!(inline, Inline::Emoji { .. }) || inline.children().into_iter().any(in_inline)
+    }
+
+    blocks.iter().any(|block| {
+        block.inline_groups().into_iter().flatten().any(in_inline)
+            || block
+                .child_blocks()
+                .into_iter()
+                .any(|child| has_emoji(std::slice::from_ref(child)))
+    })
+}
+
+/// Replace the [`Inline::Emoji`] nodes in `blocks` with text or images in
+/// `style`
+pub fn apply_emoji_style(blocks: &mut [Block], style: &EmojiStyle) {
+    fn visit(inline: &mut Inline, style: &EmojiStyle) {
+        if let Inline::Emoji { shortcode } = inline {
+            *inline = render_emoji(shortcode, style);
+            return;
+        }
+        for child in inline.children_mut() {
+            visit(child, style);
+        }
+    }
+
+    for block in blocks {
+        for group in block.inline_groups_mut() {
+            for inline in group {
+                visit(inline, style);
+            }
+        }
+        for child in block.child_blocks_mut() {
+            apply_emoji_style(std::slice::from_mut(child), style);
+        }
+    }
+}
+
+/// The emoji for `shortcode` in `style`; a shortcode with no known emoji
+/// stays a shortcode
+pub fn render_emoji(shortcode: &str, style: &EmojiStyle) -> Inline {
+    match (style, emojis::get_by_shortcode(shortcode)) {
+        (EmojiStyle::Unicode, Some(emoji)) => Inline::Text {
+            content: emoji.as_str().to_string(),
+        },
+        (EmojiStyle::Image(template), Some(emoji)) => {
+            let codepoints: Vec<String> = emoji
+                .as_str()
+                .chars()
+                .map(|c| format!("{:x}", c as u32))
+                .collect();
+            Inline::Image {
+                url: template
+                    .replace("{shortcode}", shortcode)
+                    .replace("{codepoints}", &codepoints.join("-")),
+                alt: emoji.as_str().to_string(),
+                title: None,
+                width: None,
+                height: None,
+            }
+        }
+        _ => Inline::Text {
+            content: format!(":{}:", shortcode),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{AsciidocHandler, DjotHandler, MarkdownHandler};
+    use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+
+    fn config() -> ParseConfig {
+        ParseConfig {
+            emoji_shortcodes: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_shortcodes() {
+        let source = "Hi :smile: at 10:30:45, :not_an_emoji: and `:smile:`:+1:\n";
+        let doc = MarkdownHandler::new().parse(source, &config()).unwrap();
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(&content[1], Inline::Emoji { shortcode } if shortcode == "smile"));
+        assert!(
+            matches!(&content[2], Inline::Text { content } if content == " at 10:30:45, :not_an_emoji: and ")
+        );
+        assert!(matches!(content[3], Inline::Code { .. }));
+        assert!(matches!(&content[4], Inline::Emoji { shortcode } if shortcode == "+1"));
+
+        let plain = MarkdownHandler::new()
+            .parse(source, &ParseConfig::default())
+            .unwrap();
+        assert!(!has_emoji(&plain.content));
+
+        // Djot reads shortcodes itself
+        let doc = DjotHandler::new().parse("Yes :tada:\n", &config()).unwrap();
+        assert!(has_emoji(&doc.content));
+    }
+
+    #[test]
+    fn test_emoji_style() {
+        let doc = MarkdownHandler::new()
+            .parse("Done :tada:\n", &config())
+            .unwrap();
+        let render = |emoji| {
+            let config = RenderConfig {
+                emoji,
+                ..Default::default()
+            };
+            AsciidocHandler::new().render(&doc, &config).unwrap()
+        };
+
+        assert_eq!(render(EmojiStyle::Unicode), "Done 🎉");
+        assert_eq!(render(EmojiStyle::Shortcode), "Done :tada:");
+        assert_eq!(
+            render(EmojiStyle::Image(
+                "https://example.com/{codepoints}.png".to_string()
+            )),
+            "Done image:https://example.com/1f389.png[🎉]"
+        );
+        assert!(matches!(
+            render_emoji("no_such_emoji", &EmojiStyle::Unicode),
+            Inline::Text { content } if content == ":no_such_emoji:"
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Text encoding detection and transcoding
+//!
+//! Parsers work on UTF-8 strings, but files written by older editors are often
+//! in UTF-16 or a Western single-byte encoding. [`decode`] works out which
+//! encoding a file is in and converts it; [`encode`] turns text back into the
+//! same encoding so a save does not silently change it.
+//!
+//! Detection goes in this order:
+//!
+//! 1. A byte order mark decides outright.
+//! 2. A pattern of zero bytes in every other position means UTF-16 without a
+//!    BOM. Zero bytes are valid UTF-8, so this has to come first.
+//! 3. Bytes that are valid UTF-8 are UTF-8.
+//! 4. Anything else is Windows-1252 if it uses the 0x80-0x9F range (curly
+//!    quotes, dashes, the euro sign), otherwise ISO-8859-1.
+
+use std::fmt;
+
+/// Character encoding of a file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+    /// ISO-8859-1
+    Latin1,
+}
+
+impl TextEncoding {
+    /// Name as used in HTTP and HTML `charset` labels
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Windows1252 => "windows-1252",
+            Self::Latin1 => "iso-8859-1",
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8Bom => f.write_str("utf-8 (BOM)"),
+            other => f.write_str(other.label()),
+        }
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+
+/// Detect the encoding of `bytes` and decode them, dropping any BOM
+///
+/// Malformed sequences in UTF-16 become U+FFFD; the single-byte encodings
+/// decode every byte.
+pub fn decode(bytes: &[u8]) -> (String, TextEncoding) {
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        return (
+            String::from_utf8_lossy(rest).into_owned(),
+            TextEncoding::Utf8Bom,
+        );
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        return (
+            decode_utf16(rest, u16::from_le_bytes),
+            TextEncoding::Utf16Le,
+        );
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        return (
+            decode_utf16(rest, u16::from_be_bytes),
+            TextEncoding::Utf16Be,
+        );
+    }
+    if let Some(encoding) = guess_utf16(bytes) {
+        let text = match encoding {
+            TextEncoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            _ => decode_utf16(bytes, u16::from_be_bytes),
+        };
+        return (text, encoding);
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), TextEncoding::Utf8);
+    }
+    if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) {
+        let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes);
+        (text.into_owned(), TextEncoding::Windows1252)
+    } else {
+        (
+            bytes.iter().map(|&b| b as char).collect(),
+            TextEncoding::Latin1,
+        )
+    }
+}
+
+/// Encode `text` for writing in `encoding`
+///
+/// Fails with the first character the encoding cannot represent.
+pub fn encode(text: &str, encoding: TextEncoding) -> Result<Vec<u8>, char> {
+    match encoding {
+        TextEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        TextEncoding::Utf8Bom => Ok([UTF8_BOM, text.as_bytes()].concat()),
+        TextEncoding::Utf16Le => Ok(UTF16LE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect()),
+        TextEncoding::Utf16Be => Ok(UTF16BE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect()),
+        TextEncoding::Windows1252 => {
+            let (bytes, _, unmappable) = encoding_rs::WINDOWS_1252.encode(text);
+            if unmappable {
+                // Find the character the encoder had to replace
+                let bad = text
+                    .chars()
+                    .find(|c| {
+                        let mut buf = [0; 4];
+                        encoding_rs::WINDOWS_1252.encode(c.encode_utf8(&mut buf)).2
+                    })
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                return Err(bad);
+            }
+            Ok(bytes.into_owned())
+        }
+        TextEncoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).map_err(|_| c))
+            .collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// UTF-16 without a BOM: mostly-ASCII text has a zero in every other byte
+fn guess_utf16(bytes: &[u8]) -> Option<TextEncoding> {
+    if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let zero_even = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+    let zero_odd = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if zero_odd * 10 >= pairs * 3 && zero_even * 10 < pairs {
+        Some(TextEncoding::Utf16Le)
+    } else if zero_even * 10 >= pairs * 3 && zero_odd * 10 < pairs {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode("Café".as_bytes()),
+            ("Café".to_string(), TextEncoding::Utf8)
+        );
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFhi"),
+            ("hi".to_string(), TextEncoding::Utf8Bom)
+        );
+        assert_eq!(
+            decode(b"\xFF\xFEh\0i\0"),
+            ("hi".to_string(), TextEncoding::Utf16Le)
+        );
+        assert_eq!(
+            decode(b"\0#\0 \0T\0i\0t\0l\0e"),
+            ("# Title".to_string(), TextEncoding::Utf16Be)
+        );
+        assert_eq!(
+            decode(b"\x93quoted\x94 \x80"),
+            (
+                "\u{201C}quoted\u{201D} \u{20AC}".to_string(),
+                TextEncoding::Windows1252
+            )
+        );
+        assert_eq!(
+            decode(b"Caf\xE9"),
+            ("Café".to_string(), TextEncoding::Latin1)
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for (encoding, text) in [
+            (TextEncoding::Utf8, "Café\nnaïve ☃"),
+            (TextEncoding::Utf8Bom, "Café\nnaïve ☃"),
+            (TextEncoding::Utf16Le, "Café\nnaïve ☃"),
+            (TextEncoding::Utf16Be, "Café\nnaïve ☃"),
+            (TextEncoding::Windows1252, "\u{201C}Café\u{201D}\nnaïve"),
+            (TextEncoding::Latin1, "Café\nnaïve"),
+        ] {
+            let bytes = encode(text, encoding).unwrap();
+            assert_eq!(decode(&bytes), (text.to_string(), encoding));
+        }
+
+        assert_eq!(encode("€ and ☃", TextEncoding::Windows1252), Err('☃'));
+        assert_eq!(encode("€", TextEncoding::Latin1), Err('€'));
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Subtrees left out of exports
+//!
+//! Org tags private subtrees `:noexport:`, and `org-export` leaves out every
+//! heading carrying one of the document's `#+EXCLUDE_TAGS`, with everything
+//! under it. Org tags are read as heading classes, which other formats
+//! write as attributes (`# Notes {.noexport}`, `[.noexport]`), so with
+//! [`RenderConfig::exclude_tags`](crate::traits::RenderConfig) set every
+//! renderer does the same.
+
+use crate::ast::{Block, DocumentMeta};
+use crate::frontmatter::meta_text;
+use crate::normalize::block_lists_mut;
+
+/// Tags excluded when the document names none, as in Org
+pub const DEFAULT_EXCLUDE_TAGS: &[&str] = &["noexport"];
+
+/// The tags whose subtrees `meta` leaves out: its `exclude_tags` field,
+/// separated by spaces or commas, or [`DEFAULT_EXCLUDE_TAGS`]
+pub fn exclude_tags(meta: &DocumentMeta) -> Vec<String> {
+    let field = meta
+        .custom
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("exclude_tags"))
+        .and_then(|(_, value)| meta_text(value));
+    match field {
+        Some(text) => text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect(),
+        None => DEFAULT_EXCLUDE_TAGS.iter().map(|t| t.to_string()).collect(),
+    }
+}
+
+/// Remove the headings in `blocks` tagged with any of `tags`, each with the
+/// blocks under it up to the next heading of the same or a higher level,
+/// within every block holding others too
+pub fn exclude_tagged(blocks: &mut Vec<Block>, tags: &[String]) {
+    // Level of the excluded heading whose subtree is being removed
+    let mut excluding: Option<u8> = None;
+    blocks.retain_mut(|block| {
+        if let Block::Heading { level, classes, .. } = block {
+            if excluding.is_some_and(|excluded| *level <= excluded) {
+                excluding = None;
+            }
+            if excluding.is_none() && classes.iter().any(|class| tags.contains(class)) {
+                excluding = Some(*level);
+            }
+        }
+        if excluding.is_some() {
+            return false;
+        }
+        for list in block_lists_mut(block) {
+            exclude_tagged(list, tags);
+        }
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Inline, MetaValue};
+    use crate::formats::{MarkdownHandler, OrgModeHandler};
+    use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+
+    #[test]
+    fn test_exclude_tags() {
+        let mut meta = DocumentMeta::default();
+        assert_eq!(exclude_tags(&meta), ["noexport"]);
+        meta.custom.insert(
+            "exclude_tags".to_string(),
+            MetaValue::String("private draft".to_string()),
+        );
+        assert_eq!(exclude_tags(&meta), ["private", "draft"]);
+    }
+
+    #[test]
+    fn test_exclude_subtrees() {
+        let input = "* Public\nShown.\n** Notes :noexport:\nHidden.\n*** Deeper\nHidden too.\n\
+                     ** Also public\nShown.\n* Private :private:\nKept.\n";
+        let doc = OrgModeHandler::new()
+            .parse(input, &ParseConfig::default())
+            .unwrap();
+        let config = RenderConfig {
+            exclude_tags: true,
+            ..Default::default()
+        };
+
+        let output = MarkdownHandler::new().render(&doc, &config).unwrap();
+        assert!(!output.contains("Hidden"));
+        assert!(output.contains("## Also public"));
+        assert!(output.contains("# Private {.private}"));
+
+        // Without the option, and with the document naming its own tags
+        let output = MarkdownHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert!(output.contains("## Notes {.noexport}\n\nHidden."));
+        let doc = OrgModeHandler::new()
+            .parse(
+                &format!("#+EXCLUDE_TAGS: private\n\n{}", input),
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let output = MarkdownHandler::new().render(&doc, &config).unwrap();
+        assert!(output.contains("## Notes"));
+        assert!(!output.contains("Private"));
+
+        // Classes from any format are tags
+        let doc = MarkdownHandler::new()
+            .parse(
+                "# Draft {.noexport}\n\nHidden.\n\n# Done\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let output = OrgModeHandler::new().render(&doc, &config).unwrap();
+        assert_eq!(output, "* Done");
+    }
+
+    #[test]
+    fn test_exclude_nested_headings() {
+        let heading = |text: &str, classes: &[&str]| Block::Heading {
+            level: 2,
+            content: vec![Inline::Text {
+                content: text.to_string(),
+            }],
+            id: None,
+            classes: classes.iter().map(|c| c.to_string()).collect(),
+            span: None,
+        };
+        let paragraph = |text: &str| Block::Paragraph {
+            content: vec![Inline::Text {
+                content: text.to_string(),
+            }],
+            span: None,
+        };
+        let mut blocks = vec![Block::BlockQuote {
+            content: vec![
+                paragraph("Shown."),
+                heading("Notes", &["noexport"]),
+                paragraph("Hidden."),
+                heading("Later", &[]),
+            ],
+            attribution: None,
+            admonition: None,
+            span: None,
+        }];
+
+        exclude_tagged(&mut blocks, &["noexport".to_string()]);
+        let Block::BlockQuote { content, .. } = &blocks[0] else {
+            panic!("expected a quote, got {:?}", blocks[0]);
+        };
+        assert_eq!(content.len(), 2);
+        assert!(matches!(&content[1], Block::Heading { classes, .. } if classes.is_empty()));
+    }
+}
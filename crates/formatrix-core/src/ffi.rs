@@ -4,19 +4,72 @@
 //!
 //! These functions provide a C-compatible interface for the Ada TUI
 //! to call into the Rust formatting core.
+//!
+//! Strings going in are null-terminated UTF-8. Strings coming out are UTF-8
+//! byte buffers with a separate length and no terminator, so they may hold
+//! NUL bytes; free them with `formatrix_free_buffer`.
+//!
+//! Handles are not locked. A document may be used from any thread, and by
+//! several threads at once as long as none of them edits it; to render in
+//! the background while the UI keeps editing, hand the background task a
+//! copy from `formatrix_clone_document`. The last error is kept per thread.
 
+use std::any::Any;
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
+use std::fmt::Display;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
 use std::ptr;
-
-use crate::ast::{Document, SourceFormat};
-use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "watch")]
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::ast::{Block, Document, Inline, SourceFormat};
+use crate::edit;
+use crate::file_ops::FileError;
+use crate::incremental::IncrementalParser;
+use crate::outline::{outline, OutlineEntry};
+use crate::toolkit::inline_text;
+use crate::traits::{
+    ConversionError, FinalNewline, HeadingStyle, LineEnding, ParseConfig, ParseLimits, Parser,
+    RawPolicy, RenderConfig, Renderer, RstFlavor, StyleProfile, TextPolicy,
+};
 
 /// Opaque handle to a document
+///
+/// Not locked: any thread may use it, and several at once while none edits
+/// it. Give another thread its own copy with `formatrix_clone_document`.
 pub struct DocumentHandle {
     doc: Document,
 }
 
+// The threading contract in the module docs rests on this
+const _: () = {
+    const fn send_and_sync<T: Send + Sync>() {}
+    send_and_sync::<DocumentHandle>();
+};
+
+/// Borrowed handle to a block inside a document
+///
+/// Valid until the document it came from is edited or freed; never freed
+/// itself.
+pub struct BlockHandle {
+    _private: [u8; 0],
+}
+
+/// Borrowed handle to an inline element inside a document
+///
+/// Valid until the document it came from is edited or freed; never freed
+/// itself.
+pub struct InlineHandle {
+    _private: [u8; 0],
+}
+
 /// Result code for FFI operations
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -28,6 +81,10 @@ pub enum FfiResult {
     UnsupportedFormat = 4,
     NullPointer = 5,
     Utf8Error = 6,
+    /// The library panicked; the last error has the panic message
+    InternalError = 7,
+    /// Stopped through a cancel token
+    Cancelled = 8,
 }
 
 /// Document format for FFI
@@ -71,6 +128,478 @@ fn from(f: SourceFormat) -> Self {
     }
 }
 
+/// Block kind for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiBlockType {
+    Paragraph = 0,
+    Heading = 1,
+    CodeBlock = 2,
+    BlockQuote = 3,
+    List = 4,
+    DefinitionList = 5,
+    Table = 6,
+    ThematicBreak = 7,
+    MathBlock = 8,
+    Container = 9,
+    Figure = 10,
+    Raw = 11,
+    FootnoteDefinition = 12,
+    TableOfContents = 13,
+    Comment = 14,
+    Details = 15,
+    /// Not a block: the handle was null
+    Invalid = -1,
+}
+
+impl From<&Block> for FfiBlockType {
+    fn from(block: &Block) -> Self {
+        match block {
+            Block::Paragraph { .. } => FfiBlockType::Paragraph,
+            Block::Heading { .. } => FfiBlockType::Heading,
+            Block::CodeBlock { .. } => FfiBlockType::CodeBlock,
+            Block::BlockQuote { .. } => FfiBlockType::BlockQuote,
+            Block::List { .. } => FfiBlockType::List,
+            Block::DefinitionList { .. } => FfiBlockType::DefinitionList,
+            Block::Table { .. } => FfiBlockType::Table,
+            Block::ThematicBreak { .. } => FfiBlockType::ThematicBreak,
+            Block::MathBlock { .. } => FfiBlockType::MathBlock,
+            Block::Container { .. } => FfiBlockType::Container,
+            Block::Figure { .. } => FfiBlockType::Figure,
+            Block::Raw { .. } => FfiBlockType::Raw,
+            Block::FootnoteDefinition { .. } => FfiBlockType::FootnoteDefinition,
+            Block::TableOfContents { .. } => FfiBlockType::TableOfContents,
+            Block::Comment { .. } => FfiBlockType::Comment,
+            Block::Details { .. } => FfiBlockType::Details,
+        }
+    }
+}
+
+/// Inline element kind for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiInlineType {
+    Text = 0,
+    Emphasis = 1,
+    Strong = 2,
+    Strikethrough = 3,
+    Underline = 4,
+    Superscript = 5,
+    Subscript = 6,
+    SmallCaps = 7,
+    Code = 8,
+    Math = 9,
+    Link = 10,
+    Image = 11,
+    FootnoteRef = 12,
+    Citation = 13,
+    LineBreak = 14,
+    SoftBreak = 15,
+    NonBreakingSpace = 16,
+    Span = 17,
+    RawInline = 18,
+    Quoted = 19,
+    Keyboard = 20,
+    Highlight = 21,
+    Comment = 22,
+    Emoji = 23,
+    Button = 24,
+    Menu = 25,
+    /// Not an inline element: the handle was null
+    Invalid = -1,
+}
+
+impl From<&Inline> for FfiInlineType {
+    fn from(inline: &Inline) -> Self {
+        match inline {
+            Inline::Text { .. } => FfiInlineType::Text,
+            Inline::Emphasis { .. } => FfiInlineType::Emphasis,
+            Inline::Strong { .. } => FfiInlineType::Strong,
+            Inline::Strikethrough { .. } => FfiInlineType::Strikethrough,
+            Inline::Underline { .. } => FfiInlineType::Underline,
+            Inline::Superscript { .. } => FfiInlineType::Superscript,
+            Inline::Subscript { .. } => FfiInlineType::Subscript,
+            Inline::SmallCaps { .. } => FfiInlineType::SmallCaps,
+            Inline::Code { .. } => FfiInlineType::Code,
+            Inline::Math { .. } => FfiInlineType::Math,
+            Inline::Link { .. } => FfiInlineType::Link,
+            Inline::Image { .. } => FfiInlineType::Image,
+            Inline::FootnoteRef { .. } => FfiInlineType::FootnoteRef,
+            Inline::Citation { .. } => FfiInlineType::Citation,
+            Inline::LineBreak => FfiInlineType::LineBreak,
+            Inline::SoftBreak => FfiInlineType::SoftBreak,
+            Inline::NonBreakingSpace => FfiInlineType::NonBreakingSpace,
+            Inline::Span { .. } => FfiInlineType::Span,
+            Inline::RawInline { .. } => FfiInlineType::RawInline,
+            Inline::Quoted { .. } => FfiInlineType::Quoted,
+            Inline::Keyboard { .. } => FfiInlineType::Keyboard,
+            Inline::Highlight { .. } => FfiInlineType::Highlight,
+            Inline::Comment { .. } => FfiInlineType::Comment,
+            Inline::Emoji { .. } => FfiInlineType::Emoji,
+            Inline::Button { .. } => FfiInlineType::Button,
+            Inline::Menu { .. } => FfiInlineType::Menu,
+        }
+    }
+}
+
+/// Newline sequence for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiLineEnding {
+    Lf = 0,
+    CrLf = 1,
+    Cr = 2,
+}
+
+/// Trailing newline policy for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiFinalNewline {
+    /// Leave the renderer's output untouched
+    Keep = 0,
+    /// Remove all trailing newlines
+    Strip = 1,
+    /// End with exactly one newline
+    Single = 2,
+}
+
+/// Dialect of reStructuredText for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiRstFlavor {
+    /// Plain docutils
+    Docutils = 0,
+    /// Sphinx, with its roles and directives
+    Sphinx = 1,
+}
+
+/// House style for FFI, which among other things picks the Markdown
+/// flavor written
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiStyleProfile {
+    /// Each renderer's own style
+    None = 0,
+    /// GitHub Flavored Markdown
+    Github = 1,
+    /// Pandoc's Markdown and reStructuredText
+    Pandoc = 2,
+    /// Sphinx, with MyST for Markdown
+    Sphinx = 3,
+    /// Kramdown, as used by Jekyll
+    Kramdown = 4,
+}
+
+/// Markdown heading syntax for FFI
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiHeadingStyle {
+    /// The style profile's
+    Profile = 0,
+    /// `#` marks before the heading
+    Atx = 1,
+    /// A line of `=` or `-` under headings of level 1 and 2
+    Setext = 2,
+}
+
+/// Fidelity mode for FFI: what renderers do with raw content from another
+/// format
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiRawPolicy {
+    /// Write it as it is
+    Keep = 0,
+    /// Leave it out
+    Drop = 1,
+    /// Write it as a comment
+    Comment = 2,
+    /// Parse it with its own format's parser and write the result
+    Convert = 3,
+}
+
+/// Parse options for `formatrix_parse_with_options`
+///
+/// Fill them in with `formatrix_parse_options_init` and change the fields
+/// you need. The library reads only the fields that fit in `size`, and
+/// takes its defaults for the rest, so a caller built against an older
+/// header keeps working; fields from a newer header are ignored.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FfiParseOptions {
+    /// `sizeof(FfiParseOptions)` in the caller's header
+    pub size: usize,
+    /// Record where each block came from in the source
+    pub preserve_spans: bool,
+    /// Keep raw source for lossless round-trip
+    pub preserve_raw_source: bool,
+    /// Fail on malformed input, instead of keeping the regions that do not
+    /// parse as raw blocks
+    pub strict: bool,
+    /// Expand tabs to the next multiple of this many columns (0 = keep tabs)
+    pub tab_width: usize,
+    /// Dialect reStructuredText is read as
+    pub rst_flavor: FfiRstFlavor,
+    /// Largest input accepted, in bytes (0 = no limit)
+    ///
+    /// Input past any of the limits fails with `ParseError`. With a limit
+    /// set, malformed input that is not strict is recovered as by
+    /// `formatrix_open_file`, without the spans a parse by region adds.
+    pub max_input_bytes: usize,
+    /// Deepest nesting accepted, top-level blocks being at depth 1 (0 = no
+    /// limit); set it for untrusted input, which may nest deep enough to
+    /// overflow the stack otherwise
+    pub max_depth: usize,
+    /// Most blocks accepted, nested ones included (0 = no limit)
+    pub max_blocks: usize,
+    /// Longest the parse may take, in milliseconds (0 = no limit)
+    ///
+    /// Only the Djot reader checks the time as it goes; other formats fail
+    /// with `ParseError` when it is set, since their parse cannot be
+    /// stopped part way.
+    pub timeout_ms: u64,
+    // New fields go here, starting past the padding after the last one, so
+    // no older caller's `size` covers part of one
+}
+
+impl FfiParseOptions {
+    fn limits(&self) -> ParseLimits {
+        let limit = |n: usize| (n > 0).then_some(n);
+        ParseLimits {
+            max_input_bytes: limit(self.max_input_bytes),
+            max_depth: limit(self.max_depth),
+            max_blocks: limit(self.max_blocks),
+            timeout: (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms)),
+        }
+    }
+}
+
+impl From<FfiParseOptions> for ParseConfig {
+    fn from(options: FfiParseOptions) -> Self {
+        ParseConfig {
+            preserve_spans: options.preserve_spans,
+            preserve_raw_source: options.preserve_raw_source,
+            strict: options.strict,
+            text: TextPolicy {
+                tab_width: (options.tab_width > 0).then_some(options.tab_width),
+                ..Default::default()
+            },
+            rst_flavor: match options.rst_flavor {
+                FfiRstFlavor::Docutils => RstFlavor::Docutils,
+                FfiRstFlavor::Sphinx => RstFlavor::Sphinx,
+            },
+            limits: options.limits(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Render options for `formatrix_render_with_options`
+///
+/// Fill them in with `formatrix_render_options_init` and change the fields
+/// you need. As with `FfiParseOptions`, the library reads only the fields
+/// that fit in `size`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FfiRenderOptions {
+    /// `sizeof(FfiRenderOptions)` in the caller's header
+    pub size: usize,
+    /// Target line width for wrapping (0 = no wrap)
+    pub line_width: usize,
+    /// Use hard line breaks
+    pub hard_breaks: bool,
+    /// Newline sequence used in the output
+    pub line_ending: FfiLineEnding,
+    /// Trailing newline policy
+    pub final_newline: FfiFinalNewline,
+    /// House style to follow
+    pub profile: FfiStyleProfile,
+    /// Markdown heading syntax
+    pub heading_style: FfiHeadingStyle,
+    /// Fidelity mode for raw content from another format
+    pub raw_policy: FfiRawPolicy,
+    // New fields go here, as in `FfiParseOptions`
+}
+
+impl From<FfiRenderOptions> for RenderConfig {
+    fn from(options: FfiRenderOptions) -> Self {
+        RenderConfig {
+            line_width: options.line_width,
+            hard_breaks: options.hard_breaks,
+            line_ending: match options.line_ending {
+                FfiLineEnding::Lf => LineEnding::Lf,
+                FfiLineEnding::CrLf => LineEnding::CrLf,
+                FfiLineEnding::Cr => LineEnding::Cr,
+            },
+            final_newline: match options.final_newline {
+                FfiFinalNewline::Keep => FinalNewline::Keep,
+                FfiFinalNewline::Strip => FinalNewline::Strip,
+                FfiFinalNewline::Single => FinalNewline::Single,
+            },
+            profile: match options.profile {
+                FfiStyleProfile::None => None,
+                FfiStyleProfile::Github => Some(StyleProfile::Github),
+                FfiStyleProfile::Pandoc => Some(StyleProfile::Pandoc),
+                FfiStyleProfile::Sphinx => Some(StyleProfile::Sphinx),
+                FfiStyleProfile::Kramdown => Some(StyleProfile::Kramdown),
+            },
+            heading_style: match options.heading_style {
+                FfiHeadingStyle::Profile => None,
+                FfiHeadingStyle::Atx => Some(HeadingStyle::Atx),
+                FfiHeadingStyle::Setext => Some(HeadingStyle::Setext),
+            },
+            raw_policy: match options.raw_policy {
+                FfiRawPolicy::Keep => RawPolicy::Keep,
+                FfiRawPolicy::Drop => RawPolicy::Drop,
+                FfiRawPolicy::Comment => RawPolicy::Comment,
+                FfiRawPolicy::Convert => RawPolicy::Convert,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// One heading of a document outline, from `formatrix_get_outline`
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiOutlineEntry {
+    /// Heading level, 1-6
+    pub level: u8,
+    /// Plain text of the heading, owned by the outline
+    pub title: *mut u8,
+    pub title_length: usize,
+    /// Whether the parser recorded where the heading starts
+    pub has_offset: bool,
+    /// Byte offset of the heading in the source, when `has_offset` is set
+    pub byte_offset: usize,
+}
+
+/// Receives one chunk of a streamed render
+///
+/// `data` holds `length` bytes of UTF-8, valid only during the call.
+/// Return false to stop the render.
+pub type FfiChunkCallback =
+    Option<unsafe extern "C" fn(data: *const u8, length: usize, userdata: *mut c_void) -> bool>;
+
+/// The last failure on a thread, for `formatrix_last_error_message`
+struct LastError {
+    message: CString,
+    /// Line and column, when the error points into the source
+    position: Option<(u32, u32)>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+const NULL_ARGUMENT: &str = "A required pointer argument is null";
+
+/// Run an export's body, turning a panic into `fallback`
+///
+/// Unwinding into a C or Ada caller is undefined behaviour, so every export
+/// runs its body under this, except the few that cannot panic: constants
+/// and plain lookups.
+/// The panic message becomes the last error.
+fn guard<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = panic_message(payload.as_ref());
+            set_last_error(format!("Internal error: {message}"), None);
+            fallback
+        }
+    }
+}
+
+/// Text of a caught panic
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Record `error` as this thread's last error and return `result`
+fn fail(result: FfiResult, error: impl Display) -> FfiResult {
+    set_last_error(error.to_string(), None);
+    result
+}
+
+/// [`fail`], keeping the position of a parse error
+fn fail_conversion(result: FfiResult, error: &ConversionError) -> FfiResult {
+    set_last_error(error.to_string(), error_position(error));
+    result
+}
+
+fn error_position(error: &ConversionError) -> Option<(u32, u32)> {
+    match error {
+        ConversionError::ParseError { line, column, .. } => Some((*line, *column)),
+        _ => None,
+    }
+}
+
+fn set_last_error(message: String, position: Option<(u32, u32)>) {
+    // Messages never hold NUL, but drop any rather than lose the message
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(LastError { message, position }));
+}
+
+/// Message of the last error on this thread, or null if there has been none
+///
+/// Every function that returns a failing `FfiResult` sets it first; calls
+/// that succeed leave it alone.
+///
+/// # Safety
+/// Returns a string owned by the library, valid until the next failing call
+/// on this thread or `formatrix_clear_last_error`; do not free it
+#[no_mangle]
+pub extern "C" fn formatrix_last_error_message() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(ptr::null(), |error| error.message.as_ptr())
+        })
+    })
+}
+
+/// Line and column of the last error on this thread, if it has them
+///
+/// Returns false, and leaves the outputs alone, when the last error has no
+/// position in the source.
+///
+/// # Safety
+/// - `out_line` and `out_column` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_last_error_span(
+    out_line: *mut u32,
+    out_column: *mut u32,
+) -> bool {
+    guard(false, || {
+        if out_line.is_null() || out_column.is_null() {
+            return false;
+        }
+        let position =
+            LAST_ERROR.with(|last| last.borrow().as_ref().and_then(|error| error.position));
+        match position {
+            Some((line, column)) => {
+                *out_line = line;
+                *out_column = column;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Forget the last error on this thread
+#[no_mangle]
+pub extern "C" fn formatrix_clear_last_error() {
+    guard((), || {
+        LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    })
+}
+
 /// Parse content into a document handle
 ///
 /// # Safety
@@ -82,77 +611,166 @@ fn from(f: SourceFormat) -> Self {
     format: FfiFormat,
     out_handle: *mut *mut DocumentHandle,
 ) -> FfiResult {
-    if content.is_null() || out_handle.is_null() {
-        return FfiResult::NullPointer;
+    guard(FfiResult::InternalError, || {
+        let options = FfiParseOptions::default();
+        formatrix_parse_with_options(content, format, &options, out_handle)
+    })
+}
+
+impl Default for FfiParseOptions {
+    fn default() -> Self {
+        FfiParseOptions {
+            size: mem::size_of::<Self>(),
+            preserve_spans: false,
+            preserve_raw_source: false,
+            strict: true,
+            tab_width: 0,
+            rst_flavor: FfiRstFlavor::Docutils,
+            max_input_bytes: 0,
+            max_depth: 0,
+            max_blocks: 0,
+            timeout_ms: 0,
+        }
     }
+}
 
-    let content_str = match CStr::from_ptr(content).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiResult::Utf8Error,
-    };
+/// Fill in parse options with the library's defaults
+///
+/// Writes only the first `size` bytes of `options`, and sets its `size`.
+///
+/// # Safety
+/// - `options` must point to `size` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_parse_options_init(
+    options: *mut FfiParseOptions,
+    size: usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        init_sized(options, size, FfiParseOptions::default())
+    })
+}
+
+/// Parse content into a document handle, with `options`
+///
+/// # Safety
+/// - `content` must be a valid null-terminated UTF-8 string
+/// - `options` must be a valid pointer to parse options, `size` bytes long
+/// - `out_handle` must be a valid pointer to store the result
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_parse_with_options(
+    content: *const c_char,
+    format: FfiFormat,
+    options: *const FfiParseOptions,
+    out_handle: *mut *mut DocumentHandle,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if content.is_null() || options.is_null() || out_handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
 
-    let config = ParseConfig::default();
+        let content_str = match CStr::from_ptr(content).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let options = match read_sized(options, FfiParseOptions::default()) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+        match parse_with(content_str, format, &options) {
+            Ok(doc) => {
+                *out_handle = Box::into_raw(Box::new(DocumentHandle { doc }));
+                FfiResult::Success
+            }
+            Err(result) => result,
+        }
+    })
+}
+
+fn parse_with(
+    content: &str,
+    format: FfiFormat,
+    options: &FfiParseOptions,
+) -> Result<Document, FfiResult> {
+    let config: ParseConfig = (*options).into();
     let source_format: SourceFormat = format.into();
 
-    let doc = match source_format {
+    if config.limits != ParseLimits::default() {
+        // Limited as when opening a file
+        crate::file_ops::parse_opened(content, source_format, &config)
+            .map(|(doc, _)| doc)
+            .map_err(|e| fail(open_failure(&e), e))
+    } else if options.strict {
+        parse_document(content, source_format, &config)
+    } else {
+        // Parsed by region, which also gives spans to blocks the parser
+        // left without one
+        Ok(IncrementalParser::new(content, source_format, config)
+            .document()
+            .clone())
+    }
+}
+
+fn parse_document(
+    content: &str,
+    format: SourceFormat,
+    config: &ParseConfig,
+) -> Result<Document, FfiResult> {
+    let doc = match format {
         SourceFormat::PlainText => {
             use crate::formats::PlainTextHandler;
-            match PlainTextHandler::new().parse(content_str, &config) {
+            match PlainTextHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         SourceFormat::Markdown => {
             use crate::formats::MarkdownHandler;
-            match MarkdownHandler::new().parse(content_str, &config) {
+            match MarkdownHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         SourceFormat::Djot => {
             use crate::formats::DjotHandler;
-            match DjotHandler::new().parse(content_str, &config) {
+            match DjotHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         SourceFormat::OrgMode => {
             use crate::formats::OrgModeHandler;
-            match OrgModeHandler::new().parse(content_str, &config) {
+            match OrgModeHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         // FD-S01: AsciiDoc support
         SourceFormat::AsciiDoc => {
             use crate::formats::AsciidocHandler;
-            match AsciidocHandler::new().parse(content_str, &config) {
+            match AsciidocHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         // FD-S02: RST support
         SourceFormat::ReStructuredText => {
             use crate::formats::RstHandler;
-            match RstHandler::new().parse(content_str, &config) {
+            match RstHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
         // FD-S03: Typst support
         SourceFormat::Typst => {
             use crate::formats::TypstHandler;
-            match TypstHandler::new().parse(content_str, &config) {
+            match TypstHandler::new().parse(content, config) {
                 Ok(d) => d,
-                Err(_) => return FfiResult::ParseError,
+                Err(e) => return Err(fail_conversion(FfiResult::ParseError, &e)),
             }
         }
     };
-
-    let handle = Box::new(DocumentHandle { doc });
-    *out_handle = Box::into_raw(handle);
-
-    FfiResult::Success
+    Ok(doc)
 }
 
 /// Render a document to a string in the specified format
@@ -165,157 +783,1155 @@ fn from(f: SourceFormat) -> Self {
 pub unsafe extern "C" fn formatrix_render(
     handle: *const DocumentHandle,
     format: FfiFormat,
-    out_content: *mut *mut c_char,
+    out_content: *mut *mut u8,
     out_length: *mut usize,
 ) -> FfiResult {
-    if handle.is_null() || out_content.is_null() || out_length.is_null() {
-        return FfiResult::NullPointer;
+    guard(FfiResult::InternalError, || {
+        let options = FfiRenderOptions::default();
+        formatrix_render_with_options(handle, format, &options, out_content, out_length)
+    })
+}
+
+impl Default for FfiRenderOptions {
+    fn default() -> Self {
+        let config = RenderConfig::default();
+        FfiRenderOptions {
+            size: mem::size_of::<Self>(),
+            line_width: config.line_width,
+            hard_breaks: config.hard_breaks,
+            line_ending: FfiLineEnding::Lf,
+            final_newline: FfiFinalNewline::Keep,
+            profile: FfiStyleProfile::None,
+            heading_style: FfiHeadingStyle::Profile,
+            raw_policy: FfiRawPolicy::Keep,
+        }
+    }
+}
+
+/// Fill in render options with the library's defaults
+///
+/// Writes only the first `size` bytes of `options`, and sets its `size`.
+///
+/// # Safety
+/// - `options` must point to `size` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_render_options_init(
+    options: *mut FfiRenderOptions,
+    size: usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        init_sized(options, size, FfiRenderOptions::default())
+    })
+}
+
+/// Write the first `size` bytes of `defaults` to a caller's options struct,
+/// which starts with its size
+unsafe fn init_sized<T: Copy>(options: *mut T, size: usize, defaults: T) -> FfiResult {
+    if options.is_null() {
+        return fail(FfiResult::NullPointer, NULL_ARGUMENT);
     }
+    if size < mem::size_of::<usize>() {
+        return fail(FfiResult::InvalidInput, "Options size is too small");
+    }
+    let known = size.min(mem::size_of::<T>());
+    ptr::copy_nonoverlapping(
+        (&defaults as *const T).cast::<u8>(),
+        options.cast::<u8>(),
+        known,
+    );
+    ptr::write_unaligned(options.cast::<usize>(), size);
+    FfiResult::Success
+}
+
+/// Read a caller's options struct, which starts with its size, taking
+/// `defaults` for the fields past its end
+unsafe fn read_sized<T: Copy>(options: *const T, mut defaults: T) -> Result<T, FfiResult> {
+    let size = ptr::read_unaligned(options.cast::<usize>());
+    if size < mem::size_of::<usize>() {
+        return Err(fail(FfiResult::InvalidInput, "Options size is too small"));
+    }
+    let known = size.min(mem::size_of::<T>());
+    ptr::copy_nonoverlapping(
+        options.cast::<u8>(),
+        (&mut defaults as *mut T).cast::<u8>(),
+        known,
+    );
+    Ok(defaults)
+}
+
+/// Render a document to a string in the specified format, with `options`
+///
+/// # Safety
+/// - `handle` must be a valid document handle from `formatrix_parse`
+/// - `options` must be a valid pointer to render options, `size` bytes long
+/// - `out_content` must be a valid pointer to store the result
+/// - `out_length` must be a valid pointer to store the length
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_render_with_options(
+    handle: *const DocumentHandle,
+    format: FfiFormat,
+    options: *const FfiRenderOptions,
+    out_content: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || options.is_null() || out_content.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let config = match read_sized(options, FfiRenderOptions::default()) {
+            Ok(options) => options.into(),
+            Err(result) => return result,
+        };
+        let doc = &(*handle).doc;
+        match render_document(doc, format.into(), &config) {
+            Ok(output) => write_string(output, out_content, out_length),
+            Err(result) => result,
+        }
+    })
+}
 
-    let doc = &(*handle).doc;
-    let config = RenderConfig::default();
-    let target_format: SourceFormat = format.into();
+/// Render a document with `options`, in the house style of the profile
+/// called `profile`
+///
+/// The profiles are "github", "pandoc", "sphinx" and "kramdown"; an
+/// unknown name fails with `InvalidInput`. The name overrides
+/// `options.profile`.
+///
+/// # Safety
+/// - `handle` must be a valid document handle from `formatrix_parse`
+/// - `options` must be a valid pointer to render options, `size` bytes long
+/// - `profile` must be a valid null-terminated string
+/// - `out_content` must be a valid pointer to store the result
+/// - `out_length` must be a valid pointer to store the length
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_render_with_profile(
+    handle: *const DocumentHandle,
+    format: FfiFormat,
+    options: *const FfiRenderOptions,
+    profile: *const c_char,
+    out_content: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null()
+            || options.is_null()
+            || profile.is_null()
+            || out_content.is_null()
+            || out_length.is_null()
+        {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let name = match CStr::from_ptr(profile).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+        let Some(profile) = StyleProfile::from_name(name) else {
+            return fail(
+                FfiResult::InvalidInput,
+                format!("Unknown style profile: {}", name),
+            );
+        };
+
+        let options = match read_sized(options, FfiRenderOptions::default()) {
+            Ok(options) => options,
+            Err(result) => return result,
+        };
+        let doc = &(*handle).doc;
+        let config = RenderConfig {
+            profile: Some(profile),
+            ..options.into()
+        };
+        match render_document(doc, format.into(), &config) {
+            Ok(output) => write_string(output, out_content, out_length),
+            Err(result) => result,
+        }
+    })
+}
 
-    let output = match target_format {
+fn render_document(
+    doc: &Document,
+    format: SourceFormat,
+    config: &RenderConfig,
+) -> Result<String, FfiResult> {
+    let output = match format {
         SourceFormat::PlainText => {
             use crate::formats::PlainTextHandler;
-            match PlainTextHandler::new().render(doc, &config) {
+            match PlainTextHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         SourceFormat::Markdown => {
             use crate::formats::MarkdownHandler;
-            match MarkdownHandler::new().render(doc, &config) {
+            match MarkdownHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         SourceFormat::Djot => {
             use crate::formats::DjotHandler;
-            match DjotHandler::new().render(doc, &config) {
+            match DjotHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         SourceFormat::OrgMode => {
             use crate::formats::OrgModeHandler;
-            match OrgModeHandler::new().render(doc, &config) {
+            match OrgModeHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         // FD-S01: AsciiDoc support
         SourceFormat::AsciiDoc => {
             use crate::formats::AsciidocHandler;
-            match AsciidocHandler::new().render(doc, &config) {
+            match AsciidocHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         // FD-S02: RST support
         SourceFormat::ReStructuredText => {
             use crate::formats::RstHandler;
-            match RstHandler::new().render(doc, &config) {
+            match RstHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
         // FD-S03: Typst support
         SourceFormat::Typst => {
             use crate::formats::TypstHandler;
-            match TypstHandler::new().render(doc, &config) {
+            match TypstHandler::new().render(doc, config) {
                 Ok(s) => s,
-                Err(_) => return FfiResult::RenderError,
+                Err(e) => return Err(fail_conversion(FfiResult::RenderError, &e)),
             }
         }
     };
-
-    let c_string = match CString::new(output.clone()) {
-        Ok(s) => s,
-        Err(_) => return FfiResult::InvalidInput,
-    };
-
-    *out_length = output.len();
-    *out_content = c_string.into_raw();
-
-    FfiResult::Success
+    Ok(output)
 }
 
-/// Get the title of a document
+/// Render a document as styled terminal text, wrapped to `width` columns
+///
+/// A `width` of 0 turns wrapping off; with `color` false no escape codes are
+/// written.
 ///
 /// # Safety
-/// - `handle` must be a valid document handle
-/// - `out_title` must be a valid pointer
-/// - `out_length` must be a valid pointer
+/// - `handle` must be a valid document handle from `formatrix_parse`
+/// - `out_content` must be a valid pointer to store the result
+/// - `out_length` must be a valid pointer to store the length
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_get_title(
+pub unsafe extern "C" fn formatrix_render_ansi(
     handle: *const DocumentHandle,
-    out_title: *mut *mut c_char,
+    width: usize,
+    color: bool,
+    out_content: *mut *mut u8,
     out_length: *mut usize,
 ) -> FfiResult {
-    if handle.is_null() || out_title.is_null() || out_length.is_null() {
-        return FfiResult::NullPointer;
-    }
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_content.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
 
-    let doc = &(*handle).doc;
-    let title = doc.meta.title.clone().unwrap_or_default();
+        use crate::ansi::{AnsiConfig, AnsiHandler};
+        let output = AnsiHandler::new().render(&(*handle).doc, &AnsiConfig { width, color });
+        write_string(output, out_content, out_length)
+    })
+}
 
-    let c_string = match CString::new(title.clone()) {
-        Ok(s) => s,
-        Err(_) => return FfiResult::InvalidInput,
-    };
+/// Render a document in the specified format one top-level block at a time
+///
+/// `callback` gets the front matter, if any, then each block with the blank
+/// line before it, so a caller can show the start of a large document
+/// before the rest is rendered. The chunks join up to what `formatrix_render`
+/// gives, except that each block is rendered without its neighbours.
+/// Stopping early from the callback still returns `Success`.
+///
+/// # Safety
+/// - `handle` must be a valid document handle from `formatrix_parse`
+/// - `callback` must be safe to call with `userdata`
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_render_stream(
+    handle: *const DocumentHandle,
+    format: FfiFormat,
+    callback: FfiChunkCallback,
+    userdata: *mut c_void,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        let Some(callback) = callback else {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        };
+        if handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
 
-    *out_length = title.len();
-    *out_title = c_string.into_raw();
+        let doc = &(*handle).doc;
+        let format = format.into();
+        let config = RenderConfig::default();
+        let single = |content: Vec<Block>| Document {
+            source_format: doc.source_format,
+            meta: doc.meta.clone(),
+            content,
+            raw_source: None,
+        };
+        let emit = |chunk: &str| callback(chunk.as_ptr(), chunk.len(), userdata);
+
+        // Every block is rendered with the metadata, for renderers that read
+        // it, and the front matter that comes with it is cut off again
+        let header = match render_document(&single(Vec::new()), format, &config) {
+            Ok(header) => header,
+            Err(result) => return result,
+        };
+        if !header.is_empty() && !emit(&header) {
+            return FfiResult::Success;
+        }
 
-    FfiResult::Success
+        let mut first = true;
+        for (i, block) in doc.content.iter().enumerate() {
+            let output = match render_document(&single(vec![block.clone()]), format, &config) {
+                Ok(output) => output,
+                Err(result) => return result,
+            };
+            let body = output.strip_prefix(header.as_str()).unwrap_or(&output);
+            let body = if i + 1 < doc.content.len() {
+                body.trim_end_matches('\n')
+            } else {
+                body
+            };
+            if body.trim().is_empty() {
+                continue;
+            }
+            let chunk = if first {
+                body.to_string()
+            } else {
+                format!("\n\n{body}")
+            };
+            first = false;
+            if !emit(&chunk) {
+                break;
+            }
+        }
+        FfiResult::Success
+    })
 }
 
-/// Get the number of blocks in a document
+/// Parse UTF-16 content into a document handle
+///
+/// For hosts whose strings are UTF-16, such as Windows frontends. `length`
+/// counts code units, not bytes; no terminator is needed. Parses with the
+/// options of `formatrix_parse`, and fails with `InvalidInput` on unpaired
+/// surrogates.
 ///
 /// # Safety
-/// - `handle` must be a valid document handle
+/// - `content` must point to `length` UTF-16 code units (or be null with a
+///   `length` of 0)
+/// - `out_handle` must be a valid pointer to store the result
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_block_count(handle: *const DocumentHandle) -> usize {
-    if handle.is_null() {
-        return 0;
-    }
-    (*handle).doc.content.len()
+pub unsafe extern "C" fn formatrix_parse_utf16(
+    content: *const u16,
+    length: usize,
+    format: FfiFormat,
+    out_handle: *mut *mut DocumentHandle,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if (content.is_null() && length > 0) || out_handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let units = if content.is_null() {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(content, length)
+        };
+        let content = match String::from_utf16(units) {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::InvalidInput, e),
+        };
+
+        match parse_with(&content, format, &FfiParseOptions::default()) {
+            Ok(doc) => {
+                *out_handle = Box::into_raw(Box::new(DocumentHandle { doc }));
+                FfiResult::Success
+            }
+            Err(result) => result,
+        }
+    })
 }
 
-/// Get the source format of a document
+/// Render a document to UTF-16 in the specified format
+///
+/// `out_length` gets the number of code units. Free the result with
+/// `formatrix_free_buffer_utf16`.
 ///
 /// # Safety
 /// - `handle` must be a valid document handle
+/// - `out_content` must be a valid pointer to store the result
+/// - `out_length` must be a valid pointer to store the length
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_get_format(handle: *const DocumentHandle) -> FfiFormat {
-    if handle.is_null() {
-        return FfiFormat::PlainText;
-    }
-    (*handle).doc.source_format.into()
+pub unsafe extern "C" fn formatrix_render_utf16(
+    handle: *const DocumentHandle,
+    format: FfiFormat,
+    out_content: *mut *mut u16,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_content.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let config = RenderConfig::default();
+        match render_document(&(*handle).doc, format.into(), &config) {
+            Ok(output) => {
+                let units: Box<[u16]> = output.encode_utf16().collect();
+                *out_length = units.len();
+                *out_content = Box::into_raw(units).cast::<u16>();
+                FfiResult::Success
+            }
+            Err(result) => result,
+        }
+    })
 }
 
-/// Free a document handle
+/// Get the title of a document
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `out_title` must be a valid pointer
+/// - `out_length` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_title(
+    handle: *const DocumentHandle,
+    out_title: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_title.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let doc = &(*handle).doc;
+        let title = doc.meta.title.clone().unwrap_or_default();
+        write_string(title, out_title, out_length)
+    })
+}
+
+/// Get the number of blocks in a document
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_block_count(handle: *const DocumentHandle) -> usize {
+    guard(0, || {
+        if handle.is_null() {
+            return 0;
+        }
+        (*handle).doc.content.len()
+    })
+}
+
+/// Get the source format of a document
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_format(handle: *const DocumentHandle) -> FfiFormat {
+    guard(FfiFormat::PlainText, || {
+        if handle.is_null() {
+            return FfiFormat::PlainText;
+        }
+        (*handle).doc.source_format.into()
+    })
+}
+
+/// Get the headings of a document, in document order
+///
+/// The outline tree is flattened depth first, so each entry's parent is
+/// the nearest entry before it with a lower level. Headings inside block
+/// quotes, lists and tables are left out. Byte offsets are known for
+/// headings set off by blank lines in documents parsed with
+/// `preserve_spans` and without `strict`. Free the entries with
+/// `formatrix_free_outline`.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `out_entries` must be a valid pointer to store the result
+/// - `out_count` must be a valid pointer to store the number of entries
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_outline(
+    handle: *const DocumentHandle,
+    out_entries: *mut *mut FfiOutlineEntry,
+    out_count: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_entries.is_null() || out_count.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        fn flatten(entries: Vec<OutlineEntry>, out: &mut Vec<FfiOutlineEntry>) {
+            for entry in entries {
+                let title = entry.title.into_bytes().into_boxed_slice();
+                out.push(FfiOutlineEntry {
+                    level: entry.level,
+                    title_length: title.len(),
+                    title: Box::into_raw(title).cast::<u8>(),
+                    has_offset: entry.span.is_some(),
+                    byte_offset: entry.span.map_or(0, |span| span.start),
+                });
+                flatten(entry.children, out);
+            }
+        }
+
+        let mut entries = Vec::new();
+        flatten(outline(&(*handle).doc), &mut entries);
+        let entries = entries.into_boxed_slice();
+        *out_count = entries.len();
+        *out_entries = Box::into_raw(entries).cast::<FfiOutlineEntry>();
+        FfiResult::Success
+    })
+}
+
+/// Free an outline from `formatrix_get_outline`, with its titles
+///
+/// # Safety
+/// - `entries` must be an outline from this library, with the `count` that
+///   came with it, or null
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_free_outline(entries: *mut FfiOutlineEntry, count: usize) {
+    guard((), || {
+        if entries.is_null() {
+            return;
+        }
+        let entries = Box::from_raw(ptr::slice_from_raw_parts_mut(entries, count));
+        for entry in entries.iter() {
+            formatrix_free_buffer(entry.title, entry.title_length);
+        }
+    })
+}
+
+/// Serialize a document to JSON
+///
+/// The JSON is the serde form of the document AST, the same one the GUI
+/// and the database use, so tooling in any language can read it without a
+/// per-node API.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `out_json` must be a valid pointer to store the result
+/// - `out_length` must be a valid pointer to store the length
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_document_to_json(
+    handle: *const DocumentHandle,
+    out_json: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_json.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        match serde_json::to_string(&(*handle).doc) {
+            Ok(json) => write_string(json, out_json, out_length),
+            Err(e) => fail(FfiResult::RenderError, e),
+        }
+    })
+}
+
+/// Copy a document into a new handle, for another thread to use
+///
+/// The copy shares nothing with the original, so each can be edited or
+/// freed without regard to the other.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `out_handle` must be a valid pointer to store the result
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_clone_document(
+    handle: *const DocumentHandle,
+    out_handle: *mut *mut DocumentHandle,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || out_handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let doc = (*handle).doc.clone();
+        *out_handle = Box::into_raw(Box::new(DocumentHandle { doc }));
+        FfiResult::Success
+    })
+}
+
+/// Build a document handle from the JSON `formatrix_document_to_json` gives
+///
+/// On failure the last error has the line and column of the problem.
+///
+/// # Safety
+/// - `json` must be a valid null-terminated UTF-8 string
+/// - `out_handle` must be a valid pointer to store the result
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_document_from_json(
+    json: *const c_char,
+    out_handle: *mut *mut DocumentHandle,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if json.is_null() || out_handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let json_str = match CStr::from_ptr(json).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        match serde_json::from_str::<Document>(json_str) {
+            Ok(doc) => {
+                *out_handle = Box::into_raw(Box::new(DocumentHandle { doc }));
+                FfiResult::Success
+            }
+            Err(e) => {
+                let position = (e.line() as u32, e.column() as u32);
+                set_last_error(e.to_string(), Some(position));
+                FfiResult::ParseError
+            }
+        }
+    })
+}
+
+/// Get a top-level block of a document, or null if `index` is out of range
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_block(
+    handle: *const DocumentHandle,
+    index: usize,
+) -> *const BlockHandle {
+    guard(ptr::null(), || {
+        if handle.is_null() {
+            return ptr::null();
+        }
+        let doc = &(*handle).doc;
+        block_handle(doc.content.get(index))
+    })
+}
+
+/// Get the kind of a block, or `Invalid` for a null handle
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_block_type(block: *const BlockHandle) -> FfiBlockType {
+    guard(FfiBlockType::Invalid, || {
+        if block.is_null() {
+            return FfiBlockType::Invalid;
+        }
+        as_block(block).into()
+    })
+}
+
+/// Get the text of a block
+///
+/// Code, math and raw blocks give their content as written; other blocks
+/// give the plain text of their own inline content (a heading's title, a
+/// table's caption), one line per run, and nothing of their child blocks.
+///
+/// # Safety
+/// - `block` must be a valid block handle
+/// - `out_text` must be a valid pointer
+/// - `out_length` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_block_text(
+    block: *const BlockHandle,
+    out_text: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if block.is_null() || out_text.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let text = match as_block(block) {
+            Block::CodeBlock { content, .. }
+            | Block::MathBlock { content, .. }
+            | Block::Raw { content, .. } => content.clone(),
+            block => block
+                .inline_groups()
+                .into_iter()
+                .map(inline_text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        write_string(text, out_text, out_length)
+    })
+}
+
+/// Get the level of a heading block, or 0 for any other block
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_heading_level(block: *const BlockHandle) -> u8 {
+    guard(0, || {
+        if block.is_null() {
+            return 0;
+        }
+        match as_block(block) {
+            Block::Heading { level, .. } => *level,
+            _ => 0,
+        }
+    })
+}
+
+/// Get the number of blocks nested directly inside a block
+///
+/// List items, definitions and table cells are flattened, in document
+/// order.
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_block_child_count(block: *const BlockHandle) -> usize {
+    guard(0, || {
+        if block.is_null() {
+            return 0;
+        }
+        as_block(block).child_blocks().len()
+    })
+}
+
+/// Get a block nested directly inside a block, or null if `index` is out of
+/// range
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_block_child(
+    block: *const BlockHandle,
+    index: usize,
+) -> *const BlockHandle {
+    guard(ptr::null(), || {
+        if block.is_null() {
+            return ptr::null();
+        }
+        block_handle(as_block(block).child_blocks().get(index).copied())
+    })
+}
+
+/// Get the number of inline elements held directly by a block
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_count(block: *const BlockHandle) -> usize {
+    guard(0, || {
+        if block.is_null() {
+            return 0;
+        }
+        as_block(block)
+            .inline_groups()
+            .into_iter()
+            .flatten()
+            .count()
+    })
+}
+
+/// Get an inline element held directly by a block, or null if `index` is
+/// out of range
+///
+/// # Safety
+/// - `block` must be a valid block handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline(
+    block: *const BlockHandle,
+    index: usize,
+) -> *const InlineHandle {
+    guard(ptr::null(), || {
+        if block.is_null() {
+            return ptr::null();
+        }
+        let inline = as_block(block)
+            .inline_groups()
+            .into_iter()
+            .flatten()
+            .nth(index);
+        inline_handle(inline)
+    })
+}
+
+/// Get the kind of an inline element, or `Invalid` for a null handle
+///
+/// # Safety
+/// - `inline` must be a valid inline handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_type(inline: *const InlineHandle) -> FfiInlineType {
+    guard(FfiInlineType::Invalid, || {
+        if inline.is_null() {
+            return FfiInlineType::Invalid;
+        }
+        as_inline(inline).into()
+    })
+}
+
+/// Get the plain text of an inline element and everything nested in it
+///
+/// # Safety
+/// - `inline` must be a valid inline handle
+/// - `out_text` must be a valid pointer
+/// - `out_length` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_text(
+    inline: *const InlineHandle,
+    out_text: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if inline.is_null() || out_text.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let text = match as_inline(inline) {
+            Inline::Math { content, .. }
+            | Inline::RawInline { content, .. }
+            | Inline::Keyboard { content } => content.clone(),
+            Inline::Image { alt, .. } => alt.clone(),
+            inline => inline_text([inline]),
+        };
+        write_string(text, out_text, out_length)
+    })
+}
+
+/// Get the target of a link or the source of an image
+///
+/// Fails with `InvalidInput` for any other inline element.
+///
+/// # Safety
+/// - `inline` must be a valid inline handle
+/// - `out_url` must be a valid pointer
+/// - `out_length` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_url(
+    inline: *const InlineHandle,
+    out_url: *mut *mut u8,
+    out_length: *mut usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if inline.is_null() || out_url.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        match as_inline(inline) {
+            Inline::Link { url, .. } | Inline::Image { url, .. } => {
+                write_string(url.clone(), out_url, out_length)
+            }
+            _ => fail(FfiResult::InvalidInput, "Only links and images have a URL"),
+        }
+    })
+}
+
+/// Get the number of inline elements nested directly inside an inline
+/// element
+///
+/// # Safety
+/// - `inline` must be a valid inline handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_child_count(inline: *const InlineHandle) -> usize {
+    guard(0, || {
+        if inline.is_null() {
+            return 0;
+        }
+        as_inline(inline).children().len()
+    })
+}
+
+/// Get an inline element nested directly inside an inline element, or null
+/// if `index` is out of range
+///
+/// # Safety
+/// - `inline` must be a valid inline handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_get_inline_child(
+    inline: *const InlineHandle,
+    index: usize,
+) -> *const InlineHandle {
+    guard(ptr::null(), || {
+        if inline.is_null() {
+            return ptr::null();
+        }
+        inline_handle(as_inline(inline).children().get(index).copied())
+    })
+}
+
+/// Insert a paragraph of `text` so that it becomes top-level block `index`
+///
+/// `index` may equal the block count, to append. Lines of `text` are joined
+/// by soft breaks. Block and inline handles taken before the edit are no
+/// longer valid.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `text` must be a valid null-terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_insert_paragraph(
+    handle: *mut DocumentHandle,
+    index: usize,
+    text: *const c_char,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || text.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let text = match CStr::from_ptr(text).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let paragraph = Block::Paragraph {
+            content: text_inlines(text),
+            span: None,
+        };
+        match edit::insert_block_at(&mut (*handle).doc, &[index], paragraph) {
+            Ok(_) => FfiResult::Success,
+            Err(e) => fail(FfiResult::InvalidInput, e),
+        }
+    })
+}
+
+/// Delete top-level block `index`, with everything nested in it
+///
+/// Block and inline handles taken before the edit are no longer valid.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_delete_block(
+    handle: *mut DocumentHandle,
+    index: usize,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let blocks = &mut (*handle).doc.content;
+        if index >= blocks.len() {
+            return fail(
+                FfiResult::InvalidInput,
+                edit::EditError::NoSuchBlock(vec![index]),
+            );
+        }
+        blocks.remove(index);
+        FfiResult::Success
+    })
+}
+
+/// Set the title of a document; an empty `title` removes it
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `title` must be a valid null-terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_set_title(
+    handle: *mut DocumentHandle,
+    title: *const c_char,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || title.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let title = match CStr::from_ptr(title).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        (*handle).doc.meta.title = (!title.is_empty()).then(|| title.to_string());
+        FfiResult::Success
+    })
+}
+
+/// Replace the text of top-level block `index`
+///
+/// Paragraphs and headings get `text` as plain inline content, dropping any
+/// formatting, with lines joined by soft breaks; code, math and raw blocks
+/// get it as written. Fails with `InvalidInput` for any other block. Block
+/// and inline handles taken before the edit are no longer valid.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `text` must be a valid null-terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_set_block_text(
+    handle: *mut DocumentHandle,
+    index: usize,
+    text: *const c_char,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || text.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+        let text = match CStr::from_ptr(text).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let doc = &mut (*handle).doc;
+        let Some(block) = doc.content.get_mut(index) else {
+            return fail(
+                FfiResult::InvalidInput,
+                edit::EditError::NoSuchBlock(vec![index]),
+            );
+        };
+        match block {
+            Block::Paragraph { content, span } | Block::Heading { content, span, .. } => {
+                *content = text_inlines(text);
+                *span = None;
+            }
+            Block::CodeBlock { content, span, .. }
+            | Block::MathBlock { content, span, .. }
+            | Block::Raw { content, span, .. } => {
+                *content = text.to_string();
+                *span = None;
+            }
+            _ => {
+                return fail(
+                    FfiResult::InvalidInput,
+                    "Only paragraphs, headings, code, math and raw blocks have settable text",
+                )
+            }
+        }
+        FfiResult::Success
+    })
+}
+
+/// Plain inline content for `text`, one soft break between lines
+fn text_inlines(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if n > 0 {
+            inlines.push(Inline::SoftBreak);
+        }
+        if !line.is_empty() {
+            inlines.push(Inline::Text {
+                content: line.to_string(),
+            });
+        }
+    }
+    inlines
+}
+
+fn block_handle(block: Option<&Block>) -> *const BlockHandle {
+    block.map_or(ptr::null(), |block| {
+        (block as *const Block).cast::<BlockHandle>()
+    })
+}
+
+fn inline_handle(inline: Option<&Inline>) -> *const InlineHandle {
+    inline.map_or(ptr::null(), |inline| {
+        (inline as *const Inline).cast::<InlineHandle>()
+    })
+}
+
+/// # Safety
+/// `block` must come from [`block_handle`] and its document must be alive
+unsafe fn as_block<'a>(block: *const BlockHandle) -> &'a Block {
+    &*block.cast::<Block>()
+}
+
+/// # Safety
+/// `inline` must come from [`inline_handle`] and its document must be alive
+unsafe fn as_inline<'a>(inline: *const InlineHandle) -> &'a Inline {
+    &*inline.cast::<Inline>()
+}
+
+/// Hand `text` to the caller, who frees it with `formatrix_free_buffer`
+unsafe fn write_string(text: String, out_data: *mut *mut u8, out_length: *mut usize) -> FfiResult {
+    let bytes = text.into_bytes().into_boxed_slice();
+    *out_length = bytes.len();
+    *out_data = Box::into_raw(bytes).cast::<u8>();
+    FfiResult::Success
+}
+
+/// Free a document handle
 ///
 /// # Safety
 /// - `handle` must be a valid document handle or null
 #[no_mangle]
 pub unsafe extern "C" fn formatrix_free_document(handle: *mut DocumentHandle) {
-    if !handle.is_null() {
-        drop(Box::from_raw(handle));
-    }
+    guard((), || {
+        if !handle.is_null() {
+            drop(Box::from_raw(handle));
+        }
+    })
 }
 
-/// Free a string allocated by the library
+/// Free a buffer the library handed out
 ///
 /// # Safety
-/// - `s` must be a valid string from this library or null
+/// - `data` must be a buffer from this library, with the `length` that came
+///   with it, or null
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_free_string(s: *mut c_char) {
-    if !s.is_null() {
-        drop(CString::from_raw(s));
-    }
+pub unsafe extern "C" fn formatrix_free_buffer(data: *mut u8, length: usize) {
+    guard((), || {
+        if !data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, length)));
+        }
+    })
+}
+
+/// Version of the C API this library provides
+///
+/// Raised whenever a function or type changes in a way existing callers
+/// cannot cope with; additions alone leave it. A caller built against the
+/// same value can use every declaration in its header.
+pub const FORMATRIX_ABI_VERSION: u32 = 3;
+
+/// Cargo features this build may have, and whether it does
+const FEATURES: &[(&str, bool)] = &[
+    ("markdown", cfg!(feature = "markdown")),
+    ("djot", cfg!(feature = "djot")),
+    ("orgmode", cfg!(feature = "orgmode")),
+    ("rst", cfg!(feature = "rst")),
+    ("typst", cfg!(feature = "typst")),
+    ("asciidoc", cfg!(feature = "asciidoc")),
+    ("link-check", cfg!(feature = "link-check")),
+    ("spellcheck", cfg!(feature = "spellcheck")),
+    ("async", cfg!(feature = "async")),
+    ("watch", cfg!(feature = "watch")),
+    ("archives", cfg!(feature = "archives")),
+    ("http", cfg!(feature = "http")),
+];
+
+/// Get the version of the C API, to compare with `FORMATRIX_ABI_VERSION`
+/// from the header a caller was built against
+#[no_mangle]
+pub extern "C" fn formatrix_abi_version() -> u32 {
+    FORMATRIX_ABI_VERSION
+}
+
+/// Whether the library was built with the Cargo feature `name`
+///
+/// False for names the library does not know, so a caller can ask about
+/// features newer than the library.
+///
+/// # Safety
+/// - `name` must be a valid null-terminated string or null
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_has_feature(name: *const c_char) -> bool {
+    guard(false, || {
+        if name.is_null() {
+            return false;
+        }
+        let name = CStr::from_ptr(name).to_bytes();
+        FEATURES
+            .iter()
+            .any(|&(feature, enabled)| enabled && feature.as_bytes() == name)
+    })
+}
+
+/// Free a UTF-16 buffer the library handed out
+///
+/// # Safety
+/// - `data` must be a buffer from `formatrix_render_utf16`, with the
+///   `length` that came with it, or null
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_free_buffer_utf16(data: *mut u16, length: usize) {
+    guard((), || {
+        if !data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, length)));
+        }
+    })
 }
 
 /// Get library version
@@ -334,48 +1950,50 @@ pub extern "C" fn formatrix_version() -> *const c_char {
 /// - `content` must be a valid null-terminated UTF-8 string
 #[no_mangle]
 pub unsafe extern "C" fn formatrix_detect_format(content: *const c_char) -> FfiFormat {
-    if content.is_null() {
-        return FfiFormat::PlainText;
-    }
+    guard(FfiFormat::PlainText, || {
+        if content.is_null() {
+            return FfiFormat::PlainText;
+        }
 
-    let content_str = match CStr::from_ptr(content).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiFormat::PlainText,
-    };
+        let content_str = match CStr::from_ptr(content).to_str() {
+            Ok(s) => s,
+            Err(_) => return FfiFormat::PlainText,
+        };
 
-    let trimmed = content_str.trim();
+        let trimmed = content_str.trim();
 
-    // Check for org-mode markers
-    if trimmed.starts_with("#+") || trimmed.contains("\n#+") {
-        return FfiFormat::OrgMode;
-    }
+        // Check for org-mode markers
+        if trimmed.starts_with("#+") || trimmed.contains("\n#+") {
+            return FfiFormat::OrgMode;
+        }
 
-    // Check for AsciiDoc markers
-    if trimmed.starts_with("= ") || trimmed.starts_with(":toc:") {
-        return FfiFormat::AsciiDoc;
-    }
+        // Check for AsciiDoc markers
+        if trimmed.starts_with("= ") || trimmed.starts_with(":toc:") {
+            return FfiFormat::AsciiDoc;
+        }
 
-    // Check for Markdown markers
-    if trimmed.starts_with("# ") || trimmed.contains("```") {
-        return FfiFormat::Markdown;
-    }
+        // Check for Markdown markers
+        if trimmed.starts_with("# ") || trimmed.contains("```") {
+            return FfiFormat::Markdown;
+        }
 
-    // Check for Djot markers
-    if trimmed.contains("{.") || trimmed.contains("[^") {
-        return FfiFormat::Djot;
-    }
+        // Check for Djot markers
+        if trimmed.contains("{.") || trimmed.contains("[^") {
+            return FfiFormat::Djot;
+        }
 
-    // Check for RST markers
-    if trimmed.contains(".. ") && trimmed.contains("::") {
-        return FfiFormat::ReStructuredText;
-    }
+        // Check for RST markers
+        if trimmed.contains(".. ") && trimmed.contains("::") {
+            return FfiFormat::ReStructuredText;
+        }
 
-    // Check for Typst markers
-    if trimmed.contains("#let") || trimmed.contains("#{") {
-        return FfiFormat::Typst;
-    }
+        // Check for Typst markers
+        if trimmed.contains("#let") || trimmed.contains("#{") {
+            return FfiFormat::Typst;
+        }
 
-    FfiFormat::PlainText
+        FfiFormat::PlainText
+    })
 }
 
 /// Convert content from one format to another
@@ -387,140 +2005,441 @@ pub extern "C" fn formatrix_version() -> *const c_char {
     content: *const c_char,
     from_format: FfiFormat,
     to_format: FfiFormat,
-    out_content: *mut *mut c_char,
+    out_content: *mut *mut u8,
     out_length: *mut usize,
 ) -> FfiResult {
-    if content.is_null() || out_content.is_null() || out_length.is_null() {
-        return FfiResult::NullPointer;
+    guard(FfiResult::InternalError, || {
+        if content.is_null() || out_content.is_null() || out_length.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        // Parse input
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let parse_result = formatrix_parse(content, from_format, &mut handle);
+        if parse_result != FfiResult::Success {
+            return parse_result;
+        }
+
+        // Render output
+        let render_result = formatrix_render(handle, to_format, out_content, out_length);
+
+        // Free the handle
+        formatrix_free_document(handle);
+
+        render_result
+    })
+}
+
+/// Open a file and parse it into a document handle
+///
+/// # Safety
+/// - `path` must be a valid null-terminated UTF-8 file path
+/// - `out_handle` must be a valid pointer to store the result
+/// - `out_format` must be a valid pointer to store the detected format
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_open_file(
+    path: *const c_char,
+    out_handle: *mut *mut DocumentHandle,
+    out_format: *mut FfiFormat,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if path.is_null() || out_handle.is_null() || out_format.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let path_str = match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        use crate::file_ops;
+        match file_ops::open_file(path_str) {
+            Ok(opened) => {
+                *out_format = opened.file_info.format.into();
+                let handle = Box::new(DocumentHandle {
+                    doc: opened.document,
+                });
+                *out_handle = Box::into_raw(handle);
+                FfiResult::Success
+            }
+            Err(e) => fail(open_failure(&e), e),
+        }
+    })
+}
+
+/// Save a document to a file
+///
+/// Format is determined by the file extension. If no extension matches,
+/// uses the document's source format.
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `path` must be a valid null-terminated UTF-8 file path
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_save_file(
+    handle: *const DocumentHandle,
+    path: *const c_char,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || path.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let path_str = match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let doc = &(*handle).doc;
+
+        use crate::file_ops;
+        match file_ops::save_file(doc, path_str) {
+            Ok(()) => FfiResult::Success,
+            Err(e) => fail(save_failure(&e), e),
+        }
+    })
+}
+
+/// Save a document to a file in a specific format
+///
+/// # Safety
+/// - `handle` must be a valid document handle
+/// - `path` must be a valid null-terminated UTF-8 file path
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_save_file_as(
+    handle: *const DocumentHandle,
+    path: *const c_char,
+    format: FfiFormat,
+) -> FfiResult {
+    guard(FfiResult::InternalError, || {
+        if handle.is_null() || path.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
+
+        let path_str = match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let doc = &(*handle).doc;
+        let target_format: SourceFormat = format.into();
+
+        use crate::file_ops;
+        use crate::traits::RenderConfig;
+        match file_ops::save_file_as(doc, path_str, target_format, &RenderConfig::default()) {
+            Ok(()) => FfiResult::Success,
+            Err(e) => fail(save_failure(&e), e),
+        }
+    })
+}
+
+/// Result code for a failure to open a file
+fn open_failure(error: &FileError) -> FfiResult {
+    match error {
+        FileError::Io(_) => FfiResult::InvalidInput,
+        FileError::Parse(_) => FfiResult::ParseError,
+        FileError::UnknownFormat { .. } => FfiResult::UnsupportedFormat,
+        FileError::UnsupportedFormat { .. } => FfiResult::UnsupportedFormat,
+        FileError::Render(_) => FfiResult::RenderError,
+        // Not produced by the non-interactive default
+        FileError::Cancelled => FfiResult::InvalidInput,
+        FileError::IncludeOutsideRoot { .. } | FileError::IncludeCycle { .. } => {
+            FfiResult::InvalidInput
+        }
+        FileError::Archive(_) => FfiResult::InvalidInput,
+        FileError::LimitExceeded(_) => FfiResult::ParseError,
+        // Not produced when opening a path
+        FileError::Http { .. } => FfiResult::InvalidInput,
+        // Only produced when saving
+        FileError::Unencodable { .. } => FfiResult::RenderError,
+        // Only produced by the edit-lock functions
+        FileError::Locked { .. } | FileError::ExternallyModified { .. } => FfiResult::InvalidInput,
+        // Only produced by find and replace
+        FileError::Find(_) => FfiResult::InvalidInput,
     }
+}
 
-    // Parse input
-    let mut handle: *mut DocumentHandle = ptr::null_mut();
-    let parse_result = formatrix_parse(content, from_format, &mut handle);
-    if parse_result != FfiResult::Success {
-        return parse_result;
+/// Result code for a failure to save a file
+fn save_failure(error: &FileError) -> FfiResult {
+    match error {
+        FileError::Io(_) => FfiResult::InvalidInput,
+        FileError::Render(_) => FfiResult::RenderError,
+        FileError::UnsupportedFormat { .. } => FfiResult::UnsupportedFormat,
+        _ => FfiResult::RenderError,
     }
+}
 
-    // Render output
-    let render_result = formatrix_render(handle, to_format, out_content, out_length);
+/// Shared flag for stopping background work, from
+/// `formatrix_cancel_token_new`
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
 
-    // Free the handle
-    formatrix_free_document(handle);
+/// Step of a conversion started by `formatrix_convert_file_async`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FfiConvertStage {
+    /// Reading and parsing the input
+    Opening = 0,
+    /// Rendering and writing the output
+    Saving = 1,
+    /// Done; the result says how it went
+    Finished = 2,
+}
 
-    render_result
+/// Told how a background conversion is going
+///
+/// Runs on the conversion's own thread. `result` is `Success` until the
+/// `Finished` stage; `message` is null on success and otherwise describes
+/// the failure, valid only during the call.
+pub type FfiProgressCallback = Option<
+    unsafe extern "C" fn(
+        stage: FfiConvertStage,
+        result: FfiResult,
+        message: *const c_char,
+        userdata: *mut c_void,
+    ),
+>;
+
+/// The caller's `userdata`, carried to a library thread
+struct Userdata(*mut c_void);
+
+// The caller promises, by passing it, that `userdata` may be used from the
+// library's threads
+unsafe impl Send for Userdata {}
+
+/// Create a cancel token, for `formatrix_convert_file_async`
+///
+/// One token may be shared by several conversions, to stop a whole batch.
+#[no_mangle]
+pub extern "C" fn formatrix_cancel_token_new() -> *mut CancelToken {
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(CancelToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }))
+    })
 }
 
-/// Open a file and parse it into a document handle
+/// Ask the work using a cancel token to stop
+///
+/// Work stops at its next checkpoint rather than at once.
 ///
 /// # Safety
-/// - `path` must be a valid null-terminated UTF-8 file path
-/// - `out_handle` must be a valid pointer to store the result
-/// - `out_format` must be a valid pointer to store the detected format
+/// - `token` must be a valid cancel token or null
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_open_file(
-    path: *const c_char,
-    out_handle: *mut *mut DocumentHandle,
-    out_format: *mut FfiFormat,
-) -> FfiResult {
-    if path.is_null() || out_handle.is_null() || out_format.is_null() {
-        return FfiResult::NullPointer;
-    }
-
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiResult::Utf8Error,
-    };
+pub unsafe extern "C" fn formatrix_cancel(token: *const CancelToken) {
+    guard((), || {
+        if !token.is_null() {
+            (*token).cancelled.store(true, Ordering::Relaxed);
+        }
+    })
+}
 
-    use crate::file_ops;
-    match file_ops::open_file(path_str) {
-        Ok(opened) => {
-            *out_format = opened.file_info.format.into();
-            let handle = Box::new(DocumentHandle {
-                doc: opened.document,
-            });
-            *out_handle = Box::into_raw(handle);
-            FfiResult::Success
+/// Free a cancel token
+///
+/// Conversions already using it keep their own reference, so it may be
+/// freed before they finish; they can then no longer be cancelled.
+///
+/// # Safety
+/// - `token` must be a valid cancel token or null
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_cancel_token_free(token: *mut CancelToken) {
+    guard((), || {
+        if !token.is_null() {
+            drop(Box::from_raw(token));
         }
-        Err(e) => match e {
-            file_ops::FileError::Io(_) => FfiResult::InvalidInput,
-            file_ops::FileError::Parse(_) => FfiResult::ParseError,
-            file_ops::FileError::UnknownFormat { .. } => FfiResult::UnsupportedFormat,
-            file_ops::FileError::UnsupportedFormat { .. } => FfiResult::UnsupportedFormat,
-            file_ops::FileError::Render(_) => FfiResult::RenderError,
-        },
-    }
+    })
 }
 
-/// Save a document to a file
+/// Convert the file at `input` to `to_format`, writing it to `output`, on a
+/// background thread
 ///
-/// Format is determined by the file extension. If no extension matches,
-/// uses the document's source format.
+/// Returns as soon as the conversion has started. `progress` is called as
+/// each stage starts and once more with the `Finished` stage and the
+/// outcome, which is `Cancelled` if `cancel` was triggered first; the
+/// token is checked between stages. `cancel` may be null.
 ///
 /// # Safety
-/// - `handle` must be a valid document handle
-/// - `path` must be a valid null-terminated UTF-8 file path
+/// - `input` and `output` must be valid null-terminated UTF-8 file paths
+/// - `progress` must be safe to call with `userdata` from another thread
+/// - `cancel` must be a valid cancel token or null
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_save_file(
-    handle: *const DocumentHandle,
-    path: *const c_char,
+pub unsafe extern "C" fn formatrix_convert_file_async(
+    input: *const c_char,
+    output: *const c_char,
+    to_format: FfiFormat,
+    progress: FfiProgressCallback,
+    userdata: *mut c_void,
+    cancel: *const CancelToken,
 ) -> FfiResult {
-    if handle.is_null() || path.is_null() {
-        return FfiResult::NullPointer;
-    }
-
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiResult::Utf8Error,
-    };
+    guard(FfiResult::InternalError, || {
+        let Some(progress) = progress else {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        };
+        if input.is_null() || output.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
 
-    let doc = &(*handle).doc;
+        let (input, output) = match (
+            CStr::from_ptr(input).to_str(),
+            CStr::from_ptr(output).to_str(),
+        ) {
+            (Ok(input), Ok(output)) => (PathBuf::from(input), PathBuf::from(output)),
+            (Err(e), _) | (_, Err(e)) => return fail(FfiResult::Utf8Error, e),
+        };
+        let cancelled = match cancel.as_ref() {
+            Some(token) => Arc::clone(&token.cancelled),
+            None => Arc::new(AtomicBool::new(false)),
+        };
+        let userdata = Userdata(userdata);
+
+        let job = move || {
+            let userdata = &userdata;
+            let report = |stage, result, message: Option<String>| {
+                let message = message.and_then(|m| CString::new(m.replace('\0', "")).ok());
+                let message = message.as_ref().map_or(ptr::null(), |m| m.as_ptr());
+                progress(stage, result, message, userdata.0);
+            };
+            let is_cancelled = || cancelled.load(Ordering::Relaxed);
+
+            let convert = || {
+                if is_cancelled() {
+                    return (FfiResult::Cancelled, None);
+                }
+                report(FfiConvertStage::Opening, FfiResult::Success, None);
+                let opened = match crate::file_ops::open_file(&input) {
+                    Ok(opened) => opened,
+                    Err(e) => return (open_failure(&e), Some(e.to_string())),
+                };
+
+                if is_cancelled() {
+                    return (FfiResult::Cancelled, None);
+                }
+                report(FfiConvertStage::Saving, FfiResult::Success, None);
+                let config = RenderConfig::default();
+                match crate::file_ops::save_file_as(
+                    &opened.document,
+                    &output,
+                    to_format.into(),
+                    &config,
+                ) {
+                    Ok(()) => (FfiResult::Success, None),
+                    Err(e) => (save_failure(&e), Some(e.to_string())),
+                }
+            };
+            // A panic must still end in the Finished callback the caller
+            // is waiting for
+            let (result, message) =
+                panic::catch_unwind(AssertUnwindSafe(convert)).unwrap_or_else(|payload| {
+                    let message = panic_message(payload.as_ref());
+                    (
+                        FfiResult::InternalError,
+                        Some(format!("Internal error: {message}")),
+                    )
+                });
+            report(FfiConvertStage::Finished, result, message);
+        };
+
+        match std::thread::Builder::new()
+            .name("formatrix-convert".to_string())
+            .spawn(job)
+        {
+            Ok(_) => FfiResult::Success,
+            Err(e) => fail(FfiResult::InternalError, e),
+        }
+    })
+}
 
-    use crate::file_ops;
-    match file_ops::save_file(doc, path_str) {
-        Ok(()) => FfiResult::Success,
-        Err(e) => match e {
-            file_ops::FileError::Io(_) => FfiResult::InvalidInput,
-            file_ops::FileError::Render(_) => FfiResult::RenderError,
-            file_ops::FileError::UnsupportedFormat { .. } => FfiResult::UnsupportedFormat,
-            _ => FfiResult::RenderError,
-        },
-    }
+/// Opaque handle to a file watch, from `formatrix_watch_file`
+#[cfg(feature = "watch")]
+pub struct WatchHandle {
+    _watcher: crate::file_ops::FileWatcher,
+    /// Held while the callback runs; false once the watch is stopped
+    active: Arc<Mutex<bool>>,
 }
 
-/// Save a document to a file in a specific format
+/// Told that a watched file changed on disk
+///
+/// Runs on the library's watcher thread, not the caller's. `path` is the
+/// path given to `formatrix_watch_file`, valid only during the call.
+#[cfg(feature = "watch")]
+pub type FfiWatchCallback =
+    Option<unsafe extern "C" fn(path: *const c_char, userdata: *mut c_void)>;
+
+/// Call `callback` whenever the file at `path` changes on disk
+///
+/// Changes are debounced, so a save reports once, and the watch follows a
+/// save that renames a new file over the old one; removing the file is
+/// also reported. Stop watching with `formatrix_unwatch`. Only built with
+/// the `watch` feature.
 ///
 /// # Safety
-/// - `handle` must be a valid document handle
-/// - `path` must be a valid null-terminated UTF-8 file path
+/// - `path` must be a valid null-terminated UTF-8 path to an existing file
+/// - `callback` must be safe to call with `userdata` from another thread
+/// - `out_watch` must be a valid pointer to store the result
+#[cfg(feature = "watch")]
 #[no_mangle]
-pub unsafe extern "C" fn formatrix_save_file_as(
-    handle: *const DocumentHandle,
+pub unsafe extern "C" fn formatrix_watch_file(
     path: *const c_char,
-    format: FfiFormat,
+    callback: FfiWatchCallback,
+    userdata: *mut c_void,
+    out_watch: *mut *mut WatchHandle,
 ) -> FfiResult {
-    if handle.is_null() || path.is_null() {
-        return FfiResult::NullPointer;
-    }
-
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiResult::Utf8Error,
-    };
+    guard(FfiResult::InternalError, || {
+        let Some(callback) = callback else {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        };
+        if path.is_null() || out_watch.is_null() {
+            return fail(FfiResult::NullPointer, NULL_ARGUMENT);
+        }
 
-    let doc = &(*handle).doc;
-    let target_format: SourceFormat = format.into();
+        let path = CStr::from_ptr(path);
+        let path_str = match path.to_str() {
+            Ok(s) => s,
+            Err(e) => return fail(FfiResult::Utf8Error, e),
+        };
+
+        let path = path.to_owned();
+        let userdata = Userdata(userdata);
+        let active = Arc::new(Mutex::new(true));
+        let still_active = Arc::clone(&active);
+        let watched = crate::file_ops::watch_file(path_str, move || {
+            let userdata = &userdata;
+            let active = still_active.lock().unwrap_or_else(PoisonError::into_inner);
+            if *active {
+                callback(path.as_ptr(), userdata.0);
+            }
+        });
+        match watched {
+            Ok(watcher) => {
+                let handle = Box::new(WatchHandle {
+                    _watcher: watcher,
+                    active,
+                });
+                *out_watch = Box::into_raw(handle);
+                FfiResult::Success
+            }
+            Err(e) => fail(FfiResult::InvalidInput, e),
+        }
+    })
+}
 
-    use crate::file_ops;
-    use crate::traits::RenderConfig;
-    match file_ops::save_file_as(doc, path_str, target_format, &RenderConfig::default()) {
-        Ok(()) => FfiResult::Success,
-        Err(e) => match e {
-            file_ops::FileError::Io(_) => FfiResult::InvalidInput,
-            file_ops::FileError::Render(_) => FfiResult::RenderError,
-            file_ops::FileError::UnsupportedFormat { .. } => FfiResult::UnsupportedFormat,
-            _ => FfiResult::RenderError,
-        },
-    }
+/// Stop a file watch and free its handle
+///
+/// Waits for a callback that is already running, so once this returns the
+/// callback is not called again; do not call it from the callback.
+///
+/// # Safety
+/// - `watch` must be a valid watch handle or null
+#[cfg(feature = "watch")]
+#[no_mangle]
+pub unsafe extern "C" fn formatrix_unwatch(watch: *mut WatchHandle) {
+    guard((), || {
+        if !watch.is_null() {
+            let watch = Box::from_raw(watch);
+            *watch.active.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        }
+    })
 }
 
 /// Detect format from file path (by extension)
@@ -529,22 +2448,24 @@ pub extern "C" fn formatrix_version() -> *const c_char {
 /// - `path` must be a valid null-terminated UTF-8 file path
 #[no_mangle]
 pub unsafe extern "C" fn formatrix_detect_file_format(path: *const c_char) -> FfiFormat {
-    if path.is_null() {
-        return FfiFormat::PlainText;
-    }
+    guard(FfiFormat::PlainText, || {
+        if path.is_null() {
+            return FfiFormat::PlainText;
+        }
 
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return FfiFormat::PlainText,
-    };
+        let path_str = match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(_) => return FfiFormat::PlainText,
+        };
 
-    use crate::file_ops;
-    use std::path::Path;
+        use crate::file_ops;
+        use std::path::Path;
 
-    match file_ops::format_from_extension(Path::new(path_str)) {
-        Some(format) => format.into(),
-        None => FfiFormat::PlainText,
-    }
+        match file_ops::format_from_extension(Path::new(path_str)) {
+            Some(format) => format.into(),
+            None => FfiFormat::PlainText,
+        }
+    })
 }
 
 /// Get the file extension for a format
@@ -578,6 +2499,15 @@ mod tests {
     use super::*;
     use std::ffi::CString;
 
+    /// Copy a buffer the library handed out, then free it
+    unsafe fn take_string(data: *mut u8, length: usize) -> String {
+        let text = std::str::from_utf8(std::slice::from_raw_parts(data, length))
+            .unwrap()
+            .to_string();
+        formatrix_free_buffer(data, length);
+        text
+    }
+
     #[test]
     fn test_parse_and_render() {
         let content = CString::new("# Hello\n\nWorld").unwrap();
@@ -595,6 +2525,754 @@ fn test_parse_and_render() {
         }
     }
 
+    #[test]
+    fn test_parse_with_options() {
+        let content = CString::new("a\tb\n").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+
+        unsafe {
+            let options = FfiParseOptions {
+                preserve_raw_source: true,
+                tab_width: 4,
+                ..Default::default()
+            };
+            let result = formatrix_parse_with_options(
+                content.as_ptr(),
+                FfiFormat::PlainText,
+                &options,
+                &mut handle,
+            );
+            assert_eq!(result, FfiResult::Success);
+
+            let doc = &(*handle).doc;
+            assert_eq!(doc.raw_source.as_deref(), Some("a   b\n"));
+            formatrix_free_document(handle);
+
+            let content = CString::new("See :doc:`intro`.\n").unwrap();
+            let options = FfiParseOptions {
+                rst_flavor: FfiRstFlavor::Sphinx,
+                ..Default::default()
+            };
+            formatrix_parse_with_options(
+                content.as_ptr(),
+                FfiFormat::ReStructuredText,
+                &options,
+                &mut handle,
+            );
+            let doc = &(*handle).doc;
+            let Block::Paragraph { content, .. } = &doc.content[0] else {
+                panic!("expected a paragraph");
+            };
+            assert!(matches!(content[1], Inline::Link { .. }));
+            formatrix_free_document(handle);
+
+            let deep = CString::new(">".repeat(50_000) + " x").unwrap();
+            for strict in [true, false] {
+                let options = FfiParseOptions {
+                    strict,
+                    max_depth: 128,
+                    ..Default::default()
+                };
+                let result = formatrix_parse_with_options(
+                    deep.as_ptr(),
+                    FfiFormat::Markdown,
+                    &options,
+                    &mut handle,
+                );
+                assert_eq!(result, FfiResult::ParseError);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sized_options() {
+        let content = CString::new(">".repeat(200) + " x").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let parse = |options: &FfiParseOptions, handle: &mut *mut DocumentHandle| unsafe {
+            formatrix_parse_with_options(content.as_ptr(), FfiFormat::Markdown, options, handle)
+        };
+
+        unsafe {
+            // A header from before the limits ends at `max_input_bytes`, so
+            // what follows it in memory is not read
+            let mut options = FfiParseOptions {
+                max_depth: 2,
+                ..Default::default()
+            };
+            options.size = mem::offset_of!(FfiParseOptions, max_input_bytes);
+            assert_eq!(parse(&options, &mut handle), FfiResult::Success);
+            formatrix_free_document(handle);
+            options.size = mem::size_of::<FfiParseOptions>();
+            assert_eq!(parse(&options, &mut handle), FfiResult::ParseError);
+            options.size = 0;
+            assert_eq!(parse(&options, &mut handle), FfiResult::InvalidInput);
+
+            // A newer header's fields are ignored
+            #[repr(C)]
+            struct Newer {
+                options: FfiParseOptions,
+                added: u64,
+            }
+            let newer = Newer {
+                options: FfiParseOptions {
+                    size: mem::size_of::<Newer>(),
+                    max_depth: 2,
+                    ..Default::default()
+                },
+                added: u64::MAX,
+            };
+            assert_eq!(parse(&newer.options, &mut handle), FfiResult::ParseError);
+
+            // Defaults go no further than the caller's size
+            let mut render = FfiRenderOptions {
+                line_width: 7,
+                ..Default::default()
+            };
+            let size = mem::offset_of!(FfiRenderOptions, line_width);
+            assert_eq!(
+                formatrix_render_options_init(&mut render, size),
+                FfiResult::Success
+            );
+            assert_eq!(render.size, size);
+            assert_eq!(render.line_width, 7);
+
+            let mut options = FfiParseOptions {
+                strict: false,
+                ..Default::default()
+            };
+            let size = mem::size_of::<FfiParseOptions>();
+            assert_eq!(
+                formatrix_parse_options_init(&mut options, size),
+                FfiResult::Success
+            );
+            assert!(options.strict);
+        }
+    }
+
+    #[test]
+    fn test_render_with_options() {
+        let content = CString::new("# Hello\n\nWorld").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let options = FfiRenderOptions {
+                line_ending: FfiLineEnding::CrLf,
+                final_newline: FfiFinalNewline::Single,
+                ..Default::default()
+            };
+            let result = formatrix_render_with_options(
+                handle,
+                FfiFormat::Djot,
+                &options,
+                &mut out,
+                &mut length,
+            );
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(take_string(out, length), "# Hello\r\n\r\nWorld\r\n");
+
+            let options = FfiRenderOptions {
+                profile: FfiStyleProfile::Kramdown,
+                heading_style: FfiHeadingStyle::Setext,
+                ..Default::default()
+            };
+            formatrix_render_with_options(
+                handle,
+                FfiFormat::Markdown,
+                &options,
+                &mut out,
+                &mut length,
+            );
+            assert_eq!(take_string(out, length), "Hello\n=====\n\nWorld");
+            formatrix_free_document(handle);
+
+            let content = CString::new("<aside>Note</aside>\n\nText").unwrap();
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let options = FfiRenderOptions {
+                raw_policy: FfiRawPolicy::Drop,
+                ..Default::default()
+            };
+            formatrix_render_with_options(handle, FfiFormat::Djot, &options, &mut out, &mut length);
+            assert_eq!(take_string(out, length), "Text");
+
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_render_with_profile() {
+        let content = CString::new("- *a*").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let options = FfiRenderOptions::default();
+            let profile = CString::new("Kramdown").unwrap();
+            let result = formatrix_render_with_profile(
+                handle,
+                FfiFormat::Markdown,
+                &options,
+                profile.as_ptr(),
+                &mut out,
+                &mut length,
+            );
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(take_string(out, length), "* _a_");
+
+            let profile = CString::new("docbook").unwrap();
+            let result = formatrix_render_with_profile(
+                handle,
+                FfiFormat::Markdown,
+                &options,
+                profile.as_ptr(),
+                &mut out,
+                &mut length,
+            );
+            assert_eq!(result, FfiResult::InvalidInput);
+
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_nul_in_output() {
+        let content = CString::new("# Hello").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let doc = &mut (*handle).doc;
+            doc.meta.title = Some("a\0b".to_string());
+
+            let result = formatrix_get_title(handle, &mut out, &mut length);
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(length, 3);
+            assert_eq!(take_string(out, length), "a\0b");
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_render_stream() {
+        unsafe extern "C" fn collect(
+            data: *const u8,
+            length: usize,
+            userdata: *mut c_void,
+        ) -> bool {
+            let chunks = &mut *userdata.cast::<Vec<String>>();
+            let chunk = std::slice::from_raw_parts(data, length);
+            chunks.push(std::str::from_utf8(chunk).unwrap().to_string());
+            chunks.len() < 3
+        }
+        unsafe extern "C" fn keep(data: *const u8, length: usize, userdata: *mut c_void) -> bool {
+            let all = &mut *userdata.cast::<Vec<u8>>();
+            all.extend_from_slice(std::slice::from_raw_parts(data, length));
+            true
+        }
+
+        let content = CString::new("# Hello\n\nWorld\n\n- a\n- b\n\nEnd").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+        let mut chunks: Vec<String> = Vec::new();
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let title = CString::new("Greeting").unwrap();
+            formatrix_set_title(handle, title.as_ptr());
+
+            let result = formatrix_render_stream(
+                handle,
+                FfiFormat::Markdown,
+                Some(collect),
+                (&mut chunks as *mut Vec<String>).cast(),
+            );
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(
+                chunks,
+                ["---\ntitle: Greeting\n---\n\n", "# Hello", "\n\nWorld"]
+            );
+
+            let mut all = Vec::new();
+            formatrix_render_stream(
+                handle,
+                FfiFormat::Markdown,
+                Some(keep),
+                (&mut all as *mut Vec<u8>).cast(),
+            );
+            formatrix_render(handle, FfiFormat::Markdown, &mut out, &mut length);
+            assert_eq!(String::from_utf8(all).unwrap(), take_string(out, length));
+
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_utf16() {
+        let content: Vec<u16> = "# Grüße 😀\n\nWorld".encode_utf16().collect();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u16 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            let result = formatrix_parse_utf16(
+                content.as_ptr(),
+                content.len(),
+                FfiFormat::Markdown,
+                &mut handle,
+            );
+            assert_eq!(result, FfiResult::Success);
+
+            let result = formatrix_render_utf16(handle, FfiFormat::Djot, &mut out, &mut length);
+            assert_eq!(result, FfiResult::Success);
+            let rendered = String::from_utf16(std::slice::from_raw_parts(out, length)).unwrap();
+            assert_eq!(rendered, "# Grüße 😀\n\nWorld");
+            assert_eq!(length, rendered.encode_utf16().count());
+            formatrix_free_buffer_utf16(out, length);
+            formatrix_free_document(handle);
+
+            let lone_surrogate = [0xD800u16];
+            let result =
+                formatrix_parse_utf16(lone_surrogate.as_ptr(), 1, FfiFormat::Markdown, &mut handle);
+            assert_eq!(result, FfiResult::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let content = CString::new("# Hello\n\nWorld").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut out: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let result = formatrix_render_ansi(handle, 40, false, &mut out, &mut length);
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(take_string(out, length), "Hello\n═════\n\nWorld\n");
+            assert_eq!(length, "Hello\n═════\n\nWorld\n".len());
+
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_last_error() {
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let (mut line, mut column) = (0, 0);
+
+        unsafe {
+            formatrix_clear_last_error();
+            assert!(formatrix_last_error_message().is_null());
+
+            let result = formatrix_parse(ptr::null(), FfiFormat::Markdown, &mut handle);
+            assert_eq!(result, FfiResult::NullPointer);
+            assert_eq!(
+                CStr::from_ptr(formatrix_last_error_message())
+                    .to_str()
+                    .unwrap(),
+                NULL_ARGUMENT
+            );
+            assert!(!formatrix_last_error_span(&mut line, &mut column));
+
+            let invalid = [0xffu8, 0];
+            let result = formatrix_parse(invalid.as_ptr().cast(), FfiFormat::Markdown, &mut handle);
+            assert_eq!(result, FfiResult::Utf8Error);
+            let message = CStr::from_ptr(formatrix_last_error_message())
+                .to_str()
+                .unwrap();
+            assert!(message.contains("invalid utf-8"), "{}", message);
+
+            let error = ConversionError::ParseError {
+                line: 3,
+                column: 7,
+                message: "unclosed block".to_string(),
+            };
+            fail_conversion(FfiResult::ParseError, &error);
+            assert!(formatrix_last_error_span(&mut line, &mut column));
+            assert_eq!((line, column), (3, 7));
+        }
+    }
+
+    #[test]
+    fn test_inspect_blocks() {
+        let content =
+            CString::new("## Setup\n\n> Read *the* [guide](https://example.com).\n").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut text: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            assert_eq!(formatrix_block_count(handle), 2);
+            assert!(formatrix_get_block(handle, 2).is_null());
+
+            let heading = formatrix_get_block(handle, 0);
+            assert_eq!(formatrix_get_block_type(heading), FfiBlockType::Heading);
+            assert_eq!(formatrix_get_heading_level(heading), 2);
+            formatrix_get_block_text(heading, &mut text, &mut length);
+            assert_eq!(take_string(text, length), "Setup");
+
+            let quote = formatrix_get_block(handle, 1);
+            assert_eq!(formatrix_get_block_type(quote), FfiBlockType::BlockQuote);
+            assert_eq!(formatrix_get_heading_level(quote), 0);
+            assert_eq!(formatrix_get_block_child_count(quote), 1);
+            let paragraph = formatrix_get_block_child(quote, 0);
+            assert_eq!(formatrix_get_block_type(paragraph), FfiBlockType::Paragraph);
+
+            let types: Vec<FfiInlineType> = (0..formatrix_get_inline_count(paragraph))
+                .map(|i| formatrix_get_inline_type(formatrix_get_inline(paragraph, i)))
+                .collect();
+            assert_eq!(
+                types,
+                [
+                    FfiInlineType::Text,
+                    FfiInlineType::Emphasis,
+                    FfiInlineType::Text,
+                    FfiInlineType::Link,
+                    FfiInlineType::Text,
+                ]
+            );
+
+            let link = formatrix_get_inline(paragraph, 3);
+            formatrix_get_inline_url(link, &mut text, &mut length);
+            assert_eq!(take_string(text, length), "https://example.com");
+            assert_eq!(formatrix_get_inline_child_count(link), 1);
+            let label = formatrix_get_inline_child(link, 0);
+            formatrix_get_inline_text(label, &mut text, &mut length);
+            assert_eq!(take_string(text, length), "guide");
+
+            let emphasis = formatrix_get_inline(paragraph, 1);
+            let result = formatrix_get_inline_url(emphasis, &mut text, &mut length);
+            assert_eq!(result, FfiResult::InvalidInput);
+
+            // Past the end, the null handles are typed as invalid
+            let missing = formatrix_get_block_child(quote, 1);
+            assert_eq!(formatrix_get_block_type(missing), FfiBlockType::Invalid);
+            let missing = formatrix_get_inline(paragraph, 5);
+            assert_eq!(formatrix_get_inline_type(missing), FfiInlineType::Invalid);
+
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_edit_document() {
+        let content = CString::new("# Notes\n\n```\nold\n```\n\n- item\n").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut text: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            assert_eq!(formatrix_block_count(handle), 3);
+
+            let body = CString::new("First line\nsecond line").unwrap();
+            assert_eq!(
+                formatrix_insert_paragraph(handle, 1, body.as_ptr()),
+                FfiResult::Success
+            );
+            assert_eq!(
+                formatrix_insert_paragraph(handle, 9, body.as_ptr()),
+                FfiResult::InvalidInput
+            );
+            assert_eq!(formatrix_block_count(handle), 4);
+
+            let heading = CString::new("Plans").unwrap();
+            let code = CString::new("new\n").unwrap();
+            assert_eq!(
+                formatrix_set_block_text(handle, 0, heading.as_ptr()),
+                FfiResult::Success
+            );
+            assert_eq!(
+                formatrix_set_block_text(handle, 2, code.as_ptr()),
+                FfiResult::Success
+            );
+            assert_eq!(
+                formatrix_set_block_text(handle, 3, code.as_ptr()),
+                FfiResult::InvalidInput
+            );
+
+            assert_eq!(formatrix_delete_block(handle, 3), FfiResult::Success);
+            assert_eq!(formatrix_delete_block(handle, 3), FfiResult::InvalidInput);
+
+            let title = CString::new("Plans").unwrap();
+            assert_eq!(
+                formatrix_set_title(handle, title.as_ptr()),
+                FfiResult::Success
+            );
+            formatrix_get_title(handle, &mut text, &mut length);
+            assert_eq!(take_string(text, length), "Plans");
+
+            formatrix_render(handle, FfiFormat::Markdown, &mut text, &mut length);
+            assert_eq!(
+                take_string(text, length),
+                "---\ntitle: Plans\n---\n\n# Plans\n\nFirst line\nsecond line\n\n```\nnew\n```"
+            );
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        let content = CString::new("# One\n\ntext\n\n### Deep\n\n## Two\n").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut entries: *mut FfiOutlineEntry = ptr::null_mut();
+        let mut count = 0;
+
+        unsafe {
+            let options = FfiParseOptions {
+                preserve_spans: true,
+                strict: false,
+                ..Default::default()
+            };
+            formatrix_parse_with_options(
+                content.as_ptr(),
+                FfiFormat::Markdown,
+                &options,
+                &mut handle,
+            );
+            let result = formatrix_get_outline(handle, &mut entries, &mut count);
+            assert_eq!(result, FfiResult::Success);
+
+            let outline: Vec<(u8, &str, usize)> = std::slice::from_raw_parts(entries, count)
+                .iter()
+                .map(|entry| {
+                    assert!(entry.has_offset);
+                    let title = std::slice::from_raw_parts(entry.title, entry.title_length);
+                    let title = std::str::from_utf8(title).unwrap();
+                    (entry.level, title, entry.byte_offset)
+                })
+                .collect();
+            assert_eq!(outline, [(1, "One", 0), (3, "Deep", 13), (2, "Two", 23)]);
+
+            formatrix_free_outline(entries, count);
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_clone_document() {
+        let content = CString::new("# Hello\n\nWorld").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut copy: *mut DocumentHandle = ptr::null_mut();
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let result = formatrix_clone_document(handle, &mut copy);
+            assert_eq!(result, FfiResult::Success);
+            formatrix_delete_block(handle, 0);
+
+            // Render the copy on another thread while the original changes
+            let copy_address = copy as usize;
+            let rendered = std::thread::spawn(move || {
+                let copy = copy_address as *mut DocumentHandle;
+                let (mut out, mut length) = (ptr::null_mut(), 0);
+                formatrix_render(copy, FfiFormat::Markdown, &mut out, &mut length);
+                take_string(out, length)
+            });
+            formatrix_delete_block(handle, 0);
+            assert_eq!(rendered.join().unwrap(), "# Hello\n\nWorld");
+            assert_eq!(formatrix_block_count(handle), 0);
+
+            formatrix_free_document(copy);
+            formatrix_free_document(handle);
+        }
+    }
+
+    #[test]
+    fn test_document_json() {
+        let content = CString::new("# Hello\n\nWorld").unwrap();
+        let mut handle: *mut DocumentHandle = ptr::null_mut();
+        let mut copy: *mut DocumentHandle = ptr::null_mut();
+        let mut json: *mut u8 = ptr::null_mut();
+        let mut length = 0;
+        let (mut line, mut column) = (0, 0);
+
+        unsafe {
+            formatrix_parse(content.as_ptr(), FfiFormat::Markdown, &mut handle);
+            let result = formatrix_document_to_json(handle, &mut json, &mut length);
+            assert_eq!(result, FfiResult::Success);
+
+            let json = CString::new(take_string(json, length)).unwrap();
+            let result = formatrix_document_from_json(json.as_ptr(), &mut copy);
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(formatrix_block_count(copy), 2);
+            assert_eq!(formatrix_get_format(copy), FfiFormat::Markdown);
+            formatrix_free_document(copy);
+            formatrix_free_document(handle);
+
+            let broken = CString::new("{\n  \"content\": [").unwrap();
+            let result = formatrix_document_from_json(broken.as_ptr(), &mut copy);
+            assert_eq!(result, FfiResult::ParseError);
+            assert!(formatrix_last_error_span(&mut line, &mut column));
+            assert_eq!(line, 2);
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(
+            guard(FfiResult::InternalError, || FfiResult::Success),
+            FfiResult::Success
+        );
+
+        let result = guard(FfiResult::InternalError, || -> FfiResult {
+            panic!("broken invariant")
+        });
+        assert_eq!(result, FfiResult::InternalError);
+        let message = unsafe { CStr::from_ptr(formatrix_last_error_message()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "Internal error: broken invariant"
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch_file() {
+        use std::sync::mpsc::{channel, Sender};
+        use std::time::Duration;
+
+        unsafe extern "C" fn changed(path: *const c_char, userdata: *mut c_void) {
+            let tx = &*userdata.cast::<Sender<String>>();
+            let path = CStr::from_ptr(path).to_str().unwrap().to_string();
+            tx.send(path).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Before\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let missing = CString::new(dir.path().join("gone.md").to_str().unwrap()).unwrap();
+        let (tx, rx) = channel();
+        let mut watch: *mut WatchHandle = ptr::null_mut();
+
+        unsafe {
+            let userdata = (&tx as *const Sender<String>).cast_mut().cast();
+            let result =
+                formatrix_watch_file(missing.as_ptr(), Some(changed), userdata, &mut watch);
+            assert_eq!(result, FfiResult::InvalidInput);
+
+            let result = formatrix_watch_file(c_path.as_ptr(), Some(changed), userdata, &mut watch);
+            assert_eq!(result, FfiResult::Success);
+            std::fs::write(&path, "# After\n").unwrap();
+            let reported = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!(reported, path.to_str().unwrap());
+            formatrix_unwatch(watch);
+        }
+    }
+
+    #[test]
+    fn test_convert_file_async() {
+        use std::sync::mpsc::{channel, Sender};
+        use std::time::Duration;
+
+        type Report = (FfiConvertStage, FfiResult, bool);
+        unsafe extern "C" fn progress(
+            stage: FfiConvertStage,
+            result: FfiResult,
+            message: *const c_char,
+            userdata: *mut c_void,
+        ) {
+            let tx = &*userdata.cast::<Sender<Report>>();
+            tx.send((stage, result, !message.is_null())).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("notes.md");
+        let output = dir.path().join("notes.adoc");
+        std::fs::write(&input, "# Notes\n\nSome *text*.\n").unwrap();
+        let c_input = CString::new(input.to_str().unwrap()).unwrap();
+        let c_output = CString::new(output.to_str().unwrap()).unwrap();
+        let missing = CString::new(dir.path().join("gone.md").to_str().unwrap()).unwrap();
+        let (tx, rx) = channel();
+        // A conversion thread may still be inside the callback after the
+        // test has its last report, so the sender must outlive the test
+        let tx: &'static Sender<Report> = Box::leak(Box::new(tx));
+        let userdata = (tx as *const Sender<Report>).cast_mut().cast();
+        let next = || rx.recv_timeout(Duration::from_secs(10)).unwrap();
+
+        unsafe {
+            let token = formatrix_cancel_token_new();
+            let result = formatrix_convert_file_async(
+                c_input.as_ptr(),
+                c_output.as_ptr(),
+                FfiFormat::AsciiDoc,
+                Some(progress),
+                userdata,
+                token,
+            );
+            assert_eq!(result, FfiResult::Success);
+            assert_eq!(
+                next(),
+                (FfiConvertStage::Opening, FfiResult::Success, false)
+            );
+            assert_eq!(next(), (FfiConvertStage::Saving, FfiResult::Success, false));
+            assert_eq!(
+                next(),
+                (FfiConvertStage::Finished, FfiResult::Success, false)
+            );
+            assert_eq!(
+                std::fs::read_to_string(&output).unwrap(),
+                "= Notes\n\nSome _text_."
+            );
+
+            formatrix_convert_file_async(
+                missing.as_ptr(),
+                c_output.as_ptr(),
+                FfiFormat::AsciiDoc,
+                Some(progress),
+                userdata,
+                ptr::null(),
+            );
+            assert_eq!(
+                next(),
+                (FfiConvertStage::Opening, FfiResult::Success, false)
+            );
+            assert_eq!(
+                next(),
+                (FfiConvertStage::Finished, FfiResult::InvalidInput, true)
+            );
+
+            formatrix_cancel(token);
+            formatrix_convert_file_async(
+                c_input.as_ptr(),
+                c_output.as_ptr(),
+                FfiFormat::Typst,
+                Some(progress),
+                userdata,
+                token,
+            );
+            formatrix_cancel_token_free(token);
+            assert_eq!(
+                next(),
+                (FfiConvertStage::Finished, FfiResult::Cancelled, false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_feature_queries() {
+        assert_eq!(formatrix_abi_version(), FORMATRIX_ABI_VERSION);
+        let markdown = CString::new("markdown").unwrap();
+        let pdf = CString::new("pdf").unwrap();
+        unsafe {
+            assert_eq!(
+                formatrix_has_feature(markdown.as_ptr()),
+                cfg!(feature = "markdown")
+            );
+            assert!(!formatrix_has_feature(pdf.as_ptr()));
+            assert!(!formatrix_has_feature(ptr::null()));
+        }
+    }
+
     #[test]
     fn test_detect_format() {
         let md = CString::new("# Heading\n\nContent").unwrap();
@@ -7,17 +7,69 @@
 //! - File saving with format selection
 //! - Path-based format detection from extensions
 //! - Content-based format detection heuristics
+//! - Interactive variants that consult a [`ConversionInteractor`]
+//! - Expansion of include directives, sandboxed to a root directory
+//! - Detection of the file's text encoding, and saving back in it
+//! - Line endings kept as they were when saving over an existing file
+//! - Atomic saves through a temporary file, with optional backups
+//! - Async variants in [`aio`] (feature `async`)
+//! - Parallel conversion of whole directory trees selected by a glob
+//! - Dry runs that report what a conversion would write and lose
+//! - Re-parsing files as they change on disk (feature `watch`)
+//! - Conversion of strings and `Read`/`Write` streams, with no files involved
+//! - Compressed files and documents inside archives (feature `archives`)
+//! - Documents fetched from `http(s)://` URLs (feature `http`)
+//! - Recovery from parse errors, keeping unparseable regions as raw blocks
+//! - Size, depth and time limits on parsing (see [`ParseLimits`](crate::traits::ParseLimits))
 
-use crate::ast::{Document, SourceFormat};
+use crate::assets::{collect_assets, embed_images, extract_images, AssetOptions, CollectedAsset};
+use crate::ast::{Document, SourceFormat, Span};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::encoding::{decode, encode, TextEncoding};
 use crate::formats::{
-    AsciidocHandler, DjotHandler, MarkdownHandler, OrgModeHandler, PlainTextHandler, RstHandler,
-    TypstHandler,
+    handler_for, AsciidocHandler, DjotHandler, MarkdownHandler, OrgModeHandler, PlainTextHandler,
+    RstHandler, TypstHandler,
 };
-use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+use crate::interact::{rewrite_links, ConversionInteractor, NonInteractive};
+use crate::recovery::parse_recovering_limited;
+use crate::report::ConversionReport;
+use crate::sidecar::{read_sidecar, Sidecar};
+use crate::traits::{LineEnding, ParseConfig, ParseLimits, Parser, RenderConfig, Renderer};
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+pub mod aio;
+#[cfg(feature = "archives")]
+mod archive;
+mod batch;
+mod lock;
+mod plan;
+#[cfg(feature = "http")]
+mod remote;
+mod split;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "archives")]
+pub use archive::{archive_documents, ARCHIVE_SEPARATOR};
+pub(crate) use batch::matched_inputs;
+pub use batch::{
+    convert_dir, convert_dir_with_progress, BatchEntry, BatchOptions, BatchOutcome, BatchProgress,
+    BatchReport,
+};
+pub use lock::{open_file_for_edit, save_file_checked, EditLock, FileStamp};
+pub use plan::{plan_conversion, ConversionPlan, PlannedAction, PlannedFile};
+#[cfg(feature = "http")]
+pub use remote::open_url;
+pub use split::split_file;
+#[cfg(feature = "watch")]
+pub use watch::{watch, watch_file, watch_paths, watch_with_config, FileWatcher};
+
 /// File operation errors
 #[derive(Debug, Error)]
 pub enum FileError {
@@ -40,6 +92,50 @@ pub enum FileError {
     /// Render error
     #[error("Render error: {0}")]
     Render(String),
+
+    /// A [`ConversionInteractor`] declined to continue
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// An include directive points outside the include root
+    #[error("Include outside root directory: {path}")]
+    IncludeOutsideRoot { path: String },
+
+    /// A file includes itself, directly or through other files
+    #[error("Include cycle: {}", chain.join(" -> "))]
+    IncludeCycle { chain: Vec<String> },
+
+    /// A compressed file or archive could not be read, or does not say which
+    /// document to open
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    /// The input is over one of the configured parse limits
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// Fetching a remote document failed
+    #[error("HTTP error fetching {url}: {message}")]
+    Http { url: String, message: String },
+
+    /// The document holds a character the target encoding cannot represent
+    #[error("Cannot write {character:?} in {encoding}")]
+    Unencodable {
+        encoding: TextEncoding,
+        character: char,
+    },
+
+    /// Another editor holds the edit lock on the file
+    #[error("File is locked by another editor: {path}")]
+    Locked { path: String },
+
+    /// The file changed on disk since it was opened
+    #[error("File was modified externally: {path}")]
+    ExternallyModified { path: String },
+
+    /// A find pattern is not valid
+    #[error(transparent)]
+    Find(#[from] crate::find::FindError),
 }
 
 impl From<crate::traits::ConversionError> for FileError {
@@ -51,6 +147,7 @@ fn from(err: crate::traits::ConversionError) -> Self {
                 FileError::UnsupportedFormat { format }
             }
             crate::traits::ConversionError::SerializationError(msg) => FileError::Render(msg),
+            crate::traits::ConversionError::LimitExceeded(msg) => FileError::LimitExceeded(msg),
         }
     }
 }
@@ -69,6 +166,26 @@ pub struct FileInfo {
     pub size: u64,
     /// Whether the file is read-only
     pub read_only: bool,
+    /// Encoding the file was stored in
+    pub encoding: TextEncoding,
+    /// Most common line ending in the file
+    pub line_ending: LineEnding,
+    /// State of the file when it was read, for [`save_file_checked`]; `None`
+    /// for documents that cannot be saved back, such as archive members
+    pub stamp: Option<FileStamp>,
+}
+
+/// What to keep of the previous version when saving over a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupPolicy {
+    /// Replace the file without a backup
+    #[default]
+    None,
+    /// Copy the previous version to `<name>.bak`, replacing any older backup
+    Single,
+    /// Copy the previous version to the next free `<name>.~N~`
+    Numbered,
 }
 
 /// Opened document with file metadata
@@ -78,6 +195,10 @@ pub struct OpenedDocument {
     pub document: Document,
     /// File information
     pub file_info: FileInfo,
+    /// Problems found while parsing, such as regions kept as raw source
+    pub diagnostics: Vec<Diagnostic>,
+    /// The document's sidecar file, already merged into its metadata
+    pub sidecar: Option<Sidecar>,
 }
 
 /// Detect format from file extension
@@ -97,62 +218,81 @@ pub fn format_from_extension(path: &Path) -> Option<SourceFormat> {
 
 /// Detect format from content using heuristics
 pub fn format_from_content(content: &str) -> SourceFormat {
+    format_candidates(content)
+        .first()
+        .copied()
+        .unwrap_or(SourceFormat::PlainText)
+}
+
+/// Every format whose markers appear in `content`, most specific first
+///
+/// More than one candidate means detection is ambiguous. An empty list means
+/// no markers were found and the content is treated as plain text.
+pub fn format_candidates(content: &str) -> Vec<SourceFormat> {
     let trimmed = content.trim();
+    HEURISTICS
+        .iter()
+        .filter(|(_, matches)| matches(trimmed))
+        .map(|&(format, _)| format)
+        .collect()
+}
 
-    // Check for org-mode markers (most specific first)
-    if trimmed.starts_with("#+") || trimmed.contains("\n#+") {
-        return SourceFormat::OrgMode;
-    }
+/// Content heuristic for one format
+type Heuristic = (SourceFormat, fn(&str) -> bool);
 
-    // Check for AsciiDoc markers
-    if trimmed.starts_with("= ") && !trimmed.starts_with("= {") {
-        return SourceFormat::AsciiDoc;
-    }
-    if trimmed.starts_with(":toc:") || trimmed.contains("\n:toc:") {
-        return SourceFormat::AsciiDoc;
-    }
+/// Heuristics in priority order
+const HEURISTICS: &[Heuristic] = &[
+    (SourceFormat::OrgMode, looks_like_orgmode),
+    (SourceFormat::AsciiDoc, looks_like_asciidoc),
+    (SourceFormat::Typst, looks_like_typst),
+    (SourceFormat::ReStructuredText, looks_like_rst),
+    (SourceFormat::Djot, looks_like_djot),
+    (SourceFormat::Markdown, looks_like_markdown),
+];
 
-    // Check for Typst markers
-    if trimmed.contains("#let ") || trimmed.contains("#set ") || trimmed.contains("#show ") {
-        return SourceFormat::Typst;
-    }
-    if trimmed.starts_with("#[") || trimmed.contains("\n#[") {
-        return SourceFormat::Typst;
-    }
+fn looks_like_orgmode(trimmed: &str) -> bool {
+    trimmed.starts_with("#+") || trimmed.contains("\n#+")
+}
+
+fn looks_like_asciidoc(trimmed: &str) -> bool {
+    (trimmed.starts_with("= ") && !trimmed.starts_with("= {"))
+        || trimmed.starts_with(":toc:")
+        || trimmed.contains("\n:toc:")
+}
+
+fn looks_like_typst(trimmed: &str) -> bool {
+    trimmed.contains("#let ")
+        || trimmed.contains("#set ")
+        || trimmed.contains("#show ")
+        || trimmed.starts_with("#[")
+        || trimmed.contains("\n#[")
+}
 
-    // Check for RST markers
+fn looks_like_rst(trimmed: &str) -> bool {
     if trimmed.contains(".. ") && (trimmed.contains("::") || trimmed.contains(".. code-block::")) {
-        return SourceFormat::ReStructuredText;
+        return true;
     }
     // RST title underlines
-    if trimmed.lines().any(|line| {
+    trimmed.lines().any(|line| {
         let chars: Vec<char> = line.chars().collect();
         chars.len() > 3
             && chars
                 .iter()
                 .all(|&c| c == '=' || c == '-' || c == '~' || c == '^')
-    }) {
-        return SourceFormat::ReStructuredText;
-    }
-
-    // Check for Djot markers
-    if trimmed.contains("{.") || trimmed.contains("[^") {
-        return SourceFormat::Djot;
-    }
+    })
+}
 
-    // Check for Markdown markers (most common, check last)
-    if trimmed.starts_with("# ") || trimmed.contains("\n# ") {
-        return SourceFormat::Markdown;
-    }
-    if trimmed.contains("```") || trimmed.contains("~~~") {
-        return SourceFormat::Markdown;
-    }
-    if trimmed.contains("[](") || trimmed.contains("![](") {
-        return SourceFormat::Markdown;
-    }
+fn looks_like_djot(trimmed: &str) -> bool {
+    trimmed.contains("{.") || trimmed.contains("[^")
+}
 
-    // Default to plain text
-    SourceFormat::PlainText
+fn looks_like_markdown(trimmed: &str) -> bool {
+    trimmed.starts_with("# ")
+        || trimmed.contains("\n# ")
+        || trimmed.contains("```")
+        || trimmed.contains("~~~")
+        || trimmed.contains("[](")
+        || trimmed.contains("![](")
 }
 
 /// Open a file and parse it to a Document
@@ -167,30 +307,56 @@ pub fn open_file_with_config(
     path: impl AsRef<Path>,
     config: &ParseConfig,
 ) -> FileResult<OpenedDocument> {
-    let path = path.as_ref();
+    open_file_interactive(path, config, &mut NonInteractive)
+}
 
-    // Read file content
-    let content = fs::read_to_string(path)?;
+/// Open a file, asking `interactor` to choose when the format is ambiguous
+pub fn open_file_interactive(
+    path: impl AsRef<Path>,
+    config: &ParseConfig,
+    interactor: &mut dyn ConversionInteractor,
+) -> FileResult<OpenedDocument> {
+    let path = path.as_ref();
 
-    // Get file metadata
-    let metadata = fs::metadata(path)?;
-    let size = metadata.len();
-    let read_only = metadata.permissions().readonly();
+    // Read file content, decompressing or unpacking it if needed
+    let source = read_source(path, &config.limits)?;
+    let (content, encoding) = decode(&source.bytes);
+    let line_ending = LineEnding::detect(&content);
 
     // Detect format
-    let format = format_from_extension(path).unwrap_or_else(|| format_from_content(&content));
+    let format = match format_from_extension(&source.name) {
+        Some(format) => format,
+        None => match format_candidates(&content).as_slice() {
+            [] => SourceFormat::PlainText,
+            [format] => *format,
+            candidates => interactor
+                .choose_format(path, candidates)
+                .ok_or(FileError::Cancelled)?,
+        },
+    };
 
-    // Parse based on format
-    let document = parse_content(&content, format, config)?;
+    // Expand includes, then parse based on format
+    let (content, mut diagnostics) = expand_for_open(content, format, path, &source, config)?;
+    let (mut document, parsed) = parse_opened(&content, format, config)?;
+    diagnostics.extend(parsed);
+    if let Some(dir) = &config.extract_images {
+        extract_images(&mut document, &source.dir, dir)?;
+    }
+    let sidecar = open_sidecar(path, &source, &mut document)?;
 
     Ok(OpenedDocument {
         document,
         file_info: FileInfo {
             path: path.to_string_lossy().to_string(),
             format,
-            size,
-            read_only,
+            size: source.size,
+            read_only: source.read_only,
+            encoding,
+            line_ending,
+            stamp: source.stamp,
         },
+        diagnostics,
+        sidecar,
     })
 }
 
@@ -202,33 +368,378 @@ pub fn open_file_as(
 ) -> FileResult<OpenedDocument> {
     let path = path.as_ref();
 
-    // Read file content
-    let content = fs::read_to_string(path)?;
+    // Read file content, decompressing or unpacking it if needed
+    let source = read_source(path, &config.limits)?;
+    let (content, encoding) = decode(&source.bytes);
+    let line_ending = LineEnding::detect(&content);
 
-    // Get file metadata
-    let metadata = fs::metadata(path)?;
-    let size = metadata.len();
-    let read_only = metadata.permissions().readonly();
-
-    // Parse with specified format
-    let document = parse_content(&content, format, config)?;
+    // Expand includes, then parse with specified format
+    let (content, mut diagnostics) = expand_for_open(content, format, path, &source, config)?;
+    let (mut document, parsed) = parse_opened(&content, format, config)?;
+    diagnostics.extend(parsed);
+    if let Some(dir) = &config.extract_images {
+        extract_images(&mut document, &source.dir, dir)?;
+    }
+    let sidecar = open_sidecar(path, &source, &mut document)?;
 
     Ok(OpenedDocument {
         document,
         file_info: FileInfo {
             path: path.to_string_lossy().to_string(),
             format,
-            size,
-            read_only,
+            size: source.size,
+            read_only: source.read_only,
+            encoding,
+            line_ending,
+            stamp: source.stamp,
         },
+        diagnostics,
+        sidecar,
+    })
+}
+
+/// Read a text file in whatever encoding it is stored in
+pub fn read_text(path: impl AsRef<Path>) -> FileResult<(String, TextEncoding)> {
+    Ok(decode(&fs::read(path)?))
+}
+
+/// Raw bytes of a document and where they came from
+struct Source {
+    bytes: Vec<u8>,
+    /// Name the format is detected from
+    name: PathBuf,
+    /// Directory on disk that relative paths resolve against
+    dir: PathBuf,
+    size: u64,
+    read_only: bool,
+    /// Whether the document came out of an archive, where includes cannot be
+    /// followed
+    in_archive: bool,
+    /// Set when `bytes` are the file's contents as stored
+    stamp: Option<FileStamp>,
+}
+
+fn read_source(path: &Path, limits: &ParseLimits) -> FileResult<Source> {
+    #[cfg(feature = "archives")]
+    if let Some(source) = archive::read_source(path, limits)? {
+        return Ok(source);
+    }
+
+    let metadata = fs::metadata(path)?;
+    limits.check_size(usize::try_from(metadata.len()).unwrap_or(usize::MAX))?;
+    let bytes = read_limited(fs::File::open(path)?, limits)?;
+    limits.check_size(bytes.len())?;
+    Ok(Source {
+        name: path.to_path_buf(),
+        dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        size: metadata.len(),
+        read_only: metadata.permissions().readonly(),
+        in_archive: false,
+        stamp: Some(FileStamp::new(&bytes, &metadata)),
+        bytes,
     })
 }
 
-/// Parse content string to Document
+/// Read all of `reader`, but no more than one byte past the size `limits`
+/// allow, so a file that grows or decompresses without end is caught
+/// before it fills memory
+fn read_limited(reader: impl Read, limits: &ParseLimits) -> io::Result<Vec<u8>> {
+    let cap = limits
+        .max_input_bytes
+        .map_or(u64::MAX, |max| (max as u64).saturating_add(1));
+    let mut bytes = Vec::new();
+    reader.take(cap).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read the sidecar of a document on disk and merge it into `document`
+fn open_sidecar(
+    path: &Path,
+    source: &Source,
+    document: &mut Document,
+) -> FileResult<Option<Sidecar>> {
+    if source.in_archive {
+        return Ok(None);
+    }
+    let sidecar = read_sidecar(path)?;
+    if let Some(sidecar) = &sidecar {
+        sidecar.merge_into(&mut document.meta);
+    }
+    Ok(sidecar)
+}
+
+/// Apply [`resolve_includes`] unless `config` asks to keep the directives
+fn expand_for_open(
+    content: String,
+    format: SourceFormat,
+    path: &Path,
+    source: &Source,
+    config: &ParseConfig,
+) -> FileResult<(String, Vec<Diagnostic>)> {
+    if config.keep_includes || source.in_archive {
+        return Ok((content, Vec::new()));
+    }
+    expand_within(
+        &content,
+        format,
+        path,
+        config.include_root.as_deref(),
+        &config.limits,
+    )
+}
+
+/// Diagnostic code for include directives whose target could not be read
+pub const MISSING_INCLUDE_CODE: &str = "missing-include";
+
+/// Most text include expansion may produce when no
+/// [`max_input_bytes`](ParseLimits::max_input_bytes) limit is set, so files
+/// including each other many times over cannot grow without bound
+pub const MAX_EXPANDED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Expand the include directives in `content`, read from the file at `path`
+///
+/// Recognised directives, each on a line of its own:
+/// - AsciiDoc `include::target[]`
+/// - Org `#+INCLUDE: "target"`
+/// - reStructuredText `.. include:: target` (option lines are dropped)
+/// - Markdown `{{#include target}}`
+///
+/// Targets are resolved against the directory of the including file and must
+/// stay inside `root`, which defaults to the directory of `path`. Included
+/// files are expanded recursively using the directives of their own format,
+/// and converted to `format` if they are written in a different one. A
+/// directive whose target is missing or unreadable is left as it is, and
+/// the expanded text may be at most [`MAX_EXPANDED_BYTES`] long.
+pub fn resolve_includes(
+    content: &str,
+    format: SourceFormat,
+    path: &Path,
+    root: Option<&Path>,
+) -> FileResult<String> {
+    expand_within(content, format, path, root, &ParseLimits::default()).map(|(text, _)| text)
+}
+
+/// [`resolve_includes`], refusing to grow the text past `limits` and
+/// reporting the directives it left unexpanded
+fn expand_within(
+    content: &str,
+    format: SourceFormat,
+    path: &Path,
+    root: Option<&Path>,
+    limits: &ParseLimits,
+) -> FileResult<(String, Vec<Diagnostic>)> {
+    if !content
+        .lines()
+        .any(|line| include_target(line, format).is_some())
+    {
+        return Ok((content.to_string(), Vec::new()));
+    }
+
+    let path = path.canonicalize()?;
+    let root = match root {
+        Some(root) => root.canonicalize()?,
+        None => path.parent().unwrap_or(Path::new("/")).to_path_buf(),
+    };
+    let limits = ParseLimits {
+        max_input_bytes: Some(limits.max_input_bytes.unwrap_or(MAX_EXPANDED_BYTES)),
+        ..*limits
+    };
+    let mut diagnostics = Vec::new();
+    let text = expand_includes(
+        content,
+        format,
+        &root,
+        &mut vec![path],
+        &limits,
+        &mut diagnostics,
+    )?;
+    Ok((text, diagnostics))
+}
+
+/// Expand includes in `content`, the text of the last file in `stack`
+fn expand_includes(
+    content: &str,
+    format: SourceFormat,
+    root: &Path,
+    stack: &mut Vec<PathBuf>,
+    limits: &ParseLimits,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> FileResult<String> {
+    let dir = stack
+        .last()
+        .and_then(|path| path.parent())
+        .unwrap_or(root)
+        .to_path_buf();
+
+    let mut out = String::with_capacity(content.len());
+    let mut lines = content.split_inclusive('\n').peekable();
+    while let Some(line) = lines.next() {
+        let Some(target) = include_target(line, format) else {
+            out.push_str(line);
+            continue;
+        };
+        let mut directive = line.to_string();
+        if format == SourceFormat::ReStructuredText {
+            while let Some(option) = lines.next_if(|next| is_rst_option(next)) {
+                directive.push_str(option);
+            }
+        }
+
+        let resolved = match dir.join(target).canonicalize() {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                keep_include(&mut out, &directive, target, stack, err, diagnostics);
+                limits.check_size(out.len())?;
+                continue;
+            }
+        };
+        if !resolved.starts_with(root) {
+            return Err(FileError::IncludeOutsideRoot {
+                path: target.to_string(),
+            });
+        }
+        if stack.contains(&resolved) {
+            let chain = stack
+                .iter()
+                .chain([&resolved])
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            return Err(FileError::IncludeCycle { chain });
+        }
+
+        let bytes = match fs::File::open(&resolved).and_then(|file| read_limited(file, limits)) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                keep_include(&mut out, &directive, target, stack, err, diagnostics);
+                limits.check_size(out.len())?;
+                continue;
+            }
+        };
+        let included_format = format_from_extension(&resolved).unwrap_or(format);
+        let (included, _) = decode(&bytes);
+        limits.check_size(out.len() + included.len())?;
+        stack.push(resolved);
+        let mut expanded =
+            expand_includes(&included, included_format, root, stack, limits, diagnostics)?;
+        stack.pop();
+
+        if included_format != format && included_format != SourceFormat::PlainText {
+            let config = ParseConfig {
+                limits: *limits,
+                ..Default::default()
+            };
+            let doc = parse_content(&expanded, included_format, &config)?;
+            expanded = render_content(&doc, format, &RenderConfig::default())?;
+        }
+        out.push_str(&expanded);
+        limits.check_size(out.len())?;
+        if !expanded.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// Leave an include directive whose target could not be read as it is,
+/// with a diagnostic pointing at it when it is in the file being opened
+/// rather than in one it includes
+fn keep_include(
+    out: &mut String,
+    directive: &str,
+    target: &str,
+    stack: &[PathBuf],
+    err: io::Error,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let span = (stack.len() == 1).then(|| Span {
+        start: out.len(),
+        end: out.len() + directive.trim_end().len(),
+        line: out.matches('\n').count() as u32 + 1,
+        column: 1,
+    });
+    let including = stack.last().map(|path| path.display().to_string());
+    diagnostics.push(
+        Diagnostic::new(
+            MISSING_INCLUDE_CODE,
+            Severity::Warning,
+            format!(
+                "Could not include {} from {}: {}",
+                target,
+                including.unwrap_or_default(),
+                err
+            ),
+        )
+        .with_span(span),
+    );
+    out.push_str(directive);
+}
+
+/// Target of the include directive on `line`, if it is one in `format`
+fn include_target(line: &str, format: SourceFormat) -> Option<&str> {
+    let line = line.trim_end();
+    let target = match format {
+        SourceFormat::AsciiDoc => {
+            let rest = line.strip_prefix("include::")?;
+            rest.strip_suffix(']')?.split_once('[')?.0
+        }
+        SourceFormat::OrgMode => {
+            let rest = line.trim_start();
+            if !rest.get(..10)?.eq_ignore_ascii_case("#+include:") {
+                return None;
+            }
+            let rest = rest[10..].trim_start();
+            match rest.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"')?.0,
+                None => rest.split_whitespace().next()?,
+            }
+        }
+        SourceFormat::ReStructuredText => line.strip_prefix(".. include::")?.trim(),
+        SourceFormat::Markdown => line
+            .trim_start()
+            .strip_prefix("{{#include")?
+            .strip_suffix("}}")?
+            .trim(),
+        _ => return None,
+    };
+    (!target.is_empty()).then_some(target)
+}
+
+/// Indented `:option:` line following an RST directive
+fn is_rst_option(line: &str) -> bool {
+    line.starts_with([' ', '\t']) && line.trim_start().starts_with(':')
+}
+
+/// Parse an opened file, recovering from errors unless `config` is strict
+pub(crate) fn parse_opened(
+    content: &str,
+    format: SourceFormat,
+    config: &ParseConfig,
+) -> FileResult<(Document, Vec<Diagnostic>)> {
+    if config.strict {
+        return Ok((parse_content(content, format, config)?, Vec::new()));
+    }
+    config.limits.check_input(content)?;
+    let handler = handler_for(format);
+    let (doc, diagnostics) = parse_recovering_limited(handler.as_ref(), content, config)?;
+    config.limits.check_document(&doc)?;
+    Ok((doc, diagnostics))
+}
+
+/// Parse `content`, enforcing the limits in `config`
 fn parse_content(
     content: &str,
     format: SourceFormat,
     config: &ParseConfig,
+) -> FileResult<Document> {
+    let doc = parse_unlimited(content, format, config)?;
+    config.limits.check_document(&doc)?;
+    Ok(doc)
+}
+
+fn parse_unlimited(
+    content: &str,
+    format: SourceFormat,
+    config: &ParseConfig,
 ) -> FileResult<Document> {
     let doc = match format {
         SourceFormat::PlainText => PlainTextHandler::new().parse(content, config)?,
@@ -270,18 +781,161 @@ pub fn save_file_as(
     path: impl AsRef<Path>,
     format: SourceFormat,
     config: &RenderConfig,
+) -> FileResult<()> {
+    save_file_as_interactive(doc, path, format, config, &mut NonInteractive)
+}
+
+/// Save a document with explicit format, consulting `interactor` about links
+/// into sibling documents and about any features the format cannot hold
+pub fn save_file_as_interactive(
+    doc: &Document,
+    path: impl AsRef<Path>,
+    format: SourceFormat,
+    config: &RenderConfig,
+    interactor: &mut dyn ConversionInteractor,
 ) -> FileResult<()> {
     let path = path.as_ref();
 
-    // Render content
-    let content = render_content(doc, format, config)?;
+    let mut rewritten = rewrite_links(doc, format, interactor).ok_or(FileError::Cancelled)?;
+    if config.embed_images {
+        let embedded = rewritten.get_or_insert_with(|| doc.clone());
+        embed_images(embedded, path.parent().unwrap_or(Path::new("")))?;
+    }
+    let doc = rewritten.as_ref().unwrap_or(doc);
+
+    let report = ConversionReport::new(doc, handler_for(format).as_ref());
+    if !report.is_lossless() && !interactor.confirm_lossy_save(path, &report) {
+        return Err(FileError::Cancelled);
+    }
+
+    // Render content, matching the line endings of the file being replaced
+    let content = match config.line_ending {
+        LineEnding::Preserve if path.exists() => {
+            let (existing, _) = read_text(path)?;
+            let config = RenderConfig {
+                line_ending: LineEnding::detect(&existing),
+                ..config.clone()
+            };
+            render_content(doc, format, &config)?
+        }
+        _ => render_content(doc, format, config)?,
+    };
 
     // Write to file
-    fs::write(path, content)?;
+    let bytes = encode(&content, config.encoding).map_err(|character| FileError::Unencodable {
+        encoding: config.encoding,
+        character,
+    })?;
+    write_atomic(path, &bytes, config.backup)?;
+
+    Ok(())
+}
+
+/// Write `bytes` to `path` without ever leaving a partly written file
+///
+/// The bytes go to a temporary file in the same directory, which is synced
+/// and then renamed over `path`; on Unix the directory is synced as well so
+/// the rename itself is durable. If `path` already exists it keeps its
+/// permissions, and `backup` decides whether its previous contents are kept.
+/// A symlink is followed, so the file it points to is replaced rather than
+/// the link.
+pub fn write_atomic(path: impl AsRef<Path>, bytes: &[u8], backup: BackupPolicy) -> io::Result<()> {
+    let path = path.as_ref();
+    let target = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let existing = fs::metadata(&target).ok();
+    if existing
+        .as_ref()
+        .is_some_and(|meta| meta.permissions().readonly())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is read-only", target.display()),
+        ));
+    }
+
+    let temp = temp_path(&target);
+    let result = replace_via(&temp, &target, bytes, existing.as_ref(), backup);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Write `bytes` to `temp`, back up `target`, then move `temp` over it
+fn replace_via(
+    temp: &Path,
+    target: &Path,
+    bytes: &[u8],
+    existing: Option<&fs::Metadata>,
+    backup: BackupPolicy,
+) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp)?;
+    file.write_all(bytes)?;
+    if let Some(meta) = existing {
+        file.set_permissions(meta.permissions())?;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    if existing.is_some() {
+        if let Some(backup_path) = backup_path(target, backup) {
+            fs::copy(target, backup_path)?;
+        }
+    }
+    fs::rename(temp, target)?;
+    sync_parent(target)
+}
+
+/// Sync the directory holding `target`, so the rename survives a crash
+#[cfg(unix)]
+fn sync_parent(target: &Path) -> io::Result<()> {
+    let parent = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent)?.sync_all()
+}
 
+/// Directories cannot be opened for syncing here; the rename is left to
+/// the filesystem
+#[cfg(not(unix))]
+fn sync_parent(_target: &Path) -> io::Result<()> {
     Ok(())
 }
 
+/// Hidden sibling of `target` that no other save is using
+fn temp_path(target: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Where the previous version of `target` goes under `policy`
+fn backup_path(target: &Path, policy: BackupPolicy) -> Option<PathBuf> {
+    let name = target.file_name()?.to_string_lossy().into_owned();
+    match policy {
+        BackupPolicy::None => None,
+        BackupPolicy::Single => Some(target.with_file_name(format!("{}.bak", name))),
+        BackupPolicy::Numbered => (1..)
+            .map(|n| target.with_file_name(format!("{}.~{}~", name, n)))
+            .find(|candidate| !candidate.exists()),
+    }
+}
+
 /// Render document to string
 fn render_content(
     doc: &Document,
@@ -316,13 +970,125 @@ pub fn convert_file_with_config(
     output_path: impl AsRef<Path>,
     parse_config: &ParseConfig,
     render_config: &RenderConfig,
+) -> FileResult<()> {
+    convert_file_interactive(
+        input_path,
+        output_path,
+        parse_config,
+        render_config,
+        &mut NonInteractive,
+    )
+}
+
+/// Convert a file, consulting `interactor` whenever a decision is ambiguous
+pub fn convert_file_interactive(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+    interactor: &mut dyn ConversionInteractor,
 ) -> FileResult<()> {
     // Open and parse input
-    let opened = open_file_with_config(input_path, parse_config)?;
+    let opened = open_file_interactive(input_path, parse_config, interactor)?;
 
     // Save to output (format detected from extension)
-    save_file_with_config(&opened.document, output_path, render_config)?;
+    let output_path = output_path.as_ref();
+    let format = format_from_extension(output_path).unwrap_or(opened.document.source_format);
+    save_file_as_interactive(
+        &opened.document,
+        output_path,
+        format,
+        render_config,
+        interactor,
+    )
+}
+
+/// Convert a file and copy the images and attachments it references into an
+/// assets directory beside the output, rewriting their URLs to match
+///
+/// Returns the files that were copied.
+pub fn convert_file_with_assets(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+    assets: &AssetOptions,
+) -> FileResult<Vec<CollectedAsset>> {
+    let input_path = input_path.as_ref();
+    let output_path = output_path.as_ref();
+    let mut opened = open_file_with_config(input_path, parse_config)?;
+
+    let source_dir = input_path.parent().unwrap_or(Path::new(""));
+    let output_dir = output_path.parent().unwrap_or(Path::new(""));
+    let collected = collect_assets(&mut opened.document, source_dir, output_dir, assets)?;
+
+    let format = format_from_extension(output_path).unwrap_or(opened.document.source_format);
+    save_file_as(&opened.document, output_path, format, render_config)?;
+    Ok(collected)
+}
+
+/// Parse `input` as [`open_file_as`] parses a file's contents
+///
+/// Regions that fail to parse are kept raw with a diagnostic each, unless
+/// `config` is strict, and the limits in `config` apply.
+pub fn parse_string(
+    input: &str,
+    format: SourceFormat,
+    config: &ParseConfig,
+) -> FileResult<(Document, Vec<Diagnostic>)> {
+    parse_opened(input, format, config)
+}
 
+/// Convert `input` from one format to another in memory
+pub fn convert_string(
+    input: &str,
+    from: SourceFormat,
+    to: SourceFormat,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+) -> FileResult<String> {
+    let doc = parse_content(input, from, parse_config)?;
+    render_content(&doc, to, render_config)
+}
+
+/// Read a whole document from `reader` and convert it to a string
+///
+/// The input's encoding is detected as for files. With `from` unset the
+/// format is detected from the content. Reading stops once the input is
+/// past the size `parse_config.limits` allow.
+pub fn convert_reader(
+    reader: impl Read,
+    from: Option<SourceFormat>,
+    to: SourceFormat,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+) -> FileResult<String> {
+    let bytes = read_limited(reader, &parse_config.limits)?;
+    parse_config.limits.check_size(bytes.len())?;
+    let (input, _) = decode(&bytes);
+    let from = from.unwrap_or_else(|| format_from_content(&input));
+    convert_string(&input, from, to, parse_config, render_config)
+}
+
+/// Convert a document from `reader` to `writer`, such as stdin to stdout
+///
+/// Output is written in `render_config.encoding`.
+pub fn convert_writer(
+    reader: impl Read,
+    mut writer: impl Write,
+    from: Option<SourceFormat>,
+    to: SourceFormat,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+) -> FileResult<()> {
+    let output = convert_reader(reader, from, to, parse_config, render_config)?;
+    let bytes =
+        encode(&output, render_config.encoding).map_err(|character| FileError::Unencodable {
+            encoding: render_config.encoding,
+            character,
+        })?;
+    writer.write_all(&bytes)?;
+    writer.flush()?;
     Ok(())
 }
 
@@ -363,6 +1129,7 @@ pub fn is_supported_extension(ext: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{Block, Inline};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -458,6 +1225,556 @@ fn test_convert_file() {
         assert!(content.contains("Hello") || content.contains("World"));
     }
 
+    #[test]
+    fn test_format_candidates() {
+        assert_eq!(
+            format_candidates("# Heading\n\nSee [^1] and ```code```"),
+            vec![SourceFormat::Djot, SourceFormat::Markdown]
+        );
+        assert_eq!(
+            format_candidates("# Heading\n\nParagraph"),
+            vec![SourceFormat::Markdown]
+        );
+        assert!(format_candidates("Just plain text").is_empty());
+    }
+
+    /// Records every question and gives scripted answers
+    #[derive(Default)]
+    struct Scripted {
+        asked: Vec<String>,
+        confirm: bool,
+    }
+
+    impl ConversionInteractor for Scripted {
+        fn choose_format(
+            &mut self,
+            _path: &Path,
+            candidates: &[SourceFormat],
+        ) -> Option<SourceFormat> {
+            self.asked.push(format!("format {:?}", candidates));
+            candidates.last().copied()
+        }
+
+        fn confirm_lossy_save(&mut self, _path: &Path, report: &ConversionReport) -> bool {
+            self.asked.push(format!("lossy {:?}", report.lost_features));
+            self.confirm
+        }
+
+        fn rewrite_link(
+            &mut self,
+            url: &str,
+            _suggested: &str,
+        ) -> Option<crate::interact::LinkRewrite> {
+            self.asked.push(format!("link {}", url));
+            Some(crate::interact::LinkRewrite::Rewrite)
+        }
+    }
+
+    #[test]
+    fn test_convert_file_interactive() {
+        let mut input = NamedTempFile::with_suffix(".notes").unwrap();
+        writeln!(input, "# Notes\n\nSee [^1], ~~old~~ and [next](next.md).").unwrap();
+        let output = NamedTempFile::with_suffix(".rst").unwrap();
+
+        // Declining the lossy save writes nothing
+        let mut interactor = Scripted::default();
+        let result = convert_file_interactive(
+            input.path(),
+            output.path(),
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+            &mut interactor,
+        );
+        assert!(matches!(result, Err(FileError::Cancelled)));
+        assert_eq!(
+            interactor.asked,
+            vec![
+                "format [Djot, Markdown]",
+                "link next.md",
+                "lossy [\"strikethrough\"]"
+            ]
+        );
+        assert!(fs::read_to_string(output.path()).unwrap().is_empty());
+
+        let mut interactor = Scripted {
+            confirm: true,
+            ..Default::default()
+        };
+        convert_file_interactive(
+            input.path(),
+            output.path(),
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+            &mut interactor,
+        )
+        .unwrap();
+        assert!(fs::read_to_string(output.path())
+            .unwrap()
+            .contains("next.rst"));
+    }
+
+    fn plain_text(doc: &Document) -> String {
+        render_content(doc, SourceFormat::PlainText, &RenderConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("parts")).unwrap();
+        fs::write(
+            dir.path().join("main.adoc"),
+            "= Guide\n\ninclude::parts/intro.adoc[]\n\ninclude::parts/usage.md[leveloffset=+1]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("parts/intro.adoc"),
+            "Intro text.\n\ninclude::note.txt[]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("parts/note.txt"), "A plain note.").unwrap();
+        fs::write(
+            dir.path().join("parts/usage.md"),
+            "## Usage\n\nRun **it**.\n",
+        )
+        .unwrap();
+
+        let opened = open_file(dir.path().join("main.adoc")).unwrap();
+        let text = plain_text(&opened.document);
+        assert!(text.contains("Intro text."));
+        assert!(text.contains("A plain note."));
+        assert!(text.contains("Usage"));
+        assert!(!text.contains("include::"));
+
+        // Markdown was converted to AsciiDoc before splicing
+        let content = fs::read_to_string(dir.path().join("main.adoc")).unwrap();
+        let expanded = resolve_includes(
+            &content,
+            SourceFormat::AsciiDoc,
+            &dir.path().join("main.adoc"),
+            None,
+        )
+        .unwrap();
+        assert!(expanded.contains("== Usage"));
+        assert!(expanded.contains("*it*"));
+
+        // Directives are left alone on request
+        let config = ParseConfig {
+            keep_includes: true,
+            ..Default::default()
+        };
+        let kept = open_file_with_config(dir.path().join("main.adoc"), &config).unwrap();
+        assert!(!plain_text(&kept.document).contains("Intro text."));
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        fs::create_dir(&docs).unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        fs::write(docs.join("escape.rst"), ".. include:: ../secret.txt\n").unwrap();
+        fs::write(docs.join("a.org"), "#+INCLUDE: \"b.md\"\n").unwrap();
+        fs::write(docs.join("b.md"), "{{#include a.org}}\n").unwrap();
+
+        assert!(matches!(
+            open_file(docs.join("escape.rst")),
+            Err(FileError::IncludeOutsideRoot { path }) if path == "../secret.txt"
+        ));
+
+        // A wider root lets the same include through
+        let config = ParseConfig {
+            include_root: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let opened = open_file_with_config(docs.join("escape.rst"), &config).unwrap();
+        assert!(plain_text(&opened.document).contains("secret"));
+
+        match open_file(docs.join("a.org")) {
+            Err(FileError::IncludeCycle { chain }) => {
+                assert_eq!(chain.len(), 3);
+                assert!(chain[0].ends_with("a.org") && chain[2].ends_with("a.org"));
+            }
+            other => panic!("expected a cycle, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_missing_include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("part.adoc"),
+            "Part text.\n\ninclude::gone.adoc[]\n",
+        )
+        .unwrap();
+        let main = dir.path().join("main.adoc");
+        fs::write(
+            &main,
+            "= Main\n\ninclude::missing.adoc[]\n\ninclude::part.adoc[]\n",
+        )
+        .unwrap();
+
+        // Both directives stay as written, only the one in the opened file
+        // with a span
+        let opened = open_file(&main).unwrap();
+        let missing: Vec<_> = opened
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code == MISSING_INCLUDE_CODE)
+            .collect();
+        assert_eq!(missing.len(), 2);
+        assert!(missing[0].message.contains("missing.adoc"));
+        assert_eq!(missing[0].span.unwrap().line, 3);
+        assert!(missing[1].message.contains("gone.adoc"));
+        assert!(missing[1].span.is_none());
+        assert!(plain_text(&opened.document).contains("Part text."));
+
+        let content = fs::read_to_string(&main).unwrap();
+        let expanded = resolve_includes(&content, SourceFormat::AsciiDoc, &main, None).unwrap();
+        assert!(expanded.contains("include::missing.adoc[]\n"));
+        assert!(expanded.contains("include::gone.adoc[]\n"));
+    }
+
+    #[test]
+    fn test_include_fan_out() {
+        // Each level includes the next eight times, so the leaf's megabyte
+        // would be spliced in 512 times
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("d.md"), "x".repeat(1024 * 1024)).unwrap();
+        for (name, next) in [("c.md", "d.md"), ("b.md", "c.md"), ("a.md", "b.md")] {
+            let line = format!("{{{{#include {}}}}}\n", next);
+            fs::write(dir.path().join(name), line.repeat(8)).unwrap();
+        }
+        assert!(matches!(
+            open_file(dir.path().join("a.md")),
+            Err(FileError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_convert_file_with_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("img")).unwrap();
+        fs::write(dir.path().join("img/chart.svg"), "<svg/>").unwrap();
+        fs::write(
+            dir.path().join("report.md"),
+            "# Report\n\n![chart](img/chart.svg)\n",
+        )
+        .unwrap();
+
+        let output = dir.path().join("out/report.adoc");
+        fs::create_dir(dir.path().join("out")).unwrap();
+        let collected = convert_file_with_assets(
+            dir.path().join("report.md"),
+            &output,
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+            &AssetOptions {
+                dir: PathBuf::from("media"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(collected.len(), 1);
+        assert!(dir.path().join("out/media/chart.svg").is_file());
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .contains("media/chart.svg"));
+    }
+
+    #[test]
+    fn test_embed_and_extract_on_save_and_open() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("pic.gif"), b"GIF89a").unwrap();
+        fs::write(dir.path().join("page.md"), "![pic](pic.gif)\n").unwrap();
+
+        let opened = open_file(dir.path().join("page.md")).unwrap();
+        let config = RenderConfig {
+            embed_images: true,
+            ..Default::default()
+        };
+        let bundled = dir.path().join("bundled.md");
+        save_file_with_config(&opened.document, &bundled, &config).unwrap();
+        assert!(fs::read_to_string(&bundled)
+            .unwrap()
+            .contains("data:image/gif;base64,"));
+
+        let config = ParseConfig {
+            extract_images: Some(PathBuf::from("img")),
+            ..Default::default()
+        };
+        let reopened = open_file_with_config(&bundled, &config).unwrap();
+        assert_eq!(
+            fs::read(dir.path().join("img/image-1.gif")).unwrap(),
+            b"GIF89a"
+        );
+        assert!(render_content(
+            &reopened.document,
+            SourceFormat::Markdown,
+            &RenderConfig::default()
+        )
+        .unwrap()
+        .contains("img/image-1.gif"));
+    }
+
+    #[test]
+    fn test_open_and_save_in_original_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.txt");
+        fs::write(&path, b"\x93Caf\xE9\x94 menu").unwrap();
+
+        let opened = open_file(&path).unwrap();
+        assert_eq!(opened.file_info.encoding, TextEncoding::Windows1252);
+        assert_eq!(
+            plain_text(&opened.document),
+            "\u{201C}Caf\u{e9}\u{201D} menu"
+        );
+
+        let config = RenderConfig {
+            encoding: opened.file_info.encoding,
+            ..Default::default()
+        };
+        save_file_with_config(&opened.document, &path, &config).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\x93Caf\xE9\x94 menu");
+
+        let mut doc = opened.document;
+        doc.content.push(Block::Paragraph {
+            content: vec![Inline::Text {
+                content: "\u{2603}".to_string(),
+            }],
+            span: None,
+        });
+        assert!(matches!(
+            save_file_with_config(&doc, &path, &config),
+            Err(FileError::Unencodable {
+                character: '\u{2603}',
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_preserve_line_endings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Notes\r\n\r\nFirst line\r\n").unwrap();
+
+        let opened = open_file(&path).unwrap();
+        assert_eq!(opened.file_info.line_ending, LineEnding::CrLf);
+
+        let config = RenderConfig {
+            line_ending: LineEnding::Preserve,
+            ..Default::default()
+        };
+        save_file_with_config(&opened.document, &path, &config).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("# Notes\r\n"));
+        assert!(!saved.replace("\r\n", "").contains('\n'));
+
+        // A new file has nothing to preserve
+        let fresh = dir.path().join("fresh.md");
+        save_file_with_config(&opened.document, &fresh, &config).unwrap();
+        assert!(!fs::read_to_string(&fresh).unwrap().contains('\r'));
+    }
+
+    #[test]
+    fn test_atomic_save_with_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Version 1\n").unwrap();
+
+        let doc = |title: &str| {
+            MarkdownHandler::new()
+                .parse(&format!("# {}\n", title), &ParseConfig::default())
+                .unwrap()
+        };
+
+        let single = RenderConfig {
+            backup: BackupPolicy::Single,
+            ..Default::default()
+        };
+        save_file_with_config(&doc("Version 2"), &path, &single).unwrap();
+        save_file_with_config(&doc("Version 3"), &path, &single).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("Version 3"));
+        let bak = fs::read_to_string(dir.path().join("notes.md.bak")).unwrap();
+        assert!(bak.contains("Version 2"));
+
+        let numbered = RenderConfig {
+            backup: BackupPolicy::Numbered,
+            ..Default::default()
+        };
+        save_file_with_config(&doc("Version 4"), &path, &numbered).unwrap();
+        save_file_with_config(&doc("Version 5"), &path, &numbered).unwrap();
+        let first = fs::read_to_string(dir.path().join("notes.md.~1~")).unwrap();
+        let second = fs::read_to_string(dir.path().join("notes.md.~2~")).unwrap();
+        assert!(first.contains("Version 3"));
+        assert!(second.contains("Version 4"));
+
+        // No temporary files are left behind
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(
+            names.iter().all(|name| !name.ends_with(".tmp")),
+            "{:?}",
+            names
+        );
+    }
+
+    #[test]
+    fn test_atomic_save_refuses_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.md");
+        fs::write(&path, "# Locked\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let result = write_atomic(&path, b"# Changed\n", BackupPolicy::None);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Locked\n");
+    }
+
+    #[test]
+    fn test_convert_in_memory() {
+        let org = convert_string(
+            "# Title\n\nSome *text*.\n",
+            SourceFormat::Markdown,
+            SourceFormat::OrgMode,
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+        )
+        .unwrap();
+        assert!(org.contains("* Title"));
+
+        // Format and encoding both detected from a UTF-16 stream
+        let input = encode(
+            "#+TITLE: Notes\n\n* Heading\n\nBody.\n",
+            TextEncoding::Utf16Le,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        convert_writer(
+            input.as_slice(),
+            &mut output,
+            None,
+            SourceFormat::Markdown,
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+        )
+        .unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("# Heading"));
+    }
+
+    #[test]
+    fn test_open_recovers_from_parse_errors() {
+        use crate::recovery::RECOVERY_CODE;
+
+        // The RST grammar rejects an empty bullet followed by text
+        let mut file = NamedTempFile::with_suffix(".rst").unwrap();
+        write!(file, "Title\n=====\n\nBefore.\n\n*  \nx\n\nAfter.\n").unwrap();
+
+        let opened = open_file(file.path()).unwrap();
+        assert_eq!(opened.diagnostics.len(), 1);
+        assert_eq!(opened.diagnostics[0].code, RECOVERY_CODE);
+        assert_eq!(opened.diagnostics[0].span.unwrap().line, 6);
+        assert!(matches!(
+            opened.document.content.as_slice(),
+            [
+                Block::Heading { .. },
+                Block::Paragraph { .. },
+                Block::Raw { .. },
+                Block::Paragraph { .. }
+            ]
+        ));
+
+        let strict = ParseConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let result = open_file_with_config(file.path(), &strict);
+        assert!(matches!(result, Err(FileError::Parse(_))));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        use crate::traits::ParseLimits;
+
+        let mut file = NamedTempFile::with_suffix(".md").unwrap();
+        write!(file, "# Title\n\n> > > Deep\n\n- one\n- two\n").unwrap();
+        assert!(open_file(file.path()).is_ok());
+
+        let limited = |limits: ParseLimits| ParseConfig {
+            limits,
+            ..Default::default()
+        };
+        for limits in [
+            ParseLimits {
+                max_input_bytes: Some(8),
+                ..Default::default()
+            },
+            ParseLimits {
+                max_depth: Some(2),
+                ..Default::default()
+            },
+            ParseLimits {
+                max_blocks: Some(4),
+                ..Default::default()
+            },
+        ] {
+            let result = open_file_with_config(file.path(), &limited(limits));
+            assert!(
+                matches!(result, Err(FileError::LimitExceeded(_))),
+                "{:?}",
+                limits
+            );
+        }
+
+        let untrusted = open_file_with_config(file.path(), &limited(ParseLimits::untrusted()));
+        assert!(untrusted.is_ok());
+
+        // Only the Djot reader can stop at a timeout, so other formats
+        // refuse one rather than parse on past it
+        let timed = ParseLimits {
+            timeout: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let result = open_file_with_config(file.path(), &limited(timed));
+        assert!(matches!(result, Err(FileError::LimitExceeded(_))));
+        let mut djot = NamedTempFile::with_suffix(".dj").unwrap();
+        write!(djot, "# Title\n\nText\n").unwrap();
+        assert!(open_file_with_config(djot.path(), &limited(timed)).is_ok());
+
+        let mut deep = NamedTempFile::with_suffix(".md").unwrap();
+        write!(deep, "{} Deep", ">".repeat(50_000)).unwrap();
+        let result = open_file_with_config(deep.path(), &limited(ParseLimits::untrusted()));
+        assert!(matches!(result, Err(FileError::LimitExceeded(_))));
+
+        // Includes count towards the size of the file including them
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("big.adoc"), "text ".repeat(100)).unwrap();
+        let main = dir.path().join("main.adoc");
+        fs::write(&main, "= Main\n\ninclude::big.adoc[]\n").unwrap();
+        let small = ParseLimits {
+            max_input_bytes: Some(64),
+            ..Default::default()
+        };
+        assert!(open_file(&main).is_ok());
+        let result = open_file_with_config(&main, &limited(small));
+        assert!(matches!(result, Err(FileError::LimitExceeded(_))));
+
+        // A stream is read no further than the limit
+        let result = convert_reader(
+            io::repeat(b'a'),
+            Some(SourceFormat::Markdown),
+            SourceFormat::Djot,
+            &limited(small),
+            &RenderConfig::default(),
+        );
+        assert!(matches!(result, Err(FileError::LimitExceeded(_))));
+    }
+
     #[test]
     fn test_supported_extensions() {
         assert!(is_supported_extension("md"));
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Async wrappers around the file operations, for tokio hosts
+//!
+//! Parsing and rendering are CPU-bound and the file operations already do
+//! their own IO, so each call runs the synchronous version on tokio's blocking
+//! thread pool rather than on an async worker thread. Arguments are cloned
+//! into the blocking task.
+
+use super::{FileError, FileResult, OpenedDocument};
+use crate::ast::Document;
+use crate::traits::{ParseConfig, RenderConfig};
+use std::path::Path;
+
+/// Open a file with [`open_file_with_config`](super::open_file_with_config)
+pub async fn open_file_async(
+    path: impl AsRef<Path>,
+    config: &ParseConfig,
+) -> FileResult<OpenedDocument> {
+    let path = path.as_ref().to_path_buf();
+    let config = config.clone();
+    blocking(move || super::open_file_with_config(path, &config)).await
+}
+
+/// Save a document with [`save_file_with_config`](super::save_file_with_config)
+pub async fn save_file_async(
+    doc: &Document,
+    path: impl AsRef<Path>,
+    config: &RenderConfig,
+) -> FileResult<()> {
+    let doc = doc.clone();
+    let path = path.as_ref().to_path_buf();
+    let config = config.clone();
+    blocking(move || super::save_file_with_config(&doc, path, &config)).await
+}
+
+/// Convert a file with [`convert_file_with_config`](super::convert_file_with_config)
+pub async fn convert_file_async(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    parse_config: &ParseConfig,
+    render_config: &RenderConfig,
+) -> FileResult<()> {
+    let input_path = input_path.as_ref().to_path_buf();
+    let output_path = output_path.as_ref().to_path_buf();
+    let parse_config = parse_config.clone();
+    let render_config = render_config.clone();
+    blocking(move || {
+        super::convert_file_with_config(input_path, output_path, &parse_config, &render_config)
+    })
+    .await
+}
+
+/// Run `task` on the blocking pool, turning a panic into an IO error
+async fn blocking<T, F>(task: F) -> FileResult<T>
+where
+    F: FnOnce() -> FileResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|err| FileError::Io(std::io::Error::other(err)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::SourceFormat;
+    use crate::traits::ParseLimits;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_open_save_convert() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("notes.md");
+        fs::write(&input, "# Notes\n\nSome *text*.\n").unwrap();
+
+        let opened = open_file_async(&input, &ParseConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::Markdown);
+        assert!(!opened.document.content.is_empty());
+
+        let copy = dir.path().join("copy.md");
+        save_file_async(&opened.document, &copy, &RenderConfig::default())
+            .await
+            .unwrap();
+        assert!(fs::read_to_string(&copy).unwrap().contains("# Notes"));
+
+        let org = dir.path().join("notes.org");
+        convert_file_async(
+            &input,
+            &org,
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert!(fs::read_to_string(&org).unwrap().contains("* Notes"));
+
+        let missing = open_file_async(dir.path().join("missing.md"), &ParseConfig::default()).await;
+        assert!(matches!(missing, Err(FileError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.dj");
+        fs::write(&path, "# Notes\n\nSome _text_.\n\n- one\n- two\n").unwrap();
+
+        let first = open_file_async(&path, &ParseConfig::default())
+            .await
+            .unwrap();
+        save_file_async(&first.document, &path, &RenderConfig::default())
+            .await
+            .unwrap();
+        let second = open_file_async(&path, &ParseConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(second.file_info.format, SourceFormat::Djot);
+        assert_eq!(
+            format!("{:?}", second.document.content),
+            format!("{:?}", first.document.content)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("notes.md");
+        fs::write(&input, "# Notes\n\nSome text.\n").unwrap();
+
+        let config = ParseConfig {
+            limits: ParseLimits {
+                max_input_bytes: Some(4),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = open_file_async(&input, &config).await;
+        assert!(matches!(result, Err(FileError::LimitExceeded(_))));
+
+        let doc = Document::new(SourceFormat::Markdown);
+        let result = save_file_async(
+            &doc,
+            dir.path().join("missing/notes.md"),
+            &RenderConfig::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(FileError::Io(_))));
+
+        let result = convert_file_async(
+            dir.path().join("missing.md"),
+            dir.path().join("out.org"),
+            &ParseConfig::default(),
+            &RenderConfig::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(FileError::Io(_))));
+        assert!(!dir.path().join("out.org").exists());
+    }
+}
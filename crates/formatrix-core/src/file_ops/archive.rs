@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Compressed files and documents inside archives
+//!
+//! Opening `notes.md.gz` or `notes.org.zst` decompresses the file and detects
+//! the format from the inner extension. A document inside a `.zip`, `.tar`,
+//! `.tar.gz`/`.tgz` or `.tar.zst` archive is opened through a virtual path of
+//! the form `backup.zip!/docs/notes.md`. Opening the archive itself works when
+//! it holds exactly one document; [`archive_documents`] lists the choices
+//! otherwise.
+//!
+//! Documents opened this way are marked read-only, since saving writes a
+//! plain file rather than updating the compressed file or archive.
+
+use super::{format_from_extension, read_limited, FileError, FileResult, Source};
+use crate::traits::ParseLimits;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Separates the archive from the entry in a virtual path
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar(Option<Compression>),
+}
+
+/// Paths of the supported documents inside the archive at `path`
+pub fn archive_documents(path: impl AsRef<Path>) -> FileResult<Vec<String>> {
+    let path = path.as_ref();
+    let kind = archive_kind(path).ok_or_else(|| FileError::UnknownFormat {
+        path: path.to_string_lossy().into_owned(),
+    })?;
+    let mut names = Vec::new();
+    scan(path, kind, &mut |name, _| {
+        if format_from_extension(Path::new(name)).is_some() {
+            names.push(name.to_string());
+        }
+        Ok(())
+    })?;
+    names.sort();
+    Ok(names)
+}
+
+/// Read `path` if it is compressed, in an archive or an archive itself
+///
+/// Returns `None` for ordinary files.
+pub(super) fn read_source(path: &Path, limits: &ParseLimits) -> FileResult<Option<Source>> {
+    let text = path.to_string_lossy();
+    if let Some((archive, entry)) = text.split_once(ARCHIVE_SEPARATOR) {
+        let archive = Path::new(archive);
+        let kind = archive_kind(archive).ok_or_else(|| FileError::UnknownFormat {
+            path: archive.to_string_lossy().into_owned(),
+        })?;
+        return read_entry(archive, kind, entry, limits).map(Some);
+    }
+
+    if let Some(kind) = archive_kind(path) {
+        let documents = archive_documents(path)?;
+        return match documents.as_slice() {
+            [entry] => read_entry(path, kind, entry, limits).map(Some),
+            [] => Err(FileError::Archive(format!(
+                "{} holds no supported documents",
+                text
+            ))),
+            _ => Err(FileError::Archive(format!(
+                "{} holds {} documents; open one as {}{}{}",
+                text,
+                documents.len(),
+                text,
+                ARCHIVE_SEPARATOR,
+                documents[0]
+            ))),
+        };
+    }
+
+    let Some(compression) = compression_of(path) else {
+        return Ok(None);
+    };
+    let metadata = fs::metadata(path)?;
+    let bytes = decompress(File::open(path)?, compression, limits)?;
+    limits.check_size(bytes.len())?;
+    Ok(Some(Source {
+        size: metadata.len(),
+        bytes,
+        // `notes.md.gz` is detected as `notes.md`
+        name: path.with_extension(""),
+        dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        read_only: true,
+        in_archive: false,
+        stamp: None,
+    }))
+}
+
+fn read_entry(
+    archive: &Path,
+    kind: ArchiveKind,
+    entry: &str,
+    limits: &ParseLimits,
+) -> FileResult<Source> {
+    let wanted = entry.trim_start_matches("./");
+    let mut found = None;
+    scan(archive, kind, &mut |name, reader| {
+        if found.is_none() && name == wanted {
+            found = Some(read_limited(reader, limits)?);
+        }
+        Ok(())
+    })?;
+    let bytes = found.ok_or_else(|| {
+        FileError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} not found in {}", wanted, archive.display()),
+        ))
+    })?;
+    limits.check_size(bytes.len())?;
+    Ok(Source {
+        size: bytes.len() as u64,
+        bytes,
+        name: PathBuf::from(wanted),
+        dir: archive.parent().unwrap_or(Path::new("")).to_path_buf(),
+        read_only: true,
+        in_archive: true,
+        stamp: None,
+    })
+}
+
+/// Call `visit` with the name and contents of each file in an archive
+fn scan(
+    path: &Path,
+    kind: ArchiveKind,
+    visit: &mut dyn FnMut(&str, &mut dyn Read) -> io::Result<()>,
+) -> FileResult<()> {
+    let file = File::open(path)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file).map_err(zip_error)?;
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index).map_err(zip_error)?;
+                if entry.is_file() {
+                    let name = entry.name().trim_start_matches("./").to_string();
+                    visit(&name, &mut entry)?;
+                }
+            }
+        }
+        ArchiveKind::Tar(compression) => {
+            let reader: Box<dyn Read> = match compression {
+                None => Box::new(file),
+                Some(Compression::Gzip) => Box::new(flate2::read::GzDecoder::new(file)),
+                Some(Compression::Zstd) => Box::new(zstd::Decoder::new(file)?),
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    let name = entry.path()?.to_string_lossy().into_owned();
+                    let name = name.trim_start_matches("./").to_string();
+                    visit(&name, &mut entry)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decompress(
+    reader: impl Read,
+    compression: Compression,
+    limits: &ParseLimits,
+) -> io::Result<Vec<u8>> {
+    match compression {
+        Compression::Gzip => read_limited(flate2::read::GzDecoder::new(reader), limits),
+        Compression::Zstd => read_limited(zstd::Decoder::new(reader)?, limits),
+    }
+}
+
+fn compression_of(path: &Path) -> Option<Compression> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "gz" => Some(Compression::Gzip),
+        "zst" => Some(Compression::Zstd),
+        _ => None,
+    }
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar(None))
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::Tar(Some(Compression::Gzip)))
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Some(ArchiveKind::Tar(Some(Compression::Zstd)))
+    } else {
+        None
+    }
+}
+
+fn zip_error(err: zip::result::ZipError) -> FileError {
+    match err {
+        zip::result::ZipError::Io(err) => FileError::Io(err),
+        other => FileError::Archive(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{open_file, open_file_with_config};
+    use super::*;
+    use crate::ast::SourceFormat;
+    use std::io::Write;
+
+    #[test]
+    fn test_open_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let gz = dir.path().join("notes.md.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&gz).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"# Notes\n\nCompressed.\n").unwrap();
+        encoder.finish().unwrap();
+
+        let opened = open_file(&gz).unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::Markdown);
+        assert!(opened.file_info.read_only);
+
+        let zst = dir.path().join("notes.org.zst");
+        let bytes = zstd::encode_all(&b"* Notes\n\nCompressed.\n"[..], 0).unwrap();
+        fs::write(&zst, bytes).unwrap();
+        assert_eq!(
+            open_file(&zst).unwrap().file_info.format,
+            SourceFormat::OrgMode
+        );
+    }
+
+    #[test]
+    fn test_open_from_archives() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let zip_path = dir.path().join("wiki.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, body) in [
+            ("docs/home.md", "# Home\n"),
+            ("docs/setup.adoc", "= Setup\n"),
+            ("logo.png", "\u{89}PNG"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(body.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(
+            archive_documents(&zip_path).unwrap(),
+            ["docs/home.md", "docs/setup.adoc"]
+        );
+        assert!(matches!(open_file(&zip_path), Err(FileError::Archive(_))));
+
+        let virtual_path = format!("{}!/docs/setup.adoc", zip_path.display());
+        let opened = open_file(&virtual_path).unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::AsciiDoc);
+        assert_eq!(opened.file_info.path, virtual_path);
+        assert!(opened.file_info.read_only);
+
+        let missing = open_file(format!("{}!/docs/gone.md", zip_path.display()));
+        assert!(matches!(missing, Err(FileError::Io(_))));
+
+        // A tarball holding a single document opens directly
+        let tgz = dir.path().join("export.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&tgz).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let body = b"#+TITLE: Export\n\n* Heading\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(body.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "export/notes.org", &body[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let opened = open_file(&tgz).unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::OrgMode);
+        assert_eq!(opened.document.meta.title.as_deref(), Some("Export"));
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::traits::ParseConfig {
+            limits: ParseLimits {
+                max_input_bytes: Some(4096),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let text = "a".repeat(1 << 20);
+
+        // A megabyte of text compresses to a file well under the limit
+        let gz = dir.path().join("bomb.md.gz");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&gz).unwrap(), flate2::Compression::best());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        assert!(fs::metadata(&gz).unwrap().len() < 4096);
+        assert!(matches!(
+            open_file_with_config(&gz, &config),
+            Err(FileError::LimitExceeded(_))
+        ));
+
+        let zip_path = dir.path().join("big.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        writer
+            .start_file("big.md", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(text.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert!(matches!(
+            open_file_with_config(&zip_path, &config),
+            Err(FileError::LimitExceeded(_))
+        ));
+    }
+}
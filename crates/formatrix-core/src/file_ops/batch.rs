@@ -0,0 +1,446 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Conversion of whole directory trees
+//!
+//! [`convert_dir`] walks the input directory, picks the files whose path
+//! (relative to the input directory, with `/` separators) matches a glob, and
+//! writes each one to the same relative place under the output directory with
+//! the target format's extension. Hidden directories are skipped, and so is
+//! the output directory when it lies inside the input. Files are converted in
+//! parallel on a rayon thread pool.
+//!
+//! Globs support `*` and `?` within a path segment, `**` for any number of
+//! segments, and `{a,b}` alternatives, so `**/*.{md,org}` picks every Markdown
+//! and Org file in the tree.
+
+use super::{format_from_extension, open_file_with_config, save_file_as, FileError, FileResult};
+use crate::ast::SourceFormat;
+use crate::traits::{ParseConfig, RenderConfig};
+use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Settings for [`convert_dir`]
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Configuration used to parse every input file
+    pub parse: ParseConfig,
+    /// Configuration used to render every output file
+    pub render: RenderConfig,
+    /// Files converted at once; 0 means one per CPU
+    pub jobs: usize,
+}
+
+/// What happened to one file in a batch
+///
+/// Serializes as `{"status": "converted"}`, with the error message under
+/// `error` for a failure.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "lowercase")]
+pub enum BatchOutcome {
+    /// Written to the output path
+    Converted,
+    /// Matched the glob but is not in a supported format
+    Skipped,
+    /// Reading, parsing, rendering or writing failed
+    Failed(#[serde(serialize_with = "error_message")] FileError),
+}
+
+fn error_message<S: Serializer>(error: &FileError, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(error)
+}
+
+/// One matched file in a batch
+#[derive(Debug, Serialize)]
+pub struct BatchEntry {
+    /// Path of the input, relative to the input directory
+    pub input: PathBuf,
+    /// Where the output went, or would have gone
+    pub output: PathBuf,
+    #[serde(flatten)]
+    pub outcome: BatchOutcome,
+}
+
+/// Progress through a batch, reported as each file finishes
+#[derive(Debug)]
+pub struct BatchProgress<'a> {
+    /// The file that just finished
+    pub entry: &'a BatchEntry,
+    /// Files finished so far, including this one
+    pub done: usize,
+    /// Files matched in total
+    pub total: usize,
+}
+
+/// Per-file results of [`convert_dir`], in path order
+#[derive(Debug, Default, Serialize)]
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    /// Number of files written
+    pub fn converted(&self) -> usize {
+        self.count(|outcome| matches!(outcome, BatchOutcome::Converted))
+    }
+
+    /// Number of files skipped as unsupported
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, BatchOutcome::Skipped))
+    }
+
+    /// Files that failed, with their errors
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, &FileError)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match &entry.outcome {
+                BatchOutcome::Failed(err) => Some((entry.input.as_path(), err)),
+                _ => None,
+            })
+    }
+
+    /// Whether every matched file was converted or skipped
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    fn count(&self, pred: impl Fn(&BatchOutcome) -> bool) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| pred(&entry.outcome))
+            .count()
+    }
+}
+
+/// Convert every file under `input_dir` that matches `glob` to `target_format`
+///
+/// Outputs keep their relative paths under `output_dir`, which is created as
+/// needed. Failures are recorded per file and do not stop the batch; only a
+/// failure to read the input directory itself is returned as an error.
+pub fn convert_dir(
+    input_dir: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    glob: &str,
+    target_format: SourceFormat,
+    options: &BatchOptions,
+) -> FileResult<BatchReport> {
+    convert_dir_with_progress(input_dir, output_dir, glob, target_format, options, &|_| {})
+}
+
+/// [`convert_dir`], calling `progress` as each file finishes
+///
+/// Files finish in no particular order, and `progress` may be called from
+/// several threads at once.
+pub fn convert_dir_with_progress(
+    input_dir: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    glob: &str,
+    target_format: SourceFormat,
+    options: &BatchOptions,
+    progress: &(dyn Fn(BatchProgress<'_>) + Sync),
+) -> FileResult<BatchReport> {
+    let input_dir = input_dir.as_ref();
+    let output_dir = output_dir.as_ref();
+    let inputs = matched_inputs(input_dir, output_dir, glob)?;
+    let pool = thread_pool(options.jobs)?;
+    let total = inputs.len();
+    let done = AtomicUsize::new(0);
+
+    let entries = pool.install(|| {
+        inputs
+            .into_par_iter()
+            .map(|input| {
+                let output = output_dir
+                    .join(&input)
+                    .with_extension(target_format.extension());
+                let outcome = convert_one(&input_dir.join(&input), &output, target_format, options);
+                let entry = BatchEntry {
+                    input,
+                    output,
+                    outcome,
+                };
+                progress(BatchProgress {
+                    entry: &entry,
+                    done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                    total,
+                });
+                entry
+            })
+            .collect()
+    });
+    Ok(BatchReport { entries })
+}
+
+/// Files under `input_dir` matching `glob`, relative to it and sorted
+pub(crate) fn matched_inputs(
+    input_dir: &Path,
+    output_dir: &Path,
+    glob: &str,
+) -> FileResult<Vec<PathBuf>> {
+    let pattern = Glob::new(glob);
+    let mut inputs = Vec::new();
+    let skip_dir = fs::canonicalize(output_dir).ok();
+    collect_files(input_dir, Path::new(""), skip_dir.as_deref(), &mut inputs)?;
+    inputs.retain(|relative| pattern.matches(relative));
+    Ok(inputs)
+}
+
+/// Thread pool running `jobs` files at once; 0 means one per CPU
+pub(super) fn thread_pool(jobs: usize) -> FileResult<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|err| FileError::Io(std::io::Error::other(err)))
+}
+
+fn convert_one(
+    input: &Path,
+    output: &Path,
+    target_format: SourceFormat,
+    options: &BatchOptions,
+) -> BatchOutcome {
+    if format_from_extension(input).is_none() {
+        return BatchOutcome::Skipped;
+    }
+    let result = open_file_with_config(input, &options.parse).and_then(|opened| {
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        save_file_as(&opened.document, output, target_format, &options.render)
+    });
+    match result {
+        Ok(()) => BatchOutcome::Converted,
+        Err(err) => BatchOutcome::Failed(err),
+    }
+}
+
+/// Collect the paths of all files under `root.join(relative)`, relative to
+/// `root` and sorted
+fn collect_files(
+    root: &Path,
+    relative: &Path,
+    skip_dir: Option<&Path>,
+    files: &mut Vec<PathBuf>,
+) -> FileResult<()> {
+    let dir = root.join(relative);
+    let mut entries: Vec<_> = fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let is_output = skip_dir.is_some_and(|skip| {
+                fs::canonicalize(entry.path()).is_ok_and(|canonical| canonical == skip)
+            });
+            if !hidden && !is_output {
+                collect_files(root, &path, skip_dir, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// A path glob, expanded into its `{a,b}` alternatives
+struct Glob {
+    alternatives: Vec<Vec<String>>,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Self {
+        let pattern = if pattern.is_empty() { "**" } else { pattern };
+        let alternatives = expand_braces(pattern)
+            .into_iter()
+            .map(|alt| {
+                alt.split('/')
+                    .filter(|segment| !segment.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect();
+        Self { alternatives }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let segments: Vec<String> = path
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        self.alternatives
+            .iter()
+            .any(|pattern| match_segments(pattern, &segments))
+    }
+}
+
+/// Expand the first `{a,b}` group, recursively
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| open + i) else {
+        return vec![pattern.to_string()];
+    };
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|choice| expand_braces(&format!("{}{}{}", head, choice, tail)))
+        .collect()
+}
+
+fn match_segments(pattern: &[String], segments: &[String]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=segments.len()).any(|skip| match_segments(rest, &segments[skip..]))
+        }
+        Some((first, rest)) => segments.split_first().is_some_and(|(segment, remaining)| {
+            match_segment(first.as_bytes(), segment.as_bytes()) && match_segments(rest, remaining)
+        }),
+    }
+}
+
+/// Match one path segment against `*` and `?` wildcards
+fn match_segment(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| match_segment(rest, &text[skip..])),
+        Some((b'?', rest)) => {
+            // Step over one whole UTF-8 character
+            let width = text
+                .first()
+                .map(|&b| match b {
+                    0xF0.. => 4,
+                    0xE0.. => 3,
+                    0xC0.. => 2,
+                    _ => 1,
+                })
+                .unwrap_or(0);
+            width > 0 && text.len() >= width && match_segment(rest, &text[width..])
+        }
+        Some((&c, rest)) => text.first() == Some(&c) && match_segment(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let glob = Glob::new("**/*.{md,org}");
+        assert!(glob.matches(Path::new("a.md")));
+        assert!(glob.matches(Path::new("x/y/b.org")));
+        assert!(!glob.matches(Path::new("x/c.txt")));
+
+        let glob = Glob::new("docs/?.md");
+        assert!(glob.matches(Path::new("docs/é.md")));
+        assert!(!glob.matches(Path::new("docs/ab.md")));
+        assert!(!glob.matches(Path::new("other/a.md")));
+
+        assert!(Glob::new("").matches(Path::new("any/thing")));
+    }
+
+    #[test]
+    fn test_convert_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("wiki");
+        fs::create_dir_all(input.join("guide/.drafts")).unwrap();
+        fs::write(input.join("index.md"), "# Home\n\nWelcome.\n").unwrap();
+        fs::write(input.join("guide/setup.md"), "# Setup\n\nSteps.\n").unwrap();
+        fs::write(input.join("guide/notes.org"), "* Notes\n").unwrap();
+        fs::write(input.join("guide/logo.png"), b"\x89PNG").unwrap();
+        fs::write(input.join("guide/.drafts/wip.md"), "# WIP\n").unwrap();
+
+        // Output inside the input tree is not converted again
+        let output = input.join("out");
+        let report = convert_dir(
+            &input,
+            &output,
+            "**/*",
+            SourceFormat::AsciiDoc,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+
+        let inputs: Vec<_> = report
+            .entries
+            .iter()
+            .map(|entry| entry.input.to_string_lossy().replace('\\', "/"))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                "guide/logo.png",
+                "guide/notes.org",
+                "guide/setup.md",
+                "index.md"
+            ]
+        );
+        assert_eq!(report.converted(), 3);
+        assert_eq!(report.skipped(), 1);
+        assert!(report.is_success());
+        assert!(output.join("guide/setup.adoc").is_file());
+        assert!(output.join("guide/notes.adoc").is_file());
+        assert!(output.join("index.adoc").is_file());
+        assert!(!output.join("guide/.drafts").exists());
+
+        let again = convert_dir(
+            &input,
+            &output,
+            "guide/*.md",
+            SourceFormat::AsciiDoc,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(again.entries.len(), 1);
+
+        let json = serde_json::to_value(&again).unwrap();
+        assert_eq!(json["entries"][0]["status"], "converted");
+        assert!(json["entries"][0]["output"].is_string());
+    }
+
+    #[test]
+    fn test_convert_dir_in_parallel() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("notes");
+        fs::create_dir(&input).unwrap();
+        for n in 0..20 {
+            fs::write(
+                input.join(format!("note-{:02}.md", n)),
+                format!("# Note {}\n", n),
+            )
+            .unwrap();
+        }
+
+        let seen = AtomicUsize::new(0);
+        let last = AtomicUsize::new(0);
+        let report = convert_dir_with_progress(
+            &input,
+            dir.path().join("out"),
+            "*.md",
+            SourceFormat::OrgMode,
+            &BatchOptions {
+                jobs: 4,
+                ..Default::default()
+            },
+            &|progress| {
+                assert_eq!(progress.total, 20);
+                seen.fetch_add(1, Ordering::Relaxed);
+                last.fetch_max(progress.done, Ordering::Relaxed);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.converted(), 20);
+        assert_eq!(seen.load(Ordering::Relaxed), 20);
+        assert_eq!(last.load(Ordering::Relaxed), 20);
+        // Entries stay in path order however the work was split
+        assert_eq!(report.entries[3].input, Path::new("note-03.md"));
+        let org = fs::read_to_string(dir.path().join("out/note-19.org")).unwrap();
+        assert!(org.contains("Note 19"));
+    }
+}
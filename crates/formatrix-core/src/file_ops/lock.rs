@@ -0,0 +1,231 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Edit locks and detection of changes made behind an editor's back
+//!
+//! [`open_file_for_edit`] takes an advisory lock on a lock file beside the
+//! document (`.<name>.lock`), so a second editor that asks first is refused
+//! while the first one has it open. The lock belongs to the open file
+//! handle, so a crashed editor never leaves a document locked. The lock
+//! file itself is left behind and reused: deleting it on release would let
+//! an editor that had just opened it lock the deleted file while another
+//! locks a new one in its place.
+//!
+//! Locks are only advisory, so [`save_file_checked`] also compares the file
+//! against the [`FileStamp`] taken when it was opened and refuses to
+//! overwrite changes it has not seen.
+
+use super::{open_file_with_config, save_file_with_config, FileError, FileResult, OpenedDocument};
+use crate::ast::Document;
+use crate::traits::{ParseConfig, RenderConfig};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, TryLockError};
+use std::hash::Hasher;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What a file looked like when it was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+    /// Hash of the contents, checked when the modification time has moved
+    pub hash: u64,
+}
+
+impl FileStamp {
+    /// Stamp for `bytes` read from a file with `metadata`
+    pub fn new(bytes: &[u8], metadata: &fs::Metadata) -> Self {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(bytes);
+        Self {
+            modified: metadata.modified().ok(),
+            len: bytes.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
+
+    /// Stamp the file at `path` as it is now; `None` if it does not exist
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        let path = path.as_ref();
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(Self::new(&bytes, &fs::metadata(path)?))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the file at `path` still has the contents this stamp was
+    /// taken from
+    ///
+    /// An unchanged modification time and size are trusted without reading
+    /// the file; otherwise the contents are hashed, so a file that was only
+    /// touched still counts as unchanged.
+    pub fn is_current(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = path.as_ref();
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if metadata.len() != self.len {
+            return Ok(false);
+        }
+        if self.modified.is_some() && metadata.modified().ok() == self.modified {
+            return Ok(true);
+        }
+        Ok(Self::read(path)?.is_some_and(|now| now.hash == self.hash))
+    }
+}
+
+/// Advisory lock on a document, released when dropped
+///
+/// The lock is held on `.<name>.lock` beside the document. Dropping the
+/// lock releases it but leaves the file in place, so every document that
+/// has been opened for editing keeps a lock file next to it. They are
+/// empty apart from the last holder's process id and safe to delete while
+/// no editor has the document open.
+#[derive(Debug)]
+pub struct EditLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl EditLock {
+    /// Lock the document at `path`
+    ///
+    /// Fails with [`FileError::Locked`] if another editor holds the lock.
+    pub fn acquire(path: impl AsRef<Path>) -> FileResult<Self> {
+        let path = path.as_ref();
+        let lock_path = lock_path(path);
+        let mut file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(FileError::Locked {
+                    path: path.to_string_lossy().to_string(),
+                })
+            }
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+
+        // Record the holder for anyone looking at the file by hand
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Self {
+            file,
+            path: lock_path,
+        })
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for EditLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Open a file for editing, holding an [`EditLock`] on it
+pub fn open_file_for_edit(
+    path: impl AsRef<Path>,
+    config: &ParseConfig,
+) -> FileResult<(OpenedDocument, EditLock)> {
+    let path = path.as_ref();
+    let lock = EditLock::acquire(path)?;
+    let opened = open_file_with_config(path, config)?;
+    Ok((opened, lock))
+}
+
+/// Save over a file only if it has not changed since `stamp` was taken
+///
+/// Fails with [`FileError::ExternallyModified`] if the file was changed or
+/// deleted by something else, so the caller can offer to reload or merge.
+/// Returns the stamp of the newly written file, to check the next save
+/// against.
+pub fn save_file_checked(
+    doc: &Document,
+    path: impl AsRef<Path>,
+    stamp: &FileStamp,
+    config: &RenderConfig,
+) -> FileResult<FileStamp> {
+    let path = path.as_ref();
+    if !stamp.is_current(path)? {
+        return Err(FileError::ExternallyModified {
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+    save_file_with_config(doc, path, config)?;
+    FileStamp::read(path)?.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound).into())
+}
+
+/// `.<name>.lock` beside `path`
+fn lock_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.lock", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::SourceFormat;
+
+    #[test]
+    fn test_edit_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Notes\n").unwrap();
+
+        let (opened, lock) = open_file_for_edit(&path, &ParseConfig::default()).unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::Markdown);
+        assert_eq!(lock.path(), dir.path().join(".notes.md.lock"));
+        assert!(matches!(
+            EditLock::acquire(&path),
+            Err(FileError::Locked { .. })
+        ));
+
+        drop(lock);
+        assert!(dir.path().join(".notes.md.lock").exists());
+        let lock = EditLock::acquire(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(lock.path()).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+    }
+
+    #[test]
+    fn test_save_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Notes\n").unwrap();
+
+        let opened = open_file_with_config(&path, &ParseConfig::default()).unwrap();
+        let stamp = opened.file_info.stamp.unwrap();
+        assert!(stamp.is_current(&path).unwrap());
+
+        let config = RenderConfig::default();
+        let stamp = save_file_checked(&opened.document, &path, &stamp, &config).unwrap();
+        assert!(stamp.is_current(&path).unwrap());
+
+        fs::write(&path, "# Changed elsewhere\n").unwrap();
+        assert!(matches!(
+            save_file_checked(&opened.document, &path, &stamp, &config),
+            Err(FileError::ExternallyModified { .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Changed elsewhere\n");
+
+        fs::remove_file(&path).unwrap();
+        assert!(!stamp.is_current(&path).unwrap());
+    }
+}
@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Dry runs of a conversion
+//!
+//! [`plan_conversion`] does everything a conversion would except write: each
+//! input is parsed and rendered in memory, so the plan can say which files
+//! would be created or overwritten, what each would lose, and how large it
+//! would be.
+
+use super::batch::{matched_inputs, thread_pool, BatchOptions};
+use super::{format_from_extension, open_file_with_config, render_content, FileResult};
+use crate::ast::SourceFormat;
+use crate::encoding::encode;
+use crate::formats::handler_for;
+use crate::report::ConversionReport;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// What converting one file would do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum PlannedAction {
+    /// The output would be written
+    Convert {
+        /// Whether a file already exists at the output path
+        overwrites: bool,
+        /// Size of the output in its target encoding
+        estimated_bytes: usize,
+        report: ConversionReport,
+    },
+    /// The input is not in a supported format
+    Skip,
+    /// The input could not be read, parsed or rendered
+    Fail { error: String },
+}
+
+/// One file in a [`ConversionPlan`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedFile {
+    /// Path of the input; relative to the input directory when planning a
+    /// directory
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub action: PlannedAction,
+}
+
+/// Result of [`plan_conversion`], in path order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConversionPlan {
+    pub files: Vec<PlannedFile>,
+}
+
+impl ConversionPlan {
+    /// Files the conversion would write
+    pub fn outputs(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.action, PlannedAction::Convert { .. }))
+    }
+
+    /// Files that would lose at least one feature
+    pub fn lossy(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|file| {
+            matches!(&file.action, PlannedAction::Convert { report, .. } if !report.is_lossless())
+        })
+    }
+
+    /// Total size of all outputs
+    pub fn estimated_bytes(&self) -> usize {
+        self.files
+            .iter()
+            .map(|file| match file.action {
+                PlannedAction::Convert {
+                    estimated_bytes, ..
+                } => estimated_bytes,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// Work out what converting `input` to `target_format` would do, without
+/// writing anything
+///
+/// When `input` is a directory the plan covers every file under it matching
+/// `glob`, with outputs placed as [`convert_dir`](super::convert_dir) would
+/// place them under `output`. When it is a file, `glob` is ignored and
+/// `output` is the output file.
+pub fn plan_conversion(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    glob: &str,
+    target_format: SourceFormat,
+    options: &BatchOptions,
+) -> FileResult<ConversionPlan> {
+    let input = input.as_ref();
+    let output = output.as_ref();
+
+    if !input.is_dir() {
+        let action = plan_one(input, output, target_format, options);
+        return Ok(ConversionPlan {
+            files: vec![PlannedFile {
+                input: input.to_path_buf(),
+                output: output.to_path_buf(),
+                action,
+            }],
+        });
+    }
+
+    let inputs = matched_inputs(input, output, glob)?;
+    let files = thread_pool(options.jobs)?.install(|| {
+        inputs
+            .into_par_iter()
+            .map(|relative| {
+                let output = output
+                    .join(&relative)
+                    .with_extension(target_format.extension());
+                let action = plan_one(&input.join(&relative), &output, target_format, options);
+                PlannedFile {
+                    input: relative,
+                    output,
+                    action,
+                }
+            })
+            .collect()
+    });
+    Ok(ConversionPlan { files })
+}
+
+fn plan_one(
+    input: &Path,
+    output: &Path,
+    target_format: SourceFormat,
+    options: &BatchOptions,
+) -> PlannedAction {
+    if format_from_extension(input).is_none() {
+        return PlannedAction::Skip;
+    }
+    let fail = |error: String| PlannedAction::Fail { error };
+
+    let opened = match open_file_with_config(input, &options.parse) {
+        Ok(opened) => opened,
+        Err(err) => return fail(err.to_string()),
+    };
+    let doc = &opened.document;
+    let rendered = match render_content(doc, target_format, &options.render) {
+        Ok(rendered) => rendered,
+        Err(err) => return fail(err.to_string()),
+    };
+    let estimated_bytes = match encode(&rendered, options.render.encoding) {
+        Ok(bytes) => bytes.len(),
+        Err(character) => {
+            return fail(format!(
+                "cannot write {:?} in {}",
+                character, options.render.encoding
+            ))
+        }
+    };
+
+    PlannedAction::Convert {
+        overwrites: output.exists(),
+        estimated_bytes,
+        report: ConversionReport::new(doc, handler_for(target_format).as_ref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_plan_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("wiki");
+        fs::create_dir_all(input.join("guide")).unwrap();
+        fs::write(input.join("index.md"), "# Home\n\nSee $x^2$ here.\n").unwrap();
+        fs::write(input.join("guide/setup.org"), "* Setup\n").unwrap();
+        fs::write(input.join("guide/logo.png"), b"\x89PNG").unwrap();
+        let output = dir.path().join("out");
+        fs::create_dir_all(&output).unwrap();
+        fs::write(output.join("index.txt"), "old").unwrap();
+
+        let plan = plan_conversion(
+            &input,
+            &output,
+            "**/*",
+            SourceFormat::PlainText,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(plan.files.len(), 3);
+        assert_eq!(plan.files[0].action, PlannedAction::Skip);
+        assert_eq!(plan.outputs().count(), 2);
+        assert!(plan.estimated_bytes() > 0);
+        match &plan.files[2].action {
+            PlannedAction::Convert { overwrites, .. } => assert!(overwrites),
+            other => panic!("expected a conversion, got {:?}", other),
+        }
+        assert!(!output.join("guide").exists());
+        assert_eq!(fs::read_to_string(output.join("index.txt")).unwrap(), "old");
+
+        let single = plan_conversion(
+            input.join("index.md"),
+            dir.path().join("index.adoc"),
+            "",
+            SourceFormat::AsciiDoc,
+            &BatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(single.files.len(), 1);
+        assert!(!dir.path().join("index.adoc").exists());
+    }
+}
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Opening documents over HTTP(S)
+//!
+//! The format comes from the extension in the URL path, then from the
+//! `Content-Type` header, then from the content itself. Documents opened this
+//! way are read-only, and include directives are left as written since there
+//! is no directory to resolve them against.
+
+use super::{
+    format_from_content, format_from_extension, parse_opened, FileError, FileInfo, FileResult,
+    OpenedDocument,
+};
+use crate::ast::SourceFormat;
+use crate::encoding::decode;
+use crate::traits::{LineEnding, ParseConfig};
+use std::path::Path;
+use std::time::Duration;
+
+/// Largest response body read, in bytes
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch the document at `url` and parse it
+pub fn open_url(url: &str, config: &ParseConfig) -> FileResult<OpenedDocument> {
+    let http_error = |message: String| FileError::Http {
+        url: url.to_string(),
+        message,
+    };
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut response = agent
+        .get(url)
+        .header("Accept", "text/*;q=1.0, */*;q=0.5")
+        .call()
+        .map_err(|err| http_error(err.to_string()))?;
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_BODY)
+        .read_to_vec()
+        .map_err(|err| http_error(err.to_string()))?;
+
+    let (content, encoding) = decode(&bytes);
+    let line_ending = LineEnding::detect(&content);
+    let format = format_from_extension(Path::new(url_path(url)))
+        .or_else(|| content_type.as_deref().and_then(format_from_mime))
+        .unwrap_or_else(|| format_from_content(&content));
+    let (document, diagnostics) = parse_opened(&content, format, config)?;
+
+    Ok(OpenedDocument {
+        document,
+        file_info: FileInfo {
+            path: url.to_string(),
+            format,
+            size: bytes.len() as u64,
+            read_only: true,
+            encoding,
+            line_ending,
+            stamp: None,
+        },
+        diagnostics,
+        sidecar: None,
+    })
+}
+
+/// The path part of `url`, without query or fragment
+fn url_path(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split(['?', '#']).next().unwrap_or("");
+    rest.find('/').map_or("", |slash| &rest[slash..])
+}
+
+/// Format for a `Content-Type` value; `text/plain` is left to content
+/// detection since servers send it for everything
+fn format_from_mime(content_type: &str) -> Option<SourceFormat> {
+    let mime = content_type.split(';').next()?.trim().to_lowercase();
+    match mime.as_str() {
+        "text/markdown" | "text/x-markdown" => Some(SourceFormat::Markdown),
+        "text/asciidoc" | "text/x-asciidoc" => Some(SourceFormat::AsciiDoc),
+        "text/x-org" | "text/org" => Some(SourceFormat::OrgMode),
+        "text/x-rst" | "text/prs.fallenstein.rst" => Some(SourceFormat::ReStructuredText),
+        "text/x-djot" | "text/djot" => Some(SourceFormat::Djot),
+        "text/x-typst" | "text/vnd.typst" => Some(SourceFormat::Typst),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve one response on a local port and return its base URL
+    fn serve_once(content_type: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                content_type,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_open_url() {
+        let base = serve_once("text/plain", "= Guide\n\nFrom the web.\n");
+        let opened = open_url(
+            &format!("{}/docs/guide.adoc?raw=1", base),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::AsciiDoc);
+        assert!(opened.file_info.read_only);
+
+        let base = serve_once("text/x-org; charset=utf-8", "* Heading\n");
+        let opened = open_url(&format!("{}/raw", base), &Default::default()).unwrap();
+        assert_eq!(opened.file_info.format, SourceFormat::OrgMode);
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(url_path("https://host/a/b.md?x=1#top"), "/a/b.md");
+        assert_eq!(url_path("https://host"), "");
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Splitting one file into a file per section
+//!
+//! The inverse of writing a book: a monolithic document, such as a long
+//! README, is cut at a heading level with [`Document::split_at_level`] and
+//! each section written out as a page of its own.
+
+use super::{open_file, save_file_as, FileResult};
+use crate::ast::{Block, Document};
+use crate::book::shift_headings;
+use crate::toolkit::links::{
+    classify, collect_anchors, heading_slug, visit_link_urls_mut, LinkTarget,
+};
+use crate::traits::RenderConfig;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Split the file at `input` at heading `level`, writing one file per
+/// section into `dir` in the input's format; returns the written paths
+///
+/// Files are named `NN-<slug>.<ext>` in reading order, as book chapters
+/// are, with the slug of the section's heading; blocks before the first
+/// heading take the input's file stem. Each file's headings are shifted so
+/// its top heading is level 1, and links to anchors in other sections point
+/// at the files that now hold them.
+pub fn split_file(
+    input: impl AsRef<Path>,
+    dir: impl AsRef<Path>,
+    level: u8,
+    config: &RenderConfig,
+) -> FileResult<Vec<PathBuf>> {
+    let input = input.as_ref();
+    let dir = dir.as_ref();
+    let opened = open_file(input)?;
+    let format = opened.file_info.format;
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "index".to_string());
+
+    let mut sections = opened.document.split_at_level(level);
+    let has_preamble = !sections
+        .first()
+        .is_some_and(|section| starts_section(section, level));
+    let names: Vec<String> = sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| {
+            let slug = match section.meta.title.as_deref() {
+                Some(title) if i > 0 || !has_preamble => heading_slug(title),
+                _ => stem.clone(),
+            };
+            let slug = if slug.is_empty() { "section" } else { &slug };
+            format!("{:02}-{}.{}", i + 1, slug, format.extension())
+        })
+        .collect();
+
+    // Anchors defined in more than one section stay with the first
+    let mut homes: HashMap<String, usize> = HashMap::new();
+    for (i, section) in sections.iter().enumerate() {
+        for anchor in collect_anchors(section) {
+            homes.entry(anchor).or_insert(i);
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::with_capacity(sections.len());
+    for (i, section) in sections.iter_mut().enumerate() {
+        visit_link_urls_mut(section, &mut |url| {
+            if let LinkTarget::Anchor { id } = classify(url) {
+                match homes.get(&id) {
+                    Some(&home) if home != i => *url = format!("{}#{}", names[home], id),
+                    _ => {}
+                }
+            }
+            Some(())
+        });
+        shift_headings(&mut section.content, 1);
+
+        let path = dir.join(&names[i]);
+        save_file_as(section, &path, format, config)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Whether `doc` opens with a heading of `level` or a higher level
+fn starts_section(doc: &Document, level: u8) -> bool {
+    matches!(
+        doc.content.first(),
+        Some(Block::Heading { level: heading, .. }) if *heading <= level
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("README.md");
+        fs::write(
+            &input,
+            "Badges\n\n## Install\n\nSee [usage](#usage).\n\n## Usage\n\nRun it.\n",
+        )
+        .unwrap();
+
+        let out = dir.path().join("docs");
+        let written = split_file(&input, &out, 2, &RenderConfig::default()).unwrap();
+        let names: Vec<_> = written
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["01-README.md", "02-install.md", "03-usage.md"]);
+
+        let install = fs::read_to_string(out.join("02-install.md")).unwrap();
+        assert!(install.starts_with("---\ntitle: Install\n---\n"));
+        assert!(install.ends_with("\n# Install\n\nSee [usage](03-usage.md#usage)."));
+    }
+}
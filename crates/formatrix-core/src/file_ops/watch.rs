@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Watching files for changes and re-parsing them
+//!
+//! Editors often save in several steps (write a temporary file, rename it,
+//! touch the metadata), so events are debounced and each changed file is
+//! reported once per burst. Files that are removed, or whose extension is not
+//! a supported format, are ignored; that also skips the temporary and backup
+//! files written by [`write_atomic`](super::write_atomic).
+//!
+//! [`watch_paths`] reports every changed path instead, removals included, for
+//! keeping a view of a directory tree up to date. [`watch_file`] follows one
+//! open file, through the renames of an atomic save.
+
+use super::{format_from_extension, open_file_with_config, FileError, FileResult, OpenedDocument};
+use crate::traits::ParseConfig;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a file must be quiet before it is re-parsed
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Handle for a running watch; watching stops when it is dropped
+pub struct FileWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl std::fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher").finish_non_exhaustive()
+    }
+}
+
+/// Watch `paths` and call `callback` with each changed document
+///
+/// Directories are watched recursively. The callback runs on the watcher's
+/// own thread and receives the path along with the result of re-opening it,
+/// so a file that no longer parses is reported rather than dropped.
+pub fn watch<P, F>(paths: impl IntoIterator<Item = P>, callback: F) -> FileResult<FileWatcher>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path, FileResult<OpenedDocument>) + Send + 'static,
+{
+    watch_with_config(paths, &ParseConfig::default(), callback)
+}
+
+/// [`watch`], parsing changed files with `config`
+pub fn watch_with_config<P, F>(
+    paths: impl IntoIterator<Item = P>,
+    config: &ParseConfig,
+    mut callback: F,
+) -> FileResult<FileWatcher>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path, FileResult<OpenedDocument>) + Send + 'static,
+{
+    let config = config.clone();
+    watch_paths(paths, move |changed| {
+        for path in changed {
+            if path.is_file() && format_from_extension(&path).is_some() {
+                let opened = open_file_with_config(&path, &config);
+                callback(&path, opened);
+            }
+        }
+    })
+}
+
+/// Watch `paths` and call `callback` with the paths changed in each burst
+///
+/// Every file and directory that was created, modified, renamed or removed
+/// is reported, once per burst and sorted, whatever its format.
+pub fn watch_paths<P, F>(paths: impl IntoIterator<Item = P>, callback: F) -> FileResult<FileWatcher>
+where
+    P: AsRef<Path>,
+    F: FnMut(Vec<PathBuf>) + Send + 'static,
+{
+    debounced(paths, RecursiveMode::Recursive, callback)
+}
+
+/// Watch the file at `path` and call `callback` each time it changes
+///
+/// The file's directory is watched rather than the file, so the watch
+/// survives a save that writes a new file and renames it over the old one.
+/// Removing the file counts as a change; the file must exist to start.
+pub fn watch_file<F>(path: impl AsRef<Path>, mut callback: F) -> FileResult<FileWatcher>
+where
+    F: FnMut() + Send + 'static,
+{
+    let path = path.as_ref().canonicalize()?;
+    let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+    debounced([dir], RecursiveMode::NonRecursive, move |changed| {
+        if changed.contains(&path) {
+            callback();
+        }
+    })
+}
+
+fn debounced<P, F>(
+    paths: impl IntoIterator<Item = P>,
+    mode: RecursiveMode,
+    mut callback: F,
+) -> FileResult<FileWatcher>
+where
+    P: AsRef<Path>,
+    F: FnMut(Vec<PathBuf>) + Send + 'static,
+{
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| {
+        let Ok(events) = result else {
+            return;
+        };
+        let changed: BTreeSet<_> = events.into_iter().map(|event| event.path).collect();
+        if !changed.is_empty() {
+            callback(changed.into_iter().collect());
+        }
+    })
+    .map_err(notify_error)?;
+
+    for path in paths {
+        debouncer
+            .watcher()
+            .watch(path.as_ref(), mode)
+            .map_err(notify_error)?;
+    }
+    Ok(FileWatcher {
+        _debouncer: debouncer,
+    })
+}
+
+fn notify_error(err: notify_debouncer_mini::notify::Error) -> FileError {
+    match err.kind {
+        notify_debouncer_mini::notify::ErrorKind::Io(io) => FileError::Io(io),
+        _ => FileError::Io(std::io::Error::other(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_watch_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Before\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = watch([dir.path()], move |path, opened| {
+            let _ = tx.send((path.to_path_buf(), opened));
+        })
+        .unwrap();
+
+        fs::write(dir.path().join("ignored.png"), b"\x89PNG").unwrap();
+        fs::write(&path, "# After\n\nChanged.\n").unwrap();
+
+        let (changed, opened) = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(changed.file_name(), path.file_name());
+        let opened = opened.unwrap();
+        assert!(fs::read_to_string(&opened.file_info.path)
+            .unwrap()
+            .contains("After"));
+    }
+
+    #[test]
+    fn test_watch_paths_reports_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"\x89PNG").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = watch_paths([dir.path()], move |changed| {
+            let _ = tx.send(changed);
+        })
+        .unwrap();
+
+        fs::remove_file(&path).unwrap();
+        let changed = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(changed.iter().any(|p| p.file_name() == path.file_name()));
+    }
+
+    #[test]
+    fn test_watch_file_through_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Before\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = watch_file(&path, move || {
+            let _ = tx.send(());
+        })
+        .unwrap();
+
+        fs::write(dir.path().join("other.md"), "# Other\n").unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_err());
+
+        let temp = dir.path().join(".notes.md.tmp");
+        fs::write(&temp, "# After\n").unwrap();
+        fs::rename(&temp, &path).unwrap();
+        rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    #[test]
+    fn test_watch_missing_path() {
+        let result = watch(["/definitely/not/here"], |_, _| {});
+        assert!(matches!(result, Err(FileError::Io(_))));
+    }
+}
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Find and replace over the document tree
+//!
+//! [`Document::find`] matches plain text or a regular expression against the
+//! text of every block, optionally leaving code and math alone, and
+//! [`Document::replace`] rewrites the matches. Matches do not cross element
+//! boundaries: text split by emphasis or a link is searched piece by piece.
+//!
+//! Each match carries its block path and character range in the terms of
+//! [`crate::edit`]. When the document kept its source (`raw_source`), matches
+//! are also located in it, the way the toolkit finds words, so an editor can
+//! highlight them.
+
+use crate::ast::{Block, Document, Inline, Span};
+use crate::edit::{children_mut, inline_len};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FindError {
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+}
+
+/// How to match the pattern
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FindOptions {
+    /// Treat the pattern as a regular expression
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Leave inline code and code blocks alone
+    pub skip_code: bool,
+    /// Leave inline math and math blocks alone
+    pub skip_math: bool,
+}
+
+/// One occurrence of the pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindMatch {
+    /// Index path through nested blocks, starting at `Document::content`
+    pub block_path: Vec<usize>,
+    /// Character range within the block's inline content, as counted by
+    /// [`crate::edit`], or within the text of a code or math block; a match
+    /// in inline math covers the whole element
+    pub range: Range<usize>,
+    pub text: String,
+    /// What the match was replaced with, for matches from
+    /// [`Document::replace`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    /// Location in the source, when it could be found there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+impl Document {
+    /// Every match of `pattern`, in document order
+    pub fn find(&self, pattern: &str, options: &FindOptions) -> Result<Vec<FindMatch>, FindError> {
+        let mut content = self.content.clone();
+        let mut walk = Walk::new(pattern, options, None, self.raw_source.as_deref())?;
+        walk.blocks(&mut content, &mut Vec::new());
+        Ok(walk.matches)
+    }
+
+    /// Replace every match of `pattern` with `replacement`
+    ///
+    /// In regex mode, `$1` or `$name` in `replacement` expand to capture
+    /// groups. Returns the matches as they were before the edit.
+    pub fn replace(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        options: &FindOptions,
+    ) -> Result<Vec<FindMatch>, FindError> {
+        let mut walk = Walk::new(
+            pattern,
+            options,
+            Some(replacement),
+            self.raw_source.as_deref(),
+        )?;
+        walk.blocks(&mut self.content, &mut Vec::new());
+        Ok(walk.matches)
+    }
+}
+
+/// Replace every match of `pattern` in `source`, the text `doc` was parsed
+/// from
+///
+/// Only the matched text changes; the rest of the source keeps its layout.
+/// Matches that cannot be found verbatim in the source, such as text written
+/// with escapes, come back without a span and are left alone.
+pub fn replace_in_source(
+    doc: &Document,
+    source: &str,
+    pattern: &str,
+    replacement: &str,
+    options: &FindOptions,
+) -> Result<(String, Vec<FindMatch>), FindError> {
+    let mut content = doc.content.clone();
+    let mut walk = Walk::new(pattern, options, Some(replacement), Some(source))?;
+    walk.blocks(&mut content, &mut Vec::new());
+
+    let mut output = source.to_string();
+    for found in walk.matches.iter().rev() {
+        if let (Some(span), Some(with)) = (found.span, &found.replacement) {
+            output.replace_range(span.start..span.end, with);
+        }
+    }
+    Ok((output, walk.matches))
+}
+
+/// One pass over the blocks, collecting and optionally replacing matches
+struct Walk<'a> {
+    regex: Regex,
+    options: &'a FindOptions,
+    replacement: Option<&'a str>,
+    source: Option<&'a str>,
+    /// Source offset searching resumes from
+    cursor: usize,
+    /// Line number and start offset of the line containing `scanned`
+    scanned: usize,
+    line: u32,
+    line_start: usize,
+    matches: Vec<FindMatch>,
+}
+
+impl<'a> Walk<'a> {
+    fn new(
+        pattern: &str,
+        options: &'a FindOptions,
+        replacement: Option<&'a str>,
+        source: Option<&'a str>,
+    ) -> Result<Self, FindError> {
+        let mut pattern = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        if options.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| FindError::InvalidPattern(e.to_string()))?;
+        Ok(Self {
+            regex,
+            options,
+            replacement,
+            source,
+            cursor: 0,
+            scanned: 0,
+            line: 1,
+            line_start: 0,
+            matches: Vec::new(),
+        })
+    }
+
+    fn blocks(&mut self, blocks: &mut [Block], path: &mut Vec<usize>) {
+        for (i, block) in blocks.iter_mut().enumerate() {
+            path.push(i);
+            self.block(block, path);
+            path.pop();
+        }
+    }
+
+    fn block(&mut self, block: &mut Block, path: &mut Vec<usize>) {
+        let span = block.span();
+        if let Some(span) = span {
+            self.cursor = self.cursor.max(span.start);
+        }
+        let limit = span.map(|s| s.end);
+        match block {
+            Block::CodeBlock { content, .. } => {
+                self.text(content, 0, false, !self.options.skip_code, path, limit)
+            }
+            Block::MathBlock { content, .. } => {
+                self.text(content, 0, false, !self.options.skip_math, path, limit)
+            }
+            _ => {
+                let mut offset = 0;
+                for group in block.inline_groups_mut() {
+                    offset = self.inlines(group, offset, path, limit);
+                }
+            }
+        }
+
+        for (i, child) in block.child_blocks_mut().into_iter().enumerate() {
+            path.push(i);
+            self.block(child, path);
+            path.pop();
+        }
+    }
+
+    /// Search `inlines`, whose first character is at `offset` in the block;
+    /// returns the offset after them
+    fn inlines(
+        &mut self,
+        inlines: &mut Vec<Inline>,
+        mut offset: usize,
+        path: &[usize],
+        limit: Option<usize>,
+    ) -> usize {
+        join_text(inlines);
+        for inline in inlines {
+            let len = inline_len(inline);
+            match inline {
+                Inline::Text { content } => self.text(content, offset, false, true, path, limit),
+                Inline::Code { content, .. } => {
+                    self.text(content, offset, false, !self.options.skip_code, path, limit)
+                }
+                Inline::Math { content, .. } => {
+                    self.text(content, offset, true, !self.options.skip_math, path, limit)
+                }
+                other => {
+                    if let Some(children) = children_mut(other) {
+                        self.inlines(children, offset, path, limit);
+                    }
+                }
+            }
+            offset += len;
+        }
+        offset
+    }
+
+    /// Search one piece of text at character `offset` in the block
+    ///
+    /// Skipped text is still located in the source, so that a match after it
+    /// is not found inside it.
+    fn text(
+        &mut self,
+        content: &mut String,
+        offset: usize,
+        atom: bool,
+        search: bool,
+        path: &[usize],
+        limit: Option<usize>,
+    ) {
+        let start = self.locate(content, limit);
+        if !search {
+            return;
+        }
+
+        let regex = self.regex.clone();
+        let mut replaced = String::new();
+        let mut last = 0;
+        for caps in regex.captures_iter(content) {
+            let found = caps.get(0).expect("group 0 is the whole match");
+            if found.is_empty() {
+                continue;
+            }
+            let text = found.as_str();
+            let range = if atom {
+                offset..offset + 1
+            } else {
+                let first = offset + content[..found.start()].chars().count();
+                first..first + text.chars().count()
+            };
+            let span = match start {
+                Some(start) => Some(self.span(start + found.start(), text.len())),
+                None => self.locate(text, limit).map(|at| self.span(at, text.len())),
+            };
+
+            let replacement = self.replacement.map(|with| {
+                replaced.push_str(&content[last..found.start()]);
+                let from = replaced.len();
+                if self.options.regex {
+                    caps.expand(with, &mut replaced);
+                } else {
+                    replaced.push_str(with);
+                }
+                last = found.end();
+                replaced[from..].to_string()
+            });
+            self.matches.push(FindMatch {
+                block_path: path.to_vec(),
+                range,
+                text: text.to_string(),
+                replacement,
+                span,
+            });
+        }
+        if self.replacement.is_some() && last > 0 {
+            replaced.push_str(&content[last..]);
+            *content = replaced;
+        }
+    }
+
+    /// Find `text` verbatim in the source at or after the cursor, and move
+    /// the cursor past it
+    fn locate(&mut self, text: &str, limit: Option<usize>) -> Option<usize> {
+        let source = self.source?;
+        if text.is_empty() {
+            return None;
+        }
+        let end = limit.unwrap_or(source.len()).min(source.len());
+        let start = self.cursor + source.get(self.cursor..end)?.find(text)?;
+        self.cursor = start + text.len();
+        Some(start)
+    }
+
+    /// The span of `len` bytes at source offset `start`, which is never
+    /// before the last one asked for
+    fn span(&mut self, start: usize, len: usize) -> Span {
+        let source = self.source.unwrap_or_default();
+        for (i, _) in source[self.scanned..start].match_indices('\n') {
+            self.line += 1;
+            self.line_start = self.scanned + i + 1;
+        }
+        self.scanned = start;
+        Span {
+            start,
+            end: start + len,
+            line: self.line,
+            column: source[self.line_start..start].chars().count() as u32 + 1,
+        }
+    }
+}
+
+/// Join adjacent text elements, which parsers sometimes leave split
+fn join_text(inlines: &mut Vec<Inline>) {
+    let mut i = 1;
+    while i < inlines.len() {
+        if let [Inline::Text { content: prev }, Inline::Text { content }] = &mut inlines[i - 1..=i]
+        {
+            prev.push_str(content);
+            inlines.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser, RenderConfig, Renderer};
+
+    fn parse(input: &str) -> Document {
+        let config = ParseConfig {
+            preserve_raw_source: true,
+            ..Default::default()
+        };
+        MarkdownHandler::new().parse(input, &config).unwrap()
+    }
+
+    #[test]
+    fn test_find() {
+        let source = "# Cats\n\nThe cat sat.\n\n- a *category* of `cat`\n";
+        let doc = parse(source);
+
+        let matches = doc.find("cat", &FindOptions::default()).unwrap();
+        let found: Vec<_> = matches
+            .iter()
+            .map(|m| (m.block_path.clone(), m.range.clone(), m.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (vec![0], 0..3, "Cat"),
+                (vec![1], 4..7, "cat"),
+                (vec![2, 0], 2..5, "cat"),
+                (vec![2, 0], 14..17, "cat"),
+            ]
+        );
+        for m in &matches {
+            let span = m.span.unwrap();
+            assert_eq!(source[span.start..span.end].to_lowercase(), "cat");
+        }
+        assert_eq!(matches[3].span.unwrap().line, 5);
+        assert_eq!(matches[3].span.unwrap().column, 20);
+
+        let options = FindOptions {
+            case_sensitive: true,
+            whole_word: true,
+            skip_code: true,
+            ..Default::default()
+        };
+        let matches = doc.find("cat", &options).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].block_path, vec![1]);
+
+        let options = FindOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            doc.find("(", &options),
+            Err(FindError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut doc = parse("Call 555-1234 or 555-9876.\n\n`555-0000`\n");
+        let options = FindOptions {
+            regex: true,
+            skip_code: true,
+            ..Default::default()
+        };
+        let replaced = doc.replace(r"(\d{3})-(\d{4})", "$2-$1", &options).unwrap();
+        assert_eq!(replaced.len(), 2);
+        assert_eq!(replaced[1].replacement.as_deref(), Some("9876-555"));
+        assert_eq!(replaced[1].span.unwrap().start, 17);
+
+        let output = MarkdownHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert_eq!(output, "Call 1234-555 or 9876-555.\n\n`555-0000`");
+    }
+}
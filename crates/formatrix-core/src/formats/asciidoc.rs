@@ -3,10 +3,16 @@
 //! AsciiDoc format handler using asciidoc-parser
 //! FD-S01: SHOULD requirement
 
-use crate::ast::{Block, Document, DocumentMeta, Inline, ListKind, SourceFormat};
+use super::line_nesting;
+use crate::ast::{
+    AdmonitionType, Author, Block, ColumnAlignment, ColumnSpec, DefinitionItem, Document, Inline,
+    ListItem, ListKind, MetaValue, SourceFormat, TableCell, TableRow,
+};
+use crate::frontmatter::{meta_text, FrontMatter};
 use crate::traits::{
     FormatHandler, ParseConfig, Parser as ParserTrait, RenderConfig, Renderer, Result,
 };
+use asciidoc_parser::attributes::Attrlist;
 use asciidoc_parser::{blocks::IsBlock, Document as AdocDocument, Parser as AdocParser};
 
 /// AsciiDoc format handler
@@ -30,28 +36,175 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_timeout(SourceFormat::AsciiDoc)?;
+        config.limits.check_source(input, source_depth)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
         let mut parser = AdocParser::default();
         let adoc_doc = parser.parse(input);
 
-        // Extract title from header
-        let title = adoc_doc.header().title().map(|s| s.to_string());
+        // Extract title, authors and attributes from header
+        let meta = front_matter(adoc_doc.header()).into_meta();
 
         // Convert blocks
         let content = convert_blocks(&adoc_doc);
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::AsciiDoc,
-            meta: DocumentMeta {
-                title,
-                ..Default::default()
-            },
+            meta,
             content,
             raw_source: if config.preserve_raw_source {
                 Some(input.to_string())
             } else {
                 None
             },
-        })
+        }))
+    }
+}
+
+/// How deeply delimited blocks and lists nest in AsciiDoc source, which
+/// asciidoc-parser recurses into once per level
+fn source_depth(input: &str) -> usize {
+    let mut open: Vec<&str> = Vec::new();
+    let mut deepest = 0;
+    for line in input.lines() {
+        let line = line.trim_end();
+        if let Some(at) = open.iter().rposition(|&o| o == line) {
+            open.truncate(at);
+        } else if is_delimiter(line) && !open.last().is_some_and(|&o| is_verbatim(o)) {
+            open.push(line);
+        }
+        deepest = deepest.max(open.len());
+    }
+    let lists = line_nesting(input, |line| match line.split_once(' ') {
+        Some(("-", _)) => 1,
+        Some((marker, _))
+            if marker.bytes().all(|b| b == b'*') || marker.bytes().all(|b| b == b'.') =>
+        {
+            marker.len()
+        }
+        _ => 0,
+    });
+    lists.max(1 + deepest)
+}
+
+/// Whether a line opens or closes a delimited block
+fn is_delimiter(line: &str) -> bool {
+    let mut bytes = line.bytes();
+    match bytes.next() {
+        Some(first) if line.len() >= 4 && b"=*_-.+/".contains(&first) => bytes.all(|b| b == first),
+        _ => line == "--" || line == "|===",
+    }
+}
+
+/// Whether a delimited block holds text that is not parsed, so that
+/// delimiters in it are only text
+fn is_verbatim(delimiter: &str) -> bool {
+    delimiter.starts_with(['-', '.', '+', '/']) && delimiter != "--"
+}
+
+/// Title, author line and attribute entries of the document header
+fn front_matter(header: &asciidoc_parser::document::Header<'_>) -> FrontMatter {
+    use asciidoc_parser::document::InterpretedValue;
+
+    let mut fields = Vec::new();
+    if let Some(title) = header.title() {
+        fields.push(("title".to_string(), MetaValue::String(title.to_string())));
+    }
+    if let Some(line) = header.author_line() {
+        let authors = line
+            .authors()
+            .map(|author| {
+                let author = Author {
+                    name: author.name().to_string(),
+                    email: author.email().map(String::from),
+                };
+                MetaValue::String(author.to_string())
+            })
+            .collect();
+        fields.push(("author".to_string(), MetaValue::List(authors)));
+    }
+    if let Some(line) = header.revision_line() {
+        fields.push((
+            "date".to_string(),
+            MetaValue::String(line.revdate().to_string()),
+        ));
+    }
+
+    for attribute in header.attributes() {
+        let name = attribute.name().data().to_string();
+        let value = match attribute.value() {
+            InterpretedValue::Value(value) => value.clone(),
+            InterpretedValue::Set => String::new(),
+            InterpretedValue::Unset => continue,
+        };
+        let value = match name.as_str() {
+            "authors" => MetaValue::List(
+                value
+                    .split(';')
+                    .map(|a| MetaValue::String(a.trim().to_string()))
+                    .collect(),
+            ),
+            _ => MetaValue::String(value),
+        };
+        let name = match name.as_str() {
+            "revdate" => "date".to_string(),
+            _ => name,
+        };
+        fields.push((name, value));
+    }
+    FrontMatter::from_fields(fields)
+}
+
+/// Document header: title, author line and attribute entries
+fn render_front_matter(output: &mut String, front: &FrontMatter) {
+    let authors = front
+        .authors
+        .iter()
+        .map(Author::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    match &front.title {
+        Some(title) => {
+            output.push_str(&format!("= {}\n", title));
+            if !authors.is_empty() {
+                output.push_str(&authors);
+                output.push('\n');
+            }
+        }
+        // An author line needs a title above it
+        None if !authors.is_empty() => {
+            output.push_str(&format!(":authors: {}\n", authors));
+        }
+        None => {}
+    }
+    if let Some(date) = &front.date {
+        output.push_str(&format!(":revdate: {}\n", date));
+    }
+    if let Some(modified) = &front.modified {
+        output.push_str(&format!(":modified: {}\n", modified));
+    }
+    if let Some(language) = &front.language {
+        output.push_str(&format!(":lang: {}\n", language));
+    }
+    if let Some(direction) = front.direction {
+        output.push_str(&format!(":dir: {}\n", direction.as_str()));
+    }
+    if !front.keywords.is_empty() {
+        output.push_str(&format!(":keywords: {}\n", front.keywords.join(", ")));
+    }
+    for (name, value) in &front.fields {
+        let valid = name.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        match meta_text(value) {
+            Some(text) if valid && text.is_empty() => output.push_str(&format!(":{}:\n", name)),
+            Some(text) if valid => output.push_str(&format!(":{}: {}\n", name, text)),
+            _ => {}
+        }
     }
 }
 
@@ -60,45 +213,72 @@ fn convert_blocks(doc: &AdocDocument<'_>) -> Vec<Block> {
     let mut blocks = Vec::new();
 
     for block in doc.nested_blocks() {
-        if let Some(converted) = convert_block(block) {
-            blocks.push(converted);
-        }
+        convert_block(block, &mut blocks);
     }
 
     blocks
 }
 
 /// Convert a single asciidoc-parser block to our AST
-fn convert_block(block: &asciidoc_parser::blocks::Block<'_>) -> Option<Block> {
+///
+/// Sections and preambles are flattened: a section becomes its heading
+/// followed by its nested blocks.
+fn convert_block(block: &asciidoc_parser::blocks::Block<'_>, out: &mut Vec<Block>) {
     use asciidoc_parser::blocks::Block as AdocBlock;
 
     match block {
-        AdocBlock::Simple(simple) => {
-            let content = simple.content().rendered();
-            Some(Block::Paragraph {
-                content: parse_inline_content(content),
+        // A quoted paragraph, `[quote, Author, Source]` on the line above it
+        AdocBlock::Simple(simple) if matches!(simple.declared_style(), Some("quote" | "verse")) => {
+            out.push(Block::BlockQuote {
+                content: vec![Block::Paragraph {
+                    content: parse_inline_content(simple.content().original().data()),
+                    span: None,
+                }],
+                attribution: quote_attribution(simple.attrlist()),
+                admonition: None,
                 span: None,
-            })
+            });
+        }
+
+        AdocBlock::Simple(simple) => {
+            let content = simple.content().original();
+            let has_header = simple.attrlist().is_some_and(|a| a.has_option("header"));
+            out.push(
+                parse_table(content.data(), has_header).unwrap_or_else(|| Block::Paragraph {
+                    content: parse_inline_content(content.data()),
+                    span: None,
+                }),
+            );
         }
 
         AdocBlock::Section(section) => {
-            // Section level is 1-indexed in AsciiDoc
-            let level = section.level() as u8;
-            let title_text = section.section_title().to_string();
+            // asciidoc-parser levels are 0-based (`==` is level 1)
+            let level = section.level() as u8 + 1;
+            let title = section.section_title_source();
+            let (id, title) = split_anchor(title.data());
 
-            // Create heading
-            Some(Block::Heading {
+            out.push(Block::Heading {
                 level,
-                content: vec![Inline::Text {
-                    content: title_text,
-                }],
-                id: None,
+                content: parse_inline_content(title),
+                id,
+                classes: section
+                    .attrlist()
+                    .map(|attrlist| attrlist.roles().into_iter().map(String::from).collect())
+                    .unwrap_or_default(),
                 span: None,
-            })
+            });
+
+            for child in section.nested_blocks() {
+                convert_block(child, out);
+            }
+        }
+
+        AdocBlock::List(list) => {
+            out.push(convert_list(list));
         }
 
         AdocBlock::RawDelimited(raw) => {
-            let content = raw.content().rendered().to_string();
+            let content = raw.content().original().data().to_string();
             let context = raw.raw_context();
 
             match context.as_ref() {
@@ -106,100 +286,85 @@ fn convert_block(block: &asciidoc_parser::blocks::Block<'_>) -> Option<Block> {
                     // Try to get language from attributes
                     let language = raw
                         .attrlist()
-                        .and_then(|a| a.named_or_positional_attribute("language", 1))
+                        .and_then(|a| a.named_or_positional_attribute("language", 2))
                         .map(|attr| attr.value().to_string());
 
-                    Some(Block::CodeBlock {
+                    out.push(Block::CodeBlock {
                         language,
                         content,
                         line_numbers: false,
                         highlight_lines: Vec::new(),
                         span: None,
-                    })
+                    });
                 }
-                "literal" => Some(Block::Raw {
-                    format: SourceFormat::AsciiDoc,
-                    content,
-                    span: None,
-                }),
-                "pass" | "passthrough" => Some(Block::Raw {
+                "literal" | "pass" | "passthrough" => out.push(Block::Raw {
                     format: SourceFormat::AsciiDoc,
                     content,
                     span: None,
                 }),
                 _ => {
                     // Comment blocks are skipped
-                    None
                 }
             }
         }
 
         AdocBlock::CompoundDelimited(compound) => {
-            let context = compound.raw_context();
+            let mut inner_blocks = Vec::new();
+            for child in compound.nested_blocks() {
+                convert_block(child, &mut inner_blocks);
+            }
 
-            match context.as_ref() {
-                "quote" | "verse" => {
-                    let mut inner_blocks = Vec::new();
-                    for child in compound.nested_blocks() {
-                        if let Some(converted) = convert_block(child) {
-                            inner_blocks.push(converted);
-                        }
-                    }
-                    Some(Block::BlockQuote {
-                        content: inner_blocks,
-                        attribution: None,
-                        admonition: None,
-                        span: None,
-                    })
-                }
-                "sidebar" => {
-                    let mut inner_blocks = Vec::new();
-                    for child in compound.nested_blocks() {
-                        if let Some(converted) = convert_block(child) {
-                            inner_blocks.push(converted);
-                        }
+            let admonition = compound.declared_style().and_then(admonition_type);
+            match compound.raw_context().as_ref() {
+                "quote" | "verse" => out.push(Block::BlockQuote {
+                    content: inner_blocks,
+                    attribution: quote_attribution(compound.attrlist()),
+                    admonition: None,
+                    span: None,
+                }),
+                "example" if compound.has_option("collapsible") => out.push(Block::Details {
+                    summary: compound
+                        .title_source()
+                        .map(|title| parse_inline_content(title.data())),
+                    content: inner_blocks,
+                    open: compound.has_option("open"),
+                    span: None,
+                }),
+                "example" | "open" if admonition.is_some() => out.push(Block::BlockQuote {
+                    content: inner_blocks,
+                    attribution: None,
+                    admonition,
+                    span: None,
+                }),
+                // The delimiter's kind is the first class, roles follow it
+                context @ ("example" | "open" | "sidebar") => {
+                    let mut attributes = std::collections::BTreeMap::new();
+                    if let Some(title) = compound.title() {
+                        attributes.insert("title".to_string(), title.to_string());
                     }
-                    Some(Block::Container {
-                        id: None,
-                        classes: vec!["sidebar".to_string()],
-                        attributes: std::collections::HashMap::new(),
+                    let mut classes = vec![context.to_string()];
+                    classes.extend(compound.roles().into_iter().map(String::from));
+                    out.push(Block::Container {
+                        id: compound.id().map(String::from),
+                        classes,
+                        attributes,
                         content: inner_blocks,
                         span: None,
-                    })
-                }
-                _ => {
-                    // Other compound blocks - convert nested
-                    let mut inner_blocks = Vec::new();
-                    for child in compound.nested_blocks() {
-                        if let Some(converted) = convert_block(child) {
-                            inner_blocks.push(converted);
-                        }
-                    }
-                    if !inner_blocks.is_empty() {
-                        Some(inner_blocks.remove(0))
-                    } else {
-                        None
-                    }
+                    });
                 }
+                // Other compound blocks - keep their content
+                _ => out.extend(inner_blocks),
             }
         }
 
         AdocBlock::Preamble(preamble) => {
             // Preamble contains blocks before first section
-            let mut inner_blocks = Vec::new();
             for child in preamble.nested_blocks() {
-                if let Some(converted) = convert_block(child) {
-                    inner_blocks.push(converted);
-                }
-            }
-            if !inner_blocks.is_empty() {
-                Some(inner_blocks.remove(0))
-            } else {
-                None
+                convert_block(child, out);
             }
         }
 
-        AdocBlock::Break(_) => Some(Block::ThematicBreak { span: None }),
+        AdocBlock::Break(_) => out.push(Block::ThematicBreak { span: None }),
 
         AdocBlock::Media(media) => {
             // Media blocks for images, video, audio
@@ -213,7 +378,7 @@ fn convert_block(block: &asciidoc_parser::blocks::Block<'_>) -> Option<Block> {
                 .map(|attr| attr.value().to_string())
                 .unwrap_or_default();
 
-            Some(Block::Paragraph {
+            out.push(Block::Paragraph {
                 content: vec![Inline::Image {
                     url: target,
                     alt,
@@ -222,26 +387,434 @@ fn convert_block(block: &asciidoc_parser::blocks::Block<'_>) -> Option<Block> {
                     height: None,
                 }],
                 span: None,
-            })
+            });
         }
 
         AdocBlock::DocumentAttribute(_) => {
             // Skip document attributes in block output
-            None
         }
 
         // Non-exhaustive enum, handle any future variants
+        _ => {}
+    }
+}
+
+/// Parse a `|===` table, which asciidoc-parser leaves as a simple block
+///
+/// Cells are read left to right and wrapped into rows using the cell count
+/// of the first line, so the table must not contain blank lines.
+fn parse_table(text: &str, has_header: bool) -> Option<Block> {
+    let inner = text.trim().strip_prefix("|===")?.strip_suffix("|===")?;
+    let columns = inner
+        .lines()
+        .find(|line| !line.trim().is_empty())?
+        .matches('|')
+        .count();
+    if columns == 0 {
+        return None;
+    }
+
+    let cells: Vec<TableCell> = inner
+        .split('|')
+        .skip(1)
+        .map(|cell| TableCell {
+            content: vec![Block::Paragraph {
+                content: parse_inline_content(cell.trim()),
+                span: None,
+            }],
+            colspan: 1,
+            rowspan: 1,
+            alignment: None,
+        })
+        .collect();
+    let mut rows = cells.chunks(columns).map(|cells| TableRow {
+        cells: cells.to_vec(),
+    });
+    let header = if has_header { rows.next() } else { None };
+
+    Some(Block::Table {
+        caption: None,
+        columns: vec![
+            ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            };
+            columns
+        ],
+        header,
+        body: rows.collect(),
+        footer: None,
+        span: None,
+    })
+}
+
+/// Convert a list block, detecting `[x]` / `[ ]` task markers
+///
+/// Nested lists and blocks attached with a `+` continuation arrive as the
+/// item's own blocks, so they stay inside the item.
+fn convert_list(list: &asciidoc_parser::blocks::ListBlock<'_>) -> Block {
+    use asciidoc_parser::blocks::{Block as AdocBlock, ListItemMarker, ListType};
+
+    if list.type_() == ListType::Description {
+        let mut items = Vec::new();
+        for child in list.nested_blocks() {
+            let AdocBlock::ListItem(item) = child else {
+                continue;
+            };
+            let ListItemMarker::DefinedTerm { term, .. } = item.list_item_marker() else {
+                continue;
+            };
+            let mut content = Vec::new();
+            for block in item.nested_blocks() {
+                convert_block(block, &mut content);
+            }
+            items.push(DefinitionItem {
+                term: parse_inline_content(term.original().data()),
+                // A term followed directly by the next has no definition
+                definitions: if content.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![content]
+                },
+            });
+        }
+        return Block::DefinitionList { items, span: None };
+    }
+
+    let mut items = Vec::new();
+    for child in list.nested_blocks() {
+        let AdocBlock::ListItem(item) = child else {
+            continue;
+        };
+        let mut content = Vec::new();
+        for block in item.nested_blocks() {
+            convert_block(block, &mut content);
+        }
+        // `{empty}` stands in for the text of an item that starts with
+        // another block
+        if matches!(
+            content.first(),
+            Some(Block::Paragraph { content: text, .. })
+                if matches!(text.as_slice(), [Inline::Text { content }] if content == "{empty}")
+        ) {
+            content.remove(0);
+        }
+        let checked = take_task_marker(&mut content);
+        items.push(ListItem {
+            content,
+            checked,
+            marker: None,
+        });
+    }
+
+    let kind = if items.iter().any(|i| i.checked.is_some()) {
+        ListKind::Task
+    } else if list.type_() == ListType::Ordered {
+        ListKind::Ordered
+    } else {
+        ListKind::Bullet
+    };
+
+    Block::List {
+        kind,
+        items,
+        start: None,
+        span: None,
+    }
+}
+
+/// The attribution of a `[quote, Author, Source]` block: the author, then
+/// the source after a comma
+fn quote_attribution(attrlist: Option<&Attrlist<'_>>) -> Option<Vec<Inline>> {
+    let attrlist = attrlist?;
+    let parts: Vec<&str> = (2..=3)
+        .filter_map(|n| attrlist.nth_attribute(n))
+        .map(|attr| attr.value())
+        .collect();
+    (!parts.is_empty()).then(|| parse_inline_content(&parts.join(", ")))
+}
+
+/// Strip a leading checkbox from a list item's first paragraph
+fn take_task_marker(content: &mut [Block]) -> Option<bool> {
+    let Some(Block::Paragraph { content, .. }) = content.first_mut() else {
+        return None;
+    };
+    let Some(Inline::Text { content: text }) = content.first_mut() else {
+        return None;
+    };
+    let checked = if text.starts_with("[x] ") || text.starts_with("[*] ") {
+        true
+    } else if text.starts_with("[ ] ") {
+        false
+    } else {
+        return None;
+    };
+    text.drain(..4);
+    Some(checked)
+}
+
+/// Admonition named by a block style such as `NOTE`
+fn admonition_type(style: &str) -> Option<AdmonitionType> {
+    match style {
+        "NOTE" => Some(AdmonitionType::Note),
+        "TIP" => Some(AdmonitionType::Tip),
+        "IMPORTANT" => Some(AdmonitionType::Important),
+        "WARNING" => Some(AdmonitionType::Warning),
+        "CAUTION" => Some(AdmonitionType::Caution),
         _ => None,
     }
 }
 
-/// Parse inline content from text
+/// Split a leading `[[id]]` anchor off a section title
+fn split_anchor(title: &str) -> (Option<String>, &str) {
+    if let Some(rest) = title.strip_prefix("[[") {
+        if let Some((id, after)) = rest.split_once("]]") {
+            return (Some(id.to_string()), after.trim_start());
+        }
+    }
+    (None, title)
+}
+
+/// Parse AsciiDoc inline markup
+///
+/// Handles the subset the renderer produces: constrained `*strong*` and
+/// `_emphasis_`, `` `code` ``, `link:`/URL macros, `image:` macros, `stem:[]`,
+/// `kbd:[]`, `btn:[]` and `menu:` macros, `[line-through]#...#`, ` +`
+/// hard breaks and `{nbsp}`.
 fn parse_inline_content(text: &str) -> Vec<Inline> {
-    // For now, just return as text
-    // A full implementation would parse AsciiDoc inline markup
-    vec![Inline::Text {
-        content: text.to_string(),
-    }]
+    let mut inlines = Vec::new();
+    let mut buf = String::new();
+    let mut rest = text;
+
+    fn flush(buf: &mut String, inlines: &mut Vec<Inline>) {
+        if !buf.is_empty() {
+            inlines.push(Inline::Text {
+                content: std::mem::take(buf),
+            });
+        }
+    }
+
+    while let Some(c) = rest.chars().next() {
+        let prev_is_word = buf.chars().last().is_some_and(|p| p.is_alphanumeric());
+
+        if let Some(after) = rest.strip_prefix(" +\n") {
+            flush(&mut buf, &mut inlines);
+            inlines.push(Inline::LineBreak);
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('\n') {
+            flush(&mut buf, &mut inlines);
+            inlines.push(Inline::SoftBreak);
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("{nbsp}") {
+            flush(&mut buf, &mut inlines);
+            inlines.push(Inline::NonBreakingSpace);
+            rest = after;
+            continue;
+        }
+
+        if !prev_is_word {
+            if let Some((inline, after)) = parse_macro(rest) {
+                flush(&mut buf, &mut inlines);
+                inlines.push(inline);
+                rest = after;
+                continue;
+            }
+
+            if matches!(c, '*' | '_' | '`') {
+                if let Some((inner, after)) = constrained_span(&rest[1..], c) {
+                    flush(&mut buf, &mut inlines);
+                    inlines.push(match c {
+                        '*' => Inline::Strong {
+                            content: parse_inline_content(inner),
+                        },
+                        '_' => Inline::Emphasis {
+                            content: parse_inline_content(inner),
+                        },
+                        _ => Inline::Code {
+                            content: inner.to_string(),
+                            language: None,
+                        },
+                    });
+                    rest = after;
+                    continue;
+                }
+            }
+        }
+
+        buf.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    flush(&mut buf, &mut inlines);
+    inlines
+}
+
+/// Find the end of a constrained span opened by `mark`
+///
+/// Returns the enclosed text and the remainder after the closing mark.
+fn constrained_span(text: &str, mark: char) -> Option<(&str, &str)> {
+    if text.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut search = 0;
+    while let Some(pos) = text[search..].find(mark) {
+        let end = search + pos;
+        let before = text[..end].chars().last();
+        let after = text[end + 1..].chars().next();
+        if end > 0
+            && !before.is_some_and(char::is_whitespace)
+            && !after.is_some_and(|a| a.is_alphanumeric())
+        {
+            return Some((&text[..end], &text[end + 1..]));
+        }
+        search = end + 1;
+    }
+    None
+}
+
+/// Parse a bracketed inline macro at the start of `text`
+fn parse_macro(text: &str) -> Option<(Inline, &str)> {
+    if let Some(rest) = text.strip_prefix("[line-through]#") {
+        let end = rest.find('#')?;
+        return Some((
+            Inline::Strikethrough {
+                content: parse_inline_content(&rest[..end]),
+            },
+            &rest[end + 1..],
+        ));
+    }
+
+    for (prefix, ui) in [("kbd:[", "kbd"), ("btn:[", "btn")] {
+        if let Some(rest) = text.strip_prefix(prefix) {
+            let end = closing_bracket(rest)?;
+            let content = rest[..end].replace("\\]", "]");
+            let inline = match ui {
+                "kbd" => Inline::Keyboard { content },
+                _ => Inline::Button { content },
+            };
+            return Some((inline, &rest[end + 1..]));
+        }
+    }
+
+    if let Some(rest) = text.strip_prefix("menu:") {
+        let (menu, path, after) = split_macro(rest)?;
+        let items = std::iter::once(menu)
+            .chain(
+                path.split('>')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty()),
+            )
+            .map(String::from)
+            .collect();
+        return Some((Inline::Menu { items }, after));
+    }
+
+    if let Some(rest) = text.strip_prefix("stem:[") {
+        let end = rest.find(']')?;
+        return Some((
+            Inline::Math {
+                content: rest[..end].to_string(),
+                notation: crate::ast::MathNotation::LaTeX,
+            },
+            &rest[end + 1..],
+        ));
+    }
+
+    if let Some(rest) = text
+        .strip_prefix("image::")
+        .or_else(|| text.strip_prefix("image:"))
+    {
+        let (target, attrs, after) = split_macro(rest)?;
+        let (alt, title) = split_title(attrs);
+        return Some((
+            Inline::Image {
+                url: target.to_string(),
+                alt: alt.to_string(),
+                title,
+                width: None,
+                height: None,
+            },
+            after,
+        ));
+    }
+
+    let (target, text, after, link_type) = if let Some(rest) = text.strip_prefix("link:") {
+        let (target, attrs, after) = split_macro(rest)?;
+        (target, Some(attrs), after, crate::ast::LinkType::Inline)
+    } else if text.starts_with("https://") || text.starts_with("http://") {
+        let end = text
+            .find(|c: char| c.is_whitespace() || c == '[')
+            .unwrap_or(text.len());
+        if text[end..].starts_with('[') {
+            let (target, attrs, after) = split_macro(text)?;
+            (target, Some(attrs), after, crate::ast::LinkType::Inline)
+        } else {
+            // Keep trailing sentence punctuation out of bare URLs
+            let url = text[..end].trim_end_matches(['.', ',', ';', ':', ')', '!', '?']);
+            (
+                url,
+                None,
+                &text[url.len()..],
+                crate::ast::LinkType::AutoLink,
+            )
+        }
+    } else {
+        return None;
+    };
+
+    let (label, title) = text.map(split_title).unwrap_or(("", None));
+    let content = if label.is_empty() || label == target {
+        vec![Inline::Text {
+            content: target.to_string(),
+        }]
+    } else {
+        parse_inline_content(label)
+    };
+    Some((
+        Inline::Link {
+            url: target.to_string(),
+            title,
+            content,
+            link_type,
+        },
+        after,
+    ))
+}
+
+/// Where the `]` closing a macro's attributes is in `text`, passing over
+/// any escaped as `\]`
+fn closing_bracket(text: &str) -> Option<usize> {
+    let mut search = 0;
+    loop {
+        let end = search + text[search..].find(']')?;
+        if !text[..end].ends_with('\\') {
+            return Some(end);
+        }
+        search = end + 1;
+    }
+}
+
+/// Split `target[attrs]rest` into its parts
+fn split_macro(text: &str) -> Option<(&str, &str, &str)> {
+    let open = text.find('[')?;
+    let target = &text[..open];
+    if target.is_empty() || target.contains(char::is_whitespace) {
+        return None;
+    }
+    let close = open + text[open..].find(']')?;
+    Some((target, &text[open + 1..close], &text[close + 1..]))
+}
+
+/// Split a trailing `, title="..."` attribute off macro text
+fn split_title(attrs: &str) -> (&str, Option<String>) {
+    match attrs.split_once(", title=\"") {
+        Some((label, title)) => (label, Some(title.trim_end_matches('"').to_string())),
+        None => (attrs, None),
+    }
 }
 
 impl Renderer for AsciidocHandler {
@@ -249,19 +822,27 @@ fn format(&self) -> SourceFormat {
         SourceFormat::AsciiDoc
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::AsciiDoc);
         let mut output = String::new();
 
-        // Render title if present
-        if let Some(ref title) = doc.meta.title {
-            output.push_str("= ");
-            output.push_str(title);
-            output.push_str("\n\n");
+        // Render the header if there is anything to put in it
+        let front = FrontMatter::from_meta(&doc.meta);
+        if !front.is_empty() {
+            render_front_matter(&mut output, &front);
+            output.push('\n');
         }
 
+        let mut prev_was_list = false;
         for block in &doc.content {
+            let is_list = matches!(block, Block::List { .. } | Block::DefinitionList { .. });
+            if prev_was_list && is_list {
+                // Without a separator the second list nests into the first
+                output.push_str("//\n\n");
+            }
             render_block(&mut output, block);
             output.push_str("\n\n");
+            prev_was_list = is_list;
         }
 
         // Trim trailing newlines
@@ -269,7 +850,7 @@ fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
             output.pop();
         }
 
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
@@ -282,8 +863,16 @@ fn render_block(output: &mut String, block: &Block) {
         }
 
         Block::Heading {
-            level, content, id, ..
+            level,
+            content,
+            id,
+            classes,
+            ..
         } => {
+            if !classes.is_empty() {
+                output.push_str(&format!("[.{}]\n", classes.join(".")));
+            }
+
             // AsciiDoc uses = for headings (= for level 1, == for level 2, etc.)
             output.push_str(&"=".repeat(*level as usize));
             output.push(' ');
@@ -312,6 +901,25 @@ fn render_block(output: &mut String, block: &Block) {
             output.push_str("----");
         }
 
+        Block::BlockQuote {
+            content,
+            admonition: Some(admonition),
+            ..
+        } => {
+            let style = match admonition {
+                AdmonitionType::Note | AdmonitionType::Custom => "NOTE",
+                AdmonitionType::Tip => "TIP",
+                AdmonitionType::Important => "IMPORTANT",
+                AdmonitionType::Warning | AdmonitionType::Danger => "WARNING",
+                AdmonitionType::Caution => "CAUTION",
+            };
+            let delimiter = "=".repeat(4 + nesting(content, "example"));
+            output.push_str(&format!("[{}]\n{}\n", style, delimiter));
+            render_blocks(output, content);
+            output.push('\n');
+            output.push_str(&delimiter);
+        }
+
         Block::BlockQuote {
             content,
             attribution,
@@ -324,35 +932,18 @@ fn render_block(output: &mut String, block: &Block) {
                     render_inline(output, inline);
                 }
             }
-            output.push_str("]\n____\n");
-            for inner in content {
-                render_block(output, inner);
-                output.push('\n');
-            }
-            output.push_str("____");
+            let delimiter = "_".repeat(4 + nesting(content, "quote"));
+            output.push_str(&format!("]\n{}\n", delimiter));
+            render_blocks(output, content);
+            output.push('\n');
+            output.push_str(&delimiter);
         }
 
         Block::List {
             kind, items, start, ..
-        } => {
-            for (i, item) in items.iter().enumerate() {
-                match kind {
-                    ListKind::Bullet => output.push_str("* "),
-                    ListKind::Ordered => {
-                        let num = start.unwrap_or(1) + i as u32;
-                        output.push_str(&format!("{}. ", num));
-                    }
-                    ListKind::Task => {
-                        let checked = item.checked.unwrap_or(false);
-                        output.push_str(if checked { "* [x] " } else { "* [ ] " });
-                    }
-                }
-                for block in &item.content {
-                    render_block(output, block);
-                }
-                output.push('\n');
-            }
-        }
+        } => render_list(output, *kind, items, *start, 1),
+
+        Block::DefinitionList { items, .. } => render_definition_list(output, items, 1),
 
         Block::ThematicBreak { .. } => {
             output.push_str("'''");
@@ -367,24 +958,70 @@ fn render_block(output: &mut String, block: &Block) {
             output.push_str("++++");
         }
 
+        Block::Comment { content, .. } => {
+            output.push_str("////\n");
+            output.push_str(content);
+            output.push_str("\n////");
+        }
+
         Block::Container {
-            classes, content, ..
+            id,
+            classes,
+            attributes,
+            content,
+            ..
         } => {
-            // Render as sidebar if it has sidebar class
-            if classes.contains(&"sidebar".to_string()) {
-                output.push_str("****\n");
-                for inner in content {
-                    render_block(output, inner);
-                    output.push('\n');
-                }
-                output.push_str("****");
-            } else {
+            let delimiter = match classes.first().map(String::as_str) {
+                Some("example") => "=".repeat(4 + nesting(content, "example")),
+                Some("sidebar") => "*".repeat(4 + nesting(content, "sidebar")),
+                Some("open") => "--".to_string(),
                 // Generic container - render contents
-                for inner in content {
-                    render_block(output, inner);
-                    output.push('\n');
+                _ => return render_blocks(output, content),
+            };
+            if let Some(title) = attributes.get("title") {
+                output.push_str(&format!(".{}\n", title));
+            }
+            if id.is_some() || classes.len() > 1 {
+                output.push('[');
+                if let Some(id) = id {
+                    output.push_str(&format!("#{}", id));
+                }
+                for role in &classes[1..] {
+                    output.push_str(&format!(".{}", role));
+                }
+                output.push_str("]\n");
+            }
+            output.push_str(&delimiter);
+            output.push('\n');
+            render_blocks(output, content);
+            output.push('\n');
+            output.push_str(&delimiter);
+        }
+
+        Block::Details {
+            summary,
+            content,
+            open,
+            ..
+        } => {
+            if let Some(summary) = summary {
+                output.push('.');
+                for inline in summary {
+                    render_inline(output, inline);
                 }
+                output.push('\n');
             }
+            output.push_str(if *open {
+                "[%collapsible%open]\n"
+            } else {
+                "[%collapsible]\n"
+            });
+            let delimiter = "=".repeat(4 + nesting(content, "example"));
+            output.push_str(&delimiter);
+            output.push('\n');
+            render_blocks(output, content);
+            output.push('\n');
+            output.push_str(&delimiter);
         }
 
         Block::Table {
@@ -393,26 +1030,21 @@ fn render_block(output: &mut String, block: &Block) {
             caption,
             ..
         } => {
+            // One row per line with no blank lines, so it parses back as one block
+            if header.is_some() {
+                output.push_str("[%header]\n");
+            }
             output.push_str("|===\n");
 
-            if let Some(h) = header {
-                for cell in &h.cells {
-                    output.push_str("| ");
-                    for block in &cell.content {
-                        render_block(output, block);
+            for row in header.iter().chain(body) {
+                for (i, cell) in row.cells.iter().enumerate() {
+                    if i > 0 {
+                        output.push(' ');
                     }
-                    output.push(' ');
-                }
-                output.push_str("\n\n");
-            }
-
-            for row in body {
-                for cell in &row.cells {
                     output.push_str("| ");
                     for block in &cell.content {
                         render_block(output, block);
                     }
-                    output.push('\n');
                 }
                 output.push('\n');
             }
@@ -440,6 +1072,155 @@ fn render_block(output: &mut String, block: &Block) {
     }
 }
 
+/// How deeply containers of `kind` nest within `blocks`; each level needs
+/// a longer delimiter than the one around it
+fn nesting(blocks: &[Block], kind: &str) -> usize {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Container {
+                classes, content, ..
+            } => {
+                let own = usize::from(classes.first().is_some_and(|c| c == kind));
+                own + nesting(content, kind)
+            }
+            Block::Details { content, .. } => {
+                usize::from(kind == "example") + nesting(content, kind)
+            }
+            // Admonitions are example blocks
+            Block::BlockQuote {
+                content,
+                admonition,
+                ..
+            } => {
+                let own = if admonition.is_some() {
+                    "example"
+                } else {
+                    "quote"
+                };
+                usize::from(kind == own) + nesting(content, kind)
+            }
+            other => other
+                .child_blocks()
+                .into_iter()
+                .map(|child| nesting(std::slice::from_ref(child), kind))
+                .max()
+                .unwrap_or(0),
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Render a sequence of blocks separated by blank lines
+fn render_blocks(output: &mut String, blocks: &[Block]) {
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            output.push_str("\n\n");
+        }
+        render_block(output, block);
+    }
+}
+
+/// Whether a link can be written as a bare URL that AsciiDoc autolinks
+fn is_bare_url(url: &str, content: &[Inline]) -> bool {
+    (url.starts_with("https://") || url.starts_with("http://"))
+        && matches!(content, [Inline::Text { content }] if content == url)
+}
+
+/// Render a list; nesting is expressed by repeating the marker
+fn render_list(
+    output: &mut String,
+    kind: ListKind,
+    items: &[ListItem],
+    start: Option<u32>,
+    depth: usize,
+) {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        match kind {
+            ListKind::Bullet => output.push_str(&"*".repeat(depth)),
+            ListKind::Ordered if depth == 1 => {
+                let num = start.unwrap_or(1) + i as u32;
+                output.push_str(&format!("{}.", num));
+            }
+            ListKind::Ordered => output.push_str(&".".repeat(depth)),
+            ListKind::Task => {
+                let checked = item.checked.unwrap_or(false);
+                output.push_str(&"*".repeat(depth));
+                output.push_str(if checked { " [x]" } else { " [ ]" });
+            }
+        }
+        output.push(' ');
+        if !matches!(item.content.first(), Some(Block::Paragraph { .. })) {
+            // An item needs text on its marker's line
+            output.push_str("{empty}");
+        }
+        render_item_content(output, &item.content, depth);
+    }
+}
+
+/// Render a description list; nesting is expressed by the delimiter,
+/// `::`, `:::`, `::::` and then `;;`
+fn render_definition_list(output: &mut String, items: &[DefinitionItem], depth: usize) {
+    let delimiter = match depth {
+        1..=3 => ":".repeat(depth + 1),
+        _ => ";;".to_string(),
+    };
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        for inline in &item.term {
+            render_inline(output, inline);
+        }
+        output.push_str(&delimiter);
+
+        // Further definitions of the term continue the first
+        let blocks: Vec<Block> = item.definitions.iter().flatten().cloned().collect();
+        if matches!(blocks.first(), Some(Block::Paragraph { .. })) {
+            output.push(' ');
+        }
+        render_item_content(output, &blocks, depth);
+    }
+}
+
+/// Render the blocks of a list item after its marker: a leading paragraph
+/// on the marker's line, nested lists one level deeper, and other blocks
+/// attached with a list continuation
+fn render_item_content(output: &mut String, blocks: &[Block], depth: usize) {
+    let mut after_list = false;
+    for (j, block) in blocks.iter().enumerate() {
+        match block {
+            Block::List {
+                kind, items, start, ..
+            } => {
+                output.push('\n');
+                render_list(output, *kind, items, *start, depth + 1);
+                after_list = true;
+            }
+            Block::DefinitionList { items, .. } => {
+                output.push('\n');
+                render_definition_list(output, items, depth + 1);
+                after_list = true;
+            }
+            _ => {
+                if after_list {
+                    // A blank line first attaches the block to this item
+                    // rather than to the last item of the nested list
+                    output.push_str("\n\n+\n");
+                } else if j > 0 || !matches!(block, Block::Paragraph { .. }) {
+                    // List continuation attaches the block to the item
+                    output.push_str("\n+\n");
+                }
+                render_block(output, block);
+                after_list = false;
+            }
+        }
+    }
+}
+
 fn render_inline(output: &mut String, inline: &Inline) {
     match inline {
         Inline::Text { content } => output.push_str(content),
@@ -474,6 +1255,13 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('`');
         }
 
+        Inline::Link {
+            url,
+            content,
+            title: None,
+            ..
+        } if is_bare_url(url, content) => output.push_str(url),
+
         Inline::Link {
             url,
             content,
@@ -493,7 +1281,7 @@ fn render_inline(output: &mut String, inline: &Inline) {
         Inline::Image {
             url, alt, title, ..
         } => {
-            output.push_str(&format!("image::{}[{}", url, alt));
+            output.push_str(&format!("image:{}[{}", url, alt));
             if let Some(ref t) = title {
                 output.push_str(&format!(", title=\"{}\"", t));
             }
@@ -506,6 +1294,20 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push(']');
         }
 
+        Inline::Keyboard { content } => {
+            output.push_str(&format!("kbd:[{}]", content.replace(']', "\\]")));
+        }
+
+        Inline::Button { content } => {
+            output.push_str(&format!("btn:[{}]", content.replace(']', "\\]")));
+        }
+
+        Inline::Menu { items } => {
+            if let Some((menu, path)) = items.split_first() {
+                output.push_str(&format!("menu:{}[{}]", menu, path.join(" > ")));
+            }
+        }
+
         Inline::LineBreak => {
             output.push_str(" +\n");
         }
@@ -514,6 +1316,10 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push(' ');
         }
 
+        Inline::NonBreakingSpace => {
+            output.push_str("{nbsp}");
+        }
+
         _ => {}
     }
 }
@@ -531,6 +1337,8 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "link"
                 | "image"
                 | "list"
+                | "task_list"
+                | "blockquote"
                 | "table"
                 | "math"
                 | "admonition"
@@ -538,6 +1346,14 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "cross_reference"
                 | "include"
                 | "macro"
+                | "container"
+                | "definition_list"
+                | "details"
+                | "kbd"
+                | "button"
+                | "menu"
+                | "emoji"
+                | "nested_quote"
         )
     }
 
@@ -552,6 +1368,8 @@ fn supported_features(&self) -> &[&str] {
             "link",
             "image",
             "list",
+            "task_list",
+            "blockquote",
             "table",
             "math",
             "admonition",
@@ -559,13 +1377,27 @@ fn supported_features(&self) -> &[&str] {
             "cross_reference",
             "include",
             "macro",
+            "container",
+            "definition_list",
+            "details",
+            "kbd",
+            "button",
+            "menu",
+            "emoji",
+            "nested_quote",
         ]
     }
+
+    fn normalization_exceptions(&self) -> &[&str] {
+        // Footnotes are not rendered, so anything anchored on them shifts
+        &["footnote"]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::DocumentMeta;
 
     #[test]
     fn test_parse_simple() {
@@ -596,6 +1428,7 @@ fn test_render_heading() {
                     content: "Section Title".to_string(),
                 }],
                 id: None,
+                classes: Vec::new(),
                 span: None,
             }],
             raw_source: None,
@@ -625,4 +1458,271 @@ fn test_render_code_block() {
         assert!(output.contains("[source,rust]"));
         assert!(output.contains("fn main()"));
     }
+
+    #[test]
+    fn test_front_matter() {
+        let handler = AsciidocHandler::new();
+        let doc = handler
+            .parse(
+                "= Notes\nAda Lovelace <ada@example.com>; Grace Hopper\n:revdate: 2026-01-02\n\
+                 :lang: en\n:keywords: notes, math\n:toc:\n\nBody text.\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(doc.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(
+            doc.meta.authors,
+            vec![
+                Author::parse("Ada Lovelace <ada@example.com>"),
+                Author::parse("Grace Hopper")
+            ]
+        );
+        assert_eq!(doc.meta.date.as_deref(), Some("2026-01-02"));
+        assert_eq!(doc.meta.language.as_deref(), Some("en"));
+        assert_eq!(doc.meta.keywords, vec!["notes", "math"]);
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output,
+            "= Notes\nAda Lovelace <ada@example.com>; Grace Hopper\n:revdate: 2026-01-02\n\
+             :lang: en\n:keywords: notes, math\n:toc:\n\nBody text."
+        );
+    }
+
+    #[test]
+    fn test_nested_lists() {
+        let handler = AsciidocHandler::new();
+        let source =
+            "* one\n** nested\n+\nmore\n\n+\nback in one\n* two\n.. {empty}\n+\n----\ncode\n----";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+
+        let Block::List { items, .. } = &doc.content[0] else {
+            panic!("expected a list, got {:?}", doc.content[0]);
+        };
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[0].content[1], Block::List { .. }));
+        assert!(matches!(items[0].content[2], Block::Paragraph { .. }));
+        // A list of another kind nests into the last item
+        let Block::List { kind, items, .. } = &items[1].content[1] else {
+            panic!("expected a nested list, got {:?}", items[1].content);
+        };
+        assert_eq!(*kind, ListKind::Ordered);
+        assert!(matches!(items[0].content[..], [Block::CodeBlock { .. }]));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_description_list() {
+        let handler = AsciidocHandler::new();
+        let source = "CPU:: processor\nRAM::\n+\n----\ncode\n----\nDisk::: nested term";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+
+        let Block::DefinitionList { items, .. } = &doc.content[0] else {
+            panic!("expected a description list, got {:?}", doc.content[0]);
+        };
+        assert_eq!(items.len(), 2);
+        assert!(matches!(&items[0].term[..], [Inline::Text { content }] if content == "CPU"));
+        assert!(matches!(
+            items[1].definitions[0][..],
+            [Block::CodeBlock { .. }, Block::DefinitionList { .. }]
+        ));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_description_list_in_other_formats() {
+        let doc = AsciidocHandler::new()
+            .parse(
+                "CPU:: processor\nRAM::\n+\n----\ncode\n----",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let render = |format| {
+            crate::formats::handler_for(format)
+                .render(&doc, &RenderConfig::default())
+                .unwrap()
+        };
+        assert_eq!(
+            render(SourceFormat::Markdown),
+            "CPU\n\n: processor\n\nRAM\n\n: ```\n  code\n  ```"
+        );
+        assert_eq!(
+            render(SourceFormat::OrgMode),
+            "- CPU :: processor\n- RAM :: #+BEGIN_SRC\n  code\n  #+END_SRC"
+        );
+        assert_eq!(
+            render(SourceFormat::ReStructuredText),
+            "CPU\n   processor\n\nRAM\n   ::\n\n      code"
+        );
+        assert_eq!(
+            render(SourceFormat::Typst),
+            "/ CPU: processor\n/ RAM: ```\n  code\n  ```"
+        );
+        assert_eq!(
+            render(SourceFormat::PlainText),
+            "CPU\n    processor\nRAM\n    code"
+        );
+    }
+
+    #[test]
+    fn test_compound_blocks() {
+        let handler = AsciidocHandler::new();
+        let source = "====\nex one\n\nex two\n====\n\n--\nopen one\n\nopen two\n--\n\n\
+                      [NOTE]\n====\nnote\n====\n\n\
+                      .Title\n[#outer.wide]\n=====\nouter\n\n====\ninner\n====\n=====";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        assert_eq!(doc.content.len(), 4);
+
+        let Block::Container {
+            classes, content, ..
+        } = &doc.content[0]
+        else {
+            panic!("expected an example block, got {:?}", doc.content[0]);
+        };
+        assert_eq!(classes, &["example"]);
+        assert_eq!(content.len(), 2);
+        assert!(matches!(&doc.content[1], Block::Container { content, .. } if content.len() == 2));
+        assert!(matches!(
+            doc.content[2],
+            Block::BlockQuote {
+                admonition: Some(AdmonitionType::Note),
+                ..
+            }
+        ));
+        let Block::Container {
+            id,
+            classes,
+            attributes,
+            content,
+            ..
+        } = &doc.content[3]
+        else {
+            panic!("expected an example block, got {:?}", doc.content[3]);
+        };
+        assert_eq!(id.as_deref(), Some("outer"));
+        assert_eq!(classes, &["example", "wide"]);
+        assert_eq!(attributes["title"], "Title");
+        assert!(matches!(content[1], Block::Container { .. }));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_compound_blocks_in_other_formats() {
+        let doc = AsciidocHandler::new()
+            .parse("====\nfirst\n\nsecond\n====", &ParseConfig::default())
+            .unwrap();
+        let render = |format| {
+            crate::formats::handler_for(format)
+                .render(&doc, &RenderConfig::default())
+                .unwrap()
+        };
+        assert_eq!(render(SourceFormat::Markdown), "first\n\nsecond");
+        assert_eq!(
+            render(SourceFormat::OrgMode),
+            "#+BEGIN_container\nfirst\n\nsecond\n#+END_container"
+        );
+        assert_eq!(
+            render(SourceFormat::ReStructuredText),
+            ".. container:: example\n\n   first\n\n   second"
+        );
+        assert_eq!(
+            render(SourceFormat::Typst),
+            "#block[\n  first\n\n  second\n]"
+        );
+        assert_eq!(render(SourceFormat::PlainText), "first\n\nsecond");
+    }
+
+    #[test]
+    fn test_collapsible_block() {
+        let handler = AsciidocHandler::new();
+        let source = ".Show *more*\n[%collapsible%open]\n====\nHidden\n====\n\n\
+                      [%collapsible]\n=====\nouter\n\n====\ninner\n====\n=====";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+
+        let Block::Details {
+            summary,
+            content,
+            open,
+            ..
+        } = &doc.content[0]
+        else {
+            panic!("expected a collapsible block, got {:?}", doc.content[0]);
+        };
+        assert!(*open);
+        assert!(matches!(
+            summary.as_deref(),
+            Some([_, Inline::Strong { .. }])
+        ));
+        assert_eq!(content.len(), 1);
+        assert!(matches!(
+            &doc.content[1],
+            Block::Details {
+                summary: None,
+                open: false,
+                ..
+            }
+        ));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+
+        let markdown = "<details>\n<summary>FAQ</summary>\n\nAnswer\n\n</details>";
+        let doc = crate::formats::MarkdownHandler::new()
+            .parse(markdown, &ParseConfig::default())
+            .unwrap();
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, ".FAQ\n[%collapsible]\n====\nAnswer\n====");
+    }
+
+    #[test]
+    fn test_ui_macros() {
+        let handler = AsciidocHandler::new();
+        let source = "Press kbd:[Ctrl+\\]] or btn:[Save], then menu:File[Export > PDF].";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(&content[1], Inline::Keyboard { content } if content == "Ctrl+]"));
+        assert!(matches!(&content[3], Inline::Button { content } if content == "Save"));
+        assert!(
+            matches!(&content[5], Inline::Menu { items } if items == &["File", "Export", "PDF"])
+        );
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+
+        let markdown = crate::formats::MarkdownHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert_eq!(
+            markdown,
+            "Press <kbd>Ctrl+]</kbd> or **Save**, then **File > Export > PDF**."
+        );
+    }
+
+    #[test]
+    fn test_quote_attribution() {
+        let handler = AsciidocHandler::new();
+        let source = "[quote, Abraham Lincoln, Gettysburg Address]\n____\nFour score.\n____\n\n\
+                      [quote, Anonymous]\nJust a line.\n";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let attribution = |block: &Block| match block {
+            Block::BlockQuote {
+                attribution: Some(attribution),
+                ..
+            } => serde_json::to_string(attribution).unwrap(),
+            other => panic!("expected an attributed quote, got {:?}", other),
+        };
+        assert!(attribution(&doc.content[0]).contains("Abraham Lincoln, Gettysburg Address"));
+        assert!(attribution(&doc.content[1]).contains("Anonymous"));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with("[quote, Abraham Lincoln, Gettysburg Address]\n____\n"));
+    }
 }
@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Format capability matrix
+//!
+//! For every pair of formats, how each AST feature the source can hold
+//! fares when converted to the target. A feature the target supports, and
+//! keeps stable across round trips, converts losslessly. For the rest, a
+//! small probe document using the feature is rendered with the target: if
+//! the probe's text survives the feature degrades, otherwise it is dropped.
+
+use super::handler_for;
+use crate::ast::{
+    AdmonitionType, Block, ColumnAlignment, ColumnSpec, DefinitionItem, Document, Inline, LinkType,
+    ListItem, ListKind, MathNotation, SourceFormat, TableCell, TableRow,
+};
+use crate::report::FEATURES;
+use crate::traits::{FormatHandler, RenderConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How a feature fares in a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// The target holds the feature as it is
+    Lossless,
+    /// The content survives, but as plainer markup or unstable across
+    /// round trips
+    Degraded,
+    /// The content is lost
+    Dropped,
+}
+
+/// How the features of one format fare when converted to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairCapabilities {
+    pub from: SourceFormat,
+    pub to: SourceFormat,
+    /// Each feature the source format supports, by name as in
+    /// [`FormatHandler::supported_features`]
+    pub features: BTreeMap<String, Capability>,
+}
+
+impl PairCapabilities {
+    /// Whether every feature of the source converts losslessly
+    pub fn is_lossless(&self) -> bool {
+        self.features.values().all(|c| *c == Capability::Lossless)
+    }
+}
+
+/// Text every probe document carries, to look for in the rendered output
+const MARKER: &str = "probemarker";
+
+/// Capabilities of every ordered pair of formats, a format with itself
+/// included, in [`SourceFormat::ALL`] order
+pub fn capability_matrix() -> Vec<PairCapabilities> {
+    let handlers: Vec<_> = SourceFormat::ALL.map(|f| (f, handler_for(f))).into();
+
+    // Capabilities depend only on the target, so work them out once each
+    let targets: Vec<BTreeMap<&str, Capability>> = handlers
+        .iter()
+        .map(|(_, target)| {
+            FEATURES
+                .iter()
+                .map(|&feature| (feature, capability(target.as_ref(), feature)))
+                .collect()
+        })
+        .collect();
+
+    let mut matrix = Vec::new();
+    for (from, source) in &handlers {
+        for ((to, _), capabilities) in handlers.iter().zip(&targets) {
+            matrix.push(PairCapabilities {
+                from: *from,
+                to: *to,
+                features: capabilities
+                    .iter()
+                    .filter(|(feature, _)| source.supports_feature(feature))
+                    .map(|(feature, c)| (feature.to_string(), *c))
+                    .collect(),
+            });
+        }
+    }
+    matrix
+}
+
+fn capability(target: &dyn FormatHandler, feature: &str) -> Capability {
+    if target.supports_feature(feature) && !target.normalization_exceptions().contains(&feature) {
+        return Capability::Lossless;
+    }
+    let survives = target
+        .render(&probe(feature), &RenderConfig::default())
+        .is_ok_and(|output| output.contains(MARKER));
+    if survives {
+        Capability::Degraded
+    } else {
+        Capability::Dropped
+    }
+}
+
+/// A document using `feature` and nothing else, around [`MARKER`]
+fn probe(feature: &str) -> Document {
+    let text = || {
+        vec![Inline::Text {
+            content: MARKER.to_string(),
+        }]
+    };
+    let paragraph = |content| Block::Paragraph {
+        content,
+        span: None,
+    };
+    let list = |kind, checked| Block::List {
+        kind,
+        items: vec![ListItem {
+            content: vec![paragraph(text())],
+            checked,
+            marker: None,
+        }],
+        start: None,
+        span: None,
+    };
+    let quote = |admonition| Block::BlockQuote {
+        content: vec![paragraph(text())],
+        attribution: None,
+        admonition,
+        span: None,
+    };
+
+    let content = match feature {
+        "heading" => vec![Block::Heading {
+            level: 1,
+            content: text(),
+            id: None,
+            classes: Vec::new(),
+            span: None,
+        }],
+        "bold" => vec![paragraph(vec![Inline::Strong { content: text() }])],
+        "italic" => vec![paragraph(vec![Inline::Emphasis { content: text() }])],
+        "strikethrough" => vec![paragraph(vec![Inline::Strikethrough { content: text() }])],
+        "code" => vec![paragraph(vec![Inline::Code {
+            content: MARKER.to_string(),
+            language: None,
+        }])],
+        "code_block" => vec![Block::CodeBlock {
+            language: None,
+            content: MARKER.to_string(),
+            line_numbers: false,
+            highlight_lines: Vec::new(),
+            span: None,
+        }],
+        "link" => vec![paragraph(vec![Inline::Link {
+            url: "https://example.com".to_string(),
+            title: None,
+            content: text(),
+            link_type: LinkType::Inline,
+        }])],
+        "image" => vec![paragraph(vec![Inline::Image {
+            url: "image.png".to_string(),
+            alt: MARKER.to_string(),
+            title: None,
+            width: None,
+            height: None,
+        }])],
+        "list" => vec![list(ListKind::Bullet, None)],
+        "task_list" => vec![list(ListKind::Task, Some(false))],
+        "table" => vec![Block::Table {
+            caption: None,
+            columns: vec![ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            }],
+            header: None,
+            body: vec![TableRow {
+                cells: vec![TableCell {
+                    content: vec![paragraph(text())],
+                    colspan: 1,
+                    rowspan: 1,
+                    alignment: None,
+                }],
+            }],
+            footer: None,
+            span: None,
+        }],
+        "blockquote" => vec![quote(None)],
+        "admonition" => vec![quote(Some(AdmonitionType::Note))],
+        "nested_quote" => {
+            let mut outer = quote(None);
+            if let Block::BlockQuote { content, .. } = &mut outer {
+                content.push(quote(None));
+            }
+            vec![outer]
+        }
+        "math" => vec![Block::MathBlock {
+            content: MARKER.to_string(),
+            notation: MathNotation::LaTeX,
+            span: None,
+        }],
+        "footnote" => vec![
+            paragraph(vec![Inline::FootnoteRef {
+                label: "1".to_string(),
+            }]),
+            Block::FootnoteDefinition {
+                label: "1".to_string(),
+                content: vec![paragraph(text())],
+                span: None,
+            },
+        ],
+        "container" => vec![Block::Container {
+            id: None,
+            classes: vec!["probe".to_string()],
+            attributes: BTreeMap::new(),
+            content: vec![paragraph(text())],
+            span: None,
+        }],
+        "definition_list" => vec![Block::DefinitionList {
+            items: vec![DefinitionItem {
+                term: text(),
+                definitions: vec![vec![paragraph(text())]],
+            }],
+            span: None,
+        }],
+        "details" => vec![Block::Details {
+            summary: None,
+            content: vec![paragraph(text())],
+            open: false,
+            span: None,
+        }],
+        "kbd" => vec![paragraph(vec![Inline::Keyboard {
+            content: MARKER.to_string(),
+        }])],
+        "button" => vec![paragraph(vec![Inline::Button {
+            content: MARKER.to_string(),
+        }])],
+        "menu" => vec![paragraph(vec![Inline::Menu {
+            items: vec![MARKER.to_string()],
+        }])],
+        "emoji" => vec![paragraph(vec![
+            Inline::Emoji {
+                shortcode: "smile".to_string(),
+            },
+            Inline::Text {
+                content: MARKER.to_string(),
+            },
+        ])],
+        _ => Vec::new(),
+    };
+
+    let mut doc = Document::new(SourceFormat::Djot);
+    doc.content = content;
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::used_features;
+
+    #[test]
+    fn test_probes_use_their_feature() {
+        for feature in FEATURES {
+            let used: Vec<_> = used_features(&probe(feature)).into_iter().collect();
+            assert_eq!(used, vec![feature]);
+        }
+    }
+
+    #[test]
+    fn test_capability_matrix() {
+        let matrix = capability_matrix();
+        assert_eq!(
+            matrix.len(),
+            SourceFormat::ALL.len() * SourceFormat::ALL.len()
+        );
+
+        let pair = |from, to| {
+            matrix
+                .iter()
+                .find(|p| p.from == from && p.to == to)
+                .unwrap()
+        };
+        let to_djot = &pair(SourceFormat::Markdown, SourceFormat::Djot).features;
+        assert_eq!(to_djot["details"], Capability::Degraded);
+        assert!(to_djot
+            .iter()
+            .all(|(f, c)| *f == "details" || *c == Capability::Lossless));
+        assert!(pair(SourceFormat::PlainText, SourceFormat::Markdown)
+            .features
+            .is_empty());
+
+        let to_rst = &pair(SourceFormat::Markdown, SourceFormat::ReStructuredText).features;
+        assert_eq!(to_rst["heading"], Capability::Lossless);
+        assert_eq!(to_rst["strikethrough"], Capability::Degraded);
+        assert!(!to_rst.contains_key("math"));
+        assert_eq!(
+            pair(SourceFormat::Markdown, SourceFormat::PlainText).features["table"],
+            Capability::Dropped
+        );
+    }
+}
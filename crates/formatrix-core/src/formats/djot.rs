@@ -2,12 +2,16 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Djot format handler using jotdown
 
+use super::{comment_text, leading_markers, line_nesting};
 use crate::ast::{
-    AdmonitionType, Block, Document, DocumentMeta, Inline, ListItem, ListKind, SourceFormat,
-    TableCell, TableRow,
+    AdmonitionType, Block, ColumnAlignment, ColumnSpec, DefinitionItem, Document, DocumentMeta,
+    Inline, LinkType, ListItem, ListKind, MathNotation, SourceFormat, TableCell, TableRow,
 };
-use crate::traits::{FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result};
-use jotdown::{Container, Event, Parser as JotdownParser};
+use crate::traits::{
+    Deadline, FormatHandler, ParseConfig, ParseLimits, Parser, RenderConfig, Renderer, Result,
+};
+use jotdown::{Attributes, Container, Event, Parser as JotdownParser};
+use std::collections::BTreeMap;
 
 /// Djot format handler using jotdown
 pub struct DjotHandler;
@@ -30,10 +34,16 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_source(input, source_depth)?;
+        let deadline = config.limits.deadline();
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
         let parser = JotdownParser::new(input);
-        let content = parse_events(parser);
+        let content = parse_events(parser, &config.limits, deadline)?;
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::Djot,
             meta: DocumentMeta::default(),
             content,
@@ -42,127 +52,397 @@ fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
             } else {
                 None
             },
-        })
+        }))
+    }
+}
+
+/// How deeply quotes, lists and indented blocks nest in Djot source,
+/// which jotdown recurses into once per level of quote
+fn source_depth(input: &str) -> usize {
+    line_nesting(input, |line| {
+        leading_markers(line, &[">", "-", "*", "+", ":"])
+    })
+}
+
+/// Content collected for an open container
+struct Frame<'s> {
+    container: Container<'s>,
+    attrs: Attributes<'s>,
+    blocks: Vec<Block>,
+    inlines: Vec<Inline>,
+    items: Vec<ListItem>,
+    rows: Vec<(bool, TableRow)>,
+    cells: Vec<TableCell>,
+    caption: Option<Vec<Inline>>,
+    definitions: Vec<DefinitionItem>,
+    display_math: bool,
+}
+
+impl<'s> Frame<'s> {
+    fn new(container: Container<'s>, attrs: Attributes<'s>) -> Self {
+        Self {
+            container,
+            attrs,
+            blocks: Vec::new(),
+            inlines: Vec::new(),
+            items: Vec::new(),
+            rows: Vec::new(),
+            cells: Vec::new(),
+            caption: None,
+            definitions: Vec::new(),
+            display_math: false,
+        }
+    }
+
+    /// Child blocks, or the collected inlines as a single paragraph
+    fn into_blocks(self) -> Vec<Block> {
+        if self.blocks.is_empty() && !self.inlines.is_empty() {
+            vec![Block::Paragraph {
+                content: self.inlines,
+                span: None,
+            }]
+        } else {
+            self.blocks
+        }
     }
 }
 
-/// Parse jotdown events into blocks
-fn parse_events<'a>(parser: impl Iterator<Item = Event<'a>>) -> Vec<Block> {
-    let mut blocks = Vec::new();
-    let mut stack: Vec<(Container<'a>, Vec<Block>, Vec<Inline>)> = Vec::new();
+/// Parse jotdown events into blocks, stopping once they nest deeper than
+/// `limits` allow or the deadline passes
+fn parse_events<'a>(
+    parser: impl Iterator<Item = Event<'a>>,
+    limits: &ParseLimits,
+    deadline: Deadline,
+) -> Result<Vec<Block>> {
+    let mut root = Frame::new(Container::Section { id: "".into() }, Attributes::new());
+    let mut stack: Vec<Frame<'a>> = Vec::new();
+    let mut depth = 0;
 
     for event in parser {
+        deadline.check()?;
+        let top = stack.last_mut().unwrap_or(&mut root);
         match event {
-            Event::Start(container, _attrs) => {
-                stack.push((container, Vec::new(), Vec::new()));
-            }
-
-            Event::End(container) => {
-                if let Some((_, child_blocks, inlines)) = stack.pop() {
-                    // Check if this is a Section - sections should pass through their children
-                    if matches!(container, Container::Section { .. }) {
-                        // Add all child blocks directly to parent or root
-                        if let Some((_, parent_blocks, _)) = stack.last_mut() {
-                            parent_blocks.extend(child_blocks);
-                        } else {
-                            blocks.extend(child_blocks);
-                        }
-                    } else {
-                        let block = container_to_block(container, child_blocks, inlines);
-
-                        if let Some(b) = block {
-                            if let Some((_, parent_blocks, _)) = stack.last_mut() {
-                                parent_blocks.push(b);
-                            } else {
-                                blocks.push(b);
-                            }
-                        }
-                    }
+            Event::Start(container, attrs) => {
+                if nests(&container) {
+                    depth += 1;
+                    limits.check_depth(depth)?;
                 }
+                // Attributes written before a heading belong to its section
+                let attrs = match (&container, stack.last()) {
+                    (
+                        Container::Heading { .. },
+                        Some(Frame {
+                            container: Container::Section { .. },
+                            attrs: section,
+                            ..
+                        }),
+                    ) => section.clone(),
+                    _ => attrs,
+                };
+                stack.push(Frame::new(container, attrs));
             }
 
-            Event::Str(text) => {
-                if let Some((_, _, inlines)) = stack.last_mut() {
-                    inlines.push(Inline::Text {
-                        content: text.to_string(),
-                    });
+            Event::End(_) => {
+                if let Some(frame) = stack.pop() {
+                    if nests(&frame.container) {
+                        depth -= 1;
+                    }
+                    let parent = stack.last_mut().unwrap_or(&mut root);
+                    close_frame(frame, parent);
                 }
             }
 
-            Event::Softbreak => {
-                if let Some((_, _, inlines)) = stack.last_mut() {
-                    inlines.push(Inline::SoftBreak);
-                }
+            Event::Str(text) => push_text(&mut top.inlines, &text),
+            // Symbols naming an emoji are that emoji; others stay as written
+            Event::Symbol(sym) if emojis::get_by_shortcode(&sym).is_some() => {
+                top.inlines.push(Inline::Emoji {
+                    shortcode: sym.to_string(),
+                })
             }
+            Event::Symbol(sym) => push_text(&mut top.inlines, &format!(":{}:", sym)),
+            Event::LeftSingleQuote => push_text(&mut top.inlines, "\u{2018}"),
+            Event::RightSingleQuote => push_text(&mut top.inlines, "\u{2019}"),
+            Event::LeftDoubleQuote => push_text(&mut top.inlines, "\u{201C}"),
+            Event::RightDoubleQuote => push_text(&mut top.inlines, "\u{201D}"),
+            Event::Ellipsis => push_text(&mut top.inlines, "\u{2026}"),
+            Event::EnDash => push_text(&mut top.inlines, "\u{2013}"),
+            Event::EmDash => push_text(&mut top.inlines, "\u{2014}"),
+            Event::NonBreakingSpace => top.inlines.push(Inline::NonBreakingSpace),
+            Event::Softbreak => top.inlines.push(Inline::SoftBreak),
+            Event::Hardbreak => top.inlines.push(Inline::LineBreak),
+
+            Event::FootnoteReference(label) => top.inlines.push(Inline::FootnoteRef {
+                label: label.to_string(),
+            }),
+
+            Event::ThematicBreak(_) => top.blocks.push(Block::ThematicBreak { span: None }),
+
+            // Escapes are resolved by the parser; blank lines and stray
+            // attributes carry no content
+            _ => {}
+        }
+    }
 
-            Event::Hardbreak => {
-                if let Some((_, _, inlines)) = stack.last_mut() {
-                    inlines.push(Inline::LineBreak);
-                }
-            }
+    Ok(root.blocks)
+}
 
-            Event::NonBreakingSpace => {
-                if let Some((_, _, inlines)) = stack.last_mut() {
-                    inlines.push(Inline::Text {
-                        content: "\u{00A0}".to_string(),
-                    });
+/// Whether a container is a level of nesting in the document; sections,
+/// list items and table rows only group what is in them
+fn nests(container: &Container) -> bool {
+    !matches!(
+        container,
+        Container::Section { .. }
+            | Container::ListItem
+            | Container::TaskListItem { .. }
+            | Container::TableRow { .. }
+    )
+}
+
+/// Append text, merging with a preceding text node
+fn push_text(inlines: &mut Vec<Inline>, text: &str) {
+    if let Some(Inline::Text { content }) = inlines.last_mut() {
+        content.push_str(text);
+    } else {
+        inlines.push(Inline::Text {
+            content: text.to_string(),
+        });
+    }
+}
+
+/// Concatenate plain text from verbatim-like containers
+fn inline_source(inlines: Vec<Inline>) -> String {
+    inlines
+        .into_iter()
+        .map(|i| match i {
+            Inline::Text { content } => content,
+            Inline::SoftBreak => "\n".to_string(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// Fold a finished container into its parent
+fn close_frame<'s>(mut frame: Frame<'s>, parent: &mut Frame<'s>) {
+    let title = frame.attrs.get_value("title").map(|v| v.to_string());
+
+    let inline = match &frame.container {
+        Container::Emphasis => Some(Inline::Emphasis {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Strong => Some(Inline::Strong {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Delete => Some(Inline::Strikethrough {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Insert => Some(Inline::Underline {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Mark => Some(Inline::Highlight {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Superscript => Some(Inline::Superscript {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Subscript => Some(Inline::Subscript {
+            content: std::mem::take(&mut frame.inlines),
+        }),
+        Container::Verbatim => Some(Inline::Code {
+            content: inline_source(std::mem::take(&mut frame.inlines)),
+            language: None,
+        }),
+        Container::Math { display } => {
+            parent.display_math |= *display;
+            Some(Inline::Math {
+                content: inline_source(std::mem::take(&mut frame.inlines)),
+                notation: MathNotation::LaTeX,
+            })
+        }
+        Container::RawInline { .. } => Some(Inline::RawInline {
+            format: SourceFormat::Djot,
+            content: inline_source(std::mem::take(&mut frame.inlines)),
+        }),
+        Container::Span => {
+            let class = frame.attrs.get_value("class").map(|v| v.to_string());
+            // The classes the renderer gives keys, buttons and menus
+            match class.as_deref() {
+                Some("kbd") => Some(Inline::Keyboard {
+                    content: inline_source(std::mem::take(&mut frame.inlines)),
+                }),
+                Some("button") => Some(Inline::Button {
+                    content: inline_source(std::mem::take(&mut frame.inlines)),
+                }),
+                Some("menu") => Some(Inline::Menu {
+                    items: inline_source(std::mem::take(&mut frame.inlines))
+                        .split('>')
+                        .map(|item| item.trim().to_string())
+                        .collect(),
+                }),
+                _ => {
+                    let (id, classes, attributes) = split_attributes(&frame.attrs);
+                    Some(Inline::Span {
+                        id,
+                        classes,
+                        attributes,
+                        content: std::mem::take(&mut frame.inlines),
+                    })
                 }
             }
+        }
+        Container::Link(url, link_type) => Some(Inline::Link {
+            url: url.to_string(),
+            title,
+            content: std::mem::take(&mut frame.inlines),
+            link_type: match link_type {
+                jotdown::LinkType::AutoLink | jotdown::LinkType::Email => LinkType::AutoLink,
+                jotdown::LinkType::Span(jotdown::SpanLinkType::Inline) => LinkType::Inline,
+                jotdown::LinkType::Span(_) => LinkType::Reference,
+            },
+        }),
+        Container::Image(url, _) => Some(Inline::Image {
+            url: url.to_string(),
+            alt: inline_source(std::mem::take(&mut frame.inlines)),
+            title,
+            width: None,
+            height: None,
+        }),
+        _ => None,
+    };
+    if let Some(inline) = inline {
+        // Attributes on any other inline go on a span around it
+        let (id, classes, mut attributes) = split_attributes(&frame.attrs);
+        if matches!(frame.container, Container::Link(..) | Container::Image(..)) {
+            attributes.remove("title");
+        }
+        let attributed = id.is_some() || !classes.is_empty() || !attributes.is_empty();
+        parent.inlines.push(match frame.container {
+            Container::Span => inline,
+            _ if attributed => Inline::Span {
+                id,
+                classes,
+                attributes,
+                content: vec![inline],
+            },
+            _ => inline,
+        });
+        return;
+    }
 
-            Event::Escape => {
-                // Escape sequences are handled by the parser
-            }
+    match frame.container {
+        // Sections pass their children straight through
+        Container::Section { .. } => parent.blocks.extend(frame.blocks),
 
-            Event::Blankline => {
-                // Blank lines between blocks
-            }
+        Container::ListItem | Container::TaskListItem { .. } => {
+            let checked = match frame.container {
+                Container::TaskListItem { checked } => Some(checked),
+                _ => None,
+            };
+            parent.items.push(ListItem {
+                content: frame.into_blocks(),
+                checked,
+                marker: None,
+            });
+        }
 
-            Event::ThematicBreak(_) => {
-                if let Some((_, parent_blocks, _)) = stack.last_mut() {
-                    parent_blocks.push(Block::ThematicBreak { span: None });
-                } else {
-                    blocks.push(Block::ThematicBreak { span: None });
-                }
+        Container::TableCell { alignment, .. } => {
+            parent.cells.push(TableCell {
+                content: vec![Block::Paragraph {
+                    content: frame.inlines,
+                    span: None,
+                }],
+                colspan: 1,
+                rowspan: 1,
+                alignment: match alignment {
+                    jotdown::Alignment::Unspecified => None,
+                    jotdown::Alignment::Left => Some(ColumnAlignment::Left),
+                    jotdown::Alignment::Center => Some(ColumnAlignment::Center),
+                    jotdown::Alignment::Right => Some(ColumnAlignment::Right),
+                },
+            });
+        }
+
+        Container::TableRow { head } => {
+            parent.rows.push((head, TableRow { cells: frame.cells }));
+        }
+
+        Container::Caption => parent.caption = Some(frame.inlines),
+
+        Container::DescriptionTerm => parent.definitions.push(DefinitionItem {
+            term: frame.inlines,
+            definitions: Vec::new(),
+        }),
+
+        Container::DescriptionDetails => {
+            let blocks = frame.into_blocks();
+            if let Some(item) = parent.definitions.last_mut() {
+                item.definitions.push(blocks);
             }
+        }
 
-            _ => {}
+        Container::LinkDefinition { .. } => {}
+
+        _ => {
+            if let Some(block) = container_to_block(frame) {
+                parent.blocks.push(block);
+            }
         }
     }
+}
 
-    blocks
+/// The id, classes and other attributes of an attribute set
+fn split_attributes(attrs: &Attributes) -> (Option<String>, Vec<String>, BTreeMap<String, String>) {
+    let mut id = None;
+    let mut classes = Vec::new();
+    let mut attributes = BTreeMap::new();
+    for (key, value) in attrs.unique_pairs() {
+        match key {
+            "id" => id = Some(value.to_string()),
+            "class" => classes.extend(value.to_string().split_whitespace().map(String::from)),
+            _ => {
+                attributes.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    (id, classes, attributes)
 }
 
-/// Convert a jotdown container to a block
-fn container_to_block(
-    container: Container,
-    child_blocks: Vec<Block>,
-    inlines: Vec<Inline>,
-) -> Option<Block> {
-    match container {
-        Container::Paragraph => Some(Block::Paragraph {
-            content: inlines,
-            span: None,
-        }),
+/// Convert a finished block-level container to a block
+fn container_to_block(frame: Frame) -> Option<Block> {
+    match frame.container {
+        Container::Paragraph => {
+            // A paragraph holding only display math is a math block
+            if let (true, [Inline::Math { content, notation }]) =
+                (frame.display_math, frame.inlines.as_slice())
+            {
+                return Some(Block::MathBlock {
+                    content: content.clone(),
+                    notation: *notation,
+                    span: None,
+                });
+            }
+            Some(Block::Paragraph {
+                content: frame.inlines,
+                span: None,
+            })
+        }
 
         Container::Heading { level, .. } => Some(Block::Heading {
             level: level as u8,
-            content: inlines,
+            content: frame.inlines,
             id: None,
+            classes: frame
+                .attrs
+                .get_value("class")
+                .map(|v| v.to_string().split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
             span: None,
         }),
 
         Container::CodeBlock { language } => {
-            let content = inlines
-                .into_iter()
-                .filter_map(|i| {
-                    if let Inline::Text { content } = i {
-                        Some(content)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join("");
+            let mut content = inline_source(frame.inlines);
+            if content.ends_with('\n') {
+                content.pop();
+            }
 
             Some(Block::CodeBlock {
                 language: if language.is_empty() {
@@ -177,100 +457,67 @@ fn container_to_block(
             })
         }
 
-        Container::Blockquote => Some(Block::BlockQuote {
-            content: if child_blocks.is_empty() {
-                vec![Block::Paragraph {
-                    content: inlines,
-                    span: None,
-                }]
-            } else {
-                child_blocks
-            },
-            attribution: None,
-            admonition: None,
-            span: None,
-        }),
+        Container::Blockquote => {
+            let mut content = frame.into_blocks();
+            let attribution = super::split_attribution(&mut content);
+            Some(Block::BlockQuote {
+                content,
+                attribution,
+                admonition: None,
+                span: None,
+            })
+        }
 
         Container::List { kind, .. } => {
-            let list_kind = match kind {
-                jotdown::ListKind::Unordered(_) => ListKind::Bullet,
-                jotdown::ListKind::Ordered { .. } => ListKind::Ordered,
-                jotdown::ListKind::Task(_) => ListKind::Task,
+            let (list_kind, start) = match kind {
+                jotdown::ListKind::Unordered(_) => (ListKind::Bullet, None),
+                jotdown::ListKind::Ordered { start, .. } => {
+                    (ListKind::Ordered, Some(start as u32).filter(|s| *s != 1))
+                }
+                jotdown::ListKind::Task(_) => (ListKind::Task, None),
             };
 
-            // Convert child blocks into list items
-            let items: Vec<ListItem> = child_blocks
-                .into_iter()
-                .map(|block| ListItem {
-                    content: vec![block],
-                    checked: None,
-                    marker: None,
-                })
-                .collect();
-
             Some(Block::List {
                 kind: list_kind,
-                items,
-                start: None,
+                items: frame.items,
+                start,
                 span: None,
             })
         }
 
-        Container::ListItem => {
-            // Return the content as a paragraph to be wrapped by List
-            if !child_blocks.is_empty() {
-                Some(child_blocks.into_iter().next().unwrap())
-            } else if !inlines.is_empty() {
-                Some(Block::Paragraph {
-                    content: inlines,
-                    span: None,
-                })
-            } else {
-                None
-            }
-        }
-
-        Container::TaskListItem { checked: _ } => {
-            let block = if !child_blocks.is_empty() {
-                child_blocks.into_iter().next().unwrap()
-            } else {
-                Block::Paragraph {
-                    content: inlines,
-                    span: None,
-                }
-            };
-
-            // We'll need to handle this specially in the List container
-            Some(block)
-        }
+        Container::DescriptionList => Some(Block::DefinitionList {
+            items: frame.definitions,
+            span: None,
+        }),
 
         Container::Table => {
-            // Tables need special handling
             let mut header = None;
-            let body = Vec::new();
-
-            for block in child_blocks {
-                if let Block::Raw { content, .. } = block {
-                    // Parse table rows
-                    if header.is_none() {
-                        header = Some(TableRow {
-                            cells: vec![TableCell {
-                                content: vec![Block::Paragraph {
-                                    content: vec![Inline::Text { content }],
-                                    span: None,
-                                }],
-                                colspan: 1,
-                                rowspan: 1,
-                                alignment: None,
-                            }],
-                        });
-                    }
+            let mut body = Vec::new();
+            for (head, row) in frame.rows {
+                if head && header.is_none() && body.is_empty() {
+                    header = Some(row);
+                } else {
+                    body.push(row);
                 }
             }
 
+            let columns = header
+                .as_ref()
+                .or(body.first())
+                .map(|row| {
+                    row.cells
+                        .iter()
+                        .map(|c| ColumnSpec {
+                            alignment: c.alignment.unwrap_or(ColumnAlignment::Default),
+                            width: None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
             Some(Block::Table {
-                caption: None,
-                columns: Vec::new(),
+                caption: frame.caption,
+                columns,
                 header,
                 body,
                 footer: None,
@@ -280,67 +527,52 @@ fn container_to_block(
 
         Container::Div { class } => {
             // Check if it's an admonition
-            let admonition = match class.as_ref() {
+            let admonition = match class {
                 "note" => Some(AdmonitionType::Note),
                 "tip" => Some(AdmonitionType::Tip),
                 "warning" => Some(AdmonitionType::Warning),
                 "caution" => Some(AdmonitionType::Caution),
                 "important" => Some(AdmonitionType::Important),
+                "danger" => Some(AdmonitionType::Danger),
                 _ => None,
             };
 
             if admonition.is_some() {
                 Some(Block::BlockQuote {
-                    content: child_blocks,
+                    content: frame.blocks,
                     attribution: None,
                     admonition,
                     span: None,
                 })
             } else {
-                // Just return the child blocks as-is (simplified)
-                child_blocks.into_iter().next()
+                // The fence's class comes first, then those of the attributes
+                let (id, attr_classes, attributes) = split_attributes(&frame.attrs);
+                let classes = class
+                    .split_whitespace()
+                    .map(String::from)
+                    .chain(attr_classes)
+                    .collect();
+                Some(Block::Container {
+                    id,
+                    classes,
+                    attributes,
+                    content: frame.blocks,
+                    span: None,
+                })
             }
         }
 
-        Container::Emphasis => {
-            // This should produce inline, not block
-            None
-        }
-
-        Container::Strong => None,
-
-        Container::Link(_url, _link_type) => None,
-
-        Container::Image(_url, _link_type) => None,
-
         Container::Footnote { label } => Some(Block::FootnoteDefinition {
             label: label.to_string(),
-            content: child_blocks,
+            content: frame.into_blocks(),
             span: None,
         }),
 
-        Container::RawBlock { format: _ } => {
-            let content = inlines
-                .into_iter()
-                .filter_map(|i| {
-                    if let Inline::Text { content } = i {
-                        Some(content)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join("");
-
-            Some(Block::Raw {
-                format: SourceFormat::Djot,
-                content,
-                span: None,
-            })
-        }
-
-        // Section is handled specially in the event loop
-        Container::Section { .. } => None,
+        Container::RawBlock { .. } => Some(Block::Raw {
+            format: SourceFormat::Djot,
+            content: inline_source(frame.inlines),
+            span: None,
+        }),
 
         _ => None,
     }
@@ -351,7 +583,8 @@ fn format(&self) -> SourceFormat {
         SourceFormat::Djot
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::Djot);
         let mut output = String::new();
 
         for (i, block) in doc.content.iter().enumerate() {
@@ -361,7 +594,7 @@ fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
             render_block(&mut output, block, 0);
         }
 
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
@@ -377,8 +610,16 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
         }
 
         Block::Heading {
-            level, content, id, ..
+            level,
+            content,
+            id,
+            classes,
+            ..
         } => {
+            if !classes.is_empty() {
+                let classes: Vec<String> = classes.iter().map(|c| format!(".{}", c)).collect();
+                output.push_str(&format!("{}{{{}}}\n", prefix, classes.join(" ")));
+            }
             output.push_str(&prefix);
             output.push_str(&"#".repeat(*level as usize));
             output.push(' ');
@@ -410,6 +651,7 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
 
         Block::BlockQuote {
             content,
+            attribution,
             admonition,
             ..
         } => {
@@ -428,16 +670,31 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                 output.push('\n');
                 for block in content {
                     render_block(output, block, indent);
-                    output.push('\n');
+                    output.push_str("\n\n");
                 }
                 output.push_str(&prefix);
                 output.push_str(":::");
             } else {
-                for block in content {
-                    output.push_str(&prefix);
-                    output.push_str("> ");
-                    render_block(output, block, 0);
-                    output.push('\n');
+                let content = super::quote_with_attribution(content, attribution.as_ref());
+                for (i, block) in content.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(&prefix);
+                        output.push_str(">\n");
+                    }
+                    let mut inner = String::new();
+                    render_block(&mut inner, block, 0);
+                    for line in inner.lines() {
+                        output.push_str(&prefix);
+                        output.push('>');
+                        if !line.is_empty() {
+                            output.push(' ');
+                            output.push_str(line);
+                        }
+                        output.push('\n');
+                    }
+                }
+                if output.ends_with('\n') {
+                    output.pop();
                 }
             }
         }
@@ -446,6 +703,9 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
             kind, items, start, ..
         } => {
             for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
                 output.push_str(&prefix);
                 match kind {
                     ListKind::Bullet => output.push_str("- "),
@@ -458,10 +718,15 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                         output.push_str(if checked { "- [x] " } else { "- [ ] " });
                     }
                 }
-                for block in &item.content {
-                    render_block(output, block, 0);
+                for (j, block) in item.content.iter().enumerate() {
+                    // Later blocks (and nested lists) are indented under the item
+                    if j == 0 && !matches!(block, Block::List { .. }) {
+                        render_block(output, block, 0);
+                    } else {
+                        output.push_str("\n\n");
+                        render_block(output, block, indent + 2);
+                    }
                 }
-                output.push('\n');
             }
         }
 
@@ -470,7 +735,12 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
             output.push_str("***");
         }
 
-        Block::Table { header, body, .. } => {
+        Block::Table {
+            columns,
+            header,
+            body,
+            ..
+        } => {
             if let Some(h) = header {
                 output.push_str(&prefix);
                 output.push('|');
@@ -484,10 +754,7 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                 output.push('\n');
 
                 output.push_str(&prefix);
-                output.push('|');
-                for _ in &h.cells {
-                    output.push_str(" --- |");
-                }
+                output.push_str(&super::pipe_table_rule(columns, h.cells.len()));
                 output.push('\n');
             }
 
@@ -503,14 +770,80 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                 }
                 output.push('\n');
             }
+            if output.ends_with('\n') {
+                output.pop();
+            }
         }
 
         Block::FootnoteDefinition { label, content, .. } => {
             output.push_str(&prefix);
             output.push_str(&format!("[^{}]: ", label));
+            for (i, block) in content.iter().enumerate() {
+                if i == 0 {
+                    render_block(output, block, 0);
+                } else {
+                    output.push_str("\n\n");
+                    render_block(output, block, indent + 2);
+                }
+            }
+        }
+
+        Block::DefinitionList { items, .. } => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push_str("\n\n");
+                }
+                output.push_str(&prefix);
+                output.push_str(": ");
+                for inline in &item.term {
+                    render_inline(output, inline);
+                }
+                for definition in &item.definitions {
+                    for block in definition {
+                        output.push_str("\n\n");
+                        render_block(output, block, indent + 2);
+                    }
+                }
+            }
+        }
+
+        Block::MathBlock { content, .. } => {
+            output.push_str(&prefix);
+            output.push_str("$$`");
+            output.push_str(content);
+            output.push('`');
+        }
+
+        Block::Container {
+            id,
+            classes,
+            attributes,
+            content,
+            ..
+        } => {
+            // Block attributes precede the fence they apply to, which takes
+            // the first class
+            let attrs = attribute_list(
+                id.as_ref(),
+                classes.get(1..).unwrap_or_default(),
+                attributes,
+            );
+            if !attrs.is_empty() {
+                output.push_str(&format!("{}{{{}}}\n", prefix, attrs.join(" ")));
+            }
+            output.push_str(&prefix);
+            output.push_str(":::");
+            if let Some(class) = classes.first() {
+                output.push(' ');
+                output.push_str(class);
+            }
+            output.push('\n');
             for block in content {
-                render_block(output, block, indent + 2);
+                render_block(output, block, indent);
+                output.push_str("\n\n");
             }
+            output.push_str(&prefix);
+            output.push_str(":::");
         }
 
         Block::Raw { content, .. } => {
@@ -520,10 +853,54 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
             output.push_str("\n```");
         }
 
+        Block::Comment { content, .. } => {
+            output.push_str(&prefix);
+            output.push_str(&format!("{{% {} %}}", comment_text(content, &["%}"])));
+        }
+
+        // Djot cannot fold, so the summary leads a plain div
+        Block::Details {
+            summary, content, ..
+        } => {
+            output.push_str(&prefix);
+            output.push_str("::: details\n");
+            for block in super::unfold_details(summary.as_ref(), content) {
+                render_block(output, &block, indent);
+                output.push_str("\n\n");
+            }
+            output.push_str(&prefix);
+            output.push_str(":::");
+        }
+
         _ => {}
     }
 }
 
+/// The parts of an attribute set: `#id`, `.class` and `key="value"`
+fn attribute_list(
+    id: Option<&String>,
+    classes: &[String],
+    attributes: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut attrs: Vec<String> = id.iter().map(|id| format!("#{}", id)).collect();
+    attrs.extend(classes.iter().map(|c| format!(".{}", c)));
+    attrs.extend(
+        attributes
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('"', "\\\""))),
+    );
+    attrs
+}
+
+/// Render inline content wrapped in opening and closing markup
+fn render_delimited(output: &mut String, open: &str, content: &[Inline], close: &str) {
+    output.push_str(open);
+    for inline in content {
+        render_inline(output, inline);
+    }
+    output.push_str(close);
+}
+
 fn render_inline(output: &mut String, inline: &Inline) {
     match inline {
         Inline::Text { content } => output.push_str(content),
@@ -552,12 +929,54 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push_str("-}");
         }
 
+        Inline::Underline { content } => render_delimited(output, "{+", content, "+}"),
+
+        Inline::Highlight { content } => render_delimited(output, "{=", content, "=}"),
+
+        Inline::Superscript { content } => render_delimited(output, "^", content, "^"),
+
+        Inline::Subscript { content } => render_delimited(output, "~", content, "~"),
+
+        Inline::SmallCaps { content } => render_delimited(output, "[", content, "]{.smallcaps}"),
+
+        Inline::Keyboard { content } => output.push_str(&format!("[{}]{{.kbd}}", content)),
+
+        Inline::Button { content } => output.push_str(&format!("[{}]{{.button}}", content)),
+
+        Inline::Menu { items } => output.push_str(&format!("[{}]{{.menu}}", items.join(" > "))),
+
+        Inline::Span {
+            id,
+            classes,
+            attributes,
+            content,
+        } => {
+            let attrs = attribute_list(id.as_ref(), classes, attributes);
+            render_delimited(output, "[", content, &format!("]{{{}}}", attrs.join(" ")));
+        }
+
         Inline::Code { content, .. } => {
             output.push('`');
             output.push_str(content);
             output.push('`');
         }
 
+        Inline::Math { content, .. } => {
+            output.push_str("$`");
+            output.push_str(content);
+            output.push('`');
+        }
+
+        Inline::Link {
+            url,
+            link_type: LinkType::AutoLink,
+            ..
+        } => {
+            output.push('<');
+            output.push_str(url);
+            output.push('>');
+        }
+
         Inline::Link {
             url,
             title,
@@ -570,10 +989,10 @@ fn render_inline(output: &mut String, inline: &Inline) {
             }
             output.push_str("](");
             output.push_str(url);
+            output.push(')');
             if let Some(t) = title {
-                output.push_str(&format!(" \"{}\"", t));
+                output.push_str(&format!("{{title=\"{}\"}}", t));
             }
-            output.push(')');
         }
 
         Inline::Image {
@@ -583,10 +1002,10 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push_str(alt);
             output.push_str("](");
             output.push_str(url);
+            output.push(')');
             if let Some(t) = title {
-                output.push_str(&format!(" \"{}\"", t));
+                output.push_str(&format!("{{title=\"{}\"}}", t));
             }
-            output.push(')');
         }
 
         Inline::FootnoteRef { label } => {
@@ -601,12 +1020,18 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('\n');
         }
 
+        Inline::NonBreakingSpace => output.push_str("\\ "),
+
         Inline::RawInline { content, .. } => {
             output.push_str("`");
             output.push_str(content);
             output.push_str("`");
         }
 
+        Inline::Comment { content } => {
+            output.push_str(&format!("{{% {} %}}", comment_text(content, &["%}"])));
+        }
+
         _ => {}
     }
 }
@@ -626,10 +1051,18 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "list"
                 | "task_list"
                 | "table"
+                | "math"
                 | "blockquote"
                 | "footnote"
                 | "admonition"
                 | "attributes"
+                | "container"
+                | "definition_list"
+                | "kbd"
+                | "button"
+                | "menu"
+                | "emoji"
+                | "nested_quote"
         )
     }
 
@@ -646,10 +1079,18 @@ fn supported_features(&self) -> &[&str] {
             "list",
             "task_list",
             "table",
+            "math",
             "blockquote",
             "footnote",
             "admonition",
             "attributes",
+            "container",
+            "definition_list",
+            "kbd",
+            "button",
+            "menu",
+            "emoji",
+            "nested_quote",
         ]
     }
 }
@@ -657,6 +1098,7 @@ fn supported_features(&self) -> &[&str] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::EmojiStyle;
 
     #[test]
     fn test_parse_heading() {
@@ -695,4 +1137,113 @@ fn test_roundtrip_simple() {
         assert!(output.contains("# Heading"));
         assert!(output.contains("Paragraph text"));
     }
+
+    #[test]
+    fn test_ui_spans() {
+        let handler = DjotHandler::new();
+        let source = "Press [Ctrl+C]{.kbd}, [OK]{.button} and [Edit > Copy]{.menu}";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(content[1], Inline::Keyboard { .. }));
+        assert!(matches!(content[3], Inline::Button { .. }));
+        assert!(matches!(&content[5], Inline::Menu { items } if items.len() == 2));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let handler = DjotHandler::new();
+        let source = "{#intro .wide lang=\"en\"}\n::: aside\n\
+                      See [the *notes*]{#n .aside data-x=\"1\"} and *this*{.key} :smile: :custom:.\n\n:::";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let Block::Container {
+            id,
+            classes,
+            attributes,
+            content,
+            ..
+        } = &doc.content[0]
+        else {
+            panic!("expected a div, got {:?}", doc.content[0]);
+        };
+        assert_eq!(id.as_deref(), Some("intro"));
+        assert_eq!(classes, &["aside", "wide"]);
+        assert_eq!(attributes["lang"], "en");
+        let Block::Paragraph { content, .. } = &content[0] else {
+            panic!("expected a paragraph");
+        };
+        assert!(
+            matches!(&content[1], Inline::Span { id: Some(id), attributes, .. }
+            if id == "n" && attributes["data-x"] == "1")
+        );
+        assert!(matches!(&content[3], Inline::Span { classes, content, .. }
+            if classes == &["key"] && matches!(content[0], Inline::Strong { .. })));
+        assert!(matches!(&content[5], Inline::Emoji { shortcode } if shortcode == "smile"));
+        assert!(matches!(&content[6], Inline::Text { content } if content == " :custom:."));
+
+        let config = RenderConfig {
+            emoji: EmojiStyle::Shortcode,
+            ..Default::default()
+        };
+        let output = handler.render(&doc, &config).unwrap();
+        assert_eq!(output, source.replace("*this*{.key}", "[*this*]{.key}"));
+    }
+
+    #[test]
+    fn test_divs_in_other_formats() {
+        let doc = DjotHandler::new()
+            .parse("::: aside\nfirst\n\nsecond\n:::", &ParseConfig::default())
+            .unwrap();
+        for format in SourceFormat::ALL {
+            let output = crate::formats::handler_for(format)
+                .render(&doc, &RenderConfig::default())
+                .unwrap();
+            assert!(
+                output.contains("first") && output.contains("second"),
+                "{:?} lost the div: {:?}",
+                format,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::traits::{ConversionError, ParseLimits};
+        use std::time::Duration;
+
+        let handler = DjotHandler::new();
+        let limited = |limits| ParseConfig {
+            limits,
+            ..Default::default()
+        };
+        // Divs nest by fence length, not by indentation or markers
+        let divs: String = (0..8).rev().map(|i| ":".repeat(3 + i) + "\n").collect();
+        let shallow = limited(ParseLimits {
+            max_depth: Some(8),
+            ..Default::default()
+        });
+        assert!(handler.parse(&divs, &shallow).is_ok());
+        let shallower = limited(ParseLimits {
+            max_depth: Some(7),
+            ..Default::default()
+        });
+        assert!(matches!(
+            handler.parse(&divs, &shallower),
+            Err(ConversionError::LimitExceeded(_))
+        ));
+
+        let timed_out = limited(ParseLimits {
+            timeout: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        assert!(matches!(
+            handler.parse("text", &timed_out),
+            Err(ConversionError::LimitExceeded(_))
+        ));
+    }
 }
@@ -2,13 +2,23 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Markdown format handler using comrak
 
+use super::{comment_text, leading_markers, line_nesting};
 use crate::ast::{
-    AdmonitionType, Block, Document, DocumentMeta, Inline, LinkType, ListItem, ListKind,
-    SourceFormat, TableCell, TableRow,
+    AdmonitionType, Block, ColumnAlignment, ColumnSpec, DefinitionItem, Document, Inline, LinkType,
+    ListItem, ListKind, MathNotation, SourceFormat, TableCell, TableRow,
 };
-use crate::traits::{FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result};
-use comrak::nodes::{AstNode, NodeValue};
+use crate::clipboard::parse_html;
+use crate::edit::children_mut;
+use crate::frontmatter::FrontMatter;
+use crate::normalize::block_lists_mut;
+use crate::traits::{
+    FormatHandler, HeadingStyle, MarkdownStyle, ParseConfig, ParseLimits, Parser, RenderConfig,
+    Renderer, Result,
+};
+use comrak::arena_tree::NodeEdge;
+use comrak::nodes::{AstNode, NodeValue, TableAlignment};
 use comrak::{parse_document, Arena, Options};
+use std::collections::HashSet;
 
 /// Markdown format handler using comrak (GFM-compatible)
 pub struct MarkdownHandler;
@@ -26,6 +36,7 @@ fn comrak_options() -> Options<'static> {
         options.extension.tasklist = true;
         options.extension.footnotes = true;
         options.extension.description_lists = true;
+        options.extension.math_dollars = true;
         options.parse.smart = true;
         options
     }
@@ -43,29 +54,315 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_timeout(SourceFormat::Markdown)?;
+        config.limits.check_source(input, source_depth)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
+        let (front, body) = split_front_matter(input, config.front_matter_delimiter.as_deref());
+
         let arena = Arena::new();
         let options = Self::comrak_options();
-        let root = parse_document(&arena, input, &options);
+        let root = parse_document(&arena, body, &options);
+        check_depth(root, &config.limits)?;
 
-        let content = parse_children(root);
+        let mut content = parse_children(root);
+        drop_unreachable_footnotes(&mut content);
+        if config.parse_embedded_html {
+            parse_embedded_html(&mut content);
+        }
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::Markdown,
-            meta: DocumentMeta::default(),
+            meta: front.map(FrontMatter::into_meta).unwrap_or_default(),
             content,
             raw_source: if config.preserve_raw_source {
                 Some(input.to_string())
             } else {
                 None
             },
-        })
+        }))
+    }
+}
+
+/// How deeply Markdown source nests, which comrak recurses into once per
+/// level when it resolves footnotes: quotes, lists and indented blocks by
+/// line, and emphasis and brackets by character within a paragraph
+fn source_depth(input: &str) -> usize {
+    let lines = line_nesting(input, |line| {
+        let quotes = line
+            .bytes()
+            .take_while(|b| matches!(b, b'>' | b' ' | b'\t'))
+            .filter(|&b| b == b'>')
+            .count();
+        let rest = line.trim_start_matches(['>', ' ', '\t']);
+        quotes + leading_markers(rest, &["-", "*", "+"])
+    });
+
+    let bytes = input.as_bytes();
+    let space = |b: Option<&u8>| b.is_none_or(|b| b.is_ascii_whitespace());
+    let mut open = 0usize;
+    let mut deepest = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'\n' if input[i + 1..].trim_start_matches('\r').starts_with('\n') => open = 0,
+            b'[' => open += 1,
+            b']' => open = open.saturating_sub(1),
+            delimiter @ (b'*' | b'_') => {
+                let start = i;
+                while bytes.get(i + 1) == Some(&delimiter) {
+                    i += 1;
+                }
+                let run = i + 1 - start;
+                let before = start.checked_sub(1).and_then(|j| bytes.get(j));
+                // A run can open as many emphases as it is long
+                if space(before) && !space(bytes.get(i + 1)) {
+                    open += run;
+                } else if !space(before) {
+                    open = open.saturating_sub(run);
+                }
+            }
+            _ => {}
+        }
+        deepest = deepest.max(open);
+        i += 1;
+    }
+    lines.max(1 + deepest)
+}
+
+/// Check how deeply comrak's tree nests before it is converted, which
+/// recurses once per level
+fn check_depth<'a>(root: &'a AstNode<'a>, limits: &ParseLimits) -> Result<()> {
+    if limits.max_depth.is_none() {
+        return Ok(());
+    }
+    // List items and table rows only group what is in them
+    let nests = |node: &'a AstNode<'a>| {
+        !matches!(
+            node.data.borrow().value,
+            NodeValue::Document
+                | NodeValue::Item(_)
+                | NodeValue::TaskItem(_)
+                | NodeValue::TableRow(_)
+        )
+    };
+    let mut depth = 0;
+    for edge in root.traverse() {
+        match edge {
+            NodeEdge::Start(node) if nests(node) => {
+                depth += 1;
+                limits.check_depth(depth)?;
+            }
+            NodeEdge::End(node) if nests(node) => depth -= 1,
+            _ => {}
+        }
     }
+    Ok(())
+}
+
+/// Replace raw HTML with what [`parse_html`] reads from it
+///
+/// comrak keeps each HTML block whole, but splits inline HTML at every tag,
+/// so only inline HTML complete in itself, such as `<br>` or an `<img>`, is
+/// read; an opening tag alone reads as nothing and stays raw.
+fn parse_embedded_html(blocks: &mut Vec<Block>) {
+    let mut parsed = Vec::with_capacity(blocks.len());
+    for mut block in blocks.drain(..) {
+        if let Block::Raw { content, .. } = &block {
+            let html = parse_html(content).content;
+            if !html.is_empty() {
+                parsed.extend(html);
+                continue;
+            }
+        }
+        for list in block_lists_mut(&mut block) {
+            parse_embedded_html(list);
+        }
+        for group in block.inline_groups_mut() {
+            parse_inline_html(group);
+        }
+        parsed.push(block);
+    }
+    *blocks = parsed;
+}
+
+fn parse_inline_html(inlines: &mut Vec<Inline>) {
+    let mut parsed = Vec::with_capacity(inlines.len());
+    for mut inline in inlines.drain(..) {
+        if let Inline::RawInline { content, .. } = &mut inline {
+            // A lone opening or closing tag parses to nothing or to empty
+            // formatting, and is better left as it is
+            if let [Block::Paragraph { content, .. }] = parse_html(content).content.as_mut_slice() {
+                let complete = !content.is_empty()
+                    && content
+                        .iter_mut()
+                        .all(|inline| children_mut(inline).is_none_or(|c| !c.is_empty()));
+                if complete {
+                    parsed.append(content);
+                    continue;
+                }
+            }
+        }
+        if let Some(children) = children_mut(&mut inline) {
+            parse_inline_html(children);
+        }
+        parsed.push(inline);
+    }
+    *inlines = parsed;
+}
+
+/// Split YAML (`---`) or TOML (`+++`) front matter from the start of `input`
+///
+/// Only a block that parses as a mapping counts, so a document that merely
+/// opens with a thematic break is left alone.
+fn split_front_matter<'a>(
+    input: &'a str,
+    delimiter: Option<&str>,
+) -> (Option<FrontMatter>, &'a str) {
+    let delimiters = match delimiter {
+        Some(delimiter) => vec![delimiter],
+        None => vec!["---", "+++"],
+    };
+    for delimiter in delimiters {
+        let Some(rest) = input.strip_prefix(delimiter).and_then(|rest| {
+            rest.strip_prefix('\n')
+                .or_else(|| rest.strip_prefix("\r\n"))
+        }) else {
+            continue;
+        };
+
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == delimiter {
+                let parsed = if delimiter == "+++" {
+                    FrontMatter::from_toml(&rest[..offset])
+                } else {
+                    FrontMatter::from_yaml(&rest[..offset])
+                };
+                return match parsed {
+                    Ok(front) => (Some(front), &rest[offset + line.len()..]),
+                    Err(_) => (None, input),
+                };
+            }
+            offset += line.len();
+        }
+    }
+    (None, input)
+}
+
+/// Length of the front matter starting `input`, or 0 if there is none
+pub(crate) fn front_matter_len(input: &str, delimiter: Option<&str>) -> usize {
+    input.len() - split_front_matter(input, delimiter).1.len()
+}
+
+/// Drop footnote definitions that are only referenced from definitions
+/// comrak has already dropped as unreferenced
+///
+/// Without this the next parse of the rendered output drops them instead,
+/// so a parse/render cycle would not settle.
+fn drop_unreachable_footnotes(blocks: &mut Vec<Block>) {
+    fn collect_refs<'a>(block: &'a Block, refs: &mut Vec<&'a str>) {
+        fn inline_refs<'a>(inline: &'a Inline, refs: &mut Vec<&'a str>) {
+            if let Inline::FootnoteRef { label } = inline {
+                refs.push(label);
+            }
+            for child in inline.children() {
+                inline_refs(child, refs);
+            }
+        }
+        for group in block.inline_groups() {
+            for inline in group {
+                inline_refs(inline, refs);
+            }
+        }
+        for child in block.child_blocks() {
+            collect_refs(child, refs);
+        }
+    }
+
+    let mut pending = Vec::new();
+    for block in blocks.iter() {
+        if !matches!(block, Block::FootnoteDefinition { .. }) {
+            collect_refs(block, &mut pending);
+        }
+    }
+    let mut reachable = HashSet::new();
+    while let Some(label) = pending.pop() {
+        if !reachable.insert(label.to_string()) {
+            continue;
+        }
+        for block in blocks.iter() {
+            if matches!(block, Block::FootnoteDefinition { label: l, .. } if l == label) {
+                collect_refs(block, &mut pending);
+            }
+        }
+    }
+    blocks.retain(|block| match block {
+        Block::FootnoteDefinition { label, .. } => reachable.contains(label),
+        _ => true,
+    });
 }
 
 fn parse_children<'a>(node: &'a AstNode<'a>) -> Vec<Block> {
-    node.children()
-        .filter_map(|child| parse_node(child))
-        .collect()
+    group_details(
+        node.children()
+            .filter_map(|child| parse_node(child))
+            .collect(),
+    )
+}
+
+/// Gather the blocks between a `<details>` HTML block and its `</details>`
+/// into a [`Block::Details`]
+///
+/// A blank line ends an HTML block, so the usual GFM layout, with Markdown
+/// between the tags, reaches us as the opening tags, the content and the
+/// closing tag in turn. A `<details>` closed within its own HTML block is
+/// read as HTML. An unclosed one is left as it is.
+fn group_details(blocks: Vec<Block>) -> Vec<Block> {
+    let mut out: Vec<Block> = Vec::with_capacity(blocks.len());
+    // Each details still open: where it is in `out`, and the HTML it came from
+    let mut open: Vec<(usize, Block)> = Vec::new();
+    for block in blocks {
+        if let Block::Raw { content, .. } = &block {
+            if content.trim().eq_ignore_ascii_case("</details>") {
+                if let Some((start, _)) = open.pop() {
+                    let inner: Vec<Block> = out.drain(start + 1..).collect();
+                    if let Some(Block::Details { content, .. }) = out.last_mut() {
+                        content.extend(inner);
+                    }
+                    continue;
+                }
+            } else if let Some((details, closed)) = parse_details_html(content) {
+                if !closed {
+                    open.push((out.len(), block));
+                }
+                out.push(details);
+                continue;
+            }
+        }
+        out.push(block);
+    }
+    for (start, raw) in open {
+        out[start] = raw;
+    }
+    out
+}
+
+/// The details an HTML block opens, and whether it closes it too
+fn parse_details_html(html: &str) -> Option<(Block, bool)> {
+    let tag = html.trim_start().get(..8)?;
+    if !tag.eq_ignore_ascii_case("<details") {
+        return None;
+    }
+    let mut parsed = parse_html(html).content;
+    if parsed.len() != 1 || !matches!(parsed[0], Block::Details { .. }) {
+        return None;
+    }
+    let closed = html.to_ascii_lowercase().contains("</details>");
+    Some((parsed.remove(0), closed))
 }
 
 fn parse_node<'a>(node: &'a AstNode<'a>) -> Option<Block> {
@@ -74,17 +371,36 @@ fn parse_node<'a>(node: &'a AstNode<'a>) -> Option<Block> {
     match &data.value {
         NodeValue::Document => None,
 
-        NodeValue::Paragraph => Some(Block::Paragraph {
-            content: parse_inlines(node),
-            span: None,
-        }),
+        NodeValue::Paragraph => {
+            // A paragraph of nothing but `$$` math is a math block
+            if let Some(child) = node.first_child().filter(|c| c.next_sibling().is_none()) {
+                if let NodeValue::Math(math) = &child.data.borrow().value {
+                    if math.display_math {
+                        return Some(Block::MathBlock {
+                            content: math.literal.trim().to_string(),
+                            notation: MathNotation::LaTeX,
+                            span: None,
+                        });
+                    }
+                }
+            }
+            Some(Block::Paragraph {
+                content: parse_inlines(node),
+                span: None,
+            })
+        }
 
-        NodeValue::Heading(heading) => Some(Block::Heading {
-            level: heading.level,
-            content: parse_inlines(node),
-            id: None,
-            span: None,
-        }),
+        NodeValue::Heading(heading) => {
+            let mut content = parse_inlines(node);
+            let (id, classes) = take_heading_attributes(&mut content);
+            Some(Block::Heading {
+                level: heading.level,
+                content,
+                id,
+                classes,
+                span: None,
+            })
+        }
 
         NodeValue::CodeBlock(code) => Some(Block::CodeBlock {
             language: if code.info.is_empty() {
@@ -98,12 +414,20 @@ fn parse_node<'a>(node: &'a AstNode<'a>) -> Option<Block> {
             span: None,
         }),
 
-        NodeValue::BlockQuote => Some(Block::BlockQuote {
-            content: parse_children(node),
-            attribution: None,
-            admonition: detect_admonition(node),
-            span: None,
-        }),
+        NodeValue::BlockQuote => {
+            let mut content = parse_children(node);
+            let admonition = take_alert(&mut content);
+            let attribution = match admonition {
+                Some(_) => None,
+                None => super::split_attribution(&mut content),
+            };
+            Some(Block::BlockQuote {
+                content,
+                attribution,
+                admonition,
+                span: None,
+            })
+        }
 
         NodeValue::List(list) => {
             let kind = if list.list_type == comrak::nodes::ListType::Ordered {
@@ -153,10 +477,22 @@ fn parse_node<'a>(node: &'a AstNode<'a>) -> Option<Block> {
 
         NodeValue::ThematicBreak => Some(Block::ThematicBreak { span: None }),
 
-        NodeValue::Table(_) => {
+        NodeValue::Table(table) => {
             let mut header = None;
             let mut body = Vec::new();
-            let columns = Vec::new(); // Would need to extract from table alignments
+            let columns = table
+                .alignments
+                .iter()
+                .map(|alignment| ColumnSpec {
+                    alignment: match alignment {
+                        TableAlignment::Left => ColumnAlignment::Left,
+                        TableAlignment::Center => ColumnAlignment::Center,
+                        TableAlignment::Right => ColumnAlignment::Right,
+                        TableAlignment::None => ColumnAlignment::Default,
+                    },
+                    width: None,
+                })
+                .collect();
 
             for child in node.children() {
                 match child.data.borrow().value {
@@ -207,14 +543,66 @@ fn parse_node<'a>(node: &'a AstNode<'a>) -> Option<Block> {
             span: None,
         }),
 
+        // Each item holds a term, whose paragraph is its text, and details
+        NodeValue::DescriptionList => Some(Block::DefinitionList {
+            items: node
+                .children()
+                .map(|item| {
+                    let mut definition = DefinitionItem {
+                        term: Vec::new(),
+                        definitions: Vec::new(),
+                    };
+                    for part in item.children() {
+                        match part.data.borrow().value {
+                            NodeValue::DescriptionTerm => {
+                                definition.term = part.children().flat_map(parse_inlines).collect();
+                            }
+                            NodeValue::DescriptionDetails => {
+                                definition.definitions.push(parse_children(part));
+                            }
+                            _ => {}
+                        }
+                    }
+                    definition
+                })
+                .collect(),
+            span: None,
+        }),
+
         _ => None,
     }
 }
 
 fn parse_inlines<'a>(node: &'a AstNode<'a>) -> Vec<Inline> {
-    node.children()
-        .filter_map(|child| parse_inline(child))
-        .collect()
+    group_kbd(
+        node.children()
+            .filter_map(|child| parse_inline(child))
+            .collect(),
+    )
+}
+
+/// Read text between `<kbd>` and `</kbd>` as keyboard input
+///
+/// comrak hands each tag over as its own raw inline.
+fn group_kbd(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut grouped = Vec::with_capacity(inlines.len());
+    let mut i = 0;
+    while i < inlines.len() {
+        if let [Inline::RawInline { content: open, .. }, Inline::Text { content }, Inline::RawInline { content: close, .. }, ..] =
+            &inlines[i..]
+        {
+            if open.eq_ignore_ascii_case("<kbd>") && close.eq_ignore_ascii_case("</kbd>") {
+                grouped.push(Inline::Keyboard {
+                    content: content.clone(),
+                });
+                i += 3;
+                continue;
+            }
+        }
+        grouped.push(inlines[i].clone());
+        i += 1;
+    }
+    grouped
 }
 
 fn parse_inline<'a>(node: &'a AstNode<'a>) -> Option<Inline> {
@@ -279,6 +667,11 @@ fn parse_inline<'a>(node: &'a AstNode<'a>) -> Option<Inline> {
             height: None,
         }),
 
+        NodeValue::Math(math) => Some(Inline::Math {
+            content: math.literal.trim().to_string(),
+            notation: MathNotation::LaTeX,
+        }),
+
         NodeValue::FootnoteReference(fr) => Some(Inline::FootnoteRef {
             label: fr.name.clone(),
         }),
@@ -292,12 +685,74 @@ fn parse_inline<'a>(node: &'a AstNode<'a>) -> Option<Inline> {
     }
 }
 
-fn detect_admonition<'a>(_node: &'a AstNode<'a>) -> Option<AdmonitionType> {
-    // GFM doesn't have native admonitions, but we could detect patterns like:
-    // > [!NOTE]
-    // > [!WARNING]
-    // This is a simplified implementation
-    None
+/// Take a trailing `{#id .class}` attribute block off a heading's text,
+/// as Pandoc writes them
+fn take_heading_attributes(content: &mut Vec<Inline>) -> (Option<String>, Vec<String>) {
+    let Some(Inline::Text { content: text }) = content.last_mut() else {
+        return (None, Vec::new());
+    };
+    let Some((before, attrs)) = text
+        .strip_suffix('}')
+        .and_then(|t| t.rsplit_once('{'))
+        .filter(|(before, _)| before.is_empty() || before.ends_with(' '))
+    else {
+        return (None, Vec::new());
+    };
+    let mut id = None;
+    let mut classes = Vec::new();
+    for attr in attrs.split_whitespace() {
+        match (attr.strip_prefix('#'), attr.strip_prefix('.')) {
+            (Some(name), _) if !name.is_empty() => id = Some(name.to_string()),
+            (_, Some(name)) if !name.is_empty() => classes.push(name.to_string()),
+            _ => return (None, Vec::new()),
+        }
+    }
+    if id.is_none() && classes.is_empty() {
+        return (None, Vec::new());
+    }
+
+    *text = before.trim_end().to_string();
+    if text.is_empty() {
+        content.pop();
+    }
+    (id, classes)
+}
+
+/// Take a GitHub alert marker, such as `[!WARNING]` on the first line of a
+/// quote, off the quote's content
+fn take_alert(content: &mut Vec<Block>) -> Option<AdmonitionType> {
+    let Some(Block::Paragraph {
+        content: inlines, ..
+    }) = content.first_mut()
+    else {
+        return None;
+    };
+    let line_end = inlines
+        .iter()
+        .position(|i| matches!(i, Inline::SoftBreak | Inline::LineBreak))
+        .unwrap_or(inlines.len());
+    let mut marker = String::new();
+    for inline in &inlines[..line_end] {
+        let Inline::Text { content } = inline else {
+            return None;
+        };
+        marker.push_str(content);
+    }
+    let kind = marker.trim().strip_prefix("[!")?.strip_suffix(']')?;
+    let admonition = match kind.to_ascii_uppercase().as_str() {
+        "NOTE" => AdmonitionType::Note,
+        "TIP" => AdmonitionType::Tip,
+        "IMPORTANT" => AdmonitionType::Important,
+        "WARNING" => AdmonitionType::Warning,
+        "CAUTION" => AdmonitionType::Caution,
+        _ => return None,
+    };
+
+    inlines.drain(..inlines.len().min(line_end + 1));
+    if inlines.is_empty() {
+        content.remove(0);
+    }
+    Some(admonition)
 }
 
 impl Renderer for MarkdownHandler {
@@ -305,37 +760,66 @@ fn format(&self) -> SourceFormat {
         SourceFormat::Markdown
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::Markdown);
         let mut output = String::new();
-
-        for (i, block) in doc.content.iter().enumerate() {
-            if i > 0 {
-                output.push_str("\n\n");
-            }
-            render_block(&mut output, block, 0);
+        let front = FrontMatter::from_meta(&doc.meta);
+        if !front.is_empty() {
+            output.push_str("---\n");
+            output.push_str(&front.to_yaml());
+            output.push_str("---\n\n");
         }
+        output.push_str(&render_blocks(&doc.content, &config.markdown_style()));
 
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
-fn render_block(output: &mut String, block: &Block, indent: usize) {
+fn render_block(output: &mut String, block: &Block, indent: usize, style: &MarkdownStyle) {
     let prefix = "  ".repeat(indent);
 
     match block {
         Block::Paragraph { content, .. } => {
             output.push_str(&prefix);
             for inline in content {
-                render_inline(output, inline);
+                render_inline(output, inline, style);
             }
         }
 
-        Block::Heading { level, content, .. } => {
-            output.push_str(&prefix);
-            output.push_str(&"#".repeat(*level as usize));
-            output.push(' ');
+        Block::Heading {
+            level,
+            content,
+            id,
+            classes,
+            ..
+        } => {
+            let mut text = String::new();
             for inline in content {
-                render_inline(output, inline);
+                render_inline(&mut text, inline, style);
+            }
+            // GitHub shows attributes as text, so they are only written for
+            // classes, which have nowhere else to go
+            if !classes.is_empty() {
+                let mut attrs: Vec<String> = id.iter().map(|id| format!("#{}", id)).collect();
+                attrs.extend(classes.iter().map(|c| format!(".{}", c)));
+                text.push_str(&format!(" {{{}}}", attrs.join(" ")));
+            }
+
+            output.push_str(&prefix);
+            match style.heading {
+                HeadingStyle::Setext if *level <= 2 && !text.trim().is_empty() => {
+                    let width = text.lines().map(|l| l.chars().count()).max();
+                    let underline = if *level == 1 { "=" } else { "-" };
+                    output.push_str(&text);
+                    output.push('\n');
+                    output.push_str(&prefix);
+                    output.push_str(&underline.repeat(width.unwrap_or(0).max(3)));
+                }
+                _ => {
+                    output.push_str(&"#".repeat(*level as usize));
+                    output.push(' ');
+                    output.push_str(&text);
+                }
             }
         }
 
@@ -343,7 +827,7 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
             language, content, ..
         } => {
             output.push_str(&prefix);
-            output.push_str("```");
+            output.push_str(style.code_fence);
             if let Some(lang) = language {
                 output.push_str(lang);
             }
@@ -354,51 +838,110 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                 output.push('\n');
             }
             output.push_str(&prefix);
-            output.push_str("```");
+            output.push_str(style.code_fence);
         }
 
-        Block::BlockQuote { content, .. } => {
-            for block in content {
+        Block::BlockQuote {
+            content,
+            attribution,
+            admonition,
+            ..
+        } => {
+            let content = super::quote_with_attribution(content, attribution.as_ref());
+            let mut body = render_blocks(&content, style);
+            // GitHub alerts; there is no danger alert, caution is the closest
+            if let Some(admonition) = admonition {
+                let kind = match admonition {
+                    AdmonitionType::Note | AdmonitionType::Custom => "NOTE",
+                    AdmonitionType::Tip => "TIP",
+                    AdmonitionType::Important => "IMPORTANT",
+                    AdmonitionType::Warning => "WARNING",
+                    AdmonitionType::Caution | AdmonitionType::Danger => "CAUTION",
+                };
+                body = format!("[!{}]\n{}", kind, body);
+            }
+            for (i, line) in body.lines().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
                 output.push_str(&prefix);
-                output.push_str("> ");
-                render_block(output, block, 0);
-                output.push('\n');
+                output.push('>');
+                if !line.is_empty() {
+                    output.push(' ');
+                    output.push_str(line);
+                }
             }
         }
 
-        Block::List { kind, items, .. } => {
+        Block::List {
+            kind, items, start, ..
+        } => {
             for (i, item) in items.iter().enumerate() {
-                output.push_str(&prefix);
-                match kind {
-                    ListKind::Bullet => output.push_str("- "),
-                    ListKind::Ordered => {
-                        output.push_str(&format!("{}. ", i + 1));
-                    }
-                    ListKind::Task => {
-                        let checked = item.checked.unwrap_or(false);
-                        output.push_str(if checked { "- [x] " } else { "- [ ] " });
-                    }
+                if i > 0 {
+                    output.push('\n');
                 }
-                for block in &item.content {
-                    render_block(output, block, 0);
-                }
-                output.push('\n');
+                let marker = match kind {
+                    ListKind::Bullet => format!("{} ", style.bullet),
+                    ListKind::Ordered => format!("{}. ", start.unwrap_or(1) + i as u32),
+                    ListKind::Task if item.checked == Some(true) => {
+                        format!("{} [x] ", style.bullet)
+                    }
+                    ListKind::Task => format!("{} [ ] ", style.bullet),
+                };
+                output.push_str(&prefix);
+                output.push_str(&marker);
+
+                // Continuation lines line up with the text after the marker
+                let width = if *kind == ListKind::Task {
+                    2
+                } else {
+                    marker.len()
+                };
+                let hang = format!("{}{}", prefix, " ".repeat(width));
+                push_hanging(output, &render_blocks(&item.content, style), &hang);
             }
         }
 
         Block::ThematicBreak { .. } => {
             output.push_str(&prefix);
-            output.push_str("---");
+            output.push_str(style.thematic_break);
         }
 
-        Block::Table { header, body, .. } => {
+        Block::Table {
+            columns,
+            header,
+            body,
+            ..
+        } => {
+            // Pipe tables must start with a header row; give a table without
+            // one an empty header
+            let empty_header;
+            let header = match header {
+                Some(h) => Some(h),
+                None if body.is_empty() => None,
+                None => {
+                    let width = body.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+                    empty_header = TableRow {
+                        cells: vec![
+                            TableCell {
+                                content: Vec::new(),
+                                colspan: 1,
+                                rowspan: 1,
+                                alignment: None,
+                            };
+                            width
+                        ],
+                    };
+                    Some(&empty_header)
+                }
+            };
             if let Some(h) = header {
                 output.push_str(&prefix);
                 output.push('|');
                 for cell in &h.cells {
                     output.push(' ');
                     for block in &cell.content {
-                        render_block(output, block, 0);
+                        render_block(output, block, 0, style);
                     }
                     output.push_str(" |");
                 }
@@ -406,10 +949,7 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
 
                 // Separator
                 output.push_str(&prefix);
-                output.push('|');
-                for _ in &h.cells {
-                    output.push_str(" --- |");
-                }
+                output.push_str(&super::pipe_table_rule(columns, h.cells.len()));
                 output.push('\n');
             }
 
@@ -419,54 +959,163 @@ fn render_block(output: &mut String, block: &Block, indent: usize) {
                 for cell in &row.cells {
                     output.push(' ');
                     for block in &cell.content {
-                        render_block(output, block, 0);
+                        render_block(output, block, 0, style);
                     }
                     output.push_str(" |");
                 }
                 output.push('\n');
             }
+            if output.ends_with('\n') {
+                output.pop();
+            }
+        }
+
+        Block::MathBlock { content, .. } => {
+            output.push_str(&prefix);
+            output.push_str("$$\n");
+            for line in content.lines() {
+                output.push_str(&prefix);
+                output.push_str(line);
+                output.push('\n');
+            }
+            output.push_str(&prefix);
+            output.push_str("$$");
         }
 
         Block::Raw { content, .. } => {
-            output.push_str(content);
+            output.push_str(content.trim_end_matches('\n'));
+        }
+
+        Block::Comment { content, .. } => {
+            output.push_str(&prefix);
+            output.push_str(&format!("<!-- {} -->", comment_text(content, &["--"])));
+        }
+
+        // Each definition follows its term after a blank line
+        Block::DefinitionList { items, .. } => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push_str("\n\n");
+                }
+                output.push_str(&prefix);
+                for inline in &item.term {
+                    render_inline(output, inline, style);
+                }
+                for definition in &item.definitions {
+                    output.push_str("\n\n");
+                    output.push_str(&prefix);
+                    output.push_str(": ");
+                    let hang = format!("{}  ", prefix);
+                    push_hanging(output, &render_blocks(definition, style), &hang);
+                }
+            }
+        }
+
+        // Markdown has no containers; their content stands on its own
+        Block::Container { content, .. } => {
+            output.push_str(&prefix);
+            push_hanging(output, &render_blocks(content, style), &prefix);
+        }
+
+        // Markdown is not read inside HTML blocks, so the summary is HTML
+        // and blank lines set the content apart
+        Block::Details {
+            summary,
+            content,
+            open,
+            ..
+        } => {
+            let mut details = String::from(if *open { "<details open>" } else { "<details>" });
+            if let Some(summary) = summary {
+                details.push_str(&format!(
+                    "\n<summary>{}</summary>",
+                    crate::html::render_inlines(summary)
+                ));
+            }
+            let body = render_blocks(content, style);
+            if !body.is_empty() {
+                details.push_str("\n\n");
+                details.push_str(&body);
+            }
+            details.push_str("\n\n</details>");
+            output.push_str(&prefix);
+            push_hanging(output, &details, &prefix);
         }
 
         Block::FootnoteDefinition { label, content, .. } => {
             output.push_str(&prefix);
             output.push_str(&format!("[^{}]: ", label));
-            for block in content {
-                render_block(output, block, indent + 1);
-            }
+            let hang = format!("{}    ", prefix);
+            push_hanging(output, &render_blocks(content, style), &hang);
         }
 
         _ => {}
     }
 }
 
-fn render_inline(output: &mut String, inline: &Inline) {
+/// Render blocks separated by blank lines, skipping any that produce no output
+fn render_blocks(blocks: &[Block], style: &MarkdownStyle) -> String {
+    let mut output = String::new();
+    let mut previous: Option<&Block> = None;
+    for block in blocks {
+        let mut rendered = String::new();
+        render_block(&mut rendered, block, 0, style);
+        if rendered.is_empty() {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        // Adjacent lists would merge into one when parsed back
+        if matches!(
+            (previous, block),
+            (Some(Block::List { .. }), Block::List { .. })
+        ) {
+            output.push_str("<!-- -->\n\n");
+        }
+        output.push_str(&rendered);
+        previous = Some(block);
+    }
+    output
+}
+
+/// Append `text`, indenting every non-empty line after the first by `indent`
+fn push_hanging(output: &mut String, text: &str, indent: &str) {
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            output.push('\n');
+            if !line.is_empty() {
+                output.push_str(indent);
+            }
+        }
+        output.push_str(line);
+    }
+}
+
+fn render_inline(output: &mut String, inline: &Inline, style: &MarkdownStyle) {
     match inline {
         Inline::Text { content } => output.push_str(content),
 
         Inline::Emphasis { content } => {
-            output.push('*');
+            output.push_str(style.emphasis);
             for i in content {
-                render_inline(output, i);
+                render_inline(output, i, style);
             }
-            output.push('*');
+            output.push_str(style.emphasis);
         }
 
         Inline::Strong { content } => {
-            output.push_str("**");
+            output.push_str(style.strong);
             for i in content {
-                render_inline(output, i);
+                render_inline(output, i, style);
             }
-            output.push_str("**");
+            output.push_str(style.strong);
         }
 
         Inline::Strikethrough { content } => {
             output.push_str("~~");
             for i in content {
-                render_inline(output, i);
+                render_inline(output, i, style);
             }
             output.push_str("~~");
         }
@@ -477,6 +1126,17 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('`');
         }
 
+        // Keys are written as inline HTML, as GitHub documents them
+        Inline::Keyboard { content } => {
+            output.push_str(&format!("<kbd>{}</kbd>", content));
+        }
+
+        Inline::Button { .. } | Inline::Menu { .. } => {
+            if let Some(plain) = super::plain_ui(inline) {
+                render_inline(output, &plain, style);
+            }
+        }
+
         Inline::Link {
             url,
             title,
@@ -485,7 +1145,7 @@ fn render_inline(output: &mut String, inline: &Inline) {
         } => {
             output.push('[');
             for i in content {
-                render_inline(output, i);
+                render_inline(output, i, style);
             }
             output.push_str("](");
             output.push_str(url);
@@ -512,18 +1172,30 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push_str(&format!("[^{}]", label));
         }
 
+        Inline::Math { content, .. } => {
+            output.push_str(&format!("${}$", content));
+        }
+
         Inline::LineBreak => {
-            output.push_str("  \n");
+            output.push_str(style.hard_break);
         }
 
         Inline::SoftBreak => {
             output.push('\n');
         }
 
+        Inline::NonBreakingSpace => {
+            output.push_str("&nbsp;");
+        }
+
         Inline::RawInline { content, .. } => {
             output.push_str(content);
         }
 
+        Inline::Comment { content } => {
+            output.push_str(&format!("<!-- {} -->", comment_text(content, &["--"])));
+        }
+
         _ => {}
     }
 }
@@ -544,7 +1216,13 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "task_list"
                 | "table"
                 | "blockquote"
+                | "admonition"
                 | "footnote"
+                | "definition_list"
+                | "details"
+                | "kbd"
+                | "emoji"
+                | "nested_quote"
         )
     }
 
@@ -562,7 +1240,13 @@ fn supported_features(&self) -> &[&str] {
             "task_list",
             "table",
             "blockquote",
+            "admonition",
             "footnote",
+            "definition_list",
+            "details",
+            "kbd",
+            "emoji",
+            "nested_quote",
         ]
     }
 }
@@ -595,4 +1279,337 @@ fn test_parse_paragraph() {
 
         assert_eq!(doc.content.len(), 1);
     }
+
+    #[test]
+    fn test_front_matter() {
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse(
+                "---\ntitle: Notes\nauthor: [Ada, {name: Grace, email: grace@example.com}]\n\
+                 updated: 2026-02-03\ntags: [a, b]\n---\n\n# Body\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(doc.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(
+            doc.meta.authors[1].email.as_deref(),
+            Some("grace@example.com")
+        );
+        assert_eq!(doc.meta.modified.as_deref(), Some("2026-02-03"));
+        assert!(doc.meta.custom.contains_key("tags"));
+        assert_eq!(doc.content.len(), 1);
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(
+            "---\ntitle: Notes\nauthor:\n- Ada\n- Grace <grace@example.com>\nmodified: 2026-02-03\ntags:\n"
+        ));
+        assert!(output.ends_with("---\n\n# Body"));
+
+        let toml = handler
+            .parse(
+                "+++\ntitle = \"Notes\"\n+++\nText\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(toml.meta.title.as_deref(), Some("Notes"));
+
+        // A leading thematic break is not front matter
+        let doc = handler
+            .parse("---\n\nText\n\n---\n", &ParseConfig::default())
+            .unwrap();
+        assert!(doc.meta.title.is_none());
+        assert_eq!(doc.content.len(), 3);
+    }
+
+    #[test]
+    fn test_unreachable_footnotes() {
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse(
+                "[^a]: See [^z].\n\n[^z]: Only cited by a.\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert!(doc.content.is_empty());
+
+        let doc = handler
+            .parse(
+                "Text[^a].\n\n[^a]: See [^z].\n\n[^z]: Cited by a.\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(doc.content.len(), 3);
+    }
+
+    #[test]
+    fn test_style_profiles() {
+        use crate::traits::StyleProfile;
+
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse(
+                "- *a* **b**  \nc\n\n---\n\n```rust\nx\n```\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let render = |profile| {
+            let config = RenderConfig {
+                profile,
+                ..Default::default()
+            };
+            handler.render(&doc, &config).unwrap()
+        };
+
+        let plain = render(None);
+        assert_eq!(plain, "- *a* **b**  \n  c\n\n---\n\n```rust\nx\n```");
+        assert_eq!(render(Some(StyleProfile::Github)), plain);
+        assert_eq!(
+            render(Some(StyleProfile::Kramdown)),
+            "* _a_ **b**\\\\\n  c\n\n* * *\n\n~~~rust\nx\n~~~"
+        );
+        assert!(render(Some(StyleProfile::Pandoc)).starts_with("- *a* **b**\\\n  c\n\n-----"));
+    }
+
+    #[test]
+    fn test_parse_embedded_html() {
+        let source = "Intro<br>more <b>bold</b>\n\n\
+                      <table><tr><th>A</th><td>1</td></tr></table>\n\n\
+                      <details><summary>Why?</summary>Because.</details>\n\n<!-- note -->\n";
+        let handler = MarkdownHandler::new();
+
+        let raw = handler.parse(source, &ParseConfig::default()).unwrap();
+        assert!(matches!(raw.content[1], Block::Raw { .. }));
+
+        let config = ParseConfig {
+            parse_embedded_html: true,
+            ..Default::default()
+        };
+        let doc = handler.parse(source, &config).unwrap();
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(content[1], Inline::LineBreak));
+        assert!(matches!(content[3], Inline::RawInline { .. }));
+        assert_eq!(content.len(), 6);
+        assert!(matches!(doc.content[1], Block::Table { .. }));
+        assert!(matches!(doc.content.last(), Some(Block::Raw { .. })));
+
+        let adoc = crate::formats::AsciidocHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert!(adoc.contains("|===\n"));
+        assert!(adoc.contains("Because."));
+    }
+
+    #[test]
+    fn test_details() {
+        let handler = MarkdownHandler::new();
+        let source = "<details open>\n<summary>Why <b>not</b>?</summary>\n\n\
+                      Because **this**.\n\n\
+                      <details>\n<summary>More</summary>\n\nNested\n\n</details>\n\n\
+                      </details>\n\n<details><summary>Inline</summary>Short</details>";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        assert_eq!(doc.content.len(), 2);
+
+        let Block::Details {
+            summary,
+            content,
+            open,
+            ..
+        } = &doc.content[0]
+        else {
+            panic!("expected details, got {:?}", doc.content[0]);
+        };
+        assert!(*open);
+        assert!(matches!(
+            summary.as_deref(),
+            Some([_, Inline::Strong { .. }, _])
+        ));
+        assert!(matches!(content[0], Block::Paragraph { .. }));
+        assert!(matches!(content[1], Block::Details { open: false, .. }));
+        assert!(matches!(&doc.content[1], Block::Details { content, .. } if content.len() == 1));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(
+            "<details open>\n<summary>Why <strong>not</strong>?</summary>\n\nBecause **this**."
+        ));
+        let reparsed = handler.parse(&output, &ParseConfig::default()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&reparsed.content).unwrap(),
+            serde_json::to_value(&doc.content).unwrap()
+        );
+
+        // An unclosed details is left alone
+        let doc = handler
+            .parse("<details>\n\nText\n", &ParseConfig::default())
+            .unwrap();
+        assert!(matches!(doc.content[0], Block::Raw { .. }));
+    }
+
+    #[test]
+    fn test_description_list() {
+        let handler = MarkdownHandler::new();
+        let source = "CPU\n\n: Processor\n\n  Fast.\n\nRAM *memory*\n\n: Volatile";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+
+        let Block::DefinitionList { items, .. } = &doc.content[0] else {
+            panic!("expected a description list, got {:?}", doc.content[0]);
+        };
+        assert_eq!(items.len(), 2);
+        assert!(matches!(&items[0].term[..], [Inline::Text { content }] if content == "CPU"));
+        assert_eq!(items[0].definitions[0].len(), 2);
+        assert!(matches!(items[1].term[1], Inline::Emphasis { .. }));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_kbd() {
+        let handler = MarkdownHandler::new();
+        let source = "Press <kbd>Ctrl+C</kbd> to copy.";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(&content[1], Inline::Keyboard { content } if content == "Ctrl+C"));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source);
+        let adoc = crate::formats::AsciidocHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert_eq!(adoc, "Press kbd:[Ctrl+C] to copy.");
+    }
+
+    #[test]
+    fn test_hard_break_style() {
+        use crate::traits::{HardBreak, StyleProfile};
+
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse("one  \ntwo\nthree&nbsp;four\n", &ParseConfig::default())
+            .unwrap();
+        let render = |hard_break, hard_breaks| {
+            let config = RenderConfig {
+                profile: Some(StyleProfile::Pandoc),
+                hard_break,
+                hard_breaks,
+                ..Default::default()
+            };
+            handler.render(&doc, &config).unwrap()
+        };
+
+        assert_eq!(render(None, false), "one\\\ntwo\nthree\u{a0}four");
+        assert_eq!(
+            render(Some(HardBreak::Spaces), false),
+            "one  \ntwo\nthree\u{a0}four"
+        );
+        assert_eq!(
+            render(Some(HardBreak::Backslash), true),
+            "one\\\ntwo\\\nthree\u{a0}four"
+        );
+    }
+
+    #[test]
+    fn test_heading_style() {
+        use crate::traits::HeadingStyle;
+
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse(
+                "# Title\n\n## Section\n\n### Sub\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let config = RenderConfig {
+            heading_style: Some(HeadingStyle::Setext),
+            ..Default::default()
+        };
+        let output = handler.render(&doc, &config).unwrap();
+        assert_eq!(output, "Title\n=====\n\nSection\n-------\n\n### Sub");
+        let reparsed = handler.parse(&output, &ParseConfig::default()).unwrap();
+        assert_eq!(
+            format!("{:?}", reparsed.content),
+            format!("{:?}", doc.content)
+        );
+    }
+
+    #[test]
+    fn test_quote_attribution() {
+        let handler = MarkdownHandler::new();
+        let doc = handler
+            .parse(
+                "> Simplicity is prerequisite for reliability.\n> — *Edsger Dijkstra*\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let Block::BlockQuote {
+            content,
+            attribution: Some(attribution),
+            ..
+        } = &doc.content[0]
+        else {
+            panic!("expected an attributed quote, got {:?}", doc.content[0]);
+        };
+        assert_eq!(content.len(), 1);
+        assert!(matches!(attribution.as_slice(), [Inline::Emphasis { .. }]));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output,
+            "> Simplicity is prerequisite for reliability.\n>\n> — *Edsger Dijkstra*"
+        );
+
+        // A quote that is only a dashed line keeps it as content
+        let doc = handler
+            .parse("> -- not an attribution\n", &ParseConfig::default())
+            .unwrap();
+        assert!(matches!(
+            doc.content[0],
+            Block::BlockQuote {
+                attribution: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_alerts() {
+        let handler = MarkdownHandler::new();
+        let source = "> [!WARNING]\n> Mind the gap.\n>\n> > [!tip]\n> > Nested.";
+        let doc = handler.parse(source, &ParseConfig::default()).unwrap();
+        let Block::BlockQuote {
+            content,
+            admonition: Some(AdmonitionType::Warning),
+            ..
+        } = &doc.content[0]
+        else {
+            panic!("expected a warning, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(&content[0], Block::Paragraph { content, .. }
+            if matches!(content.as_slice(), [Inline::Text { content }] if content == "Mind the gap.")));
+        assert!(matches!(
+            content[1],
+            Block::BlockQuote {
+                admonition: Some(AdmonitionType::Tip),
+                ..
+            }
+        ));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, source.replace("[!tip]", "[!TIP]"));
+
+        // The marker has to be alone on its line
+        let doc = handler
+            .parse("> [!NOTE] inline\n", &ParseConfig::default())
+            .unwrap();
+        assert!(matches!(
+            doc.content[0],
+            Block::BlockQuote {
+                admonition: None,
+                ..
+            }
+        ));
+    }
 }
@@ -2,6 +2,7 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Format handlers for each supported format
 
+pub mod capabilities;
 pub mod djot;
 pub mod markdown;
 pub mod orgmode;
@@ -10,8 +11,10 @@
 // FD-S01, FD-S02, FD-S03: SHOULD requirement implementations
 pub mod asciidoc;
 pub mod rst;
+mod sphinx;
 pub mod typst;
 
+pub use capabilities::{capability_matrix, Capability, PairCapabilities};
 pub use djot::DjotHandler;
 pub use markdown::MarkdownHandler;
 pub use orgmode::OrgModeHandler;
@@ -21,3 +24,190 @@
 pub use asciidoc::AsciidocHandler;
 pub use rst::RstHandler;
 pub use typst::TypstHandler;
+
+use crate::ast::{Block, ColumnAlignment, ColumnSpec, Inline, SourceFormat};
+use crate::traits::FormatHandler;
+
+/// Get the handler for a format
+pub fn handler_for(format: SourceFormat) -> Box<dyn FormatHandler> {
+    match format {
+        SourceFormat::PlainText => Box::new(PlainTextHandler::new()),
+        SourceFormat::Markdown => Box::new(MarkdownHandler::new()),
+        SourceFormat::AsciiDoc => Box::new(AsciidocHandler::new()),
+        SourceFormat::Djot => Box::new(DjotHandler::new()),
+        SourceFormat::OrgMode => Box::new(OrgModeHandler::new()),
+        SourceFormat::ReStructuredText => Box::new(RstHandler::new()),
+        SourceFormat::Typst => Box::new(TypstHandler::new()),
+    }
+}
+
+/// A details block for a format that cannot fold one: its summary as a
+/// bold paragraph, then its content
+pub(crate) fn unfold_details(summary: Option<&Vec<Inline>>, content: &[Block]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = summary
+        .map(|summary| Block::Paragraph {
+            content: vec![Inline::Strong {
+                content: summary.clone(),
+            }],
+            span: None,
+        })
+        .into_iter()
+        .collect();
+    blocks.extend_from_slice(content);
+    blocks
+}
+
+/// A quote's content for a format with no attribution syntax: the
+/// attribution follows as a last paragraph after an em dash
+pub(crate) fn quote_with_attribution(
+    content: &[Block],
+    attribution: Option<&Vec<Inline>>,
+) -> Vec<Block> {
+    let mut blocks = content.to_vec();
+    if let Some(attribution) = attribution {
+        let mut inlines = vec![Inline::Text {
+            content: "\u{2014} ".to_string(),
+        }];
+        inlines.extend_from_slice(attribution);
+        blocks.push(Block::Paragraph {
+            content: inlines,
+            span: None,
+        });
+    }
+    blocks
+}
+
+/// Take the attribution off the end of a quote's content: a last line that
+/// starts with an em dash, a horizontal bar or `--`, on its own or closing
+/// the last paragraph
+pub(crate) fn split_attribution(content: &mut Vec<Block>) -> Option<Vec<Inline>> {
+    let blocks = content.len();
+    let Some(Block::Paragraph {
+        content: inlines, ..
+    }) = content.last_mut()
+    else {
+        return None;
+    };
+    let line = inlines
+        .iter()
+        .rposition(|i| matches!(i, Inline::SoftBreak | Inline::LineBreak))
+        .map_or(0, |i| i + 1);
+    // A quote that is nothing but a dashed line has no attribution
+    if line == 0 && blocks < 2 {
+        return None;
+    }
+    let Some(Inline::Text { content: text }) = inlines.get(line) else {
+        return None;
+    };
+    let name = ["\u{2014}", "\u{2015}", "--"]
+        .into_iter()
+        .find_map(|dash| text.strip_prefix(dash))?
+        .trim_start_matches('-')
+        .trim_start()
+        .to_string();
+    if name.is_empty() && inlines.len() == line + 1 {
+        return None;
+    }
+
+    let mut attribution = inlines.split_off(line);
+    if name.is_empty() {
+        attribution.remove(0);
+    } else {
+        attribution[0] = Inline::Text { content: name };
+    }
+    if line == 0 {
+        content.pop();
+    } else {
+        inlines.pop();
+    }
+    Some(attribution)
+}
+
+/// A keyboard, button or menu inline for a format with no markup for it:
+/// keys as code, and buttons and menus in bold, the menus joined with `>`
+pub(crate) fn plain_ui(inline: &Inline) -> Option<Inline> {
+    let strong = |content: String| Inline::Strong {
+        content: vec![Inline::Text { content }],
+    };
+    match inline {
+        Inline::Keyboard { content } => Some(Inline::Code {
+            content: content.clone(),
+            language: None,
+        }),
+        Inline::Button { content } => Some(strong(content.clone())),
+        Inline::Menu { items } => Some(strong(items.join(" > "))),
+        _ => None,
+    }
+}
+
+/// `content` split by a space after the first character of each of the
+/// `markers` in it, such as HTML's `--`, so that it cannot end the comment
+/// it is written in
+pub(crate) fn comment_text(content: &str, markers: &[&str]) -> String {
+    let mut text = content.to_string();
+    while let Some(marker) = markers.iter().find(|marker| text.contains(**marker)) {
+        let (first, rest) = marker.split_at(1);
+        text = text.replace(marker, &format!("{} {}", first, rest));
+    }
+    text
+}
+
+/// The delimiter row of a pipe table with `count` columns, such as
+/// `| --- | :-: |`, marking the alignment of each column in `columns`
+pub(crate) fn pipe_table_rule(columns: &[ColumnSpec], count: usize) -> String {
+    let mut rule = String::from("|");
+    for i in 0..count {
+        let alignment = columns.get(i).map(|c| c.alignment);
+        rule.push_str(match alignment {
+            Some(ColumnAlignment::Left) => " :-- |",
+            Some(ColumnAlignment::Center) => " :-: |",
+            Some(ColumnAlignment::Right) => " --: |",
+            Some(ColumnAlignment::Default) | None => " --- |",
+        });
+    }
+    rule
+}
+
+/// How deeply the lines of `input` nest: one level for each step of
+/// indentation under an enclosing line, plus the levels `markers` counts at
+/// the start of each line, such as quote and list markers
+///
+/// Only an estimate, for parsers whose library recurses once per level and
+/// so must be stopped before it is given the input.
+pub(crate) fn line_nesting(input: &str, markers: impl Fn(&str) -> usize) -> usize {
+    let mut indents: Vec<usize> = Vec::new();
+    let mut deepest = 0;
+    for line in input.lines() {
+        let rest = line.trim_start();
+        if rest.is_empty() {
+            continue;
+        }
+        let indent = line.len() - rest.len();
+        while indents.last().is_some_and(|&last| last > indent) {
+            indents.pop();
+        }
+        if indent > indents.last().copied().unwrap_or(0) {
+            indents.push(indent);
+        }
+        deepest = deepest.max(1 + indents.len() + markers(rest));
+    }
+    deepest
+}
+
+/// How many of the words starting `line` are one of `markers` or a list
+/// enumerator such as `1.`, `a)` or `(2)`
+pub(crate) fn leading_markers(line: &str, markers: &[&str]) -> usize {
+    line.split_whitespace()
+        .take_while(|word| markers.contains(word) || is_enumerator(word))
+        .count()
+}
+
+fn is_enumerator(word: &str) -> bool {
+    let word = word.strip_prefix('(').unwrap_or(word);
+    let Some(label) = word.strip_suffix(['.', ')']) else {
+        return false;
+    };
+    label == "#"
+        || (!label.is_empty() && label.bytes().all(|b| b.is_ascii_digit()))
+        || (label.len() == 1 && label.bytes().all(|b| b.is_ascii_alphabetic()))
+}
@@ -2,13 +2,16 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Org-mode format handler using orgize
 
+use super::{leading_markers, line_nesting};
 use crate::ast::{
-    Block, ColumnAlignment, ColumnSpec, Document, DocumentMeta, Inline, ListItem, ListKind,
+    Block, ColumnAlignment, ColumnSpec, Document, Inline, LinkType, ListItem, ListKind, MetaValue,
     SourceFormat, TableCell, TableRow,
 };
+use crate::frontmatter::{meta_text, FrontMatter};
 use crate::traits::{FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result};
 use orgize::elements::Element;
 use orgize::Org;
+use std::collections::BTreeMap;
 
 /// Org-mode format handler using orgize
 pub struct OrgModeHandler;
@@ -31,19 +34,107 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_timeout(SourceFormat::OrgMode)?;
+        config.limits.check_source(input, source_depth)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
         let org = Org::parse(input);
-        let content = parse_org(&org);
+        let mut content = parse_org(&org);
+        name_radio_targets(&mut content, &mut radio_targets(input).into_iter());
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::OrgMode,
-            meta: DocumentMeta::default(),
+            meta: front_matter(input).into_meta(),
             content,
             raw_source: if config.preserve_raw_source {
                 Some(input.to_string())
             } else {
                 None
             },
-        })
+        }))
+    }
+}
+
+/// How deeply headings, lists and indented blocks nest in Org source,
+/// which orgize recurses into once per level
+fn source_depth(input: &str) -> usize {
+    line_nesting(input, |line| match line.split_once(' ') {
+        Some((stars, _)) if stars.bytes().all(|b| b == b'*') => stars.len(),
+        _ => leading_markers(line, &["-", "+"]),
+    })
+}
+
+/// Keywords that belong to the element after them rather than the document
+const ELEMENT_KEYWORDS: &[&str] = &[
+    "call", "caption", "header", "include", "name", "plot", "results", "tblfm",
+];
+
+/// Read the `#+KEY: value` lines at the top of the file
+fn front_matter(input: &str) -> FrontMatter {
+    let mut fields = Vec::new();
+    for line in input.lines().map(str::trim) {
+        if line.is_empty() || line == "#" || line.starts_with("# ") {
+            continue;
+        }
+        let Some((key, value)) = line.strip_prefix("#+").and_then(|l| l.split_once(':')) else {
+            break;
+        };
+        let key = key.to_ascii_lowercase();
+        if key.starts_with("begin_")
+            || key.starts_with("attr_")
+            || ELEMENT_KEYWORDS.contains(&key.as_str())
+        {
+            break;
+        }
+        fields.push((key, MetaValue::String(value.trim().to_string())));
+    }
+    FrontMatter::from_fields(fields)
+}
+
+/// `#+KEY: value` lines for the document header
+fn render_front_matter(output: &mut String, front: &FrontMatter) {
+    let mut keyword = |key: &str, value: &str| {
+        output.push_str(&format!("#+{}: {}\n", key.to_ascii_uppercase(), value));
+    };
+    if let Some(title) = &front.title {
+        keyword("title", title);
+    }
+    // Org has a single #+EMAIL, which belongs to the first author; any other
+    // address stays inline
+    for (i, author) in front.authors.iter().enumerate() {
+        if i == 0 {
+            keyword("author", &author.name);
+        } else {
+            keyword("author", &author.to_string());
+        }
+    }
+    if let Some(email) = front.authors.first().and_then(|a| a.email.as_deref()) {
+        keyword("email", email);
+    }
+    if let Some(date) = &front.date {
+        keyword("date", date);
+    }
+    if let Some(modified) = &front.modified {
+        keyword("modified", modified);
+    }
+    if let Some(language) = &front.language {
+        keyword("language", language);
+    }
+    if let Some(direction) = front.direction {
+        keyword("dir", direction.as_str());
+    }
+    if !front.keywords.is_empty() {
+        keyword("keywords", &front.keywords.join(", "));
+    }
+    for (key, value) in &front.fields {
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == ':') {
+            continue;
+        }
+        if let Some(text) = meta_text(value) {
+            keyword(key, &text);
+        }
     }
 }
 
@@ -70,22 +161,41 @@ fn parse_org(org: &Org) -> Vec<Block> {
     blocks
 }
 
+/// Blocks Org gives a meaning of its own, which a container cannot be named
+const BLOCK_NAMES: &[&str] = &[
+    "center", "comment", "example", "export", "quote", "src", "verse",
+];
+
+/// The special block name for a container of `classes`: the first class
+/// that can be one
+fn special_block_name(classes: &[String]) -> &str {
+    classes
+        .iter()
+        .map(String::as_str)
+        .find(|class| {
+            !class.is_empty()
+                && class
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                && !BLOCK_NAMES
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(class))
+        })
+        .unwrap_or("container")
+}
+
+/// Whether a container within `blocks` is written as the special block `name`
+fn has_special_block<'a>(blocks: impl IntoIterator<Item = &'a Block>, name: &str) -> bool {
+    blocks.into_iter().any(|block| {
+        matches!(block, Block::Container { classes, .. }
+            if special_block_name(classes).eq_ignore_ascii_case(name))
+            || has_special_block(block.child_blocks(), name)
+    })
+}
+
 /// Convert a simple (non-container) element to a Block
 fn convert_element(element: &Element) -> Option<Block> {
     match element {
-        Element::Title(title) => {
-            let content = vec![Inline::Text {
-                content: title.raw.to_string(),
-            }];
-
-            Some(Block::Heading {
-                level: title.level as u8,
-                content,
-                id: None,
-                span: None,
-            })
-        }
-
         Element::SourceBlock(block) => Some(Block::CodeBlock {
             language: if block.language.is_empty() {
                 None
@@ -132,6 +242,27 @@ fn handle_container<'a: 'b, 'b, I>(element: &'b Element<'a>, events: &mut I) ->
     I: Iterator<Item = orgize::Event<'a, 'b>>,
 {
     match element {
+        Element::Title(title) => {
+            let content = collect_inline_until_end(events, |e| matches!(e, Element::Title(_)));
+            // A `CUSTOM_ID` is the anchor `[[#id]]` links point at; an `ID`
+            // is what `[[id:...]]` links point at, read as the same anchor
+            let property = |key: &str| {
+                title
+                    .properties
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .map(|(_, value)| value.to_string())
+            };
+
+            Some(Block::Heading {
+                level: title.level as u8,
+                content,
+                id: property("CUSTOM_ID").or_else(|| property("ID")),
+                classes: title.tags.iter().map(|tag| tag.to_string()).collect(),
+                span: None,
+            })
+        }
+
         Element::Paragraph { .. } => {
             let mut inlines = Vec::new();
             collect_paragraph_content(&mut inlines, events);
@@ -146,17 +277,58 @@ fn handle_container<'a: 'b, 'b, I>(element: &'b Element<'a>, events: &mut I) ->
             }
         }
 
+        // Org has no summary line for a drawer; a bold first paragraph
+        // stands in for one. Drawers are always folded to begin with
+        Element::Drawer(drawer) if drawer.name.eq_ignore_ascii_case("folded") => {
+            let mut content = collect_block_content(events, |e| matches!(e, Element::Drawer(_)));
+            let summary = match content.first_mut() {
+                Some(Block::Paragraph { content: first, .. })
+                    if matches!(first.as_slice(), [Inline::Strong { .. }]) =>
+                {
+                    match first.pop() {
+                        Some(Inline::Strong { content }) => Some(content),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            if summary.is_some() {
+                content.remove(0);
+            }
+
+            Some(Block::Details {
+                summary,
+                content,
+                open: false,
+                span: None,
+            })
+        }
+
         Element::QuoteBlock(_) => {
-            let content = collect_block_content(events, |e| matches!(e, Element::QuoteBlock(_)));
+            let mut content =
+                collect_block_content(events, |e| matches!(e, Element::QuoteBlock(_)));
+            let attribution = super::split_attribution(&mut content);
 
             Some(Block::BlockQuote {
                 content,
-                attribution: None,
+                attribution,
                 admonition: None,
                 span: None,
             })
         }
 
+        // Other special blocks are containers, named after their class
+        Element::SpecialBlock(block) => {
+            let content = collect_block_content(events, |e| matches!(e, Element::SpecialBlock(_)));
+            Some(Block::Container {
+                id: None,
+                classes: vec![block.name.to_string()],
+                attributes: Default::default(),
+                content,
+                span: None,
+            })
+        }
+
         Element::List(list) => {
             let kind = if list.ordered {
                 ListKind::Ordered
@@ -174,6 +346,22 @@ fn handle_container<'a: 'b, 'b, I>(element: &'b Element<'a>, events: &mut I) ->
             })
         }
 
+        Element::FnDef(def) => {
+            let mut content = collect_block_content(events, |e| matches!(e, Element::FnDef(_)));
+            // The space after `[fn:label]` is kept as part of the first text
+            if let Some(Block::Paragraph { content, .. }) = content.first_mut() {
+                if let Some(Inline::Text { content }) = content.first_mut() {
+                    *content = content.trim_start().to_string();
+                }
+            }
+
+            Some(Block::FootnoteDefinition {
+                label: def.label.to_string(),
+                content,
+                span: None,
+            })
+        }
+
         Element::Table(_) => {
             let (header, body) = collect_table_content(events);
 
@@ -202,21 +390,64 @@ fn handle_container<'a: 'b, 'b, I>(element: &'b Element<'a>, events: &mut I) ->
     }
 }
 
+/// Push `text` as text, line breaks and non-breaking spaces
+///
+/// orgize leaves both the `\\` that ends a line with a hard break and the
+/// `\nbsp{}` entity in the text.
+fn push_text(inlines: &mut Vec<Inline>, text: &str) {
+    let mut buf = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let (inline, after) = if let Some(after) = rest.strip_prefix("\\\\\n") {
+            (Inline::LineBreak, after)
+        } else if let Some(after) = rest.strip_prefix('\n') {
+            (Inline::SoftBreak, after)
+        } else if let Some(after) = rest.strip_prefix("\\nbsp{}") {
+            (Inline::NonBreakingSpace, after)
+        } else {
+            buf.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        if !buf.is_empty() {
+            inlines.push(Inline::Text {
+                content: std::mem::take(&mut buf),
+            });
+        }
+        inlines.push(inline);
+        rest = after;
+    }
+    if !buf.is_empty() {
+        inlines.push(Inline::Text { content: buf });
+    }
+}
+
 /// Collect paragraph content (inlines) until End(Paragraph)
 fn collect_paragraph_content<'a: 'b, 'b, I>(inlines: &mut Vec<Inline>, events: &mut I)
+where
+    I: Iterator<Item = orgize::Event<'a, 'b>>,
+{
+    inlines.extend(collect_inline_until_end(events, |e| {
+        matches!(e, Element::Paragraph { .. })
+    }));
+}
+
+/// Collect inline content until a matching end element
+fn collect_inline_until_end<'a: 'b, 'b, I>(
+    events: &mut I,
+    is_end_element: fn(&Element) -> bool,
+) -> Vec<Inline>
 where
     I: Iterator<Item = orgize::Event<'a, 'b>>,
 {
     use orgize::Event;
+    let mut inlines = Vec::new();
 
     while let Some(event) = events.next() {
         match event {
-            Event::End(Element::Paragraph { .. }) => break,
-            Event::Start(Element::Text { value }) | Event::End(Element::Text { value }) => {
-                inlines.push(Inline::Text {
-                    content: value.to_string(),
-                });
-            }
+            Event::End(elem) if is_end_element(elem) => break,
+            Event::Start(Element::Text { value }) => push_text(&mut inlines, value),
             Event::Start(Element::Bold) => {
                 let bold_content = collect_inline_until_end(events, |e| matches!(e, Element::Bold));
                 inlines.push(Inline::Strong {
@@ -237,64 +468,179 @@ fn collect_paragraph_content<'a: 'b, 'b, I>(inlines: &mut Vec<Inline>, events: &
                     content: strike_content,
                 });
             }
-            Event::Start(Element::Code { value }) | Event::End(Element::Code { value }) => {
+            Event::Start(Element::Code { value }) | Event::Start(Element::Verbatim { value }) => {
                 inlines.push(Inline::Code {
                     content: value.to_string(),
                     language: None,
                 });
             }
-            Event::Start(Element::Verbatim { value }) | Event::End(Element::Verbatim { value }) => {
-                inlines.push(Inline::Code {
-                    content: value.to_string(),
-                    language: None,
+            Event::Start(Element::FnRef(fn_ref)) => {
+                inlines.push(Inline::FootnoteRef {
+                    label: fn_ref.label.to_string(),
                 });
             }
             Event::Start(Element::Link(link)) => {
-                let link_text = link
-                    .desc
-                    .as_ref()
-                    .map(|d| d.to_string())
-                    .unwrap_or_else(|| link.path.to_string());
+                let url = link_url(&link.path);
+                let content = match &link.desc {
+                    Some(desc) => parse_description(desc),
+                    None => vec![Inline::Text {
+                        content: url.clone(),
+                    }],
+                };
                 inlines.push(Inline::Link {
-                    url: link.path.to_string(),
+                    url,
                     title: None,
-                    content: vec![Inline::Text { content: link_text }],
-                    link_type: crate::ast::LinkType::Inline,
+                    content,
+                    link_type: LinkType::Inline,
                 });
             }
+            Event::Start(Element::Target(target)) => inlines.push(Inline::Span {
+                id: Some(target.target.to_string()),
+                classes: Vec::new(),
+                attributes: BTreeMap::new(),
+                content: Vec::new(),
+            }),
+            // orgize drops the text of a radio target; the placeholder is
+            // named from the source by `name_radio_targets`
+            Event::Start(Element::RadioTarget) => inlines.push(Inline::Span {
+                id: Some(String::new()),
+                classes: Vec::new(),
+                attributes: BTreeMap::new(),
+                content: Vec::new(),
+            }),
             _ => {}
         }
     }
+
+    inlines
 }
 
-/// Collect inline content until a matching end element
-fn collect_inline_until_end<'a: 'b, 'b, I, F>(events: &mut I, is_end_element: F) -> Vec<Inline>
-where
-    I: Iterator<Item = orgize::Event<'a, 'b>>,
-    F: Fn(&Element) -> bool,
-{
+/// The URL an Org link path stands for
+///
+/// `file:` links are relative URLs, and an `id:` link points at the heading
+/// with that `ID`, which the parser reads as the heading's anchor.
+fn link_url(path: &str) -> String {
+    if let Some(file) = path.strip_prefix("file:") {
+        file.to_string()
+    } else if let Some(id) = path.strip_prefix("id:") {
+        format!("#{}", id)
+    } else {
+        path.to_string()
+    }
+}
+
+/// The Org link path for `url`: the inverse of [`link_url`]
+fn link_path(url: &str) -> String {
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains(['/', '.', '#']));
+    if url.starts_with('#') || has_scheme {
+        url.to_string()
+    } else {
+        format!("file:{}", url)
+    }
+}
+
+/// Parse the markup in a link description, which orgize leaves as text
+fn parse_description(desc: &str) -> Vec<Inline> {
     use orgize::Event;
-    let mut inlines = Vec::new();
 
+    let org = Org::parse(desc);
+    let mut events = org.iter();
     while let Some(event) = events.next() {
-        match &event {
-            Event::End(elem) if is_end_element(elem) => break,
-            Event::Start(Element::Text { value }) | Event::End(Element::Text { value }) => {
-                inlines.push(Inline::Text {
-                    content: value.to_string(),
-                });
+        if let Event::Start(Element::Paragraph { .. }) = event {
+            let mut inlines = Vec::new();
+            collect_paragraph_content(&mut inlines, &mut events);
+            return inlines;
+        }
+    }
+    vec![Inline::Text {
+        content: desc.to_string(),
+    }]
+}
+
+/// The names of the `<<<radio targets>>>` in `input`, in document order
+///
+/// Blocks whose contents orgize does not parse as Org are skipped.
+fn radio_targets(input: &str) -> Vec<String> {
+    const VERBATIM: &[&str] = &["src", "example", "export", "comment"];
+
+    let mut names = Vec::new();
+    let mut verbatim: Option<&str> = None;
+    for line in input.lines() {
+        let keyword = line.trim_start().to_ascii_lowercase();
+        let block_name = |prefix: &str| {
+            keyword
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.split_whitespace().next())
+                .map(str::to_string)
+        };
+        if let Some(name) = verbatim {
+            if block_name("#+end_").as_deref() == Some(name) {
+                verbatim = None;
             }
-            Event::Start(Element::Code { value }) | Event::End(Element::Code { value }) => {
-                inlines.push(Inline::Code {
-                    content: value.to_string(),
-                    language: None,
-                });
+            continue;
+        }
+        if let Some(name) = block_name("#+begin_") {
+            verbatim = VERBATIM.iter().find(|v| **v == name).copied();
+            continue;
+        }
+        if keyword == ":" || keyword.starts_with(": ") {
+            continue;
+        }
+
+        let mut rest = line;
+        while let Some(open) = rest.find("<<<") {
+            let after = &rest[open + 3..];
+            let name = after
+                .find(['<', '>'])
+                .map(|end| &after[..end])
+                .filter(|name| after[name.len()..].starts_with(">>>"))
+                .filter(|name| !name.is_empty() && name.trim() == *name);
+            match name {
+                Some(name) => {
+                    names.push(name.to_string());
+                    rest = &after[name.len() + 3..];
+                }
+                None => rest = after,
             }
-            _ => {}
         }
     }
+    names
+}
 
-    inlines
+/// Give the radio target placeholders in `blocks` their `names`, in order
+fn name_radio_targets(blocks: &mut [Block], names: &mut impl Iterator<Item = String>) {
+    fn visit(inline: &mut Inline, names: &mut impl Iterator<Item = String>) {
+        if let Inline::Span { id, content, .. } = inline {
+            if id.as_deref() == Some("") && content.is_empty() {
+                match names.next() {
+                    Some(name) => {
+                        *content = vec![Inline::Text {
+                            content: name.clone(),
+                        }];
+                        *id = Some(name);
+                    }
+                    None => *id = None,
+                }
+                return;
+            }
+        }
+        for child in inline.children_mut() {
+            visit(child, names);
+        }
+    }
+
+    for block in blocks {
+        for group in block.inline_groups_mut() {
+            for inline in group {
+                visit(inline, names);
+            }
+        }
+        for child in block.child_blocks_mut() {
+            name_radio_targets(std::slice::from_mut(child), names);
+        }
+    }
 }
 
 /// Collect block content until a matching end element
@@ -308,24 +654,13 @@ fn collect_block_content<'a: 'b, 'b, I, F>(events: &mut I, is_end_element: F) ->
     let mut depth = 1;
 
     while let Some(event) = events.next() {
-        match &event {
-            Event::Start(elem) if is_end_element(elem) => depth += 1,
+        match event {
             Event::End(elem) if is_end_element(elem) => {
                 depth -= 1;
                 if depth == 0 {
                     break;
                 }
             }
-            Event::Start(Element::Paragraph { .. }) => {
-                let mut inlines = Vec::new();
-                collect_paragraph_content(&mut inlines, events);
-                if !inlines.is_empty() {
-                    blocks.push(Block::Paragraph {
-                        content: inlines,
-                        span: None,
-                    });
-                }
-            }
             Event::Start(Element::Text { value }) => {
                 blocks.push(Block::Paragraph {
                     content: vec![Inline::Text {
@@ -334,6 +669,16 @@ fn collect_block_content<'a: 'b, 'b, I, F>(events: &mut I, is_end_element: F) ->
                     span: None,
                 });
             }
+            Event::Start(elem) => {
+                if let Some(block) = convert_element(elem) {
+                    blocks.push(block);
+                } else if let Some(block) = handle_container(elem, events) {
+                    blocks.push(block);
+                } else if is_end_element(elem) {
+                    // A container of the same kind that is not converted
+                    depth += 1;
+                }
+            }
             _ => {}
         }
     }
@@ -503,12 +848,12 @@ fn collect_table_row_cells<'a: 'b, 'b, I>(events: &mut I) -> Vec<TableCell>
             // TableCell is an enum: Header, Body
             Event::Start(Element::TableCell(OrgTableCell::Header))
             | Event::Start(Element::TableCell(OrgTableCell::Body)) => {
-                let cell_text = collect_cell_text(events);
+                let mut content =
+                    collect_inline_until_end(events, |e| matches!(e, Element::TableCell(_)));
+                trim_inlines(&mut content);
                 cells.push(TableCell {
                     content: vec![Block::Paragraph {
-                        content: vec![Inline::Text {
-                            content: cell_text.trim().to_string(),
-                        }],
+                        content,
                         span: None,
                     }],
                     colspan: 1,
@@ -523,25 +868,15 @@ fn collect_table_row_cells<'a: 'b, 'b, I>(events: &mut I) -> Vec<TableCell>
     cells
 }
 
-/// Collect text content for a table cell until End(TableCell)
-fn collect_cell_text<'a: 'b, 'b, I>(events: &mut I) -> String
-where
-    I: Iterator<Item = orgize::Event<'a, 'b>>,
-{
-    use orgize::Event;
-    let mut text = String::new();
-
-    while let Some(event) = events.next() {
-        match event {
-            Event::End(Element::TableCell(_)) => break,
-            Event::Start(Element::Text { value }) | Event::End(Element::Text { value }) => {
-                text.push_str(&value);
-            }
-            _ => {}
-        }
+/// Trim the space around a table cell's inlines
+fn trim_inlines(inlines: &mut Vec<Inline>) {
+    if let Some(Inline::Text { content }) = inlines.first_mut() {
+        *content = content.trim_start().to_string();
     }
-
-    text
+    if let Some(Inline::Text { content }) = inlines.last_mut() {
+        *content = content.trim_end().to_string();
+    }
+    inlines.retain(|inline| !matches!(inline, Inline::Text { content } if content.is_empty()));
 }
 
 impl Renderer for OrgModeHandler {
@@ -549,9 +884,16 @@ fn format(&self) -> SourceFormat {
         SourceFormat::OrgMode
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::OrgMode);
         let mut output = String::new();
 
+        let front = FrontMatter::from_meta(&doc.meta);
+        if !front.is_empty() {
+            render_front_matter(&mut output, &front);
+            output.push('\n');
+        }
+
         for (i, block) in doc.content.iter().enumerate() {
             if i > 0 {
                 output.push_str("\n\n");
@@ -559,7 +901,7 @@ fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
             render_block(&mut output, block);
         }
 
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
@@ -571,12 +913,24 @@ fn render_block(output: &mut String, block: &Block) {
             }
         }
 
-        Block::Heading { level, content, .. } => {
+        Block::Heading {
+            level,
+            content,
+            id,
+            classes,
+            ..
+        } => {
             output.push_str(&"*".repeat(*level as usize));
             output.push(' ');
             for inline in content {
                 render_inline(output, inline);
             }
+            if !classes.is_empty() {
+                output.push_str(&format!(" :{}:", classes.join(":")));
+            }
+            if let Some(id) = id {
+                output.push_str(&format!("\n:PROPERTIES:\n:CUSTOM_ID: {}\n:END:", id));
+            }
         }
 
         Block::CodeBlock {
@@ -595,9 +949,19 @@ fn render_block(output: &mut String, block: &Block) {
             output.push_str("#+END_SRC");
         }
 
-        Block::BlockQuote { content, .. } => {
+        Block::BlockQuote {
+            content,
+            attribution,
+            ..
+        } => {
+            // Org ends a block at the first end line with its name, so quotes
+            // within quotes do not read back (`nested_quote` is unsupported)
             output.push_str("#+BEGIN_QUOTE\n");
-            for block in content {
+            let content = super::quote_with_attribution(content, attribution.as_ref());
+            for (i, block) in content.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
                 render_block(output, block);
                 output.push('\n');
             }
@@ -608,21 +972,39 @@ fn render_block(output: &mut String, block: &Block) {
             kind, items, start, ..
         } => {
             for (i, item) in items.iter().enumerate() {
-                match kind {
-                    ListKind::Bullet => output.push_str("- "),
-                    ListKind::Ordered => {
-                        let num = start.unwrap_or(1) + i as u32;
-                        output.push_str(&format!("{}. ", num));
-                    }
-                    ListKind::Task => {
-                        let checked = item.checked.unwrap_or(false);
-                        output.push_str(if checked { "- [X] " } else { "- [ ] " });
+                if i > 0 {
+                    output.push('\n');
+                }
+                let marker = match kind {
+                    ListKind::Bullet => "- ".to_string(),
+                    ListKind::Ordered => format!("{}. ", start.unwrap_or(1) + i as u32),
+                    ListKind::Task if item.checked == Some(true) => "- [X] ".to_string(),
+                    ListKind::Task => "- [ ] ".to_string(),
+                };
+                output.push_str(&marker);
+
+                // Later blocks and nested lists are indented under the item text
+                let indent = if matches!(kind, ListKind::Task) {
+                    2
+                } else {
+                    marker.len()
+                };
+                let mut body = String::new();
+                for (j, block) in item.content.iter().enumerate() {
+                    if j > 0 {
+                        body.push('\n');
                     }
+                    render_block(&mut body, block);
                 }
-                for block in &item.content {
-                    render_block(output, block);
+                for (j, line) in body.lines().enumerate() {
+                    if j > 0 {
+                        output.push('\n');
+                        if !line.is_empty() {
+                            output.push_str(&" ".repeat(indent));
+                        }
+                    }
+                    output.push_str(line);
                 }
-                output.push('\n');
             }
         }
 
@@ -655,6 +1037,9 @@ fn render_block(output: &mut String, block: &Block) {
                 }
                 output.push('\n');
             }
+            if output.ends_with('\n') {
+                output.pop();
+            }
         }
 
         Block::FootnoteDefinition { label, content, .. } => {
@@ -670,6 +1055,90 @@ fn render_block(output: &mut String, block: &Block) {
             output.push_str("\n#+END_EXPORT");
         }
 
+        // Later blocks of a definition are indented under the item text
+        Block::DefinitionList { items, .. } => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                output.push_str("- ");
+                for inline in &item.term {
+                    render_inline(output, inline);
+                }
+                output.push_str(" ::");
+                let mut body = String::new();
+                for (j, block) in item.definitions.iter().flatten().enumerate() {
+                    if j > 0 {
+                        body.push('\n');
+                    }
+                    render_block(&mut body, block);
+                }
+                for (j, line) in body.lines().enumerate() {
+                    if j == 0 {
+                        output.push(' ');
+                    } else {
+                        output.push('\n');
+                        if !line.is_empty() {
+                            output.push_str("  ");
+                        }
+                    }
+                    output.push_str(line);
+                }
+            }
+        }
+
+        // A container is a special block named after its class. Org ends a
+        // block at the first end line with its name, so one holding another
+        // of the same name is left out and its content kept
+        Block::Container {
+            classes, content, ..
+        } => {
+            let name = special_block_name(classes);
+            let wrap = !has_special_block(content, name);
+            if wrap {
+                output.push_str(&format!("#+BEGIN_{}\n", name));
+            }
+            for (i, block) in content.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                render_block(output, block);
+                output.push('\n');
+            }
+            if wrap {
+                output.push_str(&format!("#+END_{}", name));
+            } else if output.ends_with('\n') {
+                output.pop();
+            }
+        }
+
+        Block::Details {
+            summary, content, ..
+        } => {
+            output.push_str(":FOLDED:\n");
+            if let Some(summary) = summary {
+                output.push('*');
+                for inline in summary {
+                    render_inline(output, inline);
+                }
+                output.push_str("*\n\n");
+            }
+            for (i, block) in content.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                render_block(output, block);
+                output.push('\n');
+            }
+            output.push_str(":END:");
+        }
+
+        Block::Comment { content, .. } => {
+            output.push_str("#+BEGIN_COMMENT\n");
+            output.push_str(content);
+            output.push_str("\n#+END_COMMENT");
+        }
+
         _ => {}
     }
 }
@@ -708,14 +1177,45 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('~');
         }
 
+        Inline::Keyboard { .. } | Inline::Button { .. } | Inline::Menu { .. } => {
+            if let Some(plain) = super::plain_ui(inline) {
+                render_inline(output, &plain);
+            }
+        }
+
         Inline::Link { url, content, .. } => {
             output.push_str("[[");
-            output.push_str(url);
-            output.push_str("][");
+            output.push_str(&link_path(url));
+            if !matches!(content.as_slice(), [Inline::Text { content }] if content == url) {
+                output.push_str("][");
+                for i in content {
+                    render_inline(output, i);
+                }
+            }
+            output.push_str("]]");
+        }
+
+        // A radio target's text is its name
+        Inline::Span {
+            id: Some(id),
+            content,
+            ..
+        } => match content.as_slice() {
+            [Inline::Text { content }] if content == id => {
+                output.push_str(&format!("<<<{}>>>", id));
+            }
+            _ => {
+                output.push_str(&format!("<<{}>>", id));
+                for i in content {
+                    render_inline(output, i);
+                }
+            }
+        },
+
+        Inline::Span { content, .. } => {
             for i in content {
                 render_inline(output, i);
             }
-            output.push_str("]]");
         }
 
         Inline::Image { url, .. } => {
@@ -736,6 +1236,10 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('\n');
         }
 
+        Inline::NonBreakingSpace => {
+            output.push_str("\\nbsp{}");
+        }
+
         Inline::RawInline { content, .. } => {
             output.push_str(content);
         }
@@ -762,6 +1266,9 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "blockquote"
                 | "footnote"
                 | "verbatim"
+                | "container"
+                | "details"
+                | "emoji"
         )
     }
 
@@ -781,13 +1288,23 @@ fn supported_features(&self) -> &[&str] {
             "blockquote",
             "footnote",
             "verbatim",
+            "container",
+            "details",
+            "emoji",
         ]
     }
+
+    fn normalization_exceptions(&self) -> &[&str] {
+        // Math is not rendered, and footnote references inside list items are
+        // not parsed back
+        &["math", "footnote"]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{Author, DocumentMeta};
 
     #[test]
     fn test_parse_heading() {
@@ -816,6 +1333,7 @@ fn test_render_heading() {
                     content: "Test".to_string(),
                 }],
                 id: None,
+                classes: Vec::new(),
                 span: None,
             }],
             raw_source: None,
@@ -862,6 +1380,35 @@ fn test_parse_quote_block() {
         assert!(has_quote, "Should parse quote block");
     }
 
+    #[test]
+    fn test_render_nested_quote() {
+        let handler = OrgModeHandler::new();
+        let paragraph = |text: &str| Block::Paragraph {
+            content: vec![Inline::Text {
+                content: text.to_string(),
+            }],
+            span: None,
+        };
+        let quote = |content| Block::BlockQuote {
+            content,
+            attribution: None,
+            admonition: None,
+            span: None,
+        };
+        let mut doc = Document::new(SourceFormat::OrgMode);
+        doc.content = vec![quote(vec![
+            paragraph("outer"),
+            quote(vec![paragraph("inner")]),
+        ])];
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output.trim_end(),
+            "#+BEGIN_QUOTE\nouter\n\n#+BEGIN_QUOTE\ninner\n#+END_QUOTE\n#+END_QUOTE"
+        );
+        assert!(!handler.supports_feature("nested_quote"));
+    }
+
     #[test]
     fn test_parse_list() {
         let handler = OrgModeHandler::new();
@@ -891,4 +1438,136 @@ fn test_parse_table() {
         let has_table = doc.content.iter().any(|b| matches!(b, Block::Table { .. }));
         assert!(has_table, "Should parse table");
     }
+
+    #[test]
+    fn test_front_matter() {
+        let handler = OrgModeHandler::new();
+        let doc = handler
+            .parse(
+                "#+TITLE: Notes\n#+AUTHOR: Ada\n#+AUTHOR: Grace\n#+EMAIL: ada@example.com\n\
+                 #+LANGUAGE: en\n#+KEYWORDS: notes, math\n#+OPTIONS: toc:nil\n\n* Body\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(doc.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(
+            doc.meta.authors,
+            vec![
+                Author::parse("Ada <ada@example.com>"),
+                Author::parse("Grace")
+            ]
+        );
+        assert_eq!(doc.meta.language.as_deref(), Some("en"));
+        assert_eq!(doc.meta.keywords, vec!["notes", "math"]);
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(
+            "#+TITLE: Notes\n#+AUTHOR: Ada\n#+AUTHOR: Grace\n#+EMAIL: ada@example.com\n\
+             #+LANGUAGE: en\n#+KEYWORDS: notes, math\n#+OPTIONS: toc:nil\n\n* Body"
+        ));
+    }
+
+    #[test]
+    fn test_folded_drawer() {
+        let handler = OrgModeHandler::new();
+        let input = ":FOLDED:\n*Why?*\n\nBecause.\n:END:\n\n:LOGBOOK:\nNot folded\n:END:\n";
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+
+        let Block::Details {
+            summary, content, ..
+        } = &doc.content[0]
+        else {
+            panic!("expected details, got {:?}", doc.content[0]);
+        };
+        assert!(matches!(summary.as_deref(), Some([Inline::Text { .. }])));
+        assert_eq!(content.len(), 1);
+        assert!(!doc.content[1..]
+            .iter()
+            .any(|b| matches!(b, Block::Details { .. })));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(":FOLDED:\n*Why?*\n\nBecause.\n:END:"));
+    }
+
+    #[test]
+    fn test_special_blocks() {
+        let handler = OrgModeHandler::new();
+        let input = "#+BEGIN_sidebar\nAside\n\n#+BEGIN_note\nInner\n#+END_note\n#+END_sidebar";
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+
+        let Block::Container {
+            classes, content, ..
+        } = &doc.content[0]
+        else {
+            panic!("expected a container, got {:?}", doc.content[0]);
+        };
+        assert_eq!(classes, &["sidebar"]);
+        assert!(matches!(&content[1], Block::Container { classes, .. } if classes == &["note"]));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, input);
+
+        // The block around a container of the same name is left out
+        let mut nested = doc.clone();
+        if let Block::Container { content, .. } = &mut nested.content[0] {
+            content[1] = doc.content[0].clone();
+        }
+        let output = handler.render(&nested, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output,
+            "Aside\n\n#+BEGIN_sidebar\nAside\n\n#+BEGIN_note\nInner\n#+END_note\n#+END_sidebar"
+        );
+    }
+
+    #[test]
+    fn test_links_and_targets() {
+        let handler = OrgModeHandler::new();
+        let input = "* Intro\n:PROPERTIES:\n:CUSTOM_ID: intro\n:END:\n\n\
+                     See [[https://example.com][the *site*]], [[file:notes.org]], \
+                     [[id:intro][back]] and [[#intro]].[fn:1]\n\n\
+                     A <<anchor>>target and a <<<radio>>> one.\n\n\
+                     | [[https://example.com][cell]] |\n\n\
+                     #+BEGIN_SRC org\n<<<not a target>>>\n#+END_SRC\n\n\
+                     [fn:1] A note.\n";
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+
+        assert!(matches!(&doc.content[0], Block::Heading { id: Some(id), .. } if id == "intro"));
+        let Block::Paragraph { content, .. } = &doc.content[1] else {
+            panic!("expected a paragraph, got {:?}", doc.content[1]);
+        };
+        let links: Vec<_> = content
+            .iter()
+            .filter_map(|inline| match inline {
+                Inline::Link { url, content, .. } => Some((url.as_str(), content)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            links.iter().map(|(url, _)| *url).collect::<Vec<_>>(),
+            ["https://example.com", "notes.org", "#intro", "#intro"]
+        );
+        assert!(matches!(links[0].1[1], Inline::Strong { .. }));
+        assert!(matches!(content.last(), Some(Inline::FootnoteRef { label }) if label == "1"));
+
+        let Block::Paragraph { content, .. } = &doc.content[2] else {
+            panic!("expected a paragraph, got {:?}", doc.content[2]);
+        };
+        assert!(
+            matches!(&content[1], Inline::Span { id: Some(id), content, .. }
+            if id == "anchor" && content.is_empty())
+        );
+        assert!(
+            matches!(&content[3], Inline::Span { id: Some(id), content, .. }
+            if id == "radio" && content.len() == 1)
+        );
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(
+            "* Intro\n:PROPERTIES:\n:CUSTOM_ID: intro\n:END:\n\n\
+             See [[https://example.com][the *site*]], [[file:notes.org]], \
+             [[#intro][back]] and [[#intro]].[fn:1]\n\n\
+             A <<anchor>>target and a <<<radio>>> one.\n\n\
+             | [[https://example.com][cell]] |"
+        ));
+    }
 }
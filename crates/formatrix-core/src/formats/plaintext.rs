@@ -2,7 +2,7 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Plain text format handler
 
-use crate::ast::{Block, Document, DocumentMeta, Inline, SourceFormat};
+use crate::ast::{Block, Document, DocumentMeta, Inline, SourceFormat, TextDirection};
 use crate::traits::{FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result};
 
 /// Plain text format handler
@@ -26,28 +26,45 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_timeout(SourceFormat::PlainText)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
         // Split into paragraphs on blank lines
         let paragraphs: Vec<Block> = input
             .split("\n\n")
             .filter(|p| !p.trim().is_empty())
             .map(|p| Block::Paragraph {
                 content: vec![Inline::Text {
-                    content: p.trim().to_string(),
+                    content: p
+                        .trim()
+                        .lines()
+                        .map(|line| line.trim_start_matches([LRM, RLM]))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
                 }],
                 span: None,
             })
             .collect();
 
-        Ok(Document {
+        // With no header to say otherwise, the first strong character sets
+        // the direction, as it does for the bidi algorithm
+        let meta = DocumentMeta {
+            direction: TextDirection::detect(input).filter(|d| *d == TextDirection::Rtl),
+            ..Default::default()
+        };
+
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::PlainText,
-            meta: DocumentMeta::default(),
+            meta,
             content: paragraphs,
             raw_source: if config.preserve_raw_source {
                 Some(input.to_string())
             } else {
                 None
             },
-        })
+        }))
     }
 }
 
@@ -56,17 +73,60 @@ fn format(&self) -> SourceFormat {
         SourceFormat::PlainText
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::PlainText);
         let mut output = String::new();
+        let direction = doc.meta.text_direction();
 
         for (i, block) in doc.content.iter().enumerate() {
             if i > 0 {
                 output.push_str("\n\n");
             }
-            render_block(&mut output, block);
+            render_directed(&mut output, block, direction);
+        }
+
+        Ok(config.finish_output(output))
+    }
+}
+
+const LRM: char = '\u{200E}';
+const RLM: char = '\u{200F}';
+
+/// Render `block` with a left-to-right or right-to-left mark before every
+/// line that would otherwise be laid out in the wrong direction
+///
+/// Plain text has no markup, so each line is its own bidi paragraph and
+/// takes its direction from its first strong character.
+fn render_directed(output: &mut String, block: &Block, direction: Option<TextDirection>) {
+    if let Block::Container { content, .. } = block {
+        let direction = block.direction().or(direction);
+        for (i, child) in content.iter().enumerate() {
+            if i > 0 {
+                output.push_str("\n\n");
+            }
+            render_directed(output, child, direction);
         }
+        return;
+    }
 
-        Ok(output)
+    let mut text = String::new();
+    render_block(&mut text, block);
+    let mark = match direction {
+        Some(TextDirection::Ltr) => LRM,
+        Some(TextDirection::Rtl) => RLM,
+        Some(TextDirection::Auto) | None => {
+            output.push_str(&text);
+            return;
+        }
+    };
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        if !line.is_empty() && TextDirection::detect(line) != direction {
+            output.push(mark);
+        }
+        output.push_str(line);
     }
 }
 
@@ -85,9 +145,13 @@ fn render_block(output: &mut String, block: &Block) {
         Block::CodeBlock { content, .. } => {
             output.push_str(content);
         }
-        Block::BlockQuote { content, .. } => {
-            for block in content {
-                render_block(output, block);
+        Block::BlockQuote {
+            content,
+            attribution,
+            ..
+        } => {
+            for block in super::quote_with_attribution(content, attribution.as_ref()) {
+                render_block(output, &block);
             }
         }
         Block::List { items, .. } => {
@@ -100,6 +164,40 @@ fn render_block(output: &mut String, block: &Block) {
         Block::Raw { content, .. } => {
             output.push_str(content);
         }
+        Block::Container { content, .. } => {
+            for block in content {
+                render_block(output, block);
+            }
+        }
+        // The term, then its definitions indented under it
+        Block::DefinitionList { items, .. } => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                for inline in &item.term {
+                    render_inline(output, inline);
+                }
+                for block in item.definitions.iter().flatten() {
+                    let mut text = String::new();
+                    render_block(&mut text, block);
+                    for line in text.lines() {
+                        output.push('\n');
+                        if !line.is_empty() {
+                            output.push_str("    ");
+                        }
+                        output.push_str(line);
+                    }
+                }
+            }
+        }
+        Block::Details {
+            summary, content, ..
+        } => {
+            for block in super::unfold_details(summary.as_ref(), content) {
+                render_block(output, &block);
+            }
+        }
         _ => {}
     }
 }
@@ -123,8 +221,11 @@ fn render_inline(output: &mut String, inline: &Inline) {
                 render_inline(output, i);
             }
         }
+        Inline::Keyboard { content } | Inline::Button { content } => output.push_str(content),
+        Inline::Menu { items } => output.push_str(&items.join(" > ")),
         Inline::LineBreak => output.push('\n'),
         Inline::SoftBreak => output.push(' '),
+        Inline::NonBreakingSpace => output.push('\u{a0}'),
         _ => {}
     }
 }
@@ -162,4 +263,32 @@ fn test_roundtrip() {
 
         assert_eq!(output, input);
     }
+
+    #[test]
+    fn test_rtl_marks() {
+        let handler = PlainTextHandler::new();
+        let doc = handler
+            .parse("שלום עולם\n\nHTML ו-CSS\n\n2026", &ParseConfig::default())
+            .unwrap();
+        assert_eq!(doc.meta.direction, Some(TextDirection::Rtl));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, "שלום עולם\n\n\u{200F}HTML ו-CSS\n\n\u{200F}2026");
+
+        // Marks are dropped again on the way back in
+        let reparsed = handler.parse(&output, &ParseConfig::default()).unwrap();
+        assert_eq!(
+            handler.render(&reparsed, &RenderConfig::default()).unwrap(),
+            output
+        );
+
+        // Nothing changes for a document with no stated direction
+        let doc = handler
+            .parse("Hello\n\n2026", &ParseConfig::default())
+            .unwrap();
+        assert_eq!(
+            handler.render(&doc, &RenderConfig::default()).unwrap(),
+            "Hello\n\n2026"
+        );
+    }
 }
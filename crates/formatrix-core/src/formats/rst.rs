@@ -3,18 +3,29 @@
 //! reStructuredText format handler using rst_parser
 //! FD-S02: SHOULD requirement
 
+use super::{leading_markers, line_nesting, sphinx};
 use crate::ast::{
     AdmonitionType, Block, Document, DocumentMeta, Inline, LinkType, ListItem, ListKind,
     MathNotation, SourceFormat,
 };
 use crate::traits::{
-    ConversionError, FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result,
+    ConversionError, FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result, RstFlavor,
+    RstStyle,
 };
 use document_tree::{
     element_categories::{BodyElement, StructuralSubElement, SubStructure, TextOrInlineElement},
-    Document as RstDoc, HasChildren,
+    elements as e,
+    extra_attributes::ExtraAttributes,
+    Document as RstDoc, Element, HasChildren,
 };
-use rst_parser::parse;
+use rst_parser::parse_only;
+use rst_parser::transforms::{standard_transform, Transform};
+use std::borrow::Cow;
+use std::iter::once;
+
+/// Class of the spans that stand for kept substitution references, whose
+/// `name` attribute holds the substitution's name
+const SUBSTITUTION_CLASS: &str = "substitution";
 
 /// reStructuredText format handler
 pub struct RstHandler;
@@ -37,15 +48,20 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
-        let rst_doc = parse(input).map_err(|e| ConversionError::ParseError {
-            line: 0,
-            column: 0,
-            message: format!("RST parse error: {:?}", e),
-        })?;
-
-        let content = convert_rst_document(&rst_doc);
+        config.limits.check_input(input)?;
+        config
+            .limits
+            .check_timeout(SourceFormat::ReStructuredText)?;
+        config.limits.check_source(input, source_depth)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
+        let mut content = parse_blocks(input, config)?;
+        if config.rst_flavor == RstFlavor::Sphinx {
+            sphinx::parse_roles(&mut content);
+        }
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::ReStructuredText,
             meta: DocumentMeta::default(),
             content,
@@ -54,60 +70,220 @@ fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
             } else {
                 None
             },
-        })
+        }))
+    }
+}
+
+/// How deeply lists and indented blocks nest in reStructuredText source,
+/// which the grammar recurses into once per level
+fn source_depth(input: &str) -> usize {
+    line_nesting(input, |line| {
+        leading_markers(line, &["-", "*", "+", "\u{2022}"])
+    })
+}
+
+/// Parse `input` into blocks
+fn parse_blocks(input: &str, config: &ParseConfig) -> Result<Vec<Block>> {
+    // The grammar rejects blank input and needs exactly one newline after
+    // the last line
+    if input.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let source = format!("{}\n", escape_attributions(input.trim_end()));
+    let mut rst_doc = parse_only(&source).map_err(|e| ConversionError::ParseError {
+        line: 0,
+        column: 0,
+        message: format!("RST parse error: {:?}", e),
+    })?;
+    let mut keep = KeepSubstitutions::default();
+    if config.keep_substitutions {
+        rst_doc = keep.transform(rst_doc);
+    }
+    let mut content = convert_rst_document(&standard_transform(rst_doc), config.rst_flavor);
+    content.extend(keep.definitions);
+    Ok(content)
+}
+
+/// Parse the content of a Sphinx directive, keeping it raw if it does not
+/// parse
+fn parse_sphinx_content(source: &str) -> Vec<Block> {
+    parse_content(source, RstFlavor::Sphinx)
+}
+
+/// Parse the content of a directive, keeping it raw if it does not parse
+fn parse_content(source: &str, flavor: RstFlavor) -> Vec<Block> {
+    let config = ParseConfig {
+        rst_flavor: flavor,
+        ..Default::default()
+    };
+    parse_blocks(source, &config).unwrap_or_else(|_| {
+        vec![Block::Raw {
+            format: SourceFormat::ReStructuredText,
+            content: source.to_string(),
+            span: None,
+        }]
+    })
+}
+
+/// Escape the dash of block quote attributions, which the parser panics on
+///
+/// The quote keeps the line as its last paragraph, which
+/// [`split_attribution`](super::split_attribution) then takes as the
+/// attribution. Lines in literal blocks and directives are left alone.
+fn escape_attributions(input: &str) -> Cow<'_, str> {
+    if !input.contains("--") && !input.contains('\u{2014}') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut output = String::with_capacity(input.len());
+    // Indent of the line that opened the literal block or directive we are in
+    let mut literal: Option<usize> = None;
+    let mut blank_before = true;
+    for line in input.split_inclusive('\n') {
+        let body = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        if !body.is_empty() && literal.is_some_and(|outer| indent <= outer) {
+            literal = None;
+        }
+
+        let rest = &line[indent..];
+        if literal.is_none() && indent > 0 && blank_before {
+            if let Some(after) = rest.strip_prefix('\u{2014}') {
+                output.push_str(&line[..indent]);
+                output.push_str("\\--");
+                output.push_str(after);
+            } else if rest.starts_with("--") {
+                output.push_str(&line[..indent]);
+                output.push('\\');
+                output.push_str(rest);
+            } else {
+                output.push_str(line);
+            }
+        } else {
+            output.push_str(line);
+        }
+
+        if literal.is_none() && (body.ends_with("::") || body.starts_with(".. ")) {
+            literal = Some(indent);
+        }
+        blank_before = body.is_empty();
+    }
+    Cow::Owned(output)
+}
+
+/// Pass that keeps substitutions through the standard transform, which
+/// replaces references with their content and drops the definitions
+///
+/// Each reference is wrapped in an inline element of
+/// [`SUBSTITUTION_CLASS`] named after it, so its content still comes out
+/// marked, and each definition is kept as a raw block.
+#[derive(Default)]
+struct KeepSubstitutions {
+    definitions: Vec<Block>,
+}
+
+impl Transform for KeepSubstitutions {
+    fn transform_substitution_reference(
+        &mut self,
+        e: e::SubstitutionReference,
+    ) -> impl Iterator<Item = TextOrInlineElement> {
+        let mut marker = e::Inline::default();
+        marker.classes_mut().push(SUBSTITUTION_CLASS.to_string());
+        marker.names_mut().extend(e.extra().refname.iter().cloned());
+        marker.children_mut().push(e.into());
+        once(marker.into())
+    }
+
+    fn transform_substitution_definition(
+        &mut self,
+        e: e::SubstitutionDefinition,
+    ) -> impl Iterator<Item = BodyElement> {
+        let directive = match e.children().as_slice() {
+            [TextOrInlineElement::ImageInline(image)] => {
+                let extra = image.extra();
+                let mut directive = format!("image:: {}", extra.uri.as_str());
+                if let Some(alt) = extra.alt.as_deref().map(str::trim) {
+                    directive.push_str(&format!("\n   :alt: {}", alt));
+                }
+                directive
+            }
+            children => {
+                let mut text = String::from("replace:: ");
+                for inline in convert_text_elements(children) {
+                    render_inline(&mut text, &inline);
+                }
+                text
+            }
+        };
+        for name in e.names() {
+            self.definitions.push(Block::Raw {
+                format: SourceFormat::ReStructuredText,
+                content: format!(".. |{}| {}", name.0, directive),
+                span: None,
+            });
+        }
+        once(e.into())
     }
 }
 
 /// Convert RST document to our AST
-fn convert_rst_document(doc: &RstDoc) -> Vec<Block> {
+fn convert_rst_document(doc: &RstDoc, flavor: RstFlavor) -> Vec<Block> {
     let mut blocks = Vec::new();
 
     for child in doc.children() {
-        convert_structural_element(&mut blocks, child);
+        convert_structural_element(&mut blocks, child, 0, flavor);
     }
 
     blocks
 }
 
 /// Convert a structural sub-element to blocks
-fn convert_structural_element(blocks: &mut Vec<Block>, element: &StructuralSubElement) {
+///
+/// RST heading levels come from section nesting rather than the underline
+/// character, so `level` tracks the depth of the enclosing section.
+fn convert_structural_element(
+    blocks: &mut Vec<Block>,
+    element: &StructuralSubElement,
+    level: u8,
+    flavor: RstFlavor,
+) {
     match element {
         StructuralSubElement::Title(title) => {
             let inlines = convert_text_elements(title.children());
             blocks.push(Block::Heading {
-                level: 1,
+                level: level.max(1),
                 content: inlines,
                 id: None,
+                classes: Vec::new(),
                 span: None,
             });
         }
         StructuralSubElement::Subtitle(subtitle) => {
             let inlines = convert_text_elements(subtitle.children());
             blocks.push(Block::Heading {
-                level: 2,
+                level: level.max(1) + 1,
                 content: inlines,
                 id: None,
+                classes: Vec::new(),
                 span: None,
             });
         }
         StructuralSubElement::SubStructure(sub) => {
-            convert_substructure(blocks, sub);
+            convert_substructure(blocks, sub, level, flavor);
         }
         _ => {}
     }
 }
 
 /// Convert a SubStructure element
-fn convert_substructure(blocks: &mut Vec<Block>, sub: &SubStructure) {
+fn convert_substructure(blocks: &mut Vec<Block>, sub: &SubStructure, level: u8, flavor: RstFlavor) {
     match sub {
         SubStructure::BodyElement(be) => {
-            if let Some(block) = convert_body_element(be) {
-                blocks.push(block);
-            }
+            blocks.extend(convert_body_elements([be.as_ref()], flavor));
         }
         SubStructure::Section(section) => {
             for child in section.children() {
-                convert_structural_element(blocks, child);
+                convert_structural_element(blocks, child, level + 1, flavor);
             }
         }
         SubStructure::Transition(_) => {
@@ -117,11 +293,58 @@ fn convert_substructure(blocks: &mut Vec<Block>, sub: &SubStructure) {
     }
 }
 
+/// Convert body elements to blocks
+///
+/// Sphinx directives, which the parser leaves as comments, can stand for
+/// any number of blocks.
+fn convert_body_elements<'a>(
+    elements: impl IntoIterator<Item = &'a BodyElement>,
+    flavor: RstFlavor,
+) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for element in elements {
+        match element {
+            BodyElement::Comment(comment) => {
+                let text = extract_text_content(comment.children());
+                if let Some(container) = container(&text, flavor) {
+                    blocks.push(container);
+                    continue;
+                }
+                if flavor != RstFlavor::Sphinx {
+                    continue;
+                }
+                blocks.extend(
+                    sphinx::directive(&text, parse_sphinx_content)
+                        .into_iter()
+                        .flatten(),
+                );
+            }
+            element => blocks.extend(convert_body_element(element, flavor)),
+        }
+    }
+    blocks
+}
+
+/// The block for a `container` directive, which the parser leaves as a
+/// comment like any other; its argument gives the classes
+fn container(comment: &str, flavor: RstFlavor) -> Option<Block> {
+    let (first, body) = comment.split_once('\n').unwrap_or((comment, ""));
+    let classes = first.trim().strip_prefix("container::")?;
+    Some(Block::Container {
+        id: None,
+        classes: classes.split_whitespace().map(String::from).collect(),
+        attributes: Default::default(),
+        content: parse_content(body.trim_matches('\n'), flavor),
+        span: None,
+    })
+}
+
 /// Convert a body element to a block
-fn convert_body_element(element: &BodyElement) -> Option<Block> {
+fn convert_body_element(element: &BodyElement, flavor: RstFlavor) -> Option<Block> {
     match element {
         BodyElement::Paragraph(p) => {
             let inlines = convert_text_elements(p.children());
+            let inlines = line_block(&inlines).unwrap_or(inlines);
             Some(Block::Paragraph {
                 content: inlines,
                 span: None,
@@ -141,19 +364,34 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
 
         BodyElement::BlockQuote(bq) => {
             let mut inner_blocks = Vec::new();
+            let mut attribution = None;
             for child in bq.children() {
                 match child {
                     document_tree::element_categories::SubBlockQuote::BodyElement(be) => {
-                        if let Some(block) = convert_body_element(be) {
-                            inner_blocks.push(block);
+                        inner_blocks.extend(convert_body_elements([be.as_ref()], flavor));
+                    }
+                    document_tree::element_categories::SubBlockQuote::Attribution(a) => {
+                        attribution = Some(convert_text_elements(a.children()));
+                    }
+                }
+            }
+            if attribution.is_none() {
+                // An escaped dash is a string of its own
+                if let Some(Block::Paragraph { content, .. }) = inner_blocks.last_mut() {
+                    if let [Inline::Text { content: dash }, Inline::Text { content: rest }, ..] =
+                        content.as_mut_slice()
+                    {
+                        if dash == "-" {
+                            rest.insert(0, '-');
+                            content.remove(0);
                         }
                     }
-                    _ => {}
                 }
+                attribution = super::split_attribution(&mut inner_blocks);
             }
             Some(Block::BlockQuote {
                 content: inner_blocks,
-                attribution: None,
+                attribution,
                 admonition: None,
                 span: None,
             })
@@ -164,11 +402,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
                 .children()
                 .iter()
                 .filter_map(|item| {
-                    let item_blocks: Vec<Block> = item
-                        .children()
-                        .iter()
-                        .filter_map(|child| convert_body_element(child))
-                        .collect();
+                    let item_blocks = convert_body_elements(item.children(), flavor);
 
                     Some(ListItem {
                         content: item_blocks,
@@ -191,11 +425,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
                 .children()
                 .iter()
                 .filter_map(|item| {
-                    let item_blocks: Vec<Block> = item
-                        .children()
-                        .iter()
-                        .filter_map(|child| convert_body_element(child))
-                        .collect();
+                    let item_blocks = convert_body_elements(item.children(), flavor);
 
                     Some(ListItem {
                         content: item_blocks,
@@ -214,11 +444,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Note(n) => {
-            let inner_blocks: Vec<Block> = n
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(n.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -228,11 +454,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Warning(w) => {
-            let inner_blocks: Vec<Block> = w
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(w.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -242,11 +464,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Tip(t) => {
-            let inner_blocks: Vec<Block> = t
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(t.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -256,11 +474,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Important(i) => {
-            let inner_blocks: Vec<Block> = i
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(i.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -270,11 +484,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Caution(c) => {
-            let inner_blocks: Vec<Block> = c
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(c.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -284,11 +494,7 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
         }
 
         BodyElement::Danger(d) => {
-            let inner_blocks: Vec<Block> = d
-                .children()
-                .iter()
-                .filter_map(convert_body_element)
-                .collect();
+            let inner_blocks = convert_body_elements(d.children(), flavor);
             Some(Block::BlockQuote {
                 content: inner_blocks,
                 attribution: None,
@@ -297,6 +503,20 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
             })
         }
 
+        BodyElement::Image(image) => {
+            let extra = image.extra();
+            Some(Block::Paragraph {
+                content: vec![Inline::Image {
+                    url: extra.uri.as_str().to_string(),
+                    alt: extra.alt.as_deref().unwrap_or_default().trim().to_string(),
+                    title: None,
+                    width: None,
+                    height: None,
+                }],
+                span: None,
+            })
+        }
+
         BodyElement::MathBlock(m) => {
             let content = m
                 .children()
@@ -315,6 +535,37 @@ fn convert_body_element(element: &BodyElement) -> Option<Block> {
     }
 }
 
+/// The lines of a line block, with `| ` at the start of every line, joined
+/// with hard breaks
+///
+/// The parser reads a line block as a paragraph, each newline becoming a
+/// `" "` string of its own.
+fn line_block(inlines: &[Inline]) -> Option<Vec<Inline>> {
+    let starts_line = |inline: Option<&Inline>| matches!(inline, Some(Inline::Text { content }) if content.starts_with("| "));
+    if !starts_line(inlines.first()) {
+        return None;
+    }
+
+    let mut lines = Vec::with_capacity(inlines.len());
+    let mut line_start = true;
+    for (i, inline) in inlines.iter().enumerate() {
+        match inline {
+            Inline::Text { content } if line_start => {
+                lines.push(Inline::Text {
+                    content: content["| ".len()..].to_string(),
+                });
+                line_start = false;
+            }
+            Inline::Text { content } if content == " " && starts_line(inlines.get(i + 1)) => {
+                lines.push(Inline::LineBreak);
+                line_start = true;
+            }
+            _ => lines.push(inline.clone()),
+        }
+    }
+    Some(lines)
+}
+
 /// Convert TextOrInlineElement list to our Inline types
 fn convert_text_elements(elements: &[TextOrInlineElement]) -> Vec<Inline> {
     let mut inlines = Vec::new();
@@ -348,12 +599,17 @@ fn convert_text_elements(elements: &[TextOrInlineElement]) -> Vec<Inline> {
             }
             TextOrInlineElement::Reference(r) => {
                 let content = convert_text_elements(r.children());
-                // RST references - use the first name as URL for now
+                // Embedded URIs are resolved; named references stay empty
+                let refuri = r.extra().refuri.as_ref();
                 inlines.push(Inline::Link {
-                    url: String::new(), // Will be resolved by transforms
+                    url: refuri.map(|u| u.as_str().to_string()).unwrap_or_default(),
                     title: None,
                     content,
-                    link_type: LinkType::Reference,
+                    link_type: if refuri.is_some() {
+                        LinkType::Inline
+                    } else {
+                        LinkType::Reference
+                    },
                 });
             }
             TextOrInlineElement::Superscript(sup) => {
@@ -376,6 +632,32 @@ fn convert_text_elements(elements: &[TextOrInlineElement]) -> Vec<Inline> {
                     notation: MathNotation::LaTeX,
                 });
             }
+            // Images reach paragraphs through substitutions
+            TextOrInlineElement::ImageInline(image) => {
+                let extra = image.extra();
+                inlines.push(Inline::Image {
+                    url: extra.uri.as_str().to_string(),
+                    alt: extra.alt.as_deref().unwrap_or_default().trim().to_string(),
+                    title: None,
+                    width: None,
+                    height: None,
+                });
+            }
+            TextOrInlineElement::Inline(i)
+                if i.classes().iter().any(|class| class == SUBSTITUTION_CLASS) =>
+            {
+                let name = i.names().first().map(|name| name.0.clone());
+                inlines.push(Inline::Span {
+                    id: None,
+                    classes: vec![SUBSTITUTION_CLASS.to_string()],
+                    attributes: name.into_iter().map(|n| ("name".to_string(), n)).collect(),
+                    content: convert_text_elements(i.children()),
+                });
+            }
+            // An undefined substitution, left as `|name|`
+            TextOrInlineElement::Problematic(p) => {
+                inlines.extend(convert_text_elements(p.children()));
+            }
             _ => {}
         }
     }
@@ -400,22 +682,40 @@ fn format(&self) -> SourceFormat {
         SourceFormat::ReStructuredText
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
-        let mut output = String::new();
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::ReStructuredText);
+        let output = render_blocks(&doc.content, 0, &config.rst_style());
 
-        for (i, block) in doc.content.iter().enumerate() {
-            if i > 0 {
-                output.push_str("\n\n");
-            }
-            render_block(&mut output, block, 0);
-        }
-
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
-fn render_block(output: &mut String, block: &Block, _depth: usize) {
+fn render_block(output: &mut String, block: &Block, depth: usize, style: &RstStyle) {
     match block {
+        // An image on its own at the top level is a block-level directive
+        Block::Paragraph { content, .. }
+            if depth == 0 && matches!(content.as_slice(), [Inline::Image { .. }]) =>
+        {
+            if let [Inline::Image { url, alt, .. }] = content.as_slice() {
+                output.push_str(&format!(".. image:: {}", url));
+                if !alt.is_empty() {
+                    output.push_str(&format!("\n   :alt: {}", alt));
+                }
+            }
+        }
+
+        // Hard breaks only survive in a line block
+        Block::Paragraph { content, .. }
+            if content.iter().any(|i| matches!(i, Inline::LineBreak)) =>
+        {
+            let mut lines = String::new();
+            for inline in content {
+                render_inline(&mut lines, inline);
+            }
+            output.push_str("| ");
+            output.push_str(&lines.replace('\n', "\n| "));
+        }
+
         Block::Paragraph { content, .. } => {
             for inline in content {
                 render_inline(output, inline);
@@ -442,19 +742,16 @@ fn render_block(output: &mut String, block: &Block, _depth: usize) {
             content, language, ..
         } => {
             if let Some(lang) = language {
-                output.push_str(&format!(".. code-block:: {}\n\n", lang));
+                output.push_str(&format!(".. {}:: {}\n\n", style.code_directive, lang));
             } else {
                 output.push_str("::\n\n");
             }
-            for line in content.lines() {
-                output.push_str("   ");
-                output.push_str(line);
-                output.push('\n');
-            }
+            push_indented(output, content, "   ");
         }
 
         Block::BlockQuote {
             content,
+            attribution,
             admonition,
             ..
         } => {
@@ -469,37 +766,38 @@ fn render_block(output: &mut String, block: &Block, _depth: usize) {
                     AdmonitionType::Custom => "admonition",
                 };
                 output.push_str(&format!(".. {}::\n\n", directive));
-                for block in content {
-                    output.push_str("   ");
-                    render_block(output, block, 1);
-                    output.push('\n');
-                }
-            } else {
-                for block in content {
-                    output.push_str("   ");
-                    render_block(output, block, 1);
-                    output.push('\n');
-                }
             }
+            // An em dash paragraph closing a quote is its attribution
+            let content = super::quote_with_attribution(content, attribution.as_ref());
+            push_indented(output, &render_blocks(&content, depth + 1, style), "   ");
         }
 
         Block::List { kind, items, .. } => {
             for (i, item) in items.iter().enumerate() {
-                match kind {
-                    ListKind::Bullet => output.push_str("* "),
-                    ListKind::Ordered => output.push_str(&format!("{}. ", i + 1)),
-                    ListKind::Task => {
-                        let checked = item.checked.unwrap_or(false);
-                        output.push_str(if checked { "[x] " } else { "[ ] " });
-                    }
+                if i > 0 {
+                    output.push('\n');
                 }
-                for (j, block) in item.content.iter().enumerate() {
-                    if j > 0 {
-                        output.push_str("\n   ");
-                    }
-                    render_block(output, block, 0);
+                // RST has no task lists, so the checkbox stays in the text
+                let marker = match kind {
+                    ListKind::Bullet => "* ".to_string(),
+                    ListKind::Ordered => format!("{}. ", i + 1),
+                    ListKind::Task if item.checked == Some(true) => "* [x] ".to_string(),
+                    ListKind::Task => "* [ ] ".to_string(),
+                };
+                output.push_str(&marker);
+                let body = render_blocks(&item.content, depth + 1, style);
+                let (first, rest) = body.split_once('\n').unwrap_or((&body, ""));
+                output.push_str(first);
+                if !rest.is_empty() {
+                    output.push('\n');
+                    // Continuation lines align with the item text
+                    let width = if matches!(kind, ListKind::Task) {
+                        2
+                    } else {
+                        marker.len()
+                    };
+                    push_indented(output, rest, &" ".repeat(width));
                 }
-                output.push('\n');
             }
         }
 
@@ -509,17 +807,89 @@ fn render_block(output: &mut String, block: &Block, _depth: usize) {
 
         Block::MathBlock { content, .. } => {
             output.push_str(".. math::\n\n");
-            for line in content.lines() {
-                output.push_str("   ");
-                output.push_str(line);
+            push_indented(output, content, "   ");
+        }
+
+        Block::Comment { content, .. } => {
+            output.push_str("..\n");
+            push_indented(output, content, "   ");
+        }
+
+        Block::Raw { content, .. } => {
+            output.push_str(content.trim_end_matches('\n'));
+        }
+
+        Block::DefinitionList { items, .. } => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push_str("\n\n");
+                }
+                for inline in &item.term {
+                    render_inline(output, inline);
+                }
+                let blocks: Vec<Block> = item.definitions.iter().flatten().cloned().collect();
                 output.push('\n');
+                push_indented(output, &render_blocks(&blocks, depth, style), "   ");
             }
         }
 
+        Block::Container {
+            classes, content, ..
+        } => {
+            output.push_str(".. container::");
+            for class in classes {
+                output.push(' ');
+                output.push_str(class);
+            }
+            let body = render_blocks(content, depth, style);
+            if !body.is_empty() {
+                output.push_str("\n\n");
+                push_indented(output, &body, "   ");
+            }
+        }
+
+        Block::Details {
+            summary, content, ..
+        } => {
+            let blocks = super::unfold_details(summary.as_ref(), content);
+            output.push_str(&render_blocks(&blocks, depth, style));
+        }
+
         _ => {}
     }
 }
 
+/// Render blocks separated by blank lines
+fn render_blocks(blocks: &[Block], depth: usize, style: &RstStyle) -> String {
+    let mut output = String::new();
+    for block in blocks {
+        // Blocks with no RST rendering are skipped without leaving a gap
+        let mut rendered = String::new();
+        render_block(&mut rendered, block, depth, style);
+        if rendered.is_empty() {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        output.push_str(&rendered);
+    }
+    output
+}
+
+/// Append `text` with every non-empty line indented by `indent`
+fn push_indented(output: &mut String, text: &str, indent: &str) {
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        if !line.is_empty() {
+            output.push_str(indent);
+            output.push_str(line);
+        }
+    }
+}
+
 fn render_inline(output: &mut String, inline: &Inline) {
     match inline {
         Inline::Text { content } => output.push_str(content),
@@ -546,6 +916,13 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push_str("``");
         }
 
+        // Sphinx roles
+        Inline::Keyboard { content } => output.push_str(&format!(":kbd:`{}`", content)),
+        Inline::Button { content } => output.push_str(&format!(":guilabel:`{}`", content)),
+        Inline::Menu { items } => {
+            output.push_str(&format!(":menuselection:`{}`", items.join(" --> ")))
+        }
+
         Inline::Link { url, content, .. } => {
             output.push('`');
             for i in content {
@@ -556,8 +933,9 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push_str(">`_");
         }
 
+        // Images cannot appear inside a paragraph, so link to them instead
         Inline::Image { url, alt, .. } => {
-            output.push_str(&format!(".. image:: {}\n   :alt: {}", url, alt));
+            output.push_str(&format!("`{} <{}>`_", alt, url));
         }
 
         Inline::Math { content, .. } => {
@@ -590,6 +968,32 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push(' ');
         }
 
+        Inline::NonBreakingSpace => {
+            output.push('\u{a0}');
+        }
+
+        Inline::Span {
+            classes,
+            attributes,
+            ..
+        } if classes.iter().any(|class| class == SUBSTITUTION_CLASS)
+            && attributes.contains_key("name") =>
+        {
+            output.push_str(&format!("|{}|", attributes["name"]));
+        }
+
+        // No RST equivalent: keep the text, drop the styling
+        Inline::Strikethrough { content }
+        | Inline::Underline { content }
+        | Inline::SmallCaps { content }
+        | Inline::Highlight { content }
+        | Inline::Span { content, .. }
+        | Inline::Quoted { content, .. } => {
+            for i in content {
+                render_inline(output, i);
+            }
+        }
+
         _ => {}
     }
 }
@@ -622,6 +1026,9 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "directive"
                 | "role"
                 | "math"
+                | "container"
+                | "emoji"
+                | "nested_quote"
         )
     }
 
@@ -640,8 +1047,16 @@ fn supported_features(&self) -> &[&str] {
             "directive",
             "role",
             "math",
+            "container",
+            "emoji",
+            "nested_quote",
         ]
     }
+
+    fn normalization_exceptions(&self) -> &[&str] {
+        // Footnotes are not rendered and the parser does not read `.. math::`
+        &["footnote", "math"]
+    }
 }
 
 #[cfg(test)]
@@ -667,6 +1082,7 @@ fn test_render_heading() {
                     content: "Title".to_string(),
                 }],
                 id: None,
+                classes: Vec::new(),
                 span: None,
             }],
             raw_source: None,
@@ -676,4 +1092,160 @@ fn test_render_heading() {
         assert!(output.contains("Title"));
         assert!(output.contains("====="));
     }
+
+    #[test]
+    fn test_image_directive() {
+        let handler = RstHandler::new();
+        let doc = handler
+            .parse(
+                ".. image:: img/a.png\n   :alt: A picture\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert!(matches!(
+            doc.content.as_slice(),
+            [Block::Paragraph { content, .. }]
+                if matches!(content.as_slice(), [Inline::Image { url, alt, .. }]
+                    if url == "img/a.png" && alt == "A picture")
+        ));
+    }
+
+    #[test]
+    fn test_code_directive_profile() {
+        use crate::traits::StyleProfile;
+
+        let handler = RstHandler::new();
+        let doc = Document {
+            source_format: SourceFormat::ReStructuredText,
+            meta: DocumentMeta::default(),
+            content: vec![Block::CodeBlock {
+                language: Some("rust".to_string()),
+                content: "x".to_string(),
+                line_numbers: false,
+                highlight_lines: Vec::new(),
+                span: None,
+            }],
+            raw_source: None,
+        };
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.starts_with(".. code-block:: rust"));
+        let config = RenderConfig {
+            profile: Some(StyleProfile::Pandoc),
+            ..Default::default()
+        };
+        let output = handler.render(&doc, &config).unwrap();
+        assert!(output.starts_with(".. code:: rust"));
+    }
+
+    #[test]
+    fn test_quote_attribution() {
+        let handler = RstHandler::new();
+        let doc = handler
+            .parse(
+                "Intro.\n\n   Quoted.\n\n   -- Author\n\nSQL::\n\n   -- a comment\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert!(matches!(
+            &doc.content[1],
+            Block::BlockQuote { content, attribution: Some(attribution), .. }
+                if content.len() == 1
+                    && matches!(attribution.as_slice(), [Inline::Text { content }] if content == "Author")
+        ));
+        // Lines after a `::` are left unescaped
+        let last = serde_json::to_string(doc.content.last().unwrap()).unwrap();
+        assert!(last.contains("\"-- a comment\""), "{}", last);
+    }
+
+    #[test]
+    fn test_container_directive() {
+        let handler = RstHandler::new();
+        let input = "Intro.\n\n.. container:: aside wide\n\n   First.\n\n   Second.";
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+        assert!(matches!(
+            &doc.content[1],
+            Block::Container { classes, content, .. }
+                if classes == &["aside", "wide"] && content.len() == 2
+        ));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let handler = RstHandler::new();
+        let input = "Built with |project| by |logo| and |missing|.\n\n\
+                     .. |project| replace:: *docmatrix* tools\n\n\
+                     .. |logo| image:: logo.png\n   :alt: Logo\n";
+
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+        assert_eq!(doc.content.len(), 1);
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph");
+        };
+        assert!(matches!(&content[1], Inline::Emphasis { .. }));
+        assert!(content.iter().any(|inline| matches!(inline,
+            Inline::Image { url, alt, .. } if url == "logo.png" && alt == "Logo")));
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output,
+            "Built with *docmatrix* tools by `Logo <logo.png>`_ and |missing|."
+        );
+
+        // Kept, they are written back as they were
+        let config = ParseConfig {
+            keep_substitutions: true,
+            ..Default::default()
+        };
+        let doc = handler.parse(input, &config).unwrap();
+        assert_eq!(doc.content.len(), 3);
+        assert!(matches!(&doc.content[0], Block::Paragraph { content, .. }
+            if matches!(&content[1], Inline::Span { classes, attributes, content, .. }
+                if classes == &[SUBSTITUTION_CLASS]
+                    && attributes["name"] == "project"
+                    && content.len() == 2)));
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, input.trim_end());
+    }
+
+    #[test]
+    fn test_sphinx_flavor() {
+        let handler = RstHandler::new();
+        let input = "See :doc:`intro`, :py:func:`~pkg.mod.run` and :kbd:`Ctrl+C`.\n\n\
+                     .. toctree::\n   :maxdepth: 2\n\n   intro\n   usage\n\n\
+                     .. versionadded:: 2.1\n   The ``run`` function.\n\n\
+                     .. code-block:: python\n   :linenos:\n\n   print(1)\n\n\
+                     .. index:: run\n";
+        let config = ParseConfig {
+            rst_flavor: RstFlavor::Sphinx,
+            ..Default::default()
+        };
+
+        let doc = handler.parse(input, &config).unwrap();
+        assert_eq!(doc.content.len(), 4);
+        let Block::Paragraph { content, .. } = &doc.content[0] else {
+            panic!("expected a paragraph");
+        };
+        assert!(matches!(&content[1], Inline::Link { url, .. } if url == "intro"));
+        assert!(matches!(&content[3], Inline::Code { content, .. } if content == "run()"));
+        assert!(matches!(&content[5], Inline::Keyboard { content } if content == "Ctrl+C"));
+        assert!(matches!(&doc.content[1], Block::List { items, .. } if items.len() == 2));
+        assert!(matches!(
+            &doc.content[2],
+            Block::BlockQuote {
+                admonition: Some(AdmonitionType::Note),
+                ..
+            }
+        ));
+        assert!(
+            matches!(&doc.content[3], Block::CodeBlock { language: Some(language), line_numbers: true, .. }
+            if language == "python")
+        );
+
+        // Docutils reads roles as text and drops the directives as comments
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+        assert_eq!(doc.content.len(), 1);
+    }
 }
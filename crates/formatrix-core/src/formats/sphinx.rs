@@ -0,0 +1,537 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Sphinx roles and directives
+//!
+//! Sphinx adds roles such as `:doc:` and `:py:func:` and directives such as
+//! `toctree` and `versionadded` to reStructuredText, which a docutils reader
+//! leaves as plain text and comments. With
+//! [`RstFlavor::Sphinx`](crate::traits::RstFlavor) the RST handler reads them
+//! as links, code, keys, admonitions and lists instead. Directives that only
+//! steer Sphinx's own output are left out, and roles and directives with no
+//! equivalent are kept as raw reStructuredText.
+
+use crate::ast::{
+    AdmonitionType, Block, Inline, LinkType, ListItem, ListKind, MathNotation, SourceFormat,
+};
+use crate::edit::children_mut;
+
+/// Directives for Sphinx's own indexes and highlighting, left out
+const DROPPED_DIRECTIVES: &[&str] = &[
+    "index",
+    "highlight",
+    "tabularcolumns",
+    "currentmodule",
+    "module",
+    "py:module",
+    "py:currentmodule",
+    "sectionauthor",
+    "codeauthor",
+    "default-role",
+    "default-domain",
+];
+
+/// Roles for objects in the default domain, read as code
+const OBJECT_ROLES: &[&str] = &[
+    "func", "meth", "class", "mod", "attr", "exc", "data", "obj", "const", "any",
+];
+
+/// Roles for literal text, read as code
+const LITERAL_ROLES: &[&str] = &[
+    "code",
+    "literal",
+    "samp",
+    "file",
+    "command",
+    "program",
+    "envvar",
+    "option",
+    "makevar",
+    "mailheader",
+    "regexp",
+    "token",
+    "keyword",
+];
+
+/// Read the Sphinx roles in the text of `blocks`
+pub(crate) fn parse_roles(blocks: &mut [Block]) {
+    for block in blocks {
+        for group in block.inline_groups_mut() {
+            parse_inlines(group);
+        }
+        for child in block.child_blocks_mut() {
+            parse_roles(std::slice::from_mut(child));
+        }
+    }
+}
+
+fn parse_inlines(inlines: &mut Vec<Inline>) {
+    let mut parsed = Vec::with_capacity(inlines.len());
+    for mut inline in inlines.drain(..) {
+        if let Inline::Text { content } = &inline {
+            parsed.extend(split_roles(content));
+            continue;
+        }
+        if let Some(children) = children_mut(&mut inline) {
+            parse_inlines(children);
+        }
+        parsed.push(inline);
+    }
+    *inlines = parsed;
+}
+
+/// `text` as text and the nodes of the roles in it
+fn split_roles(text: &str) -> Vec<Inline> {
+    let mut out = Vec::new();
+    // Start of the text not yet taken, and of the search for the next role
+    let mut plain = 0;
+    let mut search = 0;
+    while let Some(open) = text[search..].find(':').map(|i| search + i) {
+        search = open + 1;
+        // A role starts a word
+        if text[..open].ends_with(|c: char| c.is_alphanumeric()) {
+            continue;
+        }
+        let Some((name, content, len)) = role_at(&text[open..]) else {
+            continue;
+        };
+        if open > plain {
+            out.push(Inline::Text {
+                content: text[plain..open].to_string(),
+            });
+        }
+        out.push(role(name, content));
+        plain = open + len;
+        search = plain;
+    }
+    if plain < text.len() || out.is_empty() {
+        out.push(Inline::Text {
+            content: text[plain..].to_string(),
+        });
+    }
+    out
+}
+
+/// The name and content of the role at the start of `text`, and its length
+fn role_at(text: &str) -> Option<(&str, &str, usize)> {
+    let rest = &text[1..];
+    let name_len =
+        rest.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_' | '.')))?;
+    let name = rest[..name_len].strip_suffix(':')?;
+    if name.is_empty() || name.starts_with(':') || name.contains("::") {
+        return None;
+    }
+    let content_start = 1 + name_len + 1;
+    if !rest[name_len..].starts_with('`') {
+        return None;
+    }
+    let content_len = text[content_start..].find('`')?;
+    let content = &text[content_start..content_start + content_len];
+    if content.trim().is_empty() {
+        return None;
+    }
+    Some((name, content, content_start + content_len + 1))
+}
+
+/// The title and target of a role's content: `Title <target>`, or just
+/// the target
+fn title_and_target(content: &str) -> (Option<&str>, &str) {
+    if let Some(before) = content.strip_suffix('>') {
+        if let Some((title, target)) = before.rsplit_once('<') {
+            if title.ends_with(char::is_whitespace) {
+                return (Some(title.trim()), target.trim());
+            }
+        }
+    }
+    (None, content.trim())
+}
+
+fn text(content: &str) -> Vec<Inline> {
+    vec![Inline::Text {
+        content: content.to_string(),
+    }]
+}
+
+fn link(content: &str, url: String) -> Inline {
+    Inline::Link {
+        url,
+        title: None,
+        content: text(content),
+        link_type: LinkType::Inline,
+    }
+}
+
+/// The node for a role
+fn role(name: &str, content: &str) -> Inline {
+    let (title, target) = title_and_target(content);
+    let shown = title.unwrap_or(target);
+    match name {
+        "doc" | "download" => link(shown, target.to_string()),
+        "ref" | "numref" => link(shown, format!("#{}", target)),
+        "term" => Inline::Text {
+            content: shown.to_string(),
+        },
+        // `LIFO (last-in, first-out)` shows the abbreviation
+        "abbr" => Inline::Text {
+            content: content
+                .split_once(" (")
+                .map_or(content, |(abbr, _)| abbr)
+                .to_string(),
+        },
+        "kbd" => Inline::Keyboard {
+            content: content.to_string(),
+        },
+        // `&` marks the accelerator key
+        "guilabel" => Inline::Button {
+            content: content.replace('&', ""),
+        },
+        "menuselection" => Inline::Menu {
+            items: content
+                .split("-->")
+                .map(|item| item.trim().replace('&', ""))
+                .collect(),
+        },
+        "math" => Inline::Math {
+            content: content.to_string(),
+            notation: MathNotation::LaTeX,
+        },
+        "sup" => Inline::Superscript {
+            content: text(content),
+        },
+        "sub" => Inline::Subscript {
+            content: text(content),
+        },
+        "emphasis" | "dfn" | "title-reference" | "title" | "t" => Inline::Emphasis {
+            content: text(content),
+        },
+        "strong" => Inline::Strong {
+            content: text(content),
+        },
+        name if LITERAL_ROLES.contains(&name) => Inline::Code {
+            content: content.to_string(),
+            language: None,
+        },
+        // Objects in any domain, such as `:py:func:` or `:c:macro:`
+        name if name.contains(':') || OBJECT_ROLES.contains(&name) => {
+            let role = name.rsplit(':').next().unwrap_or(name);
+            Inline::Code {
+                content: object_name(role, title, target),
+                language: None,
+            }
+        }
+        name => Inline::RawInline {
+            format: SourceFormat::ReStructuredText,
+            content: format!(":{}:`{}`", name, content),
+        },
+    }
+}
+
+/// An object's name as Sphinx shows it: `~` keeps the last component,
+/// `!` only turns the link off, and functions get parentheses
+fn object_name(role: &str, title: Option<&str>, target: &str) -> String {
+    if let Some(title) = title {
+        return title.to_string();
+    }
+    let target = target.trim_start_matches('!');
+    let mut name = match target.strip_prefix('~') {
+        Some(path) => path.rsplit('.').next().unwrap_or(path).to_string(),
+        None => target.to_string(),
+    };
+    if matches!(role, "func" | "meth" | "function" | "method") && !name.ends_with(')') {
+        name.push_str("()");
+    }
+    name
+}
+
+/// The blocks for a directive a docutils reader leaves as a comment
+///
+/// `comment` is the comment's text: the directive's name and argument, then
+/// its options and content, unindented. `parse` reads content that is
+/// itself reStructuredText. `None` if the comment is not a directive.
+pub(crate) fn directive(comment: &str, parse: fn(&str) -> Vec<Block>) -> Option<Vec<Block>> {
+    let (first, rest) = comment.split_once('\n').unwrap_or((comment, ""));
+    let (name, argument) = first.split_once("::")?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    let argument = argument.trim();
+
+    // Options come first, then the content after a blank line
+    let mut options = Vec::new();
+    let mut lines = rest.lines().peekable();
+    while let Some(option) = lines.peek().and_then(|line| directive_option(line)) {
+        options.push(option);
+        lines.next();
+    }
+    let body = lines.collect::<Vec<_>>().join("\n");
+    let body = body.trim_matches('\n');
+    let option = |key: &str| options.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+
+    let blocks = match name {
+        name if DROPPED_DIRECTIVES.contains(&name) => Vec::new(),
+
+        // Hidden trees only place documents in the navigation
+        "toctree" if option("hidden").is_some() => Vec::new(),
+        "toctree" => {
+            let items = body
+                .lines()
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    let (title, target) = title_and_target(entry);
+                    let content = if target.contains('*') {
+                        text(entry)
+                    } else {
+                        vec![link(title.unwrap_or(target), target.to_string())]
+                    };
+                    ListItem {
+                        content: vec![Block::Paragraph {
+                            content,
+                            span: None,
+                        }],
+                        checked: None,
+                        marker: None,
+                    }
+                })
+                .collect();
+            let mut blocks: Vec<Block> = option("caption")
+                .map(|caption| Block::Paragraph {
+                    content: vec![Inline::Strong {
+                        content: text(caption),
+                    }],
+                    span: None,
+                })
+                .into_iter()
+                .collect();
+            blocks.push(Block::List {
+                kind: ListKind::Bullet,
+                items,
+                start: None,
+                span: None,
+            });
+            blocks
+        }
+
+        "versionadded" | "versionchanged" | "deprecated" | "versionremoved" => {
+            let (label, admonition) = match name {
+                "versionadded" => ("Added in version", AdmonitionType::Note),
+                "versionchanged" => ("Changed in version", AdmonitionType::Note),
+                "deprecated" => ("Deprecated since version", AdmonitionType::Warning),
+                _ => ("Removed in version", AdmonitionType::Warning),
+            };
+            let mut content = parse(body);
+            let lead = format!("{} {}", label, argument);
+            match content.first_mut() {
+                Some(Block::Paragraph { content, .. }) => {
+                    content.splice(
+                        0..0,
+                        [
+                            Inline::Emphasis {
+                                content: text(&format!("{}:", lead)),
+                            },
+                            Inline::Text {
+                                content: " ".to_string(),
+                            },
+                        ],
+                    );
+                }
+                _ => content.insert(
+                    0,
+                    Block::Paragraph {
+                        content: vec![Inline::Emphasis {
+                            content: text(&format!("{}.", lead)),
+                        }],
+                        span: None,
+                    },
+                ),
+            }
+            vec![Block::BlockQuote {
+                content,
+                attribution: None,
+                admonition: Some(admonition),
+                span: None,
+            }]
+        }
+
+        "seealso" => {
+            let mut content = vec![Block::Paragraph {
+                content: vec![Inline::Strong {
+                    content: text("See also"),
+                }],
+                span: None,
+            }];
+            content.extend(parse(&format!("{}\n\n{}", argument, body)));
+            vec![Block::BlockQuote {
+                content,
+                attribution: None,
+                admonition: Some(AdmonitionType::Note),
+                span: None,
+            }]
+        }
+
+        "rubric" => vec![Block::Paragraph {
+            content: vec![Inline::Strong {
+                content: text(argument),
+            }],
+            span: None,
+        }],
+
+        "code-block" | "sourcecode" => vec![Block::CodeBlock {
+            language: (!argument.is_empty()).then(|| argument.to_string()),
+            content: body.to_string(),
+            line_numbers: option("linenos").is_some(),
+            highlight_lines: option("emphasize-lines")
+                .map(line_numbers)
+                .unwrap_or_default(),
+            span: None,
+        }],
+
+        _ => {
+            let mut raw = format!(".. {}", first.trim_end());
+            for line in rest.trim_end().lines() {
+                raw.push('\n');
+                if !line.is_empty() {
+                    raw.push_str("   ");
+                    raw.push_str(line);
+                }
+            }
+            vec![Block::Raw {
+                format: SourceFormat::ReStructuredText,
+                content: raw,
+                span: None,
+            }]
+        }
+    };
+    Some(blocks)
+}
+
+/// The name and value of a `:name: value` option line
+fn directive_option(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.strip_prefix(':')?.split_once(':')?;
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((name, value.trim()))
+}
+
+/// Line numbers from a list such as `1,3-5`
+fn line_numbers(list: &str) -> Vec<u32> {
+    list.split(',')
+        .flat_map(|part| {
+            let part = part.trim();
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let first = first.trim().parse::<u32>().ok()?;
+            let last = last.trim().parse::<u32>().ok()?;
+            Some(first..=last)
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::RstHandler;
+    use crate::traits::{ParseConfig, Parser, RstFlavor};
+
+    fn parse(input: &str, flavor: RstFlavor) -> Vec<Block> {
+        let config = ParseConfig {
+            rst_flavor: flavor,
+            ..Default::default()
+        };
+        RstHandler::new().parse(input, &config).unwrap().content
+    }
+
+    fn inlines(block: &Block) -> &[Inline] {
+        match block {
+            Block::Paragraph { content, .. } => content,
+            block => panic!("expected a paragraph, got {:?}", block),
+        }
+    }
+
+    #[test]
+    fn test_roles() {
+        let input = "Read :doc:`the guide <guide/intro>`, :ref:`setup`, \
+                     :py:func:`~pkg.mod.run`, :py:class:`pkg.Thing`, :meth:`!Thing.go`, \
+                     :c:macro:`MAX` and :custom:`thing`.\n";
+        let blocks = parse(input, RstFlavor::Sphinx);
+        let content = inlines(&blocks[0]);
+        assert!(matches!(&content[1], Inline::Link { url, content, .. }
+            if url == "guide/intro"
+                && matches!(content.as_slice(), [Inline::Text { content }] if content == "the guide")));
+        assert!(matches!(&content[3], Inline::Link { url, .. } if url == "#setup"));
+        let code: Vec<&str> = content
+            .iter()
+            .filter_map(|inline| match inline {
+                Inline::Code { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(code, ["run()", "pkg.Thing", "Thing.go()", "MAX"]);
+        assert!(matches!(content.iter().rev().nth(1),
+            Some(Inline::RawInline { format: SourceFormat::ReStructuredText, content })
+                if content == ":custom:`thing`"));
+
+        // A plain reader keeps the roles as text
+        let blocks = parse(input, RstFlavor::Docutils);
+        assert!(matches!(inlines(&blocks[0]), [Inline::Text { content }]
+            if content.contains(":py:func:`~pkg.mod.run`")));
+    }
+
+    #[test]
+    fn test_directives() {
+        let input = ".. toctree::\n   :caption: Contents\n\n   intro\n   Usage <usage>\n\n\
+                     .. toctree::\n   :hidden:\n\n   changelog\n\n\
+                     .. versionadded:: 1.2\n   The ``run`` function.\n\n\
+                     .. deprecated:: 2.0\n\n\
+                     .. code-block:: rust\n   :emphasize-lines: 1,3-4\n\n   fn main() {}\n\n\
+                     .. index:: run\n\n\
+                     .. autoclass:: pkg.Thing\n   :members:\n";
+        let blocks = parse(input, RstFlavor::Sphinx);
+        assert_eq!(blocks.len(), 6, "{:#?}", blocks);
+
+        assert!(matches!(inlines(&blocks[0]), [Inline::Strong { .. }]));
+        let Block::List { items, .. } = &blocks[1] else {
+            panic!("expected a list, got {:?}", blocks[1]);
+        };
+        assert!(
+            matches!(inlines(&items[1].content[0]), [Inline::Link { url, .. }] if url == "usage")
+        );
+
+        let Block::BlockQuote {
+            content,
+            admonition: Some(AdmonitionType::Note),
+            ..
+        } = &blocks[2]
+        else {
+            panic!("expected a note, got {:?}", blocks[2]);
+        };
+        assert!(
+            matches!(&inlines(&content[0])[0], Inline::Emphasis { content }
+            if matches!(content.as_slice(), [Inline::Text { content }] if content == "Added in version 1.2:"))
+        );
+        assert!(matches!(&blocks[3], Block::BlockQuote {
+            admonition: Some(AdmonitionType::Warning),
+            content,
+            ..
+        } if content.len() == 1));
+
+        assert!(
+            matches!(&blocks[4], Block::CodeBlock { language: Some(language), content, highlight_lines, .. }
+            if language == "rust" && content == "fn main() {}" && highlight_lines == &[1, 3, 4])
+        );
+        assert!(matches!(&blocks[5], Block::Raw { content, .. }
+            if content == ".. autoclass:: pkg.Thing\n   :members:"));
+
+        // A plain reader drops them all as comments
+        assert!(parse(input, RstFlavor::Docutils).is_empty());
+    }
+
+    #[test]
+    fn test_object_name() {
+        assert_eq!(object_name("func", None, "~a.b.run"), "run()");
+        assert_eq!(object_name("meth", None, "!run()"), "run()");
+        assert_eq!(object_name("class", Some("Thing"), "a.Thing"), "Thing");
+        assert_eq!(line_numbers("2, 5-7, x"), [2, 5, 6, 7]);
+    }
+}
@@ -3,11 +3,20 @@
 //! Typst format handler using typst-syntax
 //! FD-S03: SHOULD requirement
 
+use super::{comment_text, leading_markers, line_nesting};
 use crate::ast::{
-    Block, Document, DocumentMeta, Inline, ListItem, ListKind, MathNotation, SourceFormat,
+    Block, ColumnAlignment, ColumnSpec, DefinitionItem, Document, Inline, LinkType, ListItem,
+    ListKind, MathNotation, MetaValue, SourceFormat, TableCell, TableRow, TextDirection,
 };
+use crate::frontmatter::FrontMatter;
+use crate::toolkit::inline_text;
 use crate::traits::{FormatHandler, ParseConfig, Parser, RenderConfig, Renderer, Result};
-use typst_syntax::{parse, SyntaxKind, SyntaxNode};
+use typst_syntax::ast::{self, AstNode, Expr};
+use typst_syntax::{parse, SyntaxNode};
+
+/// What would end a block comment early; Typst comments nest, so an
+/// opening one counts too
+const COMMENT_MARKERS: &[&str] = &["*/", "/*"];
 
 /// Typst format handler
 pub struct TypstHandler;
@@ -30,254 +39,644 @@ fn format(&self) -> SourceFormat {
     }
 
     fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+        config.limits.check_input(input)?;
+        config.limits.check_timeout(SourceFormat::Typst)?;
+        config.limits.check_source(input, source_depth)?;
+        let input = config.prepare_input(input);
+        let input = input.as_ref();
+
         let tree = parse(input);
-        let content = parse_syntax_tree(&tree);
+        let markup = tree.cast::<ast::Markup>();
+        let content = markup.map(convert_markup).unwrap_or_default();
+        let meta = markup.map(front_matter).unwrap_or_default().into_meta();
 
-        Ok(Document {
+        Ok(config.finish_document(Document {
             source_format: SourceFormat::Typst,
-            meta: DocumentMeta::default(),
+            meta,
             content,
             raw_source: if config.preserve_raw_source {
                 Some(input.to_string())
             } else {
                 None
             },
-        })
+        }))
     }
 }
 
-/// Parse the Typst syntax tree into our AST
-fn parse_syntax_tree(root: &SyntaxNode) -> Vec<Block> {
-    let mut blocks = Vec::new();
-    let mut current_text = String::new();
-
-    for child in root.children() {
-        match child.kind() {
-            SyntaxKind::Text => {
-                current_text.push_str(child.text());
+/// How deeply Typst source nests, which typst-syntax recurses into once
+/// per level: lists and indented blocks by line, and brackets and strong
+/// and emphasis markup by character
+fn source_depth(input: &str) -> usize {
+    let lines = line_nesting(input, |line| leading_markers(line, &["-", "+", "/"]));
+
+    let mut open: Vec<char> = Vec::new();
+    let mut deepest = 0;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let in_code = matches!(open.last(), Some('(' | '{'));
+        match c {
+            '\\' => {
+                chars.next();
             }
-
-            SyntaxKind::Space => {
-                if !current_text.is_empty() {
-                    current_text.push(' ');
+            '(' | '[' | '{' => open.push(c),
+            ')' | ']' | '}' => {
+                let opener = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if let Some(at) = open.iter().rposition(|&o| o == opener) {
+                    open.truncate(at);
                 }
             }
-
-            SyntaxKind::Parbreak => {
-                if !current_text.trim().is_empty() {
-                    blocks.push(Block::Paragraph {
-                        content: vec![Inline::Text {
-                            content: current_text.trim().to_string(),
-                        }],
-                        span: None,
-                    });
+            '*' | '_' if !in_code => {
+                if open.last() == Some(&c) {
+                    open.pop();
+                } else {
+                    open.push(c);
                 }
-                current_text.clear();
             }
+            // Markup and argument lists do not run past a paragraph
+            '\n' if chars.peek() == Some(&'\n') => open.retain(|&o| o == '[' || o == '{'),
+            _ => {}
+        }
+        deepest = deepest.max(open.len());
+    }
+    lines.max(1 + deepest)
+}
 
-            SyntaxKind::Heading => {
-                // Flush any pending text
-                if !current_text.trim().is_empty() {
-                    blocks.push(Block::Paragraph {
-                        content: vec![Inline::Text {
-                            content: current_text.trim().to_string(),
-                        }],
-                        span: None,
-                    });
-                    current_text.clear();
-                }
+/// Convert Typst markup into blocks, grouping inline runs into paragraphs
+fn convert_markup(markup: ast::Markup<'_>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut inlines = Vec::new();
+
+    for expr in markup.exprs() {
+        match expr {
+            Expr::Parbreak(_) => flush_paragraph(&mut inlines, &mut blocks),
+
+            Expr::Heading(heading) => {
+                flush_paragraph(&mut inlines, &mut blocks);
+                blocks.push(Block::Heading {
+                    level: heading.depth().get().min(6) as u8,
+                    content: convert_inlines(heading.body()),
+                    id: None,
+                    classes: Vec::new(),
+                    span: None,
+                });
+            }
 
-                // Parse heading
-                if let Some(heading) = parse_heading(&child) {
-                    blocks.push(heading);
-                }
+            Expr::List(item) => {
+                flush_paragraph(&mut inlines, &mut blocks);
+                push_list_item(&mut blocks, ListKind::Bullet, item.body());
             }
 
-            SyntaxKind::ListItem => {
-                // Handle list items
-                if let Some(item) = parse_list_item(&child) {
-                    // Check if we can append to existing list
-                    if let Some(Block::List { items, .. }) = blocks.last_mut() {
-                        items.push(item);
-                    } else {
-                        blocks.push(Block::List {
-                            kind: ListKind::Bullet,
-                            items: vec![item],
-                            start: None,
-                            span: None,
-                        });
-                    }
-                }
+            // Read into the document metadata instead
+            Expr::Set(set) if is_header_set(set) => {}
+
+            Expr::Enum(item) => {
+                flush_paragraph(&mut inlines, &mut blocks);
+                push_list_item(&mut blocks, ListKind::Ordered, item.body());
             }
 
-            SyntaxKind::EnumItem => {
-                if let Some(item) = parse_list_item(&child) {
-                    if let Some(Block::List {
-                        kind: ListKind::Ordered,
-                        items,
-                        ..
-                    }) = blocks.last_mut()
-                    {
-                        items.push(item);
-                    } else {
-                        blocks.push(Block::List {
-                            kind: ListKind::Ordered,
-                            items: vec![item],
-                            start: Some(1),
-                            span: None,
-                        });
-                    }
+            Expr::Term(item) => {
+                flush_paragraph(&mut inlines, &mut blocks);
+                let definition = DefinitionItem {
+                    term: convert_inlines(item.term()),
+                    definitions: vec![convert_markup(item.description())],
+                };
+                if let Some(Block::DefinitionList { items, .. }) = blocks.last_mut() {
+                    items.push(definition);
+                } else {
+                    blocks.push(Block::DefinitionList {
+                        items: vec![definition],
+                        span: None,
+                    });
                 }
             }
 
-            SyntaxKind::Raw => {
-                // Code block
-                let content = extract_raw_content(&child);
-                let language = extract_raw_language(&child);
+            Expr::Raw(raw) if raw.block() => {
+                flush_paragraph(&mut inlines, &mut blocks);
                 blocks.push(Block::CodeBlock {
-                    language,
-                    content,
+                    language: raw.lang().map(|l| l.get().to_string()),
+                    content: raw_text(raw),
                     line_numbers: false,
                     highlight_lines: Vec::new(),
                     span: None,
                 });
             }
 
-            SyntaxKind::Equation => {
-                // Math equation - store as MathBlock
-                let content = child.text().to_string();
-                // Remove leading/trailing $ if present
-                let content = content.trim_matches('$').trim().to_string();
+            Expr::Equation(eq) if eq.block() => {
+                flush_paragraph(&mut inlines, &mut blocks);
                 blocks.push(Block::MathBlock {
-                    content,
-                    notation: MathNotation::LaTeX,
+                    content: node_text(eq.body().to_untyped()).trim().to_string(),
+                    notation: MathNotation::Typst,
                     span: None,
                 });
             }
 
-            SyntaxKind::Strong => {
-                current_text.push_str(&format!("*{}*", extract_text(&child)));
-            }
-
-            SyntaxKind::Emph => {
-                current_text.push_str(&format!("_{}_", extract_text(&child)));
-            }
+            Expr::FuncCall(call) => match convert_block_call(call) {
+                Some(block) => {
+                    flush_paragraph(&mut inlines, &mut blocks);
+                    blocks.push(block);
+                }
+                None => inlines.push(convert_inline_call(call)),
+            },
 
-            SyntaxKind::Link => {
-                let url = extract_text(&child);
-                current_text.push_str(&url);
-            }
+            other => push_inline(&mut inlines, other),
+        }
+    }
 
-            SyntaxKind::Markup => {
-                // Recurse into markup content
-                let inner_blocks = parse_syntax_tree(&child);
-                blocks.extend(inner_blocks);
-            }
+    flush_paragraph(&mut inlines, &mut blocks);
+    blocks
+}
 
-            _ => {
-                // For other nodes, try to extract text
-                let text = child.text().to_string();
-                if !text.is_empty() && !text.trim().is_empty() {
-                    current_text.push_str(&text);
+/// Metadata from the top-level `#set document(..)` and `#set text(lang: ..)`
+/// rules; a `dir` in the latter gives the base direction
+fn front_matter(markup: ast::Markup<'_>) -> FrontMatter {
+    let mut fields = Vec::new();
+    for expr in markup.exprs() {
+        let Expr::Set(set) = expr else { continue };
+        if !is_header_set(set) {
+            continue;
+        }
+        for arg in set.args().items() {
+            if let ast::Arg::Named(named) = arg {
+                if let Some(value) = meta_value(named.expr()) {
+                    fields.push((named.name().as_str().to_string(), value));
                 }
             }
         }
     }
+    FrontMatter::from_fields(fields)
+}
 
-    // Flush remaining text
-    if !current_text.trim().is_empty() {
-        blocks.push(Block::Paragraph {
-            content: vec![Inline::Text {
-                content: current_text.trim().to_string(),
-            }],
-            span: None,
-        });
+/// `#set document(..)`, or `#set text(..)` with only `lang` and `dir` in it
+fn is_header_set(set: ast::SetRule<'_>) -> bool {
+    let Expr::Ident(target) = set.target() else {
+        return false;
+    };
+    match target.as_str() {
+        "document" => true,
+        "text" => {
+            set.args().items().next().is_some()
+                && set.args().items().all(|arg| {
+                    matches!(arg, ast::Arg::Named(n) if matches!(n.name().as_str(), "lang" | "dir"))
+                })
+        }
+        _ => false,
     }
+}
 
-    blocks
+fn meta_value(expr: Expr<'_>) -> Option<MetaValue> {
+    Some(match expr {
+        Expr::Str(s) => MetaValue::String(s.get().to_string()),
+        Expr::Bool(b) => MetaValue::Bool(b.get()),
+        Expr::Int(i) => MetaValue::Integer(i.get()),
+        Expr::Float(f) => MetaValue::Float(f.get()),
+        // Values such as `rtl`
+        Expr::Ident(ident) => MetaValue::String(ident.as_str().to_string()),
+        Expr::Content(block) => MetaValue::String(inline_text(&convert_inlines(block.body()))),
+        Expr::Array(array) => MetaValue::List(
+            array
+                .items()
+                .filter_map(|item| match item {
+                    ast::ArrayItem::Pos(expr) => meta_value(expr),
+                    ast::ArrayItem::Spread(_) => None,
+                })
+                .collect(),
+        ),
+        Expr::FuncCall(call) if callee_name(call) == "datetime" => {
+            let part = |name: &str| {
+                call.args().items().find_map(|arg| match arg {
+                    ast::Arg::Named(n) if n.name().as_str() == name => match n.expr() {
+                        Expr::Int(i) => Some(i.get()),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+            };
+            MetaValue::String(format!(
+                "{:04}-{:02}-{:02}",
+                part("year")?,
+                part("month")?,
+                part("day")?
+            ))
+        }
+        _ => return None,
+    })
 }
 
-/// Parse a heading node
-fn parse_heading(node: &SyntaxNode) -> Option<Block> {
-    let mut level = 1u8;
-    let mut content = String::new();
+/// `#set` rules for the metadata Typst can hold: title, authors, keywords,
+/// an ISO date, the language and the direction
+///
+/// Typst rejects unknown `document` fields, so anything else is dropped.
+fn render_front_matter(output: &mut String, front: &FrontMatter) {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
 
-    for child in node.children() {
-        match child.kind() {
-            SyntaxKind::HeadingMarker => {
-                // Count = signs for level
-                level = child.text().chars().filter(|c| *c == '=').count() as u8;
-            }
-            _ => {
-                content.push_str(child.text());
-            }
-        }
+    let mut args = Vec::new();
+    if let Some(title) = &front.title {
+        args.push(format!("title: {}", quote(title)));
+    }
+    // Typst authors are plain names
+    match front.authors.as_slice() {
+        [] => {}
+        [author] => args.push(format!("author: {}", quote(&author.name))),
+        authors => args.push(format!(
+            "author: ({})",
+            authors
+                .iter()
+                .map(|a| quote(&a.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+    if !front.keywords.is_empty() {
+        args.push(format!(
+            "keywords: ({},)",
+            front
+                .keywords
+                .iter()
+                .map(|k| quote(k))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    let date = front.date.as_deref().and_then(|date| {
+        let mut parts = date.splitn(3, '-').map(|p| p.parse::<u32>().ok());
+        Some((parts.next()??, parts.next()??, parts.next()??))
+    });
+    if let Some((year, month, day)) = date {
+        args.push(format!(
+            "date: datetime(year: {}, month: {}, day: {})",
+            year, month, day
+        ));
     }
 
-    Some(Block::Heading {
-        level,
-        content: vec![Inline::Text {
-            content: content.trim().to_string(),
-        }],
-        id: None,
-        span: None,
-    })
+    if !args.is_empty() {
+        output.push_str(&format!("#set document({})\n", args.join(", ")));
+    }
+    let text_args: Vec<String> = front
+        .language
+        .iter()
+        .map(|language| format!("lang: {}", quote(language)))
+        .chain(
+            front
+                .direction
+                .filter(|d| *d != TextDirection::Auto)
+                .map(|d| format!("dir: {}", d.as_str())),
+        )
+        .collect();
+    if !text_args.is_empty() {
+        output.push_str(&format!("#set text({})\n", text_args.join(", ")));
+    }
 }
 
-/// Parse a list item
-fn parse_list_item(node: &SyntaxNode) -> Option<ListItem> {
-    let mut content_text = String::new();
+/// Convert markup that may only hold inline content (headings, list items)
+fn convert_inlines(markup: ast::Markup<'_>) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    for expr in markup.exprs() {
+        match expr {
+            Expr::FuncCall(call) => inlines.push(convert_inline_call(call)),
+            other => push_inline(&mut inlines, other),
+        }
+    }
+    trim_inlines(&mut inlines);
+    inlines
+}
 
-    for child in node.children() {
-        match child.kind() {
-            SyntaxKind::ListMarker | SyntaxKind::EnumMarker => {
-                // Skip markers
+/// Convert an inline expression, merging adjacent text
+fn push_inline(inlines: &mut Vec<Inline>, expr: Expr<'_>) {
+    let inline = match expr {
+        Expr::Text(text) => Inline::Text {
+            content: text.get().to_string(),
+        },
+        Expr::Space(space) => {
+            // A line break already consumes the whitespace after it
+            if matches!(inlines.last(), None | Some(Inline::LineBreak)) {
+                return;
             }
-            _ => {
-                content_text.push_str(child.text());
+            if space.to_untyped().text().contains('\n') {
+                Inline::SoftBreak
+            } else {
+                Inline::Text {
+                    content: " ".to_string(),
+                }
             }
         }
-    }
-
-    Some(ListItem {
-        content: vec![Block::Paragraph {
+        Expr::Linebreak(_) => Inline::LineBreak,
+        Expr::Escape(escape) => Inline::Text {
+            content: escape.get().to_string(),
+        },
+        Expr::Shorthand(shorthand) => Inline::Text {
+            content: shorthand.get().to_string(),
+        },
+        Expr::SmartQuote(quote) => Inline::Text {
+            content: quote.to_untyped().text().to_string(),
+        },
+        Expr::Strong(strong) => Inline::Strong {
+            content: convert_inlines(strong.body()),
+        },
+        Expr::Emph(emph) => Inline::Emphasis {
+            content: convert_inlines(emph.body()),
+        },
+        Expr::Raw(raw) => Inline::Code {
+            content: raw_text(raw),
+            language: raw.lang().map(|l| l.get().to_string()),
+        },
+        Expr::Link(link) => Inline::Link {
+            url: link.get().to_string(),
+            title: None,
             content: vec![Inline::Text {
-                content: content_text.trim().to_string(),
+                content: link.get().to_string(),
             }],
+            link_type: LinkType::AutoLink,
+        },
+        Expr::Equation(eq) => Inline::Math {
+            content: node_text(eq.body().to_untyped()).trim().to_string(),
+            notation: MathNotation::Typst,
+        },
+        Expr::Parbreak(_) => return,
+        // Labels, references and embedded code are kept verbatim
+        other => Inline::RawInline {
+            format: SourceFormat::Typst,
+            content: embedded_source(other),
+        },
+    };
+
+    if let (Some(Inline::Text { content: prev }), Inline::Text { content }) =
+        (inlines.last_mut(), &inline)
+    {
+        prev.push_str(content);
+        return;
+    }
+    inlines.push(inline);
+}
+
+/// Source text of an expression, with the `#` that introduces code in markup
+fn embedded_source(expr: Expr<'_>) -> String {
+    let text = node_text(expr.to_untyped());
+    match expr {
+        Expr::Label(_) | Expr::Ref(_) => text,
+        _ => format!("#{}", text),
+    }
+}
+
+fn node_text(node: &SyntaxNode) -> String {
+    node.clone().into_text().to_string()
+}
+
+fn raw_text(raw: ast::Raw<'_>) -> String {
+    raw.lines()
+        .map(|line| line.get().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Drop leading/trailing whitespace inlines and trim the outer text
+fn trim_inlines(inlines: &mut Vec<Inline>) {
+    while matches!(inlines.last(), Some(Inline::SoftBreak)) {
+        inlines.pop();
+    }
+    while matches!(inlines.first(), Some(Inline::SoftBreak)) {
+        inlines.remove(0);
+    }
+    if let Some(Inline::Text { content }) = inlines.last_mut() {
+        let len = content.trim_end().len();
+        content.truncate(len);
+    }
+    if let Some(Inline::Text { content }) = inlines.first_mut() {
+        *content = content.trim_start().to_string();
+    }
+    inlines.retain(|i| !matches!(i, Inline::Text { content } if content.is_empty()));
+}
+
+fn flush_paragraph(inlines: &mut Vec<Inline>, blocks: &mut Vec<Block>) {
+    trim_inlines(inlines);
+    if !inlines.is_empty() {
+        blocks.push(Block::Paragraph {
+            content: std::mem::take(inlines),
             span: None,
-        }],
-        checked: None,
+        });
+    }
+}
+
+/// Append a list item, extending the previous list when the kind matches
+fn push_list_item(blocks: &mut Vec<Block>, kind: ListKind, body: ast::Markup<'_>) {
+    let mut content = convert_markup(body);
+    let checked = take_task_marker(&mut content);
+    let kind = if checked.is_some() {
+        ListKind::Task
+    } else {
+        kind
+    };
+    let item = ListItem {
+        content,
+        checked,
         marker: None,
+    };
+
+    if let Some(Block::List {
+        kind: last_kind,
+        items,
+        ..
+    }) = blocks.last_mut()
+    {
+        if *last_kind == kind {
+            items.push(item);
+            return;
+        }
+    }
+    blocks.push(Block::List {
+        kind,
+        items: vec![item],
+        start: (kind == ListKind::Ordered).then_some(1),
+        span: None,
+    });
+}
+
+/// Strip a leading `[x]` / `[ ]` from a list item's first paragraph
+fn take_task_marker(content: &mut [Block]) -> Option<bool> {
+    let Some(Block::Paragraph { content, .. }) = content.first_mut() else {
+        return None;
+    };
+    let Some(Inline::Text { content: text }) = content.first_mut() else {
+        return None;
+    };
+    let checked = if text.starts_with("[x] ") {
+        true
+    } else if text.starts_with("[ ] ") {
+        false
+    } else {
+        return None;
+    };
+    text.drain(..4);
+    Some(checked)
+}
+
+/// Name of a called function (`quote`, `table.header`, ...)
+fn callee_name(call: ast::FuncCall<'_>) -> String {
+    node_text(call.callee().to_untyped())
+}
+
+fn string_arg(call: ast::FuncCall<'_>, name: Option<&str>) -> Option<String> {
+    call.args().items().find_map(|arg| match (arg, name) {
+        (ast::Arg::Pos(Expr::Str(s)), None) => Some(s.get().to_string()),
+        (ast::Arg::Named(named), Some(name)) if named.name().as_str() == name => {
+            match named.expr() {
+                Expr::Str(s) => Some(s.get().to_string()),
+                _ => None,
+            }
+        }
+        _ => None,
     })
 }
 
-/// Extract text from a node recursively
-fn extract_text(node: &SyntaxNode) -> String {
-    let mut text = String::new();
-    for child in node.children() {
-        text.push_str(child.text());
-    }
-    if text.is_empty() {
-        text = node.text().to_string();
+/// A named argument given as content or a string, such as a quote's
+/// `attribution`
+fn inline_arg(call: ast::FuncCall<'_>, name: &str) -> Option<Vec<Inline>> {
+    call.args().items().find_map(|arg| match arg {
+        ast::Arg::Named(named) if named.name().as_str() == name => match named.expr() {
+            Expr::Content(block) => Some(convert_inlines(block.body())),
+            Expr::Str(s) => Some(vec![Inline::Text {
+                content: s.get().to_string(),
+            }]),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn content_args(call: ast::FuncCall<'_>) -> impl Iterator<Item = ast::Markup<'_>> {
+    call.args().items().filter_map(|arg| match arg {
+        ast::Arg::Pos(Expr::Content(block)) => Some(block.body()),
+        _ => None,
+    })
+}
+
+/// Convert calls that produce block-level content
+fn convert_block_call(call: ast::FuncCall<'_>) -> Option<Block> {
+    match callee_name(call).as_str() {
+        "quote" => Some(Block::BlockQuote {
+            content: content_args(call).flat_map(convert_markup).collect(),
+            attribution: inline_arg(call, "attribution"),
+            admonition: None,
+            span: None,
+        }),
+        "block" => Some(Block::Container {
+            id: None,
+            classes: Vec::new(),
+            attributes: Default::default(),
+            content: content_args(call).flat_map(convert_markup).collect(),
+            span: None,
+        }),
+        "line" => Some(Block::ThematicBreak { span: None }),
+        "table" => Some(convert_table(call)),
+        _ => None,
     }
-    text.trim().to_string()
 }
 
-/// Extract content from a raw (code) block
-fn extract_raw_content(node: &SyntaxNode) -> String {
-    let text = node.text().to_string();
-    // Remove the backticks
-    text.trim_matches('`').to_string()
+/// Convert calls that produce inline content
+fn convert_inline_call(call: ast::FuncCall<'_>) -> Inline {
+    let body = || {
+        content_args(call)
+            .flat_map(convert_inlines)
+            .collect::<Vec<_>>()
+    };
+    match callee_name(call).as_str() {
+        "link" => {
+            let url = string_arg(call, None).unwrap_or_default();
+            let mut content = body();
+            if content.is_empty() {
+                content.push(Inline::Text {
+                    content: url.clone(),
+                });
+            }
+            Inline::Link {
+                url,
+                title: None,
+                content,
+                link_type: LinkType::Inline,
+            }
+        }
+        "strike" => Inline::Strikethrough { content: body() },
+        "underline" => Inline::Underline { content: body() },
+        "highlight" => Inline::Highlight { content: body() },
+        "super" => Inline::Superscript { content: body() },
+        "sub" => Inline::Subscript { content: body() },
+        "smallcaps" => Inline::SmallCaps { content: body() },
+        "image" => Inline::Image {
+            url: string_arg(call, None).unwrap_or_default(),
+            alt: string_arg(call, Some("alt")).unwrap_or_default(),
+            title: None,
+            width: None,
+            height: None,
+        },
+        "raw" => Inline::Code {
+            content: content_args(call)
+                .map(|m| node_text(m.to_untyped()))
+                .collect(),
+            language: None,
+        },
+        _ => Inline::RawInline {
+            format: SourceFormat::Typst,
+            content: embedded_source(Expr::FuncCall(call)),
+        },
+    }
 }
 
-/// Extract language from a raw block (if specified)
-fn extract_raw_language(node: &SyntaxNode) -> Option<String> {
-    for child in node.children() {
-        if child.kind() == SyntaxKind::Ident {
-            return Some(child.text().to_string());
+/// Convert `#table(columns: n, table.header[..], [..], ...)`
+fn convert_table(call: ast::FuncCall<'_>) -> Block {
+    let mut columns = None;
+    let mut header = Vec::new();
+    let mut cells = Vec::new();
+
+    let cell = |markup: ast::Markup<'_>| TableCell {
+        content: convert_markup(markup),
+        colspan: 1,
+        rowspan: 1,
+        alignment: None,
+    };
+
+    for arg in call.args().items() {
+        match arg {
+            ast::Arg::Named(named) if named.name().as_str() == "columns" => {
+                if let Expr::Int(n) = named.expr() {
+                    columns = usize::try_from(n.get()).ok();
+                }
+            }
+            ast::Arg::Pos(Expr::Content(block)) => cells.push(cell(block.body())),
+            ast::Arg::Pos(Expr::FuncCall(inner)) if callee_name(inner) == "table.header" => {
+                header.extend(content_args(inner).map(cell));
+            }
+            _ => {}
         }
     }
-    None
+
+    let width = columns
+        .or((!header.is_empty()).then_some(header.len()))
+        .unwrap_or(1)
+        .max(1);
+    let mut body = Vec::new();
+    let mut cells = cells.into_iter().peekable();
+    while cells.peek().is_some() {
+        body.push(TableRow {
+            cells: cells.by_ref().take(width).collect(),
+        });
+    }
+
+    Block::Table {
+        caption: None,
+        columns: (0..width)
+            .map(|_| ColumnSpec {
+                alignment: ColumnAlignment::Default,
+                width: None,
+            })
+            .collect(),
+        header: (!header.is_empty()).then_some(TableRow { cells: header }),
+        body,
+        footer: None,
+        span: None,
+    }
 }
 
 impl Renderer for TypstHandler {
@@ -285,17 +684,18 @@ fn format(&self) -> SourceFormat {
         SourceFormat::Typst
     }
 
-    fn render(&self, doc: &Document, _config: &RenderConfig) -> Result<String> {
+    fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String> {
+        let doc = &config.prepare_document(doc, SourceFormat::Typst);
         let mut output = String::new();
-
-        for (i, block) in doc.content.iter().enumerate() {
-            if i > 0 {
-                output.push_str("\n\n");
-            }
-            render_block(&mut output, block);
+        let mut header = String::new();
+        render_front_matter(&mut header, &FrontMatter::from_meta(&doc.meta));
+        if !header.is_empty() {
+            output.push_str(&header);
+            output.push('\n');
         }
+        output.push_str(&render_blocks(&doc.content));
 
-        Ok(output)
+        Ok(config.finish_output(output))
     }
 }
 
@@ -330,18 +730,30 @@ fn render_block(output: &mut String, block: &Block) {
             output.push_str("```");
         }
 
-        Block::BlockQuote { content, .. } => {
-            output.push_str("#quote[\n");
-            for block in content {
-                output.push_str("  ");
-                render_block(output, block);
-                output.push('\n');
+        Block::BlockQuote {
+            content,
+            attribution,
+            ..
+        } => {
+            output.push_str("#quote");
+            // Typst only shows the attribution of a block quote
+            if let Some(attribution) = attribution {
+                output.push_str("(block: true, attribution: [");
+                for inline in attribution {
+                    render_inline(output, inline);
+                }
+                output.push_str("])");
             }
-            output.push(']');
+            output.push_str("[\n");
+            push_indented(output, &render_blocks(content), "  ");
+            output.push_str("\n]");
         }
 
         Block::List { kind, items, .. } => {
             for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
                 match kind {
                     ListKind::Bullet => output.push_str("- "),
                     ListKind::Ordered => output.push_str(&format!("{}. ", i + 1)),
@@ -351,10 +763,14 @@ fn render_block(output: &mut String, block: &Block) {
                         output.push_str(if checked { "- [x] " } else { "- [ ] " });
                     }
                 }
-                for block in &item.content {
-                    render_block(output, block);
+                // Item content continues on lines indented past the marker
+                let body = render_blocks(&item.content);
+                let (first, rest) = body.split_once('\n').unwrap_or((&body, ""));
+                output.push_str(first);
+                if !rest.is_empty() {
+                    output.push('\n');
+                    push_indented(output, rest, "  ");
                 }
-                output.push('\n');
             }
         }
 
@@ -374,6 +790,10 @@ fn render_block(output: &mut String, block: &Block) {
             output.push(']');
         }
 
+        Block::Comment { content, .. } => {
+            output.push_str(&format!("/* {} */", comment_text(content, COMMENT_MARKERS)));
+        }
+
         Block::Table {
             header,
             body,
@@ -381,15 +801,26 @@ fn render_block(output: &mut String, block: &Block) {
             ..
         } => {
             output.push_str("#table(\n");
+            let columns = header
+                .as_ref()
+                .map(|h| h.cells.len())
+                .or_else(|| body.first().map(|r| r.cells.len()))
+                .unwrap_or(1);
+            output.push_str(&format!("  columns: {},\n", columns));
 
             if let Some(h) = header {
-                for cell in &h.cells {
-                    output.push_str("  table.header[");
+                output.push_str("  table.header(");
+                for (i, cell) in h.cells.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(", ");
+                    }
+                    output.push('[');
                     for block in &cell.content {
                         render_block(output, block);
                     }
-                    output.push_str("],\n");
+                    output.push(']');
                 }
+                output.push_str("),\n");
             }
 
             for row in body {
@@ -413,10 +844,81 @@ fn render_block(output: &mut String, block: &Block) {
             }
         }
 
+        // Each definition of a term is an item of its own
+        Block::DefinitionList { items, .. } => {
+            let mut first = true;
+            for item in items {
+                for definition in &item.definitions {
+                    if !first {
+                        output.push('\n');
+                    }
+                    first = false;
+                    output.push_str("/ ");
+                    for inline in &item.term {
+                        render_inline(output, inline);
+                    }
+                    output.push_str(": ");
+                    let body = render_blocks(definition);
+                    let (line, rest) = body.split_once('\n').unwrap_or((&body, ""));
+                    output.push_str(line);
+                    if !rest.is_empty() {
+                        output.push('\n');
+                        push_indented(output, rest, "  ");
+                    }
+                }
+            }
+        }
+
+        // Typst has no classes, so a container is only its box
+        Block::Container { content, .. } => {
+            output.push_str("#block[\n");
+            push_indented(output, &render_blocks(content), "  ");
+            output.push_str("\n]");
+        }
+
+        Block::Details {
+            summary, content, ..
+        } => {
+            output.push_str(&render_blocks(&super::unfold_details(
+                summary.as_ref(),
+                content,
+            )));
+        }
+
         _ => {}
     }
 }
 
+/// Render blocks separated by blank lines, skipping any that produce no output
+fn render_blocks(blocks: &[Block]) -> String {
+    let mut output = String::new();
+    for block in blocks {
+        let mut rendered = String::new();
+        render_block(&mut rendered, block);
+        if rendered.is_empty() {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str("\n\n");
+        }
+        output.push_str(&rendered);
+    }
+    output
+}
+
+/// Append `text` with every non-empty line indented by `indent`
+fn push_indented(output: &mut String, text: &str, indent: &str) {
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        if !line.is_empty() {
+            output.push_str(indent);
+            output.push_str(line);
+        }
+    }
+}
+
 fn render_inline(output: &mut String, inline: &Inline) {
     match inline {
         Inline::Text { content } => output.push_str(content),
@@ -451,6 +953,20 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push('`');
         }
 
+        Inline::Keyboard { .. } | Inline::Button { .. } | Inline::Menu { .. } => {
+            if let Some(plain) = super::plain_ui(inline) {
+                render_inline(output, &plain);
+            }
+        }
+
+        // Bare URLs are links in Typst markup
+        Inline::Link { url, content, .. }
+            if (url.starts_with("https://") || url.starts_with("http://"))
+                && matches!(content.as_slice(), [Inline::Text { content }] if content == url) =>
+        {
+            output.push_str(url)
+        }
+
         Inline::Link { url, content, .. } => {
             output.push_str("#link(\"");
             output.push_str(url);
@@ -468,7 +984,7 @@ fn render_inline(output: &mut String, inline: &Inline) {
         Inline::Math { content, notation } => {
             // Typst uses $ for inline math
             match notation {
-                MathNotation::LaTeX | MathNotation::AsciiMath => {
+                MathNotation::LaTeX | MathNotation::AsciiMath | MathNotation::Typst => {
                     output.push('$');
                     output.push_str(content);
                     output.push('$');
@@ -490,6 +1006,19 @@ fn render_inline(output: &mut String, inline: &Inline) {
             output.push(' ');
         }
 
+        Inline::NonBreakingSpace => {
+            output.push('~');
+        }
+
+        Inline::RawInline {
+            format: SourceFormat::Typst,
+            content,
+        } => output.push_str(content),
+
+        Inline::Comment { content } => {
+            output.push_str(&format!("/* {} */", comment_text(content, COMMENT_MARKERS)))
+        }
+
         _ => {}
     }
 }
@@ -507,10 +1036,15 @@ fn supports_feature(&self, feature: &str) -> bool {
                 | "link"
                 | "image"
                 | "list"
+                | "blockquote"
                 | "table"
                 | "math"
                 | "figure"
                 | "bibliography"
+                | "container"
+                | "definition_list"
+                | "emoji"
+                | "nested_quote"
         )
     }
 
@@ -525,17 +1059,29 @@ fn supported_features(&self) -> &[&str] {
             "link",
             "image",
             "list",
+            "blockquote",
             "table",
             "math",
             "figure",
             "bibliography",
+            "container",
+            "definition_list",
+            "emoji",
+            "nested_quote",
         ]
     }
+
+    fn normalization_exceptions(&self) -> &[&str] {
+        // Footnotes are not rendered, so anything anchored on them shifts
+        &["footnote"]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{Author, DocumentMeta};
+    use crate::formats::MarkdownHandler;
 
     #[test]
     fn test_parse_simple() {
@@ -565,6 +1111,7 @@ fn test_render_heading() {
                     content: "Title".to_string(),
                 }],
                 id: None,
+                classes: Vec::new(),
                 span: None,
             }],
             raw_source: None,
@@ -594,4 +1141,91 @@ fn test_render_code_block() {
         assert!(output.contains("```rust"));
         assert!(output.contains("fn main()"));
     }
+
+    #[test]
+    fn test_front_matter() {
+        let handler = TypstHandler::new();
+        let doc = handler
+            .parse(
+                "#set document(title: [Notes], author: (\"Ada\", \"Grace\"), keywords: (\"a\", \"b\"), \
+                 date: datetime(year: 2026, month: 1, day: 2))\n#set text(lang: \"en\")\n\nBody\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(doc.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(
+            doc.meta.authors,
+            vec![Author::from("Ada"), Author::from("Grace")]
+        );
+        assert_eq!(doc.meta.date.as_deref(), Some("2026-01-02"));
+        assert_eq!(doc.meta.language.as_deref(), Some("en"));
+        assert_eq!(doc.meta.keywords, vec!["a", "b"]);
+        assert_eq!(doc.content.len(), 1);
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(
+            output,
+            "#set document(title: \"Notes\", author: (\"Ada\", \"Grace\"), keywords: (\"a\", \"b\",), \
+             date: datetime(year: 2026, month: 1, day: 2))\n#set text(lang: \"en\")\n\nBody"
+        );
+
+        let rtl = handler
+            .parse(
+                "#set text(lang: \"he\", dir: rtl)\n\nשלום\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(rtl.meta.direction, Some(TextDirection::Rtl));
+        assert_eq!(rtl.content.len(), 1);
+        assert!(handler
+            .render(&rtl, &RenderConfig::default())
+            .unwrap()
+            .starts_with("#set text(lang: \"he\", dir: rtl)\n"));
+    }
+
+    #[test]
+    fn test_block() {
+        let handler = TypstHandler::new();
+        let input = "#block[\n  First\n\n  Second\n]";
+        let doc = handler.parse(input, &ParseConfig::default()).unwrap();
+        assert!(matches!(
+            &doc.content[0],
+            Block::Container { content, .. } if content.len() == 2
+        ));
+
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_math_notation() {
+        let handler = TypstHandler::new();
+        let doc = handler
+            .parse(
+                "Where $x^(n+1) in RR$:\n\n$ frac(a, b) + sum_(i=1)^n alpha_i $\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert!(matches!(
+            &doc.content[1],
+            Block::MathBlock {
+                notation: MathNotation::Typst,
+                ..
+            }
+        ));
+
+        let markdown = MarkdownHandler::new()
+            .render(&doc, &RenderConfig::default())
+            .unwrap();
+        assert!(markdown.contains("$x^{n+1} \\in \\mathbb{R}$"));
+        assert!(markdown.contains("\\frac{a}{b} + \\sum_{i=1}^n \\alpha_i"));
+
+        // And back, from TeX
+        let doc = MarkdownHandler::new()
+            .parse(&markdown, &ParseConfig::default())
+            .unwrap();
+        let output = handler.render(&doc, &RenderConfig::default()).unwrap();
+        assert!(output.contains("$x^(n+1) in RR$"));
+        assert!(output.contains("frac(a, b) + sum_(i=1)^n alpha_i"));
+    }
 }
@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Front matter - document metadata as written in a file header
+//!
+//! Each format keeps metadata in its own header syntax:
+//!
+//! | Format   | Header                                              |
+//! |----------|-----------------------------------------------------|
+//! | Markdown | YAML between `---` lines, or TOML between `+++`     |
+//! | Org      | `#+KEY: value` lines                                |
+//! | AsciiDoc | `= Title`, an author line and `:name: value` lines  |
+//! | Typst    | `#set document(title: .., author: .., ..)`          |
+//!
+//! Handlers read their header into a [`FrontMatter`], which pulls the common
+//! fields out under one set of names, and turn it into the document's
+//! [`DocumentMeta`]. Renderers go the other way, so metadata survives
+//! conversion between any two formats that have a header.
+
+use crate::ast::{Author, DocumentMeta, MetaValue, TextDirection};
+use std::collections::BTreeMap;
+
+/// Metadata read from or written to a document header
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub authors: Vec<Author>,
+    pub date: Option<String>,
+    pub modified: Option<String>,
+    /// BCP 47 language tag
+    pub language: Option<String>,
+    pub direction: Option<TextDirection>,
+    pub keywords: Vec<String>,
+    /// Every other field, keyed as written in the source
+    pub fields: BTreeMap<String, MetaValue>,
+}
+
+impl FrontMatter {
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.authors.is_empty()
+            && self.date.is_none()
+            && self.modified.is_none()
+            && self.language.is_none()
+            && self.direction.is_none()
+            && self.keywords.is_empty()
+            && self.fields.is_empty()
+    }
+
+    pub fn from_meta(meta: &DocumentMeta) -> Self {
+        Self {
+            title: meta.title.clone(),
+            authors: meta.authors.clone(),
+            date: meta.date.clone(),
+            modified: meta.modified.clone(),
+            language: meta.language.clone(),
+            direction: meta.direction,
+            keywords: meta.keywords.clone(),
+            fields: meta
+                .custom
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn into_meta(self) -> DocumentMeta {
+        DocumentMeta {
+            title: self.title,
+            authors: self.authors,
+            date: self.date,
+            modified: self.modified,
+            language: self.language,
+            direction: self.direction,
+            keywords: self.keywords,
+            custom: self.fields.into_iter().collect(),
+        }
+    }
+
+    /// Sort a flat key/value map into common and other fields
+    ///
+    /// Keys are matched case-insensitively: `title`, `author`/`authors`,
+    /// `email`, `date`, `modified`/`lastmod`/`updated`, `lang`/`language`,
+    /// `dir`/`direction` and `keywords`. An author may be written as `Name <email>` or as a map with
+    /// `name` and `email`, alone or in a list; an `email` field goes to the
+    /// first author without one. Keywords may be a list or comma-separated.
+    pub fn from_fields(fields: impl IntoIterator<Item = (String, MetaValue)>) -> Self {
+        let mut front = Self::default();
+        let mut email = None;
+        for (key, value) in fields {
+            match (key.to_ascii_lowercase().as_str(), value) {
+                ("title", value) if meta_text(&value).is_some() => front.title = meta_text(&value),
+                ("author" | "authors", MetaValue::List(items)) => {
+                    front.authors.extend(items.iter().filter_map(meta_author))
+                }
+                ("author" | "authors", value) if meta_author(&value).is_some() => {
+                    front.authors.extend(meta_author(&value))
+                }
+                ("email", value) if meta_text(&value).is_some() => email = meta_text(&value),
+                ("date", value) if meta_text(&value).is_some() => front.date = meta_text(&value),
+                ("modified" | "lastmod" | "updated", value) if meta_text(&value).is_some() => {
+                    front.modified = meta_text(&value)
+                }
+                ("lang" | "language", value) if meta_text(&value).is_some() => {
+                    front.language = meta_text(&value)
+                }
+                ("dir" | "direction", value)
+                    if meta_text(&value).is_some_and(|d| TextDirection::parse(&d).is_some()) =>
+                {
+                    front.direction = meta_text(&value).and_then(|d| TextDirection::parse(&d))
+                }
+                ("keywords", MetaValue::List(items)) => {
+                    front.keywords.extend(items.iter().filter_map(meta_text))
+                }
+                ("keywords", MetaValue::String(text)) => front.keywords.extend(
+                    text.split(',')
+                        .map(str::trim)
+                        .filter(|k| !k.is_empty())
+                        .map(String::from),
+                ),
+                (_, value) => {
+                    front.fields.insert(key, value);
+                }
+            }
+        }
+        if let Some(email) = email {
+            match front.authors.iter_mut().find(|a| a.email.is_none()) {
+                Some(author) => author.email = Some(email),
+                None => {
+                    front
+                        .fields
+                        .insert("email".to_string(), MetaValue::String(email));
+                }
+            }
+        }
+        front
+    }
+
+    /// Common fields first under their conventional names, then the rest
+    pub fn to_fields(&self) -> Vec<(String, MetaValue)> {
+        let mut fields = Vec::new();
+        if let Some(title) = &self.title {
+            fields.push(("title".to_string(), MetaValue::String(title.clone())));
+        }
+        match self.authors.as_slice() {
+            [] => {}
+            [author] => fields.push(("author".to_string(), MetaValue::String(author.to_string()))),
+            authors => fields.push((
+                "author".to_string(),
+                MetaValue::List(
+                    authors
+                        .iter()
+                        .map(|a| MetaValue::String(a.to_string()))
+                        .collect(),
+                ),
+            )),
+        }
+        if let Some(date) = &self.date {
+            fields.push(("date".to_string(), MetaValue::String(date.clone())));
+        }
+        if let Some(modified) = &self.modified {
+            fields.push(("modified".to_string(), MetaValue::String(modified.clone())));
+        }
+        if let Some(language) = &self.language {
+            fields.push(("lang".to_string(), MetaValue::String(language.clone())));
+        }
+        if let Some(direction) = self.direction {
+            fields.push((
+                "dir".to_string(),
+                MetaValue::String(direction.as_str().to_string()),
+            ));
+        }
+        if !self.keywords.is_empty() {
+            fields.push((
+                "keywords".to_string(),
+                MetaValue::List(
+                    self.keywords
+                        .iter()
+                        .cloned()
+                        .map(MetaValue::String)
+                        .collect(),
+                ),
+            ));
+        }
+        fields.extend(self.fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+        fields
+    }
+
+    /// Parse a YAML mapping; anything else is an error
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+        let serde_yaml::Value::Mapping(mapping) = value else {
+            return Err("front matter is not a mapping".to_string());
+        };
+        Ok(Self::from_fields(mapping.into_iter().filter_map(
+            |(key, value)| Some((yaml_key(&key)?, from_yaml_value(value)?)),
+        )))
+    }
+
+    /// YAML mapping, one field per line, ending in a newline
+    pub fn to_yaml(&self) -> String {
+        let mapping: serde_yaml::Mapping = self
+            .to_fields()
+            .into_iter()
+            .map(|(k, v)| (serde_yaml::Value::String(k), to_yaml_value(&v)))
+            .collect();
+        serde_yaml::to_string(&mapping).unwrap_or_default()
+    }
+
+    /// Parse a TOML table
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        let table: toml::Table = toml.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        Ok(Self::from_toml_table(table))
+    }
+
+    /// TOML table, one field per line, ending in a newline
+    pub fn to_toml(&self) -> String {
+        self.to_toml_table().to_string()
+    }
+
+    pub(crate) fn from_toml_table(table: toml::Table) -> Self {
+        Self::from_fields(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml_value(value))),
+        )
+    }
+
+    pub(crate) fn to_toml_table(&self) -> toml::Table {
+        self.to_fields()
+            .into_iter()
+            .map(|(k, v)| (k, to_toml_value(&v)))
+            .collect()
+    }
+}
+
+/// A scalar or list of scalars as a single line of text
+///
+/// Maps have no single-line form and give `None`.
+pub fn meta_text(value: &MetaValue) -> Option<String> {
+    match value {
+        MetaValue::String(s) => Some(s.clone()),
+        MetaValue::Bool(b) => Some(b.to_string()),
+        MetaValue::Integer(i) => Some(i.to_string()),
+        MetaValue::Float(f) => Some(f.to_string()),
+        MetaValue::List(items) => Some(
+            items
+                .iter()
+                .filter_map(meta_text)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        MetaValue::Map(_) => None,
+    }
+}
+
+/// An author from `Name <email>` text or a map with `name` and `email`
+fn meta_author(value: &MetaValue) -> Option<Author> {
+    match value {
+        MetaValue::Map(map) => Some(Author {
+            name: meta_text(map.get("name")?)?,
+            email: map.get("email").and_then(meta_text),
+        }),
+        value => meta_text(value).map(Author::from),
+    }
+}
+
+fn yaml_key(key: &serde_yaml::Value) -> Option<String> {
+    match key {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Nulls and tagged values without a plain meaning are dropped
+fn from_yaml_value(value: serde_yaml::Value) -> Option<MetaValue> {
+    Some(match value {
+        serde_yaml::Value::Null => return None,
+        serde_yaml::Value::Bool(b) => MetaValue::Bool(b),
+        serde_yaml::Value::Number(n) => match n.as_i64() {
+            Some(i) => MetaValue::Integer(i),
+            None => MetaValue::Float(n.as_f64()?),
+        },
+        serde_yaml::Value::String(s) => MetaValue::String(s),
+        serde_yaml::Value::Sequence(items) => {
+            MetaValue::List(items.into_iter().filter_map(from_yaml_value).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => MetaValue::Map(
+            mapping
+                .into_iter()
+                .filter_map(|(k, v)| Some((yaml_key(&k)?, from_yaml_value(v)?)))
+                .collect(),
+        ),
+        serde_yaml::Value::Tagged(tagged) => return from_yaml_value(tagged.value),
+    })
+}
+
+fn to_yaml_value(value: &MetaValue) -> serde_yaml::Value {
+    match value {
+        MetaValue::String(s) => serde_yaml::Value::String(s.clone()),
+        MetaValue::Bool(b) => serde_yaml::Value::Bool(*b),
+        MetaValue::Integer(i) => serde_yaml::Value::Number((*i).into()),
+        MetaValue::Float(f) => serde_yaml::Value::Number((*f).into()),
+        MetaValue::List(items) => {
+            serde_yaml::Value::Sequence(items.iter().map(to_yaml_value).collect())
+        }
+        MetaValue::Map(map) => {
+            // Sorted so output does not depend on hash order
+            let sorted: BTreeMap<_, _> = map.iter().collect();
+            serde_yaml::Value::Mapping(
+                sorted
+                    .into_iter()
+                    .map(|(k, v)| (serde_yaml::Value::String(k.clone()), to_yaml_value(v)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn from_toml_value(value: toml::Value) -> MetaValue {
+    match value {
+        toml::Value::String(s) => MetaValue::String(s),
+        toml::Value::Integer(i) => MetaValue::Integer(i),
+        toml::Value::Float(f) => MetaValue::Float(f),
+        toml::Value::Boolean(b) => MetaValue::Bool(b),
+        toml::Value::Datetime(d) => MetaValue::String(d.to_string()),
+        toml::Value::Array(items) => {
+            MetaValue::List(items.into_iter().map(from_toml_value).collect())
+        }
+        toml::Value::Table(table) => MetaValue::Map(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, from_toml_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_toml_value(value: &MetaValue) -> toml::Value {
+    match value {
+        MetaValue::String(s) => toml::Value::String(s.clone()),
+        MetaValue::Bool(b) => toml::Value::Boolean(*b),
+        MetaValue::Integer(i) => toml::Value::Integer(*i),
+        MetaValue::Float(f) => toml::Value::Float(*f),
+        MetaValue::List(items) => toml::Value::Array(items.iter().map(to_toml_value).collect()),
+        MetaValue::Map(map) => toml::Value::Table(
+            map.iter()
+                .map(|(k, v)| (k.clone(), to_toml_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let front = FrontMatter::from_yaml(
+            "title: Notes\nauthor: ['Ada <ada@example.com>', {name: Grace}]\ndate: 2026-01-02\n\
+             lastmod: 2026-02-03\nlang: en-GB\nkeywords: notes, math\n\
+             draft: true\nversion: 3\ntags: [a, b]\nempty:\n",
+        )
+        .unwrap();
+
+        assert_eq!(front.title.as_deref(), Some("Notes"));
+        assert_eq!(
+            front.authors,
+            vec![
+                Author::parse("Ada <ada@example.com>"),
+                Author::parse("Grace")
+            ]
+        );
+        assert_eq!(front.date.as_deref(), Some("2026-01-02"));
+        assert_eq!(front.modified.as_deref(), Some("2026-02-03"));
+        assert_eq!(front.language.as_deref(), Some("en-GB"));
+        assert_eq!(front.keywords, vec!["notes", "math"]);
+        assert_eq!(
+            front.fields.keys().collect::<Vec<_>>(),
+            vec!["draft", "tags", "version"]
+        );
+
+        let yaml = front.to_yaml();
+        assert!(yaml.starts_with("title: Notes\nauthor:\n- Ada <ada@example.com>\n- Grace\n"));
+        assert_eq!(FrontMatter::from_yaml(&yaml).unwrap(), front);
+
+        assert!(FrontMatter::from_yaml("- just\n- a list\n").is_err());
+    }
+
+    #[test]
+    fn test_toml_and_meta() {
+        let front = FrontMatter::from_toml(
+            "title = \"Notes\"\nauthors = \"Ada\"\nemail = \"ada@example.com\"\nweight = 1.5\n",
+        )
+        .unwrap();
+        assert_eq!(front.authors, vec![Author::parse("Ada <ada@example.com>")]);
+        assert!(matches!(
+            front.fields.get("weight"),
+            Some(MetaValue::Float(_))
+        ));
+
+        let meta = front.clone().into_meta();
+        assert_eq!(meta.title.as_deref(), Some("Notes"));
+        assert_eq!(FrontMatter::from_meta(&meta), front);
+        assert_eq!(FrontMatter::from_toml(&front.to_toml()).unwrap(), front);
+    }
+}
@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Undo and redo for documents being edited
+//!
+//! History is kept as text edits on the source, so it works the same for
+//! every format and every frontend: each change is a [`TextEdit`], undoing
+//! it applies the inverse, and the AST is brought up to date by an
+//! [`IncrementalParser`], which parses again only the regions the edit
+//! touched and keeps half-typed markup as raw source rather than failing.
+//!
+//! Frontends that send the whole buffer after each change can use
+//! [`DocumentHistory::replace_text`], which records only the part that
+//! differs. Consecutive typing on one line is merged into a single step.
+
+use crate::ast::{Document, SourceFormat};
+use crate::diagnostic::Diagnostic;
+use crate::incremental::IncrementalParser;
+use crate::traits::{ConversionError, ParseConfig, Result};
+
+/// Steps kept by default
+pub const DEFAULT_HISTORY_LIMIT: usize = 500;
+
+/// Replace `removed` at byte `offset` with `inserted`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub offset: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl TextEdit {
+    /// The edit that turns `old` into `new`: everything between their
+    /// common prefix and common suffix
+    pub fn diff(old: &str, new: &str) -> Self {
+        let prefix = common_prefix(old, new);
+        let suffix = common_suffix(&old[prefix..], &new[prefix..]);
+        Self {
+            offset: prefix,
+            removed: old[prefix..old.len() - suffix].to_string(),
+            inserted: new[prefix..new.len() - suffix].to_string(),
+        }
+    }
+
+    /// The edit that undoes this one
+    pub fn inverse(&self) -> Self {
+        Self {
+            offset: self.offset,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.inserted.is_empty()
+    }
+
+    /// Apply to `text`, checking that it holds `removed` at `offset`
+    pub fn apply(&self, text: &mut String) -> Result<()> {
+        let end = self.offset + self.removed.len();
+        if text.get(self.offset..end) != Some(self.removed.as_str()) {
+            return Err(ConversionError::ParseError {
+                line: 0,
+                column: 0,
+                message: format!("edit at byte {} does not match the text", self.offset),
+            });
+        }
+        text.replace_range(self.offset..end, &self.inserted);
+        Ok(())
+    }
+
+    /// Fold `next`, typed straight after this insertion, into it
+    fn merge(&mut self, next: &TextEdit) -> bool {
+        let continues = self.removed.is_empty()
+            && next.removed.is_empty()
+            && next.offset == self.offset + self.inserted.len()
+            && !next.inserted.contains('\n');
+        if continues {
+            self.inserted.push_str(&next.inserted);
+        }
+        continues
+    }
+}
+
+/// Source text of a document with its undo and redo stacks
+#[derive(Debug, Clone)]
+pub struct DocumentHistory {
+    parser: IncrementalParser,
+    undo: Vec<TextEdit>,
+    redo: Vec<TextEdit>,
+    limit: usize,
+    /// Whether the next edit may merge into the last undo step
+    merge_next: bool,
+}
+
+impl DocumentHistory {
+    pub fn new(text: impl Into<String>, format: SourceFormat) -> Self {
+        Self::new_with_config(text, format, ParseConfig::default())
+    }
+
+    /// Start a history whose text is parsed with `config`, e.g. to record
+    /// spans
+    pub fn new_with_config(
+        text: impl Into<String>,
+        format: SourceFormat,
+        config: ParseConfig,
+    ) -> Self {
+        Self {
+            parser: IncrementalParser::new(text, format, config),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: DEFAULT_HISTORY_LIMIT,
+            merge_next: false,
+        }
+    }
+
+    /// Keep at most `limit` undo steps, dropping the oldest
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        self.parser.text()
+    }
+
+    /// The document as parsed from the current text
+    pub fn document(&self) -> &Document {
+        self.parser.document()
+    }
+
+    /// Problems found in the last parse, such as regions kept as raw source
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.parser.diagnostics()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Apply `edit` as a new undo step, clearing the redo stack
+    pub fn apply(&mut self, edit: TextEdit) -> Result<()> {
+        if edit.is_empty() {
+            return Ok(());
+        }
+        self.parser.apply(&edit)?;
+        self.redo.clear();
+        let merged = self.merge_next && self.undo.last_mut().is_some_and(|last| last.merge(&edit));
+        if !merged {
+            self.undo.push(edit);
+            if self.undo.len() > self.limit {
+                self.undo.remove(0);
+            }
+        }
+        self.merge_next = true;
+        Ok(())
+    }
+
+    /// Record the change from the current text to `text`
+    pub fn replace_text(&mut self, text: &str) {
+        let edit = TextEdit::diff(self.text(), text);
+        // A diff of the current text always applies
+        let _ = self.apply(edit);
+    }
+
+    /// Start a new undo step even if the next edit continues the last one
+    pub fn break_group(&mut self) {
+        self.merge_next = false;
+    }
+
+    /// Undo the last step; `false` if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        self.step(false)
+    }
+
+    /// Redo the last undone step; `false` if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        self.step(true)
+    }
+
+    fn step(&mut self, forward: bool) -> bool {
+        let (from, to) = if forward {
+            (&mut self.redo, &mut self.undo)
+        } else {
+            (&mut self.undo, &mut self.redo)
+        };
+        let Some(edit) = from.pop() else {
+            return false;
+        };
+        let change = if forward {
+            edit.clone()
+        } else {
+            edit.inverse()
+        };
+        if self.parser.apply(&change).is_err() {
+            // The stacks only hold edits made to this text
+            return false;
+        }
+        to.push(edit);
+        self.merge_next = false;
+        true
+    }
+}
+
+/// Length in bytes of the common prefix, on a character boundary
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+/// Length in bytes of the common suffix, on a character boundary
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Block;
+
+    #[test]
+    fn test_diff() {
+        let edit = TextEdit::diff("a café b", "a cafés b");
+        assert_eq!(
+            edit,
+            TextEdit {
+                offset: 7,
+                removed: String::new(),
+                inserted: "s".to_string()
+            }
+        );
+        let edit = TextEdit::diff("naïve", "native");
+        assert_eq!((edit.removed.as_str(), edit.inserted.as_str()), ("ï", "ti"));
+
+        let mut text = "naïve".to_string();
+        edit.apply(&mut text).unwrap();
+        assert_eq!(text, "native");
+        edit.inverse().apply(&mut text).unwrap();
+        assert_eq!(text, "naïve");
+        assert!(edit.apply(&mut "other".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = DocumentHistory::new("# Title\n", SourceFormat::Markdown);
+        assert!(!history.can_undo());
+
+        // Typing is one step; a new line starts another
+        history.replace_text("# Title\nH");
+        history.replace_text("# Title\nHi");
+        history.replace_text("# Title\nHi\n\n- item\n");
+        assert_eq!(history.document().content.len(), 3);
+
+        assert!(history.undo());
+        assert_eq!(history.text(), "# Title\nHi");
+        assert!(history.undo());
+        assert_eq!(history.text(), "# Title\n");
+        assert!(!history.undo());
+        assert!(matches!(
+            history.document().content.as_slice(),
+            [Block::Heading { .. }]
+        ));
+
+        assert!(history.redo());
+        assert_eq!(history.text(), "# Title\nHi");
+        history.replace_text("# Title\nHey");
+        assert!(!history.can_redo());
+        assert!(history.undo());
+        assert_eq!(history.text(), "# Title\nHi");
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut history = DocumentHistory::new("", SourceFormat::PlainText).with_limit(2);
+        for text in ["a\n", "a\nb\n", "a\nb\nc\n"] {
+            history.replace_text(text);
+        }
+        assert!(history.undo() && history.undo());
+        assert!(!history.undo());
+        assert_eq!(history.text(), "a\n");
+    }
+}
@@ -0,0 +1,571 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! HTML rendering for previews and export
+//!
+//! HTML is an output format: there is no [`SourceFormat`](crate::ast::SourceFormat)
+//! for it, and only pasted HTML is read back, by
+//! [`parse_html`](crate::clipboard::parse_html). [`render_html`] gives a fragment to
+//! embed in a page that already exists; [`render_html_page`] gives a complete
+//! standalone document.
+//!
+//! The document's language and base direction go on the outermost element as
+//! `lang` and `dir`, and a container's `dir` attribute is kept, so
+//! right-to-left text lays out correctly with no extra styling. Raw content is
+//! passed through as written; run it through
+//! [`sanitize`](crate::toolkit::sanitize::sanitize) first if the document is
+//! not trusted.
+
+use crate::ast::{
+    AdmonitionType, Block, ColumnAlignment, Document, Inline, ListKind, MathNotation, QuoteType,
+    TableCell, TableRow,
+};
+use crate::formats::comment_text;
+use crate::math::translate;
+use crate::sourcemap::{SourceMap, SourceMapEntry};
+use crate::traits::EmojiStyle;
+use std::collections::BTreeMap;
+
+/// Render the body of `doc` as an HTML fragment
+///
+/// When the document states a language or direction, the content is wrapped
+/// in a `<div>` carrying them.
+pub fn render_html(doc: &Document) -> String {
+    render_html_with_source_map(doc).0
+}
+
+/// Render the body of `doc` as an HTML fragment, recording where each
+/// top-level block was written
+pub fn render_html_with_source_map(doc: &Document) -> (String, SourceMap) {
+    let mut body = String::new();
+    let mut map = SourceMap::default();
+    for (index, block) in doc.content.iter().enumerate() {
+        let start = body.len();
+        render_block(&mut body, block);
+        map.entries.push(SourceMapEntry {
+            block_path: vec![index],
+            output: start..body.trim_end().len(),
+            source: block.span(),
+        });
+    }
+
+    let attrs = root_attributes(doc);
+    if attrs.is_empty() {
+        return (body, map);
+    }
+    let open = format!("<div{}>\n", attrs);
+    map.shift(open.len());
+    (format!("{}{}</div>\n", open, body), map)
+}
+
+/// Render `doc` as a complete HTML5 document
+pub fn render_html_page(doc: &Document) -> String {
+    let title = doc.meta.title.as_deref().unwrap_or("Untitled");
+    format!(
+        "<!DOCTYPE html>\n<html{}>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        root_attributes(doc),
+        escape(title),
+        render_blocks(&doc.content)
+    )
+}
+
+/// ` lang=".." dir=".."` for whichever the document states
+fn root_attributes(doc: &Document) -> String {
+    let mut attrs = String::new();
+    if let Some(language) = &doc.meta.language {
+        attrs.push_str(&format!(" lang=\"{}\"", escape(language)));
+    }
+    if let Some(direction) = doc.meta.text_direction() {
+        attrs.push_str(&format!(" dir=\"{}\"", direction.as_str()));
+    }
+    attrs
+}
+
+fn render_blocks(blocks: &[Block]) -> String {
+    let mut output = String::new();
+    for block in blocks {
+        render_block(&mut output, block);
+    }
+    output
+}
+
+fn render_block(output: &mut String, block: &Block) {
+    match block {
+        Block::Paragraph { content, .. } => {
+            output.push_str(&format!("<p>{}</p>\n", render_inlines(content)));
+        }
+
+        Block::Heading {
+            level,
+            content,
+            id,
+            classes,
+            ..
+        } => {
+            let level = (*level).clamp(1, 6);
+            output.push_str(&format!(
+                "<h{}{}>{}</h{}>\n",
+                level,
+                element_attributes(id.as_deref(), classes, &BTreeMap::new()),
+                render_inlines(content),
+                level
+            ));
+        }
+
+        Block::CodeBlock {
+            language, content, ..
+        } => {
+            let class = language
+                .as_deref()
+                .map(|lang| format!(" class=\"language-{}\"", escape(lang)))
+                .unwrap_or_default();
+            output.push_str(&format!(
+                "<pre><code{}>{}</code></pre>\n",
+                class,
+                escape(content)
+            ));
+        }
+
+        Block::BlockQuote {
+            content,
+            attribution,
+            admonition,
+            ..
+        } => {
+            match admonition {
+                Some(kind) => output.push_str(&format!(
+                    "<aside class=\"admonition {}\">\n",
+                    admonition_class(*kind)
+                )),
+                None => output.push_str("<blockquote>\n"),
+            }
+            output.push_str(&render_blocks(content));
+            if let Some(attribution) = attribution {
+                output.push_str(&format!(
+                    "<footer>{}</footer>\n",
+                    render_inlines(attribution)
+                ));
+            }
+            output.push_str(match admonition {
+                Some(_) => "</aside>\n",
+                None => "</blockquote>\n",
+            });
+        }
+
+        Block::List {
+            kind, items, start, ..
+        } => {
+            let tag = match kind {
+                ListKind::Ordered => "ol",
+                ListKind::Bullet | ListKind::Task => "ul",
+            };
+            match start {
+                Some(start) if *kind == ListKind::Ordered && *start != 1 => {
+                    output.push_str(&format!("<ol start=\"{}\">\n", start))
+                }
+                _ => output.push_str(&format!("<{}>\n", tag)),
+            }
+            for item in items {
+                output.push_str("<li>");
+                if let Some(checked) = item.checked {
+                    output.push_str(if checked {
+                        "<input type=\"checkbox\" checked disabled> "
+                    } else {
+                        "<input type=\"checkbox\" disabled> "
+                    });
+                }
+                output.push_str(&render_item(&item.content));
+                output.push_str("</li>\n");
+            }
+            output.push_str(&format!("</{}>\n", tag));
+        }
+
+        Block::DefinitionList { items, .. } => {
+            output.push_str("<dl>\n");
+            for item in items {
+                output.push_str(&format!("<dt>{}</dt>\n", render_inlines(&item.term)));
+                for definition in &item.definitions {
+                    output.push_str(&format!("<dd>{}</dd>\n", render_item(definition)));
+                }
+            }
+            output.push_str("</dl>\n");
+        }
+
+        Block::Table {
+            caption,
+            columns,
+            header,
+            body,
+            footer,
+            ..
+        } => {
+            let alignments: Vec<ColumnAlignment> = columns.iter().map(|c| c.alignment).collect();
+            output.push_str("<table>\n");
+            if let Some(caption) = caption {
+                output.push_str(&format!("<caption>{}</caption>\n", render_inlines(caption)));
+            }
+            if let Some(header) = header {
+                output.push_str("<thead>\n");
+                render_row(output, header, "th", &alignments);
+                output.push_str("</thead>\n");
+            }
+            if !body.is_empty() {
+                output.push_str("<tbody>\n");
+                for row in body {
+                    render_row(output, row, "td", &alignments);
+                }
+                output.push_str("</tbody>\n");
+            }
+            if let Some(footer) = footer {
+                output.push_str("<tfoot>\n");
+                render_row(output, footer, "td", &alignments);
+                output.push_str("</tfoot>\n");
+            }
+            output.push_str("</table>\n");
+        }
+
+        Block::ThematicBreak { .. } => output.push_str("<hr>\n"),
+
+        Block::MathBlock {
+            content, notation, ..
+        } => {
+            output.push_str(&format!(
+                "<div class=\"math display\">\\[{}\\]</div>\n",
+                escape(&translate(content, *notation, MathNotation::LaTeX))
+            ));
+        }
+
+        Block::Container {
+            id,
+            classes,
+            attributes,
+            content,
+            ..
+        } => {
+            output.push_str(&format!(
+                "<div{}>\n{}</div>\n",
+                element_attributes(id.as_deref(), classes, attributes),
+                render_blocks(content)
+            ));
+        }
+
+        Block::Details {
+            summary,
+            content,
+            open,
+            ..
+        } => {
+            output.push_str(if *open {
+                "<details open>\n"
+            } else {
+                "<details>\n"
+            });
+            if let Some(summary) = summary {
+                output.push_str(&format!("<summary>{}</summary>\n", render_inlines(summary)));
+            }
+            output.push_str(&render_blocks(content));
+            output.push_str("</details>\n");
+        }
+
+        Block::Figure {
+            content,
+            caption,
+            id,
+            ..
+        } => {
+            output.push_str(&format!("<figure{}>\n", id_attribute(id.as_deref())));
+            output.push_str(&render_blocks(content));
+            if let Some(caption) = caption {
+                output.push_str(&format!(
+                    "<figcaption>{}</figcaption>\n",
+                    render_inlines(caption)
+                ));
+            }
+            output.push_str("</figure>\n");
+        }
+
+        Block::Raw { content, .. } => {
+            output.push_str(content);
+            if !content.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+
+        Block::FootnoteDefinition { label, content, .. } => {
+            output.push_str(&format!(
+                "<div class=\"footnote\" id=\"fn-{}\">\n{}</div>\n",
+                escape(label),
+                render_blocks(content)
+            ));
+        }
+
+        // Generated by the viewer, if at all
+        Block::TableOfContents { .. } => {}
+
+        Block::Comment { content, .. } => {
+            output.push_str(&format!("<!-- {} -->\n", comment_text(content, &["--"])));
+        }
+    }
+}
+
+/// A list item or definition: a lone paragraph is written without `<p>`
+fn render_item(blocks: &[Block]) -> String {
+    match blocks {
+        [Block::Paragraph { content, .. }] => render_inlines(content),
+        blocks => format!("\n{}", render_blocks(blocks)),
+    }
+}
+
+fn render_row(output: &mut String, row: &TableRow, tag: &str, alignments: &[ColumnAlignment]) {
+    output.push_str("<tr>");
+    for (i, cell) in row.cells.iter().enumerate() {
+        output.push_str(&render_cell(cell, tag, alignments.get(i).copied()));
+    }
+    output.push_str("</tr>\n");
+}
+
+fn render_cell(cell: &TableCell, tag: &str, column: Option<ColumnAlignment>) -> String {
+    let mut attrs = String::new();
+    if cell.colspan > 1 {
+        attrs.push_str(&format!(" colspan=\"{}\"", cell.colspan));
+    }
+    if cell.rowspan > 1 {
+        attrs.push_str(&format!(" rowspan=\"{}\"", cell.rowspan));
+    }
+    let align = match cell.alignment.or(column) {
+        Some(ColumnAlignment::Left) => Some("left"),
+        Some(ColumnAlignment::Center) => Some("center"),
+        Some(ColumnAlignment::Right) => Some("right"),
+        Some(ColumnAlignment::Default) | None => None,
+    };
+    if let Some(align) = align {
+        attrs.push_str(&format!(" style=\"text-align: {}\"", align));
+    }
+    let content = match cell.content.as_slice() {
+        [Block::Paragraph { content, .. }] => render_inlines(content),
+        blocks => render_blocks(blocks),
+    };
+    format!("<{}{}>{}</{}>", tag, attrs, content, tag)
+}
+
+pub(crate) fn render_inlines(inlines: &[Inline]) -> String {
+    let mut output = String::new();
+    for inline in inlines {
+        render_inline(&mut output, inline);
+    }
+    output
+}
+
+fn render_inline(output: &mut String, inline: &Inline) {
+    let mut wrap = |tag: &str, content: &[Inline]| {
+        output.push_str(&format!("<{}>{}</{}>", tag, render_inlines(content), tag));
+    };
+    match inline {
+        Inline::Text { content } => output.push_str(&escape(content)),
+        Inline::Emphasis { content } => wrap("em", content),
+        Inline::Strong { content } => wrap("strong", content),
+        Inline::Strikethrough { content } => wrap("del", content),
+        Inline::Underline { content } => wrap("u", content),
+        Inline::Superscript { content } => wrap("sup", content),
+        Inline::Subscript { content } => wrap("sub", content),
+        Inline::Highlight { content } => wrap("mark", content),
+        Inline::Quoted {
+            quote_type,
+            content,
+        } => {
+            let (open, close) = match quote_type {
+                QuoteType::Single => ('\u{2018}', '\u{2019}'),
+                QuoteType::Double => ('\u{201C}', '\u{201D}'),
+            };
+            output.push_str(&format!("{}{}{}", open, render_inlines(content), close));
+        }
+        Inline::SmallCaps { content } => output.push_str(&format!(
+            "<span style=\"font-variant: small-caps\">{}</span>",
+            render_inlines(content)
+        )),
+        Inline::Code { content, .. } => {
+            output.push_str(&format!("<code>{}</code>", escape(content)))
+        }
+        Inline::Keyboard { content } => output.push_str(&format!("<kbd>{}</kbd>", escape(content))),
+        Inline::Button { content } => {
+            output.push_str(&format!("<b class=\"button\">{}</b>", escape(content)))
+        }
+        Inline::Menu { items } => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| format!("<b class=\"menu\">{}</b>", escape(item)))
+                .collect();
+            output.push_str(&format!(
+                "<span class=\"menuseq\">{}</span>",
+                items.join("&#160;&#9656;&#160;")
+            ))
+        }
+        Inline::Math { content, notation } => output.push_str(&format!(
+            "<span class=\"math inline\">\\({}\\)</span>",
+            escape(&translate(content, *notation, MathNotation::LaTeX))
+        )),
+        Inline::Link {
+            url,
+            title,
+            content,
+            ..
+        } => {
+            let title = title
+                .as_deref()
+                .map(|t| format!(" title=\"{}\"", escape(t)))
+                .unwrap_or_default();
+            output.push_str(&format!(
+                "<a href=\"{}\"{}>{}</a>",
+                escape(url),
+                title,
+                render_inlines(content)
+            ));
+        }
+        Inline::Image {
+            url,
+            alt,
+            title,
+            width,
+            height,
+        } => {
+            output.push_str(&format!(
+                "<img src=\"{}\" alt=\"{}\"",
+                escape(url),
+                escape(alt)
+            ));
+            for (name, value) in [("title", title), ("width", width), ("height", height)] {
+                if let Some(value) = value {
+                    output.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+                }
+            }
+            output.push('>');
+        }
+        Inline::FootnoteRef { label } => output.push_str(&format!(
+            "<sup><a href=\"#fn-{}\">{}</a></sup>",
+            escape(label),
+            escape(label)
+        )),
+        Inline::Citation { keys, .. } => {
+            output.push_str(&format!("<cite>[{}]</cite>", escape(&keys.join("; "))))
+        }
+        Inline::LineBreak => output.push_str("<br>\n"),
+        Inline::SoftBreak => output.push('\n'),
+        Inline::NonBreakingSpace => output.push_str("&nbsp;"),
+        Inline::Span {
+            id,
+            classes,
+            attributes,
+            content,
+        } => output.push_str(&format!(
+            "<span{}>{}</span>",
+            element_attributes(id.as_deref(), classes, attributes),
+            render_inlines(content)
+        )),
+        Inline::RawInline { content, .. } => output.push_str(content),
+        Inline::Comment { content } => {
+            output.push_str(&format!("<!-- {} -->", comment_text(content, &["--"])))
+        }
+        Inline::Emoji { shortcode } => {
+            let emoji = crate::emoji::render_emoji(shortcode, &EmojiStyle::Unicode);
+            render_inline(output, &emoji)
+        }
+    }
+}
+
+fn id_attribute(id: Option<&str>) -> String {
+    id.map(|id| format!(" id=\"{}\"", escape(id)))
+        .unwrap_or_default()
+}
+
+/// `id`, `class` and the remaining attributes
+fn element_attributes(
+    id: Option<&str>,
+    classes: &[String],
+    attributes: &BTreeMap<String, String>,
+) -> String {
+    let mut attrs = id_attribute(id);
+    if !classes.is_empty() {
+        attrs.push_str(&format!(" class=\"{}\"", escape(&classes.join(" "))));
+    }
+    for (name, value) in attributes {
+        // Event handlers never come from document markup
+        if name.is_empty()
+            || name.starts_with("on")
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+        {
+            continue;
+        }
+        attrs.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+    }
+    attrs
+}
+
+fn admonition_class(kind: AdmonitionType) -> &'static str {
+    match kind {
+        AdmonitionType::Note => "note",
+        AdmonitionType::Tip => "tip",
+        AdmonitionType::Important => "important",
+        AdmonitionType::Warning => "warning",
+        AdmonitionType::Caution => "caution",
+        AdmonitionType::Danger => "danger",
+        AdmonitionType::Custom => "custom",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::TextDirection;
+    use crate::formats::{DjotHandler, MarkdownHandler};
+    use crate::traits::{ParseConfig, Parser};
+
+    #[test]
+    fn test_render_html() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "# Title\n\nSome *emphasis* & [a link](https://example.com \"Go\").\n\n\
+                 - [x] done\n- [ ] todo\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            render_html(&doc),
+            "<h1>Title</h1>\n\
+             <p>Some <em>emphasis</em> &amp; <a href=\"https://example.com\" title=\"Go\">a link</a>.</p>\n\
+             <ul>\n<li><input type=\"checkbox\" checked disabled> done</li>\n\
+             <li><input type=\"checkbox\" disabled> todo</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_direction() {
+        let mut doc = DjotHandler::new()
+            .parse(
+                "مرحبا\n\n{dir=ltr}\n:::\nEnglish aside\n:::\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        doc.meta.language = Some("ar".to_string());
+
+        let html = render_html(&doc);
+        assert!(html.starts_with("<div lang=\"ar\" dir=\"rtl\">\n<p>مرحبا</p>\n"));
+        assert!(html.contains("<div dir=\"ltr\">\n<p>English aside</p>\n</div>\n"));
+
+        let (mapped, map) = render_html_with_source_map(&doc);
+        assert_eq!(mapped, html);
+        assert_eq!(&html[map.entries[0].output.clone()], "<p>مرحبا</p>");
+        assert!(html[map.entries[1].output.clone()].ends_with("aside</p>\n</div>"));
+
+        doc.meta.direction = Some(TextDirection::Ltr);
+        let page = render_html_page(&doc);
+        assert!(page.contains("<html lang=\"ar\" dir=\"ltr\">"));
+    }
+}
@@ -0,0 +1,1560 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Incremental re-parsing for live previews
+//!
+//! [`IncrementalParser`] splits the source into regions at blank lines and
+//! remembers which top-level blocks each region produced. After a
+//! [`TextEdit`] only the regions around the edit are parsed again; every
+//! other block is reused, so typing in a long document costs a parse of a
+//! few paragraphs rather than the whole text.
+//!
+//! Regions never end inside a block that runs past blank lines, such as a
+//! fence, an HTML block, a Djot div, an Org drawer or unclosed Typst markup,
+//! nor inside front matter or an AsciiDoc header. Indented text, list items
+//! after a list and lines that carry on the block before them stay with the
+//! region before them, so each region parses as it would in place. An edit
+//! that leaves a block open past the regions around it, or that the next
+//! region could carry on, parses the whole text again.
+//!
+//! Some formats resolve things across the whole document - link reference
+//! and footnote definitions in Markdown, Djot and Org, and section levels in
+//! reStructuredText - and those documents are parsed as a single region
+//! instead.
+//!
+//! Each region is parsed with [`parse_recovering`], so a broken region is
+//! kept as raw source without affecting the rest. With
+//! `ParseConfig::preserve_spans`, a block that makes up a whole region gets
+//! the region as its span if the parser gave it none.
+
+use crate::ast::{Block, Document, DocumentMeta, SourceFormat, Span};
+use crate::diagnostic::Diagnostic;
+use crate::formats::markdown::front_matter_len;
+use crate::formats::{handler_for, leading_markers};
+use crate::history::TextEdit;
+use crate::recovery::parse_recovering;
+use crate::traits::{ParseConfig, Result};
+use std::ops::Range;
+
+/// A parsed document kept up to date with edits to its source
+#[derive(Debug, Clone)]
+pub struct IncrementalParser {
+    format: SourceFormat,
+    config: ParseConfig,
+    text: String,
+    document: Document,
+    diagnostics: Vec<Diagnostic>,
+    regions: Vec<Region>,
+    /// The text has document-wide definitions, so it is one region
+    whole: bool,
+}
+
+/// Byte range of a region and the line it starts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    start: usize,
+    end: usize,
+    line: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Region {
+    bounds: Bounds,
+    /// Number of top-level blocks parsed from the region
+    blocks: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl IncrementalParser {
+    pub fn new(text: impl Into<String>, format: SourceFormat, config: ParseConfig) -> Self {
+        let mut parser = Self {
+            format,
+            config,
+            text: text.into(),
+            document: Document::new(format),
+            diagnostics: Vec::new(),
+            regions: Vec::new(),
+            whole: false,
+        };
+        parser.reparse_all();
+        parser
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Problems found while parsing, such as regions kept as raw source
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Apply `edit` to the source and bring the document up to date
+    ///
+    /// Returns the range of top-level blocks that were parsed again; all
+    /// blocks outside it are the ones from before the edit.
+    pub fn apply(&mut self, edit: &TextEdit) -> Result<Range<usize>> {
+        edit.apply(&mut self.text)?;
+        if self.whole || self.regions.is_empty() {
+            return Ok(self.reparse_all());
+        }
+
+        // Regions touching the edit and one more on each side, as an edit
+        // can join a region to its neighbours
+        let count = self.regions.len();
+        let old_end = edit.offset + edit.removed.len();
+        let first = self
+            .regions
+            .iter()
+            .position(|r| r.bounds.end >= edit.offset)
+            .unwrap_or(count - 1)
+            .saturating_sub(1);
+        let last = self
+            .regions
+            .iter()
+            .rposition(|r| r.bounds.start <= old_end)
+            .map_or(0, |i| (i + 1).min(count - 1));
+        let bytes = edit.inserted.len() as isize - edit.removed.len() as isize;
+        let lines = newlines(&edit.inserted) - newlines(&edit.removed);
+
+        let (start, line) = match first {
+            0 => (0, 1),
+            _ => (
+                self.regions[first].bounds.start,
+                self.regions[first].bounds.line,
+            ),
+        };
+        let end = match last + 1 == count {
+            true => self.text.len(),
+            false => self.regions[last].bounds.end.saturating_add_signed(bytes),
+        };
+        if whole_document(self.format, &self.text[start..end]) {
+            return Ok(self.reparse_all());
+        }
+        let front = match start {
+            0 => self.front_matter(),
+            _ => 0,
+        };
+        let (bounds, open) = scan(self.format, &self.text[start..end], start, line, front);
+        if open && last + 1 < count {
+            // The window ends inside a block, or the region after it could
+            // carry on the window's last block
+            return Ok(self.reparse_all());
+        }
+
+        let (regions, blocks, meta) = self.parse_regions(bounds);
+        let block_start: usize = self.regions[..first].iter().map(|r| r.blocks).sum();
+        let block_end = block_start
+            + self.regions[first..=last]
+                .iter()
+                .map(|r| r.blocks)
+                .sum::<usize>();
+        for region in &mut self.regions[last + 1..] {
+            region.bounds.start = region.bounds.start.saturating_add_signed(bytes);
+            region.bounds.end = region.bounds.end.saturating_add_signed(bytes);
+            region.bounds.line = region.bounds.line.saturating_add_signed(lines);
+            for diagnostic in &mut region.diagnostics {
+                if let Some(span) = &mut diagnostic.span {
+                    move_span(span, bytes, lines);
+                }
+            }
+        }
+        for block in &mut self.document.content[block_end..] {
+            move_spans(block, bytes, lines);
+        }
+
+        let parsed = blocks.len();
+        self.document.content.splice(block_start..block_end, blocks);
+        self.regions.splice(first..=last, regions);
+        if first == 0 {
+            self.document.meta = meta.unwrap_or_default();
+        }
+        self.finish();
+        Ok(block_start..block_start + parsed)
+    }
+
+    fn reparse_all(&mut self) -> Range<usize> {
+        self.whole = whole_document(self.format, &self.text);
+        let bounds = match self.whole {
+            true => vec![Bounds {
+                start: 0,
+                end: self.text.len(),
+                line: 1,
+            }],
+            false => scan(self.format, &self.text, 0, 1, self.front_matter()).0,
+        };
+        let (regions, blocks, meta) = self.parse_regions(bounds);
+        self.regions = regions;
+        self.document.content = blocks;
+        self.document.meta = meta.unwrap_or_default();
+        self.finish();
+        0..self.document.content.len()
+    }
+
+    /// Parse each region, returning the blocks in order and the metadata
+    /// of the first region
+    fn parse_regions(
+        &self,
+        bounds: Vec<Bounds>,
+    ) -> (Vec<Region>, Vec<Block>, Option<DocumentMeta>) {
+        let handler = handler_for(self.format);
+        let config = ParseConfig {
+            preserve_raw_source: false,
+            ..self.config.clone()
+        };
+        let mut regions = Vec::with_capacity(bounds.len());
+        let mut blocks = Vec::new();
+        let mut meta = None;
+        for bounds in bounds {
+            let source = &self.text[bounds.start..bounds.end];
+            let (mut doc, mut diagnostics) = parse_recovering(handler.as_ref(), source, &config);
+            // Parsers seldom record spans, but a region that is one block
+            // gives that block's span
+            if let [block] = doc.content.as_mut_slice() {
+                if config.preserve_spans && block.span().is_none() {
+                    block.set_span(Some(Span {
+                        start: 0,
+                        end: source.len(),
+                        line: 1,
+                        column: 1,
+                    }));
+                }
+            }
+            let (bytes, lines) = (bounds.start as isize, bounds.line as i32 - 1);
+            for diagnostic in &mut diagnostics {
+                if let Some(span) = &mut diagnostic.span {
+                    move_span(span, bytes, lines);
+                }
+            }
+            meta.get_or_insert(doc.meta);
+            regions.push(Region {
+                bounds,
+                blocks: doc.content.len(),
+                diagnostics,
+            });
+            blocks.extend(doc.content.into_iter().map(|mut block| {
+                move_spans(&mut block, bytes, lines);
+                block
+            }));
+        }
+        (regions, blocks, meta)
+    }
+
+    /// Length of the front matter the parser would read
+    fn front_matter(&self) -> usize {
+        match self.format {
+            SourceFormat::Markdown => {
+                front_matter_len(&self.text, self.config.front_matter_delimiter.as_deref())
+            }
+            _ => 0,
+        }
+    }
+
+    fn finish(&mut self) {
+        self.diagnostics = self
+            .regions
+            .iter()
+            .flat_map(|r| r.diagnostics.iter().cloned())
+            .collect();
+        if self.config.preserve_raw_source {
+            self.document.raw_source = Some(self.text.clone());
+        }
+    }
+}
+
+/// A block that may contain blank lines, and what ends it
+#[derive(Debug)]
+enum Open {
+    /// Code fenced by a run of backticks or tildes, ended by a line of
+    /// nothing else but at least as many, or in Djot exactly as many
+    Fence {
+        fence: char,
+        len: usize,
+        exact: bool,
+    },
+    /// AsciiDoc delimited block, ended by the same line; `nests` when it
+    /// can hold other delimited blocks
+    Delimited { line: String, nests: bool },
+    /// Ends at a line containing one of the markers, ignoring case
+    Until(&'static [&'static str]),
+    /// Markdown HTML block, ended by a blank line
+    Html,
+    /// AsciiDoc section attached to a list item, which takes in the rest of
+    /// the text
+    Section,
+    /// Org block or drawer, ended by the marker on a line of its own,
+    /// ignoring case, or by a line indented less than `indent`, which ends
+    /// the list item it is in; `nests` for drawers, which can hold blocks
+    End {
+        marker: String,
+        nests: bool,
+        indent: usize,
+    },
+    /// Org property drawer of a heading, which runs on through later
+    /// headings to its end
+    Properties,
+    /// Djot div, by the number of colons that opened it
+    Div(usize),
+    /// Typst with a bracket, string, raw text, comment or equation open
+    Typst(TypstState),
+}
+
+impl Open {
+    /// The block opened by `line`, if any; `para` when it follows
+    /// paragraph text
+    fn at(format: SourceFormat, line: &str, para: bool) -> Option<Self> {
+        let trimmed = line.trim();
+        if format == SourceFormat::Typst {
+            let mut state = TypstState::default();
+            state.read(line);
+            return state.is_open().then_some(Open::Typst(state));
+        }
+        // Djot blocks cannot interrupt a paragraph
+        if para && format == SourceFormat::Djot {
+            return None;
+        }
+        if let Some(fence) = fence_at(format, line) {
+            return Some(fence);
+        }
+        let lower = trimmed.to_ascii_lowercase();
+        match format {
+            SourceFormat::Markdown if indent(line) < 4 => html_block(&lower, para),
+            SourceFormat::Djot => {
+                let colons = trimmed.bytes().take_while(|&b| b == b':').count();
+                let class = trimmed[colons..].trim();
+                let named = class
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b':' | b'_' | b'-'));
+                (colons >= 3 && named).then_some(Open::Div(colons))
+            }
+            SourceFormat::OrgMode => {
+                if let Some(name) = lower.strip_prefix("#+begin_") {
+                    let name = name.split_whitespace().next().unwrap_or_default();
+                    return Some(Open::End {
+                        marker: format!("#+end_{}", name),
+                        nests: false,
+                        indent: indent(line),
+                    });
+                }
+                let drawer = lower.strip_prefix(':')?.strip_suffix(':')?;
+                (!drawer.is_empty() && !drawer.contains(char::is_whitespace)).then(|| Open::End {
+                    marker: ":end:".to_string(),
+                    nests: true,
+                    indent: indent(line),
+                })
+            }
+            SourceFormat::AsciiDoc if is_asciidoc_delimiter(trimmed) => Some(Open::Delimited {
+                line: trimmed.to_string(),
+                nests: matches!(trimmed.as_bytes()[0], b'=' | b'*' | b'_' | b'-')
+                    && !trimmed.starts_with("----"),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether this is an Org drawer, which cannot hold another
+    fn is_drawer(&self) -> bool {
+        matches!(self, Open::End { nests: true, .. } | Open::Properties)
+    }
+
+    /// Whether other blocks can open inside this one
+    fn nests(&self) -> bool {
+        matches!(
+            self,
+            Open::Div(_)
+                | Open::Delimited { nests: true, .. }
+                | Open::End { nests: true, .. }
+                | Open::Properties
+                | Open::Section
+        )
+    }
+
+    /// Take in the next line, returning whether it ends the block
+    fn closes(&mut self, line: &str) -> bool {
+        let trimmed = line.trim();
+        match self {
+            Open::Fence { fence, len, exact } => {
+                indent(line) < 4
+                    && trimmed.chars().all(|c| c == *fence)
+                    && (trimmed.len() == *len || (!*exact && trimmed.len() > *len))
+            }
+            Open::Delimited { line, .. } => trimmed == line,
+            Open::Until(markers) => {
+                let lower = trimmed.to_ascii_lowercase();
+                markers.iter().any(|marker| lower.contains(marker))
+            }
+            Open::Html => trimmed.is_empty(),
+            Open::Section => false,
+            Open::End { marker, .. } => trimmed.eq_ignore_ascii_case(marker),
+            Open::Properties => trimmed.eq_ignore_ascii_case(":end:"),
+            Open::Div(len) => trimmed.len() >= *len && trimmed.bytes().all(|b| b == b':'),
+            Open::Typst(state) => {
+                state.read(line);
+                !state.is_open()
+            }
+        }
+    }
+}
+
+/// Markdown HTML blocks that run on past blank lines: how they start, and
+/// the markers that end them
+const HTML_BLOCKS: &[(&[&str], &[&str])] = &[
+    (
+        &["<pre", "<script", "<style", "<textarea"],
+        &["</pre>", "</script>", "</style>", "</textarea>"],
+    ),
+    (&["<!--"], &["-->"]),
+    (&["<?"], &["?>"]),
+    (&["<![cdata["], &["]]>"]),
+    (&["<!"], &[">"]),
+];
+
+/// Markdown tags that start an HTML block even inside a paragraph
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "search",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+];
+
+/// The Markdown HTML block opened by the lowercased, trimmed `line`
+fn html_block(line: &str, para: bool) -> Option<Open> {
+    let rest = line.strip_prefix('<')?;
+    let closing = rest.starts_with('/');
+    let tag = rest.strip_prefix('/').unwrap_or(rest);
+    let name_len = tag
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(tag.len());
+    let (name, after) = tag.split_at(name_len);
+    let ends_name =
+        after.is_empty() || after.starts_with([' ', '\t', '>']) || after.starts_with("/>");
+    let starts = |start: &str| match start.strip_prefix('<').unwrap_or(start) {
+        "!" => rest.starts_with('!') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()),
+        start if start.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+            !closing && ends_name && name == start
+        }
+        start => rest.starts_with(start),
+    };
+    if let Some((_, ends)) = HTML_BLOCKS
+        .iter()
+        .find(|(starts_with, _)| starts_with.iter().any(|start| starts(start)))
+    {
+        return (!ends.iter().any(|end| line.contains(end))).then_some(Open::Until(ends));
+    }
+    if ends_name && HTML_BLOCK_TAGS.contains(&name) {
+        return Some(Open::Html);
+    }
+    // Any other tag on a line of its own, which cannot interrupt a paragraph
+    let whole = match closing {
+        true => after.trim_start() == ">",
+        false => is_tag_end(after),
+    };
+    (!para && whole && name.starts_with(|c: char| c.is_ascii_alphabetic())).then_some(Open::Html)
+}
+
+/// Whether `rest` is the attributes and end of an open tag, and nothing more
+fn is_tag_end(mut rest: &str) -> bool {
+    loop {
+        let trimmed = rest.trim_start();
+        if trimmed == ">" || trimmed == "/>" {
+            return true;
+        }
+        let name_len = trimmed
+            .find(|c: char| !c.is_ascii_alphanumeric() && !matches!(c, '_' | '.' | ':' | '-'))
+            .unwrap_or(trimmed.len());
+        if trimmed.len() == rest.len()
+            || name_len == 0
+            || !trimmed.starts_with(|c: char| c.is_ascii_alphabetic() || matches!(c, '_' | ':'))
+        {
+            return false;
+        }
+        rest = &trimmed[name_len..];
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        rest = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => match value[1..].find(quote) {
+                Some(end) => &value[end + 2..],
+                None => return false,
+            },
+            _ => {
+                let len = value
+                    .find(|c: char| c.is_whitespace() || "\"'=<>`".contains(c))
+                    .unwrap_or(value.len());
+                if len == 0 {
+                    return false;
+                }
+                &value[len..]
+            }
+        };
+    }
+}
+
+/// The code fence opened by `line`, if any
+fn fence_at(format: SourceFormat, line: &str) -> Option<Open> {
+    if format == SourceFormat::OrgMode || indent(line) >= 4 {
+        return None;
+    }
+    let trimmed = line.trim();
+    let fence = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    if fence == '~' && format == SourceFormat::Djot {
+        return None;
+    }
+    let len = trimmed.chars().take_while(|&c| c == fence).count();
+    let info = &trimmed[len..];
+    // Inline code on one line does not open a block, nor does a Djot fence
+    // with more than one word after it
+    let djot = format == SourceFormat::Djot;
+    let invalid =
+        (fence == '`' && info.contains('`')) || (djot && info.trim().contains(char::is_whitespace));
+    (len >= 3 && !invalid).then_some(Open::Fence {
+        fence,
+        len,
+        exact: djot,
+    })
+}
+
+/// What an unfinished stretch of Typst leaves open, innermost last
+#[derive(Debug, Default)]
+struct TypstState(Vec<Typst>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Typst {
+    /// Parentheses or braces, holding code
+    Paren,
+    Brace,
+    /// Brackets, holding markup
+    Bracket,
+    Math,
+    String,
+    /// Raw text, by the length of the backtick run that opened it
+    Raw(usize),
+    Comment,
+}
+
+/// Keywords after `#` that make the rest of the line code
+const TYPST_STATEMENTS: &[&str] = &[
+    "let", "set", "show", "import", "include", "if", "for", "while", "return", "context",
+];
+
+impl TypstState {
+    fn is_open(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn read(&mut self, line: &str) {
+        let chars: Vec<char> = line.chars().collect();
+        // Depth at which a statement made the rest of the line code
+        let mut statement = None;
+        // After `#name` or a call, where brackets start arguments
+        let mut call = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let (c, next) = (chars[i], chars.get(i + 1).copied());
+            let run = chars[i..].iter().take_while(|&&b| b == '`').count();
+            match self.0.last() {
+                Some(&Typst::Raw(len)) => {
+                    // A single backtick closes at the first one, and any
+                    // after it start raw text again
+                    if len == 1 && run > 0 {
+                        self.0.pop();
+                        i += 1;
+                        continue;
+                    }
+                    if run == len {
+                        self.0.pop();
+                    }
+                    i += run.max(1);
+                    continue;
+                }
+                Some(Typst::Comment) => {
+                    match (c, next) {
+                        ('*', Some('/')) => drop(self.0.pop()),
+                        ('/', Some('*')) => self.0.push(Typst::Comment),
+                        _ => {
+                            i += 1;
+                            continue;
+                        }
+                    }
+                    i += 2;
+                    continue;
+                }
+                Some(Typst::String) => {
+                    match c {
+                        '\\' => i += 1,
+                        '"' => drop(self.0.pop()),
+                        _ => {}
+                    }
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            let top = self.0.last().copied();
+            let code =
+                matches!(top, Some(Typst::Paren | Typst::Brace)) || statement == Some(self.0.len());
+            match (c, next) {
+                ('\\', _) if !code => i += 1,
+                ('`', _) if top != Some(Typst::Math) => {
+                    // Two backticks are empty raw text
+                    if run != 2 {
+                        self.0.push(Typst::Raw(run));
+                    }
+                    i += run - 1;
+                }
+                ('/', Some('*')) => {
+                    self.0.push(Typst::Comment);
+                    i += 1;
+                }
+                ('/', Some('/')) if i == 0 || chars[i - 1] != ':' => return,
+                ('"', _) if code || top == Some(Typst::Math) => self.0.push(Typst::String),
+                ('$', _) if top == Some(Typst::Math) => drop(self.0.pop()),
+                ('$', _) => self.0.push(Typst::Math),
+                ('#', _) if !code => {
+                    let word: String = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_alphanumeric())
+                        .collect();
+                    if TYPST_STATEMENTS.contains(&word.as_str()) {
+                        statement = Some(self.0.len());
+                    }
+                    call = true;
+                    i += 1;
+                    continue;
+                }
+                // Brackets in markup and equations are text unless they
+                // follow a call or are in a content block
+                ('(', _) if code || call => self.0.push(Typst::Paren),
+                ('{', _) if code || call => self.0.push(Typst::Brace),
+                ('[', _) if code || call || top == Some(Typst::Bracket) => {
+                    self.0.push(Typst::Bracket)
+                }
+                (')' | ']' | '}', _) => {
+                    let closes = match c {
+                        ')' => Typst::Paren,
+                        ']' => Typst::Bracket,
+                        _ => Typst::Brace,
+                    };
+                    if top == Some(closes) {
+                        self.0.pop();
+                        // Trailing content arguments may follow
+                        call = true;
+                        i += 1;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            call = call && (c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+            i += 1;
+        }
+    }
+}
+
+/// Split `text` into regions, returning them with offsets from `base` and
+/// whether the text after it would join the last region
+///
+/// The first `front` bytes of a text starting at 0 are front matter.
+fn scan(
+    format: SourceFormat,
+    text: &str,
+    base: usize,
+    first_line: u32,
+    front: usize,
+) -> (Vec<Bounds>, bool) {
+    let mut regions: Vec<Bounds> = Vec::new();
+    let mut current: Option<Bounds> = None;
+    let mut open: Vec<Open> = Vec::new();
+    // Blank lines since the last line of `current`
+    let mut gap = false;
+    // The last line carries on past blank lines
+    let mut carried = false;
+    // The last line was paragraph text
+    let mut para = false;
+    // The last line was blank or ended a block
+    let mut fresh = true;
+    // The text since the last blank line that started a block of its own
+    // has a list item, which later items after blank lines carry on
+    let mut listed = false;
+    // Column of the content of the list item, quote or Djot definition
+    // the last line is in
+    let mut nested: Option<usize> = None;
+    // The last line that was not blank was an Org heading
+    let mut heading = false;
+    // The last line was blank or ended a block
+    let mut after_blank = true;
+    // The last line was an AsciiDoc comment taken as paragraph text
+    let mut swallowed = false;
+    // Past the comments that may start an AsciiDoc document
+    let mut started = base > 0;
+    // Indentation of the bullet of the Org list item the last line is in
+    let mut list: Option<usize> = None;
+    // The last line was an AsciiDoc list continuation
+    let mut attached = false;
+    // Lines the AsciiDoc document header may still take besides attribute
+    // entries, once the title is seen
+    let mut header: Option<usize> = None;
+    let mut offset = base;
+
+    for (number, line) in (first_line..).zip(text.split_inclusive('\n')) {
+        let end = offset + line.len();
+        let blank = line.trim().is_empty();
+        // Org blocks and drawers other than properties end at a heading
+        let stars = line.trim_start_matches('*');
+        let org_heading =
+            format == SourceFormat::OrgMode && stars.len() < line.len() && stars.starts_with(' ');
+        if org_heading {
+            open.retain(|block| matches!(block, Open::Properties));
+        }
+        if format == SourceFormat::OrgMode && !blank {
+            let outdented =
+                |block: &Open| matches!(block, Open::End { indent: at, .. } if indent(line) < *at);
+            if let Some(index) = open.iter().position(outdented) {
+                open.truncate(index);
+            }
+            let word = line.split_whitespace().next().unwrap_or_default();
+            let digits = word.strip_suffix('.').unwrap_or_default();
+            let item = matches!(word, "-" | "+")
+                || (indent(line) > 0 && word == "*")
+                || (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()));
+            let inside = list.filter(|&bullet| indent(line) > bullet);
+            list = match item {
+                true => Some(indent(line).min(inside.unwrap_or(usize::MAX))),
+                false => inside,
+            };
+        }
+        let inside = open.last().is_some_and(|block| !block.nests());
+        // The header takes attribute entries and comments, and the author
+        // and revision lines
+        let in_header = match &mut header {
+            Some(_) if blank => false,
+            Some(_) if is_comment(line) || is_attribute_entry(line) => true,
+            Some(lines) if *lines > 0 => {
+                *lines -= 1;
+                true
+            }
+            _ => false,
+        };
+        if !in_header {
+            header = None;
+        }
+        if end <= front || in_header {
+            // Front matter and document headers hold no blocks
+        } else if let Some(index) = closing(&mut open, line) {
+            open.truncate(index);
+            gap = blank && open.is_empty();
+            carried = false;
+            para = false;
+            fresh = true;
+            after_blank = true;
+            swallowed = false;
+        } else if inside {
+            // Nothing opens inside a verbatim block
+        } else if blank {
+            gap = open.is_empty();
+            para = false;
+            fresh = true;
+            after_blank = true;
+            swallowed = false;
+            attached = false;
+        } else {
+            if gap {
+                if !carried && !joins_previous(format, line, listed) {
+                    regions.extend(current.take());
+                }
+                listed = listed
+                    && (carried || line.starts_with([' ', '\t']) || is_skipped(format, line));
+            }
+            listed = listed || is_list_item(format, line);
+            gap = false;
+            if format == SourceFormat::AsciiDoc && !started && line.starts_with("= ") {
+                header = Some(2);
+            }
+            let mut opened = match format {
+                SourceFormat::AsciiDoc
+                    if attached
+                        && line.starts_with('=')
+                        && line.trim_start_matches('=').starts_with(' ') =>
+                {
+                    Some(Open::Section)
+                }
+                SourceFormat::OrgMode
+                    if heading && line.trim().eq_ignore_ascii_case(":properties:") =>
+                {
+                    Some(Open::Properties)
+                }
+                // A comment starting a block is paragraph text, and so is
+                // the line after it
+                SourceFormat::AsciiDoc if swallowed => None,
+                _ => Open::at(format, line, para),
+            }
+            .filter(|block| !(block.is_drawer() && open.iter().any(Open::is_drawer)));
+            // Only a block in a list item ends with it
+            if let Some(Open::End { indent, .. }) = &mut opened {
+                if list.is_none() {
+                    *indent = 0;
+                }
+            }
+            heading = org_heading;
+            swallowed =
+                format == SourceFormat::AsciiDoc && is_comment(line) && started && after_blank;
+            started = started || !is_comment(line);
+            after_blank = false;
+            attached = format == SourceFormat::AsciiDoc
+                && (line.trim() == "+" || (attached && is_attribute_entry(line)));
+            let plain = is_paragraph(format, line, para);
+            let container = is_container(format, line);
+            // Lines after a list item or quote, and indented lines after a
+            // blank one, carry it on
+            let continues = !fresh || nested.is_some_and(|column| indent(line) >= column);
+            let starts = fresh && plain && !container && !(nested.is_some() && continues);
+            para = opened.is_none()
+                && match format {
+                    // A Djot paragraph takes in every line up to a blank
+                    // one
+                    SourceFormat::Djot => para || starts,
+                    _ => (para && plain) || starts,
+                };
+            // A nested container keeps the column of the one around it
+            let outer = nested.filter(|_| continues);
+            nested = match container {
+                true => Some(content_column(format, line).min(outer.unwrap_or(usize::MAX))),
+                false => outer,
+            };
+            fresh = opened.is_some() || (!para && !plain && !container);
+            open.extend(opened);
+            // AsciiDoc comments leave the block before them open
+            if !(format == SourceFormat::AsciiDoc && line.starts_with("//")) {
+                carried = carries_on(format, line);
+            }
+        }
+
+        if !open.is_empty() || !blank {
+            // Leading blank lines stay in the first Markdown region, so it
+            // cannot be taken for front matter
+            let start = match regions.is_empty() && format == SourceFormat::Markdown {
+                true => (base, first_line),
+                false => (offset, number),
+            };
+            current
+                .get_or_insert(Bounds {
+                    start: start.0,
+                    end,
+                    line: start.1,
+                })
+                .end = end;
+        }
+        offset = end;
+    }
+    regions.extend(current);
+    (
+        regions,
+        !open.is_empty() || carried || base + text.len() < front,
+    )
+}
+
+/// Whether `line` is paragraph text, which Djot blocks and some Markdown
+/// HTML blocks cannot interrupt; `para` when the line before it was
+fn is_paragraph(format: SourceFormat, line: &str, para: bool) -> bool {
+    let trimmed = line.trim();
+    // Setext underlines after a paragraph
+    let rule = is_break(trimmed) || (para && trimmed.chars().all(|c| c == '='));
+    match format {
+        SourceFormat::Markdown => {
+            !rule
+                && (para || indent(line) < 4)
+                && !((trimmed.starts_with('#')
+                    && trimmed.trim_start_matches('#').starts_with([' ', '\t']))
+                    || trimmed.chars().all(|c| c == '#'))
+        }
+        SourceFormat::Djot => {
+            let row = trimmed.len() > 1 && trimmed.starts_with('|') && trimmed.ends_with('|');
+            !rule && !row && !is_attributes(trimmed) && !is_container(format, line)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `line` starts a list item or quote, or a Djot heading or
+/// definition, whose lines carry on after it
+fn is_container(format: SourceFormat, line: &str) -> bool {
+    let trimmed = line.trim();
+    let number = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+    let item = trimmed.starts_with('>')
+        || leading_markers(line, &["-", "*", "+"]) > 0
+        || (number.len() < trimmed.len() && number.starts_with(['.', ')']));
+    match format {
+        SourceFormat::Markdown => item && !is_break(trimmed),
+        // Djot markers need a space after them
+        SourceFormat::Djot => {
+            let marked = |marker: &str| {
+                trimmed
+                    .strip_prefix(marker)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+            };
+            let quote = !trimmed.starts_with('>') || marked(">");
+            let hashes = trimmed.len() - trimmed.trim_start_matches('#').len();
+            (item && quote && !is_break(trimmed))
+                || (hashes > 0 && marked(&trimmed[..hashes]))
+                || marked(":")
+        }
+        _ => false,
+    }
+}
+
+/// Whether `trimmed` is a line of Djot block attributes
+fn is_attributes(trimmed: &str) -> bool {
+    let Some(inner) = trimmed.strip_prefix('{').and_then(|t| t.strip_suffix('}')) else {
+        return false;
+    };
+    !inner.trim().is_empty()
+        && inner.split_whitespace().all(|part| {
+            part.starts_with(['#', '.', '%'])
+                || part.split_once('=').is_some_and(|(key, _)| !key.is_empty())
+        })
+}
+
+/// Column from which lines after a blank one carry on the container
+/// started by `line`; quotes and headings end at a blank line
+fn content_column(format: SourceFormat, line: &str) -> usize {
+    let rest = line.trim_start();
+    if rest.starts_with(['>', '#']) {
+        return usize::MAX;
+    }
+    // Djot takes any line indented past the marker
+    if format == SourceFormat::Djot {
+        return indent(line) + 1;
+    }
+    let marker = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    indent(line) + marker + indent(&rest[marker..])
+}
+
+/// Whether the trimmed `line` is a thematic break
+fn is_break(line: &str) -> bool {
+    line.len() >= 3 && line.chars().all(|c| matches!(c, '-' | '*' | '_' | ' '))
+}
+
+/// Index of the open block that `line` ends, if any
+fn closing(open: &mut [Open], line: &str) -> Option<usize> {
+    let last = open.len().checked_sub(1)?;
+    // A Djot div ends the outermost div it can, with the blocks inside it
+    open.iter_mut()
+        .position(|block| matches!(block, Open::Div(_)) && block.closes(line))
+        .or_else(|| open[last].closes(line).then_some(last))
+}
+
+/// Whether a chunk starting with `line` belongs to the block before it,
+/// which is `listed` if it has a list item
+fn joins_previous(format: SourceFormat, line: &str, listed: bool) -> bool {
+    if line.starts_with([' ', '\t']) {
+        return true;
+    }
+    match format {
+        // A later region starting like front matter would be taken for it
+        SourceFormat::Markdown if matches!(line.trim_end(), "---" | "+++") => return true,
+        // Table rows carry on past blank lines
+        SourceFormat::Djot if line.starts_with('|') => return true,
+        // Property drawers belong to the heading before them
+        SourceFormat::OrgMode if line.trim().eq_ignore_ascii_case(":properties:") => return true,
+        // Comments, attribute entries and document titles only stand alone
+        // at the start, a description list after a list is nested in it,
+        // and titles and attributes may belong to such a list
+        SourceFormat::AsciiDoc
+            if line.starts_with(['/', '.', '['])
+                || line.starts_with("= ")
+                || is_term(line)
+                || is_attribute_entry(line) =>
+        {
+            return true
+        }
+        // The list before skipped lines carries on past them
+        SourceFormat::Typst if is_skipped(format, line) => return true,
+        _ => {}
+    }
+    listed && is_list_item(format, line)
+}
+
+/// Whether the parser skips `line`, as it does Typst comments and stray
+/// comment ends
+fn is_skipped(format: SourceFormat, line: &str) -> bool {
+    format == SourceFormat::Typst && ["*/", "//", "/*"].iter().any(|c| line.starts_with(c))
+}
+
+/// Whether `line` starts a list item or AsciiDoc description
+fn is_list_item(format: SourceFormat, line: &str) -> bool {
+    if format == SourceFormat::AsciiDoc && is_term(line) {
+        return true;
+    }
+    let markers: &[&str] = match format {
+        SourceFormat::Markdown => &["-", "*", "+"],
+        SourceFormat::Djot => &["-", "*", "+", ":"],
+        SourceFormat::OrgMode => &["-", "+"],
+        SourceFormat::AsciiDoc => &["*", "**", "***", "-", ".", "..", "...", "+"],
+        SourceFormat::Typst => &["-", "+", "/"],
+        _ => &[],
+    };
+    leading_markers(line, markers) > 0
+}
+
+/// Whether a chunk ending with `line` runs on into the next one
+fn carries_on(format: SourceFormat, line: &str) -> bool {
+    let trimmed = line.trim();
+    match format {
+        // Attributes and captions belong to the next block
+        SourceFormat::Djot => trimmed.starts_with('{') && trimmed.ends_with('}'),
+        SourceFormat::OrgMode => trimmed.starts_with("#+"),
+        // List continuations, description terms, attribute lists and titles
+        SourceFormat::AsciiDoc => {
+            trimmed == "+"
+                || is_term(trimmed)
+                || (trimmed.starts_with('[') && trimmed.ends_with(']'))
+                || (trimmed.starts_with('.') && !trimmed.starts_with(". "))
+        }
+        _ => false,
+    }
+}
+
+/// Whether `line` is an AsciiDoc line comment
+fn is_comment(line: &str) -> bool {
+    line.starts_with("//") && !line.starts_with("////")
+}
+
+/// Whether `line` is an AsciiDoc attribute entry such as `:toc: left`
+fn is_attribute_entry(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix(':') else {
+        return false;
+    };
+    let rest = rest.strip_prefix('!').unwrap_or(rest);
+    let name = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-')
+        .unwrap_or(rest.len());
+    let rest = rest[name..].strip_prefix('!').unwrap_or(&rest[name..]);
+    name > 0
+        && rest
+            .strip_prefix(':')
+            .is_some_and(|rest| rest.trim().len() < rest.len() || rest.is_empty())
+}
+
+/// Whether `line` is an AsciiDoc description list term
+fn is_term(line: &str) -> bool {
+    let line = line.trim_end();
+    [":: ", "::\t", "::: ", ";; "]
+        .iter()
+        .any(|marker| line.contains(marker))
+        || line.ends_with("::")
+        || line.ends_with(";;")
+}
+
+/// Width of the whitespace starting `line`
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether `text` has definitions that other regions may refer to
+fn whole_document(format: SourceFormat, text: &str) -> bool {
+    match format {
+        // Section levels follow the order adornments first appear in
+        SourceFormat::ReStructuredText => true,
+        SourceFormat::Markdown | SourceFormat::Djot => text.lines().any(|line| {
+            let line = line.trim_start();
+            line.starts_with('[') && line.contains("]:")
+        }),
+        SourceFormat::OrgMode => text.lines().any(|line| line.starts_with("[fn:")),
+        _ => false,
+    }
+}
+
+/// Whether `line` delimits an AsciiDoc block; tables are plain blocks
+/// to the parser, so they are not counted
+fn is_asciidoc_delimiter(line: &str) -> bool {
+    if line == "--" {
+        return true;
+    }
+    let mut chars = line.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    line.len() >= 4 && "-=*+._/".contains(first) && chars.all(|c| c == first)
+}
+
+fn newlines(text: &str) -> i32 {
+    text.bytes().filter(|&b| b == b'\n').count() as i32
+}
+
+fn move_span(span: &mut Span, bytes: isize, lines: i32) {
+    span.start = span.start.saturating_add_signed(bytes);
+    span.end = span.end.saturating_add_signed(bytes);
+    span.line = span.line.saturating_add_signed(lines);
+}
+
+fn move_spans(block: &mut Block, bytes: isize, lines: i32) {
+    if let Some(span) = block.span_mut() {
+        move_span(span, bytes, lines);
+    }
+    for child in block.child_blocks_mut() {
+        move_spans(child, bytes, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The blocks from parsing the whole text at once
+    pub(super) fn parse_whole(format: SourceFormat, text: &str) -> String {
+        let config = ParseConfig::default();
+        let (doc, _) = parse_recovering(handler_for(format).as_ref(), text, &config);
+        format!("{:?}", doc.content)
+    }
+
+    fn edit(parser: &mut IncrementalParser, from: &str, to: &str) -> Range<usize> {
+        let offset = parser.text().find(from).unwrap();
+        let range = parser
+            .apply(&TextEdit {
+                offset,
+                removed: from.to_string(),
+                inserted: to.to_string(),
+            })
+            .unwrap();
+
+        // The result must match parsing the new text from scratch
+        assert_eq!(
+            format!("{:?}", parser.document().content),
+            parse_whole(parser.format, parser.text()),
+        );
+        let fresh = IncrementalParser::new(parser.text(), parser.format, ParseConfig::default());
+        assert_eq!(parser.regions.len(), fresh.regions.len());
+        range
+    }
+
+    #[test]
+    fn test_reparses_only_the_edited_region() {
+        let text = "# Title\n\nOne.\n\n- a\n\n- b\n\n      indented\n\nTwo.\n\nThree.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::Markdown, ParseConfig::default());
+        assert_eq!(parser.regions.len(), 5);
+
+        assert_eq!(edit(&mut parser, "Two.", "Two and a half."), 2..5);
+        assert_eq!(edit(&mut parser, "One.", "One.\n\nOne more."), 0..4);
+        assert_eq!(parser.document().content.len(), 6);
+        assert_eq!(edit(&mut parser, "\n\nOne more.", ""), 0..3);
+        assert_eq!(edit(&mut parser, "# Title", "# New title"), 0..2);
+    }
+
+    #[test]
+    fn test_fences_and_definitions() {
+        let text = "Intro.\n\nCode:\n\nMiddle.\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::Markdown, ParseConfig::default());
+
+        // An unclosed fence runs to the end of the text
+        edit(&mut parser, "Code:", "```");
+        assert_eq!(parser.document().content.len(), 2);
+        edit(&mut parser, "Middle.", "Middle.\n```");
+        assert_eq!(parser.document().content.len(), 3);
+
+        // A footnote definition switches to parsing the whole text
+        let range = edit(&mut parser, "End.", "End.[^1]\n\n[^1]: Note.");
+        assert_eq!(range, 0..parser.document().content.len());
+        assert!(parser.whole);
+    }
+
+    #[test]
+    fn test_later_regions_move() {
+        let text = "= Title\n\nFirst.\n\n----\ncode\n\nmore\n----\n\nLast.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::AsciiDoc, ParseConfig::default());
+        assert_eq!(parser.regions.len(), 4);
+        edit(&mut parser, "First.", "First, longer.");
+        assert_eq!(
+            parser.regions[2].bounds.start,
+            text.find("----").unwrap() + 8
+        );
+        assert_eq!(parser.regions[3].bounds.line, 11);
+    }
+
+    #[test]
+    fn test_blocks_running_past_the_window() {
+        // A fence opened before the regions after the window holds them
+        let text = "Intro.\n\nMiddle.\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::Markdown, ParseConfig::default());
+        let range = edit(&mut parser, "Intro.", "```");
+        assert_eq!(range, 0..1);
+        edit(&mut parser, "```", "Intro.");
+        assert_eq!(parser.document().content.len(), 3);
+
+        // A closing fence only has the fence characters on it
+        edit(&mut parser, "Middle.", "```\ncode\n\n``` not closed");
+        edit(&mut parser, "``` not closed", "```");
+    }
+
+    #[test]
+    fn test_markdown_blocks() {
+        // Front matter and HTML blocks may hold blank lines
+        let text = "---\ntitle: x\n\nmore: y\n---\n\nIntro.\n\n<pre>\ncode\n\nmore\n</pre>\n\n<!-- c\n\n-->\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::Markdown, ParseConfig::default());
+        edit(&mut parser, "Intro.", "Intro, edited.");
+        edit(&mut parser, "more\n", "more code\n");
+        edit(&mut parser, "-->", "still a comment");
+        edit(&mut parser, "title: x", "title: y");
+
+        // A block-level tag starts a block that ends at a blank line
+        edit(&mut parser, "End.", "<div>\n*text*\n\nEnd.");
+
+        // A region starting like front matter stays with the one before it,
+        // and so do leading blank lines
+        let text = "\n\nIntro.\n\n---\ntitle: x\n---\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::Markdown, ParseConfig::default());
+        edit(&mut parser, "End.", "End, edited.");
+        edit(&mut parser, "Intro.", "");
+    }
+
+    #[test]
+    fn test_asciidoc_blocks() {
+        // The document header takes attribute entries past the title, and
+        // delimited blocks nest unless they are verbatim
+        let text = "// comment\n= Title\nAuthor\n:toc: left\n\nIntro.\n\n====\nExample.\n\n----\ncode\n\nmore\n----\n\n====\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::AsciiDoc, ParseConfig::default());
+        edit(&mut parser, "Intro.", "Intro, edited.");
+        edit(&mut parser, "code", "more code");
+        edit(&mut parser, "End.", "End, edited.");
+
+        // Titles, attribute lists and list continuations carry on past
+        // blank lines, and a section attached to a list item takes in the
+        // rest of the text
+        let text = ".Title\n[source]\n\n----\ncode\n----\n\n* item\n+\n\nterm:: def\n\nMore.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::AsciiDoc, ParseConfig::default());
+        edit(&mut parser, "More.", "More, edited.");
+        edit(&mut parser, "+\n", "+\n== Section\n");
+        edit(&mut parser, "code", "more code");
+
+        // A comment starting a block is paragraph text
+        let text = "Intro.\n\n// comment\n----\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::AsciiDoc, ParseConfig::default());
+        edit(&mut parser, "End.", "End, edited.");
+    }
+
+    #[test]
+    fn test_org_blocks() {
+        // Drawers hold blank lines and blocks, and property drawers run
+        // through later headings
+        let text = "* Head\n:PROPERTIES:\n:ID: x\n\n** Sub\n:END:\n\n:LOGBOOK:\n\n#+begin_src\n\n:END:\n#+end_src\n:END:\n\nText.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::OrgMode, ParseConfig::default());
+        edit(&mut parser, "Text.", "Text, edited.");
+        edit(&mut parser, ":ID: x", ":ID: y");
+        edit(&mut parser, "#+end_src", "#+END_SRC");
+
+        // Keywords belong to the block after them, and a block in a list
+        // item ends with it
+        let text = "#+NAME: x\n\n| a |\n\n- item\n  #+begin_quote\n\n  text\n- next\n\nEnd.\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::OrgMode, ParseConfig::default());
+        edit(&mut parser, "| a |", "| b |");
+        edit(&mut parser, "End.", "End, edited.");
+
+        // A quote line carries on the list item before it
+        let text = "\n> quote\n- item\n\n- item\n";
+        let mut parser =
+            IncrementalParser::new(text, SourceFormat::OrgMode, ParseConfig::default());
+        assert_eq!(
+            format!("{:?}", parser.document().content),
+            parse_whole(SourceFormat::OrgMode, text),
+        );
+        edit(
+            &mut parser,
+            "- item\n\n- item\n",
+            "- item\n\n- item\n> quote",
+        );
+    }
+
+    #[test]
+    fn test_typst_markup() {
+        // Unclosed raw text, comments and content blocks hold the rest of
+        // the text
+        let mut parser = IncrementalParser::new(
+            "Intro.\n\nLater.\n\nEnd.\n",
+            SourceFormat::Typst,
+            ParseConfig::default(),
+        );
+        edit(&mut parser, "Intro.", "```\nraw");
+        edit(&mut parser, "```", "");
+        edit(&mut parser, "Later.", "#box[\n\ncontent");
+        edit(&mut parser, "End.", "End.]");
+        edit(&mut parser, "#box[", "/* comment");
+
+        // Brackets in markup are text, and a comment starting a region
+        // leaves the list before it open
+        let text = "- a [b\n\n- c\n\n// note\n\n- d\n";
+        let mut parser = IncrementalParser::new(text, SourceFormat::Typst, ParseConfig::default());
+        edit(&mut parser, "- c", "- c, edited");
+        edit(&mut parser, "- d", "- d, edited");
+    }
+
+    #[test]
+    fn test_djot_blocks() {
+        // Divs nest, and a closing line ends the outermost div it can
+        let text = "Intro.\n\n::: outer\n\n:::: inner\n\nText.\n\n::::\n\nMore.\n\n:::\n\nEnd.\n";
+        let mut parser = IncrementalParser::new(text, SourceFormat::Djot, ParseConfig::default());
+        edit(&mut parser, "More.", "More, edited.");
+        edit(&mut parser, "::::\n\nMore", "More");
+        edit(&mut parser, "Text.", "Text.\n\n::::::");
+
+        // Attributes and table rows carry on past blank lines, and a fence
+        // closes only at the same number of backticks
+        let text = "Intro.\n\n{.note}\n\nPara.\n\n| a |\n\n| b |\n\n````\ncode\n\n```\n\n````\n";
+        let mut parser = IncrementalParser::new(text, SourceFormat::Djot, ParseConfig::default());
+        edit(&mut parser, "Para.", "Para, edited.");
+        edit(&mut parser, "| b |", "| c |");
+        edit(&mut parser, "code", "more code");
+
+        // Nothing opens in the middle of a paragraph
+        let mut parser = IncrementalParser::new(
+            "Intro.\n\nText\n\nEnd.\n",
+            SourceFormat::Djot,
+            ParseConfig::default(),
+        );
+        edit(&mut parser, "Text", "Text\n```");
+        edit(&mut parser, "End.", "End.\n::: note");
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::tests::parse_whole;
+    use super::*;
+    use proptest::prelude::*;
+
+    // Lines for each format, covering blocks that run past blank lines
+    fn lines(format: SourceFormat) -> &'static [&'static str] {
+        match format {
+            SourceFormat::Markdown => &[
+                "# Head",
+                "Text *em*",
+                "- item",
+                "1. item",
+                "  indented",
+                "    code",
+                "> quote",
+                "```",
+                "```js",
+                "~~~",
+                "<!-- c",
+                "-->",
+                "<pre>",
+                "</pre>",
+                "<div>",
+                "</div>",
+                "---",
+                "| a | b |",
+                "|---|---|",
+                "===",
+                "Setext",
+                "a: b",
+                "",
+            ],
+            SourceFormat::Djot => &[
+                "# Head",
+                "Text _em_",
+                "- item",
+                "1. item",
+                "  indented",
+                "> quote",
+                ":::",
+                "::: warning",
+                "::::",
+                "```",
+                "````",
+                "{.cls}",
+                "Term",
+                ": def",
+                "| a |",
+                "---",
+                "",
+            ],
+            SourceFormat::OrgMode => &[
+                "* Head",
+                "** Sub",
+                "Text /em/",
+                "- item",
+                "1. item",
+                "  indented",
+                "> quote",
+                "#+begin_src",
+                "#+end_src",
+                "#+BEGIN_EXAMPLE",
+                "#+END_EXAMPLE",
+                ":PROPERTIES:",
+                ":LOGBOOK:",
+                ":END:",
+                "#+NAME: x",
+                "| a |",
+                ": fixed",
+                "",
+            ],
+            SourceFormat::AsciiDoc => &[
+                "= Title",
+                "== Head",
+                "Text _em_",
+                "* item",
+                ". item",
+                "term::",
+                "+",
+                "----",
+                "====",
+                "....",
+                "****",
+                "[source]",
+                ".Title",
+                "[NOTE]",
+                ":toc: left",
+                "////",
+                "",
+            ],
+            _ => &[
+                "= Head",
+                "Text *strong*",
+                "- item",
+                "/ Term: def",
+                "```",
+                "```rust",
+                "#let x = (",
+                ")",
+                "#[",
+                "]",
+                "/* c",
+                "*/",
+                "`raw",
+                "$ x $",
+                "$",
+                "// c",
+                "#{",
+                "}",
+                "\"str",
+                "",
+            ],
+        }
+    }
+
+    fn format_strategy() -> impl Strategy<Value = SourceFormat> {
+        prop_oneof![
+            Just(SourceFormat::Markdown),
+            Just(SourceFormat::Djot),
+            Just(SourceFormat::OrgMode),
+            Just(SourceFormat::AsciiDoc),
+            Just(SourceFormat::Typst),
+        ]
+    }
+
+    // A text, and an edit replacing some of its lines with others
+    fn edit_strategy() -> impl Strategy<Value = (SourceFormat, String, TextEdit)> {
+        format_strategy().prop_flat_map(|format| {
+            let line = prop::sample::select(lines(format));
+            (
+                Just(format),
+                prop::collection::vec(line.clone(), 1..12),
+                prop::collection::vec(line, 0..3),
+                any::<prop::sample::Index>(),
+                0..3usize,
+            )
+                .prop_map(|(format, lines, inserted, at, removed)| {
+                    let join = |lines: &[&str]| -> String {
+                        lines.iter().map(|line| format!("{}\n", line)).collect()
+                    };
+                    let at = at.index(lines.len() + 1);
+                    let removed = removed.min(lines.len() - at);
+                    let edit = TextEdit {
+                        offset: join(&lines[..at]).len(),
+                        removed: join(&lines[at..at + removed]),
+                        inserted: join(&inserted),
+                    };
+                    (format, join(&lines), edit)
+                })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_edits_match_a_full_parse((format, text, edit) in edit_strategy()) {
+            let mut parser = IncrementalParser::new(&text, format, ParseConfig::default());
+            parser.apply(&edit).unwrap();
+            // Regions that fail to parse are recovered on their own, so
+            // only texts without errors parse the same either way
+            let config = ParseConfig::default();
+            let (_, diagnostics) = parse_recovering(handler_for(format).as_ref(), parser.text(), &config);
+            prop_assume!(diagnostics.is_empty());
+            prop_assert_eq!(
+                format!("{:?}", parser.document().content),
+                parse_whole(format, parser.text())
+            );
+        }
+    }
+}
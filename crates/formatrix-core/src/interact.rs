@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Interactive conversion - decisions a conversion cannot make on its own
+//!
+//! File operations reach a few points where a person may reasonably want a
+//! say: content that looks like several formats, a save that will drop
+//! features, and links to sibling documents in the source format. At each of
+//! those points a [`ConversionInteractor`] is asked. Any answer of `None` or
+//! `false` cancels the operation before anything is written.
+//!
+//! [`NonInteractive`] answers exactly as the plain `file_ops` functions always
+//! have; [`PromptInteractor`] asks on a terminal.
+
+use crate::ast::{Document, SourceFormat};
+use crate::file_ops::format_from_extension;
+use crate::report::ConversionReport;
+use crate::toolkit::links::{classify, extract_links, visit_link_urls_mut, LinkKind, LinkTarget};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// What to do with a link to a document in the source format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "target", rename_all = "snake_case")]
+pub enum LinkRewrite {
+    /// Leave the link as written
+    Keep,
+    /// Point it at the suggested target-format file
+    Rewrite,
+    /// Point it somewhere else entirely
+    Replace(String),
+}
+
+/// Callbacks for the decisions made during open, save and convert
+pub trait ConversionInteractor {
+    /// Pick a format for content that matches several, most likely first
+    ///
+    /// Only called when the file extension does not decide the format.
+    /// Returning `None` cancels.
+    fn choose_format(&mut self, path: &Path, candidates: &[SourceFormat]) -> Option<SourceFormat>;
+
+    /// Confirm a save that will lose the features listed in `report`
+    ///
+    /// Returning `false` cancels.
+    fn confirm_lossy_save(&mut self, path: &Path, report: &ConversionReport) -> bool;
+
+    /// Decide what happens to a link that points at another document in the
+    /// source format, given the same link with the target extension
+    ///
+    /// Returning `None` cancels.
+    fn rewrite_link(&mut self, url: &str, suggested: &str) -> Option<LinkRewrite>;
+}
+
+/// Takes the most likely format, saves regardless of loss, and keeps links
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonInteractive;
+
+impl ConversionInteractor for NonInteractive {
+    fn choose_format(&mut self, _path: &Path, candidates: &[SourceFormat]) -> Option<SourceFormat> {
+        candidates.first().copied()
+    }
+
+    fn confirm_lossy_save(&mut self, _path: &Path, _report: &ConversionReport) -> bool {
+        true
+    }
+
+    fn rewrite_link(&mut self, _url: &str, _suggested: &str) -> Option<LinkRewrite> {
+        Some(LinkRewrite::Keep)
+    }
+}
+
+/// Asks each question on a line-oriented terminal
+///
+/// End of input or an I/O error cancels the operation.
+pub struct PromptInteractor<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> PromptInteractor<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    /// Print `question` and read one trimmed answer line
+    fn ask(&mut self, question: &str) -> Option<String> {
+        write!(self.output, "{} ", question).ok()?;
+        self.output.flush().ok()?;
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+impl PromptInteractor<std::io::StdinLock<'static>, std::io::Stderr> {
+    /// Prompt on stderr so stdout stays free for converted output
+    pub fn stdio() -> Self {
+        Self::new(std::io::stdin().lock(), std::io::stderr())
+    }
+}
+
+impl<R: BufRead, W: Write> ConversionInteractor for PromptInteractor<R, W> {
+    fn choose_format(&mut self, path: &Path, candidates: &[SourceFormat]) -> Option<SourceFormat> {
+        writeln!(
+            self.output,
+            "{} could be in more than one format:",
+            path.display()
+        )
+        .ok()?;
+        for (i, format) in candidates.iter().enumerate() {
+            writeln!(self.output, "  {}) {:?}", i + 1, format).ok()?;
+        }
+
+        loop {
+            let answer = self.ask(&format!("Format [1-{}, default 1]:", candidates.len()))?;
+            if answer.is_empty() {
+                return candidates.first().copied();
+            }
+            if let Some(format) = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| candidates.get(i))
+            {
+                return Some(*format);
+            }
+        }
+    }
+
+    fn confirm_lossy_save(&mut self, path: &Path, report: &ConversionReport) -> bool {
+        let question = format!(
+            "Saving {} as {:?} will lose: {}. Continue? [y/N]",
+            path.display(),
+            report.to,
+            report.lost_features.join(", ")
+        );
+        self.ask(&question)
+            .is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    fn rewrite_link(&mut self, url: &str, suggested: &str) -> Option<LinkRewrite> {
+        let question = format!(
+            "Rewrite link {} to {}? [Y/n, or type a new target]",
+            url, suggested
+        );
+        let answer = self.ask(&question)?;
+        Some(match answer.to_lowercase().as_str() {
+            "" | "y" | "yes" => LinkRewrite::Rewrite,
+            "n" | "no" => LinkRewrite::Keep,
+            _ => LinkRewrite::Replace(answer),
+        })
+    }
+}
+
+/// The target-format counterpart of a link into another source-format
+/// document, or `None` if the link points anywhere else
+fn suggested_rewrite(url: &str, from: SourceFormat, to: SourceFormat) -> Option<String> {
+    let LinkTarget::LocalFile { path, fragment } = classify(url) else {
+        return None;
+    };
+    if format_from_extension(Path::new(&path)) != Some(from) {
+        return None;
+    }
+
+    let mut suggested = Path::new(&path)
+        .with_extension(to.extension())
+        .to_string_lossy()
+        .into_owned();
+    if let Some(fragment) = fragment {
+        suggested.push('#');
+        suggested.push_str(&fragment);
+    }
+    Some(suggested)
+}
+
+/// Ask about every link into a sibling source-format document before `doc`
+/// is rendered as `target`
+///
+/// Returns `None` if the interactor cancelled, and a copy of `doc` with the
+/// answers applied if there was anything to ask about.
+pub(crate) fn rewrite_links(
+    doc: &Document,
+    target: SourceFormat,
+    interactor: &mut dyn ConversionInteractor,
+) -> Option<Option<Document>> {
+    let from = doc.source_format;
+    if from == target
+        || !extract_links(doc).iter().any(|link| {
+            link.kind == LinkKind::Link && suggested_rewrite(&link.url, from, target).is_some()
+        })
+    {
+        return Some(None);
+    }
+
+    let mut rewritten = doc.clone();
+    let mut rewrite = |url: &mut String| {
+        if let Some(suggested) = suggested_rewrite(url, from, target) {
+            match interactor.rewrite_link(url, &suggested)? {
+                LinkRewrite::Keep => {}
+                LinkRewrite::Rewrite => *url = suggested,
+                LinkRewrite::Replace(replacement) => *url = replacement,
+            }
+        }
+        Some(())
+    };
+    visit_link_urls_mut(&mut rewritten, &mut rewrite)?;
+    Some(Some(rewritten))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::toolkit::links::extract_links;
+    use crate::traits::{ParseConfig, Parser};
+
+    fn prompt(answers: &str) -> PromptInteractor<&[u8], Vec<u8>> {
+        PromptInteractor::new(answers.as_bytes(), Vec::new())
+    }
+
+    #[test]
+    fn test_suggested_rewrite() {
+        let (md, org) = (SourceFormat::Markdown, SourceFormat::OrgMode);
+        assert_eq!(
+            suggested_rewrite("guide/intro.md#setup", md, org).as_deref(),
+            Some("guide/intro.org#setup")
+        );
+        assert_eq!(suggested_rewrite("notes.txt", md, org), None);
+        assert_eq!(suggested_rewrite("https://example.com/a.md", md, org), None);
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "See [intro](intro.md), [setup](setup.md) and [site](https://example.com).\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        let mut interactor = prompt("y\nsetup-guide.org\n");
+        let rewritten = rewrite_links(&doc, SourceFormat::OrgMode, &mut interactor)
+            .unwrap()
+            .unwrap();
+        let urls: Vec<_> = extract_links(&rewritten)
+            .into_iter()
+            .map(|l| l.url)
+            .collect();
+        assert_eq!(
+            urls,
+            vec!["intro.org", "setup-guide.org", "https://example.com"]
+        );
+
+        // Running out of answers cancels
+        assert!(rewrite_links(&doc, SourceFormat::OrgMode, &mut prompt("n\n")).is_none());
+        // Same-format saves never ask
+        assert!(rewrite_links(&doc, SourceFormat::Markdown, &mut prompt(""))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_prompt_choose_format() {
+        let candidates = [SourceFormat::ReStructuredText, SourceFormat::Markdown];
+        let path = Path::new("notes");
+
+        let mut interactor = prompt("7\n2\n");
+        assert_eq!(
+            interactor.choose_format(path, &candidates),
+            Some(SourceFormat::Markdown)
+        );
+        assert_eq!(
+            prompt("\n").choose_format(path, &candidates),
+            Some(SourceFormat::ReStructuredText)
+        );
+        assert_eq!(prompt("").choose_format(path, &candidates), None);
+    }
+}
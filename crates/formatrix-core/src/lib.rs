@@ -6,30 +6,155 @@
 //! - A unified AST that all document formats convert to/from
 //! - Parser and renderer traits for format handlers
 //! - Implementations for 7 formats: TXT, MD, ADOC, DJOT, ORG, RST, TYP
+//! - Document metadata carried through each format's header syntax
+//! - Sidecar files for metadata a format cannot hold
+//! - HTML rendering with language and text direction
+//! - Styled terminal output, wrapped to width, for TUI and CLI previews
+//! - Source maps from blocks to rendered output, for preview sync
+//! - Document linting with configurable rules
+//! - Best-effort parsing that keeps unparseable regions as raw blocks
+//! - A policy for raw markup rendered to another format: keep, drop,
+//!   comment out or convert
+//! - Emoji shortcodes read in any format, and written as characters,
+//!   shortcodes or images
+//! - Org-style exclude tags: `noexport` subtrees left out in any format
+//! - Math translated between Typst and TeX notation on conversion
+//! - Sphinx-flavored reStructuredText: roles, `toctree` and version notes
+//! - Conversion reports and a normalization contract for every handler
+//! - A capability matrix: what each format pair keeps, degrades or drops
+//! - Pasting clipboard text or HTML as markup for the current format
+//! - Interactive conversion callbacks for ambiguous decisions
+//! - Ranked full-text search across document sets
+//! - Plain text of a document or block, with code, math or alt text left out
+//! - Built-in templates for new documents
+//! - Book assembly from chapters in mixed formats
+//! - Joining documents, with metadata merged and clashing anchors renamed
+//! - Projects of related documents, with links resolved and text replaced
+//!   across them
+//! - Collection of referenced images and attachments on conversion
+//! - Embedding images as data URIs and extracting them again
+//! - Text encoding detection, with saving back in the original encoding
+//! - Atomic saves with optional backups of the previous version
+//! - Edit locks, and saves that refuse to overwrite external changes
+//! - Autosaved drafts for recovering unsaved work after a crash
+//! - Document outlines: the heading tree, for navigation, and single sections
+//! - Structural edits: splitting, merging and restyling blocks
+//! - Structural diffs of two documents, in the same format or not
+//! - A canonical form of the tree, for stable comparison and caching
+//! - Find and replace over document text, in plain text or regex mode
+//! - Incremental re-parsing of the regions an edit touches
+//! - Undo and redo over source edits, with the AST parsed again after each
+//! - Async file operations for tokio hosts (feature `async`)
 //! - C FFI exports for the Ada TUI (FD-M10)
 
+pub mod ansi;
+pub mod assets;
 pub mod ast;
+pub mod autosave;
+pub mod book;
+pub mod clipboard;
+pub mod diagnostic;
+pub mod diff;
+pub mod edit;
+pub mod emoji;
+pub mod encoding;
+pub mod exclude;
 pub mod file_ops;
+pub mod find;
 pub mod formats;
+pub mod frontmatter;
+pub mod history;
+pub mod html;
+pub mod incremental;
+pub mod interact;
+pub mod lint;
+pub mod math;
+pub mod merge;
+pub mod normalize;
+pub mod outline;
+pub mod project;
+pub mod raw;
+pub mod recovery;
+pub mod report;
+pub mod search;
+pub mod sidecar;
+pub mod sourcemap;
+pub mod templates;
+pub mod text;
+pub mod toolkit;
 pub mod traits;
 
 // FD-M10: C FFI exports for Ada TUI
 #[cfg(feature = "ffi")]
 pub mod ffi;
 
-pub use ast::{Block, Document, DocumentMeta, Inline, SourceFormat};
+pub use ansi::{AnsiConfig, AnsiHandler};
+pub use assets::{
+    attach_image, embed_images, extract_images, import_file, AssetOptions, AttachOptions,
+    CollectedAsset,
+};
+pub use ast::{Author, Block, Document, DocumentMeta, Inline, SourceFormat, TextDirection};
+pub use autosave::{Autosaver, Draft, RecoveryDir};
+pub use clipboard::{convert_paste, parse_html, Paste, PasteSource};
+pub use diagnostic::{Diagnostic, Severity};
+pub use diff::{diff_documents, DiffBlock, DiffHunk, HunkKind};
+pub use edit::{Affected, EditError, InlineStyle};
+pub use encoding::TextEncoding;
 pub use file_ops::{
-    convert_file, convert_file_with_config, extension_for_format, format_from_content,
-    format_from_extension, is_supported_extension, open_file, open_file_as, open_file_with_config,
-    save_file, save_file_as, save_file_with_config, supported_extensions, FileError, FileInfo,
-    FileResult, OpenedDocument,
+    convert_dir, convert_dir_with_progress, convert_file, convert_file_interactive,
+    convert_file_with_assets, convert_file_with_config, convert_reader, convert_string,
+    convert_writer, extension_for_format, format_candidates, format_from_content,
+    format_from_extension, is_supported_extension, open_file, open_file_as, open_file_for_edit,
+    open_file_interactive, open_file_with_config, parse_string, plan_conversion, read_text,
+    resolve_includes, save_file, save_file_as, save_file_as_interactive, save_file_checked,
+    save_file_with_config, split_file, supported_extensions, write_atomic, BackupPolicy,
+    BatchEntry, BatchOptions, BatchOutcome, BatchProgress, BatchReport, ConversionPlan, EditLock,
+    FileError, FileInfo, FileResult, FileStamp, OpenedDocument, PlannedAction, PlannedFile,
+};
+pub use find::{FindError, FindMatch, FindOptions};
+pub use frontmatter::FrontMatter;
+pub use history::{DocumentHistory, TextEdit};
+pub use html::{render_html, render_html_page, render_html_with_source_map};
+pub use incremental::IncrementalParser;
+pub use interact::{ConversionInteractor, LinkRewrite, NonInteractive, PromptInteractor};
+pub use lint::{LintConfig, LintRule, Linter};
+pub use merge::{concat, MergeOptions, MetaMerge};
+pub use outline::{extract_section, outline, OutlineEntry};
+pub use project::{Project, ProjectManifest};
+pub use recovery::parse_recovering;
+pub use report::ConversionReport;
+pub use search::{SearchHit, SearchIndex};
+pub use sidecar::Sidecar;
+pub use sourcemap::{SourceMap, SourceMapEntry};
+pub use text::TextOptions;
+pub use traits::{
+    ConversionError, EmojiStyle, FinalNewline, HardBreak, HeadingStyle, LineEnding, MarkdownStyle,
+    NormalizationForm, ParseConfig, ParseLimits, Parser, RawPolicy, RenderConfig, Renderer, Result,
+    RstFlavor, RstStyle, StyleProfile, TextPolicy,
 };
-pub use traits::{ConversionError, ParseConfig, Parser, RenderConfig, Renderer, Result};
 
 // Re-export FFI types when enabled
 #[cfg(feature = "ffi")]
 pub use ffi::{
-    formatrix_block_count, formatrix_convert, formatrix_detect_format, formatrix_free_document,
-    formatrix_free_string, formatrix_get_format, formatrix_get_title, formatrix_parse,
-    formatrix_render, formatrix_version, DocumentHandle, FfiFormat, FfiResult,
+    formatrix_abi_version, formatrix_block_count, formatrix_cancel, formatrix_cancel_token_free,
+    formatrix_cancel_token_new, formatrix_clear_last_error, formatrix_clone_document,
+    formatrix_convert, formatrix_convert_file_async, formatrix_delete_block,
+    formatrix_detect_format, formatrix_document_from_json, formatrix_document_to_json,
+    formatrix_free_buffer, formatrix_free_buffer_utf16, formatrix_free_document,
+    formatrix_free_outline, formatrix_get_block, formatrix_get_block_child,
+    formatrix_get_block_child_count, formatrix_get_block_text, formatrix_get_block_type,
+    formatrix_get_format, formatrix_get_heading_level, formatrix_get_inline,
+    formatrix_get_inline_child, formatrix_get_inline_child_count, formatrix_get_inline_count,
+    formatrix_get_inline_text, formatrix_get_inline_type, formatrix_get_inline_url,
+    formatrix_get_outline, formatrix_get_title, formatrix_has_feature, formatrix_insert_paragraph,
+    formatrix_last_error_message, formatrix_last_error_span, formatrix_parse,
+    formatrix_parse_options_init, formatrix_parse_utf16, formatrix_parse_with_options,
+    formatrix_render, formatrix_render_ansi, formatrix_render_options_init,
+    formatrix_render_stream, formatrix_render_utf16, formatrix_render_with_options,
+    formatrix_render_with_profile, formatrix_set_block_text, formatrix_set_title,
+    formatrix_version, BlockHandle, CancelToken, DocumentHandle, FfiBlockType, FfiChunkCallback,
+    FfiConvertStage, FfiFinalNewline, FfiFormat, FfiInlineType, FfiLineEnding, FfiOutlineEntry,
+    FfiParseOptions, FfiProgressCallback, FfiRenderOptions, FfiResult, FORMATRIX_ABI_VERSION,
 };
+#[cfg(all(feature = "ffi", feature = "watch"))]
+pub use ffi::{formatrix_unwatch, formatrix_watch_file, FfiWatchCallback, WatchHandle};
@@ -0,0 +1,520 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Document linting
+//!
+//! A [`Linter`] runs a set of [`LintRule`]s over a parsed document (and,
+//! for text-level rules, its source) and collects [`Diagnostic`]s. Rule
+//! severities can be overridden or rules switched off via [`LintConfig`].
+
+use crate::ast::{Block, Document, Inline, Span};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::toolkit::inline_text;
+use crate::toolkit::links::heading_slug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Lint configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintConfig {
+    /// Per-rule severity overrides; `None` disables the rule
+    #[serde(default)]
+    pub rules: HashMap<String, Option<Severity>>,
+    /// Maximum line length for `line-length` (in characters)
+    #[serde(default = "default_max_line_length")]
+    pub max_line_length: usize,
+}
+
+fn default_max_line_length() -> usize {
+    100
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::new(),
+            max_line_length: default_max_line_length(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Override the severity of a rule
+    pub fn set_severity(&mut self, rule: &str, severity: Severity) {
+        self.rules.insert(rule.to_string(), Some(severity));
+    }
+
+    /// Switch a rule off
+    pub fn disable(&mut self, rule: &str) {
+        self.rules.insert(rule.to_string(), None);
+    }
+
+    /// Effective severity for a rule, or `None` if disabled
+    pub fn severity_for(&self, rule: &dyn LintRule) -> Option<Severity> {
+        match self.rules.get(rule.id()) {
+            Some(setting) => *setting,
+            None => Some(rule.default_severity()),
+        }
+    }
+}
+
+/// Input to a lint rule
+pub struct LintContext<'a> {
+    pub doc: &'a Document,
+    /// Source text, when available (needed by text-level rules)
+    pub source: Option<&'a str>,
+    pub config: &'a LintConfig,
+}
+
+/// A single lint check
+///
+/// Rules report findings with their default severity; the [`Linter`] applies
+/// any configured override afterwards.
+pub trait LintRule: Send + Sync {
+    /// Stable kebab-case identifier used in config and output
+    fn id(&self) -> &'static str;
+
+    /// One-line description
+    fn description(&self) -> &'static str;
+
+    /// Severity when not overridden
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Run the check, pushing diagnostics onto `out`
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>);
+}
+
+/// Runs a set of lint rules over documents
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule>>,
+}
+
+impl Linter {
+    /// Create a linter with no rules
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Create a linter with all built-in rules
+    pub fn with_default_rules() -> Self {
+        let mut linter = Self::new();
+        linter.register(Box::new(HeadingIncrement));
+        linter.register(Box::new(DuplicateHeadingId));
+        linter.register(Box::new(TrailingWhitespace));
+        linter.register(Box::new(BareUrl));
+        linter.register(Box::new(MissingAltText));
+        linter.register(Box::new(LineLength));
+        linter
+    }
+
+    pub fn register(&mut self, rule: Box<dyn LintRule>) {
+        self.rules.push(rule);
+    }
+
+    /// Registered rules, in run order
+    pub fn rules(&self) -> impl Iterator<Item = &dyn LintRule> {
+        self.rules.iter().map(|r| r.as_ref())
+    }
+
+    /// Lint a document
+    ///
+    /// `source` falls back to `doc.raw_source`; text-level rules are skipped
+    /// when neither is available. Results are sorted by position.
+    pub fn lint(
+        &self,
+        doc: &Document,
+        source: Option<&str>,
+        config: &LintConfig,
+    ) -> Vec<Diagnostic> {
+        let ctx = LintContext {
+            doc,
+            source: source.or(doc.raw_source.as_deref()),
+            config,
+        };
+
+        let mut diagnostics = Vec::new();
+        for rule in &self.rules {
+            let Some(severity) = config.severity_for(rule.as_ref()) else {
+                continue;
+            };
+            let start = diagnostics.len();
+            rule.check(&ctx, &mut diagnostics);
+            for diag in &mut diagnostics[start..] {
+                diag.severity = severity;
+            }
+        }
+
+        diagnostics.sort_by_key(|d| d.span.map(|s| (s.line, s.column)));
+        diagnostics
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::with_default_rules()
+    }
+}
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+/// Visit every block in document order
+fn walk_blocks<'a>(blocks: impl IntoIterator<Item = &'a Block>, f: &mut dyn FnMut(&'a Block)) {
+    for block in blocks {
+        f(block);
+        walk_blocks(block.child_blocks(), f);
+    }
+}
+
+/// Visit every inline in document order, with its enclosing block
+fn walk_inlines<'a>(doc: &'a Document, f: &mut dyn FnMut(&'a Inline, &'a Block)) {
+    fn visit<'a>(inline: &'a Inline, block: &'a Block, f: &mut dyn FnMut(&'a Inline, &'a Block)) {
+        f(inline, block);
+        for child in inline.children() {
+            visit(child, block, f);
+        }
+    }
+    walk_blocks(&doc.content, &mut |block| {
+        for group in block.inline_groups() {
+            for inline in group {
+                visit(inline, block, f);
+            }
+        }
+    });
+}
+
+/// Iterate source lines as (line number, byte offset, text without newline)
+fn source_lines(source: &str) -> impl Iterator<Item = (u32, usize, &str)> {
+    let mut offset = 0;
+    source
+        .split_inclusive('\n')
+        .enumerate()
+        .map(move |(i, raw)| {
+            let start = offset;
+            offset += raw.len();
+            let line = raw.trim_end_matches('\n').trim_end_matches('\r');
+            (i as u32 + 1, start, line)
+        })
+}
+
+fn line_span(line: u32, line_start: usize, text: &str, from: usize, to: usize) -> Span {
+    Span {
+        start: line_start + from,
+        end: line_start + to,
+        line,
+        column: text[..from].chars().count() as u32 + 1,
+    }
+}
+
+// =============================================================================
+// Built-in rules
+// =============================================================================
+
+/// Headings should only increase one level at a time
+pub struct HeadingIncrement;
+
+impl LintRule for HeadingIncrement {
+    fn id(&self) -> &'static str {
+        "heading-increment"
+    }
+
+    fn description(&self) -> &'static str {
+        "Heading levels should only increment by one level at a time"
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        let mut previous: Option<u8> = None;
+        walk_blocks(&ctx.doc.content, &mut |block| {
+            if let Block::Heading { level, span, .. } = block {
+                if let Some(prev) = previous {
+                    if *level > prev + 1 {
+                        out.push(
+                            Diagnostic::new(
+                                self.id(),
+                                self.default_severity(),
+                                format!("Heading level jumps from {} to {}", prev, level),
+                            )
+                            .with_span(*span),
+                        );
+                    }
+                }
+                previous = Some(*level);
+            }
+        });
+    }
+}
+
+/// Heading anchors must be unique
+pub struct DuplicateHeadingId;
+
+impl LintRule for DuplicateHeadingId {
+    fn id(&self) -> &'static str {
+        "duplicate-heading-id"
+    }
+
+    fn description(&self) -> &'static str {
+        "Heading ids (explicit or generated) should be unique"
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        let mut seen = HashSet::new();
+        walk_blocks(&ctx.doc.content, &mut |block| {
+            if let Block::Heading {
+                content, id, span, ..
+            } = block
+            {
+                let anchor = id
+                    .clone()
+                    .unwrap_or_else(|| heading_slug(&inline_text(content)));
+                if !anchor.is_empty() && !seen.insert(anchor.clone()) {
+                    out.push(
+                        Diagnostic::new(
+                            self.id(),
+                            self.default_severity(),
+                            format!("Duplicate heading id '{}'", anchor),
+                        )
+                        .with_span(*span),
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Lines should not end in spaces or tabs
+pub struct TrailingWhitespace;
+
+impl LintRule for TrailingWhitespace {
+    fn id(&self) -> &'static str {
+        "trailing-whitespace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Lines should not end with whitespace"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        let Some(source) = ctx.source else { return };
+        for (line, start, text) in source_lines(source) {
+            let trimmed = text.trim_end_matches([' ', '\t']);
+            if trimmed.len() < text.len() {
+                out.push(
+                    Diagnostic::new(self.id(), self.default_severity(), "Trailing whitespace")
+                        .with_span(Some(line_span(
+                            line,
+                            start,
+                            text,
+                            trimmed.len(),
+                            text.len(),
+                        ))),
+                );
+            }
+        }
+    }
+}
+
+/// URLs in running text should be proper links
+pub struct BareUrl;
+
+impl LintRule for BareUrl {
+    fn id(&self) -> &'static str {
+        "bare-url"
+    }
+
+    fn description(&self) -> &'static str {
+        "URLs in text should be written as links"
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        walk_inlines(ctx.doc, &mut |inline, block| {
+            if let Inline::Text { content } = inline {
+                for word in content.split_whitespace() {
+                    if word.starts_with("http://") || word.starts_with("https://") {
+                        out.push(
+                            Diagnostic::new(
+                                self.id(),
+                                self.default_severity(),
+                                format!("Bare URL '{}'", word),
+                            )
+                            .with_span(block.span()),
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Images need alt text for accessibility
+pub struct MissingAltText;
+
+impl LintRule for MissingAltText {
+    fn id(&self) -> &'static str {
+        "missing-alt-text"
+    }
+
+    fn description(&self) -> &'static str {
+        "Images should have alternative text"
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        walk_inlines(ctx.doc, &mut |inline, block| {
+            if let Inline::Image { url, alt, .. } = inline {
+                if alt.trim().is_empty() {
+                    out.push(
+                        Diagnostic::new(
+                            self.id(),
+                            self.default_severity(),
+                            format!("Image '{}' has no alt text", url),
+                        )
+                        .with_span(block.span()),
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Lines should stay within the configured width
+pub struct LineLength;
+
+impl LintRule for LineLength {
+    fn id(&self) -> &'static str {
+        "line-length"
+    }
+
+    fn description(&self) -> &'static str {
+        "Lines should not exceed the configured maximum length"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, ctx: &LintContext<'_>, out: &mut Vec<Diagnostic>) {
+        let Some(source) = ctx.source else { return };
+        let max = ctx.config.max_line_length;
+        for (line, start, text) in source_lines(source) {
+            let len = text.chars().count();
+            // Long URLs can't be wrapped, so don't complain about them
+            if len <= max || text.contains("://") {
+                continue;
+            }
+            let from = text.char_indices().nth(max).map_or(text.len(), |(i, _)| i);
+            out.push(
+                Diagnostic::new(
+                    self.id(),
+                    self.default_severity(),
+                    format!("Line is {} characters long (maximum {})", len, max),
+                )
+                .with_span(Some(line_span(line, start, text, from, text.len()))),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::SourceFormat;
+
+    fn heading(level: u8, text: &str) -> Block {
+        Block::Heading {
+            level,
+            content: vec![Inline::Text {
+                content: text.to_string(),
+            }],
+            id: None,
+            classes: Vec::new(),
+            span: None,
+        }
+    }
+
+    fn doc_with(content: Vec<Block>) -> Document {
+        Document {
+            content,
+            ..Document::new(SourceFormat::Markdown)
+        }
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_heading_rules() {
+        let doc = doc_with(vec![
+            heading(1, "Intro"),
+            heading(3, "Details"),
+            heading(2, "Intro"),
+        ]);
+        let diagnostics = Linter::default().lint(&doc, None, &LintConfig::default());
+        assert_eq!(
+            codes(&diagnostics),
+            vec!["heading-increment", "duplicate-heading-id"]
+        );
+    }
+
+    #[test]
+    fn test_inline_rules() {
+        let doc = doc_with(vec![Block::Paragraph {
+            content: vec![
+                Inline::Text {
+                    content: "See https://example.com for more".to_string(),
+                },
+                Inline::Image {
+                    url: "a.png".to_string(),
+                    alt: String::new(),
+                    title: None,
+                    width: None,
+                    height: None,
+                },
+            ],
+            span: None,
+        }]);
+        let diagnostics = Linter::default().lint(&doc, None, &LintConfig::default());
+        assert_eq!(codes(&diagnostics), vec!["bare-url", "missing-alt-text"]);
+    }
+
+    #[test]
+    fn test_text_rules_have_spans() {
+        let doc = doc_with(Vec::new());
+        let source = "fine\ntrailing  \n";
+        let diagnostics = Linter::default().lint(&doc, Some(source), &LintConfig::default());
+        assert_eq!(diagnostics.len(), 1);
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (2, 9));
+        assert_eq!(&source[span.start..span.end], "  ");
+    }
+
+    #[test]
+    fn test_line_length_uses_config() {
+        let doc = doc_with(Vec::new());
+        let config = LintConfig {
+            max_line_length: 10,
+            ..Default::default()
+        };
+        let diagnostics =
+            Linter::default().lint(&doc, Some("short\nthis line is too long\n"), &config);
+        assert_eq!(codes(&diagnostics), vec!["line-length"]);
+        assert_eq!(diagnostics[0].span.unwrap().column, 11);
+    }
+
+    #[test]
+    fn test_severity_overrides() {
+        let doc = doc_with(vec![heading(1, "A"), heading(4, "B"), heading(1, "A")]);
+        let mut config = LintConfig::default();
+        config.set_severity("heading-increment", Severity::Error);
+        config.disable("duplicate-heading-id");
+
+        let diagnostics = Linter::default().lint(&doc, None, &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
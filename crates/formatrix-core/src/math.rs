@@ -0,0 +1,1202 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Math notation
+//!
+//! Typst writes math in its own syntax rather than TeX: `frac(a, b)` for
+//! `\frac{a}{b}`, `alpha` for `\alpha`, `x^(n+1)` for `x^{n+1}`. Math keeps
+//! the notation it was parsed in, and renderers translate it to their
+//! target's, so Typst math reaches Markdown, reStructuredText and HTML as
+//! TeX, and TeX math reaches Typst as Typst math. The translation covers
+//! fractions, roots, scripts, symbols, accents, fonts, matrices and cases;
+//! commands it does not know keep their name.
+
+use crate::ast::{Block, Inline, MathNotation, SourceFormat};
+use std::borrow::Cow;
+
+/// The notation math is written in for `format`
+pub fn target_notation(format: SourceFormat) -> MathNotation {
+    match format {
+        SourceFormat::Typst => MathNotation::Typst,
+        _ => MathNotation::LaTeX,
+    }
+}
+
+/// `content`, written in `from` notation, in `to` notation
+///
+/// Notations with no translation between them are left as they are.
+pub fn translate(content: &str, from: MathNotation, to: MathNotation) -> Cow<'_, str> {
+    match (from, to) {
+        (MathNotation::Typst, MathNotation::LaTeX) => Cow::Owned(typst_to_tex(content)),
+        (MathNotation::LaTeX, MathNotation::Typst) => Cow::Owned(tex_to_typst(content)),
+        _ => Cow::Borrowed(content),
+    }
+}
+
+fn translates(from: MathNotation, to: MathNotation) -> bool {
+    matches!(
+        (from, to),
+        (MathNotation::Typst, MathNotation::LaTeX) | (MathNotation::LaTeX, MathNotation::Typst)
+    )
+}
+
+/// Whether `blocks` hold math that [`translate_math`] would change
+pub fn needs_translation(blocks: &[Block], to: MathNotation) -> bool {
+    fn in_inline(inline: &Inline, to: MathNotation) -> bool {
+        match inline {
+            Inline::Math { notation, .. } => translates(*notation, to),
+            other => other
+                .children()
+                .into_iter()
+                .any(|child| in_inline(child, to)),
+        }
+    }
+
+    blocks.iter().any(|block| {
+        matches!(block, Block::MathBlock { notation, .. } if translates(*notation, to))
+            || block
+                .inline_groups()
+                .into_iter()
+                .flatten()
+                .any(|inline| in_inline(inline, to))
+            || needs_translation_in(block.child_blocks(), to)
+    })
+}
+
+fn needs_translation_in(children: Vec<&Block>, to: MathNotation) -> bool {
+    children
+        .into_iter()
+        .any(|child| needs_translation(std::slice::from_ref(child), to))
+}
+
+/// Write the math in `blocks` in `to` notation
+pub fn translate_math(blocks: &mut [Block], to: MathNotation) {
+    fn visit(inline: &mut Inline, to: MathNotation) {
+        if let Inline::Math { content, notation } = inline {
+            if translates(*notation, to) {
+                *content = translate(content, *notation, to).into_owned();
+                *notation = to;
+            }
+            return;
+        }
+        for child in inline.children_mut() {
+            visit(child, to);
+        }
+    }
+
+    for block in blocks {
+        if let Block::MathBlock {
+            content, notation, ..
+        } = block
+        {
+            if translates(*notation, to) {
+                *content = translate(content, *notation, to).into_owned();
+                *notation = to;
+            }
+        }
+        for group in block.inline_groups_mut() {
+            for inline in group {
+                visit(inline, to);
+            }
+        }
+        for child in block.child_blocks_mut() {
+            translate_math(std::slice::from_mut(child), to);
+        }
+    }
+}
+
+/// TeX command names and the Typst symbols or shorthands they stand for,
+/// where the two differ; the first entry for a name is the one written
+const SYMBOLS: &[(&str, &str)] = &[
+    ("varepsilon", "epsilon"),
+    ("epsilon", "epsilon.alt"),
+    ("varphi", "phi"),
+    ("phi", "phi.alt"),
+    ("vartheta", "theta.alt"),
+    ("varpi", "pi.alt"),
+    ("varrho", "rho.alt"),
+    ("varsigma", "sigma.alt"),
+    ("varkappa", "kappa.alt"),
+    ("infty", "infinity"),
+    ("infty", "oo"),
+    ("int", "integral"),
+    ("iint", "integral.double"),
+    ("iiint", "integral.triple"),
+    ("oint", "integral.cont"),
+    ("prod", "product"),
+    ("coprod", "product.co"),
+    ("cdot", "dot.op"),
+    ("cdot", "dot"),
+    ("ldots", "dots"),
+    ("ldots", "dots.h"),
+    ("ldots", "..."),
+    ("dots", "dots"),
+    ("cdots", "dots.c"),
+    ("vdots", "dots.v"),
+    ("ddots", "dots.down"),
+    ("pm", "plus.minus"),
+    ("mp", "minus.plus"),
+    ("le", "<="),
+    ("leq", "<="),
+    ("ge", ">="),
+    ("geq", ">="),
+    ("ne", "!="),
+    ("neq", "!="),
+    ("to", "->"),
+    ("rightarrow", "->"),
+    ("to", "arrow.r"),
+    ("leftarrow", "<-"),
+    ("gets", "<-"),
+    ("leftarrow", "arrow.l"),
+    ("leftrightarrow", "<->"),
+    ("Rightarrow", "=>"),
+    ("Rightarrow", "arrow.r.double"),
+    ("implies", "==>"),
+    ("Leftarrow", "arrow.l.double"),
+    ("Leftrightarrow", "<=>"),
+    ("iff", "<==>"),
+    ("mapsto", "|->"),
+    ("uparrow", "arrow.t"),
+    ("downarrow", "arrow.b"),
+    ("longrightarrow", "arrow.r.long"),
+    ("hookrightarrow", "arrow.r.hook"),
+    ("ll", "<<"),
+    ("gg", ">>"),
+    ("coloneqq", ":="),
+    ("sim", "tilde.op"),
+    ("simeq", "tilde.eq"),
+    ("cong", "tilde.equiv"),
+    ("propto", "prop"),
+    ("neg", "not"),
+    ("lnot", "not"),
+    ("land", "and"),
+    ("wedge", "and"),
+    ("lor", "or"),
+    ("vee", "or"),
+    ("cap", "sect"),
+    ("cup", "union"),
+    ("bigcap", "sect.big"),
+    ("bigcup", "union.big"),
+    ("setminus", "without"),
+    ("subseteq", "subset.eq"),
+    ("supseteq", "supset.eq"),
+    ("subsetneq", "subset.neq"),
+    ("notin", "in.not"),
+    ("ni", "in.rev"),
+    ("emptyset", "nothing"),
+    ("varnothing", "nothing"),
+    ("circ", "compose"),
+    ("langle", "angle.l"),
+    ("rangle", "angle.r"),
+    ("lfloor", "floor.l"),
+    ("rfloor", "floor.r"),
+    ("lceil", "ceil.l"),
+    ("rceil", "ceil.r"),
+    ("mid", "divides"),
+    ("vert", "bar.v"),
+    ("Vert", "bar.v.double"),
+    ("|", "||"),
+    ("hbar", "planck.reduce"),
+    ("qquad", "wide"),
+    ("leqslant", "lt.eq.slant"),
+    ("geqslant", "gt.eq.slant"),
+    ("preceq", "prec.eq"),
+    ("succeq", "succ.eq"),
+    ("triangle", "triangle.t"),
+];
+
+/// Typst functions and the TeX commands for them that take one argument;
+/// the first entry for a name is the one written
+const ACCENTS: &[(&str, &str)] = &[
+    ("sqrt", "sqrt"),
+    ("hat", "hat"),
+    ("hat", "widehat"),
+    ("tilde", "tilde"),
+    ("tilde", "widetilde"),
+    ("macron", "bar"),
+    ("overline", "overline"),
+    ("underline", "underline"),
+    ("arrow", "vec"),
+    ("arrow", "overrightarrow"),
+    ("dot", "dot"),
+    ("dot.double", "ddot"),
+    ("overbrace", "overbrace"),
+    ("underbrace", "underbrace"),
+    ("bold", "mathbf"),
+    ("bold", "boldsymbol"),
+    ("bold", "bm"),
+    ("italic", "mathit"),
+    ("upright", "mathrm"),
+    ("cal", "mathcal"),
+    ("bb", "mathbb"),
+    ("frak", "mathfrak"),
+    ("sans", "mathsf"),
+    ("mono", "mathtt"),
+];
+
+/// TeX matrix environments and the delimiter Typst's `mat` takes for each
+const MATRICES: &[(&str, Option<&str>)] = &[
+    ("pmatrix", None),
+    ("bmatrix", Some("\"[\"")),
+    ("Bmatrix", Some("\"{\"")),
+    ("vmatrix", Some("\"|\"")),
+    ("Vmatrix", Some("\"||\"")),
+    ("matrix", Some("#none")),
+    ("smallmatrix", Some("#none")),
+    ("array", Some("#none")),
+];
+
+/// Typst shorthands, longest first
+const SHORTHANDS: &[&str] = &[
+    "<==>", "==>", "<=>", "<->", "|->", "...", "<=", ">=", "!=", "->", "=>", "<-", "<<", ">>",
+    ":=", "||",
+];
+
+// Typst to TeX
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tok<'a> {
+    Space,
+    /// A name, with any modifiers: `alpha`, `arrow.r.long`
+    Ident(&'a str),
+    Number(&'a str),
+    /// A string's text, with its escapes left in
+    Str(&'a str),
+    Shorthand(&'a str),
+    Escape(char),
+    LineBreak,
+    /// Embedded code, from the `#`
+    Code(&'a str),
+    Char(char),
+}
+
+fn typst_tokens(src: &str) -> Vec<Tok<'_>> {
+    let letters_end = |s: &str| {
+        s.find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(s.len())
+    };
+
+    let mut toks = Vec::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        let (tok, len) = if c.is_whitespace() {
+            let len = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            (Tok::Space, len)
+        } else if c.is_ascii_alphabetic() {
+            let mut len = letters_end(rest);
+            // Only names take modifiers; `x.y` is three characters
+            while len > 1
+                && rest[len..].starts_with('.')
+                && rest[len + 1..].starts_with(|c: char| c.is_ascii_alphabetic())
+            {
+                len += 1 + letters_end(&rest[len + 1..]);
+            }
+            (Tok::Ident(&rest[..len]), len)
+        } else if c.is_ascii_digit() {
+            let digits_end = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let mut len = digits_end(rest);
+            if rest[len..].starts_with('.')
+                && rest[len + 1..].starts_with(|c: char| c.is_ascii_digit())
+            {
+                len += 1 + digits_end(&rest[len + 1..]);
+            }
+            (Tok::Number(&rest[..len]), len)
+        } else if c == '"' {
+            let mut end = rest.len();
+            let mut escaped = false;
+            for (i, c) in rest.char_indices().skip(1) {
+                match c {
+                    '"' if !escaped => {
+                        end = i;
+                        break;
+                    }
+                    '\\' => escaped = !escaped,
+                    _ => escaped = false,
+                }
+            }
+            (Tok::Str(&rest[1..end]), (end + 1).min(rest.len()))
+        } else if c == '\\' {
+            match rest[1..].chars().next() {
+                Some(e) if !e.is_whitespace() => (Tok::Escape(e), 1 + e.len_utf8()),
+                _ => (Tok::LineBreak, 1),
+            }
+        } else if c == '#' {
+            let len = 1 + rest[1..]
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '.' | '_' | '-')))
+                .unwrap_or(rest.len() - 1);
+            (Tok::Code(&rest[..len]), len)
+        } else if let Some(shorthand) = SHORTHANDS.iter().find(|s| rest.starts_with(**s)) {
+            (Tok::Shorthand(shorthand), shorthand.len())
+        } else {
+            (Tok::Char(c), c.len_utf8())
+        };
+        toks.push(tok);
+        rest = &rest[len..];
+    }
+    toks
+}
+
+/// A piece of translated TeX
+#[derive(Default)]
+struct Unit {
+    tex: String,
+    /// The content of a parenthesized group, which loses its parentheses as
+    /// a fraction or script
+    inner: Option<String>,
+    space: bool,
+}
+
+impl Unit {
+    fn new(tex: String) -> Self {
+        Self {
+            tex,
+            ..Default::default()
+        }
+    }
+
+    fn arg(self) -> String {
+        self.inner.unwrap_or(self.tex)
+    }
+}
+
+/// Append `tex`, keeping a command name apart from letters after it
+fn push_tex(out: &mut String, tex: &str) {
+    let ends_in_command = out
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .ends_with('\\')
+        && out.ends_with(|c: char| c.is_ascii_alphabetic());
+    if ends_in_command && tex.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        out.push(' ');
+    }
+    out.push_str(tex);
+}
+
+fn join_tex(units: Vec<Unit>) -> String {
+    let mut out = String::new();
+    for unit in units {
+        if unit.space {
+            if !out.is_empty() && !out.ends_with(' ') {
+                out.push(' ');
+            }
+        } else {
+            push_tex(&mut out, &unit.tex);
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// `arg` as a TeX script or argument: braced unless it is one character
+fn braced(arg: String) -> String {
+    if arg.chars().count() == 1 {
+        arg
+    } else {
+        format!("{{{}}}", arg)
+    }
+}
+
+/// `text` inside `\text{}`
+fn tex_text(text: &str) -> String {
+    let mut out = String::from("\\text{");
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            '{' | '}' | '_' | '#' | '%' | '&' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('}');
+    out
+}
+
+fn typst_to_tex(src: &str) -> String {
+    let mut math = TypstMath {
+        toks: typst_tokens(src),
+        pos: 0,
+    };
+    let mut units = math.seq(|_| false);
+    // Stray closing parentheses end no group
+    while math.pos < math.toks.len() {
+        math.pos += 1;
+        units.push(Unit::new(")".to_string()));
+        units.extend(math.seq(|_| false));
+    }
+    join_tex(units).trim().to_string()
+}
+
+struct TypstMath<'a> {
+    toks: Vec<Tok<'a>>,
+    pos: usize,
+}
+
+impl<'a> TypstMath<'a> {
+    fn peek(&self) -> Option<Tok<'a>> {
+        self.toks.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(Tok::Space) {
+            self.pos += 1;
+        }
+    }
+
+    /// Units up to the token `stop` accepts, which is left for the caller,
+    /// or a closing parenthesis
+    fn seq(&mut self, stop: fn(&Tok) -> bool) -> Vec<Unit> {
+        let mut units: Vec<Unit> = Vec::new();
+        while let Some(tok) = self.peek() {
+            if stop(&tok) || tok == Tok::Char(')') {
+                break;
+            }
+            match tok {
+                Tok::Space => {
+                    self.pos += 1;
+                    units.push(Unit {
+                        space: true,
+                        ..Default::default()
+                    });
+                }
+                Tok::Char(op @ ('^' | '_')) => {
+                    self.pos += 1;
+                    let base = pop_unit(&mut units);
+                    self.skip_spaces();
+                    let script = self.unit().arg();
+                    units.push(Unit::new(format!("{}{}{}", base.tex, op, braced(script))));
+                }
+                Tok::Char('/') => {
+                    self.pos += 1;
+                    let numerator = pop_unit(&mut units).arg();
+                    let denominator = self.operand().arg();
+                    units.push(Unit::new(format!(
+                        "\\frac{{{}}}{{{}}}",
+                        numerator, denominator
+                    )));
+                }
+                _ => {
+                    let unit = self.unit();
+                    units.push(unit);
+                }
+            }
+        }
+        units
+    }
+
+    /// A fraction's denominator: a unit with its scripts
+    fn operand(&mut self) -> Unit {
+        self.skip_spaces();
+        let mut unit = self.unit();
+        while let Some(Tok::Char(op @ ('^' | '_'))) = self.peek() {
+            self.pos += 1;
+            let script = self.unit().arg();
+            unit = Unit::new(format!("{}{}{}", unit.tex, op, braced(script)));
+        }
+        unit
+    }
+
+    fn unit(&mut self) -> Unit {
+        let Some(tok) = self.peek() else {
+            return Unit::default();
+        };
+        self.pos += 1;
+        let tex = match tok {
+            Tok::Ident(name) if self.peek() == Some(Tok::Char('(')) && name.len() > 1 => {
+                self.pos += 1;
+                return self.call(name);
+            }
+            Tok::Ident(name) => symbol_tex(name),
+            Tok::Number(number) => number.to_string(),
+            Tok::Str(text) => tex_text(text),
+            Tok::Shorthand(shorthand) => symbol_tex(shorthand),
+            Tok::Escape(c @ ('{' | '}' | '%' | '&' | '#' | '$' | '_')) => format!("\\{}", c),
+            Tok::Escape(c) => c.to_string(),
+            Tok::LineBreak => "\\\\".to_string(),
+            Tok::Code(code) => tex_text(code),
+            Tok::Char('(') => {
+                let inner = join_tex(self.seq(|_| false));
+                let close = if self.peek() == Some(Tok::Char(')')) {
+                    self.pos += 1;
+                    ")"
+                } else {
+                    ""
+                };
+                return Unit {
+                    tex: format!("({}{}", inner, close),
+                    inner: Some(inner),
+                    space: false,
+                };
+            }
+            Tok::Char(c @ ('{' | '}' | '%' | '#' | '$')) => format!("\\{}", c),
+            Tok::Char(c) => c.to_string(),
+            Tok::Space => " ".to_string(),
+        };
+        Unit::new(tex)
+    }
+
+    /// The arguments of a call, after its opening parenthesis: the rows of
+    /// positional arguments, split at semicolons, and the `delim` argument
+    fn args(&mut self) -> (Vec<Vec<String>>, Option<String>) {
+        let mut rows = vec![Vec::new()];
+        let mut delim = None;
+        loop {
+            self.skip_spaces();
+            let named = match (self.peek(), self.toks.get(self.pos + 1).copied()) {
+                (Some(Tok::Ident(name)), Some(Tok::Char(':'))) => Some(name),
+                _ => None,
+            };
+            if let Some(name) = named {
+                self.pos += 2;
+                self.skip_spaces();
+                let value = match self.peek() {
+                    Some(Tok::Str(s)) => Some(format!("\"{}\"", s)),
+                    Some(Tok::Code(code)) => Some(code.to_string()),
+                    _ => None,
+                };
+                if name == "delim" {
+                    delim = value;
+                }
+                self.seq(|t| matches!(t, Tok::Char(',' | ';')));
+            } else {
+                let arg = join_tex(self.seq(|t| matches!(t, Tok::Char(',' | ';'))));
+                rows.last_mut().expect("one row").push(arg);
+            }
+            match self.peek() {
+                Some(Tok::Char(',')) => self.pos += 1,
+                Some(Tok::Char(';')) => {
+                    self.pos += 1;
+                    rows.push(Vec::new());
+                }
+                Some(_) => {
+                    self.pos += 1;
+                    break;
+                }
+                None => break,
+            }
+        }
+        // A trailing comma or semicolon leaves nothing after it
+        if let Some(row) = rows.last_mut() {
+            if row.last().is_some_and(|arg| arg.is_empty()) {
+                row.pop();
+            }
+        }
+        rows.retain(|row| !row.is_empty());
+        (rows, delim)
+    }
+
+    fn call(&mut self, name: &str) -> Unit {
+        let (rows, delim) = self.args();
+        let args: Vec<&String> = rows.iter().flatten().collect();
+        let tex = match (name, args.as_slice()) {
+            ("frac", [a, b]) => format!("\\frac{{{}}}{{{}}}", a, b),
+            ("binom", [a, b]) => format!("\\binom{{{}}}{{{}}}", a, b),
+            ("root", [n, x]) => format!("\\sqrt[{}]{{{}}}", n, x),
+            ("abs", [x]) => format!("\\left| {} \\right|", x),
+            ("norm", [x]) => format!("\\left\\| {} \\right\\|", x),
+            ("floor", [x]) => format!("\\lfloor {} \\rfloor", x),
+            ("ceil", [x]) => format!("\\lceil {} \\rceil", x),
+            ("lr", _) => args
+                .iter()
+                .map(|a| a.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            ("op", [x]) => {
+                let x = x
+                    .strip_prefix("\\text{")
+                    .and_then(|x| x.strip_suffix('}'))
+                    .unwrap_or(x);
+                format!("\\operatorname{{{}}}", x)
+            }
+            ("mat", _) => matrix_tex(delim.as_deref(), &rows),
+            ("vec", _) => {
+                let column: Vec<Vec<String>> = args.iter().map(|a| vec![a.to_string()]).collect();
+                matrix_tex(delim.as_deref(), &column)
+            }
+            ("cases", _) => {
+                let cases: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+                format!("\\begin{{cases}} {} \\end{{cases}}", cases.join(" \\\\ "))
+            }
+            (name, [x]) if ACCENTS.iter().any(|(typst, _)| *typst == name) => {
+                let (_, tex) = ACCENTS
+                    .iter()
+                    .find(|(typst, _)| *typst == name)
+                    .expect("checked above");
+                format!("\\{}{{{}}}", tex, x)
+            }
+            (name, _) => {
+                let rows: Vec<String> = rows.iter().map(|row| row.join(", ")).collect();
+                let mut tex = symbol_tex(name);
+                push_tex(&mut tex, &format!("({})", rows.join("; ")));
+                tex
+            }
+        };
+        Unit::new(tex)
+    }
+}
+
+/// The last unit that is not a space, taken off `units`
+fn pop_unit(units: &mut Vec<Unit>) -> Unit {
+    while units.last().is_some_and(|unit| unit.space) {
+        units.pop();
+    }
+    units.pop().unwrap_or_default()
+}
+
+fn matrix_tex(delim: Option<&str>, rows: &[Vec<String>]) -> String {
+    let env = MATRICES
+        .iter()
+        .find(|(_, d)| *d == delim)
+        .map_or("pmatrix", |(env, _)| env);
+    let rows: Vec<String> = rows.iter().map(|row| row.join(" & ")).collect();
+    format!("\\begin{{{0}}} {1} \\end{{{0}}}", env, rows.join(" \\\\ "))
+}
+
+/// The TeX for a Typst symbol name or shorthand
+fn symbol_tex(name: &str) -> String {
+    if name.chars().count() == 1 {
+        return name.to_string();
+    }
+    if let Some((tex, _)) = SYMBOLS.iter().find(|(_, typst)| *typst == name) {
+        return format!("\\{}", tex);
+    }
+    let mut letters = name.chars();
+    match (letters.next(), letters.next(), letters.next()) {
+        // `RR` is blackboard bold R
+        (Some(a), Some(b), None) if a == b && a.is_ascii_uppercase() => {
+            format!("\\mathbb{{{}}}", a)
+        }
+        _ if name == "dif" => "\\mathrm{d}".to_string(),
+        _ if name.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+            // Modifiers TeX has no name for are dropped
+            format!("\\{}", name.split('.').next().unwrap_or(name))
+        }
+        _ => name.to_string(),
+    }
+}
+
+// TeX to Typst
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TexTok<'a> {
+    /// A control word, without its backslash
+    Word(&'a str),
+    /// A control symbol such as `\,`, without its backslash
+    Sym(char),
+    Open,
+    Close,
+    Char(char),
+    Space,
+}
+
+fn tex_tokens(src: &str) -> Vec<TexTok<'_>> {
+    let mut toks = Vec::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        let (tok, len) = match c {
+            '\\' => match rest[1..].chars().next() {
+                Some(l) if l.is_ascii_alphabetic() => {
+                    let len = rest[1..]
+                        .find(|c: char| !c.is_ascii_alphabetic())
+                        .unwrap_or(rest.len() - 1);
+                    (Some(TexTok::Word(&rest[1..1 + len])), 1 + len)
+                }
+                Some(s) => (Some(TexTok::Sym(s)), 1 + s.len_utf8()),
+                None => (None, 1),
+            },
+            '{' => (Some(TexTok::Open), 1),
+            '}' => (Some(TexTok::Close), 1),
+            '%' => (None, rest.find('\n').unwrap_or(rest.len())),
+            c if c.is_whitespace() => {
+                let len = rest
+                    .find(|c: char| !c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (Some(TexTok::Space), len)
+            }
+            c => (Some(TexTok::Char(c)), c.len_utf8()),
+        };
+        toks.extend(tok);
+        rest = &rest[len..];
+    }
+    toks
+}
+
+fn tex_to_typst(src: &str) -> String {
+    let mut math = TexMath {
+        toks: tex_tokens(src),
+        pos: 0,
+    };
+    let mut pieces = math.seq(|_| false);
+    // Stray closing braces end no group
+    while math.pos < math.toks.len() {
+        math.pos += 1;
+        pieces.extend(math.seq(|_| false));
+    }
+    join_typst(&pieces)
+}
+
+/// Join pieces of Typst math, keeping names and numbers apart
+fn join_typst(pieces: &[String]) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '"';
+    let mut out = String::new();
+    for piece in pieces.iter().filter(|p| !p.is_empty()) {
+        if piece == " " {
+            if !out.is_empty() && !out.ends_with(' ') {
+                out.push(' ');
+            }
+            continue;
+        }
+        if let (Some(last), Some(first)) = (out.chars().last(), piece.chars().next()) {
+            // A name right before a parenthesis would be called
+            let name_before = out
+                .rsplit(|c: char| !(c.is_ascii_alphabetic() || c == '.'))
+                .next()
+                .is_some_and(|word| word.len() > 1);
+            let apart = (is_word(last)
+                && is_word(first)
+                && !(last.is_ascii_digit() && first.is_ascii_digit()))
+                || (last == ')' && is_word(first))
+                || last == '\\'
+                || piece == "\\"
+                || (first == '(' && name_before);
+            if apart && last != ' ' {
+                out.push(' ');
+            }
+        }
+        out.push_str(piece);
+    }
+    out.trim_end().to_string()
+}
+
+/// Whether `typst` is a single unit a script can take without parentheses
+fn is_atom(typst: &str) -> bool {
+    if typst.chars().count() == 1 {
+        return true;
+    }
+    if !typst.is_empty() && typst.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        return !typst.starts_with(|c: char| c.is_ascii_digit())
+            || typst.chars().all(|c| c.is_ascii_digit());
+    }
+    // A call: a name, then parentheses closing at the end
+    let Some(open) = typst.find('(') else {
+        return false;
+    };
+    let name = &typst[..open];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic() || c == '.') {
+        return false;
+    }
+    let mut depth = 0;
+    for (i, c) in typst.char_indices().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i == typst.len() - 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// `typst` as an argument of a call, with commas and semicolons outside
+/// any parentheses escaped
+fn call_arg(typst: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut chars = typst.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                out.extend(chars.next());
+                continue;
+            }
+            '"' => in_string = !in_string,
+            '(' | '[' if !in_string => depth += 1,
+            ')' | ']' if !in_string => depth -= 1,
+            ',' | ';' if !in_string && depth <= 0 => out.push('\\'),
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `text` as a Typst string
+fn typst_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+struct TexMath<'a> {
+    toks: Vec<TexTok<'a>>,
+    pos: usize,
+}
+
+impl<'a> TexMath<'a> {
+    fn peek(&self) -> Option<TexTok<'a>> {
+        self.toks.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(TexTok::Space) {
+            self.pos += 1;
+        }
+    }
+
+    /// Pieces up to the token `stop` accepts, which is left for the caller,
+    /// or a closing brace
+    fn seq(&mut self, stop: fn(&TexTok) -> bool) -> Vec<String> {
+        let mut pieces: Vec<String> = Vec::new();
+        while let Some(tok) = self.peek() {
+            if stop(&tok) || tok == TexTok::Close {
+                break;
+            }
+            match tok {
+                TexTok::Space => {
+                    self.pos += 1;
+                    pieces.push(" ".to_string());
+                }
+                TexTok::Char(op @ ('^' | '_')) => {
+                    self.pos += 1;
+                    while pieces.last().is_some_and(|piece| piece == " ") {
+                        pieces.pop();
+                    }
+                    let base = pieces.pop().unwrap_or_default();
+                    let script = self.arg();
+                    let script = if is_atom(&script) {
+                        script
+                    } else {
+                        format!("({})", script)
+                    };
+                    pieces.push(format!("{}{}{}", base, op, script));
+                }
+                _ => {
+                    let piece = self.arg();
+                    pieces.push(piece);
+                }
+            }
+        }
+        pieces
+    }
+
+    /// A group's content, after its opening brace
+    fn group(&mut self) -> String {
+        let content = join_typst(&self.seq(|_| false));
+        if self.peek() == Some(TexTok::Close) {
+            self.pos += 1;
+        }
+        content
+    }
+
+    /// The next argument: a group, or a single token
+    fn arg(&mut self) -> String {
+        self.skip_spaces();
+        let Some(tok) = self.peek() else {
+            return String::new();
+        };
+        self.pos += 1;
+        match tok {
+            TexTok::Open => self.group(),
+            TexTok::Word(word) => self.word(word),
+            TexTok::Sym(sym) => sym_typst(sym),
+            TexTok::Char(c) => char_typst(c),
+            TexTok::Close | TexTok::Space => String::new(),
+        }
+    }
+
+    /// The source of the next argument, untranslated
+    fn raw_arg(&mut self) -> String {
+        self.skip_spaces();
+        let mut raw = String::new();
+        let mut depth = 0;
+        while let Some(tok) = self.peek() {
+            self.pos += 1;
+            match tok {
+                TexTok::Open => {
+                    depth += 1;
+                    if depth == 1 {
+                        continue;
+                    }
+                    raw.push('{');
+                }
+                TexTok::Close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    raw.push('}');
+                }
+                TexTok::Word(word) => {
+                    raw.push('\\');
+                    raw.push_str(word);
+                }
+                TexTok::Sym(sym) => raw.push(sym),
+                TexTok::Char(c) => raw.push(c),
+                TexTok::Space => raw.push(' '),
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+        raw
+    }
+
+    fn word(&mut self, word: &str) -> String {
+        match word {
+            "frac" | "dfrac" | "tfrac" | "cfrac" | "binom" => {
+                let name = if word == "binom" { "binom" } else { "frac" };
+                let a = self.arg();
+                let b = self.arg();
+                format!("{}({}, {})", name, call_arg(&a), call_arg(&b))
+            }
+            "sqrt" => {
+                self.skip_spaces();
+                if self.peek() == Some(TexTok::Char('[')) {
+                    self.pos += 1;
+                    let index = join_typst(&self.seq(|t| *t == TexTok::Char(']')));
+                    if self.peek() == Some(TexTok::Char(']')) {
+                        self.pos += 1;
+                    }
+                    let x = self.arg();
+                    format!("root({}, {})", call_arg(&index), call_arg(&x))
+                } else {
+                    format!("sqrt({})", call_arg(&self.arg()))
+                }
+            }
+            "text" | "textrm" | "textit" | "textbf" | "textnormal" | "mbox" => {
+                typst_string(&self.raw_arg())
+            }
+            "operatorname" => format!("op({})", typst_string(&self.raw_arg())),
+            "mathrm" | "mathbb" => {
+                let save = self.pos;
+                let raw = self.raw_arg();
+                let letters = !raw.is_empty() && raw.chars().all(|c| c.is_ascii_alphabetic());
+                match (word, raw.len()) {
+                    ("mathrm", 2..) if letters => typst_string(&raw),
+                    ("mathbb", 1) if letters && raw.starts_with(|c: char| c.is_uppercase()) => {
+                        raw.repeat(2)
+                    }
+                    _ => {
+                        self.pos = save;
+                        let name = if word == "mathrm" { "upright" } else { "bb" };
+                        format!("{}({})", name, call_arg(&self.arg()))
+                    }
+                }
+            }
+            "left" | "right" | "middle" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr"
+            | "Bigl" | "Bigr" | "biggl" | "biggr" | "Biggl" | "Biggr" => {
+                // Typst sizes delimiters itself
+                self.skip_spaces();
+                if self.peek() == Some(TexTok::Char('.')) {
+                    self.pos += 1;
+                    String::new()
+                } else {
+                    self.arg()
+                }
+            }
+            "begin" => {
+                let env = self.raw_arg();
+                self.environment(&env)
+            }
+            "end" => {
+                self.raw_arg();
+                String::new()
+            }
+            "quad" => "quad".to_string(),
+            word => match ACCENTS.iter().find(|(_, tex)| *tex == word) {
+                Some((typst, _)) => format!("{}({})", typst, call_arg(&self.arg())),
+                None => SYMBOLS
+                    .iter()
+                    .find(|(tex, _)| *tex == word)
+                    .map_or(word, |(_, typst)| typst)
+                    .to_string(),
+            },
+        }
+    }
+
+    fn environment(&mut self, env: &str) -> String {
+        let env = env.trim_end_matches('*');
+        if env == "array" {
+            // Column specification
+            self.raw_arg();
+        }
+
+        let mut rows: Vec<Vec<String>> = vec![Vec::new()];
+        loop {
+            let cell = join_typst(&self.seq(|t| {
+                matches!(
+                    t,
+                    TexTok::Char('&') | TexTok::Sym('\\') | TexTok::Word("end")
+                )
+            }));
+            rows.last_mut().expect("one row").push(cell);
+            match self.peek() {
+                Some(TexTok::Char('&')) => self.pos += 1,
+                Some(TexTok::Sym('\\')) => {
+                    self.pos += 1;
+                    rows.push(Vec::new());
+                }
+                Some(TexTok::Word("end")) => {
+                    self.pos += 1;
+                    self.raw_arg();
+                    break;
+                }
+                // A stray closing brace
+                Some(_) => self.pos += 1,
+                None => break,
+            }
+        }
+        if rows
+            .last()
+            .is_some_and(|row| row.iter().all(|cell| cell.is_empty()))
+        {
+            rows.pop();
+        }
+
+        if let Some((_, delim)) = MATRICES.iter().find(|(name, _)| *name == env) {
+            let rows: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    let cells: Vec<String> = row.iter().map(|cell| call_arg(cell)).collect();
+                    cells.join(", ")
+                })
+                .collect();
+            let delim = delim.map(|d| format!("delim: {}, ", d)).unwrap_or_default();
+            return format!("mat({}{})", delim, rows.join("; "));
+        }
+        let rows: Vec<String> = rows.iter().map(|row| row.join(" & ")).collect();
+        if env == "cases" {
+            let rows: Vec<String> = rows.iter().map(|row| call_arg(row)).collect();
+            format!("cases({})", rows.join(", "))
+        } else {
+            rows.join(" \\ ")
+        }
+    }
+}
+
+/// The Typst for a TeX control symbol
+fn sym_typst(sym: char) -> String {
+    match sym {
+        '\\' => "\\".to_string(),
+        ',' => "thin".to_string(),
+        ':' | '>' => "med".to_string(),
+        ';' => "thick".to_string(),
+        '!' => String::new(),
+        ' ' => "space".to_string(),
+        '|' => "||".to_string(),
+        '&' | '_' | '#' | '$' => format!("\\{}", sym),
+        sym => sym.to_string(),
+    }
+}
+
+/// The Typst for a character of TeX math
+fn char_typst(c: char) -> String {
+    match c {
+        // A slash would make a fraction
+        '/' => "\\/".to_string(),
+        '"' => "\\\"".to_string(),
+        '~' => "space.nobreak".to_string(),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_typst(tex: &str, typst: &str) {
+        let translated = tex_to_typst(tex);
+        assert_eq!(translated, typst, "from {:?}", tex);
+        assert!(
+            !typst_syntax::parse_math(&translated).erroneous(),
+            "{:?} does not parse",
+            translated
+        );
+    }
+
+    #[test]
+    fn test_typst_to_tex() {
+        let cases = [
+            ("x^2 + y^2 = z^2", "x^2 + y^2 = z^2"),
+            ("(a + b)/2", "\\frac{a + b}{2}"),
+            ("a / b^2", "\\frac{a}{b^2}"),
+            ("sum_(i=1)^n i", "\\sum_{i=1}^n i"),
+            ("alpha beta <= pi", "\\alpha \\beta \\le \\pi"),
+            ("sqrt(x) + root(3, y)", "\\sqrt{x} + \\sqrt[3]{y}"),
+            ("frac(1, 2) in RR", "\\frac{1}{2} \\in \\mathbb{R}"),
+            ("f(x) -> oo", "f(x) \\to \\infty"),
+            ("\"if\" x != 0", "\\text{if} x \\ne 0"),
+            (
+                "mat(1, 2; 3, 4)",
+                "\\begin{pmatrix} 1 & 2 \\\\ 3 & 4 \\end{pmatrix}",
+            ),
+            (
+                "mat(delim: \"[\", a, b)",
+                "\\begin{bmatrix} a & b \\end{bmatrix}",
+            ),
+            (
+                "cases(1 & x > 0, 0 & \"else\")",
+                "\\begin{cases} 1 & x > 0 \\\\ 0 & \\text{else} \\end{cases}",
+            ),
+            ("hat(x) dot bold(v)", "\\hat{x} \\cdot \\mathbf{v}"),
+            (
+                "abs(x) arrow.r.long y",
+                "\\left| x \\right| \\longrightarrow y",
+            ),
+            ("integral_0^1 x dif x", "\\int_0^1 x \\mathrm{d} x"),
+        ];
+        for (typst, tex) in cases {
+            assert_eq!(typst_to_tex(typst), tex, "from {:?}", typst);
+        }
+    }
+
+    #[test]
+    fn test_tex_to_typst() {
+        assert_typst("x^2 + y^{n+1}", "x^2 + y^(n+1)");
+        assert_typst("\\frac{a+b}{2}", "frac(a+b, 2)");
+        assert_typst("\\frac12", "frac(1, 2)");
+        assert_typst("\\sum_{i=1}^{n} i", "sum_(i=1)^n i");
+        assert_typst("\\alpha\\beta \\leq \\pi", "alpha beta <= pi");
+        assert_typst("xy + 2x", "x y + 2 x");
+        assert_typst("\\sqrt[3]{x}", "root(3, x)");
+        assert_typst("x \\in \\mathbb{R}", "x in RR");
+        assert_typst("\\text{if } x \\neq 0", "\"if \" x != 0");
+        assert_typst("a/b", "a\\/b");
+        assert_typst("\\frac{f(a, b)}{c, d}", "frac(f(a, b), c\\, d)");
+        assert_typst(
+            "\\begin{pmatrix} 1 & 2 \\\\ 3 & 4 \\end{pmatrix}",
+            "mat(1, 2; 3, 4)",
+        );
+        assert_typst(
+            "\\begin{bmatrix} a & b \\end{bmatrix}",
+            "mat(delim: \"[\", a, b)",
+        );
+        assert_typst(
+            "f(x) = \\begin{cases} 1 & x > 0 \\\\ 0 & \\text{else} \\end{cases}",
+            "f(x) = cases(1 & x > 0, 0 & \"else\")",
+        );
+        assert_typst(
+            "\\left( \\hat{x} \\cdot \\mathbf{v} \\right)",
+            "( hat(x) dot.op bold(v) )",
+        );
+        assert_typst("\\sin(x) \\mathrm{d}x", "sin (x) upright(d) x");
+        assert_typst("a \\\\ b", "a \\ b");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for tex in [
+            "x^2 + y^2 = z^2",
+            "\\frac{1}{2} \\in \\mathbb{R}",
+            "\\sum_{i=1}^n \\alpha_i",
+            "\\begin{pmatrix} 1 & 2 \\\\ 3 & 4 \\end{pmatrix}",
+        ] {
+            assert_eq!(typst_to_tex(&tex_to_typst(tex)), tex);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Joining documents end to end
+//!
+//! [`Document::append`] adds one document to the end of another and
+//! [`concat`] joins a list. The appended document's title can become a
+//! heading over its content, metadata is merged by a [`MetaMerge`] strategy,
+//! and anchors or footnote labels that already exist in the first document
+//! are renamed, with the appended document's own links following them.
+
+use crate::ast::{Block, Document, Inline};
+use crate::book::shift_headings;
+use crate::toolkit::inline_text;
+use crate::toolkit::links::{
+    classify, collect_anchors, heading_slug, visit_link_urls_mut, LinkTarget,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Which metadata the joined document keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaMerge {
+    /// Only the first document's
+    Keep,
+    /// The first document's, with fields it leaves empty taken from the
+    /// appended one
+    #[default]
+    Fill,
+    /// As `Fill`, and authors and keywords from both
+    Combine,
+}
+
+/// How [`Document::append`] joins two documents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MergeOptions {
+    /// Put the appended document's title at the top of its content as a
+    /// level 1 heading, with its own headings shifted to start at level 2
+    pub demote_titles: bool,
+    pub meta: MetaMerge,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            demote_titles: true,
+            meta: MetaMerge::default(),
+        }
+    }
+}
+
+impl Document {
+    /// Add `other` to the end of this document
+    ///
+    /// Heading anchors and ids in `other` that this document already has
+    /// get a `-1`, `-2`, ... suffix, the way GitHub numbers repeated
+    /// headings, and clashing footnote labels are renamed the same way;
+    /// links within `other` are updated to match. Source positions no
+    /// longer apply, so the kept source and the appended blocks' spans are
+    /// dropped.
+    pub fn append(&mut self, mut other: Document, options: &MergeOptions) {
+        let title = other.meta.title.take();
+        if let Some(title) = title.clone().filter(|_| options.demote_titles) {
+            shift_headings(&mut other.content, 2);
+            other.content.insert(
+                0,
+                Block::Heading {
+                    level: 1,
+                    content: vec![Inline::Text { content: title }],
+                    id: None,
+                    classes: Vec::new(),
+                    span: None,
+                },
+            );
+        } else {
+            other.meta.title = title;
+        }
+
+        rename_anchors(&mut other, collect_anchors(self));
+        rename_footnotes(&mut other.content, footnote_labels(&self.content));
+        clear_spans(&mut other.content);
+        merge_meta(self, other.meta, options.meta);
+
+        self.content.append(&mut other.content);
+        self.raw_source = None;
+    }
+}
+
+/// Join `docs` in order, appending each to the first with `options`
+///
+/// `None` if there are no documents.
+pub fn concat(
+    docs: impl IntoIterator<Item = Document>,
+    options: &MergeOptions,
+) -> Option<Document> {
+    let mut docs = docs.into_iter();
+    let mut joined = docs.next()?;
+    for doc in docs {
+        joined.append(doc, options);
+    }
+    Some(joined)
+}
+
+/// Give every anchor in `doc` that is in `taken` a new one, and point the
+/// document's links at the new names
+fn rename_anchors(doc: &mut Document, mut taken: HashSet<String>) {
+    fn unique(anchor: &str, taken: &HashSet<String>) -> String {
+        (1..)
+            .map(|n| format!("{}-{}", anchor, n))
+            .find(|candidate| !taken.contains(candidate))
+            .expect("unbounded")
+    }
+
+    /// The anchor `id` defines after renaming, recording any rename
+    fn claim(
+        id: &mut Option<String>,
+        anchor: String,
+        taken: &mut HashSet<String>,
+        renamed: &mut HashMap<String, String>,
+    ) {
+        if taken.contains(&anchor) {
+            let new = unique(&anchor, taken);
+            renamed.entry(anchor).or_insert_with(|| new.clone());
+            *id = Some(new.clone());
+            taken.insert(new);
+        } else {
+            taken.insert(anchor);
+        }
+    }
+
+    fn visit_inlines(
+        inlines: &mut [Inline],
+        taken: &mut HashSet<String>,
+        renamed: &mut HashMap<String, String>,
+    ) {
+        for inline in inlines {
+            if let Inline::Span {
+                id: id @ Some(_), ..
+            } = inline
+            {
+                let anchor = id.clone().unwrap_or_default();
+                claim(id, anchor, taken, renamed);
+            }
+            for child in inline.children_mut() {
+                visit_inlines(std::slice::from_mut(child), taken, renamed);
+            }
+        }
+    }
+
+    fn visit_blocks(
+        blocks: &mut [Block],
+        taken: &mut HashSet<String>,
+        renamed: &mut HashMap<String, String>,
+    ) {
+        for block in blocks {
+            match block {
+                Block::Heading { id, content, .. } => {
+                    let anchor = id
+                        .clone()
+                        .unwrap_or_else(|| heading_slug(&inline_text(content.iter())));
+                    claim(id, anchor, taken, renamed);
+                }
+                Block::Container {
+                    id: id @ Some(_), ..
+                }
+                | Block::Figure {
+                    id: id @ Some(_), ..
+                } => {
+                    let anchor = id.clone().unwrap_or_default();
+                    claim(id, anchor, taken, renamed);
+                }
+                _ => {}
+            }
+            for group in block.inline_groups_mut() {
+                visit_inlines(group, taken, renamed);
+            }
+            for child in block.child_blocks_mut() {
+                visit_blocks(std::slice::from_mut(child), taken, renamed);
+            }
+        }
+    }
+
+    let mut renamed = HashMap::new();
+    visit_blocks(&mut doc.content, &mut taken, &mut renamed);
+    if renamed.is_empty() {
+        return;
+    }
+    visit_link_urls_mut(doc, &mut |url| {
+        if let LinkTarget::Anchor { id } = classify(url) {
+            if let Some(new) = renamed.get(&id) {
+                *url = format!("#{}", new);
+            }
+        }
+        Some(())
+    });
+}
+
+/// Footnote labels defined or referred to in `blocks`
+fn footnote_labels(blocks: &[Block]) -> HashSet<String> {
+    let mut labels = HashSet::new();
+    visit_footnotes(blocks, &mut |label| {
+        labels.insert(label.clone());
+    });
+    labels
+}
+
+/// Rename the footnotes in `blocks` whose labels are in `taken`
+fn rename_footnotes(blocks: &mut [Block], taken: HashSet<String>) {
+    let own = footnote_labels(blocks);
+    let mut used: HashSet<String> = taken.union(&own).cloned().collect();
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    for label in own.iter().filter(|label| taken.contains(*label)) {
+        let new = (1..)
+            .map(|n| format!("{}-{}", label, n))
+            .find(|candidate| !used.contains(candidate))
+            .expect("unbounded");
+        used.insert(new.clone());
+        renamed.insert(label.clone(), new);
+    }
+    if !renamed.is_empty() {
+        visit_footnotes_mut(blocks, &mut |label| {
+            if let Some(new) = renamed.get(label.as_str()) {
+                *label = new.clone();
+            }
+        });
+    }
+}
+
+fn visit_footnotes(blocks: &[Block], f: &mut impl FnMut(&String)) {
+    fn visit_inline(inline: &Inline, f: &mut impl FnMut(&String)) {
+        if let Inline::FootnoteRef { label } = inline {
+            f(label);
+        }
+        for child in inline.children() {
+            visit_inline(child, f);
+        }
+    }
+
+    for block in blocks {
+        if let Block::FootnoteDefinition { label, .. } = block {
+            f(label);
+        }
+        for inline in block.inline_groups().into_iter().flatten() {
+            visit_inline(inline, f);
+        }
+        for child in block.child_blocks() {
+            visit_footnotes(std::slice::from_ref(child), f);
+        }
+    }
+}
+
+fn visit_footnotes_mut(blocks: &mut [Block], f: &mut impl FnMut(&mut String)) {
+    fn visit_inline(inline: &mut Inline, f: &mut impl FnMut(&mut String)) {
+        if let Inline::FootnoteRef { label } = inline {
+            f(label);
+        }
+        for child in inline.children_mut() {
+            visit_inline(child, f);
+        }
+    }
+
+    for block in blocks {
+        if let Block::FootnoteDefinition { label, .. } = block {
+            f(label);
+        }
+        for group in block.inline_groups_mut() {
+            for inline in group {
+                visit_inline(inline, f);
+            }
+        }
+        for child in block.child_blocks_mut() {
+            visit_footnotes_mut(std::slice::from_mut(child), f);
+        }
+    }
+}
+
+fn clear_spans(blocks: &mut [Block]) {
+    for block in blocks {
+        block.set_span(None);
+        for child in block.child_blocks_mut() {
+            clear_spans(std::slice::from_mut(child));
+        }
+    }
+}
+
+fn merge_meta(doc: &mut Document, other: crate::ast::DocumentMeta, strategy: MetaMerge) {
+    if strategy == MetaMerge::Keep {
+        return;
+    }
+    let meta = &mut doc.meta;
+    meta.title = meta.title.take().or(other.title);
+    meta.date = meta.date.take().or(other.date);
+    meta.modified = meta.modified.take().or(other.modified);
+    meta.language = meta.language.take().or(other.language);
+    meta.direction = meta.direction.or(other.direction);
+    for (key, value) in other.custom {
+        meta.custom.entry(key).or_insert(value);
+    }
+
+    if strategy == MetaMerge::Combine {
+        for author in other.authors {
+            if !meta.authors.contains(&author) {
+                meta.authors.push(author);
+            }
+        }
+        for keyword in other.keywords {
+            if !meta.keywords.contains(&keyword) {
+                meta.keywords.push(keyword);
+            }
+        }
+    } else {
+        if meta.authors.is_empty() {
+            meta.authors = other.authors;
+        }
+        if meta.keywords.is_empty() {
+            meta.keywords = other.keywords;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::toolkit::links::extract_links;
+    use crate::traits::{ParseConfig, Parser};
+
+    fn parse(source: &str) -> Document {
+        MarkdownHandler::new()
+            .parse(source, &ParseConfig::default())
+            .unwrap()
+    }
+
+    fn headings(doc: &Document) -> Vec<(u8, Option<String>)> {
+        doc.content
+            .iter()
+            .filter_map(|block| match block {
+                Block::Heading { level, id, .. } => Some((*level, id.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_append() {
+        let mut doc =
+            parse("---\ntitle: Notes\nauthor: Ada\n---\n\n# Setup\n\nA[^1].\n\n[^1]: First\n");
+        let other = parse(
+            "---\ntitle: Todo\nauthor: Grace\nlang: en\n---\n\n\
+             # Setup\n\nSee [setup](#setup)[^1].\n\n[^1]: Second\n",
+        );
+
+        doc.append(other, &MergeOptions::default());
+        assert_eq!(doc.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(doc.meta.language.as_deref(), Some("en"));
+        assert_eq!(doc.meta.authors.len(), 1);
+        assert_eq!(
+            headings(&doc),
+            vec![(1, None), (1, None), (2, Some("setup-1".to_string()))]
+        );
+        let urls: Vec<_> = extract_links(&doc).into_iter().map(|l| l.url).collect();
+        assert_eq!(urls, vec!["#setup-1"]);
+        assert_eq!(footnote_labels(&doc.content).len(), 2);
+        assert!(footnote_labels(&doc.content).contains("1-1"));
+    }
+
+    #[test]
+    fn test_concat() {
+        let docs = vec![
+            parse("---\nauthor: Ada\n---\n\nOne\n"),
+            parse("---\nauthor: Grace\nkeywords: [x]\n---\n\nTwo\n"),
+            parse("Three\n"),
+        ];
+        let options = MergeOptions {
+            demote_titles: false,
+            meta: MetaMerge::Combine,
+        };
+
+        let doc = concat(docs, &options).unwrap();
+        assert_eq!(doc.content.len(), 3);
+        assert_eq!(doc.meta.authors.len(), 2);
+        assert_eq!(doc.meta.keywords, vec!["x"]);
+        assert!(concat(Vec::new(), &options).is_none());
+    }
+}
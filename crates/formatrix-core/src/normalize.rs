@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Canonical form of the document tree
+//!
+//! Parsers and edits can build the same content as different trees: text
+//! split over several `Text` inlines, paragraphs emptied by a deletion, divs
+//! and spans that carry nothing. [`Document::normalize`] rewrites a tree
+//! into one canonical form, so documents with the same content compare,
+//! diff and serialize the same, and can be used as cache keys.
+
+use crate::ast::{Block, Document, Inline};
+use crate::edit::children_mut;
+
+impl Document {
+    /// Rewrite the tree into its canonical form
+    ///
+    /// - Adjacent text inlines are merged, and empty ones dropped
+    /// - Paragraphs with no content, or only whitespace, are removed
+    /// - Containers and spans with no id, classes or attributes are replaced
+    ///   by their content, as is strong, strikethrough, underline, small
+    ///   caps or highlight whose only child is the same
+    /// - Classes are sorted, without duplicates; attributes are kept sorted
+    ///   by the tree itself
+    ///
+    /// Normalizing again changes nothing. Spans of the blocks that remain are
+    /// kept, so the tree still points into its source.
+    pub fn normalize(&mut self) {
+        normalize_blocks(&mut self.content);
+    }
+}
+
+fn normalize_blocks(blocks: &mut Vec<Block>) {
+    let mut normalized = Vec::with_capacity(blocks.len());
+    for mut block in blocks.drain(..) {
+        for list in block_lists_mut(&mut block) {
+            normalize_blocks(list);
+        }
+        for group in block.inline_groups_mut() {
+            normalize_inlines(group);
+        }
+
+        match block {
+            Block::Container {
+                id: None,
+                classes,
+                attributes,
+                content,
+                ..
+            } if classes.is_empty() && attributes.is_empty() => normalized.extend(content),
+            Block::Paragraph { ref content, .. } if is_blank(content) => {}
+            Block::Container {
+                id,
+                mut classes,
+                attributes,
+                content,
+                span,
+            } => {
+                sort_classes(&mut classes);
+                normalized.push(Block::Container {
+                    id,
+                    classes,
+                    attributes,
+                    content,
+                    span,
+                });
+            }
+            block => normalized.push(block),
+        }
+    }
+    *blocks = normalized;
+}
+
+fn normalize_inlines(inlines: &mut Vec<Inline>) {
+    let mut normalized = Vec::with_capacity(inlines.len());
+    for mut inline in inlines.drain(..) {
+        if let Some(children) = children_mut(&mut inline) {
+            normalize_inlines(children);
+        }
+        if let Inline::Citation { prefix, suffix, .. } = &mut inline {
+            prefix.iter_mut().chain(suffix).for_each(normalize_inlines);
+        }
+
+        match inline {
+            Inline::Span {
+                id: None,
+                classes,
+                attributes,
+                content,
+            } if classes.is_empty() && attributes.is_empty() => {
+                for child in content {
+                    push_inline(&mut normalized, child);
+                }
+            }
+            Inline::Span {
+                id,
+                mut classes,
+                attributes,
+                content,
+            } => {
+                sort_classes(&mut classes);
+                normalized.push(Inline::Span {
+                    id,
+                    classes,
+                    attributes,
+                    content,
+                });
+            }
+            mut inline => {
+                if let Some(child) = same_kind_child(&mut inline) {
+                    inline = child;
+                }
+                push_inline(&mut normalized, inline);
+            }
+        }
+    }
+    *inlines = normalized;
+}
+
+/// Append `inline`, joining it to text just before it
+fn push_inline(inlines: &mut Vec<Inline>, inline: Inline) {
+    match (inlines.last_mut(), inline) {
+        (_, Inline::Text { content }) if content.is_empty() => {}
+        (Some(Inline::Text { content: last }), Inline::Text { content }) => last.push_str(&content),
+        (_, inline) => inlines.push(inline),
+    }
+}
+
+/// The only child of a formatting inline that means the same when nested
+/// in itself, when that child is of the same kind
+fn same_kind_child(inline: &mut Inline) -> Option<Inline> {
+    let kind = std::mem::discriminant(&*inline);
+    let content = match inline {
+        Inline::Strong { content }
+        | Inline::Strikethrough { content }
+        | Inline::Underline { content }
+        | Inline::SmallCaps { content }
+        | Inline::Highlight { content } => content,
+        _ => return None,
+    };
+    match content.as_slice() {
+        [child] if std::mem::discriminant(child) == kind => content.pop(),
+        _ => None,
+    }
+}
+
+fn is_blank(inlines: &[Inline]) -> bool {
+    inlines.iter().all(|inline| match inline {
+        Inline::Text { content } => content.trim().is_empty(),
+        Inline::SoftBreak | Inline::LineBreak => true,
+        _ => false,
+    })
+}
+
+fn sort_classes(classes: &mut Vec<String>) {
+    classes.sort();
+    classes.dedup();
+}
+
+/// The block sequences nested directly inside `block`
+pub(crate) fn block_lists_mut(block: &mut Block) -> Vec<&mut Vec<Block>> {
+    match block {
+        Block::BlockQuote { content, .. }
+        | Block::Container { content, .. }
+        | Block::Details { content, .. }
+        | Block::Figure { content, .. }
+        | Block::FootnoteDefinition { content, .. } => vec![content],
+        Block::List { items, .. } => items.iter_mut().map(|i| &mut i.content).collect(),
+        Block::DefinitionList { items, .. } => {
+            items.iter_mut().flat_map(|i| &mut i.definitions).collect()
+        }
+        Block::Table {
+            header,
+            body,
+            footer,
+            ..
+        } => header
+            .iter_mut()
+            .chain(body)
+            .chain(footer)
+            .flat_map(|row| &mut row.cells)
+            .map(|cell| &mut cell.content)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::SourceFormat;
+
+    fn text(content: &str) -> Inline {
+        Inline::Text {
+            content: content.to_string(),
+        }
+    }
+
+    fn paragraph(content: Vec<Inline>) -> Block {
+        Block::Paragraph {
+            content,
+            span: None,
+        }
+    }
+
+    fn container(classes: &[&str], content: Vec<Block>) -> Block {
+        Block::Container {
+            id: None,
+            classes: classes.iter().map(|c| c.to_string()).collect(),
+            attributes: Default::default(),
+            content,
+            span: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut doc = Document::new(SourceFormat::Markdown);
+        doc.content = vec![
+            paragraph(vec![
+                text("a"),
+                text(""),
+                Inline::Span {
+                    id: None,
+                    classes: Vec::new(),
+                    attributes: Default::default(),
+                    content: vec![text("b")],
+                },
+                Inline::Strong {
+                    content: vec![Inline::Strong {
+                        content: vec![text("c"), text("d")],
+                    }],
+                },
+            ]),
+            Block::BlockQuote {
+                content: vec![paragraph(vec![text(" "), Inline::SoftBreak])],
+                attribution: None,
+                admonition: None,
+                span: None,
+            },
+            container(&[], vec![paragraph(vec![text("e")])]),
+            container(&["b", "a", "b"], vec![paragraph(vec![text("f")])]),
+        ];
+
+        doc.normalize();
+        let expected = vec![
+            paragraph(vec![
+                text("ab"),
+                Inline::Strong {
+                    content: vec![text("cd")],
+                },
+            ]),
+            Block::BlockQuote {
+                content: Vec::new(),
+                attribution: None,
+                admonition: None,
+                span: None,
+            },
+            paragraph(vec![text("e")]),
+            container(&["a", "b"], vec![paragraph(vec![text("f")])]),
+        ];
+        let json = serde_json::to_value(&doc.content).unwrap();
+        assert_eq!(json, serde_json::to_value(&expected).unwrap());
+
+        doc.normalize();
+        assert_eq!(serde_json::to_value(&doc.content).unwrap(), json);
+    }
+
+    #[test]
+    fn test_normalize_keeps_meaningful_nesting() {
+        let mut doc = Document::new(SourceFormat::Markdown);
+        let nested = paragraph(vec![Inline::Emphasis {
+            content: vec![Inline::Emphasis {
+                content: vec![text("a")],
+            }],
+        }]);
+        doc.content = vec![nested.clone()];
+
+        doc.normalize();
+        assert_eq!(
+            serde_json::to_value(&doc.content).unwrap(),
+            serde_json::to_value(vec![nested]).unwrap()
+        );
+    }
+}
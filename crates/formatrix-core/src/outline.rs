@@ -0,0 +1,333 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Document outline
+//!
+//! The headings of a document as a tree, for an outline sidebar. A heading
+//! goes under the nearest heading before it with a lower level, so a skipped
+//! level still nests. Headings inside containers (sections, sidebars) count;
+//! those in block quotes, lists and tables do not.
+//!
+//! [`extract_section`] cuts one heading's section out as a document of its
+//! own, for focused editing or exporting a single chapter, and
+//! [`Document::split_at_level`] cuts a whole document into its sections.
+
+use crate::ast::{Block, Document, Inline, Span};
+use crate::toolkit::inline_text;
+use crate::toolkit::links::heading_slug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// One heading and the headings under it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub level: u8,
+    pub title: String,
+    /// Anchor of the heading: its explicit id, or the generated slug
+    pub id: String,
+    /// Index path through nested blocks, starting at `Document::content`
+    pub block_path: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OutlineEntry>,
+}
+
+/// The heading tree of `doc`
+pub fn outline(doc: &Document) -> Vec<OutlineEntry> {
+    let mut headings = Vec::new();
+    collect(&doc.content, &mut Vec::new(), &mut headings);
+
+    // Open entries from the outermost in; each closes at the next heading
+    // of the same or a lower level
+    let mut roots = Vec::new();
+    let mut open: Vec<OutlineEntry> = Vec::new();
+    for entry in headings {
+        close(&mut open, &mut roots, entry.level);
+        open.push(entry);
+    }
+    close(&mut open, &mut roots, 0);
+    roots
+}
+
+/// The section under the heading whose anchor is `heading_id`, as a
+/// standalone document
+///
+/// The section is the heading and what follows it up to the next heading of
+/// the same or a higher level beside it, or the end of its container; its
+/// subsections come with it. Metadata is kept, and footnote definitions
+/// that the section refers to are appended. `None` if no heading has that
+/// anchor.
+pub fn extract_section(doc: &Document, heading_id: &str) -> Option<Document> {
+    let headings = outline(doc);
+    let entry = find_entry(&headings, heading_id)?;
+    let (&index, parents) = entry.block_path.split_last()?;
+    let mut siblings = &doc.content;
+    for &i in parents {
+        match &siblings[i] {
+            Block::Container { content, .. } => siblings = content,
+            _ => return None,
+        }
+    }
+
+    let end = siblings[index + 1..]
+        .iter()
+        .position(|block| matches!(block, Block::Heading { level, .. } if *level <= entry.level))
+        .map_or(siblings.len(), |i| index + 1 + i);
+    // Footnote definitions often collect at the end of a document, so they
+    // are taken by reference wherever they are rather than by position
+    let mut content: Vec<Block> = siblings[index..end]
+        .iter()
+        .filter(|block| !matches!(block, Block::FootnoteDefinition { .. }))
+        .cloned()
+        .collect();
+
+    with_footnotes(&mut content, doc);
+
+    Some(Document {
+        source_format: doc.source_format,
+        meta: doc.meta.clone(),
+        content,
+        raw_source: None,
+    })
+}
+
+impl Document {
+    /// One document per section at heading `level`
+    ///
+    /// A section starts at each top-level heading of `level` or a higher
+    /// level, and runs to the next one. Blocks before the first such heading
+    /// make a leading document of their own. Every section inherits the
+    /// metadata, titled with its heading's text; the leading document keeps
+    /// the original title. Footnote definitions go with the sections that
+    /// refer to them, as in [`extract_section`].
+    pub fn split_at_level(&self, level: u8) -> Vec<Document> {
+        let mut sections: Vec<(Option<String>, Vec<Block>)> = Vec::new();
+        let mut title = self.meta.title.clone();
+        let mut current = Vec::new();
+        for block in &self.content {
+            match block {
+                Block::FootnoteDefinition { .. } => continue,
+                Block::Heading {
+                    level: heading,
+                    content,
+                    ..
+                } if *heading <= level => {
+                    if !current.is_empty() {
+                        sections.push((title, std::mem::take(&mut current)));
+                    }
+                    title = Some(inline_text(content));
+                }
+                _ => {}
+            }
+            current.push(block.clone());
+        }
+        if !current.is_empty() {
+            sections.push((title, current));
+        }
+
+        sections
+            .into_iter()
+            .map(|(title, mut content)| {
+                with_footnotes(&mut content, self);
+                let mut meta = self.meta.clone();
+                meta.title = title;
+                Document {
+                    source_format: self.source_format,
+                    meta,
+                    content,
+                    raw_source: None,
+                }
+            })
+            .collect()
+    }
+}
+
+fn find_entry<'a>(entries: &'a [OutlineEntry], id: &str) -> Option<&'a OutlineEntry> {
+    entries.iter().find_map(|e| {
+        (e.id == id)
+            .then_some(e)
+            .or_else(|| find_entry(&e.children, id))
+    })
+}
+
+/// Append the definitions from `doc` of the footnotes `content` refers to
+fn with_footnotes(content: &mut Vec<Block>, doc: &Document) {
+    let mut labels = HashSet::new();
+    footnote_refs(content.iter(), &mut labels);
+    footnote_definitions(&doc.content, &mut |label, block| {
+        if labels.remove(label) {
+            content.push(block.clone());
+        }
+    });
+}
+
+/// Labels of the footnotes referred to in `blocks`
+fn footnote_refs<'a>(blocks: impl IntoIterator<Item = &'a Block>, labels: &mut HashSet<String>) {
+    fn visit(inline: &Inline, labels: &mut HashSet<String>) {
+        if let Inline::FootnoteRef { label } = inline {
+            labels.insert(label.clone());
+        }
+        for child in inline.children() {
+            visit(child, labels);
+        }
+    }
+
+    for block in blocks {
+        for inline in block.inline_groups().into_iter().flatten() {
+            visit(inline, labels);
+        }
+        footnote_refs(block.child_blocks(), labels);
+    }
+}
+
+/// Call `f` with each footnote definition in `blocks`, at any depth
+fn footnote_definitions<'a>(
+    blocks: impl IntoIterator<Item = &'a Block>,
+    f: &mut impl FnMut(&str, &'a Block),
+) {
+    for block in blocks {
+        if let Block::FootnoteDefinition { label, .. } = block {
+            f(label, block);
+        }
+        footnote_definitions(block.child_blocks(), f);
+    }
+}
+
+fn collect(blocks: &[Block], path: &mut Vec<usize>, out: &mut Vec<OutlineEntry>) {
+    for (i, block) in blocks.iter().enumerate() {
+        path.push(i);
+        match block {
+            Block::Heading {
+                level,
+                content,
+                id,
+                span,
+                ..
+            } => {
+                let title = inline_text(content);
+                out.push(OutlineEntry {
+                    level: *level,
+                    id: id.clone().unwrap_or_else(|| heading_slug(&title)),
+                    title,
+                    block_path: path.clone(),
+                    span: *span,
+                    children: Vec::new(),
+                });
+            }
+            Block::Container { content, .. } => collect(content, path, out),
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+/// Close the open entries of `level` or deeper, attaching each to its parent
+fn close(open: &mut Vec<OutlineEntry>, roots: &mut Vec<OutlineEntry>, level: u8) {
+    while open.last().is_some_and(|entry| entry.level >= level) {
+        let entry = open.pop().expect("checked above");
+        match open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser};
+
+    #[test]
+    fn test_outline() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "# Guide\n\n### Deep\n\n## Setup\n\nText\n\n## Use it!\n\n# Appendix\n\n> # Quoted\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        let tree = outline(&doc);
+
+        let summary = |entries: &[OutlineEntry]| -> Vec<(String, Vec<usize>)> {
+            entries
+                .iter()
+                .map(|e| (e.id.clone(), e.block_path.clone()))
+                .collect()
+        };
+        assert_eq!(
+            summary(&tree),
+            vec![
+                ("guide".to_string(), vec![0]),
+                ("appendix".to_string(), vec![5])
+            ]
+        );
+        assert_eq!(
+            summary(&tree[0].children),
+            vec![
+                ("deep".to_string(), vec![1]),
+                ("setup".to_string(), vec![2]),
+                ("use-it".to_string(), vec![4]),
+            ]
+        );
+        assert_eq!(tree[0].children[2].title, "Use it!");
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn test_extract_section() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "# Guide\n\nIntro[^a]\n\n## Setup\n\nStep[^b]\n\n### Detail\n\nMore\n\n## Use\n\nDone\n\n[^a]: First\n[^b]: Second\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        let setup = extract_section(&doc, "setup").unwrap();
+        let kinds: Vec<_> = setup
+            .content
+            .iter()
+            .map(|block| match block {
+                Block::Heading { level, .. } => format!("h{}", level),
+                Block::Paragraph { .. } => "p".to_string(),
+                Block::FootnoteDefinition { label, .. } => format!("fn {}", label),
+                _ => "other".to_string(),
+            })
+            .collect();
+        assert_eq!(kinds, ["h2", "p", "h3", "p", "fn b"]);
+
+        assert_eq!(extract_section(&doc, "use").unwrap().content.len(), 2);
+        assert!(extract_section(&doc, "missing").is_none());
+    }
+
+    #[test]
+    fn test_split_at_level() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "---\ntitle: Guide\nauthor: Ada\n---\n\nIntro\n\n## Setup\n\nStep[^a]\n\n### Detail\n\n\
+                 # Use\n\nDone\n\n[^a]: Note\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        let sections = doc.split_at_level(2);
+        let summary: Vec<_> = sections
+            .iter()
+            .map(|s| (s.meta.title.clone().unwrap_or_default(), s.content.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Guide".to_string(), 1),
+                ("Setup".to_string(), 4),
+                ("Use".to_string(), 2),
+            ]
+        );
+        assert!(matches!(
+            sections[1].content.last(),
+            Some(Block::FootnoteDefinition { label, .. }) if label == "a"
+        ));
+        assert!(sections.iter().all(|s| s.meta.authors.len() == 1));
+
+        assert_eq!(doc.split_at_level(1).len(), 2);
+    }
+}
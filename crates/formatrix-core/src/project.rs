@@ -0,0 +1,535 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Projects: a folder of related documents
+//!
+//! A project is a directory whose supported files belong together, such as a
+//! documentation site or a book in progress. An optional manifest named
+//! [`MANIFEST_FILE`] in the root fixes the reading order and carries
+//! project-wide metadata:
+//!
+//! ```json
+//! {
+//!   "title": "Handbook",
+//!   "documents": ["index.md", "guide/setup.adoc"],
+//!   "include": "**/*.{md,adoc}",
+//!   "meta": { "version": "2.1" }
+//! }
+//! ```
+//!
+//! Files matching `include` but missing from `documents` follow the listed
+//! ones in path order, so a new file shows up without editing the manifest.
+//! Without a manifest every supported file is part of the project.
+//!
+//! Links between project files are resolved against the loaded documents,
+//! which is what a workspace sidebar needs for backlinks and what
+//! [`Book::from_project`](crate::book::Book::from_project) builds on.
+
+use crate::ast::{Document, MetaValue, SourceFormat};
+use crate::book::normalize;
+use crate::encoding::{decode, encode};
+use crate::file_ops::{
+    format_from_extension, is_supported_extension, matched_inputs, open_file_with_config,
+    save_file_as, write_atomic, BackupPolicy, FileError, FileResult,
+};
+use crate::find::{replace_in_source, FindMatch, FindOptions};
+use crate::toolkit::links::{
+    classify, collect_anchors, extract_links, visit_link_urls_mut, LinkKind, LinkTarget,
+};
+use crate::traits::{ParseConfig, RenderConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file in a project root
+pub const MANIFEST_FILE: &str = "formatrix.json";
+
+/// Project settings as read from the manifest
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Documents in reading order, relative to the project root
+    #[serde(default)]
+    pub documents: Vec<PathBuf>,
+    /// Glob selecting the files that belong to the project (default: all
+    /// supported files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+    /// Project-wide metadata, e.g. a version or an audience
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, MetaValue>,
+}
+
+/// One parsed document of a project
+#[derive(Debug, Clone)]
+pub struct ProjectDocument {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub document: Document,
+}
+
+/// How a link between project files resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    Resolved,
+    /// The target is not a document of the project
+    MissingDocument,
+    /// The target document has no heading or anchor with that id
+    MissingAnchor,
+}
+
+/// A link from one project document to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectLink {
+    /// Linking document, relative to the project root
+    pub from: PathBuf,
+    /// The link target as written
+    pub url: String,
+    /// Target document, relative to the project root
+    pub to: PathBuf,
+    pub fragment: Option<String>,
+    pub status: LinkStatus,
+}
+
+/// What a replace would change in one project document
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReplacement {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Matches in the document as it is now, each with its replacement
+    pub matches: Vec<FindMatch>,
+    /// Matches that could not be found in the source and stay as they are
+    pub skipped: usize,
+    #[serde(skip)]
+    original: Vec<u8>,
+    #[serde(skip)]
+    replaced: Vec<u8>,
+}
+
+/// Result of [`Project::plan_replace`], in reading order
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplacePlan {
+    pub files: Vec<FileReplacement>,
+}
+
+impl ReplacePlan {
+    /// Matches the replace would change, across all files
+    pub fn replaced(&self) -> usize {
+        self.files
+            .iter()
+            .map(|file| file.matches.len() - file.skipped)
+            .sum()
+    }
+}
+
+/// A loaded project
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: ProjectManifest,
+    /// Documents in reading order
+    pub documents: Vec<ProjectDocument>,
+}
+
+impl Project {
+    /// Load the project rooted at `root`
+    pub fn open(root: impl AsRef<Path>) -> FileResult<Self> {
+        Self::open_with_config(root, &ParseConfig::default())
+    }
+
+    /// Load the project rooted at `root`, parsing every document with `config`
+    pub fn open_with_config(root: impl AsRef<Path>, config: &ParseConfig) -> FileResult<Self> {
+        let root = root.as_ref().to_path_buf();
+        let manifest_path = root.join(MANIFEST_FILE);
+        let manifest: ProjectManifest = if manifest_path.is_file() {
+            let json = fs::read_to_string(&manifest_path)?;
+            serde_json::from_str(&json).map_err(|e| FileError::Parse(e.to_string()))?
+        } else {
+            ProjectManifest::default()
+        };
+
+        let mut paths: Vec<PathBuf> = manifest.documents.iter().map(|p| normalize(p)).collect();
+        let glob = manifest.include.as_deref().unwrap_or("**/*");
+        for path in matched_inputs(&root, &root, glob)? {
+            if format_from_extension(&path).is_some() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        let documents = paths
+            .into_iter()
+            .map(|path| {
+                let opened = open_file_with_config(root.join(&path), config)?;
+                Ok(ProjectDocument {
+                    path,
+                    document: opened.document,
+                })
+            })
+            .collect::<FileResult<_>>()?;
+
+        Ok(Self {
+            root,
+            manifest,
+            documents,
+        })
+    }
+
+    /// Write the manifest, recording the current document order
+    pub fn save_manifest(&mut self) -> FileResult<()> {
+        self.manifest.documents = self.documents.iter().map(|d| d.path.clone()).collect();
+        let json = serde_json::to_string_pretty(&self.manifest)
+            .map_err(|e| FileError::Render(e.to_string()))?;
+        fs::write(self.root.join(MANIFEST_FILE), json + "\n")?;
+        Ok(())
+    }
+
+    /// The document at `path`, relative to the project root
+    pub fn document(&self, path: impl AsRef<Path>) -> Option<&ProjectDocument> {
+        let path = normalize(path.as_ref());
+        self.documents.iter().find(|d| d.path == path)
+    }
+
+    /// Every link from one project file to another, in reading order
+    ///
+    /// Links to files that are not documents (images, PDFs) are left out.
+    pub fn links(&self) -> Vec<ProjectLink> {
+        let mut links = Vec::new();
+        for doc in &self.documents {
+            for link in extract_links(&doc.document) {
+                if link.kind != LinkKind::Link {
+                    continue;
+                }
+                if let Some(resolved) = self.resolve_link(&doc.path, &link.url) {
+                    links.push(resolved);
+                }
+            }
+        }
+        links
+    }
+
+    /// Links that point at a missing document or anchor
+    pub fn broken_links(&self) -> Vec<ProjectLink> {
+        self.links()
+            .into_iter()
+            .filter(|link| link.status != LinkStatus::Resolved)
+            .collect()
+    }
+
+    /// Links from other documents into `path`
+    pub fn backlinks(&self, path: impl AsRef<Path>) -> Vec<ProjectLink> {
+        let path = normalize(path.as_ref());
+        self.links()
+            .into_iter()
+            .filter(|link| link.to == path)
+            .collect()
+    }
+
+    /// Resolve `url`, as written in the document at `from`, to a project file
+    ///
+    /// `None` when the URL is not a link to a local document.
+    pub fn resolve_link(&self, from: &Path, url: &str) -> Option<ProjectLink> {
+        let LinkTarget::LocalFile { path, fragment } = classify(url) else {
+            return None;
+        };
+        let ext = Path::new(&path).extension()?.to_str()?.to_lowercase();
+        if !is_supported_extension(&ext) {
+            return None;
+        }
+
+        let dir = from.parent().unwrap_or(Path::new(""));
+        let to = normalize(&dir.join(&path));
+        let status = match self.document(&to) {
+            None => LinkStatus::MissingDocument,
+            Some(target) => match &fragment {
+                Some(id) if !collect_anchors(&target.document).contains(id) => {
+                    LinkStatus::MissingAnchor
+                }
+                _ => LinkStatus::Resolved,
+            },
+        };
+        Some(ProjectLink {
+            from: from.to_path_buf(),
+            url: url.to_string(),
+            to,
+            fragment,
+            status,
+        })
+    }
+
+    /// Work out what replacing `pattern` with `replacement` in every
+    /// document would change, without writing anything
+    ///
+    /// Documents are edited as source text, so only the matched text changes
+    /// and each file keeps its encoding and layout.
+    pub fn plan_replace(
+        &self,
+        pattern: &str,
+        replacement: &str,
+        options: &FindOptions,
+    ) -> FileResult<ReplacePlan> {
+        let mut files = Vec::new();
+        for doc in &self.documents {
+            let original = fs::read(self.root.join(&doc.path))?;
+            let (source, encoding) = decode(&original);
+            let (output, matches) =
+                replace_in_source(&doc.document, &source, pattern, replacement, options)?;
+            if matches.is_empty() {
+                continue;
+            }
+            let replaced =
+                encode(&output, encoding).map_err(|character| FileError::Unencodable {
+                    encoding,
+                    character,
+                })?;
+            files.push(FileReplacement {
+                path: doc.path.clone(),
+                skipped: matches.iter().filter(|m| m.span.is_none()).count(),
+                matches,
+                original,
+                replaced,
+            });
+        }
+        Ok(ReplacePlan { files })
+    }
+
+    /// Write the changes in `plan`: every file or none
+    ///
+    /// Nothing is written if a file changed since the plan was made, and if
+    /// writing one fails, those already written get their previous contents
+    /// back. The changed documents are parsed again.
+    pub fn apply_replace(&mut self, plan: &ReplacePlan) -> FileResult<()> {
+        let changed: Vec<_> = plan
+            .files
+            .iter()
+            .filter(|file| file.replaced != file.original)
+            .collect();
+        for file in &changed {
+            let path = self.root.join(&file.path);
+            if fs::read(&path)? != file.original {
+                return Err(FileError::ExternallyModified {
+                    path: path.display().to_string(),
+                });
+            }
+        }
+
+        for (i, file) in changed.iter().enumerate() {
+            if let Err(e) = write_atomic(
+                self.root.join(&file.path),
+                &file.replaced,
+                BackupPolicy::None,
+            ) {
+                for done in &changed[..i] {
+                    let _ = write_atomic(
+                        self.root.join(&done.path),
+                        &done.original,
+                        BackupPolicy::None,
+                    );
+                }
+                return Err(e.into());
+            }
+        }
+
+        for file in changed {
+            let opened =
+                open_file_with_config(self.root.join(&file.path), &ParseConfig::default())?;
+            if let Some(doc) = self.documents.iter_mut().find(|d| d.path == file.path) {
+                doc.document = opened.document;
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert every document to `format` under `output_dir`, keeping the
+    /// directory layout
+    ///
+    /// Links between project documents are pointed at the converted files.
+    /// Returns the written paths in reading order.
+    pub fn convert_all(
+        &self,
+        output_dir: impl AsRef<Path>,
+        format: SourceFormat,
+        config: &RenderConfig,
+    ) -> FileResult<Vec<PathBuf>> {
+        let output_dir = output_dir.as_ref();
+        self.documents
+            .iter()
+            .map(|doc| {
+                let mut converted = doc.document.clone();
+                converted.source_format = format;
+                visit_link_urls_mut(&mut converted, &mut |url| {
+                    if let Some(link) = self.resolve_link(&doc.path, url) {
+                        if link.status != LinkStatus::MissingDocument {
+                            *url = retarget(url, format);
+                        }
+                    }
+                    Some(())
+                });
+
+                let path = output_dir.join(doc.path.with_extension(format.extension()));
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                save_file_as(&converted, &path, format, config)?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// `url` with the extension of its path changed to that of `format`
+fn retarget(url: &str, format: SourceFormat) -> String {
+    let (path, rest) = match url.find(['?', '#']) {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    format!("{}.{}{}", stem, format.extension(), rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> (tempfile::TempDir, Project) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("index.md"),
+            "# Home\n\nSee [setup](guide/setup.adoc#install), [usage](guide/usage.md) \
+             and [the logo](logo.png).\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("guide")).unwrap();
+        fs::write(
+            dir.path().join("guide/setup.adoc"),
+            "== Setup\n\n=== Install\n\nBack link:../index.md#missing[home].\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("guide/notes.md"), "# Notes\n").unwrap();
+        fs::write(dir.path().join("logo.png"), b"\x89PNG").unwrap();
+        fs::write(
+            dir.path().join(MANIFEST_FILE),
+            r#"{ "title": "Handbook", "documents": ["index.md", "guide/setup.adoc"] }"#,
+        )
+        .unwrap();
+
+        let project = Project::open(dir.path()).unwrap();
+        (dir, project)
+    }
+
+    #[test]
+    fn test_open_and_links() {
+        let (_dir, project) = project();
+        let paths: Vec<_> = project.documents.iter().map(|d| d.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("index.md"),
+                PathBuf::from("guide/setup.adoc"),
+                PathBuf::from("guide/notes.md"),
+            ]
+        );
+
+        let statuses: Vec<_> = project
+            .links()
+            .into_iter()
+            .map(|link| (link.from, link.to, link.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (
+                    PathBuf::from("index.md"),
+                    PathBuf::from("guide/setup.adoc"),
+                    LinkStatus::Resolved
+                ),
+                (
+                    PathBuf::from("index.md"),
+                    PathBuf::from("guide/usage.md"),
+                    LinkStatus::MissingDocument
+                ),
+                (
+                    PathBuf::from("guide/setup.adoc"),
+                    PathBuf::from("index.md"),
+                    LinkStatus::MissingAnchor
+                ),
+            ]
+        );
+        assert_eq!(project.broken_links().len(), 2);
+        assert_eq!(project.backlinks("index.md").len(), 1);
+    }
+
+    #[test]
+    fn test_convert_all() {
+        let (dir, mut project) = project();
+        let out = dir.path().join("out");
+        let written = project
+            .convert_all(&out, SourceFormat::Djot, &RenderConfig::default())
+            .unwrap();
+        assert_eq!(
+            written,
+            vec![
+                out.join("index.dj"),
+                out.join("guide/setup.dj"),
+                out.join("guide/notes.dj"),
+            ]
+        );
+        let index = fs::read_to_string(out.join("index.dj")).unwrap();
+        assert!(index.contains("guide/setup.dj#install"));
+        assert!(index.contains("guide/usage.md"));
+        assert!(index.contains("logo.png"));
+
+        project.documents.reverse();
+        project.save_manifest().unwrap();
+        let reopened = Project::open(dir.path()).unwrap();
+        assert_eq!(reopened.manifest.title.as_deref(), Some("Handbook"));
+        assert_eq!(reopened.documents[0].path, PathBuf::from("guide/notes.md"));
+    }
+
+    #[test]
+    fn test_replace() {
+        let (dir, mut project) = project();
+        let plan = project
+            .plan_replace("setup", "install", &FindOptions::default())
+            .unwrap();
+        let counts: Vec<_> = plan
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.matches.len()))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (PathBuf::from("index.md"), 1),
+                (PathBuf::from("guide/setup.adoc"), 1),
+            ]
+        );
+        assert_eq!(plan.replaced(), 2);
+
+        // Nothing is written once a file has changed under the plan
+        let setup = dir.path().join("guide/setup.adoc");
+        let before = fs::read_to_string(&setup).unwrap();
+        fs::write(&setup, before.replace("Back", "Go back")).unwrap();
+        assert!(matches!(
+            project.apply_replace(&plan),
+            Err(FileError::ExternallyModified { .. })
+        ));
+        assert!(fs::read_to_string(dir.path().join("index.md"))
+            .unwrap()
+            .contains("[setup]"));
+
+        fs::write(&setup, before).unwrap();
+        project.apply_replace(&plan).unwrap();
+        let index = fs::read_to_string(dir.path().join("index.md")).unwrap();
+        assert!(index.starts_with("# Home\n\nSee [install](guide/setup.adoc#install)"));
+        assert_eq!(
+            fs::read_to_string(&setup).unwrap(),
+            "== install\n\n=== Install\n\nBack link:../index.md#missing[home].\n"
+        );
+        assert!(project
+            .plan_replace("setup", "install", &FindOptions::default())
+            .unwrap()
+            .files
+            .is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Raw content rendered to another format
+//!
+//! Raw blocks and inlines carry markup passed through from their source
+//! format, such as HTML in Markdown or an export block in Org, which means
+//! nothing to most other formats. The [`RawPolicy`] of a render decides what
+//! becomes of them; raw content in the target's own format is always kept.
+
+use crate::ast::{Block, Document, Inline, SourceFormat};
+use crate::edit::children_mut;
+use crate::formats::handler_for;
+use crate::normalize::block_lists_mut;
+use crate::traits::{ParseConfig, RawPolicy};
+use std::borrow::Cow;
+
+/// `doc` with `policy` applied to raw content in formats other than
+/// `target`
+///
+/// Borrows `doc` when there is nothing to change.
+pub fn apply_raw_policy(
+    doc: &Document,
+    target: SourceFormat,
+    policy: RawPolicy,
+) -> Cow<'_, Document> {
+    if policy == RawPolicy::Keep || !has_foreign_raw(&doc.content, target) {
+        return Cow::Borrowed(doc);
+    }
+    let mut doc = doc.clone();
+    rewrite_blocks(&mut doc.content, target, policy);
+    Cow::Owned(doc)
+}
+
+fn has_foreign_raw(blocks: &[Block], target: SourceFormat) -> bool {
+    fn in_inline(inline: &Inline, target: SourceFormat) -> bool {
+        match inline {
+            Inline::RawInline { format, .. } => *format != target,
+            other => other
+                .children()
+                .into_iter()
+                .any(|child| in_inline(child, target)),
+        }
+    }
+
+    blocks.iter().any(|block| match block {
+        Block::Raw { format, .. } => *format != target,
+        _ => {
+            block
+                .inline_groups()
+                .into_iter()
+                .flatten()
+                .any(|inline| in_inline(inline, target))
+                || block
+                    .child_blocks()
+                    .into_iter()
+                    .any(|child| has_foreign_raw(std::slice::from_ref(child), target))
+        }
+    })
+}
+
+fn rewrite_blocks(blocks: &mut Vec<Block>, target: SourceFormat, policy: RawPolicy) {
+    let mut rewritten = Vec::with_capacity(blocks.len());
+    for mut block in blocks.drain(..) {
+        let replacement = match &block {
+            Block::Raw {
+                format, content, ..
+            } if *format != target => replace_raw_block(*format, content, target, policy),
+            _ => None,
+        };
+        if let Some(replacement) = replacement {
+            rewritten.extend(replacement);
+            continue;
+        }
+
+        for list in block_lists_mut(&mut block) {
+            rewrite_blocks(list, target, policy);
+        }
+        for group in block.inline_groups_mut() {
+            rewrite_inlines(group, target, policy);
+        }
+        rewritten.push(block);
+    }
+    *blocks = rewritten;
+}
+
+fn rewrite_inlines(inlines: &mut Vec<Inline>, target: SourceFormat, policy: RawPolicy) {
+    let mut rewritten = Vec::with_capacity(inlines.len());
+    for mut inline in inlines.drain(..) {
+        let replacement = match &inline {
+            Inline::RawInline { format, content } if *format != target => {
+                replace_raw_inline(*format, content, policy)
+            }
+            _ => None,
+        };
+        if let Some(replacement) = replacement {
+            rewritten.extend(replacement);
+            continue;
+        }
+
+        if let Some(children) = children_mut(&mut inline) {
+            rewrite_inlines(children, target, policy);
+        }
+        if let Inline::Citation { prefix, suffix, .. } = &mut inline {
+            for group in prefix.iter_mut().chain(suffix) {
+                rewrite_inlines(group, target, policy);
+            }
+        }
+        rewritten.push(inline);
+    }
+    *inlines = rewritten;
+}
+
+/// What a raw block from another format becomes; `None` keeps it
+fn replace_raw_block(
+    format: SourceFormat,
+    content: &str,
+    target: SourceFormat,
+    policy: RawPolicy,
+) -> Option<Vec<Block>> {
+    match policy {
+        RawPolicy::Keep => None,
+        RawPolicy::Drop => Some(Vec::new()),
+        // Plain text has no comment syntax to hide them in
+        RawPolicy::Comment if target == SourceFormat::PlainText => Some(Vec::new()),
+        RawPolicy::Comment => Some(vec![Block::Comment {
+            content: content.trim_end_matches('\n').to_string(),
+            span: None,
+        }]),
+        RawPolicy::Convert => convert(format, content).map(|doc| doc.content),
+    }
+}
+
+/// What a raw inline from another format becomes; `None` keeps it
+fn replace_raw_inline(
+    format: SourceFormat,
+    content: &str,
+    policy: RawPolicy,
+) -> Option<Vec<Inline>> {
+    match policy {
+        RawPolicy::Keep => None,
+        RawPolicy::Drop => Some(Vec::new()),
+        RawPolicy::Comment => Some(vec![Inline::Comment {
+            content: content.to_string(),
+        }]),
+        // Only a single paragraph fits back into a line
+        RawPolicy::Convert => match convert(format, content)?.content.as_mut_slice() {
+            [Block::Paragraph { content, .. }] => Some(std::mem::take(content)),
+            _ => None,
+        },
+    }
+}
+
+/// `content` parsed as `format`, unless the parser finds nothing in it but
+/// raw content again
+fn convert(format: SourceFormat, content: &str) -> Option<Document> {
+    let doc = handler_for(format)
+        .parse(content, &ParseConfig::default())
+        .ok()?;
+    let understood = doc
+        .content
+        .iter()
+        .any(|block| !matches!(block, Block::Raw { .. }));
+    understood.then_some(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        AsciidocHandler, DjotHandler, MarkdownHandler, OrgModeHandler, TypstHandler,
+    };
+    use crate::traits::{Parser, RenderConfig, Renderer};
+
+    fn raw_doc() -> Document {
+        let mut doc = Document::new(SourceFormat::Typst);
+        doc.content = vec![
+            Block::Paragraph {
+                content: vec![
+                    Inline::Text {
+                        content: "See ".to_string(),
+                    },
+                    Inline::RawInline {
+                        format: SourceFormat::Typst,
+                        content: "<intro>".to_string(),
+                    },
+                ],
+                span: None,
+            },
+            Block::Raw {
+                format: SourceFormat::Markdown,
+                content: "*Note*\n".to_string(),
+                span: None,
+            },
+        ];
+        doc
+    }
+
+    fn render_markdown(doc: &Document, policy: RawPolicy) -> String {
+        let config = RenderConfig {
+            raw_policy: policy,
+            ..Default::default()
+        };
+        MarkdownHandler::new().render(doc, &config).unwrap()
+    }
+
+    #[test]
+    fn test_raw_policy() {
+        let mut doc = raw_doc();
+        assert!(matches!(
+            apply_raw_policy(&doc, SourceFormat::Markdown, RawPolicy::Keep),
+            Cow::Borrowed(_)
+        ));
+        doc.content[1] = Block::Raw {
+            format: SourceFormat::Djot,
+            content: "*Note*\n".to_string(),
+            span: None,
+        };
+
+        assert_eq!(
+            render_markdown(&doc, RawPolicy::Keep),
+            "See <intro>\n\n*Note*"
+        );
+        assert_eq!(render_markdown(&doc, RawPolicy::Drop), "See ");
+        assert_eq!(
+            render_markdown(&doc, RawPolicy::Comment),
+            "See <!-- <intro> -->\n\n<!-- *Note* -->"
+        );
+        assert_eq!(
+            render_markdown(&doc, RawPolicy::Convert),
+            "See <intro>\n\n**Note**"
+        );
+
+        let config = RenderConfig {
+            raw_policy: RawPolicy::Comment,
+            ..Default::default()
+        };
+        let typst = TypstHandler::new().render(&doc, &config).unwrap();
+        assert_eq!(typst, "See <intro>\n\n/* *Note* */");
+    }
+
+    #[test]
+    fn test_raw_policy_comment_terminators() {
+        let mut doc = OrgModeHandler::new()
+            .parse(
+                "#+BEGIN_EXPORT latex\n$a --> b$ <script>x</script>\n#+END_EXPORT\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        doc.content.push(Block::Paragraph {
+            content: vec![Inline::RawInline {
+                format: SourceFormat::OrgMode,
+                content: "a --> b */ %} c".to_string(),
+            }],
+            span: None,
+        });
+        let config = RenderConfig {
+            raw_policy: RawPolicy::Comment,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            MarkdownHandler::new().render(&doc, &config).unwrap(),
+            "<!-- $a - -> b$ <script>x</script> -->\n\n<!-- a - -> b */ %} c -->"
+        );
+        assert_eq!(
+            DjotHandler::new().render(&doc, &config).unwrap(),
+            "{% $a --> b$ <script>x</script> %}\n\n{% a --> b */ % } c %}"
+        );
+        assert_eq!(
+            TypstHandler::new().render(&doc, &config).unwrap(),
+            "/* $a --> b$ <script>x</script> */\n\n/* a --> b * / %} c */"
+        );
+        let commented = apply_raw_policy(&doc, SourceFormat::Markdown, RawPolicy::Comment);
+        assert_eq!(
+            crate::html::render_html(&commented),
+            "<!-- $a - -> b$ <script>x</script> -->\n<p><!-- a - -> b */ %} c --></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_raw_policy_convert() {
+        let mut doc = MarkdownHandler::new()
+            .parse("Intro\n\n<div>*kept*</div>\n", &ParseConfig::default())
+            .unwrap();
+        doc.content.push(Block::Raw {
+            format: SourceFormat::Markdown,
+            content: "# Heading\n".to_string(),
+            span: None,
+        });
+
+        let converted = apply_raw_policy(&doc, SourceFormat::AsciiDoc, RawPolicy::Convert);
+        assert!(matches!(
+            converted.content[2],
+            Block::Heading { level: 1, .. }
+        ));
+        // The parser finds nothing but HTML in the div, so it stays raw
+        assert!(matches!(converted.content[1], Block::Raw { .. }));
+
+        let config = RenderConfig {
+            raw_policy: RawPolicy::Convert,
+            ..Default::default()
+        };
+        let rendered = AsciidocHandler::new().render(&doc, &config).unwrap();
+        assert_eq!(
+            rendered,
+            "Intro\n\n++++\n<div>*kept*</div>\n++++\n\n= Heading"
+        );
+    }
+}
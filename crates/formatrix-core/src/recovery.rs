@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Best-effort parsing of malformed input
+//!
+//! When a parser rejects its input, or panics on it, the input is split into
+//! chunks at blank lines (never inside a fenced code block) and each chunk is
+//! parsed on its own. Chunks that still fail are kept verbatim as
+//! [`Block::Raw`] with a diagnostic pointing at them, so one bad table costs
+//! the table rather than the whole document.
+//!
+//! Metadata is only recovered when the first chunk parses, since that is
+//! where front matter lives.
+
+use crate::ast::{Block, Document, Span};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::traits::{ConversionError, ParseConfig, Parser, Result as ConversionResult};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Diagnostic code for regions kept as raw source
+pub const RECOVERY_CODE: &str = "unparsed-region";
+
+/// Parse `input`, recovering what it can if the parser fails
+///
+/// Returns the document with a diagnostic for each region kept as raw
+/// source; the list is empty when the input parsed cleanly.
+pub fn parse_recovering(
+    parser: &dyn Parser,
+    input: &str,
+    config: &ParseConfig,
+) -> (Document, Vec<Diagnostic>) {
+    match try_parse(parser, input, config) {
+        Ok(doc) => (doc, Vec::new()),
+        Err(_) => recover(parser, input, config),
+    }
+}
+
+/// Parse `input` like [`parse_recovering`], but refuse input past the
+/// limits in `config` instead of keeping it raw
+pub(crate) fn parse_recovering_limited(
+    parser: &dyn Parser,
+    input: &str,
+    config: &ParseConfig,
+) -> ConversionResult<(Document, Vec<Diagnostic>)> {
+    match panic::catch_unwind(AssertUnwindSafe(|| parser.parse(input, config))) {
+        Ok(Ok(doc)) => Ok((doc, Vec::new())),
+        Ok(Err(err @ ConversionError::LimitExceeded(_))) => Err(err),
+        _ => Ok(recover(parser, input, config)),
+    }
+}
+
+/// Parse `input` a chunk at a time, once the parser has rejected it whole
+fn recover(parser: &dyn Parser, input: &str, config: &ParseConfig) -> (Document, Vec<Diagnostic>) {
+    let mut doc = Document::new(parser.format());
+    let mut diagnostics = Vec::new();
+    // Consecutive failing chunks are kept as one raw block
+    let mut failed: Option<(Chunk, String)> = None;
+
+    for (index, chunk) in chunks(input).into_iter().enumerate() {
+        match try_parse(parser, &input[chunk.start..chunk.end], config) {
+            Ok(mut parsed) => {
+                if let Some((region, message)) = failed.take() {
+                    keep_raw(&mut doc, &mut diagnostics, input, region, message);
+                }
+                if index == 0 {
+                    doc.meta = parsed.meta;
+                }
+                for block in &mut parsed.content {
+                    shift_spans(block, &chunk);
+                }
+                doc.content.append(&mut parsed.content);
+            }
+            Err(message) => {
+                failed = Some(match failed {
+                    Some((region, first)) => (
+                        Chunk {
+                            end: chunk.end,
+                            ..region
+                        },
+                        first,
+                    ),
+                    None => (chunk, message),
+                });
+            }
+        }
+    }
+    if let Some((region, message)) = failed {
+        keep_raw(&mut doc, &mut diagnostics, input, region, message);
+    }
+
+    if config.preserve_raw_source {
+        doc.raw_source = Some(input.to_string());
+    }
+    (doc, diagnostics)
+}
+
+/// Run the parser, turning a panic into an error message
+fn try_parse(parser: &dyn Parser, input: &str, config: &ParseConfig) -> Result<Document, String> {
+    match panic::catch_unwind(AssertUnwindSafe(|| parser.parse(input, config))) {
+        Ok(Ok(doc)) => Ok(doc),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(payload) => Err(payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "parser panicked".to_string())),
+    }
+}
+
+fn keep_raw(
+    doc: &mut Document,
+    diagnostics: &mut Vec<Diagnostic>,
+    input: &str,
+    region: Chunk,
+    message: String,
+) {
+    let span = Span {
+        start: region.start,
+        end: region.end,
+        line: region.line,
+        column: 1,
+    };
+    diagnostics.push(
+        Diagnostic::new(
+            RECOVERY_CODE,
+            Severity::Error,
+            format!("Kept as raw source after a parse error: {}", message),
+        )
+        .with_span(Some(span)),
+    );
+    doc.content.push(Block::Raw {
+        format: doc.source_format,
+        content: input[region.start..region.end].trim_end().to_string(),
+        span: Some(span),
+    });
+}
+
+/// Byte range of a chunk and the line it starts on
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    start: usize,
+    end: usize,
+    line: u32,
+}
+
+/// Split `input` into runs of non-blank lines
+fn chunks(input: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current: Option<Chunk> = None;
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    for (number, line) in input.split_inclusive('\n').enumerate() {
+        let end = offset + line.len();
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        }
+
+        if trimmed.is_empty() && fence.is_none() {
+            chunks.extend(current.take());
+        } else {
+            let chunk = current.get_or_insert(Chunk {
+                start: offset,
+                end,
+                line: number as u32 + 1,
+            });
+            chunk.end = end;
+        }
+        offset = end;
+    }
+    chunks.extend(current);
+    chunks
+}
+
+/// Move spans from chunk-relative to input-relative positions
+fn shift_spans(block: &mut Block, chunk: &Chunk) {
+    if let Some(span) = block.span_mut() {
+        span.start += chunk.start;
+        span.end += chunk.start;
+        span.line += chunk.line - 1;
+    }
+    for child in block.child_blocks_mut() {
+        shift_spans(child, chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::SourceFormat;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ConversionError, Result};
+
+    /// Markdown parser that rejects any input containing `!!`
+    struct Picky;
+
+    impl Parser for Picky {
+        fn format(&self) -> SourceFormat {
+            SourceFormat::Markdown
+        }
+
+        fn parse(&self, input: &str, config: &ParseConfig) -> Result<Document> {
+            if input.contains("!!") {
+                return Err(ConversionError::ParseError {
+                    line: 0,
+                    column: 0,
+                    message: "bad table".to_string(),
+                });
+            }
+            MarkdownHandler::new().parse(input, config)
+        }
+    }
+
+    #[test]
+    fn test_clean_input() {
+        let (doc, diagnostics) = parse_recovering(&Picky, "# Fine\n", &ParseConfig::default());
+        assert_eq!(doc.content.len(), 1);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_recover_around_bad_region() {
+        let input = "# Title\n\nGood.\n\n| a !! |\n|---\n\nstill !! bad\n\nMiddle.\n\n```\n!!\n\ncode\n```\n\nEnd.\n";
+        let (doc, diagnostics) = parse_recovering(&Picky, input, &ParseConfig::default());
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].span.unwrap().line, 5);
+        assert!(diagnostics[0].message.contains("bad table"));
+        assert_eq!(diagnostics[1].span.unwrap().line, 12);
+
+        assert!(matches!(doc.content[0], Block::Heading { .. }));
+        assert!(matches!(doc.content[1], Block::Paragraph { .. }));
+        match &doc.content[2] {
+            Block::Raw { content, .. } => {
+                assert_eq!(content, "| a !! |\n|---\n\nstill !! bad")
+            }
+            other => panic!("expected raw block, got {:?}", other),
+        }
+        // The fenced block is one chunk, blank line and all
+        assert!(matches!(doc.content[3], Block::Paragraph { .. }));
+        match &doc.content[4] {
+            Block::Raw { content, .. } => assert_eq!(content, "```\n!!\n\ncode\n```"),
+            other => panic!("expected raw block, got {:?}", other),
+        }
+        assert!(matches!(doc.content[5], Block::Paragraph { .. }));
+    }
+}
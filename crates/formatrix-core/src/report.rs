@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Conversion reports and the normalization contract
+//!
+//! Every handler guarantees that a single parse/render cycle normalizes a
+//! document: `render(parse(render(parse(x))))` equals `render(parse(x))`.
+//! Features a handler cannot hold to that are listed by
+//! [`FormatHandler::normalization_exceptions`] and surfaced in the
+//! [`ConversionReport`] for any document that uses them.
+
+use crate::ast::{Block, Document, Inline, ListKind, SourceFormat, Span};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::traits::{FormatHandler, ParseConfig, RenderConfig, Renderer, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Diagnostic code for a feature the target format cannot hold
+pub const LOST_FEATURE_CODE: &str = "lost-feature";
+
+/// What a conversion to a target format will lose or leave unstable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionReport {
+    pub from: SourceFormat,
+    pub to: SourceFormat,
+    /// Features used by the document that the target cannot represent
+    pub lost_features: Vec<String>,
+    /// Features used by the document that the target exempts from the
+    /// normalization guarantee
+    pub unstable_features: Vec<String>,
+}
+
+impl ConversionReport {
+    /// Build the report for rendering `doc` with `target`
+    pub fn new(doc: &Document, target: &dyn FormatHandler) -> Self {
+        let used = used_features(doc);
+        let exceptions = target.normalization_exceptions();
+
+        Self {
+            from: doc.source_format,
+            to: Renderer::format(target),
+            lost_features: used
+                .iter()
+                .filter(|f| !target.supports_feature(f))
+                .map(|f| f.to_string())
+                .collect(),
+            unstable_features: used
+                .iter()
+                .filter(|f| exceptions.contains(f))
+                .map(|f| f.to_string())
+                .collect(),
+        }
+    }
+
+    /// Whether every feature in the document survives the conversion
+    pub fn is_lossless(&self) -> bool {
+        self.lost_features.is_empty()
+    }
+
+    /// Where `doc` uses the lost features: one warning per block and
+    /// feature, at the block's span or that of the nearest block around it
+    /// with one
+    ///
+    /// Spans are only there if the document was parsed with
+    /// [`ParseConfig::preserve_spans`] by a parser that records them.
+    pub fn diagnostics(&self, doc: &Document) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if !self.is_lossless() {
+            self.visit(&doc.content, None, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    fn visit<'a>(
+        &self,
+        blocks: impl IntoIterator<Item = &'a Block>,
+        outer: Option<Span>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for block in blocks {
+            let span = block.span().or(outer);
+            let mut used: BTreeSet<_> = block_feature(block).into_iter().collect();
+            for group in block.inline_groups() {
+                inline_features(group, &mut used);
+            }
+            for feature in used {
+                if self.lost_features.iter().any(|lost| lost == feature) {
+                    let message =
+                        format!("{} cannot be represented in {}", feature, self.to.label());
+                    diagnostics.push(
+                        Diagnostic::new(LOST_FEATURE_CODE, Severity::Warning, message)
+                            .with_span(span),
+                    );
+                }
+            }
+            self.visit(block.child_blocks(), span, diagnostics);
+        }
+    }
+}
+
+/// Every feature [`used_features`] can report
+pub const FEATURES: [&str; 23] = [
+    "heading",
+    "bold",
+    "italic",
+    "strikethrough",
+    "code",
+    "code_block",
+    "link",
+    "image",
+    "list",
+    "task_list",
+    "table",
+    "blockquote",
+    "admonition",
+    "math",
+    "footnote",
+    "container",
+    "definition_list",
+    "details",
+    "kbd",
+    "button",
+    "menu",
+    "emoji",
+    "nested_quote",
+];
+
+/// Features used by a document, named as in [`FormatHandler::supported_features`]
+pub fn used_features(doc: &Document) -> BTreeSet<&'static str> {
+    fn visit_blocks<'a>(
+        blocks: impl IntoIterator<Item = &'a Block>,
+        in_quote: bool,
+        used: &mut BTreeSet<&'static str>,
+    ) {
+        for block in blocks {
+            // A quote holding or held by another counts as a nested quote
+            let quote = matches!(block, Block::BlockQuote { .. });
+            let nested = quote && (in_quote || holds_quote(block));
+            used.extend(block_feature(block).filter(|f| !(nested && *f == "blockquote")));
+            if nested {
+                used.insert("nested_quote");
+            }
+            for group in block.inline_groups() {
+                inline_features(group, used);
+            }
+            visit_blocks(block.child_blocks(), in_quote || quote, used);
+        }
+    }
+
+    let mut used = BTreeSet::new();
+    visit_blocks(&doc.content, false, &mut used);
+    used
+}
+
+/// Whether a quote is nested anywhere within `block`
+fn holds_quote(block: &Block) -> bool {
+    block
+        .child_blocks()
+        .into_iter()
+        .any(|child| matches!(child, Block::BlockQuote { .. }) || holds_quote(child))
+}
+
+/// The feature a block itself stands for, apart from its contents
+fn block_feature(block: &Block) -> Option<&'static str> {
+    match block {
+        Block::Heading { .. } => Some("heading"),
+        Block::CodeBlock { .. } => Some("code_block"),
+        Block::BlockQuote {
+            admonition: Some(_),
+            ..
+        } => Some("admonition"),
+        Block::BlockQuote { .. } => Some("blockquote"),
+        Block::List {
+            kind: ListKind::Task,
+            ..
+        } => Some("task_list"),
+        Block::List { .. } => Some("list"),
+        Block::Table { .. } => Some("table"),
+        Block::MathBlock { .. } => Some("math"),
+        Block::FootnoteDefinition { .. } => Some("footnote"),
+        Block::Container { .. } => Some("container"),
+        Block::DefinitionList { .. } => Some("definition_list"),
+        Block::Details { .. } => Some("details"),
+        _ => None,
+    }
+}
+
+/// Add the features used by `inlines`, at any depth, to `used`
+fn inline_features<'a>(
+    inlines: impl IntoIterator<Item = &'a Inline>,
+    used: &mut BTreeSet<&'static str>,
+) {
+    for inline in inlines {
+        let feature = match inline {
+            Inline::Strong { .. } => Some("bold"),
+            Inline::Emphasis { .. } => Some("italic"),
+            Inline::Strikethrough { .. } => Some("strikethrough"),
+            Inline::Code { .. } => Some("code"),
+            Inline::Link { .. } => Some("link"),
+            Inline::Image { .. } => Some("image"),
+            Inline::Math { .. } => Some("math"),
+            Inline::FootnoteRef { .. } => Some("footnote"),
+            Inline::Keyboard { .. } => Some("kbd"),
+            Inline::Button { .. } => Some("button"),
+            Inline::Menu { .. } => Some("menu"),
+            Inline::Emoji { .. } => Some("emoji"),
+            _ => None,
+        };
+        used.extend(feature);
+        inline_features(inline.children(), used);
+    }
+}
+
+/// Run one parse/render cycle, producing the handler's normal form of `input`
+///
+/// Normalizing the result again returns it unchanged, except where the input
+/// uses one of the handler's [`FormatHandler::normalization_exceptions`].
+pub fn normalize(handler: &dyn FormatHandler, input: &str) -> Result<String> {
+    let doc = handler.parse(input, &ParseConfig::default())?;
+    handler.render(&doc, &RenderConfig::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{handler_for, MarkdownHandler};
+    use crate::incremental::IncrementalParser;
+    use crate::traits::Parser;
+
+    fn markdown(input: &str) -> Document {
+        MarkdownHandler::new()
+            .parse(input, &ParseConfig::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_used_features() {
+        let doc = markdown("# Title\n\n- item with **bold** and [a link](x.md)\n");
+        let used: Vec<_> = used_features(&doc).into_iter().collect();
+        assert_eq!(used, vec!["bold", "heading", "link", "list"]);
+    }
+
+    #[test]
+    fn test_report_lost_features() {
+        let doc = markdown("Some ~~struck~~ text\n");
+        let report =
+            ConversionReport::new(&doc, handler_for(SourceFormat::ReStructuredText).as_ref());
+        assert_eq!(report.from, SourceFormat::Markdown);
+        assert_eq!(report.lost_features, vec!["strikethrough"]);
+        assert!(!report.is_lossless());
+
+        let report = ConversionReport::new(&doc, handler_for(SourceFormat::Djot).as_ref());
+        assert!(report.is_lossless());
+    }
+
+    #[test]
+    fn test_report_structural_and_ui_features() {
+        let source = "--\nopen\n--\n\n\
+                      [%collapsible]\n====\nhidden\n====\n\n\
+                      Term:: definition\n\n\
+                      Press kbd:[Ctrl+S] or btn:[Save], then menu:File[Export] :smile:\n";
+        let config = ParseConfig {
+            emoji_shortcodes: true,
+            ..Default::default()
+        };
+        let doc = handler_for(SourceFormat::AsciiDoc)
+            .parse(source, &config)
+            .unwrap();
+        let used: Vec<_> = used_features(&doc).into_iter().collect();
+        assert_eq!(
+            used,
+            vec![
+                "button",
+                "container",
+                "definition_list",
+                "details",
+                "emoji",
+                "kbd",
+                "menu"
+            ]
+        );
+
+        let report = ConversionReport::new(&doc, handler_for(SourceFormat::Markdown).as_ref());
+        assert_eq!(report.lost_features, vec!["button", "container", "menu"]);
+        let report = ConversionReport::new(&doc, handler_for(SourceFormat::OrgMode).as_ref());
+        assert_eq!(
+            report.lost_features,
+            vec!["button", "definition_list", "kbd", "menu"]
+        );
+    }
+
+    #[test]
+    fn test_report_diagnostics() {
+        let source = "# Title\n\nSome ~~struck~~ text\n\n> Quoted ~~too~~\n";
+        let config = ParseConfig {
+            preserve_spans: true,
+            ..Default::default()
+        };
+        let parser = IncrementalParser::new(source, SourceFormat::Markdown, config);
+        let report = ConversionReport::new(
+            parser.document(),
+            handler_for(SourceFormat::ReStructuredText).as_ref(),
+        );
+
+        let diagnostics = report.diagnostics(parser.document());
+        let lines: Vec<_> = diagnostics.iter().map(|d| d.span.map(|s| s.line)).collect();
+        assert_eq!(lines, vec![Some(3), Some(5)]);
+        assert_eq!(
+            diagnostics[0].message,
+            "strikethrough cannot be represented in RST"
+        );
+        assert_eq!(diagnostics[0].code, LOST_FEATURE_CODE);
+    }
+
+    #[test]
+    fn test_normalize_is_stable() {
+        let input = "Title\n=====\n\nSome *emphasis* and ``code``.\n\n* one\n* two\n";
+        for format in SourceFormat::ALL {
+            let handler = handler_for(format);
+            let once = normalize(handler.as_ref(), input).unwrap();
+            let twice = normalize(handler.as_ref(), &once).unwrap();
+            assert_eq!(once, twice, "{:?} is not stable", format);
+        }
+    }
+}
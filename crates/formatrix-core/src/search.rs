@@ -0,0 +1,413 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Full-text search across a set of documents
+//!
+//! Documents in any supported format are indexed block by block: every
+//! heading, paragraph, caption and code block becomes one searchable unit.
+//! Queries match blocks containing all query terms and are ranked with BM25,
+//! with headings weighted above body text.
+//!
+//! The index lives in memory and can be saved to and loaded from disk as
+//! JSON; term postings are rebuilt on load rather than stored.
+
+use crate::ast::{Block, Document, SourceFormat, Span};
+use crate::file_ops::{format_from_extension, FileError, FileResult};
+use crate::formats::handler_for;
+use crate::toolkit::{inline_text, WordLocator};
+use crate::traits::ParseConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// BM25 term-frequency saturation
+const K1: f32 = 1.2;
+/// BM25 length normalization
+const B: f32 = 0.75;
+/// Score multiplier for heading blocks
+const HEADING_BOOST: f32 = 2.0;
+
+/// One searchable block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedBlock {
+    /// Index path through nested blocks, starting at `Document::content`
+    pub block_path: Vec<usize>,
+    pub text: String,
+    pub heading: bool,
+    /// Location in the source, when the source was indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// A document in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedDocument {
+    pub path: String,
+    pub format: SourceFormat,
+    pub title: Option<String>,
+    pub blocks: Vec<IndexedBlock>,
+}
+
+/// A block matching a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub title: Option<String>,
+    pub block_path: Vec<usize>,
+    /// Full text of the matching block
+    pub text: String,
+    /// Byte ranges of matched terms within `text`
+    pub highlights: Vec<Range<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    pub score: f32,
+}
+
+/// Where a term occurs: (document, block, occurrences in that block)
+type Posting = (usize, usize, u32);
+
+/// Ranked full-text index over a set of documents
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    documents: Vec<IndexedDocument>,
+    #[serde(skip)]
+    postings: HashMap<String, Vec<Posting>>,
+    #[serde(skip)]
+    block_lengths: Vec<Vec<u32>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load an index written by [`SearchIndex::save`]
+    pub fn load(path: impl AsRef<Path>) -> FileResult<Self> {
+        let json = fs::read_to_string(path)?;
+        let mut index: Self =
+            serde_json::from_str(&json).map_err(|e| FileError::Parse(e.to_string()))?;
+        index.rebuild_postings();
+        Ok(index)
+    }
+
+    /// Write the index to disk as JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> FileResult<()> {
+        let json = serde_json::to_string(self).map_err(|e| FileError::Render(e.to_string()))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Indexed documents in the order they were added
+    pub fn documents(&self) -> &[IndexedDocument] {
+        &self.documents
+    }
+
+    /// Index every supported file under `dir`, recursively
+    ///
+    /// Hidden directories are skipped, as are files that fail to read or
+    /// parse. Returns the number of files indexed.
+    pub fn index_dir(&mut self, dir: impl AsRef<Path>) -> FileResult<usize> {
+        let mut count = 0;
+        let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|entry| entry.path());
+
+        for entry in entries {
+            let path = entry.path();
+            if path.is_dir() {
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden {
+                    count += self.index_dir(&path)?;
+                }
+                continue;
+            }
+
+            let Some(format) = format_from_extension(&path) else {
+                continue;
+            };
+            let Ok(source) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(doc) = handler_for(format).parse(&source, &ParseConfig::default()) else {
+                continue;
+            };
+            self.add(path.to_string_lossy(), &doc, Some(&source));
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Add or replace the document at `path`
+    ///
+    /// Pass the text `doc` was parsed from to get block spans in hits.
+    pub fn add(&mut self, path: impl Into<String>, doc: &Document, source: Option<&str>) {
+        let path = path.into();
+        self.remove(&path);
+
+        let mut blocks = Vec::new();
+        let mut locator = source.map(WordLocator::new);
+        collect_blocks(&doc.content, &mut Vec::new(), &mut blocks);
+        for block in &mut blocks {
+            if let Some(locator) = &mut locator {
+                block.span = locate(locator, &block.text);
+            }
+        }
+
+        self.documents.push(IndexedDocument {
+            path,
+            format: doc.source_format,
+            title: doc.meta.title.clone(),
+            blocks,
+        });
+        self.index_document(self.documents.len() - 1);
+    }
+
+    /// Drop the document at `path`; returns whether it was indexed
+    pub fn remove(&mut self, path: &str) -> bool {
+        let before = self.documents.len();
+        self.documents.retain(|d| d.path != path);
+        let removed = self.documents.len() != before;
+        if removed {
+            self.rebuild_postings();
+        }
+        removed
+    }
+
+    /// Blocks containing every term of `query`, best first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let total_blocks: usize = self.block_lengths.iter().map(Vec::len).sum();
+        let avg_len =
+            self.block_lengths.iter().flatten().sum::<u32>() as f32 / total_blocks.max(1) as f32;
+
+        let mut scores: HashMap<(usize, usize), (f32, usize)> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                return Vec::new();
+            };
+            let df = postings.len() as f32;
+            let idf = ((total_blocks as f32 - df + 0.5) / (df + 0.5) + 1.0).ln();
+
+            for &(doc, block, tf) in postings {
+                let len = self.block_lengths[doc][block] as f32;
+                let tf = tf as f32;
+                let score = idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg_len));
+                let entry = scores.entry((doc, block)).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .into_iter()
+            .filter(|(_, (_, matched))| *matched == terms.len())
+            .map(|((doc, block), (score, _))| {
+                let document = &self.documents[doc];
+                let indexed = &document.blocks[block];
+                SearchHit {
+                    path: document.path.clone(),
+                    title: document.title.clone(),
+                    block_path: indexed.block_path.clone(),
+                    text: indexed.text.clone(),
+                    highlights: highlights(&indexed.text, &terms),
+                    span: indexed.span,
+                    score: if indexed.heading {
+                        score * HEADING_BOOST
+                    } else {
+                        score
+                    },
+                }
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.block_path.cmp(&b.block_path))
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    fn rebuild_postings(&mut self) {
+        self.postings.clear();
+        self.block_lengths.clear();
+        for doc in 0..self.documents.len() {
+            self.index_document(doc);
+        }
+    }
+
+    fn index_document(&mut self, doc: usize) {
+        let mut lengths = Vec::new();
+        for (block, indexed) in self.documents[doc].blocks.iter().enumerate() {
+            let terms = tokenize(&indexed.text);
+            lengths.push(terms.len() as u32);
+
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for term in terms {
+                *counts.entry(term).or_default() += 1;
+            }
+            for (term, count) in counts {
+                self.postings
+                    .entry(term)
+                    .or_default()
+                    .push((doc, block, count));
+            }
+        }
+        self.block_lengths.push(lengths);
+    }
+}
+
+/// Lowercased words of `text`
+fn tokenize(text: &str) -> Vec<String> {
+    text.unicode_words().map(|w| w.to_lowercase()).collect()
+}
+
+/// Byte ranges of `terms` within `text`
+fn highlights(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    text.unicode_word_indices()
+        .filter(|(_, word)| terms.contains(&word.to_lowercase()))
+        .map(|(start, word)| start..start + word.len())
+        .collect()
+}
+
+/// Source span from the first to the last word of `text`
+fn locate(locator: &mut WordLocator<'_>, text: &str) -> Option<Span> {
+    let mut span: Option<Span> = None;
+    for word in text.unicode_words() {
+        if let Some(found) = locator.find(word) {
+            match &mut span {
+                Some(span) => span.end = found.end,
+                None => span = Some(found),
+            }
+        }
+    }
+    span
+}
+
+/// Flatten searchable blocks in document order
+fn collect_blocks<'a>(
+    blocks: impl IntoIterator<Item = &'a Block>,
+    path: &mut Vec<usize>,
+    out: &mut Vec<IndexedBlock>,
+) {
+    for (i, block) in blocks.into_iter().enumerate() {
+        path.push(i);
+
+        let text = match block {
+            Block::CodeBlock { content, .. } => content.clone(),
+            _ => block
+                .inline_groups()
+                .into_iter()
+                .map(inline_text)
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        if !text.trim().is_empty() {
+            out.push(IndexedBlock {
+                block_path: path.clone(),
+                text,
+                heading: matches!(block, Block::Heading { .. }),
+                span: None,
+            });
+        }
+
+        // child_blocks flattens list items and table cells, so number
+        // children by their position in that sequence
+        collect_blocks(block.child_blocks(), path, out);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::Parser;
+
+    fn index(files: &[(&str, &str)]) -> SearchIndex {
+        let mut index = SearchIndex::new();
+        for (path, source) in files {
+            let doc = MarkdownHandler::new()
+                .parse(source, &ParseConfig::default())
+                .unwrap();
+            index.add(*path, &doc, Some(source));
+        }
+        index
+    }
+
+    #[test]
+    fn test_ranked_hits() {
+        let index = index(&[
+            ("a.md", "# Intro\n\nTables render in every format.\n"),
+            (
+                "b.md",
+                "# Tables\n\nSee the intro.\n\n```\nlet tables = 1;\n```\n",
+            ),
+        ]);
+
+        let hits = index.search("tables", 10);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(
+            (hits[0].path.as_str(), hits[0].text.as_str()),
+            ("b.md", "Tables")
+        );
+        assert_eq!(hits[0].highlights, vec![0..6]);
+
+        let span = hits
+            .iter()
+            .find(|h| h.path == "a.md")
+            .unwrap()
+            .span
+            .unwrap();
+        assert_eq!((span.line, span.column), (3, 1));
+
+        // Every term must match
+        let hits = index.search("tables format", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].block_path, vec![1]);
+        assert!(index.search("tables missing", 10).is_empty());
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut index = index(&[("a.md", "alpha\n"), ("b.md", "alpha beta\n")]);
+        let doc = MarkdownHandler::new()
+            .parse("gamma\n", &ParseConfig::default())
+            .unwrap();
+        index.add("a.md", &doc, None);
+
+        assert_eq!(index.search("alpha", 10).len(), 1);
+        assert_eq!(index.search("gamma", 10)[0].path, "a.md");
+        assert!(index.remove("b.md"));
+        assert!(index.search("alpha", 10).is_empty());
+    }
+
+    #[test]
+    fn test_index_dir_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.md"), "# Notes\n\nFind me\n").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/more.org"), "* Also find me\n").unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/HEAD.md"), "find\n").unwrap();
+        fs::write(dir.path().join("image.png"), "find").unwrap();
+
+        let mut index = SearchIndex::new();
+        assert_eq!(index.index_dir(dir.path()).unwrap(), 2);
+
+        let saved = dir.path().join("index.json");
+        index.save(&saved).unwrap();
+        let loaded = SearchIndex::load(&saved).unwrap();
+        assert_eq!(loaded.documents().len(), 2);
+        assert_eq!(loaded.search("find", 10).len(), 2);
+    }
+}
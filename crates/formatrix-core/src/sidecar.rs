@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Sidecar files - metadata kept next to a document
+//!
+//! Some metadata has nowhere to go in a document's own syntax: plain text has
+//! no header at all, and custom fields or conversion preferences do not
+//! survive a trip through most formats. A TOML file named
+//! `<file>.formatrix.toml` beside the document holds them instead:
+//!
+//! ```toml
+//! [meta]
+//! title = "Meeting notes"
+//! reviewed = true
+//!
+//! [convert]
+//! format = "dj"
+//! wrap = "80"
+//! ```
+//!
+//! `[meta]` takes the same fields as front matter and is merged into the
+//! document's metadata when it is opened; anything the document sets itself
+//! wins. `[convert]` names a preferred target format (by extension or label)
+//! and passes any other keys through as format options.
+
+use crate::ast::{DocumentMeta, SourceFormat};
+use crate::file_ops::{FileError, FileResult};
+use crate::frontmatter::FrontMatter;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Appended to a document's file name to name its sidecar
+pub const SIDECAR_SUFFIX: &str = ".formatrix.toml";
+
+/// How a document prefers to be converted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionPreferences {
+    /// Target format when none is given
+    pub format: Option<SourceFormat>,
+    /// Format-specific options, as in `ParseConfig::format_options`
+    pub options: BTreeMap<String, String>,
+}
+
+/// Contents of a sidecar file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sidecar {
+    pub meta: FrontMatter,
+    pub convert: ConversionPreferences,
+}
+
+impl Sidecar {
+    /// Whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.meta.is_empty() && self.convert.format.is_none() && self.convert.options.is_empty()
+    }
+
+    /// Parse sidecar TOML; unknown top-level tables are ignored
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let mut table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let meta = match table.remove("meta") {
+            Some(toml::Value::Table(meta)) => FrontMatter::from_toml_table(meta),
+            Some(_) => return Err("`meta` is not a table".to_string()),
+            None => FrontMatter::default(),
+        };
+
+        let mut convert = ConversionPreferences::default();
+        match table.remove("convert") {
+            Some(toml::Value::Table(options)) => {
+                for (key, value) in options {
+                    let text = match value {
+                        toml::Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    if key == "format" {
+                        convert.format = Some(
+                            format_by_name(&text)
+                                .ok_or_else(|| format!("unknown format `{}`", text))?,
+                        );
+                    } else {
+                        convert.options.insert(key, text);
+                    }
+                }
+            }
+            Some(_) => return Err("`convert` is not a table".to_string()),
+            None => {}
+        }
+        Ok(Self { meta, convert })
+    }
+
+    /// Sidecar TOML, with empty tables left out
+    pub fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+        if !self.meta.is_empty() {
+            table.insert(
+                "meta".to_string(),
+                toml::Value::Table(self.meta.to_toml_table()),
+            );
+        }
+        let mut convert: toml::Table = self
+            .convert
+            .options
+            .iter()
+            .map(|(k, v)| (k.clone(), toml::Value::String(v.clone())))
+            .collect();
+        if let Some(format) = self.convert.format {
+            convert.insert(
+                "format".to_string(),
+                toml::Value::String(format.extension().to_string()),
+            );
+        }
+        if !convert.is_empty() {
+            table.insert("convert".to_string(), toml::Value::Table(convert));
+        }
+        table.to_string()
+    }
+
+    /// Fill in `meta` from the sidecar, keeping every field it already has
+    pub fn merge_into(&self, meta: &mut DocumentMeta) {
+        let sidecar = &self.meta;
+        if meta.title.is_none() {
+            meta.title.clone_from(&sidecar.title);
+        }
+        if meta.authors.is_empty() {
+            meta.authors.clone_from(&sidecar.authors);
+        }
+        if meta.date.is_none() {
+            meta.date.clone_from(&sidecar.date);
+        }
+        if meta.modified.is_none() {
+            meta.modified.clone_from(&sidecar.modified);
+        }
+        if meta.language.is_none() {
+            meta.language.clone_from(&sidecar.language);
+        }
+        if meta.direction.is_none() {
+            meta.direction = sidecar.direction;
+        }
+        if meta.keywords.is_empty() {
+            meta.keywords.clone_from(&sidecar.keywords);
+        }
+        for (key, value) in &sidecar.fields {
+            meta.custom
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Where the sidecar of the document at `path` lives
+pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(SIDECAR_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Read the sidecar of the document at `path`, if it has one
+pub fn read_sidecar(path: impl AsRef<Path>) -> FileResult<Option<Sidecar>> {
+    let sidecar = sidecar_path(path);
+    let text = match fs::read_to_string(&sidecar) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    Sidecar::from_toml(&text)
+        .map(Some)
+        .map_err(|e| FileError::Parse(format!("{}: {}", sidecar.display(), e)))
+}
+
+/// Write the sidecar of the document at `path`
+///
+/// An empty sidecar removes the file instead.
+pub fn write_sidecar(path: impl AsRef<Path>, sidecar: &Sidecar) -> FileResult<()> {
+    let target = sidecar_path(path);
+    if sidecar.is_empty() {
+        match fs::remove_file(&target) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => return Ok(()),
+        }
+    }
+    fs::write(target, sidecar.to_toml())?;
+    Ok(())
+}
+
+fn format_by_name(name: &str) -> Option<SourceFormat> {
+    SourceFormat::ALL.into_iter().find(|format| {
+        name.eq_ignore_ascii_case(format.extension()) || name.eq_ignore_ascii_case(format.label())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::MetaValue;
+
+    #[test]
+    fn test_round_trip_and_merge() {
+        let sidecar = Sidecar::from_toml(
+            "[meta]\ntitle = \"Notes\"\nlang = \"fr\"\nreviewed = true\n\n\
+             [convert]\nformat = \"DJOT\"\nwrap = 80\n",
+        )
+        .unwrap();
+        assert_eq!(sidecar.convert.format, Some(SourceFormat::Djot));
+        assert_eq!(sidecar.convert.options["wrap"], "80");
+        assert_eq!(Sidecar::from_toml(&sidecar.to_toml()).unwrap(), sidecar);
+
+        let mut meta = DocumentMeta {
+            title: Some("Own title".to_string()),
+            ..Default::default()
+        };
+        sidecar.merge_into(&mut meta);
+        assert_eq!(meta.title.as_deref(), Some("Own title"));
+        assert_eq!(meta.language.as_deref(), Some("fr"));
+        assert_eq!(meta.custom.get("reviewed"), Some(&MetaValue::Bool(true)));
+
+        assert!(Sidecar::from_toml("[convert]\nformat = \"docx\"\n").is_err());
+    }
+
+    #[test]
+    fn test_read_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let doc = dir.path().join("notes.txt");
+        assert_eq!(
+            sidecar_path(&doc),
+            dir.path().join("notes.txt.formatrix.toml")
+        );
+        assert_eq!(read_sidecar(&doc).unwrap(), None);
+
+        let mut sidecar = Sidecar::default();
+        sidecar.meta.title = Some("Notes".to_string());
+        write_sidecar(&doc, &sidecar).unwrap();
+        assert_eq!(read_sidecar(&doc).unwrap(), Some(sidecar.clone()));
+
+        fs::write(&doc, "Plain text has no header.\n").unwrap();
+        let opened = crate::file_ops::open_file(&doc).unwrap();
+        assert_eq!(opened.document.meta.title.as_deref(), Some("Notes"));
+        assert_eq!(opened.sidecar, Some(sidecar));
+
+        write_sidecar(&doc, &Sidecar::default()).unwrap();
+        assert!(!sidecar_path(&doc).exists());
+    }
+}
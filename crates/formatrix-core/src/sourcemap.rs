@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Source maps between a document and its rendered output
+//!
+//! A [`SourceMap`] records where each block ended up in a renderer's output,
+//! alongside the block's source span when the parser recorded one. A preview
+//! uses it both ways: scrolling the editor finds the output for a source
+//! offset, and clicking in the output finds the source.
+//!
+//! [`Renderer::render_with_source_map`](crate::traits::Renderer::render_with_source_map)
+//! works for every renderer by finding each top-level block's own rendering
+//! in the full output; [`render_html_with_source_map`](crate::html::render_html_with_source_map)
+//! records the ranges as it writes them.
+
+use crate::ast::{Document, DocumentMeta, Span};
+use crate::traits::Result;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Where one block ended up in the output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMapEntry {
+    /// Index path through nested blocks, starting at `Document::content`
+    pub block_path: Vec<usize>,
+    /// Byte range of the block in the output
+    pub output: Range<usize>,
+    /// Source span of the block, when the parser recorded one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Span>,
+}
+
+/// Mapping from blocks to output ranges, in output order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub entries: Vec<SourceMapEntry>,
+}
+
+impl SourceMap {
+    /// The innermost block whose output contains byte `offset`
+    pub fn at_output(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.output.contains(&offset))
+            .max_by_key(|e| e.block_path.len())
+    }
+
+    /// The innermost block whose source span contains byte `offset`
+    pub fn at_source(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.source.is_some_and(|s| (s.start..s.end).contains(&offset)))
+            .max_by_key(|e| e.block_path.len())
+    }
+
+    /// The entry for the block at `path`
+    pub fn entry(&self, path: &[usize]) -> Option<&SourceMapEntry> {
+        self.entries.iter().find(|e| e.block_path == path)
+    }
+
+    /// Move every output range `by` bytes further into the output
+    pub(crate) fn shift(&mut self, by: usize) {
+        for entry in &mut self.entries {
+            entry.output = entry.output.start + by..entry.output.end + by;
+        }
+    }
+
+    /// Map the top-level blocks of `doc` into `output` by rendering each on
+    /// its own and finding the result, in order
+    ///
+    /// Blocks whose own rendering does not appear verbatim, because the
+    /// renderer depends on surrounding blocks, are left out.
+    pub(crate) fn locate(
+        output: &str,
+        doc: &Document,
+        render: impl Fn(&Document) -> Result<String>,
+    ) -> Self {
+        let mut entries = Vec::new();
+        let mut cursor = 0;
+        for (index, block) in doc.content.iter().enumerate() {
+            let single = Document {
+                source_format: doc.source_format,
+                meta: DocumentMeta::default(),
+                content: vec![block.clone()],
+                raw_source: None,
+            };
+            let Ok(rendered) = render(&single) else {
+                continue;
+            };
+            let rendered = rendered.trim();
+            if rendered.is_empty() {
+                continue;
+            }
+            if let Some(found) = output[cursor..].find(rendered) {
+                let start = cursor + found;
+                cursor = start + rendered.len();
+                entries.push(SourceMapEntry {
+                    block_path: vec![index],
+                    output: start..cursor,
+                    source: block.span(),
+                });
+            }
+        }
+        Self { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::SourceFormat;
+    use crate::formats::MarkdownHandler;
+    use crate::incremental::IncrementalParser;
+    use crate::traits::{ParseConfig, RenderConfig, Renderer};
+
+    #[test]
+    fn test_render_with_source_map() {
+        let source = "# Title\n\nSome *text*.\n\n- one\n- two\n";
+        let config = ParseConfig {
+            preserve_spans: true,
+            ..Default::default()
+        };
+        let parser = IncrementalParser::new(source, SourceFormat::Markdown, config);
+        let (output, map) = MarkdownHandler::new()
+            .render_with_source_map(parser.document(), &RenderConfig::default())
+            .unwrap();
+
+        assert_eq!(map.entries.len(), 3);
+        assert_eq!(&output[map.entries[1].output.clone()], "Some *text*.");
+        assert_eq!(&output[map.entries[2].output.clone()], "- one\n- two");
+
+        // Click in the output, land in the source, and back again
+        let clicked = map.at_output(output.find("two").unwrap()).unwrap();
+        assert_eq!(clicked.block_path, vec![2]);
+        let span = clicked.source.unwrap();
+        assert_eq!(&source[span.start..span.end], "- one\n- two\n");
+        assert_eq!(
+            map.at_source(source.find("text").unwrap())
+                .unwrap()
+                .block_path,
+            vec![1]
+        );
+        assert!(map.at_output(output.len()).is_none());
+    }
+}
@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Templates for new documents
+//!
+//! Each built-in template declares the fields it needs and builds a format-
+//! neutral [`Document`], so the same template can start a file in any
+//! supported format. Authors and dates go into [`DocumentMeta`]; other fields
+//! are kept in `meta.custom` as well as appearing in the body.
+
+use crate::ast::{
+    Author, Block, Document, DocumentMeta, Inline, ListItem, ListKind, MetaValue, SourceFormat,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unknown template: {0}")]
+    UnknownTemplate(String),
+
+    #[error("Missing required field: {0}")]
+    MissingField(String),
+}
+
+/// A value a template asks for
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateField {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub required: bool,
+    /// Used when the field is optional and left empty
+    pub default: Option<&'static str>,
+}
+
+/// A built-in document template
+#[derive(Debug, Clone, Serialize)]
+pub struct Template {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub fields: &'static [TemplateField],
+    #[serde(skip)]
+    build: fn(&Values<'_>) -> Vec<Block>,
+}
+
+impl Template {
+    /// Build a document in `format` from the given field values
+    pub fn instantiate(
+        &self,
+        values: &HashMap<String, String>,
+        format: SourceFormat,
+    ) -> Result<Document, TemplateError> {
+        let mut resolved = HashMap::new();
+        for field in self.fields {
+            let value = values
+                .get(field.name)
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .or(field.default);
+            match value {
+                Some(value) => {
+                    resolved.insert(field.name, value);
+                }
+                None if field.required => {
+                    return Err(TemplateError::MissingField(field.name.to_string()))
+                }
+                None => {}
+            }
+        }
+        let values = Values(resolved);
+
+        // The title is the first heading rather than `meta.title`, which only
+        // some renderers emit
+        let mut meta = DocumentMeta {
+            authors: values
+                .list("author")
+                .into_iter()
+                .map(Author::from)
+                .collect(),
+            date: values.get("date").map(str::to_string),
+            ..Default::default()
+        };
+        for (name, value) in &values.0 {
+            if !matches!(*name, "title" | "author" | "date") {
+                meta.custom
+                    .insert(name.to_string(), MetaValue::String(value.to_string()));
+            }
+        }
+
+        Ok(Document {
+            source_format: format,
+            meta,
+            content: (self.build)(&values),
+            raw_source: None,
+        })
+    }
+}
+
+/// Resolved field values
+struct Values<'a>(HashMap<&'static str, &'a str>);
+
+impl Values<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).copied()
+    }
+
+    /// Value of a required field or one with a default
+    fn value(&self, name: &str) -> &str {
+        self.get(name).unwrap_or_default()
+    }
+
+    /// Comma-separated field split into trimmed entries
+    fn list(&self, name: &str) -> Vec<String> {
+        self.get(name)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// All built-in templates
+pub fn builtin_templates() -> &'static [Template] {
+    BUILTIN
+}
+
+/// Look up a built-in template by id
+pub fn template(id: &str) -> Result<&'static Template, TemplateError> {
+    BUILTIN
+        .iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| TemplateError::UnknownTemplate(id.to_string()))
+}
+
+const fn field(
+    name: &'static str,
+    label: &'static str,
+    required: bool,
+    default: Option<&'static str>,
+) -> TemplateField {
+    TemplateField {
+        name,
+        label,
+        required,
+        default,
+    }
+}
+
+static BUILTIN: &[Template] = &[
+    Template {
+        id: "readme",
+        name: "README",
+        description: "Project overview with installation and usage sections",
+        fields: &[
+            field("title", "Project name", true, None),
+            field(
+                "description",
+                "One-line description",
+                false,
+                Some("A short description of the project."),
+            ),
+            field("license", "License", false, Some("MPL-2.0")),
+        ],
+        build: readme,
+    },
+    Template {
+        id: "adr",
+        name: "Architecture decision record",
+        description: "Context, decision and consequences of one design choice",
+        fields: &[
+            field("number", "Number", true, None),
+            field("title", "Decision", true, None),
+            field("status", "Status", false, Some("Proposed")),
+            field("date", "Date", false, None),
+        ],
+        build: adr,
+    },
+    Template {
+        id: "meeting-notes",
+        name: "Meeting notes",
+        description: "Attendees, agenda, notes and action items",
+        fields: &[
+            field("title", "Meeting", false, Some("Meeting notes")),
+            field("date", "Date", true, None),
+            field("attendees", "Attendees (comma-separated)", false, None),
+        ],
+        build: meeting_notes,
+    },
+    Template {
+        id: "rfc",
+        name: "RFC",
+        description: "Proposal with motivation, design and alternatives",
+        fields: &[
+            field("title", "Title", true, None),
+            field("author", "Authors (comma-separated)", true, None),
+            field("status", "Status", false, Some("Draft")),
+            field("date", "Date", false, None),
+        ],
+        build: rfc,
+    },
+];
+
+fn text(content: &str) -> Inline {
+    Inline::Text {
+        content: content.to_string(),
+    }
+}
+
+fn heading(level: u8, content: &str) -> Block {
+    Block::Heading {
+        level,
+        content: vec![text(content)],
+        id: None,
+        classes: Vec::new(),
+        span: None,
+    }
+}
+
+fn paragraph(content: &str) -> Block {
+    Block::Paragraph {
+        content: vec![text(content)],
+        span: None,
+    }
+}
+
+/// Paragraph of `label: value` lines
+fn fields_paragraph(fields: &[(&str, Option<&str>)]) -> Block {
+    let mut content = Vec::new();
+    for (label, value) in fields {
+        let Some(value) = value else { continue };
+        if !content.is_empty() {
+            content.push(Inline::LineBreak);
+        }
+        content.push(Inline::Strong {
+            content: vec![text(&format!("{}:", label))],
+        });
+        content.push(text(&format!(" {}", value)));
+    }
+    Block::Paragraph {
+        content,
+        span: None,
+    }
+}
+
+fn list(kind: ListKind, items: &[&str]) -> Block {
+    Block::List {
+        kind,
+        items: items
+            .iter()
+            .map(|item| ListItem {
+                content: vec![paragraph(item)],
+                checked: (kind == ListKind::Task).then_some(false),
+                marker: None,
+            })
+            .collect(),
+        start: (kind == ListKind::Ordered).then_some(1),
+        span: None,
+    }
+}
+
+fn readme(values: &Values<'_>) -> Vec<Block> {
+    vec![
+        heading(1, values.value("title")),
+        paragraph(values.value("description")),
+        heading(2, "Installation"),
+        paragraph("Describe how to install the project."),
+        heading(2, "Usage"),
+        paragraph("Show the most common way to use it."),
+        heading(2, "Contributing"),
+        paragraph("Explain how to report issues and submit changes."),
+        heading(2, "License"),
+        paragraph(&format!("Licensed under {}.", values.value("license"))),
+    ]
+}
+
+fn adr(values: &Values<'_>) -> Vec<Block> {
+    vec![
+        heading(
+            1,
+            &format!("ADR {}: {}", values.value("number"), values.value("title")),
+        ),
+        fields_paragraph(&[
+            ("Status", values.get("status")),
+            ("Date", values.get("date")),
+        ]),
+        heading(2, "Context"),
+        paragraph("What is the issue that motivates this decision?"),
+        heading(2, "Decision"),
+        paragraph("What change is being proposed or has been agreed?"),
+        heading(2, "Consequences"),
+        paragraph("What becomes easier or harder because of this change?"),
+    ]
+}
+
+fn meeting_notes(values: &Values<'_>) -> Vec<Block> {
+    let attendees = values.list("attendees");
+    let attendees: Vec<&str> = if attendees.is_empty() {
+        vec!["Name"]
+    } else {
+        attendees.iter().map(String::as_str).collect()
+    };
+
+    vec![
+        heading(1, values.value("title")),
+        fields_paragraph(&[("Date", values.get("date"))]),
+        heading(2, "Attendees"),
+        list(ListKind::Bullet, &attendees),
+        heading(2, "Agenda"),
+        list(ListKind::Ordered, &["Topic"]),
+        heading(2, "Notes"),
+        paragraph("Discussion and decisions."),
+        heading(2, "Action items"),
+        list(ListKind::Task, &["Owner: task"]),
+    ]
+}
+
+fn rfc(values: &Values<'_>) -> Vec<Block> {
+    vec![
+        heading(1, &format!("RFC: {}", values.value("title"))),
+        fields_paragraph(&[
+            ("Authors", values.get("author")),
+            ("Status", values.get("status")),
+            ("Date", values.get("date")),
+        ]),
+        heading(2, "Summary"),
+        paragraph("One paragraph explanation of the proposal."),
+        heading(2, "Motivation"),
+        paragraph("Why are we doing this? What problems does it solve?"),
+        heading(2, "Detailed design"),
+        paragraph("Explain the design in enough detail to implement it."),
+        heading(2, "Drawbacks"),
+        paragraph("Why should we not do this?"),
+        heading(2, "Alternatives"),
+        paragraph("What other designs were considered, and why were they rejected?"),
+        heading(2, "Unresolved questions"),
+        paragraph("What needs to be settled before this is accepted?"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::handler_for;
+    use crate::traits::RenderConfig;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_template_renders_in_every_format() {
+        let values = values(&[
+            ("title", "Formatrix"),
+            ("number", "7"),
+            ("date", "2026-01-01"),
+            ("author", "Ada, Grace"),
+        ]);
+        for template in builtin_templates() {
+            for format in SourceFormat::ALL {
+                let doc = template.instantiate(&values, format).unwrap();
+                let output = handler_for(format)
+                    .render(&doc, &RenderConfig::default())
+                    .unwrap();
+                assert!(
+                    output.contains("Formatrix") || output.contains("Meeting notes"),
+                    "{} in {:?}",
+                    template.id,
+                    format
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fields_and_defaults() {
+        let adr = template("adr").unwrap();
+        assert_eq!(
+            adr.instantiate(&values(&[("number", "3")]), SourceFormat::Markdown)
+                .unwrap_err(),
+            TemplateError::MissingField("title".to_string())
+        );
+
+        let doc = adr
+            .instantiate(
+                &values(&[("number", "3"), ("title", "Use Djot"), ("date", "")]),
+                SourceFormat::Markdown,
+            )
+            .unwrap();
+        assert_eq!(doc.meta.date, None);
+        assert!(matches!(
+            doc.meta.custom.get("status"),
+            Some(MetaValue::String(s)) if s == "Proposed"
+        ));
+
+        let rfc = template("rfc")
+            .unwrap()
+            .instantiate(
+                &values(&[("title", "Tabs"), ("author", "Ada, Grace")]),
+                SourceFormat::Djot,
+            )
+            .unwrap();
+        assert_eq!(
+            rfc.meta.authors,
+            vec![Author::from("Ada"), Author::from("Grace")]
+        );
+
+        assert_eq!(
+            template("memo").unwrap_err(),
+            TemplateError::UnknownTemplate("memo".to_string())
+        );
+    }
+}
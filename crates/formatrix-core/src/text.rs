@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Plain text of the document tree
+//!
+//! [`Document::plain_text`] and [`Block::text_content`] flatten content to
+//! a string for search, statistics, spell checking and lint rules. Options
+//! choose the separators and leave out code, math, alt text or raw markup;
+//! formatting such as emphasis and links keeps only its text.
+
+use crate::ast::{Block, Document, Inline, QuoteType};
+use serde::{Deserialize, Serialize};
+
+/// What plain text includes and how it is joined
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextOptions {
+    /// Between blocks, including nested ones such as list items
+    pub block_separator: String,
+    /// For soft and hard line breaks within a block
+    pub line_break: String,
+    /// Leave out inline code and code blocks
+    pub skip_code: bool,
+    /// Leave out inline math and math blocks
+    pub skip_math: bool,
+    /// Leave out the alt text of images
+    pub skip_alt_text: bool,
+    /// Keep raw blocks and inlines, markup passed through from the source
+    pub include_raw: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            block_separator: "\n\n".to_string(),
+            line_break: " ".to_string(),
+            skip_code: false,
+            skip_math: false,
+            skip_alt_text: false,
+            include_raw: false,
+        }
+    }
+}
+
+impl Document {
+    /// Text of the document's blocks, without its metadata
+    pub fn plain_text(&self, options: &TextOptions) -> String {
+        join_blocks(&self.content, options)
+    }
+}
+
+impl Block {
+    /// Text of this block and the blocks inside it
+    ///
+    /// The block's own inline groups, such as a heading or a table caption,
+    /// come first, joined by spaces, then nested blocks in document order.
+    pub fn text_content(&self, options: &TextOptions) -> String {
+        let own = match self {
+            Block::CodeBlock { content, .. } if !options.skip_code => content.clone(),
+            Block::MathBlock { content, .. } if !options.skip_math => content.clone(),
+            Block::Raw { content, .. } if options.include_raw => content.clone(),
+            _ => self
+                .inline_groups()
+                .into_iter()
+                .map(|group| inlines_text(group, options))
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        std::iter::once(own)
+            .chain(
+                self.child_blocks()
+                    .into_iter()
+                    .map(|block| block.text_content(options)),
+            )
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(&options.block_separator)
+    }
+}
+
+/// Text of a run of inlines
+pub fn inlines_text<'a>(
+    inlines: impl IntoIterator<Item = &'a Inline>,
+    options: &TextOptions,
+) -> String {
+    let mut text = String::new();
+    push_inlines(&mut text, inlines, options);
+    text
+}
+
+fn join_blocks(blocks: &[Block], options: &TextOptions) -> String {
+    blocks
+        .iter()
+        .map(|block| block.text_content(options))
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(&options.block_separator)
+}
+
+fn push_inlines<'a>(
+    text: &mut String,
+    inlines: impl IntoIterator<Item = &'a Inline>,
+    options: &TextOptions,
+) {
+    for inline in inlines {
+        match inline {
+            Inline::Text { content }
+            | Inline::Keyboard { content }
+            | Inline::Button { content } => text.push_str(content),
+            Inline::Menu { items } => text.push_str(&items.join(" > ")),
+            Inline::Code { content, .. } if !options.skip_code => text.push_str(content),
+            Inline::Math { content, .. } if !options.skip_math => text.push_str(content),
+            Inline::RawInline { content, .. } if options.include_raw => text.push_str(content),
+            Inline::Image { alt, .. } if !options.skip_alt_text => text.push_str(alt),
+            Inline::SoftBreak | Inline::LineBreak => text.push_str(&options.line_break),
+            Inline::NonBreakingSpace => text.push('\u{a0}'),
+            Inline::Quoted {
+                quote_type,
+                content,
+            } => {
+                let quote = match quote_type {
+                    QuoteType::Single => '\'',
+                    QuoteType::Double => '"',
+                };
+                text.push(quote);
+                push_inlines(text, content, options);
+                text.push(quote);
+            }
+            other => push_inlines(text, other.children(), options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser};
+
+    #[test]
+    fn test_plain_text() {
+        let doc = MarkdownHandler::new()
+            .parse(
+                "# Title\n\nSome *styled*\ntext with `code` and ![a cat](cat.png).\n\n\
+                 - one\n- two\n\n```\nlet x;\n```\n\n<div>raw</div>\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            doc.plain_text(&TextOptions::default()),
+            "Title\n\nSome styled text with code and a cat.\n\none\n\ntwo\n\nlet x;\n"
+        );
+
+        let options = TextOptions {
+            block_separator: "\n".to_string(),
+            skip_code: true,
+            skip_alt_text: true,
+            include_raw: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.plain_text(&options),
+            "Title\nSome styled text with  and .\none\ntwo\n<div>raw</div>\n"
+        );
+        assert_eq!(doc.content[2].text_content(&options), "one\ntwo");
+    }
+}
@@ -0,0 +1,568 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Link extraction and dead-link checking
+//!
+//! Collects every `Inline::Link` and `Inline::Image` target in a document and
+//! validates what can be validated offline: intra-document anchors and local
+//! file targets. Remote (http/https) links are left unchecked unless the
+//! `link-check` feature is enabled, in which case [`check_remote_links`]
+//! probes them concurrently.
+
+use super::inline_text;
+use crate::ast::{Block, Document, Inline, Span};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Whether a target came from a link or an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Link,
+    Image,
+}
+
+/// Where a link was found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkLocation {
+    /// Index path through nested blocks, starting at `Document::content`
+    pub block_path: Vec<usize>,
+    /// Source span of the enclosing block, when the parser recorded one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// A link or image target extracted from a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractedLink {
+    pub url: String,
+    pub kind: LinkKind,
+    pub location: LinkLocation,
+}
+
+/// Classification of a link target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LinkTarget {
+    /// `#anchor` within the same document
+    Anchor { id: String },
+    /// Relative or absolute filesystem path, with optional `#fragment`
+    LocalFile {
+        path: String,
+        fragment: Option<String>,
+    },
+    /// http or https URL
+    Remote,
+    /// Any other scheme (mailto:, data:, tel:, ...)
+    Other,
+}
+
+/// Result of checking a single link
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LinkStatus {
+    /// Target exists
+    Valid,
+    /// Target is missing or unreachable
+    Broken { reason: String },
+    /// Target was not checked (remote link, or no base directory known)
+    Unchecked,
+}
+
+/// A link together with its check result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkCheck {
+    pub link: ExtractedLink,
+    pub target: LinkTarget,
+    pub status: LinkStatus,
+}
+
+impl LinkCheck {
+    /// Whether this link was found to be broken
+    pub fn is_broken(&self) -> bool {
+        matches!(self.status, LinkStatus::Broken { .. })
+    }
+}
+
+/// Extract every link and image target in document order
+pub fn extract_links(doc: &Document) -> Vec<ExtractedLink> {
+    let mut links = Vec::new();
+    let mut path = Vec::new();
+    for (i, block) in doc.content.iter().enumerate() {
+        path.push(i);
+        collect_block(block, &mut path, &mut links);
+        path.pop();
+    }
+    links
+}
+
+fn collect_block(block: &Block, path: &mut Vec<usize>, out: &mut Vec<ExtractedLink>) {
+    for group in block.inline_groups() {
+        for inline in group {
+            collect_inline(inline, block, path, out);
+        }
+    }
+    for (i, child) in block.child_blocks().into_iter().enumerate() {
+        path.push(i);
+        collect_block(child, path, out);
+        path.pop();
+    }
+}
+
+fn collect_inline(inline: &Inline, block: &Block, path: &[usize], out: &mut Vec<ExtractedLink>) {
+    let kind = match inline {
+        Inline::Link { url, .. } => Some((url, LinkKind::Link)),
+        Inline::Image { url, .. } => Some((url, LinkKind::Image)),
+        _ => None,
+    };
+    if let Some((url, kind)) = kind {
+        out.push(ExtractedLink {
+            url: url.clone(),
+            kind,
+            location: LinkLocation {
+                block_path: path.to_vec(),
+                span: block.span(),
+            },
+        });
+    }
+    for child in inline.children() {
+        collect_inline(child, block, path, out);
+    }
+}
+
+/// Classify a link target by its URL
+pub fn classify(url: &str) -> LinkTarget {
+    if let Some(id) = url.strip_prefix('#') {
+        return LinkTarget::Anchor { id: id.to_string() };
+    }
+    let lower = url.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return LinkTarget::Remote;
+    }
+    if has_scheme(url) {
+        return LinkTarget::Other;
+    }
+
+    let without_query = url.split('?').next().unwrap_or(url);
+    let (path, fragment) = match without_query.split_once('#') {
+        Some((p, f)) => (p, Some(f.to_string())),
+        None => (without_query, None),
+    };
+    LinkTarget::LocalFile {
+        path: percent_decode(path),
+        fragment,
+    }
+}
+
+/// True for `scheme:...` URLs (but not Windows drive letters like `C:\`)
+fn has_scheme(url: &str) -> bool {
+    match url.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = input
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// GitHub-style anchor slug for heading text
+///
+/// Lowercases, drops punctuation other than `-` and `_`, and turns spaces into `-`.
+pub fn heading_slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c.to_lowercase().collect::<String>())
+            } else if c.is_whitespace() {
+                Some("-".to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Collect all anchor targets defined in a document
+///
+/// Includes explicit heading, container, figure and span ids, plus the
+/// generated slug of every heading.
+pub fn collect_anchors(doc: &Document) -> HashSet<String> {
+    let mut anchors = HashSet::new();
+    for block in &doc.content {
+        collect_block_anchors(block, &mut anchors);
+    }
+    anchors
+}
+
+fn collect_block_anchors(block: &Block, anchors: &mut HashSet<String>) {
+    match block {
+        Block::Heading { content, id, .. } => {
+            if let Some(id) = id {
+                anchors.insert(id.clone());
+            }
+            anchors.insert(heading_slug(&inline_text(content)));
+        }
+        Block::Container { id: Some(id), .. } | Block::Figure { id: Some(id), .. } => {
+            anchors.insert(id.clone());
+        }
+        _ => {}
+    }
+    for group in block.inline_groups() {
+        for inline in group {
+            collect_inline_anchors(inline, anchors);
+        }
+    }
+    for child in block.child_blocks() {
+        collect_block_anchors(child, anchors);
+    }
+}
+
+fn collect_inline_anchors(inline: &Inline, anchors: &mut HashSet<String>) {
+    if let Inline::Span { id: Some(id), .. } = inline {
+        anchors.insert(id.clone());
+    }
+    for child in inline.children() {
+        collect_inline_anchors(child, anchors);
+    }
+}
+
+/// Call `f` on the target of every link in `doc`, in document order
+///
+/// Stops and returns `None` as soon as `f` does.
+pub fn visit_link_urls_mut(
+    doc: &mut Document,
+    f: &mut dyn FnMut(&mut String) -> Option<()>,
+) -> Option<()> {
+    visit_urls_mut(doc, LinkKind::Link, f)
+}
+
+/// Call `f` on the source of every image in `doc`, in document order
+///
+/// Stops and returns `None` as soon as `f` does.
+pub fn visit_image_urls_mut(
+    doc: &mut Document,
+    f: &mut dyn FnMut(&mut String) -> Option<()>,
+) -> Option<()> {
+    visit_urls_mut(doc, LinkKind::Image, f)
+}
+
+fn visit_urls_mut(
+    doc: &mut Document,
+    kind: LinkKind,
+    f: &mut dyn FnMut(&mut String) -> Option<()>,
+) -> Option<()> {
+    fn visit_inline(
+        inline: &mut Inline,
+        kind: LinkKind,
+        f: &mut dyn FnMut(&mut String) -> Option<()>,
+    ) -> Option<()> {
+        match (&mut *inline, kind) {
+            (Inline::Link { url, .. }, LinkKind::Link)
+            | (Inline::Image { url, .. }, LinkKind::Image) => f(url)?,
+            _ => {}
+        }
+        for child in inline.children_mut() {
+            visit_inline(child, kind, f)?;
+        }
+        Some(())
+    }
+
+    fn visit_block(
+        block: &mut Block,
+        kind: LinkKind,
+        f: &mut dyn FnMut(&mut String) -> Option<()>,
+    ) -> Option<()> {
+        for group in block.inline_groups_mut() {
+            for inline in group {
+                visit_inline(inline, kind, f)?;
+            }
+        }
+        for child in block.child_blocks_mut() {
+            visit_block(child, kind, f)?;
+        }
+        Some(())
+    }
+
+    for block in &mut doc.content {
+        visit_block(block, kind, f)?;
+    }
+    Some(())
+}
+
+/// Extract and check every link in a document
+///
+/// Anchors are validated against the document's headings and ids. Local file
+/// targets are resolved against `base_dir` (usually the document's directory);
+/// without one they are reported as [`LinkStatus::Unchecked`]. Remote links are
+/// always `Unchecked` here — see [`check_remote_links`].
+pub fn check_links(doc: &Document, base_dir: Option<&Path>) -> Vec<LinkCheck> {
+    let anchors = collect_anchors(doc);
+    extract_links(doc)
+        .into_iter()
+        .map(|link| {
+            let target = classify(&link.url);
+            let status = match &target {
+                LinkTarget::Anchor { id } => check_anchor(id, &anchors),
+                LinkTarget::LocalFile { path, .. } => match base_dir {
+                    Some(dir) => check_local_file(dir, path),
+                    None => LinkStatus::Unchecked,
+                },
+                LinkTarget::Remote => LinkStatus::Unchecked,
+                LinkTarget::Other => LinkStatus::Valid,
+            };
+            LinkCheck {
+                link,
+                target,
+                status,
+            }
+        })
+        .collect()
+}
+
+fn check_anchor(id: &str, anchors: &HashSet<String>) -> LinkStatus {
+    if id.is_empty() || anchors.contains(id) {
+        LinkStatus::Valid
+    } else {
+        LinkStatus::Broken {
+            reason: format!("no anchor '#{}' in document", id),
+        }
+    }
+}
+
+fn check_local_file(base_dir: &Path, target: &str) -> LinkStatus {
+    if target.is_empty() {
+        return LinkStatus::Valid;
+    }
+    let path = PathBuf::from(target);
+    let resolved = if path.is_absolute() {
+        path
+    } else {
+        base_dir.join(path)
+    };
+    if resolved.exists() {
+        LinkStatus::Valid
+    } else {
+        LinkStatus::Broken {
+            reason: format!("file not found: {}", resolved.display()),
+        }
+    }
+}
+
+/// Probe remote links over HTTP, updating their status in place
+///
+/// Only checks entries whose target is [`LinkTarget::Remote`]. Each distinct
+/// URL is requested once (HEAD, falling back to GET when the server rejects
+/// HEAD), with up to `concurrency` requests in flight.
+#[cfg(feature = "link-check")]
+pub fn check_remote_links(
+    checks: &mut [LinkCheck],
+    timeout: std::time::Duration,
+    concurrency: usize,
+) {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    let mut urls: Vec<String> = checks
+        .iter()
+        .filter(|c| c.target == LinkTarget::Remote)
+        .map(|c| c.link.url.clone())
+        .collect();
+    urls.sort();
+    urls.dedup();
+    if urls.is_empty() {
+        return;
+    }
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let queue = Mutex::new(urls.into_iter());
+    let results = Mutex::new(HashMap::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.max(1) {
+            scope.spawn(|| loop {
+                let next = queue.lock().ok().and_then(|mut q| q.next());
+                let Some(url) = next else { break };
+                let status = probe_url(&agent, &url);
+                if let Ok(mut r) = results.lock() {
+                    r.insert(url, status);
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap_or_default();
+    for check in checks.iter_mut() {
+        if let Some(status) = results.get(&check.link.url) {
+            check.status = status.clone();
+        }
+    }
+}
+
+#[cfg(feature = "link-check")]
+fn probe_url(agent: &ureq::Agent, url: &str) -> LinkStatus {
+    let status = match agent.head(url).call() {
+        Ok(resp) if resp.status().as_u16() == 405 || resp.status().as_u16() == 501 => {
+            agent.get(url).call().map(|r| r.status().as_u16())
+        }
+        Ok(resp) => Ok(resp.status().as_u16()),
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok(code) if code < 400 => LinkStatus::Valid,
+        Ok(code) => LinkStatus::Broken {
+            reason: format!("HTTP {}", code),
+        },
+        Err(e) => LinkStatus::Broken {
+            reason: e.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{LinkType, SourceFormat};
+
+    fn link(url: &str) -> Inline {
+        Inline::Link {
+            url: url.to_string(),
+            title: None,
+            content: vec![Inline::Text {
+                content: "here".to_string(),
+            }],
+            link_type: LinkType::Inline,
+        }
+    }
+
+    fn doc_with(content: Vec<Block>) -> Document {
+        Document {
+            content,
+            ..Document::new(SourceFormat::Markdown)
+        }
+    }
+
+    #[test]
+    fn test_extract_nested_links_and_images() {
+        let doc = doc_with(vec![Block::BlockQuote {
+            content: vec![Block::Paragraph {
+                content: vec![
+                    Inline::Emphasis {
+                        content: vec![link("https://example.com")],
+                    },
+                    Inline::Image {
+                        url: "img/logo.png".to_string(),
+                        alt: "logo".to_string(),
+                        title: None,
+                        width: None,
+                        height: None,
+                    },
+                ],
+                span: None,
+            }],
+            attribution: None,
+            admonition: None,
+            span: None,
+        }]);
+
+        let links = extract_links(&doc);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].kind, LinkKind::Link);
+        assert_eq!(links[0].location.block_path, vec![0, 0]);
+        assert_eq!(links[1].kind, LinkKind::Image);
+        assert_eq!(links[1].url, "img/logo.png");
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("#intro"),
+            LinkTarget::Anchor {
+                id: "intro".to_string()
+            }
+        );
+        assert_eq!(classify("HTTPS://example.com"), LinkTarget::Remote);
+        assert_eq!(classify("mailto:me@example.com"), LinkTarget::Other);
+        assert_eq!(
+            classify("docs/my%20file.md#setup"),
+            LinkTarget::LocalFile {
+                path: "docs/my file.md".to_string(),
+                fragment: Some("setup".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_anchors() {
+        let doc = doc_with(vec![
+            Block::Heading {
+                level: 2,
+                content: vec![Inline::Text {
+                    content: "Getting Started!".to_string(),
+                }],
+                id: None,
+                classes: Vec::new(),
+                span: None,
+            },
+            Block::Paragraph {
+                content: vec![link("#getting-started"), link("#missing")],
+                span: None,
+            },
+        ]);
+
+        let checks = check_links(&doc, None);
+        assert_eq!(checks[0].status, LinkStatus::Valid);
+        assert!(checks[1].is_broken());
+    }
+
+    #[test]
+    fn test_check_local_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("exists.md"), "# Hi").unwrap();
+
+        let doc = doc_with(vec![Block::Paragraph {
+            content: vec![
+                link("exists.md"),
+                link("gone.md"),
+                link("https://example.com"),
+            ],
+            span: None,
+        }]);
+
+        let checks = check_links(&doc, Some(dir.path()));
+        assert_eq!(checks[0].status, LinkStatus::Valid);
+        assert!(checks[1].is_broken());
+        assert_eq!(checks[2].status, LinkStatus::Unchecked);
+
+        let unchecked = check_links(&doc, None);
+        assert_eq!(unchecked[1].status, LinkStatus::Unchecked);
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Document toolkit - analysis utilities that operate on the unified AST
+//!
+//! Everything here is format-neutral: tools take a parsed [`Document`](crate::ast::Document)
+//! and never look at the source text directly.
+
+pub mod links;
+pub mod sanitize;
+#[cfg(feature = "spellcheck")]
+pub mod spell;
+
+use crate::ast::{Inline, Span};
+
+/// Flatten inline content to plain text
+pub(crate) fn inline_text<'a>(inlines: impl IntoIterator<Item = &'a Inline>) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text { content } | Inline::Code { content, .. } => text.push_str(content),
+            Inline::SoftBreak | Inline::LineBreak | Inline::NonBreakingSpace => text.push(' '),
+            other => text.push_str(&inline_text(other.children())),
+        }
+    }
+    text
+}
+
+/// Finds successive words of a parsed document in its source text
+///
+/// Parsers do not record inline positions, so tools recover them by scanning
+/// forward through the source in document order.
+pub(crate) struct WordLocator<'a> {
+    source: &'a str,
+    offset: usize,
+    /// Line number and start offset of the line containing `offset`
+    line: u32,
+    line_start: usize,
+}
+
+impl<'a> WordLocator<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    /// Next whole-word occurrence of `word` at or after the cursor
+    pub(crate) fn find(&mut self, word: &str) -> Option<Span> {
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '\'' || c == '’';
+        let mut from = self.offset;
+
+        while let Some(pos) = self.source[from..].find(word) {
+            let start = from + pos;
+            let end = start + word.len();
+            let before = self.source[..start].chars().next_back();
+            let after = self.source[end..].chars().next();
+            if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+                for (i, _) in self.source[self.offset..start].match_indices('\n') {
+                    self.line += 1;
+                    self.line_start = self.offset + i + 1;
+                }
+                self.offset = end;
+                return Some(Span {
+                    start,
+                    end,
+                    line: self.line,
+                    column: self.source[self.line_start..start].chars().count() as u32 + 1,
+                });
+            }
+            from = end;
+        }
+        None
+    }
+}
@@ -0,0 +1,602 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Raw HTML sanitizing
+//!
+//! Raw blocks and inlines are passed through to the output untouched, so a
+//! document opened from an untrusted source can carry scripts, event handlers
+//! and `javascript:` URLs into an HTML rendering or a preview pane.
+//! [`sanitize`] rewrites every `Block::Raw` and `Inline::RawInline` so that
+//! only allowlisted tags and attributes survive. It also holds links and
+//! images to the allowed URL schemes, and drops comments, whose content is
+//! written out as it is.
+//!
+//! Parsers record raw content under the source format rather than as HTML, so
+//! all raw content is treated as HTML here.
+
+use crate::ast::{Block, Document, Inline};
+use crate::edit::children_mut;
+use crate::normalize::block_lists_mut;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// What happens to a tag that is not allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SanitizeMode {
+    /// Remove the tag; for script-like elements, remove their content too
+    #[default]
+    Strip,
+    /// Escape the tag so it shows as text
+    Escape,
+}
+
+/// Allowlist for [`sanitize`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SanitizeConfig {
+    pub mode: SanitizeMode,
+    /// Tag names kept, lowercase
+    pub allowed_tags: HashSet<String>,
+    /// Attribute names kept on allowed tags, lowercase; `on*` handlers are
+    /// always removed
+    pub allowed_attributes: HashSet<String>,
+    /// URL schemes allowed in `href` and `src`; relative URLs are always
+    /// allowed
+    pub allowed_schemes: HashSet<String>,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        let set = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            mode: SanitizeMode::default(),
+            allowed_tags: set(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "caption",
+                "cite",
+                "code",
+                "dd",
+                "del",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "ins",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "q",
+                "s",
+                "samp",
+                "small",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "tr",
+                "u",
+                "ul",
+                "var",
+            ]),
+            allowed_attributes: set(&[
+                "alt", "class", "colspan", "height", "href", "id", "lang", "rowspan", "src",
+                "title", "width",
+            ]),
+            allowed_schemes: set(&["http", "https", "mailto"]),
+        }
+    }
+}
+
+/// Elements whose content is dropped along with the tags when stripping
+const CONTENT_ELEMENTS: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template",
+];
+
+/// Sanitize every raw block and inline in `doc`, unlink links and images
+/// to disallowed URLs, and drop comments
+///
+/// Returns the number of tags, attributes, links, images and comments
+/// removed or escaped.
+pub fn sanitize(doc: &mut Document, config: &SanitizeConfig) -> usize {
+    fn visit_blocks(blocks: &mut Vec<Block>, config: &SanitizeConfig, changes: &mut usize) {
+        let before = blocks.len();
+        blocks.retain(|block| !matches!(block, Block::Comment { .. }));
+        *changes += before - blocks.len();
+        for block in blocks {
+            if let Block::Raw { content, .. } = block {
+                *content = clean_html(content, config, changes);
+            }
+            for group in block.inline_groups_mut() {
+                visit_inlines(group, config, changes);
+            }
+            for list in block_lists_mut(block) {
+                visit_blocks(list, config, changes);
+            }
+        }
+    }
+
+    fn visit_inlines(inlines: &mut Vec<Inline>, config: &SanitizeConfig, changes: &mut usize) {
+        let before = inlines.len();
+        inlines.retain(|inline| !matches!(inline, Inline::Comment { .. }));
+        *changes += before - inlines.len();
+        for inline in inlines {
+            match inline {
+                Inline::RawInline { content, .. } => {
+                    *content = clean_html(content, config, changes);
+                }
+                // A link keeps its text, and an image its alt text
+                Inline::Link { url, content, .. } if !url_allowed(url, config) => {
+                    *changes += 1;
+                    let content = std::mem::take(content);
+                    *inline = Inline::Span {
+                        id: None,
+                        classes: Vec::new(),
+                        attributes: BTreeMap::new(),
+                        content,
+                    };
+                }
+                Inline::Image { url, alt, .. } if !url_allowed(url, config) => {
+                    *changes += 1;
+                    let content = std::mem::take(alt);
+                    *inline = Inline::Text { content };
+                }
+                _ => {}
+            }
+            if let Some(children) = children_mut(inline) {
+                visit_inlines(children, config, changes);
+            }
+            if let Inline::Citation { prefix, suffix, .. } = inline {
+                for group in prefix.iter_mut().chain(suffix) {
+                    visit_inlines(group, config, changes);
+                }
+            }
+        }
+    }
+
+    let mut changes = 0;
+    visit_blocks(&mut doc.content, config, &mut changes);
+    changes
+}
+
+/// Sanitize one fragment of HTML
+pub fn sanitize_html(html: &str, config: &SanitizeConfig) -> String {
+    clean_html(html, config, &mut 0)
+}
+
+fn clean_html(html: &str, config: &SanitizeConfig, changes: &mut usize) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        rest = &rest[lt..];
+
+        // Comments can hide conditional markup; drop them
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            *changes += 1;
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        let Some(tag) = Tag::parse(rest) else {
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        let source = &rest[..tag.len];
+        rest = &rest[tag.len..];
+
+        if config.allowed_tags.contains(&tag.name) {
+            out.push_str(&tag.render(config, changes));
+            continue;
+        }
+
+        *changes += 1;
+        match config.mode {
+            SanitizeMode::Escape => out.push_str(&escape(source)),
+            SanitizeMode::Strip => {
+                if !tag.closing && CONTENT_ELEMENTS.contains(&tag.name.as_str()) {
+                    rest = skip_element(rest, &tag.name);
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Text after the closing tag of `name`, or nothing if it is never closed
+pub(crate) fn skip_element<'a>(html: &'a str, name: &str) -> &'a str {
+    let lower = html.to_ascii_lowercase();
+    let close = format!("</{}", name);
+    match lower.find(&close) {
+        Some(start) => {
+            let after = &html[start..];
+            after.find('>').map_or("", |end| &after[end + 1..])
+        }
+        None => "",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A start or end tag
+pub(crate) struct Tag {
+    pub(crate) name: String,
+    pub(crate) closing: bool,
+    pub(crate) self_closing: bool,
+    pub(crate) attributes: Vec<(String, Option<String>)>,
+    /// Bytes of source the tag spans
+    pub(crate) len: usize,
+}
+
+impl Tag {
+    /// Parse the tag at the start of `html`, which begins with `<`
+    pub(crate) fn parse(html: &str) -> Option<Self> {
+        let bytes = html.as_bytes();
+        let mut i = 1;
+        let closing = bytes.get(i) == Some(&b'/');
+        if closing {
+            i += 1;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+            i += 1;
+        }
+        if i == name_start || !bytes[name_start].is_ascii_alphabetic() {
+            return None;
+        }
+        let name = html[name_start..i].to_ascii_lowercase();
+
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+        loop {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i)? {
+                b'>' => break,
+                b'/' => {
+                    self_closing = true;
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let attr_start = i;
+            while i < bytes.len()
+                && !matches!(bytes[i], b'=' | b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r')
+            {
+                i += 1;
+            }
+            let attr = html[attr_start..i].to_ascii_lowercase();
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+
+            let value = if bytes.get(i) == Some(&b'=') {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                match bytes.get(i)? {
+                    quote @ (b'"' | b'\'') => {
+                        let end = i + 1 + html[i + 1..].find(*quote as char)?;
+                        let value = html[i + 1..end].to_string();
+                        i = end + 1;
+                        Some(value)
+                    }
+                    _ => {
+                        let start = i;
+                        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>'
+                        {
+                            i += 1;
+                        }
+                        Some(html[start..i].to_string())
+                    }
+                }
+            } else {
+                None
+            };
+            attributes.push((attr, value));
+        }
+
+        Some(Self {
+            name,
+            closing,
+            self_closing,
+            attributes,
+            len: i + 1,
+        })
+    }
+
+    /// The tag with disallowed attributes removed
+    fn render(&self, config: &SanitizeConfig, changes: &mut usize) -> String {
+        if self.closing {
+            return format!("</{}>", self.name);
+        }
+
+        let mut out = format!("<{}", self.name);
+        for (name, value) in &self.attributes {
+            let allowed = !name.starts_with("on")
+                && config.allowed_attributes.contains(name)
+                && value
+                    .as_deref()
+                    .is_none_or(|v| !is_url_attribute(name) || url_allowed(v, config));
+            if !allowed {
+                *changes += 1;
+                continue;
+            }
+            match value {
+                Some(value) => {
+                    out.push_str(&format!(" {}=\"{}\"", name, value.replace('"', "&quot;")))
+                }
+                None => out.push_str(&format!(" {}", name)),
+            }
+        }
+        out.push_str(if self.self_closing { " />" } else { ">" });
+        out
+    }
+}
+
+fn is_url_attribute(name: &str) -> bool {
+    matches!(
+        name,
+        "href" | "src" | "action" | "formaction" | "xlink:href"
+    )
+}
+
+/// Whether a URL is relative or uses an allowed scheme
+fn url_allowed(url: &str, config: &SanitizeConfig) -> bool {
+    // Browsers decode character references in attribute values, then ignore
+    // control characters and whitespace inside schemes
+    let cleaned: String = decode_char_refs(url)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    // A reference left undecoded could still stand for the scheme's colon
+    let before_path = cleaned.split(['/', '?', '#']).next().unwrap_or_default();
+    if before_path.contains('&') {
+        return false;
+    }
+    match cleaned.split_once(':') {
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => config
+            .allowed_schemes
+            .contains(&scheme.to_ascii_lowercase()),
+        _ => true,
+    }
+}
+
+/// Decode numeric character references, with or without their `;`, and
+/// the named ones that can spell out a scheme
+fn decode_char_refs(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp + 1..];
+        if let Some(number) = rest.strip_prefix('#') {
+            let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None => (number, 10),
+            };
+            let len = digits
+                .find(|c: char| !c.is_digit(radix))
+                .unwrap_or(digits.len());
+            if len > 0 {
+                let c = u32::from_str_radix(&digits[..len], radix)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                out.push(c);
+                rest = &digits[len..];
+                rest = rest.strip_prefix(';').unwrap_or(rest);
+                continue;
+            }
+        }
+        let named = rest.split_once(';').and_then(|(name, after)| {
+            let c = match name {
+                "colon" => ':',
+                "Tab" => '\t',
+                "NewLine" => '\n',
+                "amp" => '&',
+                "sol" => '/',
+                "quest" => '?',
+                "num" => '#',
+                _ => return None,
+            };
+            Some((c, after))
+        });
+        match named {
+            Some((c, after)) => {
+                out.push(c);
+                rest = after;
+            }
+            None => out.push('&'),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::MarkdownHandler;
+    use crate::traits::{ParseConfig, Parser};
+
+    #[test]
+    fn test_strips_scripts_handlers_and_bad_urls() {
+        let config = SanitizeConfig::default();
+        assert_eq!(
+            sanitize_html(
+                "<p onclick=\"steal()\" class=x>Hi<script>alert('<b>')</script></p>",
+                &config
+            ),
+            "<p class=\"x\">Hi</p>"
+        );
+        assert_eq!(
+            sanitize_html(
+                "<a href=\" java\tscript:x()\">a</a><a href='/ok'>b</a>",
+                &config
+            ),
+            "<a>a</a><a href=\"/ok\">b</a>"
+        );
+        assert_eq!(
+            sanitize_html(
+                "<a href=\"javascript&#58;alert(1)\">a</a><a href=\"javascript&colon;alert(1)\">b</a>",
+                &config
+            ),
+            "<a>a</a><a>b</a>"
+        );
+        assert_eq!(
+            sanitize_html(
+                "<a href=\"java&#x73;cript&#0058alert(1)\">a</a><a href=\"javascript&unknown;x\">b</a>",
+                &config
+            ),
+            "<a>a</a><a>b</a>"
+        );
+        assert_eq!(
+            sanitize_html(
+                "<a href=\"https&#58;//x.org/?a=1&amp;b=2\">a</a><a href=\"/p?a&b\">b</a>",
+                &config
+            ),
+            "<a href=\"https&#58;//x.org/?a=1&amp;b=2\">a</a><a href=\"/p?a&b\">b</a>"
+        );
+        assert_eq!(
+            sanitize_html("<!-- <script> -->1 < 2<iframe src=x>", &config),
+            "1 &lt; 2"
+        );
+    }
+
+    #[test]
+    fn test_escape_mode_and_allowlist() {
+        let mut config = SanitizeConfig {
+            mode: SanitizeMode::Escape,
+            ..Default::default()
+        };
+        assert_eq!(
+            sanitize_html("<video src=a.mp4></video>", &config),
+            "&lt;video src=a.mp4&gt;&lt;/video&gt;"
+        );
+
+        config.allowed_tags.insert("video".to_string());
+        assert_eq!(
+            sanitize_html("<video src=a.mp4></video>", &config),
+            "<video src=\"a.mp4\"></video>"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_document() {
+        let mut doc = MarkdownHandler::new()
+            .parse(
+                "<div onload=x()>\n<script>evil()</script>\n</div>\n\nText <img src=y onerror=z()>\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(sanitize(&mut doc, &SanitizeConfig::default()), 3);
+
+        let raw: Vec<String> = doc
+            .content
+            .iter()
+            .flat_map(|block| match block {
+                Block::Raw { content, .. } => vec![content.clone()],
+                Block::Paragraph { content, .. } => content
+                    .iter()
+                    .filter_map(|inline| match inline {
+                        Inline::RawInline { content, .. } => Some(content.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        assert_eq!(raw, vec!["<div>\n\n</div>\n", "<img src=\"y\">"]);
+    }
+
+    #[test]
+    fn test_sanitize_links_and_images() {
+        let mut doc = MarkdownHandler::new()
+            .parse(
+                "[ok](https://x.org) [bad](javascript:alert(1)) [rel](/p)\n\n\
+                 ![pic](java&#x73;cript:x) ![fine](a.png)\n",
+                &ParseConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(sanitize(&mut doc, &SanitizeConfig::default()), 2);
+
+        let html = crate::html::render_html(&doc);
+        assert!(!html.contains("script:"), "{}", html);
+        assert!(html.contains("<a href=\"https://x.org\">ok</a> <span>bad</span>"));
+        assert!(html.contains("<a href=\"/p\">rel</a>"));
+        assert!(
+            html.contains("pic <img src=\"a.png\" alt=\"fine\""),
+            "{}",
+            html
+        );
+    }
+
+    #[test]
+    fn test_sanitize_comments() {
+        let mut doc = Document::new(crate::ast::SourceFormat::Markdown);
+        doc.content = vec![
+            Block::Comment {
+                content: "--><script>evil()</script>".to_string(),
+                span: None,
+            },
+            Block::BlockQuote {
+                content: vec![Block::Paragraph {
+                    content: vec![
+                        Inline::Text {
+                            content: "Kept".to_string(),
+                        },
+                        Inline::Comment {
+                            content: "--><img src=x onerror=y()>".to_string(),
+                        },
+                    ],
+                    span: None,
+                }],
+                attribution: None,
+                admonition: None,
+                span: None,
+            },
+        ];
+        assert_eq!(sanitize(&mut doc, &SanitizeConfig::default()), 2);
+        assert_eq!(
+            crate::html::render_html(&doc),
+            "<blockquote>\n<p>Kept</p>\n</blockquote>\n"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Spell checking against Hunspell dictionaries
+//!
+//! Walks the prose of a document - headings, paragraphs, captions, link text -
+//! and checks each word with a Hunspell `.aff`/`.dic` pair. Inline code, math,
+//! code blocks, raw blocks and anything that looks like a URL, email address
+//! or path are skipped.
+//!
+//! Parsers do not record inline positions, so when the source text is
+//! available each misspelling is located by scanning forward through it in
+//! document order.
+
+use super::WordLocator;
+use crate::ast::{Block, Document, Inline, Span};
+use serde::{Deserialize, Serialize};
+use spellbook::Dictionary;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Most suggestions returned per misspelling
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum SpellError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid dictionary: {0}")]
+    Dictionary(String),
+}
+
+/// A word not found in the dictionary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Misspelling {
+    pub word: String,
+    /// Location in the source, when the source was supplied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// Best replacements first
+    pub suggestions: Vec<String>,
+}
+
+/// A loaded dictionary plus words the user chose to ignore
+pub struct SpellChecker {
+    dictionary: Dictionary,
+    ignored: HashSet<String>,
+}
+
+impl SpellChecker {
+    /// Build from the contents of a Hunspell `.aff` and `.dic` pair
+    pub fn new(aff: &str, dic: &str) -> Result<Self, SpellError> {
+        let dictionary =
+            Dictionary::new(aff, dic).map_err(|e| SpellError::Dictionary(e.to_string()))?;
+        Ok(Self {
+            dictionary,
+            ignored: HashSet::new(),
+        })
+    }
+
+    /// Load `<base>.aff` and `<base>.dic`, e.g. `/usr/share/hunspell/en_US`
+    pub fn from_files(base: impl AsRef<Path>) -> Result<Self, SpellError> {
+        let base = base.as_ref().to_string_lossy();
+        let aff = std::fs::read_to_string(format!("{}.aff", base))?;
+        let dic = std::fs::read_to_string(format!("{}.dic", base))?;
+        Self::new(&aff, &dic)
+    }
+
+    /// Accept `word` from now on (a personal dictionary entry)
+    pub fn ignore_word(&mut self, word: impl Into<String>) {
+        self.ignored.insert(word.into());
+    }
+
+    /// Whether a single word is spelled correctly
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.ignored.contains(word) || self.dictionary.check(word)
+    }
+
+    /// Check every prose word in `doc`
+    ///
+    /// Pass the text `doc` was parsed from to get spans.
+    pub fn check(&self, doc: &Document, source: Option<&str>) -> Vec<Misspelling> {
+        let mut locator = source.map(WordLocator::new);
+        let mut suggestions: HashMap<String, Vec<String>> = HashMap::new();
+        let mut misspellings = Vec::new();
+
+        for word in prose_words(doc) {
+            // Locate every word, not just misspellings, so repeated words
+            // resolve to the right occurrence
+            let span = locator.as_mut().and_then(|l| l.find(&word));
+            if self.is_correct(&word) {
+                continue;
+            }
+
+            let suggestions = suggestions
+                .entry(word.clone())
+                .or_insert_with(|| {
+                    let mut out = Vec::new();
+                    self.dictionary.suggest(&word, &mut out);
+                    out.truncate(MAX_SUGGESTIONS);
+                    out
+                })
+                .clone();
+            misspellings.push(Misspelling {
+                word,
+                span,
+                suggestions,
+            });
+        }
+        misspellings
+    }
+}
+
+/// Checkable words of the document in order
+fn prose_words(doc: &Document) -> Vec<String> {
+    fn visit_blocks<'a>(blocks: impl IntoIterator<Item = &'a Block>, text: &mut Vec<String>) {
+        for block in blocks {
+            for group in block.inline_groups() {
+                let mut run = String::new();
+                visit_inlines(group, &mut run);
+                text.push(run);
+            }
+            visit_blocks(block.child_blocks(), text);
+        }
+    }
+
+    fn visit_inlines<'a>(inlines: impl IntoIterator<Item = &'a Inline>, run: &mut String) {
+        for inline in inlines {
+            match inline {
+                Inline::Text { content } => run.push_str(content),
+                Inline::SoftBreak | Inline::LineBreak | Inline::NonBreakingSpace => run.push(' '),
+                // Code, math and raw content are not prose
+                Inline::Code { .. } | Inline::Math { .. } | Inline::RawInline { .. } => {
+                    run.push(' ')
+                }
+                other => {
+                    run.push(' ');
+                    visit_inlines(other.children(), run);
+                    run.push(' ');
+                }
+            }
+        }
+    }
+
+    let mut runs = Vec::new();
+    visit_blocks(&doc.content, &mut runs);
+
+    runs.iter()
+        .flat_map(|run| run.split_whitespace())
+        .filter(|chunk| !is_address(chunk))
+        .flat_map(|chunk| chunk.unicode_words())
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .filter(|word| !word.chars().any(|c| c.is_numeric()))
+        .map(|word| word.to_string())
+        .collect()
+}
+
+/// URLs, email addresses and paths
+fn is_address(chunk: &str) -> bool {
+    chunk.contains("://")
+        || chunk.starts_with("www.")
+        || chunk.contains('@')
+        || chunk.contains('/')
+        || chunk.contains('\\')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{DjotHandler, MarkdownHandler};
+    use crate::traits::{ParseConfig, Parser};
+
+    const AFF: &str = "SET UTF-8\nTRY esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'\n";
+    const DIC: &str = "8\nthe\ncat\nsat\non\nmat\nsee\nand\nquick\n";
+
+    fn checker() -> SpellChecker {
+        SpellChecker::new(AFF, DIC).unwrap()
+    }
+
+    #[test]
+    fn test_skips_code_math_and_urls() {
+        let source = "The cat `sta` on $`x` https://exmaple.com [mat](https://mta.org)\n";
+        let doc = DjotHandler::new()
+            .parse(source, &ParseConfig::default())
+            .unwrap();
+        assert!(checker().check(&doc, Some(source)).is_empty());
+    }
+
+    #[test]
+    fn test_misspellings_have_spans_and_suggestions() {
+        let source = "# The cat\n\nThe cta sat on\nthe mat, the cta.\n";
+        let doc = MarkdownHandler::new()
+            .parse(source, &ParseConfig::default())
+            .unwrap();
+        let found = checker().check(&doc, Some(source));
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].word, "cta");
+        assert!(found[0].suggestions.contains(&"cat".to_string()));
+
+        let spans: Vec<_> = found.iter().map(|m| m.span.unwrap()).collect();
+        assert_eq!((spans[0].line, spans[0].column), (3, 5));
+        assert_eq!((spans[1].line, spans[1].column), (4, 14));
+        assert_eq!(&source[spans[1].start..spans[1].end], "cta");
+    }
+
+    #[test]
+    fn test_ignore_word() {
+        let doc = MarkdownHandler::new()
+            .parse("See formatrix\n", &ParseConfig::default())
+            .unwrap();
+        let mut checker = checker();
+        assert_eq!(checker.check(&doc, None)[0].span, None);
+
+        checker.ignore_word("formatrix");
+        assert!(checker.check(&doc, None).is_empty());
+    }
+}
@@ -2,9 +2,16 @@
 // Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
 //! Parser and Renderer traits for format handlers
 
-use crate::ast::{Document, SourceFormat};
+use crate::ast::{Block, Document, Inline, SourceFormat};
+use crate::encoding::TextEncoding;
+use crate::file_ops::BackupPolicy;
+use crate::sourcemap::SourceMap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 /// Error type for parsing and rendering
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +34,10 @@ pub enum ConversionError {
 
     #[error("Serialization error: {0}")]
     SerializationError(String),
+
+    /// The input or the parsed document is over a [`ParseLimits`] limit
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
 }
 
 pub type Result<T> = std::result::Result<T, ConversionError>;
@@ -42,6 +53,578 @@ pub struct ParseConfig {
     pub front_matter_delimiter: Option<String>,
     /// Format-specific options
     pub format_options: HashMap<String, String>,
+    /// Leave include directives unexpanded when opening files
+    pub keep_includes: bool,
+    /// Directory included files must stay inside (default: the directory of
+    /// the file being opened)
+    pub include_root: Option<PathBuf>,
+    /// Write embedded `data:` images into this directory (relative to the
+    /// opened file) and link to the files instead
+    pub extract_images: Option<PathBuf>,
+    /// Clean-up applied to the input before parsing
+    pub text: TextPolicy,
+    /// Resource limits for untrusted input
+    pub limits: ParseLimits,
+    /// Fail on malformed input when opening files, instead of keeping the
+    /// regions that do not parse as raw blocks
+    pub strict: bool,
+    /// Read HTML embedded in Markdown into AST nodes, so tables and other
+    /// structure survive conversion to formats without raw HTML; HTML the
+    /// reader makes nothing of stays raw
+    pub parse_embedded_html: bool,
+    /// Read `:smile:`-style shortcodes for known emoji into
+    /// [`Inline::Emoji`](crate::ast::Inline::Emoji) nodes, whatever the
+    /// format
+    pub emoji_shortcodes: bool,
+    /// Keep reStructuredText substitution references as `|name|`, with
+    /// their definitions, instead of replacing them with what they stand
+    /// for; other formats still show the substituted content
+    pub keep_substitutions: bool,
+    /// Dialect reStructuredText is read as
+    pub rst_flavor: RstFlavor,
+}
+
+impl ParseConfig {
+    /// Apply the text policy to a parser's input
+    ///
+    /// Every parser calls this as its first step, so text from different
+    /// editors reaches the AST in the same form.
+    pub fn prepare_input<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        self.text.apply(input)
+    }
+
+    /// Apply the options that act on the parsed tree
+    ///
+    /// Every parser calls this on the document it returns, so they agree
+    /// on anything not tied to one format's syntax.
+    pub fn finish_document(&self, mut doc: Document) -> Document {
+        if self.emoji_shortcodes {
+            crate::emoji::parse_shortcodes(&mut doc.content);
+        }
+        doc
+    }
+}
+
+/// Dialect of reStructuredText
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RstFlavor {
+    /// Plain docutils: roles and directives it does not know are left as
+    /// text and comments
+    #[default]
+    Docutils,
+    /// Sphinx: its roles and directives, such as `:doc:`, `:py:func:`,
+    /// `toctree` and `versionadded`, are read as links, code, lists and
+    /// admonitions
+    Sphinx,
+}
+
+/// Unicode normalization form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalizationForm {
+    /// Leave text as it is
+    #[default]
+    None,
+    /// Canonical composition: `é` as one code point
+    Nfc,
+    /// Canonical decomposition: `é` as `e` plus a combining accent
+    Nfd,
+}
+
+/// Unicode and whitespace clean-up for text going into or out of a handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextPolicy {
+    pub normalization: NormalizationForm,
+    /// Keep no-break spaces (U+00A0, U+202F); otherwise they become plain
+    /// spaces
+    pub preserve_nbsp: bool,
+    /// Expand tabs to the next multiple of this many columns; `None` keeps
+    /// tabs
+    pub tab_width: Option<usize>,
+}
+
+impl Default for TextPolicy {
+    fn default() -> Self {
+        Self {
+            normalization: NormalizationForm::None,
+            preserve_nbsp: true,
+            tab_width: None,
+        }
+    }
+}
+
+impl TextPolicy {
+    /// Whether [`TextPolicy::apply`] never changes anything
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the policy to `text`, borrowing it when nothing changes
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        use unicode_normalization::{
+            is_nfc_quick, is_nfd_quick, IsNormalized, UnicodeNormalization,
+        };
+
+        let mut text = Cow::Borrowed(text);
+        match self.normalization {
+            NormalizationForm::None => {}
+            NormalizationForm::Nfc => {
+                if is_nfc_quick(text.chars()) != IsNormalized::Yes {
+                    text = Cow::Owned(text.nfc().collect());
+                }
+            }
+            NormalizationForm::Nfd => {
+                if is_nfd_quick(text.chars()) != IsNormalized::Yes {
+                    text = Cow::Owned(text.nfd().collect());
+                }
+            }
+        }
+        if !self.preserve_nbsp && text.contains(['\u{A0}', '\u{202F}']) {
+            text = Cow::Owned(text.replace(['\u{A0}', '\u{202F}'], " "));
+        }
+        if let Some(width) = self.tab_width.filter(|w| *w > 0) {
+            if text.contains('\t') {
+                text = Cow::Owned(expand_tabs(&text, width));
+            }
+        }
+        text
+    }
+}
+
+/// Replace each tab with spaces up to the next tab stop
+fn expand_tabs(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                out.push(c);
+                column = 0;
+            }
+            _ => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Resource limits for parsing untrusted input
+///
+/// Every parser checks the input size before it starts, and stops as soon
+/// as its nesting passes the depth limit: while it builds the tree, and on
+/// the source beforehand where the parser library it wraps would itself
+/// recurse once per level. The block count is checked on the parsed
+/// document. Only the Djot reader can honour the timeout, checking it as
+/// it goes; the other readers refuse a config that sets one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+    /// Largest input accepted, in bytes; files are checked as they are
+    /// read and decompressed, and again once decoded and included into
+    pub max_input_bytes: Option<usize>,
+    /// Deepest nesting accepted, of blocks and of the inline elements in
+    /// them; top-level blocks are at depth 1
+    pub max_depth: Option<usize>,
+    /// Most blocks accepted, nested ones included
+    pub max_blocks: Option<usize>,
+    /// Longest a parse may take
+    ///
+    /// The Djot reader checks the time between steps and stops once it
+    /// passes. The libraries the other readers wrap cannot be stopped part
+    /// way, so those readers fail with [`ConversionError::LimitExceeded`]
+    /// when it is set, rather than leave a parse running past it.
+    pub timeout: Option<Duration>,
+}
+
+impl ParseLimits {
+    /// Limits suited to documents from an untrusted source
+    ///
+    /// No [`timeout`](Self::timeout) is set, since only Djot input can
+    /// have one; set it yourself when the input is Djot.
+    pub fn untrusted() -> Self {
+        Self {
+            max_input_bytes: Some(16 * 1024 * 1024),
+            max_depth: Some(128),
+            max_blocks: Some(200_000),
+            timeout: None,
+        }
+    }
+
+    /// Whether the reader for `format` can honour a
+    /// [`timeout`](Self::timeout)
+    pub fn supports_timeout(format: SourceFormat) -> bool {
+        format == SourceFormat::Djot
+    }
+
+    /// Refuse a [`timeout`](Self::timeout) the reader for `format` cannot
+    /// honour
+    pub fn check_timeout(&self, format: SourceFormat) -> Result<()> {
+        match self.timeout {
+            Some(_) if !Self::supports_timeout(format) => Err(ConversionError::LimitExceeded(
+                format!("a parse timeout cannot be enforced for {:?}", format),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the size of the input
+    pub fn check_input(&self, input: &str) -> Result<()> {
+        self.check_size(input.len())
+    }
+
+    /// Check a size in bytes against [`max_input_bytes`](Self::max_input_bytes)
+    pub fn check_size(&self, len: usize) -> Result<()> {
+        match self.max_input_bytes {
+            Some(max) if len > max => Err(ConversionError::LimitExceeded(format!(
+                "input is {} bytes, at most {} allowed",
+                len, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a nesting depth a parser has reached
+    pub fn check_depth(&self, depth: usize) -> Result<()> {
+        match self.max_depth {
+            Some(max) if depth > max => Err(ConversionError::LimitExceeded(format!(
+                "nested more than {} deep",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the nesting `scan` finds in `input`, for a parser library that
+    /// recurses once per level before its tree can be checked
+    pub(crate) fn check_source(&self, input: &str, scan: impl FnOnce(&str) -> usize) -> Result<()> {
+        match self.max_depth {
+            Some(_) => self.check_depth(scan(input)),
+            None => Ok(()),
+        }
+    }
+
+    /// The time a parse starting now must finish by
+    pub(crate) fn deadline(&self) -> Deadline {
+        Deadline(self.timeout.map(|timeout| Instant::now() + timeout))
+    }
+
+    /// Check the nesting depth and block count of a parsed document
+    pub fn check_document(&self, doc: &Document) -> Result<()> {
+        if self.max_depth.is_none() && self.max_blocks.is_none() {
+            return Ok(());
+        }
+        enum Node<'a> {
+            Block(&'a Block),
+            Inline(&'a Inline),
+        }
+
+        let mut blocks = 0;
+        let mut stack: Vec<_> = doc
+            .content
+            .iter()
+            .map(|block| (Node::Block(block), 1))
+            .collect();
+        while let Some((node, depth)) = stack.pop() {
+            self.check_depth(depth)?;
+            match node {
+                Node::Block(block) => {
+                    blocks += 1;
+                    if let Some(max) = self.max_blocks.filter(|&max| blocks > max) {
+                        return Err(ConversionError::LimitExceeded(format!(
+                            "more than {} blocks",
+                            max
+                        )));
+                    }
+                    let inlines = block.inline_groups().into_iter().flatten();
+                    stack.extend(inlines.map(|inline| (Node::Inline(inline), depth + 1)));
+                    let children = block.child_blocks().into_iter();
+                    stack.extend(children.map(|child| (Node::Block(child), depth + 1)));
+                }
+                Node::Inline(inline) => {
+                    let children = inline.children().into_iter();
+                    stack.extend(children.map(|child| (Node::Inline(child), depth + 1)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The time a parse must finish by, checked by parsers between steps so
+/// that one past its [timeout](ParseLimits::timeout) stops instead of
+/// running on unseen
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Fail once the deadline has passed
+    pub(crate) fn check(&self) -> Result<()> {
+        match self.0 {
+            Some(deadline) if Instant::now() >= deadline => Err(ConversionError::LimitExceeded(
+                "parsing took longer than its timeout".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Line ending written by renderers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// Unix-style `\n`
+    #[default]
+    Lf,
+    /// Windows-style `\r\n`
+    CrLf,
+    /// Classic Mac-style `\r`
+    Cr,
+    /// Whatever the file being overwritten uses
+    ///
+    /// Output that is not saved over an existing file gets `\n`.
+    Preserve,
+}
+
+impl LineEnding {
+    /// The newline sequence for this style
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf | Self::Preserve => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+
+    /// The most common line ending in `text`; `Lf` if there are none
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let cr = text.matches('\r').count() - crlf;
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf && crlf >= cr {
+            Self::CrLf
+        } else if cr > lf && cr > crlf {
+            Self::Cr
+        } else {
+            Self::Lf
+        }
+    }
+}
+
+/// Policy for newlines at the end of rendered output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalNewline {
+    /// Leave the renderer's output untouched
+    #[default]
+    Keep,
+    /// Remove all trailing newlines
+    Strip,
+    /// End with exactly one newline
+    Single,
+}
+
+/// What renderers do with raw blocks and inlines from another format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RawPolicy {
+    /// Write them as they are, in the target's passthrough syntax if it has
+    /// one
+    #[default]
+    Keep,
+    /// Leave them out
+    Drop,
+    /// Write them as comments, where the target has comment syntax
+    Comment,
+    /// Parse them with their own format's parser and write the result;
+    /// content the parser makes nothing of is kept
+    Convert,
+}
+
+/// How renderers write [`Inline::Emoji`](crate::ast::Inline::Emoji) nodes
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStyle {
+    /// The emoji character itself
+    #[default]
+    Unicode,
+    /// The `:shortcode:`
+    Shortcode,
+    /// An image, from a URL template in which `{shortcode}` stands for the
+    /// shortcode and `{codepoints}` for the character's code points in
+    /// lowercase hex, joined with `-`
+    Image(String),
+}
+
+/// How the Markdown renderer writes a hard line break
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HardBreak {
+    /// Two spaces at the end of the line
+    Spaces,
+    /// A backslash at the end of the line, which editors cannot strip
+    Backslash,
+}
+
+impl HardBreak {
+    /// The break in Markdown, including its newline
+    pub const fn markdown(&self) -> &'static str {
+        match self {
+            Self::Spaces => "  \n",
+            Self::Backslash => "\\\n",
+        }
+    }
+}
+
+/// Markdown heading syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingStyle {
+    /// `#` marks before the heading
+    #[default]
+    Atx,
+    /// A line of `=` or `-` under headings of level 1 and 2; deeper ones
+    /// still take `#` marks
+    Setext,
+}
+
+/// House style of a publishing toolchain
+///
+/// A profile bundles the choices a toolchain's own writer makes, such as
+/// list bullets and code fences, so output matches what the rest of a
+/// pipeline produces. Renderers without such choices ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StyleProfile {
+    /// GitHub Flavored Markdown, as GitHub's editor writes it
+    Github,
+    /// Pandoc's Markdown and reStructuredText writers
+    Pandoc,
+    /// Sphinx, with MyST for Markdown
+    Sphinx,
+    /// Kramdown, as used by Jekyll
+    Kramdown,
+}
+
+impl StyleProfile {
+    /// Every profile
+    pub const ALL: [Self; 4] = [Self::Github, Self::Pandoc, Self::Sphinx, Self::Kramdown];
+
+    /// The profile's name, as accepted by [`StyleProfile::from_name`]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Github => "github",
+            Self::Pandoc => "pandoc",
+            Self::Sphinx => "sphinx",
+            Self::Kramdown => "kramdown",
+        }
+    }
+
+    /// The profile called `name`, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.as_str().eq_ignore_ascii_case(name))
+    }
+
+    /// Markdown syntax this profile writes
+    pub const fn markdown(&self) -> MarkdownStyle {
+        match self {
+            Self::Github => MarkdownStyle::GITHUB,
+            Self::Pandoc => MarkdownStyle {
+                hard_break: "\\\n",
+                thematic_break:
+                    "------------------------------------------------------------------------",
+                ..MarkdownStyle::GITHUB
+            },
+            Self::Sphinx => MarkdownStyle {
+                hard_break: "\\\n",
+                ..MarkdownStyle::GITHUB
+            },
+            Self::Kramdown => MarkdownStyle {
+                bullet: '*',
+                emphasis: "_",
+                code_fence: "~~~",
+                hard_break: "\\\\\n",
+                thematic_break: "* * *",
+                ..MarkdownStyle::GITHUB
+            },
+        }
+    }
+
+    /// reStructuredText syntax this profile writes
+    pub const fn rst(&self) -> RstStyle {
+        match self {
+            Self::Pandoc => RstStyle {
+                code_directive: "code",
+            },
+            Self::Github | Self::Sphinx | Self::Kramdown => RstStyle::SPHINX,
+        }
+    }
+}
+
+/// Choices the Markdown renderer makes where the syntax allows several
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkdownStyle {
+    /// Bullet list marker
+    pub bullet: char,
+    /// Delimiter around emphasis
+    pub emphasis: &'static str,
+    /// Delimiter around strong emphasis
+    pub strong: &'static str,
+    /// Fence around code blocks
+    pub code_fence: &'static str,
+    /// A hard line break, including its newline
+    pub hard_break: &'static str,
+    /// Line between sections
+    pub thematic_break: &'static str,
+    /// Heading syntax
+    pub heading: HeadingStyle,
+}
+
+impl MarkdownStyle {
+    /// The renderer's style without a profile
+    pub const GITHUB: Self = Self {
+        bullet: '-',
+        emphasis: "*",
+        strong: "**",
+        code_fence: "```",
+        hard_break: "  \n",
+        thematic_break: "---",
+        heading: HeadingStyle::Atx,
+    };
+}
+
+impl Default for MarkdownStyle {
+    fn default() -> Self {
+        Self::GITHUB
+    }
+}
+
+/// Choices the reStructuredText renderer makes where the syntax allows
+/// several
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RstStyle {
+    /// Directive for code blocks with a language
+    pub code_directive: &'static str,
+}
+
+impl RstStyle {
+    /// The renderer's style without a profile
+    pub const SPHINX: Self = Self {
+        code_directive: "code-block",
+    };
+}
+
+impl Default for RstStyle {
+    fn default() -> Self {
+        Self::SPHINX
+    }
 }
 
 /// Configuration for rendering
@@ -51,10 +634,37 @@ pub struct RenderConfig {
     pub line_width: usize,
     /// Indentation string (default: 2 spaces)
     pub indent: String,
-    /// Use hard line breaks
+    /// Write every soft line break as a hard one
     pub hard_breaks: bool,
+    /// Markdown hard line break syntax; `None` keeps the profile's
+    pub hard_break: Option<HardBreak>,
+    /// Markdown heading syntax; `None` keeps the profile's
+    pub heading_style: Option<HeadingStyle>,
+    /// Newline sequence used in the output
+    pub line_ending: LineEnding,
+    /// Trailing newline policy
+    pub final_newline: FinalNewline,
     /// Format-specific options
     pub format_options: HashMap<String, String>,
+    /// Inline local images as base64 `data:` URIs when saving to a file
+    pub embed_images: bool,
+    /// Clean-up applied to the rendered output
+    pub text: TextPolicy,
+    /// Encoding used when saving to a file; set it from
+    /// [`FileInfo::encoding`](crate::file_ops::FileInfo::encoding) to keep a
+    /// file's original encoding
+    pub encoding: TextEncoding,
+    /// Backup kept of the previous version when saving over a file
+    pub backup: BackupPolicy,
+    /// House style to follow; `None` keeps each renderer's own
+    pub profile: Option<StyleProfile>,
+    /// Treatment of raw content whose format differs from the target
+    pub raw_policy: RawPolicy,
+    /// How emoji are written
+    pub emoji: EmojiStyle,
+    /// Leave out the subtrees under headings tagged with one of the
+    /// document's exclude tags, as Org export does
+    pub exclude_tags: bool,
 }
 
 impl Default for RenderConfig {
@@ -63,7 +673,131 @@ fn default() -> Self {
             line_width: 80,
             indent: "  ".to_string(),
             hard_breaks: false,
+            hard_break: None,
+            heading_style: None,
+            line_ending: LineEnding::default(),
+            final_newline: FinalNewline::default(),
             format_options: HashMap::new(),
+            embed_images: false,
+            text: TextPolicy::default(),
+            encoding: TextEncoding::default(),
+            backup: BackupPolicy::default(),
+            profile: None,
+            raw_policy: RawPolicy::default(),
+            emoji: EmojiStyle::default(),
+            exclude_tags: false,
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Markdown syntax to write, from the profile and the hard break and
+    /// heading choices
+    pub fn markdown_style(&self) -> MarkdownStyle {
+        let mut style: MarkdownStyle = self
+            .profile
+            .map(|profile| profile.markdown())
+            .unwrap_or_default();
+        if let Some(hard_break) = self.hard_break {
+            style.hard_break = hard_break.markdown();
+        }
+        if let Some(heading) = self.heading_style {
+            style.heading = heading;
+        }
+        style
+    }
+
+    /// reStructuredText syntax to write, from the profile
+    pub fn rst_style(&self) -> RstStyle {
+        self.profile
+            .map(|profile| profile.rst())
+            .unwrap_or_default()
+    }
+
+    /// Apply the raw policy, emoji style, hard breaks setting and exclude
+    /// tags to a document about to be rendered as `target`, and write its
+    /// math in the target's notation
+    ///
+    /// Every renderer calls this as its first step, so raw content from
+    /// other formats, emoji, line breaks, private subtrees and math are
+    /// treated the same whatever the target.
+    pub fn prepare_document<'a>(
+        &self,
+        doc: &'a Document,
+        target: SourceFormat,
+    ) -> Cow<'a, Document> {
+        let mut doc = crate::raw::apply_raw_policy(doc, target, self.raw_policy);
+        if crate::emoji::has_emoji(&doc.content) {
+            crate::emoji::apply_emoji_style(&mut doc.to_mut().content, &self.emoji);
+        }
+        if self.hard_breaks {
+            harden_breaks(&mut doc.to_mut().content);
+        }
+        if self.exclude_tags {
+            let tags = crate::exclude::exclude_tags(&doc.meta);
+            crate::exclude::exclude_tagged(&mut doc.to_mut().content, &tags);
+        }
+        let notation = crate::math::target_notation(target);
+        if crate::math::needs_translation(&doc.content, notation) {
+            crate::math::translate_math(&mut doc.to_mut().content, notation);
+        }
+        doc
+    }
+
+    /// Apply the text and newline policies to a renderer's output
+    ///
+    /// Every renderer calls this as its last step so that line endings and
+    /// the trailing newline are consistent regardless of the target format.
+    pub fn finish_output(&self, output: String) -> String {
+        let output = if output.contains('\r') {
+            output.replace("\r\n", "\n")
+        } else {
+            output
+        };
+        let mut output = match self.text.apply(&output) {
+            Cow::Borrowed(_) => output,
+            Cow::Owned(cleaned) => cleaned,
+        };
+
+        match self.final_newline {
+            FinalNewline::Keep => {}
+            FinalNewline::Strip => {
+                let len = output.trim_end_matches('\n').len();
+                output.truncate(len);
+            }
+            FinalNewline::Single => {
+                let len = output.trim_end_matches('\n').len();
+                output.truncate(len);
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+            }
+        }
+
+        match self.line_ending {
+            LineEnding::Lf | LineEnding::Preserve => output,
+            LineEnding::CrLf | LineEnding::Cr => output.replace('\n', self.line_ending.as_str()),
+        }
+    }
+}
+
+/// Turn the soft line breaks in `blocks` into hard ones
+fn harden_breaks(blocks: &mut [Block]) {
+    fn visit(inline: &mut Inline) {
+        if let Inline::SoftBreak = inline {
+            *inline = Inline::LineBreak;
+        }
+        for child in inline.children_mut() {
+            visit(child);
+        }
+    }
+
+    for block in blocks {
+        for group in block.inline_groups_mut() {
+            group.iter_mut().for_each(visit);
+        }
+        for child in block.child_blocks_mut() {
+            harden_breaks(std::slice::from_mut(child));
         }
     }
 }
@@ -84,6 +818,17 @@ pub trait Renderer: Send + Sync {
 
     /// Render a Document to a string
     fn render(&self, doc: &Document, config: &RenderConfig) -> Result<String>;
+
+    /// Render a Document along with where each top-level block ended up
+    fn render_with_source_map(
+        &self,
+        doc: &Document,
+        config: &RenderConfig,
+    ) -> Result<(String, SourceMap)> {
+        let output = self.render(doc, config)?;
+        let map = SourceMap::locate(&output, doc, |single| self.render(single, config));
+        Ok((output, map))
+    }
 }
 
 /// Extension trait for streaming operations (not dyn-compatible)
@@ -123,6 +868,15 @@ pub trait FormatHandler: Parser + Renderer {
 
     /// Get list of supported features
     fn supported_features(&self) -> &[&str];
+
+    /// Features exempt from the normalization contract
+    ///
+    /// One parse/render cycle must normalize a document so that a second
+    /// cycle changes nothing (see [`crate::report::normalize`]); documents
+    /// using any feature listed here may keep changing on later cycles.
+    fn normalization_exceptions(&self) -> &[&str] {
+        &[]
+    }
 }
 
 /// Registry of format handlers
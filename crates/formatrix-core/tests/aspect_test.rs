@@ -4,8 +4,8 @@
 
 use formatrix_core::{
     ast::{Document, DocumentMeta, SourceFormat},
-    traits::{Parser, ParseConfig},
-    formats::PlainTextHandler,
+    traits::{ConversionError, Parser, ParseConfig, ParseLimits},
+    formats::{handler_for, PlainTextHandler},
 };
 
 /// Test handling of oversized documents (1MB+)
@@ -37,6 +37,42 @@ fn test_deeply_nested_elements() {
     assert!(result.is_ok(), "should handle deep nesting without stack overflow");
 }
 
+/// Test that nesting far past the depth limit fails instead of overflowing
+/// the stack
+#[test]
+fn test_nesting_past_depth_limit() {
+    let indented = |marker: &str| {
+        (0..1000)
+            .map(|i| format!("{}{} x\n\n", " ".repeat(2 * i), marker))
+            .collect::<String>()
+    };
+    let delimited = (0..1000)
+        .map(|i| "=".repeat(4 + i) + "\n")
+        .collect::<String>();
+    let inputs = [
+        (SourceFormat::Markdown, ">".repeat(50_000) + " x"),
+        (SourceFormat::Markdown, "*".repeat(20_000) + "x" + &"*".repeat(20_000)),
+        (SourceFormat::Djot, "> ".repeat(50_000) + "x"),
+        (SourceFormat::ReStructuredText, indented("-")),
+        (SourceFormat::OrgMode, indented("-")),
+        (SourceFormat::Typst, "#quote[".repeat(50_000) + "x"),
+        (SourceFormat::AsciiDoc, delimited),
+    ];
+
+    let config = ParseConfig {
+        limits: ParseLimits::untrusted(),
+        ..Default::default()
+    };
+    for (format, input) in inputs {
+        let result = handler_for(format).parse(&input, &config);
+        assert!(
+            matches!(result, Err(ConversionError::LimitExceeded(_))),
+            "{:?} should stop at the depth limit",
+            format
+        );
+    }
+}
+
 /// Test handling of null bytes (safety aspect)
 #[test]
 fn test_null_byte_handling() {
@@ -217,7 +253,7 @@ fn test_document_metadata_large_values() {
     let meta = DocumentMeta {
         title: Some(large_title.clone()),
         authors: (0..1000)
-            .map(|i| format!("Author {}", i))
+            .map(|i| format!("Author {}", i).into())
             .collect(),
         ..Default::default()
     };
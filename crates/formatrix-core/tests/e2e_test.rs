@@ -5,7 +5,8 @@
 use formatrix_core::{
     ast::{Block, Document, DocumentMeta, Inline, SourceFormat},
     traits::{Parser, ParseConfig, RenderConfig, Renderer},
-    formats::PlainTextHandler,
+    formats::{handler_for, PlainTextHandler},
+    ConversionReport,
 };
 
 /// Test basic plaintext parsing
@@ -152,9 +153,12 @@ fn test_document_metadata() {
         source_format: SourceFormat::PlainText,
         meta: DocumentMeta {
             title: Some("My Document".to_string()),
-            authors: vec!["Author One".to_string(), "Author Two".to_string()],
+            authors: vec!["Author One".into(), "Author Two".into()],
             date: Some("2026-04-04".to_string()),
+            modified: None,
             language: Some("en".to_string()),
+            direction: None,
+            keywords: vec![],
             custom: Default::default(),
         },
         content: vec![],
@@ -259,3 +263,145 @@ fn test_plaintext_special_chars() {
 
     let _doc = parser.parse(input, &config).expect("parse failed");
 }
+
+/// Test that hard breaks and non-breaking spaces survive each format
+#[test]
+fn test_breaks_round_trip() {
+    let text = |s: &str| Inline::Text { content: s.to_string() };
+    let doc = Document {
+        source_format: SourceFormat::Markdown,
+        meta: DocumentMeta::default(),
+        content: vec![Block::Paragraph {
+            content: vec![
+                text("one"),
+                Inline::LineBreak,
+                text("two"),
+                Inline::SoftBreak,
+                text("three"),
+                Inline::NonBreakingSpace,
+                text("four"),
+            ],
+            span: None,
+        }],
+        raw_source: None,
+    };
+
+    for format in [
+        SourceFormat::Markdown,
+        SourceFormat::AsciiDoc,
+        SourceFormat::Djot,
+        SourceFormat::OrgMode,
+        SourceFormat::ReStructuredText,
+        SourceFormat::Typst,
+    ] {
+        let handler = handler_for(format);
+        let output = handler.render(&doc, &RenderConfig::default()).expect("render failed");
+        let parsed = handler.parse(&output, &ParseConfig::default()).expect("re-parse failed");
+        let Block::Paragraph { content, .. } = &parsed.content[0] else {
+            panic!("{:?}: expected a paragraph, got {:?}", format, parsed.content);
+        };
+
+        let breaks = content.iter().filter(|i| matches!(i, Inline::LineBreak)).count();
+        assert_eq!(breaks, 1, "{:?}: {:?}", format, output);
+        let nbsp = content.iter().any(|i| match i {
+            Inline::NonBreakingSpace => true,
+            Inline::Text { content } => content.contains('\u{a0}'),
+            _ => false,
+        });
+        assert!(nbsp, "{:?}: {:?}", format, output);
+    }
+}
+
+/// Test that quote attributions survive each format
+#[test]
+fn test_quote_attribution_round_trip() {
+    let doc = Document {
+        source_format: SourceFormat::Markdown,
+        meta: DocumentMeta::default(),
+        content: vec![Block::BlockQuote {
+            content: vec![Block::Paragraph {
+                content: vec![Inline::Text { content: "To be.".to_string() }],
+                span: None,
+            }],
+            attribution: Some(vec![Inline::Text { content: "Hamlet".to_string() }]),
+            admonition: None,
+            span: None,
+        }],
+        raw_source: None,
+    };
+
+    for format in [
+        SourceFormat::Markdown,
+        SourceFormat::AsciiDoc,
+        SourceFormat::Djot,
+        SourceFormat::OrgMode,
+        SourceFormat::ReStructuredText,
+        SourceFormat::Typst,
+    ] {
+        let handler = handler_for(format);
+        let output = handler.render(&doc, &RenderConfig::default()).expect("render failed");
+        let parsed = handler.parse(&output, &ParseConfig::default()).expect("re-parse failed");
+        match &parsed.content[0] {
+            Block::BlockQuote { content, attribution: Some(attribution), .. } => {
+                assert_eq!(content.len(), 1, "{:?}: {:?}", format, output);
+                assert!(
+                    matches!(attribution.as_slice(), [Inline::Text { content }] if content == "Hamlet"),
+                    "{:?}: {:?}",
+                    format,
+                    attribution
+                );
+            }
+            other => panic!("{:?}: expected an attributed quote, got {:?}", format, other),
+        }
+    }
+}
+
+/// Test that quotes nested three deep keep their nesting in each format that
+/// supports it, and are reported as lost in the others
+#[test]
+fn test_nested_quote_round_trip() {
+    fn quote(text: &str, inner: Option<Block>) -> Block {
+        let mut content = vec![Block::Paragraph {
+            content: vec![Inline::Text { content: text.to_string() }],
+            span: None,
+        }];
+        content.extend(inner);
+        Block::BlockQuote { content, attribution: None, admonition: None, span: None }
+    }
+
+    fn depth(blocks: &[Block]) -> usize {
+        blocks
+            .iter()
+            .map(|block| match block {
+                Block::BlockQuote { content, .. } => 1 + depth(content),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    let doc = Document {
+        source_format: SourceFormat::Markdown,
+        meta: DocumentMeta::default(),
+        content: vec![quote("outer", Some(quote("middle", Some(quote("inner", None)))))],
+        raw_source: None,
+    };
+
+    for format in [
+        SourceFormat::Markdown,
+        SourceFormat::AsciiDoc,
+        SourceFormat::Djot,
+        SourceFormat::ReStructuredText,
+        SourceFormat::Typst,
+    ] {
+        let handler = handler_for(format);
+        let output = handler.render(&doc, &RenderConfig::default()).expect("render failed");
+        let parsed = handler.parse(&output, &ParseConfig::default()).expect("re-parse failed");
+        assert_eq!(depth(&parsed.content), 3, "{:?}: {:?}", format, output);
+        assert_eq!(parsed.content.len(), 1, "{:?}: {:?}", format, output);
+        assert!(ConversionReport::new(&doc, handler.as_ref()).is_lossless());
+    }
+
+    let report = ConversionReport::new(&doc, handler_for(SourceFormat::OrgMode).as_ref());
+    assert_eq!(report.lost_features, vec!["nested_quote"]);
+}
@@ -3,9 +3,13 @@
 //! Property-based tests for format conversion correctness
 
 use formatrix_core::{
-    ast::{DocumentMeta, SourceFormat},
-    traits::{Parser, ParseConfig, RenderConfig, Renderer},
-    formats::PlainTextHandler,
+    ast::{
+        Block, Document, DocumentMeta, Inline, LinkType, ListItem, ListKind, MathNotation,
+        SourceFormat, TableCell, TableRow,
+    },
+    formats::{handler_for, PlainTextHandler},
+    report::{normalize, used_features},
+    traits::{ParseConfig, Parser, RenderConfig, Renderer},
 };
 use proptest::prelude::*;
 
@@ -22,7 +26,7 @@ fn prop_plaintext_parse_no_panic(s in ".*") {
     fn prop_document_metadata_stable(title in "[a-zA-Z0-9 ]{0,100}") {
         let meta = DocumentMeta {
             title: Some(title.clone()),
-            authors: vec!["Author".to_string()],
+            authors: vec!["Author".into()],
             ..Default::default()
         };
 
@@ -107,3 +111,196 @@ fn test_parse_config_defaults_stable() {
     assert_eq!(c1.preserve_spans, c2.preserve_spans);
     assert_eq!(c1.preserve_raw_source, c2.preserve_raw_source);
 }
+
+// Document generator for the normalization contract. It builds documents
+// from a random mix of blocks and inlines; documents that use a feature a
+// handler lists in `normalization_exceptions` are skipped for that handler
+// only.
+
+fn text() -> impl Strategy<Value = String> {
+    prop::collection::vec("[a-z]{1,8}", 1..5).prop_map(|words| words.join(" "))
+}
+
+fn plain(content: String) -> Vec<Inline> {
+    vec![Inline::Text { content }]
+}
+
+fn inline() -> impl Strategy<Value = Inline> {
+    prop_oneof![
+        text().prop_map(|content| Inline::Text { content }),
+        text().prop_map(|t| Inline::Emphasis { content: plain(t) }),
+        text().prop_map(|t| Inline::Strong { content: plain(t) }),
+        "[a-z]{1,8}".prop_map(|content| Inline::Code {
+            content,
+            language: None,
+        }),
+        text().prop_map(|t| Inline::Strikethrough { content: plain(t) }),
+        "[a-z]{1,8}".prop_map(|alt| Inline::Image {
+            url: format!("img/{}.png", alt),
+            alt,
+            title: None,
+            width: None,
+            height: None,
+        }),
+        "[a-z]{1,8}".prop_map(|content| Inline::Math {
+            content,
+            notation: MathNotation::LaTeX,
+        }),
+        "[a-z]{1,3}".prop_map(|label| Inline::FootnoteRef { label }),
+        ("[a-z]{1,8}", text()).prop_map(|(page, t)| Inline::Link {
+            url: format!("https://example.com/{}", page),
+            title: None,
+            content: plain(t),
+            link_type: LinkType::Inline,
+        }),
+    ]
+}
+
+fn paragraph() -> impl Strategy<Value = Block> {
+    prop::collection::vec(inline(), 1..4).prop_map(|inlines| {
+        // Separate inlines with spaces so markup never abuts
+        let mut content = Vec::new();
+        for (i, inline) in inlines.into_iter().enumerate() {
+            if i > 0 {
+                content.push(Inline::Text {
+                    content: " ".to_string(),
+                });
+            }
+            content.push(inline);
+        }
+        Block::Paragraph {
+            content,
+            span: None,
+        }
+    })
+}
+
+fn list(kind: ListKind) -> impl Strategy<Value = Block> {
+    prop::collection::vec(paragraph(), 1..4).prop_map(move |paragraphs| Block::List {
+        kind,
+        items: paragraphs
+            .into_iter()
+            .map(|p| ListItem {
+                content: vec![p],
+                checked: (kind == ListKind::Task).then_some(true),
+                marker: None,
+            })
+            .collect(),
+        start: (kind == ListKind::Ordered).then_some(1),
+        span: None,
+    })
+}
+
+fn table() -> impl Strategy<Value = Block> {
+    let row = || prop::collection::vec(text(), 2..=2);
+    (row(), prop::collection::vec(row(), 1..3)).prop_map(|(header, body)| {
+        let row = |cells: Vec<String>| TableRow {
+            cells: cells
+                .into_iter()
+                .map(|t| TableCell {
+                    content: vec![Block::Paragraph {
+                        content: plain(t),
+                        span: None,
+                    }],
+                    colspan: 1,
+                    rowspan: 1,
+                    alignment: None,
+                })
+                .collect(),
+        };
+        Block::Table {
+            caption: None,
+            columns: Vec::new(),
+            header: Some(row(header)),
+            body: body.into_iter().map(row).collect(),
+            footer: None,
+            span: None,
+        }
+    })
+}
+
+fn block() -> impl Strategy<Value = Block> {
+    prop_oneof![
+        (1u8..=3, text()).prop_map(|(level, t)| Block::Heading {
+            level,
+            content: plain(t),
+            id: None,
+            classes: Vec::new(),
+            span: None,
+        }),
+        paragraph(),
+        list(ListKind::Bullet),
+        list(ListKind::Ordered),
+        list(ListKind::Task),
+        (paragraph(), list(ListKind::Bullet)).prop_map(|(p, nested)| Block::List {
+            kind: ListKind::Bullet,
+            items: vec![ListItem {
+                content: vec![p, nested],
+                checked: None,
+                marker: None,
+            }],
+            start: None,
+            span: None,
+        }),
+        table(),
+        "[a-z]{1,8}".prop_map(|content| Block::MathBlock {
+            content,
+            notation: MathNotation::LaTeX,
+            span: None,
+        }),
+        ("[a-z]{1,3}", paragraph()).prop_map(|(label, p)| Block::FootnoteDefinition {
+            label,
+            content: vec![p],
+            span: None,
+        }),
+        (
+            prop::option::of("[a-z]{1,6}"),
+            prop::collection::vec(text(), 1..4)
+        )
+            .prop_map(|(language, lines)| Block::CodeBlock {
+                language,
+                content: lines.join("\n"),
+                line_numbers: false,
+                highlight_lines: Vec::new(),
+                span: None,
+            }),
+        prop::collection::vec(paragraph(), 1..3).prop_map(|content| Block::BlockQuote {
+            content,
+            attribution: None,
+            admonition: None,
+            span: None,
+        }),
+    ]
+}
+
+fn document() -> impl Strategy<Value = Document> {
+    prop::collection::vec(block(), 1..6).prop_map(|content| Document {
+        source_format: SourceFormat::Markdown,
+        meta: DocumentMeta::default(),
+        content,
+        raw_source: None,
+    })
+}
+
+proptest! {
+    /// Property: one parse/render cycle normalizes output for every handler
+    #[test]
+    fn prop_normalization_is_idempotent(doc in document()) {
+        let used = used_features(&doc);
+        for format in SourceFormat::ALL {
+            let handler = handler_for(format);
+            if handler
+                .normalization_exceptions()
+                .iter()
+                .any(|f| used.contains(f))
+            {
+                continue;
+            }
+
+            let rendered = handler.render(&doc, &RenderConfig::default()).expect("render");
+            let once = normalize(handler.as_ref(), &rendered).expect("first cycle");
+            let twice = normalize(handler.as_ref(), &once).expect("second cycle");
+            prop_assert_eq!(once, twice, "{:?} output changed on the second cycle", format);
+        }
+    }
+}
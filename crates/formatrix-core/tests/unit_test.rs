@@ -4,7 +4,10 @@
 
 use formatrix_core::{
     ast::{Block, Document, DocumentMeta, Inline, SourceFormat, MetaValue},
-    traits::{FormatHandler, Parser, ParseConfig, RenderConfig, Renderer},
+    traits::{
+        FinalNewline, FormatHandler, LineEnding, NormalizationForm, Parser, ParseConfig,
+        RenderConfig, Renderer, TextPolicy,
+    },
     formats::PlainTextHandler,
 };
 use std::collections::HashMap;
@@ -148,6 +151,7 @@ fn test_renderer_heading() {
                 content: "Title".to_string(),
             }],
             id: None,
+            classes: Vec::new(),
             span: None,
         }],
         raw_source: None,
@@ -205,9 +209,12 @@ fn test_roundtrip_multiple_paragraphs() {
 fn test_document_metadata_creation() {
     let meta = DocumentMeta {
         title: Some("Title".to_string()),
-        authors: vec!["Author 1".to_string()],
+        authors: vec!["Author 1".into()],
         date: Some("2026-04-04".to_string()),
+        modified: None,
         language: Some("en".to_string()),
+        direction: None,
+        keywords: vec![],
         custom: HashMap::new(),
     };
 
@@ -313,6 +320,7 @@ fn test_block_heading_creation() {
             content: "Heading".to_string(),
         }],
         id: None,
+        classes: Vec::new(),
         span: None,
     };
 
@@ -375,6 +383,95 @@ fn test_render_config_customization() {
     assert_eq!(config.format_options.get("opt"), Some(&"val".to_string()));
 }
 
+#[test]
+fn test_render_config_final_newline_single() {
+    let renderer = PlainTextHandler::new();
+    let mut config = RenderConfig {
+        final_newline: FinalNewline::Single,
+        ..Default::default()
+    };
+
+    let doc = PlainTextHandler::new()
+        .parse("Para 1\n\nPara 2\n\n\n", &ParseConfig::default())
+        .expect("parse failed");
+    let output = renderer.render(&doc, &config).expect("render failed");
+    assert_eq!(output, "Para 1\n\nPara 2\n");
+
+    config.final_newline = FinalNewline::Strip;
+    assert_eq!(config.finish_output("text\n\n".to_string()), "text");
+}
+
+#[test]
+fn test_render_config_crlf_line_ending() {
+    let config = RenderConfig {
+        line_ending: LineEnding::CrLf,
+        final_newline: FinalNewline::Single,
+        ..Default::default()
+    };
+
+    let output = config.finish_output("a\r\nb\nc".to_string());
+    assert_eq!(output, "a\r\nb\r\nc\r\n");
+}
+
+#[test]
+fn test_line_ending_detect() {
+    assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+    assert_eq!(LineEnding::detect("a\rb\rc"), LineEnding::Cr);
+    assert_eq!(LineEnding::detect("a\nb\r\nc\n"), LineEnding::Lf);
+    assert_eq!(LineEnding::detect("no newline"), LineEnding::Lf);
+
+    let config = RenderConfig {
+        line_ending: LineEnding::Cr,
+        ..Default::default()
+    };
+    assert_eq!(config.finish_output("a\nb".to_string()), "a\rb");
+}
+
+#[test]
+fn test_parse_config_text_policy() {
+    let config = ParseConfig {
+        text: TextPolicy {
+            normalization: NormalizationForm::Nfc,
+            preserve_nbsp: false,
+            tab_width: Some(4),
+        },
+        ..Default::default()
+    };
+
+    let doc = PlainTextHandler::new()
+        .parse("Cafe\u{301}\u{a0}au lait\n\tab\tc", &config)
+        .expect("parse failed");
+    let Block::Paragraph { content, .. } = &doc.content[0] else {
+        panic!("expected a paragraph");
+    };
+    let Inline::Text { content } = &content[0] else {
+        panic!("expected text");
+    };
+    assert_eq!(content, "Caf\u{e9} au lait\n    ab  c");
+
+    // The default policy leaves text alone
+    assert!(ParseConfig::default().text.is_identity());
+    assert_eq!(
+        ParseConfig::default().prepare_input("e\u{301}\t\u{a0}"),
+        "e\u{301}\t\u{a0}"
+    );
+}
+
+#[test]
+fn test_render_config_text_policy() {
+    let config = RenderConfig {
+        text: TextPolicy {
+            normalization: NormalizationForm::Nfd,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        config.finish_output("Caf\u{e9}\u{a0}!".to_string()),
+        "Cafe\u{301}\u{a0}!"
+    );
+}
+
 // ============================================================================
 // FormatHandler Tests
 // ============================================================================
@@ -6,7 +6,8 @@
 //! All functions are synchronous — gossamer-rs command handlers run on the
 //! main thread, so we use std::fs instead of tokio::fs.
 
-use formatrix_core::{ParseConfig, RenderConfig};
+use crate::settings;
+use formatrix_core::ParseConfig;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
@@ -25,6 +26,8 @@ pub enum DocumentEvent {
         format: String,
         timestamp: f64,
         source: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        origin: Option<EventOrigin>,
     },
     Modified {
         id: String,
@@ -34,6 +37,8 @@ pub enum DocumentEvent {
         format: String,
         timestamp: f64,
         source: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        origin: Option<EventOrigin>,
     },
     Deleted {
         id: String,
@@ -41,6 +46,8 @@ pub enum DocumentEvent {
         path: String,
         timestamp: f64,
         source: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        origin: Option<EventOrigin>,
     },
     Converted {
         id: String,
@@ -51,6 +58,53 @@ pub enum DocumentEvent {
         timestamp: f64,
         source: String,
     },
+    /// An export moved to its next stage: `parsing`, `rendering`,
+    /// `writing`, then `done` or `failed`
+    ExportProgress {
+        id: String,
+        job: String,
+        stage: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        timestamp: f64,
+        source: String,
+    },
+    /// The heading outline of an open buffer changed after an edit
+    OutlineChanged {
+        id: String,
+        buffer: String,
+        outline: Vec<formatrix_core::OutlineEntry>,
+        timestamp: f64,
+        source: String,
+    },
+    /// The word or character count of an open buffer changed after an edit
+    StatsChanged {
+        id: String,
+        buffer: String,
+        word_count: usize,
+        char_count: usize,
+        timestamp: f64,
+        source: String,
+    },
+    /// Files or directories in the open workspace were created, changed,
+    /// renamed or removed
+    WorkspaceChanged {
+        id: String,
+        paths: Vec<String>,
+        timestamp: f64,
+        source: String,
+    },
+}
+
+/// The window, view and open document a document event came from
+///
+/// Other views of the same document reload it on a `Modified` event from a
+/// different view.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventOrigin {
+    pub window: String,
+    pub view: String,
+    pub document: String,
 }
 
 /// Source identifier for document events
@@ -75,10 +129,10 @@ pub fn created(content: &str, path: &str, format: &str) -> Self {
             format: format.to_string(),
             timestamp: current_timestamp(),
             source: EVENT_SOURCE.to_string(),
+            origin: None,
         }
     }
 
-    #[allow(dead_code)]
     pub fn modified(content: &str, old_content: &str, path: &str, format: &str) -> Self {
         DocumentEvent::Modified {
             id: Self::generate_id(),
@@ -88,6 +142,7 @@ pub fn modified(content: &str, old_content: &str, path: &str, format: &str) -> S
             format: format.to_string(),
             timestamp: current_timestamp(),
             source: EVENT_SOURCE.to_string(),
+            origin: None,
         }
     }
 
@@ -99,6 +154,7 @@ pub fn deleted(content: &str, path: &str) -> Self {
             path: path.to_string(),
             timestamp: current_timestamp(),
             source: EVENT_SOURCE.to_string(),
+            origin: None,
         }
     }
 
@@ -113,6 +169,59 @@ pub fn converted(source_content: &str, target_content: &str, from: &str, to: &st
             source: EVENT_SOURCE.to_string(),
         }
     }
+
+    pub fn export_progress(job: &str, stage: &str, error: Option<String>) -> Self {
+        DocumentEvent::ExportProgress {
+            id: Self::generate_id(),
+            job: job.to_string(),
+            stage: stage.to_string(),
+            error,
+            timestamp: current_timestamp(),
+            source: EVENT_SOURCE.to_string(),
+        }
+    }
+
+    pub fn outline_changed(buffer: &str, outline: Vec<formatrix_core::OutlineEntry>) -> Self {
+        DocumentEvent::OutlineChanged {
+            id: Self::generate_id(),
+            buffer: buffer.to_string(),
+            outline,
+            timestamp: current_timestamp(),
+            source: EVENT_SOURCE.to_string(),
+        }
+    }
+
+    pub fn stats_changed(buffer: &str, word_count: usize, char_count: usize) -> Self {
+        DocumentEvent::StatsChanged {
+            id: Self::generate_id(),
+            buffer: buffer.to_string(),
+            word_count,
+            char_count,
+            timestamp: current_timestamp(),
+            source: EVENT_SOURCE.to_string(),
+        }
+    }
+
+    pub fn workspace_changed(paths: Vec<String>) -> Self {
+        DocumentEvent::WorkspaceChanged {
+            id: Self::generate_id(),
+            paths,
+            timestamp: current_timestamp(),
+            source: EVENT_SOURCE.to_string(),
+        }
+    }
+
+    /// Mark the event as coming from a view of an open document; events
+    /// without an origin field keep none
+    pub fn with_origin(mut self, from: EventOrigin) -> Self {
+        if let DocumentEvent::Created { origin, .. }
+        | DocumentEvent::Modified { origin, .. }
+        | DocumentEvent::Deleted { origin, .. } = &mut self
+        {
+            *origin = Some(from);
+        }
+        self
+    }
 }
 
 fn hash_content(content: &str) -> String {
@@ -174,12 +283,27 @@ pub struct DocumentData {
 pub struct ConversionResult {
     pub content: String,
     pub warnings: Vec<String>,
+    /// What the conversion lost; `None` when nothing was converted
+    pub report: Option<formatrix_core::ConversionReport>,
+    /// Where the source uses each lost feature, for jump-to-location links
+    pub diagnostics: Vec<formatrix_core::Diagnostic>,
+}
+
+/// Result of saving a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedDocument {
+    #[serde(flatten)]
+    pub meta: DocumentMeta,
+    /// What converting to the file's format lost; `None` when the file is in
+    /// the buffer's own format
+    pub report: Option<formatrix_core::ConversionReport>,
+    pub diagnostics: Vec<formatrix_core::Diagnostic>,
 }
 
 /// Load a document from the filesystem (synchronous — uses std::fs)
 pub fn load_document(path: String) -> Result<DocumentData, String> {
-    let content = std::fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let (content, _) =
+        formatrix_core::read_text(&path).map_err(|e| format!("Failed to read file: {}", e))?;
 
     // Detect format from extension
     let format = std::path::Path::new(&path)
@@ -213,27 +337,229 @@ pub fn load_document(path: String) -> Result<DocumentData, String> {
     })
 }
 
+/// Files dropped onto the editor
+#[derive(Debug, Clone, Serialize)]
+pub struct DropResult {
+    /// Dropped documents, to open in new tabs
+    pub documents: Vec<DocumentData>,
+    /// The document with references to the dropped images inserted
+    pub content: String,
+    /// Byte offset of the cursor after the inserted images
+    pub cursor: usize,
+    pub images: Vec<DroppedImage>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// An image copied into the document's assets directory
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedImage {
+    pub source: String,
+    /// Path of the copy, relative to the document
+    pub url: String,
+}
+
+/// A dropped file that was neither opened nor inserted
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Handle files dropped onto the document in `content` at byte `offset`
+///
+/// Documents are loaded to open in new tabs. Images are copied into an
+/// `assets` directory next to the document at `path`, and an image for each
+/// is inserted at the drop position, so the document must have been saved.
+pub fn drop_files(
+    paths: Vec<String>,
+    content: String,
+    format: String,
+    path: Option<String>,
+    offset: usize,
+) -> Result<DropResult, String> {
+    use formatrix_core::assets::{import_file, is_image, AssetOptions};
+    use formatrix_core::file_ops::format_from_extension;
+    use std::path::Path;
+
+    let target =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    if !content.is_char_boundary(offset) {
+        return Err(format!("Invalid cursor offset: {}", offset));
+    }
+    let doc_dir = path
+        .as_deref()
+        .map(|p| Path::new(p).parent().unwrap_or(Path::new("")).to_path_buf());
+
+    let mut documents = Vec::new();
+    let mut images = Vec::new();
+    let mut skipped = Vec::new();
+    for dropped in paths {
+        let file = Path::new(&dropped);
+        let result = if format_from_extension(file).is_some() {
+            load_document(dropped.clone()).map(|data| documents.push(data))
+        } else if !is_image(file) {
+            Err("Not a document or image".to_string())
+        } else if let Some(doc_dir) = &doc_dir {
+            import_file(file, doc_dir, &AssetOptions::default())
+                .map(|asset| {
+                    images.push(DroppedImage {
+                        source: dropped.clone(),
+                        url: asset.url,
+                    })
+                })
+                .map_err(|e| e.to_string())
+        } else {
+            Err("Save the document before adding images".to_string())
+        };
+        if let Err(reason) = result {
+            skipped.push(SkippedFile {
+                path: dropped,
+                reason,
+            });
+        }
+    }
+
+    let mut content = content;
+    let mut cursor = offset;
+    if !images.is_empty() {
+        let urls: Vec<_> = images.iter().map(|image| image.url.as_str()).collect();
+        let markup = image_markup(target, &urls)?;
+        content.insert_str(offset, &markup);
+        cursor += markup.len();
+    }
+
+    Ok(DropResult {
+        documents,
+        content,
+        cursor,
+        images,
+        skipped,
+    })
+}
+
+/// An image pasted into a document
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachedImage {
+    /// Where the image was saved
+    pub path: String,
+    /// Path of the image, relative to the document
+    pub url: String,
+    /// Markup to insert at the cursor
+    pub markup: String,
+}
+
+/// Save pasted image data, a base64 `data:` URI, next to the document at
+/// `path` and return the markup that shows it in `format`
+pub fn attach_image(
+    data: String,
+    path: String,
+    format: String,
+    options: formatrix_core::AttachOptions,
+) -> Result<AttachedImage, String> {
+    let target =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let (saved, url) = formatrix_core::attach_image(&data, std::path::Path::new(&path), &options)
+        .map_err(|e| e.to_string())?;
+    Ok(AttachedImage {
+        path: saved.to_string_lossy().into_owned(),
+        markup: image_markup(target, &[url.as_str()])?,
+        url,
+    })
+}
+
+/// Markup for the images at `urls`, one paragraph each, with their file
+/// names as alt text
+fn image_markup(format: formatrix_core::SourceFormat, urls: &[&str]) -> Result<String, String> {
+    use formatrix_core::{Block, Document, Inline};
+
+    let mut doc = Document::new(format);
+    doc.content = urls
+        .iter()
+        .map(|url| Block::Paragraph {
+            content: vec![Inline::Image {
+                url: url.to_string(),
+                alt: std::path::Path::new(url)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                title: None,
+                width: None,
+                height: None,
+            }],
+            span: None,
+        })
+        .collect();
+    let markup = formatrix_core::formats::handler_for(format)
+        .render(&doc, &settings::render_config())
+        .map_err(|e| e.to_string())?;
+    Ok(markup.trim_end_matches('\n').to_string())
+}
+
 /// Save a document to the filesystem (synchronous — uses std::fs)
+///
+/// When the path's extension names another format than the buffer's, the
+/// document is converted to it and the conversion report returned.
 pub fn save_document(
     path: String,
     content: String,
     format: String,
-) -> Result<DocumentMeta, String> {
-    std::fs::write(&path, &content)
-        .map_err(|e| format!("Failed to write file: {}", e))?;
+) -> Result<SavedDocument, String> {
+    use formatrix_core::formats::handler_for;
+    use formatrix_core::{format_from_extension, save_file_as, ConversionReport};
+
+    let target = format_from_extension(std::path::Path::new(&path));
+    let (report, diagnostics) = match (format_from_id(&format), target) {
+        (Some(source), Some(target)) if source != target => {
+            let doc = parse_with_format(&content, &format)?;
+            let report = ConversionReport::new(&doc, handler_for(target).as_ref());
+            save_file_as(&doc, &path, target, &settings::render_config())
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+            let diagnostics = lost_feature_diagnostics(&content, source, &report);
+            (Some(report), diagnostics)
+        }
+        _ => {
+            let backup = settings::render_config().backup;
+            formatrix_core::write_atomic(&path, content.as_bytes(), backup)
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+            (None, Vec::new())
+        }
+    };
 
     let word_count = content.split_whitespace().count();
     let char_count = content.chars().count();
 
-    Ok(DocumentMeta {
-        path: Some(path),
-        format,
-        modified: false,
-        word_count,
-        char_count,
+    Ok(SavedDocument {
+        meta: DocumentMeta {
+            path: Some(path),
+            format,
+            modified: false,
+            word_count,
+            char_count,
+        },
+        report,
+        diagnostics,
     })
 }
 
+/// Locate the lost features of `report` in `content`
+///
+/// Only lossy conversions pay for the second parse that records spans.
+fn lost_feature_diagnostics(
+    content: &str,
+    format: formatrix_core::SourceFormat,
+    report: &formatrix_core::ConversionReport,
+) -> Vec<formatrix_core::Diagnostic> {
+    if report.is_lossless() {
+        return Vec::new();
+    }
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..settings::parse_config()
+    };
+    let parser = formatrix_core::IncrementalParser::new(content, format, config);
+    report.diagnostics(parser.document())
+}
+
 /// Convert document content from one format to another
 pub fn convert_to_format(
     content: String,
@@ -251,12 +577,14 @@ pub fn convert_to_format(
         return Ok(ConversionResult {
             content,
             warnings: Vec::new(),
+            report: None,
+            diagnostics: Vec::new(),
         });
     }
 
     // Parse source format
-    let parse_config = ParseConfig::default();
-    let render_config = RenderConfig::default();
+    let parse_config = settings::parse_config();
+    let render_config = settings::render_config();
 
     let doc = match from_format.as_str() {
         "txt" => PlainTextHandler::new()
@@ -313,6 +641,25 @@ pub fn convert_to_format(
         }
     };
 
+    // Report what the target format could not hold
+    let (report, diagnostics) = match (format_from_id(&from_format), format_from_id(&to_format)) {
+        (Some(source), Some(target)) => {
+            let handler = formatrix_core::formats::handler_for(target);
+            let report = formatrix_core::ConversionReport::new(&doc, handler.as_ref());
+            let diagnostics = lost_feature_diagnostics(&content, source, &report);
+            (Some(report), diagnostics)
+        }
+        _ => (None, Vec::new()),
+    };
+    let warnings = report
+        .iter()
+        .flat_map(|report| {
+            report.lost_features.iter().map(|feature| {
+                format!("{} cannot be represented in {}", feature, report.to.label())
+            })
+        })
+        .collect();
+
     // Emit conversion event
     emit_event(DocumentEvent::converted(
         &content,
@@ -323,7 +670,9 @@ pub fn convert_to_format(
 
     Ok(ConversionResult {
         content: output,
-        warnings: Vec::new(),
+        warnings,
+        report,
+        diagnostics,
     })
 }
 
@@ -343,7 +692,7 @@ pub fn parse_document(content: String, format: String) -> Result<ParsedDocument,
     };
     use formatrix_core::traits::Parser;
 
-    let parse_config = ParseConfig::default();
+    let parse_config = settings::parse_config();
 
     let doc = match format.as_str() {
         "txt" => PlainTextHandler::new()
@@ -388,8 +737,8 @@ pub fn render_document(content: String, to_format: String) -> Result<String, Str
     use formatrix_core::traits::{Parser, Renderer};
 
     // Parse as markdown by default for rendering
-    let parse_config = ParseConfig::default();
-    let render_config = RenderConfig::default();
+    let parse_config = settings::parse_config();
+    let render_config = settings::render_config();
 
     let doc = MarkdownHandler::new()
         .parse(&content, &parse_config)
@@ -433,6 +782,83 @@ pub fn detect_format(content: String) -> String {
     format.extension().to_string()
 }
 
+/// A paste into a document
+#[derive(Debug, Clone, Serialize)]
+pub struct PasteResult {
+    /// The document with the paste inserted
+    pub content: String,
+    /// The markup inserted
+    pub inserted: String,
+    /// Format id the clipboard text was read as, or `html`
+    pub detected: String,
+    /// Byte offset of the cursor after the paste
+    pub cursor: usize,
+}
+
+/// Paste clipboard `text`, or its `html` flavor when there is one, into
+/// `content` at byte `offset`, converted to the document's format
+pub fn paste_convert(
+    content: String,
+    format: String,
+    offset: usize,
+    text: String,
+    html: Option<String>,
+) -> Result<PasteResult, String> {
+    use formatrix_core::PasteSource;
+
+    let target =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    if !content.is_char_boundary(offset) {
+        return Err(format!("Invalid cursor offset: {}", offset));
+    }
+
+    let paste =
+        formatrix_core::convert_paste(&text, html.as_deref(), target, &settings::render_config())
+            .map_err(|e| e.to_string())?;
+    let detected = match paste.source {
+        PasteSource::Html => "html",
+        PasteSource::Text(format) => format_id(format),
+    };
+
+    let mut content = content;
+    content.insert_str(offset, &paste.markup);
+    Ok(PasteResult {
+        content,
+        cursor: offset + paste.markup.len(),
+        inserted: paste.markup,
+        detected: detected.to_string(),
+    })
+}
+
+/// Two documents compared block by block, for the compare view
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentComparison {
+    pub old_format: String,
+    pub new_format: String,
+    pub hunks: Vec<formatrix_core::DiffHunk>,
+}
+
+/// Compare the documents at `old_path` and `new_path`, which may be in
+/// different formats
+pub fn compare_documents(old_path: String, new_path: String) -> Result<DocumentComparison, String> {
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..settings::parse_config()
+    };
+    let open = |path: &str| {
+        formatrix_core::file_ops::open_file_with_config(path, &config)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))
+    };
+    let old = open(&old_path)?;
+    let new = open(&new_path)?;
+
+    Ok(DocumentComparison {
+        old_format: format_id(old.file_info.format).to_string(),
+        new_format: format_id(new.file_info.format).to_string(),
+        hunks: formatrix_core::diff_documents(&old.document, &new.document),
+    })
+}
+
 /// Format info for frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FormatInfo {
@@ -481,3 +907,730 @@ pub fn get_supported_formats() -> Vec<FormatInfo> {
         },
     ]
 }
+
+/// What converting between two formats does to each feature of the first
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatCapabilities {
+    pub from: String,
+    pub to: String,
+    pub features: std::collections::BTreeMap<String, formatrix_core::formats::Capability>,
+}
+
+/// Capabilities of every format pair, by frontend format id, for the
+/// format picker to annotate lossy targets
+pub fn get_capability_matrix() -> Vec<FormatCapabilities> {
+    formatrix_core::formats::capability_matrix()
+        .into_iter()
+        .map(|pair| FormatCapabilities {
+            from: format_id(pair.from).to_string(),
+            to: format_id(pair.to).to_string(),
+            features: pair.features,
+        })
+        .collect()
+}
+
+/// Parse content in one of the frontend's format ids
+fn parse_with_format(content: &str, format: &str) -> Result<formatrix_core::Document, String> {
+    use formatrix_core::formats::{
+        AsciidocHandler, DjotHandler, MarkdownHandler, OrgModeHandler, PlainTextHandler,
+        RstHandler, TypstHandler,
+    };
+    use formatrix_core::traits::Parser;
+
+    let config = settings::parse_config();
+    let result = match format {
+        "txt" => PlainTextHandler::new().parse(content, &config),
+        "md" => MarkdownHandler::new().parse(content, &config),
+        "adoc" => AsciidocHandler::new().parse(content, &config),
+        "djot" => DjotHandler::new().parse(content, &config),
+        "org" => OrgModeHandler::new().parse(content, &config),
+        "rst" => RstHandler::new().parse(content, &config),
+        "typ" => TypstHandler::new().parse(content, &config),
+        _ => return Err(format!("Unsupported format: {}", format)),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Check all links in a document
+///
+/// Local file targets are resolved relative to `path`'s directory when the
+/// document has been saved. Remote links are probed only when `check_remote`
+/// is set and the `link-check` feature is enabled.
+pub fn check_links(
+    content: String,
+    format: String,
+    path: Option<String>,
+    check_remote: bool,
+) -> Result<Vec<formatrix_core::toolkit::links::LinkCheck>, String> {
+    use formatrix_core::toolkit::links;
+
+    let doc = parse_with_format(&content, &format)?;
+    let base_dir = path
+        .as_deref()
+        .and_then(|p| std::path::Path::new(p).parent())
+        .map(|p| p.to_path_buf());
+
+    #[allow(unused_mut)]
+    let mut checks = links::check_links(&doc, base_dir.as_deref());
+
+    #[cfg(feature = "link-check")]
+    if check_remote {
+        links::check_remote_links(&mut checks, std::time::Duration::from_secs(10), 8);
+    }
+    #[cfg(not(feature = "link-check"))]
+    let _ = check_remote;
+
+    Ok(checks)
+}
+
+/// Lint a document for editor squiggles
+pub fn lint_document(
+    content: String,
+    format: String,
+) -> Result<Vec<formatrix_core::Diagnostic>, String> {
+    use formatrix_core::{LintConfig, Linter};
+
+    let doc = parse_with_format(&content, &format)?;
+    Ok(Linter::default().lint(&doc, Some(&content), &LintConfig::default()))
+}
+
+/// Find `pattern` in a document, with source spans for highlighting
+pub fn find_in_document(
+    content: String,
+    format: String,
+    pattern: String,
+    options: formatrix_core::FindOptions,
+) -> Result<Vec<formatrix_core::FindMatch>, String> {
+    let mut doc = parse_with_format(&content, &format)?;
+    doc.raw_source = Some(content);
+    doc.find(&pattern, &options).map_err(|e| e.to_string())
+}
+
+/// Result of a replace for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceResult {
+    pub content: String,
+    pub replaced: usize,
+    /// Matches that could not be found verbatim in the source, such as text
+    /// written with escapes, and were left alone
+    pub skipped: usize,
+}
+
+/// Replace every match of `pattern` in the source text
+///
+/// Only the matched text changes; the rest of the source keeps its layout.
+pub fn replace_in_document(
+    content: String,
+    format: String,
+    pattern: String,
+    replacement: String,
+    options: formatrix_core::FindOptions,
+) -> Result<ReplaceResult, String> {
+    let doc = parse_with_format(&content, &format)?;
+    let (content, matches) =
+        formatrix_core::find::replace_in_source(&doc, &content, &pattern, &replacement, &options)
+            .map_err(|e| e.to_string())?;
+
+    let skipped = matches.iter().filter(|m| m.span.is_none()).count();
+    Ok(ReplaceResult {
+        content,
+        replaced: matches.len() - skipped,
+        skipped,
+    })
+}
+
+/// Replace `pattern` in every document of the project at `root`
+///
+/// Returns the changes per file; they are written, all or none, only when
+/// `apply` is set.
+pub fn workspace_replace(
+    root: String,
+    pattern: String,
+    replacement: String,
+    options: formatrix_core::FindOptions,
+    apply: bool,
+) -> Result<formatrix_core::project::ReplacePlan, String> {
+    let mut project = formatrix_core::Project::open(&root).map_err(|e| e.to_string())?;
+    let plan = project
+        .plan_replace(&pattern, &replacement, &options)
+        .map_err(|e| e.to_string())?;
+    if apply {
+        project.apply_replace(&plan).map_err(|e| e.to_string())?;
+    }
+    Ok(plan)
+}
+
+/// Dictionary loaded by the last spell check, keyed by its path
+#[cfg(feature = "spellcheck")]
+static SPELL_CHECKER: std::sync::LazyLock<
+    std::sync::Mutex<Option<(String, formatrix_core::toolkit::spell::SpellChecker)>>,
+> = std::sync::LazyLock::new(|| std::sync::Mutex::new(None));
+
+/// Spell check a document for editor squiggles
+///
+/// `dictionary` is a Hunspell pair path without extension, e.g.
+/// `/usr/share/hunspell/en_US`; it is loaded once and reused until a
+/// different path is passed. Words in `ignored` are never reported.
+#[cfg(feature = "spellcheck")]
+pub fn spell_check(
+    content: String,
+    format: String,
+    dictionary: String,
+    ignored: Vec<String>,
+) -> Result<Vec<formatrix_core::toolkit::spell::Misspelling>, String> {
+    use formatrix_core::toolkit::spell::SpellChecker;
+
+    let doc = parse_with_format(&content, &format)?;
+
+    let mut cached = SPELL_CHECKER
+        .lock()
+        .map_err(|_| "spell checker unavailable".to_string())?;
+    if cached.as_ref().is_none_or(|(path, _)| *path != dictionary) {
+        let checker = SpellChecker::from_files(&dictionary).map_err(|e| e.to_string())?;
+        *cached = Some((dictionary, checker));
+    }
+    let (_, checker) = cached.as_ref().expect("dictionary loaded above");
+
+    Ok(checker
+        .check(&doc, Some(&content))
+        .into_iter()
+        .filter(|m| !ignored.contains(&m.word))
+        .collect())
+}
+
+/// Index of the folder searched last, keyed by its path
+static SEARCH_INDEX: std::sync::LazyLock<
+    std::sync::Mutex<Option<(String, formatrix_core::SearchIndex)>>,
+> = std::sync::LazyLock::new(|| std::sync::Mutex::new(None));
+
+/// Search every document under `dir`
+///
+/// The folder is indexed on first use and the index reused for later
+/// queries; set `reindex` after files change on disk.
+pub fn search_files(
+    dir: String,
+    query: String,
+    limit: usize,
+    reindex: bool,
+) -> Result<Vec<formatrix_core::SearchHit>, String> {
+    use formatrix_core::SearchIndex;
+
+    let mut cached = SEARCH_INDEX
+        .lock()
+        .map_err(|_| "search index unavailable".to_string())?;
+    if reindex || cached.as_ref().is_none_or(|(path, _)| *path != dir) {
+        let mut index = SearchIndex::new();
+        index.index_dir(&dir).map_err(|e| e.to_string())?;
+        *cached = Some((dir, index));
+    }
+    let (_, index) = cached.as_ref().expect("index built above");
+
+    Ok(index.search(&query, limit))
+}
+
+/// Map a core format to the frontend's format id
+pub(crate) fn format_id(format: formatrix_core::SourceFormat) -> &'static str {
+    use formatrix_core::SourceFormat;
+
+    match format {
+        SourceFormat::PlainText => "txt",
+        SourceFormat::Markdown => "md",
+        SourceFormat::AsciiDoc => "adoc",
+        SourceFormat::Djot => "djot",
+        SourceFormat::OrgMode => "org",
+        SourceFormat::ReStructuredText => "rst",
+        SourceFormat::Typst => "typ",
+    }
+}
+
+/// Map a frontend format id to a core format
+pub(crate) fn format_from_id(id: &str) -> Option<formatrix_core::SourceFormat> {
+    formatrix_core::SourceFormat::ALL
+        .into_iter()
+        .find(|&format| format_id(format) == id)
+}
+
+/// Get the built-in document templates and the fields each one needs
+pub fn list_templates() -> Vec<formatrix_core::templates::Template> {
+    formatrix_core::templates::builtin_templates().to_vec()
+}
+
+/// Start a new, unsaved document from a built-in template
+pub fn new_from_template(
+    template: String,
+    format: String,
+    fields: std::collections::HashMap<String, String>,
+) -> Result<DocumentData, String> {
+    use formatrix_core::formats::handler_for;
+    use formatrix_core::templates;
+
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let doc = templates::template(&template)
+        .and_then(|t| t.instantiate(&fields, source_format))
+        .map_err(|e| e.to_string())?;
+    let content = handler_for(source_format)
+        .render(&doc, &settings::render_config())
+        .map_err(|e| e.to_string())?;
+
+    let word_count = content.split_whitespace().count();
+    let char_count = content.chars().count();
+
+    Ok(DocumentData {
+        content,
+        meta: DocumentMeta {
+            path: None,
+            format,
+            modified: true,
+            word_count,
+            char_count,
+        },
+    })
+}
+
+/// A question the frontend answers with a dialog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConversionPrompt {
+    ChooseFormat {
+        path: String,
+        candidates: Vec<String>,
+    },
+    ConfirmLossySave {
+        path: String,
+        to_format: String,
+        lost_features: Vec<String>,
+    },
+    RewriteLink {
+        url: String,
+        suggested: String,
+    },
+}
+
+/// Dialog answers collected so far, sent back with each retry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversionDecisions {
+    /// Format id chosen for ambiguous content
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub confirm_lossy: Option<bool>,
+    /// Answers keyed by the original link url
+    #[serde(default)]
+    pub link_rewrites: std::collections::HashMap<String, formatrix_core::LinkRewrite>,
+}
+
+/// Outcome of an interactive file conversion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileConversion {
+    Done {
+        path: String,
+    },
+    /// Show `prompt`, add the answer to the decisions and call again
+    NeedsDecision {
+        prompt: ConversionPrompt,
+    },
+    /// The user declined in an earlier dialog
+    Cancelled,
+}
+
+/// Answers from [`ConversionDecisions`]; the first unanswered question is
+/// recorded and cancels the conversion so the frontend can ask it
+struct DialogInteractor {
+    decisions: ConversionDecisions,
+    prompt: Option<ConversionPrompt>,
+}
+
+impl formatrix_core::ConversionInteractor for DialogInteractor {
+    fn choose_format(
+        &mut self,
+        path: &std::path::Path,
+        candidates: &[formatrix_core::SourceFormat],
+    ) -> Option<formatrix_core::SourceFormat> {
+        let chosen = self.decisions.format.as_deref().and_then(|id| {
+            candidates
+                .iter()
+                .copied()
+                .find(|&format| format_id(format) == id)
+        });
+        if chosen.is_none() {
+            self.prompt = Some(ConversionPrompt::ChooseFormat {
+                path: path.to_string_lossy().to_string(),
+                candidates: candidates
+                    .iter()
+                    .map(|&f| format_id(f).to_string())
+                    .collect(),
+            });
+        }
+        chosen
+    }
+
+    fn confirm_lossy_save(
+        &mut self,
+        path: &std::path::Path,
+        report: &formatrix_core::ConversionReport,
+    ) -> bool {
+        match self.decisions.confirm_lossy {
+            Some(confirmed) => confirmed,
+            None => {
+                self.prompt = Some(ConversionPrompt::ConfirmLossySave {
+                    path: path.to_string_lossy().to_string(),
+                    to_format: format_id(report.to).to_string(),
+                    lost_features: report.lost_features.clone(),
+                });
+                false
+            }
+        }
+    }
+
+    fn rewrite_link(&mut self, url: &str, suggested: &str) -> Option<formatrix_core::LinkRewrite> {
+        let answer = self.decisions.link_rewrites.get(url).cloned();
+        if answer.is_none() {
+            self.prompt = Some(ConversionPrompt::RewriteLink {
+                url: url.to_string(),
+                suggested: suggested.to_string(),
+            });
+        }
+        answer
+    }
+}
+
+/// Convert a file on disk, asking the frontend to resolve anything ambiguous
+///
+/// Returns `NeedsDecision` until every question has an answer in `decisions`;
+/// nothing is written before then.
+pub fn convert_file(
+    input_path: String,
+    output_path: String,
+    decisions: ConversionDecisions,
+) -> Result<FileConversion, String> {
+    use formatrix_core::{convert_file_interactive, FileError};
+
+    let mut interactor = DialogInteractor {
+        decisions,
+        prompt: None,
+    };
+    let result = convert_file_interactive(
+        &input_path,
+        &output_path,
+        &settings::parse_config(),
+        &settings::render_config(),
+        &mut interactor,
+    );
+
+    match result {
+        Ok(()) => Ok(FileConversion::Done { path: output_path }),
+        Err(FileError::Cancelled) => Ok(match interactor.prompt {
+            Some(prompt) => FileConversion::NeedsDecision { prompt },
+            None => FileConversion::Cancelled,
+        }),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Edit history of each open buffer, keyed by its id
+static HISTORIES: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashMap<String, formatrix_core::DocumentHistory>>,
+> = std::sync::LazyLock::new(|| std::sync::Mutex::new(std::collections::HashMap::new()));
+
+/// Text of a buffer after an edit, undo or redo
+#[derive(Debug, Serialize)]
+pub struct HistoryState {
+    pub content: String,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+impl HistoryState {
+    fn of(history: &formatrix_core::DocumentHistory) -> Self {
+        Self {
+            content: history.text().to_string(),
+            can_undo: history.can_undo(),
+            can_redo: history.can_redo(),
+        }
+    }
+}
+
+/// Record the new text of buffer `id` as an undoable edit
+///
+/// The first call for a buffer starts its history from `content`.
+pub fn record_edit(id: String, format: String, content: String) -> Result<HistoryState, String> {
+    use formatrix_core::DocumentHistory;
+
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let mut histories = HISTORIES
+        .lock()
+        .map_err(|_| "history unavailable".to_string())?;
+    let history = histories.entry(id.clone()).or_insert_with(|| {
+        let config = ParseConfig {
+            preserve_spans: true,
+            ..settings::parse_config()
+        };
+        DocumentHistory::new_with_config(content.as_str(), source_format, config)
+    });
+    history.replace_text(&content);
+    update_outline(&id, history.document());
+    update_stats(&id, history.text());
+    Ok(HistoryState::of(history))
+}
+
+/// Undo the last edit to buffer `id`
+pub fn undo_document(id: String) -> Result<HistoryState, String> {
+    step_history(&id, formatrix_core::DocumentHistory::undo)
+}
+
+/// Redo the last undone edit to buffer `id`
+pub fn redo_document(id: String) -> Result<HistoryState, String> {
+    step_history(&id, formatrix_core::DocumentHistory::redo)
+}
+
+/// Forget the history of a closed buffer
+pub fn close_history(id: String) {
+    if let Ok(mut histories) = HISTORIES.lock() {
+        histories.remove(&id);
+    }
+    if let Ok(mut outlines) = OUTLINES.lock() {
+        outlines.remove(&id);
+    }
+    if let Ok(mut stats) = STATS.lock() {
+        stats.remove(&id);
+    }
+}
+
+fn step_history(
+    id: &str,
+    step: fn(&mut formatrix_core::DocumentHistory) -> bool,
+) -> Result<HistoryState, String> {
+    let mut histories = HISTORIES
+        .lock()
+        .map_err(|_| "history unavailable".to_string())?;
+    let history = histories
+        .get_mut(id)
+        .ok_or_else(|| format!("No history for buffer {}", id))?;
+    step(history);
+    update_outline(id, history.document());
+    update_stats(id, history.text());
+    Ok(HistoryState::of(history))
+}
+
+/// Last outline sent for each buffer with an outline panel, keyed by its id
+static OUTLINES: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashMap<String, Vec<formatrix_core::OutlineEntry>>>,
+> = std::sync::LazyLock::new(|| std::sync::Mutex::new(std::collections::HashMap::new()));
+
+/// Heading outline of buffer `id`, with source spans
+///
+/// From then on, edits recorded for the buffer emit an `OutlineChanged`
+/// event whenever its headings, or the lines they are on, change.
+pub fn get_document_outline(
+    id: String,
+    format: String,
+    content: String,
+) -> Result<Vec<formatrix_core::OutlineEntry>, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..settings::parse_config()
+    };
+    let parser = formatrix_core::IncrementalParser::new(content, source_format, config);
+    let outline = formatrix_core::outline(parser.document());
+    if let Ok(mut outlines) = OUTLINES.lock() {
+        outlines.insert(id, outline.clone());
+    }
+    Ok(outline)
+}
+
+/// Emit `OutlineChanged` if the outline of buffer `id` differs from the last
+/// one sent
+fn update_outline(id: &str, doc: &formatrix_core::Document) {
+    let Ok(mut outlines) = OUTLINES.lock() else {
+        return;
+    };
+    let Some(last) = outlines.get_mut(id) else {
+        return;
+    };
+    let outline = formatrix_core::outline(doc);
+    if !same_outline(last, &outline) {
+        *last = outline.clone();
+        emit_event(DocumentEvent::outline_changed(id, outline));
+    }
+}
+
+/// Whether two outlines show the same, ignoring byte offsets, which move
+/// with every edit above a heading
+fn same_outline(a: &[formatrix_core::OutlineEntry], b: &[formatrix_core::OutlineEntry]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(x, y)| {
+            x.level == y.level
+                && x.title == y.title
+                && x.id == y.id
+                && x.block_path == y.block_path
+                && x.span.map(|s| s.line) == y.span.map(|s| s.line)
+                && same_outline(&x.children, &y.children)
+        })
+}
+
+/// One section of a document on its own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedSection {
+    /// The section as markup in the document's format
+    pub content: String,
+    pub document: formatrix_core::Document,
+}
+
+/// The section under the heading with anchor `heading_id`, for focus mode
+/// or exporting a single chapter
+///
+/// Anchors are the `id`s of the outline entries.
+pub fn extract_section(
+    content: String,
+    format: String,
+    heading_id: String,
+) -> Result<ExtractedSection, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let doc = parse_with_format(&content, &format)?;
+    let document = formatrix_core::extract_section(&doc, &heading_id)
+        .ok_or_else(|| format!("No heading with id {}", heading_id))?;
+    let content = formatrix_core::formats::handler_for(source_format)
+        .render(&document, &settings::render_config())
+        .map_err(|e| e.to_string())?;
+    Ok(ExtractedSection { content, document })
+}
+
+/// An open tab's buffer, as passed to [`merge_tabs`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabContent {
+    pub content: String,
+    pub format: String,
+}
+
+/// The result of merging tabs into one document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedTabs {
+    /// The merged document as markup in the requested format
+    pub content: String,
+    pub document: formatrix_core::Document,
+}
+
+/// Join the documents in `tabs`, in order, into one document in `format`
+///
+/// Each tab may be in its own format. Titles of tabs after the first become
+/// headings, and anchors that clash with earlier tabs are renamed.
+pub fn merge_tabs(
+    tabs: Vec<TabContent>,
+    format: String,
+    options: formatrix_core::MergeOptions,
+) -> Result<MergedTabs, String> {
+    let target =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let docs = tabs
+        .iter()
+        .map(|tab| parse_with_format(&tab.content, &tab.format))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut document =
+        formatrix_core::concat(docs, &options).ok_or_else(|| "No tabs to merge".to_string())?;
+    document.source_format = target;
+    let content = formatrix_core::formats::handler_for(target)
+        .render(&document, &settings::render_config())
+        .map_err(|e| e.to_string())?;
+    Ok(MergedTabs { content, document })
+}
+
+/// Status bar figures for a buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentStats {
+    pub word_count: usize,
+    pub char_count: usize,
+    /// Counts for the selected text; zero without a selection
+    pub selection_word_count: usize,
+    pub selection_char_count: usize,
+    /// Line and column of the cursor, from 1; columns count characters
+    pub line: usize,
+    pub column: usize,
+    /// Title of the innermost section holding the cursor
+    pub section: Option<String>,
+}
+
+/// Last word and character counts sent for each buffer with a status bar,
+/// keyed by its id
+static STATS: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashMap<String, (usize, usize)>>,
+> = std::sync::LazyLock::new(|| std::sync::Mutex::new(std::collections::HashMap::new()));
+
+/// Status bar figures for buffer `id` with the cursor at byte `cursor`,
+/// and the selection, if any, running from `anchor` to it
+///
+/// From then on, edits recorded for the buffer emit a `StatsChanged` event
+/// whenever its word or character count changes.
+pub fn get_document_stats(
+    id: String,
+    format: String,
+    content: String,
+    cursor: usize,
+    anchor: Option<usize>,
+) -> Result<DocumentStats, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    for offset in std::iter::once(cursor).chain(anchor) {
+        if !content.is_char_boundary(offset) {
+            return Err(format!("Invalid cursor offset: {}", offset));
+        }
+    }
+
+    let selected = match anchor {
+        Some(anchor) => &content[anchor.min(cursor)..anchor.max(cursor)],
+        None => "",
+    };
+    let before = &content[..cursor];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..settings::parse_config()
+    };
+    let parser = formatrix_core::IncrementalParser::new(content.as_str(), source_format, config);
+    let section = section_at(&formatrix_core::outline(parser.document()), cursor);
+
+    let stats = DocumentStats {
+        word_count: content.split_whitespace().count(),
+        char_count: content.chars().count(),
+        selection_word_count: selected.split_whitespace().count(),
+        selection_char_count: selected.chars().count(),
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        section,
+    };
+    if let Ok(mut counts) = STATS.lock() {
+        counts.insert(id, (stats.word_count, stats.char_count));
+    }
+    Ok(stats)
+}
+
+/// Title of the last heading at or before `offset`, at any depth
+fn section_at(outline: &[formatrix_core::OutlineEntry], offset: usize) -> Option<String> {
+    let entry = outline
+        .iter()
+        .take_while(|entry| entry.span.is_some_and(|s| s.start <= offset))
+        .last()?;
+    section_at(&entry.children, offset).or_else(|| Some(entry.title.clone()))
+}
+
+/// Emit `StatsChanged` if the counts of buffer `id` differ from the last
+/// ones sent
+fn update_stats(id: &str, content: &str) {
+    let Ok(mut stats) = STATS.lock() else {
+        return;
+    };
+    let Some(last) = stats.get_mut(id) else {
+        return;
+    };
+    let counts = (content.split_whitespace().count(), content.chars().count());
+    if *last != counts {
+        *last = counts;
+        emit_event(DocumentEvent::stats_changed(id, counts.0, counts.1));
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Link target completion
+//!
+//! When the user types `[[` or `](`, the editor asks [`complete_link`] for
+//! targets starting with, or else containing, what follows: headings of the
+//! buffer as `#anchor`s, documents in the workspace as paths relative to the
+//! buffer's file, and web addresses linked to lately.
+//!
+//! Web links in the buffer are remembered in the session as they are seen,
+//! so a URL used in one document is offered in the next.
+
+use crate::commands::format_from_id;
+use crate::{session, settings, workspace};
+use formatrix_core::formats::handler_for;
+use formatrix_core::toolkit::links::{classify, extract_links, LinkTarget};
+use formatrix_core::OutlineEntry;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+/// Most completions returned
+const MAX_COMPLETIONS: usize = 50;
+
+/// What a completion points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    Heading,
+    File,
+    Url,
+}
+
+/// One suggested link target
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCompletion {
+    pub kind: CompletionKind,
+    /// Shown in the list: a heading's title, a file's name or the URL
+    pub label: String,
+    /// Inserted as the link target
+    pub target: String,
+}
+
+/// Link targets matching `prefix`, for the buffer `content`
+///
+/// `path` is the buffer's file, if it has one; file targets are relative to
+/// its directory, or to the workspace root for an unsaved buffer. Files are
+/// only offered while a workspace is open.
+pub fn complete_link(
+    prefix: String,
+    content: String,
+    format: String,
+    path: Option<String>,
+) -> Result<Vec<LinkCompletion>, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let doc = handler_for(source_format)
+        .parse(&content, &settings::parse_config())
+        .map_err(|e| e.to_string())?;
+
+    let mut candidates = Vec::new();
+    push_headings(&formatrix_core::outline(&doc), &mut candidates);
+    if let Ok(tree) = workspace::workspace_tree() {
+        // The tree has canonical paths
+        let current = path
+            .as_deref()
+            .map(|p| std::fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p)));
+        let current = current.as_deref();
+        let base = match current.and_then(Path::parent) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from(&tree.path),
+        };
+        push_files(&tree, current, &base, &mut candidates);
+    }
+
+    let used: Vec<String> = extract_links(&doc)
+        .into_iter()
+        .filter(|link| classify(&link.url) == LinkTarget::Remote)
+        .map(|link| link.url)
+        .collect();
+    for url in session::remember_urls(used)? {
+        candidates.push(LinkCompletion {
+            kind: CompletionKind::Url,
+            label: url.clone(),
+            target: url,
+        });
+    }
+
+    // Prefix matches first, then the rest; each group keeps its order
+    let prefix = prefix.to_lowercase();
+    let starts = |c: &LinkCompletion| {
+        [&c.label, &c.target]
+            .iter()
+            .any(|text| text.to_lowercase().starts_with(&prefix))
+    };
+    let contains = |c: &LinkCompletion| {
+        [&c.label, &c.target]
+            .iter()
+            .any(|text| text.to_lowercase().contains(&prefix))
+    };
+    let (mut matches, rest): (Vec<_>, Vec<_>) = candidates.into_iter().partition(starts);
+    matches.extend(rest.into_iter().filter(contains));
+    matches.truncate(MAX_COMPLETIONS);
+    Ok(matches)
+}
+
+fn push_headings(entries: &[OutlineEntry], out: &mut Vec<LinkCompletion>) {
+    for entry in entries {
+        out.push(LinkCompletion {
+            kind: CompletionKind::Heading,
+            label: entry.title.clone(),
+            target: format!("#{}", entry.id),
+        });
+        push_headings(&entry.children, out);
+    }
+}
+
+fn push_files(
+    node: &workspace::FileNode,
+    current: Option<&Path>,
+    base: &Path,
+    out: &mut Vec<LinkCompletion>,
+) {
+    for child in &node.children {
+        let path = Path::new(&child.path);
+        if child.is_dir {
+            push_files(child, current, base, out);
+        } else if current != Some(path) {
+            out.push(LinkCompletion {
+                kind: CompletionKind::File,
+                label: child.name.clone(),
+                target: relative_link(base, path),
+            });
+        }
+    }
+}
+
+/// `path` as a link from a document in `base`, with `/` separators
+fn relative_link(base: &Path, path: &Path) -> String {
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); base.len() - common];
+    parts.extend(
+        path[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Export dialog backend
+//!
+//! [`export_document`] checks its arguments, then parses, renders and writes
+//! on a worker thread so the window stays responsive. Progress is reported
+//! as `ExportProgress` document events, one per stage, ending with `done`
+//! or `failed`.
+//!
+//! Targets are the seven editable formats and HTML. PDF, EPUB and DOCX are
+//! listed by [`export_targets`] but not available yet, so the dialog can
+//! show them disabled.
+
+use crate::commands::{
+    emit_event, format_from_id, get_supported_formats, DocumentEvent, FormatInfo,
+};
+use crate::settings;
+use formatrix_core::formats::handler_for;
+use formatrix_core::{
+    embed_images, render_html, render_html_page, write_atomic, RenderConfig, SourceFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Options from the export dialog
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Column to wrap at; 0 leaves lines as they are
+    pub line_width: usize,
+    /// Inline local images, relative to the destination, as data URIs
+    pub embed_images: bool,
+    /// For HTML, write only the body rather than a complete page
+    pub fragment: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            line_width: settings::render_config().line_width,
+            embed_images: false,
+            fragment: false,
+        }
+    }
+}
+
+/// A target offered by the export dialog
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportTarget {
+    #[serde(flatten)]
+    pub format: FormatInfo,
+    pub available: bool,
+}
+
+/// Targets without a renderer yet: id and label
+const PLANNED_TARGETS: [(&str, &str); 3] = [("pdf", "PDF"), ("epub", "EPUB"), ("docx", "Word")];
+
+/// Every export target, in the order the dialog lists them
+pub fn export_targets() -> Vec<ExportTarget> {
+    let target = |id: &str, label: &str, available| ExportTarget {
+        format: FormatInfo {
+            id: id.to_string(),
+            label: label.to_string(),
+            extension: id.to_string(),
+        },
+        available,
+    };
+    let mut targets: Vec<ExportTarget> = get_supported_formats()
+        .into_iter()
+        .map(|format| ExportTarget {
+            format,
+            available: true,
+        })
+        .collect();
+    targets.push(target("html", "HTML", true));
+    targets.extend(PLANNED_TARGETS.map(|(id, label)| target(id, label, false)));
+    targets
+}
+
+/// Start exporting `content` to `destination` as `target`
+///
+/// Returns the job id that progress events carry.
+pub fn export_document(
+    content: String,
+    format: String,
+    target: String,
+    options: ExportOptions,
+    destination: String,
+) -> Result<String, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let target_format = format_from_id(&target);
+    if target_format.is_none() && target != "html" {
+        return Err(match PLANNED_TARGETS.iter().find(|(id, _)| *id == target) {
+            Some((_, name)) => format!("Export to {} is not available yet", name),
+            None => format!("Unsupported export format: {}", target),
+        });
+    }
+
+    static JOBS: AtomicU64 = AtomicU64::new(0);
+    let job = format!("export-{}", JOBS.fetch_add(1, Ordering::Relaxed));
+    let id = job.clone();
+    std::thread::spawn(move || {
+        let progress = |stage: &str| emit_event(DocumentEvent::export_progress(&id, stage, None));
+        let destination = PathBuf::from(destination);
+        let result = run(
+            &content,
+            source_format,
+            target_format,
+            &options,
+            &destination,
+            &progress,
+        );
+        let (stage, error) = match result {
+            Ok(()) => ("done", None),
+            Err(e) => ("failed", Some(e)),
+        };
+        emit_event(DocumentEvent::export_progress(&id, stage, error));
+    });
+    Ok(job)
+}
+
+fn run(
+    content: &str,
+    format: SourceFormat,
+    target: Option<SourceFormat>,
+    options: &ExportOptions,
+    destination: &Path,
+    progress: &dyn Fn(&str),
+) -> Result<(), String> {
+    progress("parsing");
+    let mut doc = handler_for(format)
+        .parse(content, &settings::parse_config())
+        .map_err(|e| e.to_string())?;
+
+    let config = RenderConfig {
+        line_width: options.line_width,
+        embed_images: options.embed_images,
+        ..settings::render_config()
+    };
+    match target {
+        Some(target) => {
+            progress("writing");
+            formatrix_core::save_file_as(&doc, destination, target, &config)
+                .map_err(|e| e.to_string())
+        }
+        None => {
+            progress("rendering");
+            if options.embed_images {
+                let base = destination.parent().unwrap_or(Path::new(""));
+                embed_images(&mut doc, base).map_err(|e| e.to_string())?;
+            }
+            let html = if options.fragment {
+                render_html(&doc)
+            } else {
+                render_html_page(&doc)
+            };
+            progress("writing");
+            write_atomic(destination, html.as_bytes(), config.backup).map_err(|e| e.to_string())
+        }
+    }
+}
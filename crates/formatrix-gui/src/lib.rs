@@ -6,5 +6,16 @@
 
 #![forbid(unsafe_code)]
 pub mod commands;
+pub mod completion;
+pub mod export;
+pub mod preview;
+pub mod print;
+pub mod session;
+pub mod settings;
+#[cfg(feature = "spellcheck")]
+pub mod spelling;
+pub mod tables;
+pub mod windows;
+pub mod workspace;
 
 pub use commands::*;
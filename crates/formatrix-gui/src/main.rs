@@ -8,6 +8,17 @@
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod commands;
+mod completion;
+mod export;
+mod preview;
+mod print;
+mod session;
+mod settings;
+#[cfg(feature = "spellcheck")]
+mod spelling;
+mod tables;
+mod windows;
+mod workspace;
 
 fn main() -> Result<(), gossamer_rs::Error> {
     // Initialize logging
@@ -117,11 +128,906 @@ fn main() -> Result<(), gossamer_rs::Error> {
         serde_json::to_value(result).map_err(|e| e.to_string())
     });
 
+    app.command("paste_convert", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let text = payload["text"]
+            .as_str()
+            .ok_or_else(|| "missing 'text' argument".to_string())?
+            .to_string();
+        let html = payload["html"].as_str().map(|s| s.to_string());
+        let result = commands::paste_convert(content, format, offset, text, html)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("drop_files", |payload| {
+        let paths = payload["paths"]
+            .as_array()
+            .ok_or_else(|| "missing 'paths' argument".to_string())?
+            .iter()
+            .filter_map(|p| p.as_str().map(|s| s.to_string()))
+            .collect();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let path = payload["path"].as_str().map(|s| s.to_string());
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let result = commands::drop_files(paths, content, format, path, offset)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("attach_image", |payload| {
+        let data = payload["data"]
+            .as_str()
+            .ok_or_else(|| "missing 'data' argument".to_string())?
+            .to_string();
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::attach_image(data, path, format, options)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("compare_documents", |payload| {
+        let old_path = payload["old_path"]
+            .as_str()
+            .ok_or_else(|| "missing 'old_path' argument".to_string())?
+            .to_string();
+        let new_path = payload["new_path"]
+            .as_str()
+            .ok_or_else(|| "missing 'new_path' argument".to_string())?
+            .to_string();
+        let result = commands::compare_documents(old_path, new_path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
     app.command("get_supported_formats", |_payload| {
         let result = commands::get_supported_formats();
         serde_json::to_value(result).map_err(|e| e.to_string())
     });
 
+    app.command("get_capability_matrix", |_payload| {
+        let result = commands::get_capability_matrix();
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("check_links", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let path = payload["path"].as_str().map(|s| s.to_string());
+        let check_remote = payload["check_remote"].as_bool().unwrap_or(false);
+        let result = commands::check_links(content, format, path, check_remote)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("lint_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let result = commands::lint_document(content, format)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("find_in_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let pattern = payload["pattern"]
+            .as_str()
+            .ok_or_else(|| "missing 'pattern' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::find_in_document(content, format, pattern, options)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("replace_in_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let pattern = payload["pattern"]
+            .as_str()
+            .ok_or_else(|| "missing 'pattern' argument".to_string())?
+            .to_string();
+        let replacement = payload["replacement"]
+            .as_str()
+            .ok_or_else(|| "missing 'replacement' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::replace_in_document(content, format, pattern, replacement, options)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("workspace_replace", |payload| {
+        let root = payload["root"]
+            .as_str()
+            .ok_or_else(|| "missing 'root' argument".to_string())?
+            .to_string();
+        let pattern = payload["pattern"]
+            .as_str()
+            .ok_or_else(|| "missing 'pattern' argument".to_string())?
+            .to_string();
+        let replacement = payload["replacement"]
+            .as_str()
+            .ok_or_else(|| "missing 'replacement' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let apply = payload["apply"].as_bool().unwrap_or(false);
+        let result = commands::workspace_replace(root, pattern, replacement, options, apply)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    #[cfg(feature = "spellcheck")]
+    app.command("spell_check", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let dictionary = payload["dictionary"]
+            .as_str()
+            .ok_or_else(|| "missing 'dictionary' argument".to_string())?
+            .to_string();
+        let ignored = payload["ignored"]
+            .as_array()
+            .map(|words| {
+                words
+                    .iter()
+                    .filter_map(|w| w.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let result = commands::spell_check(content, format, dictionary, ignored)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    #[cfg(feature = "spellcheck")]
+    app.command("spellcheck_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let result = spelling::spellcheck_document(content, format)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    #[cfg(feature = "spellcheck")]
+    app.command("add_to_dictionary", |payload| {
+        let word = payload["word"]
+            .as_str()
+            .ok_or_else(|| "missing 'word' argument".to_string())?
+            .to_string();
+        spelling::add_to_dictionary(word)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("complete_link", |payload| {
+        let prefix = payload["prefix"].as_str().unwrap_or("").to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let path = payload["path"].as_str().map(|s| s.to_string());
+        let result = completion::complete_link(prefix, content, format, path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("add_recent_url", |payload| {
+        let url = payload["url"]
+            .as_str()
+            .ok_or_else(|| "missing 'url' argument".to_string())?
+            .to_string();
+        session::add_recent_url(url)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("search_files", |payload| {
+        let dir = payload["dir"]
+            .as_str()
+            .ok_or_else(|| "missing 'dir' argument".to_string())?
+            .to_string();
+        let query = payload["query"]
+            .as_str()
+            .ok_or_else(|| "missing 'query' argument".to_string())?
+            .to_string();
+        let limit = payload["limit"].as_u64().unwrap_or(50) as usize;
+        let reindex = payload["reindex"].as_bool().unwrap_or(false);
+        let result = commands::search_files(dir, query, limit, reindex)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("list_templates", |_payload| {
+        let result = commands::list_templates();
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("new_from_template", |payload| {
+        let template = payload["template"]
+            .as_str()
+            .ok_or_else(|| "missing 'template' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let fields = match &payload["fields"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::new_from_template(template, format, fields)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("convert_file", |payload| {
+        let input_path = payload["input_path"]
+            .as_str()
+            .ok_or_else(|| "missing 'input_path' argument".to_string())?
+            .to_string();
+        let output_path = payload["output_path"]
+            .as_str()
+            .ok_or_else(|| "missing 'output_path' argument".to_string())?
+            .to_string();
+        let decisions = match &payload["decisions"] {
+            serde_json::Value::Null => commands::ConversionDecisions::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::convert_file(input_path, output_path, decisions)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("restore_session", |_payload| {
+        let result = session::restore_session()?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("save_session", |payload| {
+        let session = serde_json::from_value(payload["session"].clone())
+            .map_err(|e| format!("invalid 'session' argument: {}", e))?;
+        session::save_session(session)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("add_recent_file", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let result = session::add_recent_file(path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("enable_autosave", |payload| {
+        let interval_secs = match payload["interval_secs"].as_u64() {
+            Some(secs) => secs,
+            None => settings::get_settings()?.autosave_interval,
+        };
+        session::enable_autosave(interval_secs)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("autosave_buffer", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let path = payload["path"].as_str().map(|s| s.to_string());
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        session::autosave_buffer(id, path, format, content)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("discard_draft", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        session::discard_draft(id)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("recoverable_drafts", |_payload| {
+        let result = session::recoverable_drafts()?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("record_edit", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let result = commands::record_edit(id, format, content)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("undo_document", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let result = commands::undo_document(id)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("redo_document", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let result = commands::redo_document(id)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("close_history", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        commands::close_history(id);
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("print_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = print::print_document(content, format, options)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("get_settings", |_payload| {
+        let result = settings::get_settings()?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("update_settings", |payload| {
+        let changes = match &payload["changes"] {
+            serde_json::Value::Object(_) => payload["changes"].clone(),
+            _ => return Err("missing 'changes' argument".to_string()),
+        };
+        let result = settings::update_settings(changes)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("export_targets", |_payload| {
+        let result = export::export_targets();
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("export_document", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let target = payload["target"]
+            .as_str()
+            .ok_or_else(|| "missing 'target' argument".to_string())?
+            .to_string();
+        let destination = payload["destination"]
+            .as_str()
+            .ok_or_else(|| "missing 'destination' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = export::export_document(content, format, target, options, destination)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("get_document_outline", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let result = commands::get_document_outline(id, format, content)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("extract_section", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let heading_id = payload["heading_id"]
+            .as_str()
+            .ok_or_else(|| "missing 'heading_id' argument".to_string())?
+            .to_string();
+        let result = commands::extract_section(content, format, heading_id)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("merge_tabs", |payload| {
+        let tabs = serde_json::from_value(payload["tabs"].clone())
+            .map_err(|e| format!("invalid 'tabs' argument: {}", e))?;
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let options = match &payload["options"] {
+            serde_json::Value::Null => Default::default(),
+            value => serde_json::from_value(value.clone()).map_err(|e| e.to_string())?,
+        };
+        let result = commands::merge_tabs(tabs, format, options)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("get_document_stats", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let cursor = payload["cursor"]
+            .as_u64()
+            .ok_or_else(|| "missing 'cursor' argument".to_string())? as usize;
+        let anchor = payload["anchor"].as_u64().map(|a| a as usize);
+        let result = commands::get_document_stats(id, format, content, cursor, anchor)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("render_preview", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let result = preview::render_preview(id, format, content)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("render_preview_patch", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let result = preview::render_preview_patch(id, content)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("preview_block_at", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let result = preview::preview_block_at(id, offset)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("preview_source_of", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        let block = payload["block"]
+            .as_str()
+            .ok_or_else(|| "missing 'block' argument".to_string())?
+            .to_string();
+        let result = preview::preview_source_of(id, block)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("close_preview", |payload| {
+        let id = payload["id"]
+            .as_str()
+            .ok_or_else(|| "missing 'id' argument".to_string())?
+            .to_string();
+        preview::close_preview(id);
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("open_view", |payload| {
+        let window = payload["window"]
+            .as_str()
+            .unwrap_or(windows::MAIN_WINDOW)
+            .to_string();
+        let path = payload["path"].as_str().map(|s| s.to_string());
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let result = windows::open_view(window, path, format, content)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("open_in_new_window", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let result = windows::open_in_new_window(path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("split_view", |payload| {
+        let view = payload["view"]
+            .as_str()
+            .ok_or_else(|| "missing 'view' argument".to_string())?
+            .to_string();
+        let result = windows::split_view(view)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("update_view", |payload| {
+        let view = payload["view"]
+            .as_str()
+            .ok_or_else(|| "missing 'view' argument".to_string())?
+            .to_string();
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        windows::update_view(view, content)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("get_view", |payload| {
+        let view = payload["view"]
+            .as_str()
+            .ok_or_else(|| "missing 'view' argument".to_string())?
+            .to_string();
+        let result = windows::get_view(view)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("window_views", |payload| {
+        let window = payload["window"]
+            .as_str()
+            .ok_or_else(|| "missing 'window' argument".to_string())?
+            .to_string();
+        let result = windows::window_views(window)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("close_view", |payload| {
+        let view = payload["view"]
+            .as_str()
+            .ok_or_else(|| "missing 'view' argument".to_string())?
+            .to_string();
+        windows::close_view(view)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("close_window", |payload| {
+        let window = payload["window"]
+            .as_str()
+            .ok_or_else(|| "missing 'window' argument".to_string())?
+            .to_string();
+        windows::close_window(window)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("open_workspace", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let result = workspace::open_workspace(path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("close_workspace", |_payload| {
+        workspace::close_workspace()?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("workspace_tree", |_payload| {
+        let result = workspace::workspace_tree()?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("watch_workspace", |_payload| {
+        workspace::watch_workspace()?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("create_file", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let result = workspace::create_file(path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("create_directory", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        let result = workspace::create_directory(path)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("rename_file", |payload| {
+        let from = payload["from"]
+            .as_str()
+            .ok_or_else(|| "missing 'from' argument".to_string())?
+            .to_string();
+        let to = payload["to"]
+            .as_str()
+            .ok_or_else(|| "missing 'to' argument".to_string())?
+            .to_string();
+        let result = workspace::rename_file(from, to)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("delete_file", |payload| {
+        let path = payload["path"]
+            .as_str()
+            .ok_or_else(|| "missing 'path' argument".to_string())?
+            .to_string();
+        workspace::delete_file(path)?;
+        Ok(serde_json::json!(null))
+    });
+
+    app.command("insert_table", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let rows = payload["rows"]
+            .as_u64()
+            .ok_or_else(|| "missing 'rows' argument".to_string())? as usize;
+        let columns = payload["columns"]
+            .as_u64()
+            .ok_or_else(|| "missing 'columns' argument".to_string())?
+            as usize;
+        let header = payload["header"].as_bool().unwrap_or(true);
+        let result = tables::insert_table(content, format, offset, rows, columns, header)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("insert_table_row", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let at = payload["at"]
+            .as_u64()
+            .ok_or_else(|| "missing 'at' argument".to_string())? as usize;
+        let result = tables::insert_table_row(content, format, offset, at)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("remove_table_row", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let index = payload["index"]
+            .as_u64()
+            .ok_or_else(|| "missing 'index' argument".to_string())? as usize;
+        let result = tables::remove_table_row(content, format, offset, index)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("insert_table_column", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let at = payload["at"]
+            .as_u64()
+            .ok_or_else(|| "missing 'at' argument".to_string())? as usize;
+        let result = tables::insert_table_column(content, format, offset, at)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("remove_table_column", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let index = payload["index"]
+            .as_u64()
+            .ok_or_else(|| "missing 'index' argument".to_string())? as usize;
+        let result = tables::remove_table_column(content, format, offset, index)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("toggle_table_header", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let result = tables::toggle_table_header(content, format, offset)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
+    app.command("set_table_alignment", |payload| {
+        let content = payload["content"]
+            .as_str()
+            .ok_or_else(|| "missing 'content' argument".to_string())?
+            .to_string();
+        let format = payload["format"]
+            .as_str()
+            .ok_or_else(|| "missing 'format' argument".to_string())?
+            .to_string();
+        let offset = payload["offset"]
+            .as_u64()
+            .ok_or_else(|| "missing 'offset' argument".to_string())? as usize;
+        let column = payload["column"]
+            .as_u64()
+            .ok_or_else(|| "missing 'column' argument".to_string())? as usize;
+        let alignment = serde_json::from_value(payload["alignment"].clone())
+            .map_err(|e| format!("invalid 'alignment' argument: {}", e))?;
+        let result = tables::set_table_alignment(content, format, offset, column, alignment)?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    });
+
     app.run();
     Ok(())
 }
@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Live HTML preview with incremental updates
+//!
+//! [`render_preview`] renders a buffer as a list of HTML blocks, each with an
+//! id the webview puts on the block's element. After an edit,
+//! [`render_preview_patch`] re-parses only the regions the edit touched and
+//! returns just the blocks that changed, so the webview patches the DOM in
+//! place instead of replacing the whole preview.
+//!
+//! [`preview_block_at`] and [`preview_source_of`] map between source offsets
+//! and block ids for scroll synchronization and click-to-source. Raw HTML in
+//! the document is sanitized, since the preview runs in the app's webview.
+
+use formatrix_core::ast::{Block, Span};
+use formatrix_core::toolkit::sanitize::{sanitize, SanitizeConfig};
+use formatrix_core::{render_html, Document, IncrementalParser, ParseConfig, TextEdit};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// One rendered block
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewBlock {
+    pub id: String,
+    pub html: String,
+}
+
+/// The whole preview of a buffer
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    /// `lang` and `dir` for the preview's root element
+    pub lang: Option<String>,
+    pub dir: Option<&'static str>,
+    pub blocks: Vec<PreviewBlock>,
+}
+
+/// Changes to apply to the preview after an edit
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreviewPatch {
+    pub lang: Option<String>,
+    pub dir: Option<&'static str>,
+    /// Ids of the blocks to remove
+    pub remove: Vec<String>,
+    /// Id of the block to insert after; `None` to insert at the top
+    pub after: Option<String>,
+    pub insert: Vec<PreviewBlock>,
+}
+
+struct PreviewState {
+    parser: IncrementalParser,
+    /// Id and HTML of each top-level block
+    blocks: Vec<PreviewBlock>,
+    next_id: u64,
+}
+
+impl PreviewState {
+    fn add_block(&mut self, html: String) -> PreviewBlock {
+        self.next_id += 1;
+        PreviewBlock {
+            id: format!("b{}", self.next_id),
+            html,
+        }
+    }
+
+    fn render(&self, block: &Block) -> String {
+        let mut single = Document::new(self.parser.document().source_format);
+        single.content.push(block.clone());
+        sanitize(&mut single, &SanitizeConfig::default());
+        render_html(&single)
+    }
+
+    fn root(&self) -> (Option<String>, Option<&'static str>) {
+        let meta = &self.parser.document().meta;
+        let dir = meta.text_direction().map(|d| d.as_str());
+        (meta.language.clone(), dir)
+    }
+}
+
+/// Previews of open buffers, keyed by buffer id
+static PREVIEWS: LazyLock<Mutex<HashMap<String, PreviewState>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Render buffer `id` from scratch
+pub fn render_preview(id: String, format: String, content: String) -> Result<Preview, String> {
+    let source_format = crate::commands::format_from_id(&format)
+        .ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..Default::default()
+    };
+    let mut state = PreviewState {
+        parser: IncrementalParser::new(content, source_format, config),
+        blocks: Vec::new(),
+        next_id: 0,
+    };
+    let html: Vec<String> = state
+        .parser
+        .document()
+        .content
+        .iter()
+        .map(|b| state.render(b))
+        .collect();
+    state.blocks = html.into_iter().map(|h| state.add_block(h)).collect();
+
+    let (lang, dir) = state.root();
+    let preview = Preview {
+        lang,
+        dir,
+        blocks: state.blocks.clone(),
+    };
+    lock_previews()?.insert(id, state);
+    Ok(preview)
+}
+
+/// Bring the preview of buffer `id` up to date with `content`
+///
+/// Blocks that render the same as before keep their ids and are not sent.
+pub fn render_preview_patch(id: String, content: String) -> Result<PreviewPatch, String> {
+    let mut previews = lock_previews()?;
+    let state = previews
+        .get_mut(&id)
+        .ok_or_else(|| format!("No preview for buffer {}", id))?;
+
+    let edit = TextEdit::diff(state.parser.text(), &content);
+    let (lang, dir) = state.root();
+    if edit.is_empty() {
+        return Ok(PreviewPatch {
+            lang,
+            dir,
+            ..Default::default()
+        });
+    }
+    let old_len = state.blocks.len();
+    let reparsed = state.parser.apply(&edit).map_err(|e| e.to_string())?;
+    let new_len = state.parser.document().content.len();
+    // Old blocks in `start..old_end` became the reparsed ones; skip any at
+    // either end that render the same
+    let mut start = reparsed.start;
+    let mut old_end = reparsed.end + old_len - new_len;
+    let mut html: Vec<String> = state.parser.document().content[reparsed.clone()]
+        .iter()
+        .map(|b| state.render(b))
+        .collect();
+    while start < old_end && !html.is_empty() && state.blocks[start].html == html[0] {
+        html.remove(0);
+        start += 1;
+    }
+    while start < old_end && html.last() == Some(&state.blocks[old_end - 1].html) {
+        html.pop();
+        old_end -= 1;
+    }
+
+    let insert: Vec<PreviewBlock> = html.into_iter().map(|h| state.add_block(h)).collect();
+    let remove = state
+        .blocks
+        .splice(start..old_end, insert.clone())
+        .map(|b| b.id)
+        .collect();
+    let (lang, dir) = state.root();
+    Ok(PreviewPatch {
+        lang,
+        dir,
+        remove,
+        after: start.checked_sub(1).map(|i| state.blocks[i].id.clone()),
+        insert,
+    })
+}
+
+/// Id of the block at byte `offset` of the source, or the last block before
+/// it
+pub fn preview_block_at(id: String, offset: usize) -> Result<Option<String>, String> {
+    let previews = lock_previews()?;
+    let state = previews
+        .get(&id)
+        .ok_or_else(|| format!("No preview for buffer {}", id))?;
+    let index = state
+        .parser
+        .document()
+        .content
+        .iter()
+        .rposition(|b| b.span().is_some_and(|s| s.start <= offset));
+    Ok(index.map(|i| state.blocks[i].id.clone()))
+}
+
+/// Source span of preview block `block`, if known
+pub fn preview_source_of(id: String, block: String) -> Result<Option<Span>, String> {
+    let previews = lock_previews()?;
+    let state = previews
+        .get(&id)
+        .ok_or_else(|| format!("No preview for buffer {}", id))?;
+    let index = state.blocks.iter().position(|b| b.id == block);
+    Ok(index.and_then(|i| state.parser.document().content[i].span()))
+}
+
+/// Forget the preview of a closed buffer
+pub fn close_preview(id: String) {
+    if let Ok(mut previews) = PREVIEWS.lock() {
+        previews.remove(&id);
+    }
+}
+
+fn lock_previews() -> Result<std::sync::MutexGuard<'static, HashMap<String, PreviewState>>, String>
+{
+    PREVIEWS
+        .lock()
+        .map_err(|_| "preview unavailable".to_string())
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Print backend
+//!
+//! [`print_document`] renders a buffer as a complete HTML page with print
+//! CSS, for the webview to load and hand to the OS print dialog. Page size
+//! and margins go in an `@page` rule; the running header and footer go in
+//! its margin boxes, where the print engine counts pages. Raw HTML in the
+//! document is sanitized, as for the preview.
+//!
+//! PDF comes from the print dialog's "Save as PDF". Typst could produce it
+//! directly, but only its syntax crate is bundled, not the compiler.
+
+use crate::commands::format_from_id;
+use formatrix_core::formats::handler_for;
+use formatrix_core::render_html_page;
+use formatrix_core::toolkit::sanitize::{sanitize, SanitizeConfig};
+use serde::Deserialize;
+
+/// Options from the print dialog
+///
+/// In `header` and `footer`, `{title}` is the document title and `{page}`
+/// and `{pages}` the page number and count; an empty string leaves the
+/// margin blank.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PrintOptions {
+    /// CSS page size: a name such as `A4` or `letter`, or two lengths
+    pub page_size: String,
+    pub landscape: bool,
+    /// CSS margin around the page content
+    pub margin: String,
+    pub header: String,
+    pub footer: String,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            page_size: "A4".to_string(),
+            landscape: false,
+            margin: "20mm".to_string(),
+            header: "{title}".to_string(),
+            footer: "{page} / {pages}".to_string(),
+        }
+    }
+}
+
+/// Render `content` as a printable HTML page
+pub fn print_document(
+    content: String,
+    format: String,
+    options: PrintOptions,
+) -> Result<String, String> {
+    let source_format =
+        format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let mut doc = handler_for(source_format)
+        .parse(&content, &crate::settings::parse_config())
+        .map_err(|e| e.to_string())?;
+    sanitize(&mut doc, &SanitizeConfig::default());
+
+    let title = doc.meta.title.as_deref().unwrap_or("Untitled");
+    let style = print_css(&options, title)?;
+    let page = render_html_page(&doc);
+    Ok(page.replacen(
+        "</head>",
+        &format!("<style>\n{}</style>\n</head>", style),
+        1,
+    ))
+}
+
+fn print_css(options: &PrintOptions, title: &str) -> Result<String, String> {
+    // Options end up inside a style element; keep them to plain values
+    for value in [&options.page_size, &options.margin] {
+        if value.contains(['{', '}', ';', '<']) {
+            return Err(format!("Invalid page setting: {}", value));
+        }
+    }
+
+    let mut page = format!(
+        "  size: {}{};\n  margin: {};\n",
+        options.page_size,
+        if options.landscape { " landscape" } else { "" },
+        options.margin
+    );
+    for (box_name, template) in [
+        ("top-center", &options.header),
+        ("bottom-center", &options.footer),
+    ] {
+        if !template.is_empty() {
+            page.push_str(&format!(
+                "  @{} {{ content: {}; font-size: 9pt; color: #555; }}\n",
+                box_name,
+                css_content(template, title)
+            ));
+        }
+    }
+
+    Ok(format!(
+        "@page {{\n{}}}\n{}",
+        page,
+        concat!(
+            "body { font: 11pt/1.5 serif; margin: 0; }\n",
+            "h1, h2, h3, h4, h5, h6 { break-after: avoid; }\n",
+            "pre, blockquote, table, figure, img { break-inside: avoid; }\n",
+            "pre { white-space: pre-wrap; font-size: 9pt; }\n",
+            "img { max-width: 100%; }\n",
+            "table { border-collapse: collapse; }\n",
+            "th, td { border: 1px solid #999; padding: 0.2em 0.5em; }\n",
+            "a { color: inherit; }\n",
+        )
+    ))
+}
+
+/// A CSS `content` value for `template`, with page counters in place of
+/// `{page}` and `{pages}`
+fn css_content(template: &str, title: &str) -> String {
+    let quote = |text: &str| {
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\A ")
+            .replace('<', "\\3C ");
+        format!("\"{}\"", escaped)
+    };
+
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(len) = rest[open..].find('}') else {
+            break;
+        };
+        let counter = match &rest[open + 1..open + len] {
+            "title" => {
+                text.push_str(&rest[..open]);
+                text.push_str(title);
+                rest = &rest[open + len + 1..];
+                continue;
+            }
+            "page" => "counter(page)",
+            "pages" => "counter(pages)",
+            _ => {
+                text.push_str(&rest[..open + 1]);
+                rest = &rest[open + 1..];
+                continue;
+            }
+        };
+        text.push_str(&rest[..open]);
+        if !text.is_empty() {
+            parts.push(quote(&std::mem::take(&mut text)));
+        }
+        parts.push(counter.to_string());
+        rest = &rest[open + len + 1..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() || parts.is_empty() {
+        parts.push(quote(&text));
+    }
+    parts.join(" ")
+}
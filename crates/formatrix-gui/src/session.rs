@@ -0,0 +1,366 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Session persistence: recent files and links, the open workspace and
+//! autosave
+//!
+//! The frontend sends its open tabs, cursor positions and any unsaved text
+//! to [`save_session`] whenever they change, and calls [`restore_session`] at
+//! startup, so the workspace comes back after a restart or a crash. The
+//! session is one JSON file in the user's state directory, replaced
+//! atomically so a crash mid-write never loses the previous one.
+//!
+//! Between session saves, [`enable_autosave`] keeps drafts of dirty buffers
+//! in a recovery directory beside it; [`recoverable_drafts`] lists what is
+//! left there at the next launch.
+
+use formatrix_core::{write_atomic, Autosaver, BackupPolicy, Draft, RecoveryDir};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// How many recent files are remembered
+const MAX_RECENT_FILES: usize = 20;
+
+/// How many recently used URLs are remembered for link completion
+const MAX_RECENT_URLS: usize = 100;
+
+/// Cursor position in a tab, zero-based
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// One open editor tab
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTab {
+    /// `None` for a document that has never been saved
+    pub path: Option<String>,
+    pub format: String,
+    #[serde(default)]
+    pub cursor: CursorPosition,
+    /// Edited text not yet written to `path`
+    #[serde(default)]
+    pub unsaved: Option<String>,
+}
+
+/// Everything restored at startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    /// Most recent first
+    #[serde(default)]
+    pub recent_files: Vec<String>,
+    /// Web addresses linked to lately, most recent first
+    #[serde(default)]
+    pub recent_urls: Vec<String>,
+    #[serde(default)]
+    pub tabs: Vec<SessionTab>,
+    /// Index into `tabs`
+    #[serde(default)]
+    pub active_tab: Option<usize>,
+}
+
+/// Load the last saved session
+///
+/// A missing or unreadable session gives an empty one. Recent files that no
+/// longer exist are dropped, as are tabs whose file is gone and which hold no
+/// unsaved text.
+pub fn restore_session() -> Result<Session, String> {
+    restore_from(&session_path()?)
+}
+
+/// Persist the open tabs, replacing those saved before
+///
+/// Only `tabs` and `active_tab` are taken from `session`; the recent files
+/// and URLs kept by [`add_recent_file`] and [`remember_urls`] are left as
+/// they are.
+pub fn save_session(session: Session) -> Result<(), String> {
+    save_tabs_to(&session_path()?, session)
+}
+
+/// Move `path` to the top of the recent file list and return the new list
+pub fn add_recent_file(path: String) -> Result<Vec<String>, String> {
+    let session_file = session_path()?;
+    let mut session = restore_from(&session_file)?;
+    session.recent_files.retain(|file| *file != path);
+    session.recent_files.insert(0, path);
+    session.recent_files.truncate(MAX_RECENT_FILES);
+    write_session(&session_file, &session)?;
+    Ok(session.recent_files)
+}
+
+/// Move `url` to the top of the recently used URLs
+pub fn add_recent_url(url: String) -> Result<(), String> {
+    let session_file = session_path()?;
+    let mut session = restore_from(&session_file)?;
+    session.recent_urls.retain(|u| *u != url);
+    session.recent_urls.insert(0, url);
+    session.recent_urls.truncate(MAX_RECENT_URLS);
+    write_session(&session_file, &session)
+}
+
+/// Remember those of `urls` not seen before, and return the recently used
+/// URLs
+///
+/// URLs already in the list keep their place, so the session is only
+/// written when something new turns up.
+pub fn remember_urls(urls: Vec<String>) -> Result<Vec<String>, String> {
+    remember_urls_in(&session_path()?, urls)
+}
+
+fn restore_from(path: &Path) -> Result<Session, String> {
+    let mut session = read_session(path)?;
+    session.recent_files.retain(|file| Path::new(file).exists());
+    session.tabs.retain(|tab| {
+        tab.unsaved.is_some() || tab.path.as_ref().is_none_or(|p| Path::new(p).exists())
+    });
+    if session.active_tab.is_some_and(|i| i >= session.tabs.len()) {
+        session.active_tab = session.tabs.len().checked_sub(1);
+    }
+    Ok(session)
+}
+
+fn save_tabs_to(path: &Path, session: Session) -> Result<(), String> {
+    let mut stored = read_session(path)?;
+    stored.tabs = session.tabs;
+    stored.active_tab = session.active_tab;
+    write_session(path, &stored)
+}
+
+fn remember_urls_in(path: &Path, urls: Vec<String>) -> Result<Vec<String>, String> {
+    let mut session = restore_from(path)?;
+    let mut new: Vec<String> = Vec::new();
+    for url in urls {
+        if !session.recent_urls.contains(&url) && !new.contains(&url) {
+            new.push(url);
+        }
+    }
+    if !new.is_empty() {
+        session.recent_urls.splice(0..0, new);
+        session.recent_urls.truncate(MAX_RECENT_URLS);
+        write_session(path, &session)?;
+    }
+    Ok(session.recent_urls)
+}
+
+/// The session stored at `path` as written, or an empty one
+fn read_session(path: &Path) -> Result<Session, String> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => Ok(serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable session {}: {}", path.display(), e);
+            Session::default()
+        })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Session::default()),
+        Err(e) => Err(format!("Failed to read session: {}", e)),
+    }
+}
+
+/// Autosaver started by [`enable_autosave`]
+static AUTOSAVER: LazyLock<Mutex<Option<Autosaver>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Start writing drafts of dirty buffers every `interval_secs` seconds, or
+/// stop if it is 0
+///
+/// Calling it again restarts the timer with the new interval; pending
+/// drafts are written first.
+pub fn enable_autosave(interval_secs: u64) -> Result<(), String> {
+    let mut autosaver = AUTOSAVER
+        .lock()
+        .map_err(|_| "autosave unavailable".to_string())?;
+    // Dropping the old one writes what it still holds
+    *autosaver = None;
+    if interval_secs > 0 {
+        let recovery = RecoveryDir::new(state_dir()?.join("recovery"));
+        *autosaver = Some(Autosaver::start(
+            recovery,
+            Duration::from_secs(interval_secs),
+        ));
+    }
+    Ok(())
+}
+
+/// Hand the current text of a changed buffer to the autosaver
+///
+/// Does nothing while autosave is off.
+pub fn autosave_buffer(
+    id: String,
+    path: Option<String>,
+    format: String,
+    content: String,
+) -> Result<(), String> {
+    let source_format = crate::commands::format_from_id(&format)
+        .ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let autosaver = AUTOSAVER
+        .lock()
+        .map_err(|_| "autosave unavailable".to_string())?;
+    if let Some(autosaver) = autosaver.as_ref() {
+        autosaver.update(id, path.map(PathBuf::from), source_format, content);
+    }
+    Ok(())
+}
+
+/// Forget the draft of a buffer that was saved, closed or recovered
+pub fn discard_draft(id: String) -> Result<(), String> {
+    let autosaver = AUTOSAVER
+        .lock()
+        .map_err(|_| "autosave unavailable".to_string())?;
+    match autosaver.as_ref() {
+        Some(autosaver) => autosaver.mark_clean(&id),
+        None => RecoveryDir::new(state_dir()?.join("recovery")).discard(&id),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Drafts left by an earlier run, newest first
+pub fn recoverable_drafts() -> Result<Vec<Draft>, String> {
+    RecoveryDir::new(state_dir()?.join("recovery"))
+        .drafts()
+        .map_err(|e| e.to_string())
+}
+
+fn write_session(path: &Path, session: &Session) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to save session: {}", e))?;
+    }
+    let json = serde_json::to_vec_pretty(session).map_err(|e| e.to_string())?;
+    write_atomic(path, &json, BackupPolicy::None)
+        .map_err(|e| format!("Failed to save session: {}", e))
+}
+
+fn session_path() -> Result<PathBuf, String> {
+    Ok(state_dir()?.join("session.json"))
+}
+
+/// The application's directory in the platform's per-user state directory
+fn state_dir() -> Result<PathBuf, String> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    let base = env_dir("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env_dir("APPDATA").map(PathBuf::from))
+        .or_else(|| env_dir("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .ok_or_else(|| "No home directory to keep the session in".to_string())?;
+    Ok(base.join("formatrix-docs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(path: Option<&Path>, unsaved: Option<&str>) -> SessionTab {
+        SessionTab {
+            path: path.map(|p| p.to_string_lossy().into_owned()),
+            format: "md".to_string(),
+            cursor: CursorPosition::default(),
+            unsaved: unsaved.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_save_keeps_recent_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.md");
+        std::fs::write(&file, "# Notes").unwrap();
+        let session_file = dir.path().join("state/session.json");
+        let stored = Session {
+            recent_files: vec![file.to_string_lossy().into_owned()],
+            recent_urls: vec!["https://example.com".to_string()],
+            ..Default::default()
+        };
+        write_session(&session_file, &stored).unwrap();
+
+        // The frontend only sends its tabs
+        let payload: Session = serde_json::from_str(
+            r#"{"tabs": [{"path": null, "format": "md", "unsaved": "draft"}], "active_tab": 0}"#,
+        )
+        .unwrap();
+        save_tabs_to(&session_file, payload).unwrap();
+
+        let session = restore_from(&session_file).unwrap();
+        assert_eq!(session.recent_files, stored.recent_files);
+        assert_eq!(session.recent_urls, stored.recent_urls);
+        assert_eq!(session.tabs.len(), 1);
+        assert_eq!(session.tabs[0].unsaved.as_deref(), Some("draft"));
+        assert_eq!(session.active_tab, Some(0));
+    }
+
+    #[test]
+    fn test_restore_drops_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.md");
+        std::fs::write(&kept, "text").unwrap();
+        let gone = dir.path().join("gone.md");
+        let session_file = dir.path().join("session.json");
+        let stored = Session {
+            recent_files: vec![
+                gone.to_string_lossy().into_owned(),
+                kept.to_string_lossy().into_owned(),
+            ],
+            tabs: vec![
+                tab(Some(&kept), None),
+                tab(Some(&gone), None),
+                tab(Some(&gone), Some("edited")),
+                tab(None, Some("new")),
+            ],
+            active_tab: Some(1),
+            ..Default::default()
+        };
+        write_session(&session_file, &stored).unwrap();
+
+        let session = restore_from(&session_file).unwrap();
+        assert_eq!(
+            session.recent_files,
+            vec![kept.to_string_lossy().into_owned()]
+        );
+        let unsaved: Vec<_> = session.tabs.iter().map(|t| t.unsaved.as_deref()).collect();
+        assert_eq!(unsaved, [None, Some("edited"), Some("new")]);
+        assert_eq!(session.active_tab, Some(1));
+    }
+
+    #[test]
+    fn test_restore_clamps_active_tab() {
+        let dir = tempfile::tempdir().unwrap();
+        let session_file = dir.path().join("session.json");
+        let gone = dir.path().join("gone.md");
+        let stored = Session {
+            tabs: vec![tab(None, Some("one")), tab(Some(&gone), None)],
+            active_tab: Some(1),
+            ..Default::default()
+        };
+        write_session(&session_file, &stored).unwrap();
+        assert_eq!(restore_from(&session_file).unwrap().active_tab, Some(0));
+
+        let stored = Session {
+            tabs: vec![tab(Some(&gone), None)],
+            active_tab: Some(0),
+            ..Default::default()
+        };
+        write_session(&session_file, &stored).unwrap();
+        assert_eq!(restore_from(&session_file).unwrap().active_tab, None);
+
+        // A missing session is an empty one
+        let empty = restore_from(&dir.path().join("none.json")).unwrap();
+        assert!(empty.tabs.is_empty() && empty.active_tab.is_none());
+    }
+
+    #[test]
+    fn test_remember_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        let session_file = dir.path().join("session.json");
+        let url = |n: usize| format!("https://example.com/{}", n);
+
+        let urls = remember_urls_in(&session_file, vec![url(1), url(2), url(1)]).unwrap();
+        assert_eq!(urls, [url(1), url(2)]);
+
+        // New ones go first, known ones keep their place
+        let urls = remember_urls_in(&session_file, vec![url(2), url(3)]).unwrap();
+        assert_eq!(urls, [url(3), url(1), url(2)]);
+
+        let many = (4..4 + MAX_RECENT_URLS).map(url).collect();
+        let urls = remember_urls_in(&session_file, many).unwrap();
+        assert_eq!(urls.len(), MAX_RECENT_URLS);
+        assert_eq!(urls[0], url(4));
+        assert!(!urls.contains(&url(3)));
+        assert_eq!(restore_from(&session_file).unwrap().recent_urls, urls);
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! User preferences
+//!
+//! Settings are one JSON file in the user's config directory, read once and
+//! kept in memory. Commands take their parse and render options from
+//! [`parse_config`] and [`render_config`] rather than the core defaults, so
+//! the preferences apply to every conversion, save and export.
+//!
+//! [`update_settings`] takes only the fields that changed; fields missing
+//! from the file, such as ones added by a newer version, keep their
+//! defaults.
+
+use formatrix_core::{
+    write_atomic, BackupPolicy, FinalNewline, HardBreak, LineEnding, ParseConfig, RenderConfig,
+    RstFlavor, StyleProfile,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// Colour theme of the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follow the operating system
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// How documents are written out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Column to wrap at; 0 leaves lines as they are
+    pub line_width: usize,
+    pub indent: String,
+    pub hard_breaks: bool,
+    /// Markdown hard break syntax, `"spaces"` or `"backslash"`; `null` to
+    /// follow the profile
+    pub hard_break: Option<HardBreak>,
+    pub line_ending: LineEnding,
+    pub final_newline: FinalNewline,
+    /// What to keep of the previous version when saving over a file
+    pub backup: BackupPolicy,
+    /// House style to write in, such as `"pandoc"`; `null` for none
+    pub profile: Option<StyleProfile>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        let config = RenderConfig::default();
+        Self {
+            line_width: config.line_width,
+            indent: config.indent,
+            hard_breaks: config.hard_breaks,
+            hard_break: config.hard_break,
+            line_ending: config.line_ending,
+            final_newline: config.final_newline,
+            backup: config.backup,
+            profile: config.profile,
+        }
+    }
+}
+
+/// Everything in the preferences dialog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Format id for new documents
+    pub default_format: String,
+    pub render: RenderSettings,
+    /// Leave include directives unexpanded when opening files
+    pub keep_includes: bool,
+    /// Leave reStructuredText substitutions unexpanded when opening files
+    pub keep_substitutions: bool,
+    /// Dialect reStructuredText files are read as
+    pub rst_flavor: RstFlavor,
+    /// Seconds between autosaves; 0 turns autosave off
+    pub autosave_interval: u64,
+    pub theme: Theme,
+    /// Dictionary used for spell checking, such as `en_US`
+    pub spell_check_language: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_format: "md".to_string(),
+            render: RenderSettings::default(),
+            keep_includes: false,
+            keep_substitutions: false,
+            rst_flavor: RstFlavor::default(),
+            autosave_interval: 30,
+            theme: Theme::default(),
+            spell_check_language: "en_US".to_string(),
+        }
+    }
+}
+
+/// Settings as last read or written; `None` until first needed
+static SETTINGS: LazyLock<Mutex<Option<Settings>>> = LazyLock::new(|| Mutex::new(None));
+
+/// The current settings
+pub fn get_settings() -> Result<Settings, String> {
+    let mut settings = SETTINGS
+        .lock()
+        .map_err(|_| "settings unavailable".to_string())?;
+    if settings.is_none() {
+        *settings = Some(read_settings()?);
+    }
+    Ok(settings.clone().unwrap_or_default())
+}
+
+/// Merge `changes`, a partial settings object, into the settings and save
+/// them
+///
+/// Nested objects are merged field by field. A change of autosave interval
+/// takes effect at once.
+pub fn update_settings(changes: serde_json::Value) -> Result<Settings, String> {
+    let old = get_settings()?;
+    let mut merged = serde_json::to_value(&old).map_err(|e| e.to_string())?;
+    merge(&mut merged, changes);
+    let new: Settings =
+        serde_json::from_value(merged).map_err(|e| format!("Invalid settings: {}", e))?;
+    if crate::commands::format_from_id(&new.default_format).is_none() {
+        return Err(format!("Unsupported format: {}", new.default_format));
+    }
+
+    write_settings(&new)?;
+    if new.autosave_interval != old.autosave_interval {
+        crate::session::enable_autosave(new.autosave_interval)?;
+    }
+    let mut settings = SETTINGS
+        .lock()
+        .map_err(|_| "settings unavailable".to_string())?;
+    *settings = Some(new.clone());
+    Ok(new)
+}
+
+/// Parse options from the settings
+pub fn parse_config() -> ParseConfig {
+    get_settings().unwrap_or_default().parse_config()
+}
+
+/// Render options from the settings
+pub fn render_config() -> RenderConfig {
+    get_settings().unwrap_or_default().render.render_config()
+}
+
+impl Settings {
+    fn parse_config(&self) -> ParseConfig {
+        ParseConfig {
+            keep_includes: self.keep_includes,
+            keep_substitutions: self.keep_substitutions,
+            rst_flavor: self.rst_flavor,
+            ..Default::default()
+        }
+    }
+}
+
+impl RenderSettings {
+    fn render_config(self) -> RenderConfig {
+        RenderConfig {
+            line_width: self.line_width,
+            indent: self.indent,
+            hard_breaks: self.hard_breaks,
+            hard_break: self.hard_break,
+            line_ending: self.line_ending,
+            final_newline: self.final_newline,
+            backup: self.backup,
+            profile: self.profile,
+            ..Default::default()
+        }
+    }
+}
+
+fn merge(target: &mut serde_json::Value, changes: serde_json::Value) {
+    match (target, changes) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(changes)) => {
+            for (key, value) in changes {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, changes) => *target = changes,
+    }
+}
+
+/// Read the settings file; a missing or unreadable one gives the defaults
+fn read_settings() -> Result<Settings, String> {
+    let path = settings_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(json) => Ok(serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable settings {}: {}", path.display(), e);
+            Settings::default()
+        })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(format!("Failed to read settings: {}", e)),
+    }
+}
+
+fn write_settings(settings: &Settings) -> Result<(), String> {
+    let path = settings_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+    let json = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&path, &json, BackupPolicy::None)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// The settings file in the platform's per-user config directory
+fn settings_path() -> Result<PathBuf, String> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    let base = env_dir("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env_dir("APPDATA").map(PathBuf::from))
+        .or_else(|| env_dir("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or_else(|| "No home directory to keep the settings in".to_string())?;
+    Ok(base.join("formatrix-docs").join("settings.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_configs() {
+        let settings = Settings::default();
+        let parse = settings.parse_config();
+        assert!(!parse.keep_includes);
+        assert!(!parse.keep_substitutions);
+        assert_eq!(parse.rst_flavor, RstFlavor::default());
+
+        let render = settings.render.render_config();
+        let core = RenderConfig::default();
+        assert_eq!(render.line_width, core.line_width);
+        assert_eq!(render.indent, core.indent);
+        assert_eq!(render.line_ending, core.line_ending);
+        assert_eq!(render.final_newline, core.final_newline);
+        assert_eq!(render.backup, core.backup);
+        assert_eq!(render.profile, core.profile);
+    }
+
+    #[test]
+    fn test_configs_follow_settings() {
+        let settings: Settings = serde_json::from_value(json!({
+            "keep_includes": true,
+            "keep_substitutions": true,
+            "rst_flavor": "sphinx",
+            "render": {
+                "line_width": 72,
+                "indent": "\t",
+                "hard_breaks": true,
+                "hard_break": "backslash",
+                "line_ending": "crlf",
+                "final_newline": "single",
+                "backup": "numbered",
+                "profile": "pandoc"
+            }
+        }))
+        .unwrap();
+
+        let parse = settings.parse_config();
+        assert!(parse.keep_includes);
+        assert!(parse.keep_substitutions);
+        assert_eq!(parse.rst_flavor, RstFlavor::Sphinx);
+
+        let render = settings.render.render_config();
+        assert_eq!(render.line_width, 72);
+        assert_eq!(render.indent, "\t");
+        assert!(render.hard_breaks);
+        assert_eq!(render.hard_break, Some(HardBreak::Backslash));
+        assert_eq!(render.line_ending, LineEnding::CrLf);
+        assert_eq!(render.final_newline, FinalNewline::Single);
+        assert_eq!(render.backup, BackupPolicy::Numbered);
+        assert_eq!(render.profile, Some(StyleProfile::Pandoc));
+    }
+
+    #[test]
+    fn test_merge_keeps_unchanged_fields() {
+        let mut value = serde_json::to_value(Settings::default()).unwrap();
+        merge(
+            &mut value,
+            json!({"render": {"line_width": 100}, "theme": "dark"}),
+        );
+        let settings: Settings = serde_json::from_value(value).unwrap();
+        assert_eq!(settings.render.line_width, 100);
+        assert_eq!(settings.render.indent, RenderSettings::default().indent);
+        assert_eq!(settings.theme, Theme::Dark);
+        assert_eq!(settings.autosave_interval, 30);
+
+        // Fields missing from a stored file keep their defaults
+        let settings: Settings = serde_json::from_value(json!({"theme": "light"})).unwrap();
+        assert_eq!(settings.default_format, "md");
+        assert_eq!(
+            settings.render.render_config().line_width,
+            RenderConfig::default().line_width
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Spell checking with the project's own dictionary
+//!
+//! [`spellcheck_document`] checks a buffer against the Hunspell dictionary
+//! for the language in the settings, found in the usual system locations
+//! or a directory on `DICPATH`. Each misspelling comes with its span and
+//! suggestions, for the editor to underline and offer as quick fixes.
+//!
+//! Words added with [`add_to_dictionary`] go in a plain word list in the
+//! open workspace, `.formatrix/dictionary.txt`, one word per line, so they
+//! travel with the project and apply to every document in it.
+
+use crate::commands::spell_check;
+use crate::settings;
+use crate::workspace::workspace_root;
+use formatrix_core::toolkit::spell::Misspelling;
+use formatrix_core::{write_atomic, BackupPolicy};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Custom dictionary, relative to the workspace root
+const DICTIONARY_FILE: &str = ".formatrix/dictionary.txt";
+
+/// Spell check `content` with the settings' language and the workspace
+/// dictionary
+pub fn spellcheck_document(content: String, format: String) -> Result<Vec<Misspelling>, String> {
+    let language = settings::get_settings()?.spell_check_language;
+    let dictionary = find_dictionary(&language)
+        .ok_or_else(|| format!("No dictionary installed for {}", language))?;
+    let words = match workspace_root() {
+        Ok(root) => read_words(&root.join(DICTIONARY_FILE))?,
+        Err(_) => Vec::new(),
+    };
+    spell_check(
+        content,
+        format,
+        dictionary.to_string_lossy().into_owned(),
+        words,
+    )
+}
+
+/// Add `word` to the workspace dictionary
+pub fn add_to_dictionary(word: String) -> Result<(), String> {
+    let word = word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(format!("Not a single word: {:?}", word));
+    }
+    let path = workspace_root()?.join(DICTIONARY_FILE);
+    let mut words = read_words(&path)?;
+    if words.iter().any(|w| w == word) {
+        return Ok(());
+    }
+    words.push(word.to_string());
+    words.sort();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to save dictionary: {}", e))?;
+    }
+    let text: String = words.iter().map(|w| format!("{}\n", w)).collect();
+    write_atomic(&path, text.as_bytes(), BackupPolicy::None)
+        .map_err(|e| format!("Failed to save dictionary: {}", e))
+}
+
+/// Words in a dictionary file; a missing file has none
+fn read_words(path: &Path) -> Result<Vec<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read dictionary: {}", e)),
+    }
+}
+
+/// The `.aff`/`.dic` base path for `language`, searching `DICPATH` first
+fn find_dictionary(language: &str) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("DICPATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = Path::new(&home);
+        dirs.push(home.join(".local/share/hunspell"));
+        dirs.push(home.join("Library/Spelling"));
+    }
+    for dir in [
+        "/usr/share/hunspell",
+        "/usr/local/share/hunspell",
+        "/usr/share/myspell",
+        "/usr/share/myspell/dicts",
+        "/Library/Spelling",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    dirs.into_iter()
+        .map(|dir| dir.join(language))
+        .find(|base| base.with_extension("aff").is_file() && base.with_extension("dic").is_file())
+}
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Table toolbar backend
+//!
+//! Each command finds the table at the cursor, applies one of the table
+//! edits in [`formatrix_core::edit`] to its AST, and renders the table back
+//! in the buffer's format over the text it came from, so the toolbar works
+//! the same whatever the format. The rest of the buffer is left as it was.
+//!
+//! Rows and columns are numbered from zero as the table shows them, the
+//! header row first.
+
+use crate::commands::format_from_id;
+use crate::settings;
+use formatrix_core::ast::ColumnAlignment;
+use formatrix_core::edit::{self, Affected, EditError};
+use formatrix_core::formats::handler_for;
+use formatrix_core::{Block, Document, IncrementalParser, ParseConfig, SourceFormat};
+use serde::Serialize;
+
+/// A buffer after a table command
+#[derive(Debug, Clone, Serialize)]
+pub struct TableEdit {
+    pub content: String,
+    /// Byte range of the table in `content`
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Insert an empty table of `rows` by `columns` after the paragraph holding
+/// the cursor, set off by blank lines
+pub fn insert_table(
+    content: String,
+    format: String,
+    offset: usize,
+    rows: usize,
+    columns: usize,
+    header: bool,
+) -> Result<TableEdit, String> {
+    let source_format = parse_format(&format)?;
+    check_offset(&content, offset)?;
+    let mut doc = Document::new(source_format);
+    edit::insert_table(&mut doc, &[0], rows, columns, header).map_err(|e| e.to_string())?;
+    let table = render_block(source_format, &doc.content[0])?;
+
+    let block_end = content[offset..]
+        .find("\n\n")
+        .map_or(content.len(), |i| offset + i);
+    let (before, after) = content.split_at(block_end);
+    let rest = after.trim_start_matches('\n');
+    let lead = if before.trim().is_empty() || before.ends_with("\n\n") {
+        ""
+    } else if before.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let start = before.len() + lead.len();
+    let trail = if rest.is_empty() { "\n" } else { "\n\n" };
+    Ok(TableEdit {
+        content: format!("{}{}{}{}{}", before, lead, table, trail, rest),
+        start,
+        end: start + table.len(),
+    })
+}
+
+/// Insert an empty row so that it becomes row `at`, or as near to it as the
+/// header and footer allow
+pub fn insert_table_row(
+    content: String,
+    format: String,
+    offset: usize,
+    at: usize,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, |doc, path| {
+        edit::insert_table_row(doc, path, at)
+    })
+}
+
+pub fn remove_table_row(
+    content: String,
+    format: String,
+    offset: usize,
+    index: usize,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, |doc, path| {
+        edit::remove_table_row(doc, path, index)
+    })
+}
+
+/// Insert an empty column so that it becomes column `at`
+pub fn insert_table_column(
+    content: String,
+    format: String,
+    offset: usize,
+    at: usize,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, |doc, path| {
+        edit::insert_table_column(doc, path, at)
+    })
+}
+
+pub fn remove_table_column(
+    content: String,
+    format: String,
+    offset: usize,
+    index: usize,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, |doc, path| {
+        edit::remove_table_column(doc, path, index)
+    })
+}
+
+/// Make the first row a header row, or the header row an ordinary one
+pub fn toggle_table_header(
+    content: String,
+    format: String,
+    offset: usize,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, edit::toggle_table_header)
+}
+
+pub fn set_table_alignment(
+    content: String,
+    format: String,
+    offset: usize,
+    column: usize,
+    alignment: ColumnAlignment,
+) -> Result<TableEdit, String> {
+    edit_table(content, &format, offset, |doc, path| {
+        edit::set_column_alignment(doc, path, column, alignment)
+    })
+}
+
+/// Apply `edit` to the table at byte `offset` of `content` and put the
+/// table back in its place
+fn edit_table(
+    content: String,
+    format: &str,
+    offset: usize,
+    edit: impl FnOnce(&mut Document, &[usize]) -> Result<Affected, EditError>,
+) -> Result<TableEdit, String> {
+    let source_format = parse_format(format)?;
+    check_offset(&content, offset)?;
+    let config = ParseConfig {
+        preserve_spans: true,
+        ..settings::parse_config()
+    };
+    let parser = IncrementalParser::new(content, source_format, config);
+    let mut doc = parser.document().clone();
+    let found = doc.content.iter().enumerate().find_map(|(i, block)| {
+        let span = block.span()?;
+        let table = matches!(block, Block::Table { .. });
+        (table && span.start <= offset && offset <= span.end).then_some((i, span))
+    });
+    let (index, span) = found.ok_or_else(|| "No table at the cursor".to_string())?;
+    edit(&mut doc, &[index]).map_err(|e| e.to_string())?;
+    let table = render_block(source_format, &doc.content[index])?;
+
+    // The span runs to the end of the table's region; keep the line breaks
+    // after the table itself
+    let content = parser.text();
+    let end = span.start + content[span.start..span.end].trim_end().len();
+    Ok(TableEdit {
+        content: format!("{}{}{}", &content[..span.start], table, &content[end..]),
+        start: span.start,
+        end: span.start + table.len(),
+    })
+}
+
+/// `block` alone in `format`, without a trailing line break
+fn render_block(format: SourceFormat, block: &Block) -> Result<String, String> {
+    let mut doc = Document::new(format);
+    doc.content.push(block.clone());
+    let markup = handler_for(format)
+        .render(&doc, &settings::render_config())
+        .map_err(|e| e.to_string())?;
+    Ok(markup.trim_end_matches('\n').to_string())
+}
+
+fn parse_format(format: &str) -> Result<SourceFormat, String> {
+    format_from_id(format).ok_or_else(|| format!("Unsupported format: {}", format))
+}
+
+fn check_offset(content: &str, offset: usize) -> Result<(), String> {
+    if !content.is_char_boundary(offset) {
+        return Err(format!("Invalid cursor offset: {}", offset));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "Intro\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\nAfter\n";
+
+    fn cursor() -> usize {
+        TABLE.find("| 1").unwrap()
+    }
+
+    fn table(edit: &TableEdit) -> &str {
+        &edit.content[edit.start..edit.end]
+    }
+
+    #[test]
+    fn test_insert_row_at_bounds() {
+        // The header stays in place; row 0 lands at the top of the body.
+        let first = insert_table_row(TABLE.into(), "md".into(), cursor(), 0).unwrap();
+        assert_eq!(
+            table(&first),
+            "| a | b |\n| --- | --- |\n|  |  |\n| 1 | 2 |"
+        );
+        assert!(first.content.starts_with("Intro\n\n") && first.content.ends_with("\n\nAfter\n"));
+
+        let last = insert_table_row(TABLE.into(), "md".into(), cursor(), 2).unwrap();
+        assert_eq!(table(&last), "| a | b |\n| --- | --- |\n| 1 | 2 |\n|  |  |");
+
+        let past = insert_table_row(TABLE.into(), "md".into(), cursor(), 3);
+        assert!(past.is_err());
+    }
+
+    #[test]
+    fn test_remove_row_at_bounds() {
+        let first = remove_table_row(TABLE.into(), "md".into(), cursor(), 0).unwrap();
+        assert_eq!(table(&first), "|  |  |\n| --- | --- |\n| 1 | 2 |");
+
+        let last = remove_table_row(TABLE.into(), "md".into(), cursor(), 1).unwrap();
+        assert_eq!(table(&last), "| a | b |\n| --- | --- |");
+
+        assert!(remove_table_row(TABLE.into(), "md".into(), cursor(), 2).is_err());
+
+        let single = "| a |\n";
+        assert!(remove_table_row(single.into(), "org".into(), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_insert_column_at_bounds() {
+        let first = insert_table_column(TABLE.into(), "md".into(), cursor(), 0).unwrap();
+        assert_eq!(
+            table(&first),
+            "|  | a | b |\n| --- | --- | --- |\n|  | 1 | 2 |"
+        );
+
+        let last = insert_table_column(TABLE.into(), "md".into(), cursor(), 2).unwrap();
+        assert_eq!(
+            table(&last),
+            "| a | b |  |\n| --- | --- | --- |\n| 1 | 2 |  |"
+        );
+
+        assert!(insert_table_column(TABLE.into(), "md".into(), cursor(), 3).is_err());
+    }
+
+    #[test]
+    fn test_remove_column_at_bounds() {
+        let last = remove_table_column(TABLE.into(), "md".into(), cursor(), 1).unwrap();
+        assert_eq!(table(&last), "| a |\n| --- |\n| 1 |");
+
+        assert!(remove_table_column(TABLE.into(), "md".into(), cursor(), 2).is_err());
+        assert!(remove_table_column(last.content, "md".into(), last.start, 0).is_err());
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let ragged = "| a | b | c |\n| 1 |\n| x | y |\n";
+
+        let appended = insert_table_column(ragged.into(), "org".into(), 0, 3).unwrap();
+        assert_eq!(
+            appended.content,
+            "| a | b | c |  |\n|---|\n| 1 |  |\n| x | y |  |\n"
+        );
+
+        let inserted = insert_table_column(ragged.into(), "org".into(), 0, 1).unwrap();
+        assert_eq!(
+            inserted.content,
+            "| a |  | b | c |\n|---|\n| 1 |  |\n| x |  | y |\n"
+        );
+
+        let removed = remove_table_column(ragged.into(), "org".into(), 0, 2).unwrap();
+        assert_eq!(removed.content, "| a | b |\n|---|\n| 1 |\n| x | y |\n");
+
+        let row = insert_table_row(ragged.into(), "org".into(), 0, 1).unwrap();
+        assert_eq!(
+            row.content,
+            "| a | b | c |\n|---|\n|  |  |  |\n| 1 |\n| x | y |\n"
+        );
+    }
+
+    #[test]
+    fn test_cursor_errors() {
+        assert_eq!(
+            insert_table_row("text".into(), "md".into(), 0, 0).unwrap_err(),
+            "No table at the cursor"
+        );
+        assert!(insert_table_row("é".into(), "md".into(), 1, 0)
+            .unwrap_err()
+            .starts_with("Invalid cursor offset"));
+        assert!(insert_table("Para".into(), "md".into(), 0, 0, 2, true).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Windows, views and the documents they show
+//!
+//! Each editor pane is a view of one open document, in one window. Views of
+//! the same file share the document, whether split side by side in one
+//! window or in windows of their own: edits go through [`update_view`],
+//! which emits a `Modified` document event with the view as its origin, and
+//! the other views of that document reload it with [`get_view`].
+//!
+//! The backend only keeps track of windows. [`open_in_new_window`] returns
+//! the new window's id, which the caller hands to the webview it opens for
+//! it; the first window is [`MAIN_WINDOW`], where [`open_view`] puts views
+//! that name no window.
+
+use crate::commands::{emit_event, format_from_id, DocumentEvent, EventOrigin};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// Id of the window the app starts with
+pub const MAIN_WINDOW: &str = "main";
+
+/// A view as the frontend sees it
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewData {
+    pub window: String,
+    pub view: String,
+    pub document: String,
+    pub path: Option<String>,
+    pub format: String,
+    pub content: String,
+}
+
+/// One of the window's views
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowView {
+    pub view: String,
+    pub document: String,
+    pub path: Option<String>,
+}
+
+struct OpenDocument {
+    /// `None` for a document that has never been saved
+    path: Option<String>,
+    format: String,
+    content: String,
+}
+
+struct View {
+    window: String,
+    document: String,
+}
+
+#[derive(Default)]
+struct Registry {
+    documents: HashMap<String, OpenDocument>,
+    views: HashMap<String, View>,
+    next_id: u64,
+}
+
+impl Registry {
+    fn new_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    /// The open document for `path`, opened from `content` if there is none
+    fn document_for(&mut self, path: Option<String>, format: String, content: String) -> String {
+        let existing = path.as_ref().and_then(|path| {
+            self.documents
+                .iter()
+                .find(|(_, doc)| doc.path.as_ref() == Some(path))
+                .map(|(id, _)| id.clone())
+        });
+        existing.unwrap_or_else(|| {
+            let id = self.new_id("d");
+            self.documents.insert(
+                id.clone(),
+                OpenDocument {
+                    path,
+                    format,
+                    content,
+                },
+            );
+            id
+        })
+    }
+
+    fn add_view(&mut self, window: String, document: String) -> ViewData {
+        let view = self.new_id("v");
+        self.views.insert(
+            view.clone(),
+            View {
+                window: window.clone(),
+                document: document.clone(),
+            },
+        );
+        let doc = &self.documents[&document];
+        ViewData {
+            window,
+            view,
+            path: doc.path.clone(),
+            format: doc.format.clone(),
+            content: doc.content.clone(),
+            document,
+        }
+    }
+
+    fn view(&self, view: &str) -> Result<&View, String> {
+        self.views
+            .get(view)
+            .ok_or_else(|| format!("No such view: {}", view))
+    }
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| Mutex::new(Registry::default()));
+
+/// Show a buffer in a new view in `window`
+///
+/// If the buffer's file is already open, the view shares that document and
+/// `content` is ignored.
+pub fn open_view(
+    window: String,
+    path: Option<String>,
+    format: String,
+    content: String,
+) -> Result<ViewData, String> {
+    format_from_id(&format).ok_or_else(|| format!("Unsupported format: {}", format))?;
+    let mut registry = lock_registry()?;
+    let document = registry.document_for(path, format, content);
+    Ok(registry.add_view(window, document))
+}
+
+/// Open the file at `path` in a new window
+///
+/// A file already open elsewhere is shown as it is there, unsaved edits
+/// included.
+pub fn open_in_new_window(path: String) -> Result<ViewData, String> {
+    let mut registry = lock_registry()?;
+    let window = registry.new_id("w");
+    let already_open = registry
+        .documents
+        .values()
+        .any(|doc| doc.path.as_ref() == Some(&path));
+    let document = if already_open {
+        registry.document_for(Some(path), String::new(), String::new())
+    } else {
+        // Release the registry while reading the file
+        drop(registry);
+        let data = crate::commands::load_document(path.clone())?;
+        registry = lock_registry()?;
+        registry.document_for(Some(path), data.meta.format, data.content)
+    };
+    Ok(registry.add_view(window, document))
+}
+
+/// Split `view`: a second view of its document beside it in its window
+pub fn split_view(view: String) -> Result<ViewData, String> {
+    let mut registry = lock_registry()?;
+    let View { window, document } = registry.view(&view)?;
+    let (window, document) = (window.clone(), document.clone());
+    Ok(registry.add_view(window, document))
+}
+
+/// Record an edit made in `view` and tell the document's other views
+pub fn update_view(view: String, content: String) -> Result<(), String> {
+    let mut registry = lock_registry()?;
+    let View { window, document } = registry.view(&view)?;
+    let origin = EventOrigin {
+        window: window.clone(),
+        view: view.clone(),
+        document: document.clone(),
+    };
+    let doc = registry
+        .documents
+        .get_mut(&origin.document)
+        .ok_or_else(|| format!("No such document: {}", origin.document))?;
+    if doc.content == content {
+        return Ok(());
+    }
+    let old_content = std::mem::replace(&mut doc.content, content);
+    let event = DocumentEvent::modified(
+        &doc.content,
+        &old_content,
+        doc.path.as_deref().unwrap_or(""),
+        &doc.format,
+    );
+    emit_event(event.with_origin(origin));
+    Ok(())
+}
+
+/// The document shown in `view`, as last edited in any of its views
+pub fn get_view(view: String) -> Result<ViewData, String> {
+    let registry = lock_registry()?;
+    let View { window, document } = registry.view(&view)?;
+    let doc = &registry.documents[document];
+    Ok(ViewData {
+        window: window.clone(),
+        view,
+        document: document.clone(),
+        path: doc.path.clone(),
+        format: doc.format.clone(),
+        content: doc.content.clone(),
+    })
+}
+
+/// The views in `window`, in the order they were opened
+pub fn window_views(window: String) -> Result<Vec<WindowView>, String> {
+    let registry = lock_registry()?;
+    let mut views: Vec<_> = registry
+        .views
+        .iter()
+        .filter(|(_, v)| v.window == window)
+        .map(|(id, v)| WindowView {
+            view: id.clone(),
+            document: v.document.clone(),
+            path: registry.documents[&v.document].path.clone(),
+        })
+        .collect();
+    views.sort_by_key(|v| v.view[1..].parse::<u64>().unwrap_or(0));
+    Ok(views)
+}
+
+/// Close `view`; its document is closed with its last view
+pub fn close_view(view: String) -> Result<(), String> {
+    let mut registry = lock_registry()?;
+    let Some(View { document, .. }) = registry.views.remove(&view) else {
+        return Ok(());
+    };
+    if !registry.views.values().any(|v| v.document == document) {
+        registry.documents.remove(&document);
+    }
+    Ok(())
+}
+
+/// Close every view in `window`
+pub fn close_window(window: String) -> Result<(), String> {
+    let views: Vec<String> = window_views(window)?.into_iter().map(|v| v.view).collect();
+    for view in views {
+        close_view(view)?;
+    }
+    Ok(())
+}
+
+fn lock_registry() -> Result<MutexGuard<'static, Registry>, String> {
+    REGISTRY
+        .lock()
+        .map_err(|_| "window registry unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::get_document_events;
+
+    // The registry is shared by every test, so each one uses windows and
+    // paths of its own
+
+    fn open(window: &str, path: Option<&str>, content: &str) -> ViewData {
+        open_view(
+            window.to_string(),
+            path.map(str::to_string),
+            "md".to_string(),
+            content.to_string(),
+        )
+        .unwrap()
+    }
+
+    fn views_in(window: &str) -> Vec<String> {
+        window_views(window.to_string())
+            .unwrap()
+            .into_iter()
+            .map(|v| v.view)
+            .collect()
+    }
+
+    #[test]
+    fn test_views_share_open_documents() {
+        let first = open("share", Some("/share/a.md"), "# A");
+        let second = open("share-other", Some("/share/a.md"), "ignored");
+        assert_eq!(second.document, first.document);
+        assert_eq!(second.content, "# A");
+
+        // Unsaved buffers never share
+        let new = open("share", None, "");
+        let other = open("share", None, "");
+        assert_ne!(new.document, other.document);
+
+        assert!(open_view("share".into(), None, "docx".into(), String::new()).is_err());
+    }
+
+    #[test]
+    fn test_window_views_in_open_order() {
+        let views: Vec<_> = (0..3)
+            .map(|i| open("order", Some(&format!("/order/{}.md", i)), "").view)
+            .collect();
+        let split = split_view(views[0].clone()).unwrap();
+        assert_eq!(split.window, "order");
+        assert_eq!(split.document, get_view(views[0].clone()).unwrap().document);
+
+        let mut expected = views.clone();
+        expected.push(split.view);
+        assert_eq!(views_in("order"), expected);
+        assert!(views_in("order-empty").is_empty());
+    }
+
+    #[test]
+    fn test_close_view_closes_document_with_last_view() {
+        let first = open("close", Some("/close/a.md"), "old");
+        let split = split_view(first.view.clone()).unwrap();
+
+        close_view(first.view.clone()).unwrap();
+        assert!(get_view(first.view.clone()).is_err());
+        assert_eq!(get_view(split.view.clone()).unwrap().content, "old");
+
+        // Closing the last view closes the document, so the next open
+        // reads its content afresh
+        close_view(split.view).unwrap();
+        let reopened = open("close", Some("/close/a.md"), "new");
+        assert_ne!(reopened.document, first.document);
+        assert_eq!(reopened.content, "new");
+
+        // Closing twice is harmless
+        close_view(first.view).unwrap();
+    }
+
+    #[test]
+    fn test_close_window() {
+        let kept = open("close-kept", Some("/close-window/a.md"), "text");
+        open("close-window", Some("/close-window/a.md"), "");
+        open("close-window", None, "");
+
+        close_window("close-window".to_string()).unwrap();
+        assert!(views_in("close-window").is_empty());
+        assert_eq!(views_in("close-kept"), [kept.view.clone()]);
+        assert_eq!(get_view(kept.view).unwrap().content, "text");
+    }
+
+    #[test]
+    fn test_update_view_tells_other_views() {
+        let edited = open("update", Some("/update/a.md"), "before");
+        let other = open("update-other", Some("/update/a.md"), "");
+
+        update_view(edited.view.clone(), "after".to_string()).unwrap();
+        let seen = get_view(other.view.clone()).unwrap();
+        assert_eq!(seen.content, "after");
+        assert_eq!(seen.window, "update-other");
+
+        let from_edit = |event: &DocumentEvent| match event {
+            DocumentEvent::Modified {
+                origin: Some(origin),
+                ..
+            } => origin.view == edited.view,
+            _ => false,
+        };
+        let events = get_document_events(usize::MAX);
+        let origins: Vec<_> = events.iter().filter(|e| from_edit(e)).collect();
+        assert_eq!(origins.len(), 1);
+        let DocumentEvent::Modified {
+            origin: Some(origin),
+            ..
+        } = origins[0]
+        else {
+            unreachable!();
+        };
+        assert_eq!(origin.window, "update");
+        assert_eq!(origin.document, edited.document);
+
+        // An edit that changes nothing is not announced
+        update_view(edited.view.clone(), "after".to_string()).unwrap();
+        let events = get_document_events(usize::MAX);
+        assert_eq!(events.iter().filter(|e| from_edit(e)).count(), 1);
+
+        assert!(update_view("v0".to_string(), String::new()).is_err());
+    }
+}
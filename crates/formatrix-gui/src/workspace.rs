@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (c) Jonathan D.A. Jewell <j.d.a.jewell@open.ac.uk>
+//! Workspace folder for the project explorer
+//!
+//! [`open_workspace`] makes a directory the workspace and returns its file
+//! tree: subdirectories and the documents in supported formats, with hidden
+//! entries left out. [`watch_workspace`] reports changes under it as
+//! `WorkspaceChanged` document events, on which the sidebar asks for the
+//! tree again.
+//!
+//! File operations take paths inside the workspace, absolute or relative to
+//! its root, and refuse anything outside it.
+
+use crate::commands::{emit_event, format_id, DocumentEvent};
+use formatrix_core::file_ops::{watch_paths, FileWatcher};
+use formatrix_core::format_from_extension;
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// A directory or document in the workspace tree
+#[derive(Debug, Clone, Serialize)]
+pub struct FileNode {
+    pub name: String,
+    /// Absolute path, for opening the document
+    pub path: String,
+    pub is_dir: bool,
+    /// Format id of a document; `None` for directories and other files
+    pub format: Option<String>,
+    /// Entries of a directory: directories first, then by name
+    pub children: Vec<FileNode>,
+}
+
+struct Workspace {
+    root: PathBuf,
+    watcher: Option<FileWatcher>,
+}
+
+/// The open workspace, if any
+static WORKSPACE: LazyLock<Mutex<Option<Workspace>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Make the directory at `path` the workspace and list it
+///
+/// A watch on the previous workspace stops.
+pub fn open_workspace(path: String) -> Result<FileNode, String> {
+    let root = fs::canonicalize(&path).map_err(|e| format!("Failed to open workspace: {}", e))?;
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", path));
+    }
+    let tree = dir_node(&root).map_err(|e| format!("Failed to read workspace: {}", e))?;
+    *lock_workspace()? = Some(Workspace {
+        root,
+        watcher: None,
+    });
+    Ok(tree)
+}
+
+/// Close the workspace and stop watching it
+pub fn close_workspace() -> Result<(), String> {
+    *lock_workspace()? = None;
+    Ok(())
+}
+
+/// The workspace tree as it is now
+pub fn workspace_tree() -> Result<FileNode, String> {
+    let root = workspace_root()?;
+    dir_node(&root).map_err(|e| format!("Failed to read workspace: {}", e))
+}
+
+/// Emit a `WorkspaceChanged` event whenever directories or documents in the
+/// workspace are created, changed, renamed or removed
+pub fn watch_workspace() -> Result<(), String> {
+    let mut workspace = lock_workspace()?;
+    let workspace = workspace
+        .as_mut()
+        .ok_or_else(|| "No workspace open".to_string())?;
+    if workspace.watcher.is_some() {
+        return Ok(());
+    }
+
+    let root = workspace.root.clone();
+    let watcher = watch_paths([&workspace.root], move |changed| {
+        let paths: Vec<String> = changed
+            .into_iter()
+            .filter(|path| shown_change(&root, path))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        if !paths.is_empty() {
+            emit_event(DocumentEvent::workspace_changed(paths));
+        }
+    })
+    .map_err(|e| format!("Failed to watch workspace: {}", e))?;
+    workspace.watcher = Some(watcher);
+    Ok(())
+}
+
+/// Create an empty document at `path`, and any directories above it
+pub fn create_file(path: String) -> Result<FileNode, String> {
+    let target = resolve(&path)?;
+    if format_from_extension(&target).is_none() {
+        return Err(format!("Not a supported document type: {}", path));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create file: {}", e))?;
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+    Ok(file_node(&target))
+}
+
+/// Create a directory at `path`, and any directories above it
+pub fn create_directory(path: String) -> Result<FileNode, String> {
+    let target = resolve(&path)?;
+    if target.exists() {
+        return Err(format!("Already exists: {}", path));
+    }
+    fs::create_dir_all(&target).map_err(|e| format!("Failed to create directory: {}", e))?;
+    dir_node(&target).map_err(|e| e.to_string())
+}
+
+/// Rename or move the file or directory at `from` to `to`
+pub fn rename_file(from: String, to: String) -> Result<FileNode, String> {
+    let (source, target) = (resolve(&from)?, resolve(&to)?);
+    if target.exists() {
+        return Err(format!("Already exists: {}", to));
+    }
+    fs::rename(&source, &target).map_err(|e| format!("Failed to rename: {}", e))?;
+    if target.is_dir() {
+        dir_node(&target).map_err(|e| e.to_string())
+    } else {
+        Ok(file_node(&target))
+    }
+}
+
+/// Delete the file or empty directory at `path`
+pub fn delete_file(path: String) -> Result<(), String> {
+    let target = resolve(&path)?;
+    let result = if target.is_dir() {
+        fs::remove_dir(&target)
+    } else {
+        fs::remove_file(&target)
+    };
+    result.map_err(|e| format!("Failed to delete {}: {}", path, e))
+}
+
+pub(crate) fn workspace_root() -> Result<PathBuf, String> {
+    lock_workspace()?
+        .as_ref()
+        .map(|w| w.root.clone())
+        .ok_or_else(|| "No workspace open".to_string())
+}
+
+/// `path` as an absolute path inside the workspace, other than its root
+fn resolve(path: &str) -> Result<PathBuf, String> {
+    resolve_in(&workspace_root()?, path)
+}
+
+fn resolve_in(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let target = root.join(path);
+    let escapes = Path::new(path)
+        .components()
+        .any(|c| matches!(c, Component::ParentDir));
+    if escapes || target == root || !target.starts_with(root) {
+        return Err(format!("Outside the workspace: {}", path));
+    }
+    Ok(target)
+}
+
+/// Whether a change to `path` can alter the tree: hidden entries and other
+/// files are left out, but a removed path may have been a directory
+fn shown_change(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    let hidden = relative
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+    !hidden
+        && (path.is_dir()
+            || format_from_extension(path).is_some()
+            || (!path.exists() && path.extension().is_none()))
+}
+
+fn dir_node(dir: &Path) -> std::io::Result<FileNode> {
+    let mut children = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            match dir_node(&path) {
+                Ok(node) => children.push(node),
+                Err(e) => tracing::warn!("Skipping {}: {}", path.display(), e),
+            }
+        } else if format_from_extension(&path).is_some() {
+            children.push(file_node(&path));
+        }
+    }
+    children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(FileNode {
+        name: file_name(dir),
+        path: dir.to_string_lossy().into_owned(),
+        is_dir: true,
+        format: None,
+        children,
+    })
+}
+
+fn file_node(path: &Path) -> FileNode {
+    FileNode {
+        name: file_name(path),
+        path: path.to_string_lossy().into_owned(),
+        is_dir: false,
+        format: format_from_extension(path).map(|f| format_id(f).to_string()),
+        children: Vec::new(),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned())
+}
+
+fn lock_workspace() -> Result<MutexGuard<'static, Option<Workspace>>, String> {
+    WORKSPACE
+        .lock()
+        .map_err(|_| "workspace unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(node: &FileNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tree_lists_documents_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("guide/empty")).unwrap();
+        fs::create_dir(root.join(".git")).unwrap();
+        for file in [
+            "b.md",
+            "a.adoc",
+            "notes.org",
+            "image.png",
+            ".hidden.md",
+            "guide/intro.rst",
+            "guide/data.json",
+            ".git/HEAD.md",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let tree = dir_node(root).unwrap();
+        assert!(tree.is_dir && tree.format.is_none());
+        // Directories first, then by name; hidden entries and files in
+        // other formats left out
+        assert_eq!(names(&tree), ["guide", "a.adoc", "b.md", "notes.org"]);
+        assert_eq!(tree.children[2].format.as_deref(), Some("md"));
+        assert_eq!(tree.children[2].path, root.join("b.md").to_string_lossy());
+
+        let guide = &tree.children[0];
+        assert!(guide.is_dir);
+        assert_eq!(names(guide), ["empty", "intro.rst"]);
+        assert!(guide.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_shown_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("doc.md"), "").unwrap();
+        fs::write(root.join("image.png"), "").unwrap();
+
+        assert!(shown_change(root, &root.join("doc.md")));
+        assert!(shown_change(root, &root.join("sub")));
+        assert!(!shown_change(root, &root.join("image.png")));
+        assert!(!shown_change(root, &root.join(".git/index")));
+        assert!(!shown_change(root, &root.join(".draft.md")));
+        // Gone, and may have been a directory
+        assert!(shown_change(root, &root.join("removed")));
+        assert!(!shown_change(root, &root.join("removed.png")));
+        assert!(!shown_change(root, Path::new("/elsewhere/doc.md")));
+    }
+
+    #[test]
+    fn test_resolve_stays_inside() {
+        let root = Path::new("/work");
+        assert_eq!(resolve_in(root, "a/b.md").unwrap(), root.join("a/b.md"));
+        assert_eq!(resolve_in(root, "/work/b.md").unwrap(), root.join("b.md"));
+        assert!(resolve_in(root, "../b.md").is_err());
+        assert!(resolve_in(root, "a/../../b.md").is_err());
+        assert!(resolve_in(root, "/elsewhere/b.md").is_err());
+        assert!(resolve_in(root, "").is_err());
+    }
+
+    #[test]
+    fn test_file_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = open_workspace(dir.path().to_string_lossy().into_owned()).unwrap();
+        assert!(tree.children.is_empty());
+
+        let created = create_file("notes/todo.md".to_string()).unwrap();
+        assert_eq!(created.format.as_deref(), Some("md"));
+        assert!(create_file("notes/todo.md".to_string()).is_err());
+        assert!(create_file("notes/todo.png".to_string()).is_err());
+
+        create_directory("drafts".to_string()).unwrap();
+        assert!(create_directory("drafts".to_string()).is_err());
+        let moved = rename_file("notes/todo.md".to_string(), "drafts/todo.md".to_string()).unwrap();
+        assert_eq!(moved.name, "todo.md");
+
+        let tree = workspace_tree().unwrap();
+        assert_eq!(names(&tree), ["drafts", "notes"]);
+        assert_eq!(names(&tree.children[0]), ["todo.md"]);
+
+        assert!(delete_file("drafts".to_string()).is_err());
+        delete_file("drafts/todo.md".to_string()).unwrap();
+        delete_file("drafts".to_string()).unwrap();
+        assert!(delete_file("../outside".to_string()).is_err());
+
+        close_workspace().unwrap();
+        assert!(workspace_tree().is_err());
+    }
+}
@@ -8,6 +8,7 @@
 //! - Output: Target format and filename pattern
 
 #![forbid(unsafe_code)]
+use formatrix_core::{ConversionInteractor, NonInteractive};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -75,15 +76,28 @@ pub struct PipelineOutput {
 /// Pipeline executor
 pub struct PipelineExecutor {
     pipelines: std::collections::HashMap<String, Pipeline>,
+    interactor: Box<dyn ConversionInteractor>,
 }
 
 impl PipelineExecutor {
     pub fn new() -> Self {
         Self {
             pipelines: std::collections::HashMap::new(),
+            interactor: Box::new(NonInteractive),
         }
     }
 
+    /// Resolve ambiguous decisions in file steps with `interactor` instead of
+    /// the non-interactive defaults
+    pub fn set_interactor(&mut self, interactor: Box<dyn ConversionInteractor>) {
+        self.interactor = interactor;
+    }
+
+    /// The interactor file steps should pass to `formatrix_core::file_ops`
+    pub fn interactor(&mut self) -> &mut dyn ConversionInteractor {
+        self.interactor.as_mut()
+    }
+
     /// Load a pipeline from a Nickel file
     pub fn load_pipeline(&mut self, _path: &std::path::Path) -> Result<()> {
         // TODO: Parse Nickel file and register pipeline
@@ -53,6 +53,8 @@ pub const Result = enum(c_int) {
     unsupported_format = 4,
     null_pointer = 5,
     utf8_error = 6,
+    internal_error = 7,
+    cancelled = 8,
 
     pub fn isSuccess(self: Result) bool {
         return self == .success;
@@ -67,6 +69,8 @@ pub const Result = enum(c_int) {
             .unsupported_format => Error.UnsupportedFormat,
             .null_pointer => Error.NullPointer,
             .utf8_error => Error.Utf8Error,
+            .internal_error => Error.InternalError,
+            .cancelled => Error.Cancelled,
         };
     }
 };
@@ -79,6 +83,100 @@ pub const Error = error{
     UnsupportedFormat,
     NullPointer,
     Utf8Error,
+    InternalError,
+    Cancelled,
+};
+
+/// Newline sequence used in rendered output
+pub const LineEnding = enum(c_int) {
+    lf = 0,
+    crlf = 1,
+    cr = 2,
+};
+
+/// Trailing newline policy for rendered output
+pub const FinalNewline = enum(c_int) {
+    /// Leave the renderer's output untouched
+    keep = 0,
+    /// Remove all trailing newlines
+    strip = 1,
+    /// End with exactly one newline
+    single = 2,
+};
+
+/// Dialect of reStructuredText
+pub const RstFlavor = enum(c_int) {
+    docutils = 0,
+    sphinx = 1,
+};
+
+/// House style, which among other things picks the Markdown flavor written
+pub const StyleProfile = enum(c_int) {
+    /// Each renderer's own style
+    none = 0,
+    github = 1,
+    pandoc = 2,
+    sphinx = 3,
+    kramdown = 4,
+};
+
+/// Markdown heading syntax
+pub const HeadingStyle = enum(c_int) {
+    /// The style profile's
+    profile = 0,
+    atx = 1,
+    setext = 2,
+};
+
+/// Fidelity mode: what renderers do with raw content from another format
+pub const RawPolicy = enum(c_int) {
+    keep = 0,
+    drop = 1,
+    comment = 2,
+    convert = 3,
+};
+
+/// Parse options; start from `ParseOptions.default()`
+pub const ParseOptions = extern struct {
+    /// Size of this struct, so the library reads only the fields it has
+    size: usize,
+    preserve_spans: bool,
+    preserve_raw_source: bool,
+    strict: bool,
+    tab_width: usize,
+    rst_flavor: RstFlavor,
+    /// Limits for untrusted input; 0 means no limit
+    max_input_bytes: usize,
+    max_depth: usize,
+    max_blocks: usize,
+    /// Only the Djot reader can stop at it; other formats refuse to parse
+    /// when it is set
+    timeout_ms: u64,
+
+    pub fn default() ParseOptions {
+        var options: ParseOptions = undefined;
+        _ = formatrix_parse_options_init(&options, @sizeOf(ParseOptions));
+        return options;
+    }
+};
+
+/// Render options; start from `RenderOptions.default()`
+pub const RenderOptions = extern struct {
+    /// Size of this struct, so the library reads only the fields it has
+    size: usize,
+    line_width: usize,
+    hard_breaks: bool,
+    line_ending: LineEnding,
+    final_newline: FinalNewline,
+    profile: StyleProfile,
+    heading_style: HeadingStyle,
+    raw_policy: RawPolicy,
+
+    pub fn default() RenderOptions {
+        var options: RenderOptions = undefined;
+        _ = formatrix_render_options_init(&options, @sizeOf(RenderOptions));
+        return options;
+    }
 };
 
 /// Opaque document handle
@@ -91,10 +189,37 @@ extern "c" fn formatrix_parse(
     out_handle: *?*DocumentHandle,
 ) Result;
 
+extern "c" fn formatrix_parse_options_init(options: *ParseOptions, size: usize) Result;
+
+extern "c" fn formatrix_parse_with_options(
+    content: [*:0]const u8,
+    format: Format,
+    options: *const ParseOptions,
+    out_handle: *?*DocumentHandle,
+) Result;
+
 extern "c" fn formatrix_render(
     handle: *const DocumentHandle,
     format: Format,
-    out_content: *?[*:0]u8,
+    out_content: *?[*]u8,
+    out_length: *usize,
+) Result;
+
+extern "c" fn formatrix_render_options_init(options: *RenderOptions, size: usize) Result;
+
+extern "c" fn formatrix_render_with_options(
+    handle: *const DocumentHandle,
+    format: Format,
+    options: *const RenderOptions,
+    out_content: *?[*]u8,
+    out_length: *usize,
+) Result;
+
+extern "c" fn formatrix_render_ansi(
+    handle: *const DocumentHandle,
+    width: usize,
+    color: bool,
+    out_content: *?[*]u8,
     out_length: *usize,
 ) Result;
 
@@ -117,7 +242,7 @@ extern "c" fn formatrix_save_file_as(
 
 extern "c" fn formatrix_get_title(
     handle: *const DocumentHandle,
-    out_title: *?[*:0]u8,
+    out_title: *?[*]u8,
     out_length: *usize,
 ) Result;
 
@@ -133,16 +258,24 @@ extern "c" fn formatrix_convert(
     content: [*:0]const u8,
     from_format: Format,
     to_format: Format,
-    out_content: *?[*:0]u8,
+    out_content: *?[*]u8,
     out_length: *usize,
 ) Result;
 
 extern "c" fn formatrix_free_document(handle: ?*DocumentHandle) void;
 
-extern "c" fn formatrix_free_string(s: ?[*:0]u8) void;
+extern "c" fn formatrix_free_buffer(data: ?[*]u8, length: usize) void;
 
 extern "c" fn formatrix_version() [*:0]const u8;
 
+extern "c" fn formatrix_abi_version() u32;
+
+extern "c" fn formatrix_has_feature(name: [*:0]const u8) bool;
+
+extern "c" fn formatrix_last_error_message() ?[*:0]const u8;
+
+extern "c" fn formatrix_last_error_span(out_line: *u32, out_column: *u32) bool;
+
 /// A parsed document with automatic resource management
 pub const Document = struct {
     handle: *DocumentHandle,
@@ -161,6 +294,18 @@ pub const Document = struct {
         return Self{ .handle = handle.? };
     }
 
+    /// Parse content in the specified format, with `options`
+    pub fn parseWithOptions(content: [:0]const u8, format: Format, options: ParseOptions) Error!Self {
+        var handle: ?*DocumentHandle = null;
+        const result = formatrix_parse_with_options(content.ptr, format, &options, &handle);
+
+        if (result.toError()) |err| {
+            return err;
+        }
+
+        return Self{ .handle = handle.? };
+    }
+
     /// Open a file and parse it
     pub fn openFile(path: [:0]const u8) Error!struct { doc: Self, format: Format } {
         var handle: ?*DocumentHandle = null;
@@ -185,7 +330,7 @@ pub const Document = struct {
 
     /// Render the document to the specified format
     pub fn render(self: Self, format: Format, allocator: std.mem.Allocator) Error![]u8 {
-        var content: ?[*:0]u8 = null;
+        var content: ?[*]u8 = null;
         var length: usize = 0;
 
         const result = formatrix_render(self.handle, format, &content, &length);
@@ -194,7 +339,7 @@ pub const Document = struct {
             return err;
         }
 
-        defer formatrix_free_string(content);
+        defer formatrix_free_buffer(content, length);
 
         // Copy to Zig-managed memory
         const owned = try allocator.alloc(u8, length);
@@ -202,6 +347,43 @@ pub const Document = struct {
         return owned;
     }
 
+    /// Render the document to the specified format, with `options`
+    pub fn renderWithOptions(self: Self, format: Format, options: RenderOptions, allocator: std.mem.Allocator) Error![]u8 {
+        var content: ?[*]u8 = null;
+        var length: usize = 0;
+
+        const result = formatrix_render_with_options(self.handle, format, &options, &content, &length);
+
+        if (result.toError()) |err| {
+            return err;
+        }
+
+        defer formatrix_free_buffer(content, length);
+
+        const owned = try allocator.alloc(u8, length);
+        @memcpy(owned, content.?[0..length]);
+        return owned;
+    }
+
+    /// Render the document as styled terminal text, wrapped to `width`
+    /// columns (0 for no wrapping)
+    pub fn renderAnsi(self: Self, width: usize, color: bool, allocator: std.mem.Allocator) Error![]u8 {
+        var content: ?[*]u8 = null;
+        var length: usize = 0;
+
+        const result = formatrix_render_ansi(self.handle, width, color, &content, &length);
+
+        if (result.toError()) |err| {
+            return err;
+        }
+
+        defer formatrix_free_buffer(content, length);
+
+        const owned = try allocator.alloc(u8, length);
+        @memcpy(owned, content.?[0..length]);
+        return owned;
+    }
+
     /// Save the document to a file (format detected from extension)
     pub fn saveFile(self: Self, path: [:0]const u8) Error!void {
         const result = formatrix_save_file(self.handle, path.ptr);
@@ -220,7 +402,7 @@ pub const Document = struct {
 
     /// Get the document title (if any)
     pub fn getTitle(self: Self, allocator: std.mem.Allocator) Error!?[]u8 {
-        var title: ?[*:0]u8 = null;
+        var title: ?[*]u8 = null;
         var length: usize = 0;
 
         const result = formatrix_get_title(self.handle, &title, &length);
@@ -229,12 +411,12 @@ pub const Document = struct {
             return err;
         }
 
+        defer formatrix_free_buffer(title, length);
+
         if (length == 0) {
             return null;
         }
 
-        defer formatrix_free_string(title);
-
         const owned = try allocator.alloc(u8, length);
         @memcpy(owned, title.?[0..length]);
         return owned;
@@ -258,7 +440,7 @@ pub fn convert(
     to_format: Format,
     allocator: std.mem.Allocator,
 ) Error![]u8 {
-    var out_content: ?[*:0]u8 = null;
+    var out_content: ?[*]u8 = null;
     var out_length: usize = 0;
 
     const result = formatrix_convert(
@@ -273,7 +455,7 @@ pub fn convert(
         return err;
     }
 
-    defer formatrix_free_string(out_content);
+    defer formatrix_free_buffer(out_content, out_length);
 
     const owned = try allocator.alloc(u8, out_length);
     @memcpy(owned, out_content.?[0..out_length]);
@@ -295,6 +477,39 @@ pub fn version() [:0]const u8 {
     return std.mem.span(formatrix_version());
 }
 
+/// Version of the C API these bindings were written against
+pub const abi_version: u32 = 3;
+
+/// Version of the C API the loaded library provides
+///
+/// Anything but `abi_version` means the bindings and the library disagree.
+pub fn abiVersion() u32 {
+    return formatrix_abi_version();
+}
+
+/// Whether the library was built with the Cargo feature `name`
+pub fn hasFeature(name: [:0]const u8) bool {
+    return formatrix_has_feature(name.ptr);
+}
+
+/// Message of the last error on this thread, if any
+///
+/// Valid until the next failing call on this thread.
+pub fn lastErrorMessage() ?[:0]const u8 {
+    const message = formatrix_last_error_message() orelse return null;
+    return std.mem.span(message);
+}
+
+/// Line and column of the last error on this thread, if it has them
+pub fn lastErrorSpan() ?struct { line: u32, column: u32 } {
+    var line: u32 = 0;
+    var column: u32 = 0;
+    if (!formatrix_last_error_span(&line, &column)) {
+        return null;
+    }
+    return .{ .line = line, .column = column };
+}
+
 // Tests
 test "format extension" {
     try std.testing.expectEqualStrings("md", Format.markdown.extension());